@@ -0,0 +1,146 @@
+use tower_lsp::lsp_types::Range;
+use tree_sitter::{Node, Tree};
+
+use crate::analysis::text;
+use crate::atomics::is_write;
+use crate::callgraph::collect_kind;
+use crate::types::VariableInfo;
+use crate::util::node_to_range;
+
+/// A read or write of a variable through a pointer taken with `&`: `*p` or `p.field`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AliasAccess {
+    /// The pointer variable or parameter.
+    pub pointer: String,
+    /// The `*p` or `p.field` expression.
+    pub range: Range,
+    pub write: bool,
+}
+
+/// Accesses of `var_info` through pointers to it. Pointers are followed within one function:
+/// `p := &x`, `var p = &x` and `p = &x` alias `x` for the rest of the function that declares
+/// them, and `func(p *T) { ... }(&x)` aliases it inside the literal. Pointers copied to other
+/// pointers or stored in structs are not followed.
+pub fn alias_accesses(tree: &Tree, code: &str, var_info: &VariableInfo) -> Vec<AliasAccess> {
+    let mut addresses = Vec::new();
+    collect_kind(tree.root_node(), "unary_expression", &mut addresses);
+    let mut accesses = Vec::new();
+    for address in addresses {
+        let (Some(operator), Some(operand)) = (
+            address.child_by_field_name("operator"),
+            address.child_by_field_name("operand"),
+        ) else {
+            continue;
+        };
+        if operator.kind() != "&"
+            || text(code, operand) != var_info.name
+            || !var_info.uses.contains(&node_to_range(operand))
+        {
+            continue;
+        }
+        let Some((pointer, scope, from)) = pointer_bound_to(address, code) else {
+            continue;
+        };
+        collect_dereferences(scope, code, pointer, from, &mut accesses);
+    }
+    accesses.sort_by_key(|access| (access.range.start.line, access.range.start.character));
+    accesses.dedup();
+    accesses
+}
+
+/// The pointer `address` is stored in, the node its uses are searched in, and the byte offset
+/// they start at.
+fn pointer_bound_to<'a>(address: Node<'a>, code: &'a str) -> Option<(&'a str, Node<'a>, usize)> {
+    let list = address.parent()?;
+    if list.kind() == "argument_list" {
+        // `func(p *T) { ... }(&x)`
+        let call = list.parent()?;
+        let literal = call.child_by_field_name("function")?;
+        if literal.kind() != "func_literal" {
+            return None;
+        }
+        let mut cursor = list.walk();
+        let index = list
+            .named_children(&mut cursor)
+            .position(|argument| argument == address)?;
+        let parameters = literal.child_by_field_name("parameters")?;
+        let mut names = Vec::new();
+        let mut cursor = parameters.walk();
+        for parameter in parameters.named_children(&mut cursor) {
+            let mut name_cursor = parameter.walk();
+            names.extend(parameter.children_by_field_name("name", &mut name_cursor));
+        }
+        let name = names.get(index)?;
+        let body = literal.child_by_field_name("body")?;
+        return Some((text(code, *name), body, body.start_byte()));
+    }
+    if list.kind() != "expression_list" {
+        return None;
+    }
+    let statement = list.parent()?;
+    let mut cursor = list.walk();
+    let index = list
+        .named_children(&mut cursor)
+        .position(|value| value == address)?;
+    let mut names = Vec::new();
+    match statement.kind() {
+        "var_spec" => {
+            let mut cursor = statement.walk();
+            names.extend(statement.children_by_field_name("name", &mut cursor));
+        }
+        "short_var_declaration" | "assignment_statement" => {
+            let targets = statement.child_by_field_name("left")?;
+            if targets == list {
+                return None;
+            }
+            let mut cursor = targets.walk();
+            names.extend(targets.named_children(&mut cursor));
+        }
+        _ => return None,
+    }
+    let name = names
+        .get(index)
+        .filter(|name| name.kind() == "identifier")?;
+    let mut scope = statement;
+    while !matches!(
+        scope.kind(),
+        "function_declaration" | "method_declaration" | "func_literal"
+    ) {
+        scope = scope.parent()?;
+    }
+    Some((text(code, *name), scope, statement.end_byte()))
+}
+
+fn collect_dereferences(
+    scope: Node,
+    code: &str,
+    pointer: &str,
+    from: usize,
+    out: &mut Vec<AliasAccess>,
+) {
+    let mut candidates = Vec::new();
+    collect_kind(scope, "unary_expression", &mut candidates);
+    collect_kind(scope, "selector_expression", &mut candidates);
+    for candidate in candidates {
+        if candidate.start_byte() < from {
+            continue;
+        }
+        let target = match candidate.kind() {
+            "unary_expression" => candidate
+                .child_by_field_name("operator")
+                .filter(|operator| operator.kind() == "*")
+                .and_then(|_| candidate.child_by_field_name("operand")),
+            _ => candidate.child_by_field_name("operand"),
+        };
+        if !target
+            .is_some_and(|target| target.kind() == "identifier" && text(code, target) == pointer)
+        {
+            continue;
+        }
+        out.push(AliasAccess {
+            pointer: pointer.to_string(),
+            range: node_to_range(candidate),
+            write: is_write(candidate),
+        });
+    }
+}
@@ -2,10 +2,18 @@
 #![allow(clippy::clone_on_copy)]
 #![allow(clippy::only_used_in_recursion)]
 
+use crate::budget::Deadline;
+use crate::callgraph::{collect_kind, local_names};
+use crate::detect::{ENTITIES, SYNC_CALLS};
+use crate::error::{AnalysisError, AnalysisResult};
+use crate::sync_config::sync_config;
 use crate::types::{GraphData, GraphEdge, GraphEdgeType, GraphEntityType, GraphNode};
-use crate::{types::*, util::node_to_range};
+use crate::{
+    types::*,
+    util::{find_node, node_to_range, walk_tree, LineIndex, Visit, MAX_TRAVERSAL_DEPTH},
+};
 use serde_json::json;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use tower_lsp::lsp_types::{Position, Range};
 use tree_sitter::{Node, Point, Tree};
 
@@ -47,7 +55,7 @@ pub fn has_synchronization_in_block(tree: &Tree, range: Range, code: &str) -> bo
         loop {
             let node = cursor.node();
             let kind = node.kind();
-            if kind != "{" && kind != "}" && find_sync_in_node(node, code) {
+            if kind != "{" && kind != "}" && find_sync_in_node(node, block, code) {
                 return true;
             }
             if !cursor.goto_next_sibling() {
@@ -58,41 +66,48 @@ pub fn has_synchronization_in_block(tree: &Tree, range: Range, code: &str) -> bo
     false
 }
 
-fn find_sync_in_node(node: Node, code: &str) -> bool {
-    if node.kind() == "call_expression" {
-        if is_mutex_call(node, code) || is_atomic_call(node, code) {
-            return true;
+/// An atomic call does not synchronize `block` when the block also writes its operand plainly.
+fn find_sync_in_node(node: Node, block: Node, code: &str) -> bool {
+    SYNC_CALLS.any_match(node, code, |captures| {
+        if let Some(select) = captures.get("select") {
+            return is_communicating_select(select);
         }
-    }
-    let mut cursor = node.walk();
-    if cursor.goto_first_child() {
-        loop {
-            if find_sync_in_node(cursor.node(), code) {
-                return true;
-            }
-            if !cursor.goto_next_sibling() {
-                break;
-            }
-        }
-    }
-    false
+        let Some(call) = captures.get("call") else {
+            return false;
+        };
+        is_mutex_call(call, code)
+            || is_sync_map_call(call, code)
+            || is_xsync_call(call, code)
+            || (is_atomic_call(call, code)
+                && !crate::atomics::atomic_operand(call).is_some_and(|operand| {
+                    crate::atomics::is_written_in(block, text(code, operand), code)
+                }))
+    })
 }
 
+/// A `sync` lock or wait call, or a call to one of the configured synchronization functions.
 #[inline]
 fn is_mutex_call(call: Node, code: &str) -> bool {
-    if let Some(sel) = call.child_by_field_name("function") {
-        if sel.kind() == "selector_expression" {
-            if let Some(field) = sel.child_by_field_name("field") {
-                let name = text(code, field);
-                return matches!(name, "Lock" | "Unlock" | "RLock" | "RUnlock" | "Wait");
-            }
-        }
-    }
-    false
+    let Some(function) = call.child_by_field_name("function") else {
+        return false;
+    };
+    let method = match function.kind() {
+        "selector_expression" => match function.child_by_field_name("field") {
+            Some(field) => text(code, field),
+            None => return false,
+        },
+        "identifier" => text(code, function),
+        _ => return false,
+    };
+    (function.kind() == "selector_expression"
+        && matches!(method, "Lock" | "Unlock" | "RLock" | "RUnlock" | "Wait"))
+        || sync_config().contains(text(code, function), method)
 }
 
+/// A `sync/atomic` function, or a method of a typed atomic (`atomic.Value`, `atomic.Int64`, the
+/// generic `atomic.Pointer[T]`, ...) called on a variable or field the file declares with it.
 #[inline]
-fn is_atomic_call(call: Node, code: &str) -> bool {
+pub(crate) fn is_atomic_call(call: Node, code: &str) -> bool {
     let func = match call.child_by_field_name("function") {
         Some(f) => f,
         None => return false,
@@ -105,10 +120,348 @@ fn is_atomic_call(call: Node, code: &str) -> bool {
                 return crate::types::ATOMIC_FUNCS.contains(&f);
             }
         }
+        if field.is_some_and(|f| ATOMIC_METHODS.contains(&f)) {
+            return typed_atomic_receiver(call, code).is_some();
+        }
     }
     false
 }
 
+/// The declared atomic type (`Value`, `Pointer`, ...) of the variable or field a method call is
+/// made on, such as `Value` for `cfg.Load()` after `var cfg atomic.Value`.
+pub(crate) fn typed_atomic_receiver<'a>(call: Node, code: &'a str) -> Option<&'a str> {
+    let func = call.child_by_field_name("function")?;
+    let operand = func.child_by_field_name("operand")?;
+    let name = match operand.kind() {
+        "identifier" => text(code, operand),
+        "selector_expression" => text(code, operand.child_by_field_name("field")?),
+        _ => return None,
+    };
+    let mut root = call;
+    while let Some(parent) = root.parent() {
+        root = parent;
+    }
+    atomic_declarations(root, code).get(name).copied()
+}
+
+/// Variables and fields declared with a typed atomic, by name: `var v atomic.Value`, a
+/// `p atomic.Pointer[T]` field, `v := new(atomic.Int64)` or `p := &atomic.Pointer[T]{}`.
+fn atomic_declarations<'a>(root: Node, code: &'a str) -> HashMap<&'a str, &'a str> {
+    let mut declarations = HashMap::new();
+    for kind in ["var_spec", "field_declaration", "short_var_declaration"] {
+        let mut found = Vec::new();
+        collect_kind(root, kind, &mut found);
+        for node in found {
+            let names: Vec<Node> = match node.child_by_field_name("left") {
+                Some(left) => {
+                    let mut cursor = left.walk();
+                    left.named_children(&mut cursor).collect()
+                }
+                None => {
+                    let mut cursor = node.walk();
+                    node.children_by_field_name("name", &mut cursor).collect()
+                }
+            };
+            if let Some(typ) = node
+                .child_by_field_name("type")
+                .and_then(|typ| atomic_type(text(code, typ)))
+            {
+                declarations.extend(names.iter().map(|name| (text(code, *name), typ)));
+                continue;
+            }
+            let Some(values) = node
+                .child_by_field_name("value")
+                .or_else(|| node.child_by_field_name("right"))
+            else {
+                continue;
+            };
+            for (i, name) in names.into_iter().enumerate() {
+                if let Some(typ) = values
+                    .named_child(i)
+                    .and_then(|value| atomic_type(text(code, value)))
+                {
+                    declarations.insert(text(code, name), typ);
+                }
+            }
+        }
+    }
+    declarations
+}
+
+/// `Value` for `atomic.Value`, `*atomic.Value`, `atomic.Value{}`, `&atomic.Value{}` or
+/// `new(atomic.Value)`; `Pointer` for `atomic.Pointer[T]` in the same forms.
+fn atomic_type(text: &str) -> Option<&str> {
+    let text = text.trim();
+    let text = text
+        .strip_prefix("new(")
+        .and_then(|inner| inner.strip_suffix(')'))
+        .unwrap_or(text);
+    let text = text.trim_start_matches(['*', '&']);
+    let text = text.strip_suffix("{}").unwrap_or(text);
+    let base = text.strip_prefix("atomic.")?.split('[').next()?.trim();
+    ATOMIC_TYPES.iter().copied().find(|typ| *typ == base)
+}
+
+/// `singleflight.Group` methods; calls sharing a key run the function once, and callers wait for
+/// it to finish.
+const SINGLEFLIGHT_METHODS: &[&str] = &["Do", "DoChan"];
+
+/// `golang.org/x/sync` calls that exclude other goroutines: `singleflight.Group` methods, and
+/// `Acquire`/`Release` on a `semaphore.Weighted` created with weight 1, which is a mutex. Larger
+/// semaphores only limit how many goroutines run at once and are not synchronization.
+fn is_xsync_call(call: Node, code: &str) -> bool {
+    let Some(func) = call.child_by_field_name("function") else {
+        return false;
+    };
+    if func.kind() != "selector_expression" {
+        return false;
+    }
+    let method = func.child_by_field_name("field").map(|n| text(code, n));
+    let name = match func.child_by_field_name("operand") {
+        Some(operand) if operand.kind() == "identifier" => text(code, operand),
+        Some(operand) if operand.kind() == "selector_expression" => {
+            match operand.child_by_field_name("field") {
+                Some(field) => text(code, field),
+                None => return false,
+            }
+        }
+        _ => return false,
+    };
+    let mut root = call;
+    while let Some(parent) = root.parent() {
+        root = parent;
+    }
+    match method {
+        Some(method) if SINGLEFLIGHT_METHODS.contains(&method) => {
+            declarations_of_type(root, code, "singleflight.Group", None)
+                .iter()
+                .any(|decl| text(code, *decl) == name)
+        }
+        Some("Acquire" | "Release") => binary_semaphore_names(root, code).contains(name),
+        _ => false,
+    }
+}
+
+/// Names of `semaphore.Weighted` values created with `semaphore.NewWeighted(1)`.
+fn binary_semaphore_names<'a>(root: Node, code: &'a str) -> HashSet<&'a str> {
+    let mut calls = Vec::new();
+    collect_kind(root, "call_expression", &mut calls);
+    let mut names = HashSet::new();
+    for call in calls {
+        let is_binary = call
+            .child_by_field_name("function")
+            .is_some_and(|f| text(code, f) == "semaphore.NewWeighted")
+            && call
+                .child_by_field_name("arguments")
+                .and_then(|arguments| arguments.named_child(0))
+                .is_some_and(|weight| text(code, weight) == "1");
+        if !is_binary {
+            continue;
+        }
+        // `sem := semaphore.NewWeighted(1)`, `var sem = ...` or a `sem: ...` field in a literal.
+        let Some(parent) = call.parent() else {
+            continue;
+        };
+        let target = match parent.kind() {
+            "expression_list" => parent
+                .parent()
+                .and_then(|statement| match statement.kind() {
+                    "var_spec" => statement.child_by_field_name("name"),
+                    _ => statement
+                        .child_by_field_name("left")
+                        .and_then(|left| left.named_child(0)),
+                }),
+            "literal_element" => parent
+                .parent()
+                .filter(|element| {
+                    element.kind() == "keyed_element" && element.named_child(1) == Some(parent)
+                })
+                .and_then(|element| element.named_child(0)),
+            _ => None,
+        };
+        if let Some(target) = target {
+            let name = text(code, target);
+            names.insert(name.rsplit('.').next().unwrap_or(name));
+        }
+    }
+    names
+}
+
+/// `sync.Map` methods; each one is safe for concurrent use.
+const SYNC_MAP_METHODS: &[&str] = &[
+    "Clear",
+    "CompareAndDelete",
+    "CompareAndSwap",
+    "Delete",
+    "Load",
+    "LoadAndDelete",
+    "LoadOrStore",
+    "Range",
+    "Store",
+    "Swap",
+];
+
+/// A `sync.Map` method called on a variable or field the file declares as a `sync.Map`.
+fn is_sync_map_call(call: Node, code: &str) -> bool {
+    let Some(func) = call.child_by_field_name("function") else {
+        return false;
+    };
+    if func.kind() != "selector_expression" {
+        return false;
+    }
+    let method = func.child_by_field_name("field").map(|n| text(code, n));
+    if !method.is_some_and(|m| SYNC_MAP_METHODS.contains(&m)) {
+        return false;
+    }
+    let name = match func.child_by_field_name("operand") {
+        Some(operand) if operand.kind() == "identifier" => text(code, operand),
+        Some(operand) if operand.kind() == "selector_expression" => {
+            match operand.child_by_field_name("field") {
+                Some(field) => text(code, field),
+                None => return false,
+            }
+        }
+        _ => return false,
+    };
+    let mut root = call;
+    while let Some(parent) = root.parent() {
+        root = parent;
+    }
+    sync_map_declarations(root, code)
+        .iter()
+        .any(|decl| text(code, *decl) == name)
+}
+
+/// Names of variables and struct fields declared as `sync.Map` (or a pointer to one), or
+/// initialized with `sync.Map{}`, `&sync.Map{}` or `new(sync.Map)`.
+pub fn sync_map_declarations<'a>(root: Node<'a>, code: &str) -> Vec<Node<'a>> {
+    declarations_of_type(root, code, "sync.Map", None)
+}
+
+/// Names of variables and struct fields declared as `errgroup.Group` (or a pointer to one),
+/// initialized like [`sync_map_declarations`], or the group returned by `errgroup.WithContext`.
+pub fn errgroup_declarations<'a>(root: Node<'a>, code: &str) -> Vec<Node<'a>> {
+    declarations_of_type(root, code, "errgroup.Group", Some("errgroup.WithContext"))
+}
+
+/// `g.Go(func() error { ... })` (or `g.TryGo`) on an `errgroup.Group` declared in the file. The
+/// function literal runs in a new goroutine, like the body of a `go` statement.
+pub fn is_errgroup_go(call: Node, code: &str) -> bool {
+    let Some(func) = call.child_by_field_name("function") else {
+        return false;
+    };
+    if func.kind() != "selector_expression"
+        || !func
+            .child_by_field_name("field")
+            .is_some_and(|field| matches!(text(code, field), "Go" | "TryGo"))
+    {
+        return false;
+    }
+    let name = match func.child_by_field_name("operand") {
+        Some(operand) if operand.kind() == "identifier" => text(code, operand),
+        Some(operand) if operand.kind() == "selector_expression" => {
+            match operand.child_by_field_name("field") {
+                Some(field) => text(code, field),
+                None => return false,
+            }
+        }
+        _ => return false,
+    };
+    let mut root = call;
+    while let Some(parent) = root.parent() {
+        root = parent;
+    }
+    errgroup_declarations(root, code)
+        .iter()
+        .any(|decl| text(code, *decl) == name)
+}
+
+/// The errgroup `Go` call `literal` is passed to.
+fn errgroup_launch<'a>(literal: Node<'a>, code: &str) -> Option<Node<'a>> {
+    if literal.kind() != "func_literal" {
+        return None;
+    }
+    let call = literal.parent()?.parent()?;
+    (call.kind() == "call_expression" && is_errgroup_go(call, code)).then_some(call)
+}
+
+/// Declarations of `type_name` values: by type, by a `T{}`, `&T{}` or `new(T)` initializer, or
+/// as the first result of `constructor`.
+fn declarations_of_type<'a>(
+    root: Node<'a>,
+    code: &str,
+    type_name: &str,
+    constructor: Option<&str>,
+) -> Vec<Node<'a>> {
+    let is_type = |node: Node| text(code, node).trim_start_matches('*') == type_name;
+    let literals = [
+        format!("{}{{}}", type_name),
+        format!("&{}{{}}", type_name),
+        format!("new({})", type_name),
+    ];
+    let mut decls = Vec::new();
+    for kind in ["var_spec", "field_declaration", "short_var_declaration"] {
+        let mut found = Vec::new();
+        collect_kind(root, kind, &mut found);
+        for node in found {
+            let names: Vec<Node> = match kind {
+                "short_var_declaration" => node
+                    .child_by_field_name("left")
+                    .map(|left| {
+                        let mut cursor = left.walk();
+                        left.named_children(&mut cursor).collect()
+                    })
+                    .unwrap_or_default(),
+                _ => {
+                    let mut cursor = node.walk();
+                    node.children_by_field_name("name", &mut cursor).collect()
+                }
+            };
+            if node.child_by_field_name("type").is_some_and(is_type) {
+                decls.extend(names);
+                continue;
+            }
+            let values = node
+                .child_by_field_name("value")
+                .or_else(|| node.child_by_field_name("right"));
+            let Some(values) = values else {
+                continue;
+            };
+            let constructed = values.named_child(0).is_some_and(|value| {
+                value.kind() == "call_expression"
+                    && value
+                        .child_by_field_name("function")
+                        .is_some_and(|f| Some(text(code, f)) == constructor)
+            });
+            if constructed {
+                decls.extend(names.into_iter().take(1));
+                continue;
+            }
+            for (i, name) in names.into_iter().enumerate() {
+                let value = values
+                    .named_child(i)
+                    .map(|v| text(code, v).replace(' ', ""));
+                if value.is_some_and(|value| literals.contains(&value)) {
+                    decls.push(name);
+                }
+            }
+        }
+    }
+    decls
+}
+
+/// A `select` with at least one send or receive case; `default`-only selects never block.
+fn is_communicating_select(node: Node) -> bool {
+    if node.kind() != "select_statement" {
+        return false;
+    }
+    let mut cursor = node.walk();
+    let communicates = node
+        .named_children(&mut cursor)
+        .any(|case| case.kind() == "communication_case");
+    communicates
+}
+
 pub fn determine_race_severity(
     tree: &Tree,
     range: Range,
@@ -116,38 +469,80 @@ pub fn determine_race_severity(
     is_write: bool,
     sync_funcs: &HashSet<String>,
 ) -> RaceSeverity {
-    if is_access_synchronized(tree, range, code, sync_funcs) {
-        RaceSeverity::Low
-    } else if is_in_goroutine(tree, range) || is_write {
-        RaceSeverity::High
-    } else {
-        RaceSeverity::Medium
+    match access_guard(tree, range, code, sync_funcs) {
+        AccessGuard::Exclusive => RaceSeverity::Low,
+        // Readers holding RLock run concurrently with each other but never with a writer
+        // holding Lock; a write under RLock races with those readers.
+        AccessGuard::Shared if !is_write => RaceSeverity::Low,
+        AccessGuard::Shared => RaceSeverity::High,
+        // Every conflicting access is ordered by a send/receive pair on some channel.
+        AccessGuard::None if crate::channels::is_ordered_by_channel(tree, range, code) => {
+            RaceSeverity::Low
+        }
+        // The goroutine gives the variable away over a channel and never touches it again.
+        AccessGuard::None if crate::channels::is_ownership_transferred(tree, range, code) => {
+            RaceSeverity::Low
+        }
+        AccessGuard::None if is_in_goroutine_or_group(tree, range, code) || is_write => {
+            RaceSeverity::High
+        }
+        AccessGuard::None => RaceSeverity::Medium,
     }
 }
 
+/// How the lock calls around an access protect it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessGuard {
+    None,
+    /// Only read locks (`RLock`) are held.
+    Shared,
+    /// A `Lock`, an atomic operation, a `sync.Map` method, a synchronizing call or a `select`
+    /// case covers the access.
+    Exclusive,
+}
+
 fn is_access_synchronized(
     tree: &Tree,
     range: Range,
     code: &str,
     sync_funcs: &HashSet<String>,
 ) -> bool {
+    access_guard(tree, range, code, sync_funcs) != AccessGuard::None
+}
+
+pub fn access_guard(
+    tree: &Tree,
+    range: Range,
+    code: &str,
+    sync_funcs: &HashSet<String>,
+) -> AccessGuard {
     let target_point = Point {
         row: range.start.line as usize,
         column: range.start.character as usize,
     };
     let target_node = match find_node_at_position(tree.root_node(), target_point) {
         Some(node) => node,
-        None => return has_synchronization_in_block(tree, range, code),
+        None if has_synchronization_in_block(tree, range, code) => return AccessGuard::Exclusive,
+        None => return AccessGuard::None,
     };
     let mut current = Some(target_node);
     while let Some(candidate) = current {
+        // The case body runs only after its send or receive completed, which orders it with
+        // the goroutine on the other end of the channel.
+        if candidate.kind() == "communication_case" {
+            return AccessGuard::Exclusive;
+        }
         if candidate.kind() == "call_expression" {
-            if is_mutex_call(candidate, code) || is_atomic_call(candidate, code) {
-                return true;
+            if is_mutex_call(candidate, code)
+                || is_atomic_call(candidate, code)
+                || is_sync_map_call(candidate, code)
+                || is_xsync_call(candidate, code)
+            {
+                return AccessGuard::Exclusive;
             }
             if let Some(name) = call_expression_name(candidate, code) {
                 if sync_funcs.contains(&name) {
-                    return true;
+                    return AccessGuard::Exclusive;
                 }
             }
         }
@@ -156,11 +551,18 @@ fn is_access_synchronized(
     current = Some(target_node);
     while let Some(candidate) = current {
         if candidate.kind() == "block" {
-            return has_active_lock_for_target(candidate, target_node, code);
+            let held = active_locks_for_target(candidate, target_node, code);
+            return if held.iter().any(|lock| lock.exclusive > 0) {
+                AccessGuard::Exclusive
+            } else if held.is_empty() {
+                AccessGuard::None
+            } else {
+                AccessGuard::Shared
+            };
         }
         current = candidate.parent();
     }
-    false
+    AccessGuard::None
 }
 
 pub fn is_access_synchronized_at(
@@ -172,19 +574,15 @@ pub fn is_access_synchronized_at(
     is_access_synchronized(tree, range, code, sync_funcs)
 }
 
-pub fn is_access_in_atomic_context(tree: &Tree, range: Range, code: &str) -> bool {
-    let target_point = Point {
-        row: range.start.line as usize,
-        column: range.start.character as usize,
-    };
-    let mut current = find_node_at_position(tree.root_node(), target_point);
+pub fn is_access_in_atomic_context(tree: &Tree, range: Range, code: &str) -> AnalysisResult<bool> {
+    let mut current = Some(node_at(tree, range.start)?);
     while let Some(node) = current {
         if node.kind() == "call_expression" && is_atomic_call(node, code) {
-            return true;
+            return Ok(true);
         }
         current = node.parent();
     }
-    false
+    Ok(false)
 }
 
 pub fn is_struct_field_declaration(tree: &Tree, range: Range) -> bool {
@@ -211,7 +609,11 @@ pub fn is_struct_field_declaration(tree: &Tree, range: Range) -> bool {
     false
 }
 
-pub fn is_heavy_work_in_call_context(tree: &Tree, range: Range, code: &str) -> bool {
+pub fn is_heavy_work_in_call_context(
+    tree: &Tree,
+    range: Range,
+    code: &str,
+) -> AnalysisResult<bool> {
     let heavy_full_names = [
         "fmt.Println",
         "fmt.Printf",
@@ -220,11 +622,7 @@ pub fn is_heavy_work_in_call_context(tree: &Tree, range: Range, code: &str) -> b
         "sort.SliceStable",
     ];
     let heavy_simple_names = ["append", "copy"];
-    let target_point = Point {
-        row: range.start.line as usize,
-        column: range.start.character as usize,
-    };
-    let mut current = find_node_at_position(tree.root_node(), target_point);
+    let mut current = Some(node_at(tree, range.start)?);
     while let Some(node) = current {
         if node.kind() == "call_expression" {
             if let Some(function) = node.child_by_field_name("function") {
@@ -232,13 +630,13 @@ pub fn is_heavy_work_in_call_context(tree: &Tree, range: Range, code: &str) -> b
                 if heavy_full_names.iter().any(|n| *n == name)
                     || heavy_simple_names.iter().any(|n| *n == name)
                 {
-                    return true;
+                    return Ok(true);
                 }
             }
         }
         current = node.parent();
     }
-    false
+    Ok(false)
 }
 
 pub fn field_type_kind_at_declaration(tree: &Tree, range: Range, code: &str) -> FieldTypeKind {
@@ -306,18 +704,10 @@ pub fn detect_retention_pattern(
     None
 }
 
-pub fn is_value_copy_context(tree: &Tree, range: Range, code: &str) -> bool {
-    let target_point = Point {
-        row: range.start.line as usize,
-        column: range.start.character as usize,
-    };
-    let target_node = match find_node_at_position(tree.root_node(), target_point) {
-        Some(node) => node,
-        None => return false,
-    };
-
+pub fn is_value_copy_context(tree: &Tree, range: Range, code: &str) -> AnalysisResult<bool> {
+    let target_node = node_at(tree, range.start)?;
     if is_under_address_of(target_node, code) {
-        return false;
+        return Ok(false);
     }
     let mut current = Some(target_node);
     while let Some(node) = current {
@@ -335,7 +725,7 @@ pub fn is_value_copy_context(tree: &Tree, range: Range, code: &str) -> bool {
                             | "short_var_declaration"
                             | "return_statement"
                     ) {
-                        return true;
+                        return Ok(true);
                     }
                 }
             }
@@ -343,7 +733,7 @@ pub fn is_value_copy_context(tree: &Tree, range: Range, code: &str) -> bool {
         }
         current = Some(parent);
     }
-    false
+    Ok(false)
 }
 
 pub fn access_context_key(tree: &Tree, range: Range) -> Option<(u32, u32, u32, u32)> {
@@ -355,7 +745,7 @@ pub fn access_context_key(tree: &Tree, range: Range) -> Option<(u32, u32, u32, u
     while let Some(node) = current {
         if matches!(
             node.kind(),
-            "function_declaration" | "method_declaration" | "function_literal"
+            "function_declaration" | "method_declaration" | "func_literal"
         ) {
             let r = node_to_range(node);
             return Some((r.start.line, r.start.character, r.end.line, r.end.character));
@@ -365,6 +755,103 @@ pub fn access_context_key(tree: &Tree, range: Range) -> Option<(u32, u32, u32, u
     None
 }
 
+pub fn infer_variable_type(tree: &Tree, code: &str, declaration: Range) -> Option<String> {
+    let target_point = Point {
+        row: declaration.start.line as usize,
+        column: declaration.start.character as usize,
+    };
+    let ident = find_node_at_position(tree.root_node(), target_point)?;
+    let mut current = ident.parent();
+    while let Some(node) = current {
+        match node.kind() {
+            "var_spec" | "const_spec" => {
+                if let Some(typ) = node.child_by_field_name("type") {
+                    return Some(text(code, typ).to_string());
+                }
+                let mut cursor = node.walk();
+                let index = node
+                    .children_by_field_name("name", &mut cursor)
+                    .filter(|n| n.kind() == "identifier")
+                    .position(|n| n.start_byte() == ident.start_byte())?;
+                let value = node.child_by_field_name("value")?;
+                return expression_type_hint(value.named_child(index)?, code);
+            }
+            "short_var_declaration" => {
+                let left = node.child_by_field_name("left")?;
+                let right = node.child_by_field_name("right")?;
+                if left.named_child_count() != right.named_child_count() {
+                    return None;
+                }
+                let index = (0..left.named_child_count())
+                    .filter_map(|i| left.named_child(i))
+                    .position(|n| n.start_byte() == ident.start_byte())?;
+                return expression_type_hint(right.named_child(index)?, code);
+            }
+            "parameter_declaration" | "variadic_parameter_declaration" | "field_declaration" => {
+                let typ = node.child_by_field_name("type")?;
+                let prefix = if node.kind() == "variadic_parameter_declaration" {
+                    "..."
+                } else {
+                    ""
+                };
+                return Some(format!("{}{}", prefix, text(code, typ)));
+            }
+            "block" | "function_declaration" | "method_declaration" | "source_file" => {
+                return None;
+            }
+            _ => {}
+        }
+        current = node.parent();
+    }
+    None
+}
+
+fn expression_type_hint(expr: Node, code: &str) -> Option<String> {
+    match expr.kind() {
+        "composite_literal" => expr
+            .child_by_field_name("type")
+            .map(|t| text(code, t).to_string()),
+        "unary_expression" => {
+            let operator = expr.child_by_field_name("operator")?;
+            let operand = expr.child_by_field_name("operand")?;
+            if text(code, operator) == "&" {
+                return expression_type_hint(operand, code).map(|t| format!("*{}", t));
+            }
+            None
+        }
+        "call_expression" => {
+            let function = expr.child_by_field_name("function")?;
+            let args = expr.child_by_field_name("arguments")?;
+            let first = args.named_child(0)?;
+            match text(code, function) {
+                "make" => Some(text(code, first).to_string()),
+                "new" => Some(format!("*{}", text(code, first))),
+                _ => None,
+            }
+        }
+        "type_conversion_expression" => expr
+            .child_by_field_name("type")
+            .map(|t| text(code, t).to_string()),
+        "func_literal" => {
+            let params = expr.child_by_field_name("parameters")?;
+            let mut signature = format!("func{}", text(code, params));
+            if let Some(result) = expr.child_by_field_name("result") {
+                signature.push(' ');
+                signature.push_str(text(code, result));
+            }
+            Some(signature)
+        }
+        "parenthesized_expression" => expression_type_hint(expr.named_child(0)?, code),
+        "int_literal" => Some("int".to_string()),
+        "float_literal" => Some("float64".to_string()),
+        "imaginary_literal" => Some("complex128".to_string()),
+        "rune_literal" => Some("rune".to_string()),
+        "interpreted_string_literal" | "raw_string_literal" => Some("string".to_string()),
+        "true" | "false" => Some("bool".to_string()),
+        _ => None,
+    }
+}
+
 fn assignment_rhs_for_target(target_node: Node) -> Option<Node> {
     let target_start = target_node.start_byte();
     let mut current = Some(target_node);
@@ -435,7 +922,7 @@ pub fn collect_sync_functions(tree: &Tree, code: &str) -> HashSet<String> {
         match node.kind() {
             "function_declaration" | "method_declaration" => {
                 if let Some(body) = node.child_by_field_name("body") {
-                    if find_sync_in_node(body, code) {
+                    if find_sync_in_node(body, body, code) {
                         if let Some(name_node) = node.child_by_field_name("name") {
                             let name = text(code, name_node).to_string();
                             if !name.is_empty() {
@@ -453,9 +940,199 @@ pub fn collect_sync_functions(tree: &Tree, code: &str) -> HashSet<String> {
             }
         }
     }
+    names.extend(collect_atomic_wrappers(tree, code));
     names
 }
 
+/// Methods of user types that only touch their data through an `atomic.Value` or typed atomic
+/// field (`Get`/`Set` style wrappers). The wrapper body is inlined one level: a method qualifies
+/// when it calls an atomic method on the receiver's atomic field and writes no receiver field
+/// directly. A name shared with any non-wrapper function or method is left out.
+pub fn collect_atomic_wrappers(tree: &Tree, code: &str) -> HashSet<String> {
+    let root = tree.root_node();
+    let mut atomic_fields: HashMap<String, Vec<String>> = HashMap::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        if node.kind() == "type_spec" {
+            if let (Some(name), Some(typ)) = (
+                node.child_by_field_name("name"),
+                node.child_by_field_name("type"),
+            ) {
+                if typ.kind() == "struct_type" {
+                    let fields = struct_atomic_fields(typ, code);
+                    if !fields.is_empty() {
+                        atomic_fields.insert(text(code, name).to_string(), fields);
+                    }
+                }
+            }
+        }
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            stack.push(child);
+        }
+    }
+    let mut wrappers = HashSet::new();
+    let mut others = HashSet::new();
+    let mut cursor = root.walk();
+    for decl in root.named_children(&mut cursor) {
+        if !matches!(decl.kind(), "function_declaration" | "method_declaration") {
+            continue;
+        }
+        let Some(name) = decl.child_by_field_name("name") else {
+            continue;
+        };
+        let name = text(code, name).to_string();
+        let fields = decl
+            .child_by_field_name("receiver")
+            .and_then(receiver_type_name)
+            .and_then(|typ| atomic_fields.get(text(code, typ)));
+        let receiver = decl
+            .child_by_field_name("receiver")
+            .and_then(|receiver| receiver.named_child(0))
+            .and_then(|param| param.child_by_field_name("name"))
+            .map(|n| text(code, n));
+        let is_wrapper = match (fields, receiver, decl.child_by_field_name("body")) {
+            (Some(fields), Some(receiver), Some(body)) => {
+                is_atomic_wrapper_body(body, code, receiver, fields)
+            }
+            _ => false,
+        };
+        if is_wrapper {
+            wrappers.insert(name);
+        } else {
+            others.insert(name);
+        }
+    }
+    wrappers.retain(|name| !others.contains(name));
+    wrappers
+}
+
+const ATOMIC_TYPES: &[&str] = &[
+    "Value", "Bool", "Int32", "Int64", "Uint32", "Uint64", "Uintptr", "Pointer",
+];
+
+const ATOMIC_METHODS: &[&str] = &[
+    "Load",
+    "Store",
+    "Swap",
+    "CompareAndSwap",
+    "Add",
+    "And",
+    "Or",
+];
+
+/// Field names holding `atomic.*` values; embedded fields are named after their type.
+fn struct_atomic_fields(struct_type: Node, code: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut stack = vec![struct_type];
+    while let Some(node) = stack.pop() {
+        if node.kind() == "field_declaration" {
+            let Some(mut typ) = node.child_by_field_name("type") else {
+                continue;
+            };
+            if typ.kind() == "generic_type" {
+                match typ.child_by_field_name("type") {
+                    Some(inner) => typ = inner,
+                    None => continue,
+                }
+            }
+            if typ.kind() != "qualified_type" {
+                continue;
+            }
+            let package = typ.child_by_field_name("package").map(|n| text(code, n));
+            let type_name = typ.child_by_field_name("name").map(|n| text(code, n));
+            let (Some("atomic"), Some(type_name)) = (package, type_name) else {
+                continue;
+            };
+            if !ATOMIC_TYPES.contains(&type_name) {
+                continue;
+            }
+            let mut cursor = node.walk();
+            let names: Vec<String> = node
+                .children_by_field_name("name", &mut cursor)
+                .map(|n| text(code, n).to_string())
+                .collect();
+            if names.is_empty() {
+                fields.push(type_name.to_string());
+            } else {
+                fields.extend(names);
+            }
+            continue;
+        }
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            stack.push(child);
+        }
+    }
+    fields
+}
+
+fn is_atomic_wrapper_body(body: Node, code: &str, receiver: &str, fields: &[String]) -> bool {
+    let mut has_atomic_call = false;
+    let mut stack = vec![body];
+    while let Some(node) = stack.pop() {
+        match node.kind() {
+            "call_expression" if is_receiver_atomic_call(node, code, receiver, fields) => {
+                has_atomic_call = true;
+            }
+            "assignment_statement" | "inc_statement" | "dec_statement" => {
+                let target = node
+                    .child_by_field_name("left")
+                    .or_else(|| node.named_child(0));
+                if let Some(target) = target {
+                    let target_text = text(code, target);
+                    if target_text
+                        .split(',')
+                        .any(|t| t.trim().starts_with(&format!("{}.", receiver)))
+                    {
+                        return false;
+                    }
+                }
+            }
+            _ => {}
+        }
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            stack.push(child);
+        }
+    }
+    has_atomic_call
+}
+
+/// `recv.field.Load()` on an atomic field, or `recv.Load()` through an embedded atomic.
+fn is_receiver_atomic_call(call: Node, code: &str, receiver: &str, fields: &[String]) -> bool {
+    let Some(function) = call.child_by_field_name("function") else {
+        return false;
+    };
+    if function.kind() != "selector_expression" {
+        return false;
+    }
+    let method = function.child_by_field_name("field").map(|n| text(code, n));
+    if !method.map(|m| ATOMIC_METHODS.contains(&m)).unwrap_or(false) {
+        return false;
+    }
+    let Some(operand) = function.child_by_field_name("operand") else {
+        return false;
+    };
+    match operand.kind() {
+        "identifier" => {
+            text(code, operand) == receiver
+                && fields.iter().any(|f| ATOMIC_TYPES.contains(&f.as_str()))
+        }
+        "selector_expression" => {
+            let base = operand
+                .child_by_field_name("operand")
+                .map(|n| text(code, n));
+            let field = operand.child_by_field_name("field").map(|n| text(code, n));
+            base == Some(receiver)
+                && field
+                    .map(|f| fields.iter().any(|x| x == f))
+                    .unwrap_or(false)
+        }
+        _ => false,
+    }
+}
+
 fn call_expression_name(call: Node, code: &str) -> Option<String> {
     let func = call.child_by_field_name("function")?;
     match func.kind() {
@@ -467,7 +1144,15 @@ fn call_expression_name(call: Node, code: &str) -> Option<String> {
     }
 }
 
-fn has_active_lock_for_target(block: Node, target_node: Node, code: &str) -> bool {
+/// A mutex held at some point, with the number of `Lock` and `RLock` calls not yet released.
+struct HeldLock {
+    mutex: String,
+    exclusive: i32,
+    shared: i32,
+}
+
+/// Returns the mutexes still held at `target_node`, in acquisition order.
+fn active_locks_for_target(block: Node, target_node: Node, code: &str) -> Vec<HeldLock> {
     let target_context = find_execution_context(target_node);
     let target_byte = target_node.start_byte();
     let mut calls = Vec::new();
@@ -483,8 +1168,7 @@ fn has_active_lock_for_target(block: Node, target_node: Node, code: &str) -> boo
         }
     }
     calls.sort_by_key(|n| n.start_byte());
-    use std::collections::HashMap;
-    let mut lock_depths: HashMap<String, i32> = HashMap::new();
+    let mut held: Vec<HeldLock> = Vec::new();
     for call in calls {
         if call.start_byte() > target_byte {
             break;
@@ -497,7 +1181,7 @@ fn has_active_lock_for_target(block: Node, target_node: Node, code: &str) -> boo
         if !same_context {
             continue;
         }
-        let (mutex_key, delta) = match lock_event(call, code) {
+        let (mutex_key, delta, shared) = match lock_event(call, code) {
             Some(event) => event,
             None => continue,
         };
@@ -513,13 +1197,63 @@ fn has_active_lock_for_target(block: Node, target_node: Node, code: &str) -> boo
         if delta < 0 && is_deferred {
             continue;
         }
-        let depth = lock_depths.entry(mutex_key.clone()).or_insert(0);
-        *depth += delta;
-        if *depth <= 0 {
-            lock_depths.remove(&mutex_key);
+        let idx = match held.iter().position(|lock| lock.mutex == mutex_key) {
+            Some(idx) => idx,
+            None if delta > 0 => {
+                held.push(HeldLock {
+                    mutex: mutex_key,
+                    exclusive: 0,
+                    shared: 0,
+                });
+                held.len() - 1
+            }
+            None => continue,
+        };
+        let lock = &mut held[idx];
+        if shared {
+            lock.shared = (lock.shared + delta).max(0);
+        } else {
+            lock.exclusive = (lock.exclusive + delta).max(0);
+        }
+        if lock.exclusive + lock.shared == 0 {
+            held.remove(idx);
         }
     }
-    lock_depths.values().any(|depth| *depth > 0)
+    held
+}
+
+/// Infers the mutex guarding the access at `range`: the most recently acquired
+/// lock still held in the access's function body.
+pub fn guarding_mutex(tree: &Tree, range: Range, code: &str) -> Option<String> {
+    let target_point = Point {
+        row: range.start.line as usize,
+        column: range.start.character as usize,
+    };
+    let target_node = find_node_at_position(tree.root_node(), target_point)?;
+    let scope = find_execution_context(target_node)
+        .and_then(|ctx| ctx.child_by_field_name("body"))
+        .unwrap_or(tree.root_node());
+    active_locks_for_target(scope, target_node, code)
+        .pop()
+        .map(|lock| lock.mutex)
+}
+
+/// Every mutex held at `range` in its function body, in acquisition order.
+pub fn held_mutexes(tree: &Tree, range: Range, code: &str) -> Vec<String> {
+    let target_point = Point {
+        row: range.start.line as usize,
+        column: range.start.character as usize,
+    };
+    let Some(target_node) = find_node_at_position(tree.root_node(), target_point) else {
+        return Vec::new();
+    };
+    let scope = find_execution_context(target_node)
+        .and_then(|ctx| ctx.child_by_field_name("body"))
+        .unwrap_or(tree.root_node());
+    active_locks_for_target(scope, target_node, code)
+        .into_iter()
+        .map(|lock| lock.mutex)
+        .collect()
 }
 
 fn find_execution_context(node: Node) -> Option<Node> {
@@ -527,7 +1261,7 @@ fn find_execution_context(node: Node) -> Option<Node> {
     while let Some(candidate) = current {
         if matches!(
             candidate.kind(),
-            "function_declaration" | "method_declaration" | "function_literal"
+            "function_declaration" | "method_declaration" | "func_literal"
         ) {
             return Some(candidate);
         }
@@ -536,7 +1270,8 @@ fn find_execution_context(node: Node) -> Option<Node> {
     None
 }
 
-fn lock_event(call: Node, code: &str) -> Option<(String, i32)> {
+/// (mutex, +1 for a lock / -1 for an unlock, whether it is the read lock).
+fn lock_event(call: Node, code: &str) -> Option<(String, i32, bool)> {
     let function = call.child_by_field_name("function")?;
     if function.kind() != "selector_expression" {
         return None;
@@ -544,53 +1279,137 @@ fn lock_event(call: Node, code: &str) -> Option<(String, i32)> {
     let operand = function.child_by_field_name("operand")?;
     let field = function.child_by_field_name("field")?;
     let method = text(code, field);
-    let delta = match method {
-        "Lock" | "RLock" => 1,
-        "Unlock" | "RUnlock" => -1,
+    let (delta, shared) = match method {
+        "Lock" => (1, false),
+        "RLock" => (1, true),
+        "Unlock" => (-1, false),
+        "RUnlock" => (-1, true),
+        // A weight-1 semaphore is held like a mutex.
+        "Acquire" | "Release" if is_xsync_call(call, code) => {
+            (if method == "Acquire" { 1 } else { -1 }, false)
+        }
+        // Helpers taking the critical section as a function literal cover it through the call
+        // instead.
+        _ if !has_function_literal_argument(call) => {
+            sync_config().lock_event(text(code, function), method)?
+        }
         _ => return None,
     };
     let key = text(code, operand).trim();
     if key.is_empty() {
         return None;
     }
-    Some((key.to_string(), delta))
+    Some((key.to_string(), delta, shared))
 }
 
-pub fn find_variable_at_position(tree: &Tree, code: &str, pos: Position) -> Option<VariableInfo> {
-    let target_point = Point {
-        row: pos.line as usize,
-        column: pos.character as usize,
+fn has_function_literal_argument(call: Node) -> bool {
+    call.child_by_field_name("arguments")
+        .is_some_and(|arguments| {
+            let mut cursor = arguments.walk();
+            let found = arguments
+                .named_children(&mut cursor)
+                .any(|argument| argument.kind() == "func_literal");
+            found
+        })
+}
+
+/// The variable or field at `pos`, if any; an error when `pos` is outside `code` or `tree` no
+/// longer matches it.
+pub fn find_variable_at_position(
+    tree: &Tree,
+    code: &str,
+    pos: Position,
+) -> AnalysisResult<Option<VariableInfo>> {
+    check_position(code, pos)?;
+    let target_point = point(pos);
+    let Some(target_node) = find_node_at_position(tree.root_node(), target_point) else {
+        return Ok(None);
     };
-    let target_node = find_node_at_position(tree.root_node(), target_point)?;
     if is_selector_call_symbol(target_node) {
-        return None;
+        return Ok(None);
     }
-    let var_name = extract_variable_name(target_node, code)?;
+    let Some(var_name) = extract_variable_name(target_node, code)? else {
+        return Ok(None);
+    };
     if is_field_identifier_context(target_node, target_point) {
-        return collect_field_info(tree, code, &var_name, target_point);
+        return Ok(collect_field_info(tree, code, &var_name, target_point));
     }
     let function_scope = find_function_scope(tree.root_node(), target_point);
-    collect_variable_info(tree, code, &var_name, function_scope, target_point)
+    Ok(collect_variable_info(
+        tree,
+        code,
+        &var_name,
+        function_scope,
+        target_point,
+    ))
+}
+
+fn point(position: Position) -> Point {
+    Point {
+        row: position.line as usize,
+        column: position.character as usize,
+    }
+}
+
+/// Fails when `position` is past the last line of `code` or the end of its line.
+pub fn check_position(code: &str, position: Position) -> AnalysisResult<()> {
+    match code.split('\n').nth(position.line as usize) {
+        // A line has at most as many UTF-16 units as bytes.
+        Some(line) if position.character as usize <= line.len() => Ok(()),
+        _ => Err(AnalysisError::InvalidPosition(position)),
+    }
+}
+
+/// The smallest meaningful node at `position`.
+pub fn node_at(tree: &Tree, position: Position) -> AnalysisResult<Node<'_>> {
+    find_node_at_position(tree.root_node(), point(position))
+        .ok_or(AnalysisError::NoNodeAt(position))
+}
+
+/// The text of `node`, failing when `code` is not the text its tree was parsed from.
+pub fn node_text<'a>(code: &'a str, node: Node) -> AnalysisResult<&'a str> {
+    code.get(node.byte_range())
+        .ok_or(AnalysisError::ByteRangeOutOfBounds {
+            start: node.start_byte(),
+            end: node.end_byte(),
+            len: code.len(),
+        })
+}
+
+/// Fails when `code` is not the text `tree` was parsed from, before a pass reads it.
+pub fn check_tree(tree: &Tree, code: &str) -> AnalysisResult<()> {
+    node_text(code, tree.root_node()).map(|_| ())
 }
 
+/// The smallest meaningful node containing `target`, preferring the outermost of equal size.
 fn find_node_at_position(node: tree_sitter::Node, target: Point) -> Option<tree_sitter::Node> {
     if !is_position_in_node_range(node, target) {
         return None;
     }
-    let mut best_match = node;
-    let mut best_size = node_size(node);
-    for i in 0..node.child_count() {
-        if let Some(child) = node.child(i) {
-            if let Some(child_match) = find_node_at_position(child, target) {
-                let child_size = node_size(child_match);
-                if child_size < best_size && is_meaningful_node(child_match) {
-                    best_match = child_match;
-                    best_size = child_size;
+    // Each node on the path being searched, the next child to look at, and its best match.
+    let mut path = vec![(node, 0, node, node_size(node))];
+    while let Some((current, next_child, _, _)) = path.last_mut() {
+        if let Some(child) = current.child(*next_child) {
+            *next_child += 1;
+            if path.len() < MAX_TRAVERSAL_DEPTH && is_position_in_node_range(child, target) {
+                path.push((child, 0, child, node_size(child)));
+            }
+            continue;
+        }
+        let Some((_, _, best, best_size)) = path.pop() else {
+            break;
+        };
+        match path.last_mut() {
+            Some((_, _, parent_best, parent_size)) => {
+                if best_size < *parent_size && is_meaningful_node(best) {
+                    *parent_best = best;
+                    *parent_size = best_size;
                 }
             }
+            None => return Some(best),
         }
     }
-    Some(best_match)
+    None
 }
 
 fn is_position_in_node_range(node: tree_sitter::Node, position: Point) -> bool {
@@ -677,6 +1496,7 @@ pub fn find_node_at_cursor_with_context(tree: &Tree, position: Position) -> Opti
 
 fn determine_cursor_context(node: tree_sitter::Node) -> CursorContextType {
     match node.kind() {
+        "identifier" if is_type_parameter(node) => CursorContextType::TypeReference,
         "identifier" => {
             if let Some(parent) = node.parent() {
                 match parent.kind() {
@@ -704,10 +1524,15 @@ fn determine_cursor_context(node: tree_sitter::Node) -> CursorContextType {
                 CursorContextType::Unknown
             }
         }
-        "field_identifier" => CursorContextType::FieldAccess,
+        "field_identifier" => match node.parent() {
+            Some(parent) if parent.kind() == "method_declaration" => {
+                CursorContextType::FunctionName
+            }
+            _ => CursorContextType::FieldAccess,
+        },
         "type_identifier" => CursorContextType::TypeReference,
         "package_identifier" => CursorContextType::PackageReference,
-        "function_declaration" => CursorContextType::FunctionDeclaration,
+        "function_declaration" | "method_declaration" => CursorContextType::FunctionDeclaration,
         "go_statement" => CursorContextType::GoroutineStatement,
         "channel_type" => CursorContextType::ChannelType,
         "interface_type" => CursorContextType::InterfaceType,
@@ -720,8 +1545,11 @@ pub fn find_variable_at_position_enhanced(
     tree: &Tree,
     code: &str,
     pos: Position,
-) -> Option<VariableInfo> {
-    let cursor_context = find_node_at_cursor_with_context(tree, pos)?;
+) -> AnalysisResult<Option<VariableInfo>> {
+    check_position(code, pos)?;
+    let Some(cursor_context) = find_node_at_cursor_with_context(tree, pos) else {
+        return Ok(None);
+    };
     match cursor_context.context_type {
         CursorContextType::VariableDeclaration
         | CursorContextType::ParameterDeclaration
@@ -733,33 +1561,37 @@ pub fn find_variable_at_position_enhanced(
     }
 }
 
-fn extract_variable_name(node: tree_sitter::Node, code: &str) -> Option<String> {
+fn extract_variable_name(node: tree_sitter::Node, code: &str) -> AnalysisResult<Option<String>> {
     match node.kind() {
-        "identifier" => {
-            let byte_range = node.byte_range();
-            code.get(byte_range).map(|s| s.to_string())
-        }
-        "field_identifier" => {
-            let byte_range = node.byte_range();
-            code.get(byte_range).map(|s| s.to_string())
-        }
-        "method_identifier" => {
-            let byte_range = node.byte_range();
-            code.get(byte_range).map(|s| s.to_string())
+        "identifier" if is_type_parameter(node) => Ok(None),
+        "type_parameter_list" | "type_arguments" | "generic_type" => Ok(None),
+        "identifier" | "field_identifier" | "method_identifier" => {
+            Ok(Some(node_text(code, node)?.to_string()))
         }
         _ => {
             for i in 0..node.child_count() {
                 if let Some(child) = node.child(i) {
-                    if let Some(name) = extract_variable_name(child, code) {
-                        return Some(name);
+                    if let Some(name) = extract_variable_name(child, code)? {
+                        return Ok(Some(name));
                     }
                 }
             }
-            None
+            Ok(None)
         }
     }
 }
 
+/// `T` in `func F[T any]()` or `type S[T any] struct{}`: a type, not a variable, even though the
+/// grammar parses it as an `identifier` in a `parameter_declaration`.
+pub(crate) fn is_type_parameter(node: Node) -> bool {
+    node.kind() == "identifier"
+        && node
+            .parent()
+            .filter(|parent| parent.kind() == "parameter_declaration")
+            .and_then(|parent| parent.parent())
+            .is_some_and(|list| list.kind() == "type_parameter_list")
+}
+
 fn is_field_identifier_context(node: tree_sitter::Node, target: Point) -> bool {
     if node.kind() == "field_identifier" {
         return true;
@@ -820,6 +1652,8 @@ fn collect_field_info(
             start_byte: 0,
             end_byte: 0,
         },
+        declared_in: None,
+        package_uses: Vec::new(),
     };
     let mut found_declaration = false;
     fn traverse_fields(
@@ -943,6 +1777,8 @@ fn collect_variable_info(
         potential_race: false,
         race_severity: RaceSeverity::Medium,
         var_id: decl.var_id,
+        declared_in: None,
+        package_uses: Vec::new(),
     };
     collect_uses_for_decl(search_root, code, var_name, decl, &mut var_info);
     Some(var_info)
@@ -965,7 +1801,7 @@ fn is_scope_node(kind: &str) -> bool {
         kind,
         "function_declaration"
             | "method_declaration"
-            | "function_literal"
+            | "func_literal"
             | "block"
             | "if_statement"
             | "for_statement"
@@ -1011,62 +1847,41 @@ fn resolve_decl_for_target(
     var_name: &str,
     target: Point,
 ) -> Option<DeclInfo> {
-    fn traverse(
-        node: tree_sitter::Node,
-        code: &str,
-        var_name: &str,
-        target: Point,
-        scope_stack: &mut Vec<ScopeEntry>,
-    ) -> Option<DeclInfo> {
-        let is_scope = is_scope_node(node.kind());
-        if is_scope {
+    let mut scope_stack: Vec<ScopeEntry> = vec![ScopeEntry { decl: None }];
+    let mut found = None;
+    walk_tree(root, |visit| {
+        let node = match visit {
+            Visit::Enter(node) => node,
+            Visit::Leave(node) => {
+                if is_scope_node(node.kind()) {
+                    scope_stack.pop();
+                }
+                return true;
+            }
+        };
+        if is_scope_node(node.kind()) {
             scope_stack.push(ScopeEntry { decl: None });
         }
         if let Some(decl) =
-            find_decl_in_node(node, code, var_name, current_scope_has_decl(scope_stack))
+            find_decl_in_node(node, code, var_name, current_scope_has_decl(&scope_stack))
         {
             if let Some(top) = scope_stack.last_mut() {
                 top.decl = Some(decl);
             }
             if range_contains_point(decl.range, target) {
-                if is_scope {
-                    scope_stack.pop();
-                }
-                return Some(decl);
-            }
-        }
-        if node.kind() == "identifier" {
-            if let Some(name) = code.get(node.byte_range()) {
-                if name == var_name && node_contains_point(node, target) {
-                    let found = resolve_current_decl(scope_stack);
-                    if is_scope {
-                        scope_stack.pop();
-                    }
-                    return found;
-                }
-            }
-        }
-        let mut cursor = node.walk();
-        if cursor.goto_first_child() {
-            loop {
-                if let Some(found) = traverse(cursor.node(), code, var_name, target, scope_stack) {
-                    if is_scope {
-                        scope_stack.pop();
-                    }
-                    return Some(found);
-                }
-                if !cursor.goto_next_sibling() {
-                    break;
-                }
+                found = Some(decl);
+                return false;
             }
         }
-        if is_scope {
-            scope_stack.pop();
+        if node.kind() == "identifier"
+            && code.get(node.byte_range()) == Some(var_name)
+            && node_contains_point(node, target)
+        {
+            found = resolve_current_decl(&scope_stack);
         }
-        None
-    }
-    let mut scope_stack: Vec<ScopeEntry> = vec![ScopeEntry { decl: None }];
-    traverse(root, code, var_name, target, &mut scope_stack)
+        found.is_none()
+    });
+    found
 }
 
 fn collect_uses_for_decl(
@@ -1080,20 +1895,22 @@ fn collect_uses_for_decl(
         a.var_id.start_byte == b.var_id.start_byte && a.var_id.end_byte == b.var_id.end_byte
     }
 
-    fn traverse(
-        node: tree_sitter::Node,
-        code: &str,
-        var_name: &str,
-        target_decl: DeclInfo,
-        scope_stack: &mut Vec<ScopeEntry>,
-        var_info: &mut VariableInfo,
-    ) {
-        let is_scope = is_scope_node(node.kind());
-        if is_scope {
+    let mut scope_stack: Vec<ScopeEntry> = vec![ScopeEntry { decl: None }];
+    walk_tree(root, |visit| {
+        let node = match visit {
+            Visit::Enter(node) => node,
+            Visit::Leave(node) => {
+                if is_scope_node(node.kind()) {
+                    scope_stack.pop();
+                }
+                return true;
+            }
+        };
+        if is_scope_node(node.kind()) {
             scope_stack.push(ScopeEntry { decl: None });
         }
         if let Some(decl) =
-            find_decl_in_node(node, code, var_name, current_scope_has_decl(scope_stack))
+            find_decl_in_node(node, code, var_name, current_scope_has_decl(&scope_stack))
         {
             if let Some(top) = scope_stack.last_mut() {
                 top.decl = Some(decl);
@@ -1102,7 +1919,7 @@ fn collect_uses_for_decl(
         if node.kind() == "identifier" {
             if let Some(name) = code.get(node.byte_range()) {
                 if name == var_name {
-                    if let Some(current) = resolve_current_decl(scope_stack) {
+                    if let Some(current) = resolve_current_decl(&scope_stack) {
                         if decl_eq(current, target_decl) {
                             let use_range = node_to_range(node);
                             if use_range != var_info.declaration
@@ -1118,35 +1935,8 @@ fn collect_uses_for_decl(
                 }
             }
         }
-        let mut cursor = node.walk();
-        if cursor.goto_first_child() {
-            loop {
-                traverse(
-                    cursor.node(),
-                    code,
-                    var_name,
-                    target_decl,
-                    scope_stack,
-                    var_info,
-                );
-                if !cursor.goto_next_sibling() {
-                    break;
-                }
-            }
-        }
-        if is_scope {
-            scope_stack.pop();
-        }
-    }
-    let mut scope_stack: Vec<ScopeEntry> = vec![ScopeEntry { decl: None }];
-    traverse(
-        root,
-        code,
-        var_name,
-        target_decl,
-        &mut scope_stack,
-        var_info,
-    );
+        true
+    });
 }
 
 fn find_decl_in_node(
@@ -1295,25 +2085,9 @@ fn find_identifier_in_node<'a>(
     code: &'a str,
     var_name: &'a str,
 ) -> Option<tree_sitter::Node<'a>> {
-    if node.kind() == "identifier" {
-        if let Some(name) = code.get(node.byte_range()) {
-            if name == var_name {
-                return Some(node);
-            }
-        }
-    }
-    let mut cursor = node.walk();
-    if cursor.goto_first_child() {
-        loop {
-            if let Some(found) = find_identifier_in_node(cursor.node(), code, var_name) {
-                return Some(found);
-            }
-            if !cursor.goto_next_sibling() {
-                break;
-            }
-        }
-    }
-    None
+    find_node(node, |node| {
+        node.kind() == "identifier" && code.get(node.byte_range()) == Some(var_name)
+    })
 }
 
 fn range_clause_declares(node: tree_sitter::Node) -> bool {
@@ -1361,25 +2135,13 @@ fn check_pointer_context(node: tree_sitter::Node, code: &str, var_info: &mut Var
 }
 
 fn contains_address_of(node: tree_sitter::Node, code: &str) -> bool {
-    if node.kind() == "unary_expression" {
-        if let Some(operator) = node.child_by_field_name("operator") {
-            if text(code, operator) == "&" {
-                return true;
-            }
-        }
-    }
-    let mut cursor = node.walk();
-    if cursor.goto_first_child() {
-        loop {
-            if contains_address_of(cursor.node(), code) {
-                return true;
-            }
-            if !cursor.goto_next_sibling() {
-                break;
-            }
-        }
-    }
-    false
+    find_node(node, |node| {
+        node.kind() == "unary_expression"
+            && node
+                .child_by_field_name("operator")
+                .is_some_and(|operator| text(code, operator) == "&")
+    })
+    .is_some()
 }
 
 fn is_reference_type_kind(kind: &str) -> bool {
@@ -1390,87 +2152,50 @@ fn is_reference_type_kind(kind: &str) -> bool {
 }
 
 fn contains_reference_type(node: tree_sitter::Node) -> bool {
-    if is_reference_type_kind(node.kind()) {
-        return true;
-    }
-    let mut cursor = node.walk();
-    if cursor.goto_first_child() {
-        loop {
-            if contains_reference_type(cursor.node()) {
-                return true;
-            }
-            if !cursor.goto_next_sibling() {
-                break;
-            }
-        }
-    }
-    false
+    find_node(node, |node| is_reference_type_kind(node.kind())).is_some()
 }
 
-pub fn is_variable_reassignment(tree: &Tree, var_name: &str, use_range: Range, code: &str) -> bool {
-    let target_point = Point {
-        row: use_range.start.line as usize,
-        column: use_range.start.character as usize,
-    };
-
-    if let Some(node) = find_node_at_position(tree.root_node(), target_point) {
-        if let Some(parent) = node.parent() {
-            match parent.kind() {
-                "assignment_statement" => {
-                    // x = value
-                    if let Some(left) = parent.child_by_field_name("left") {
-                        if contains_variable_name(left, var_name, code) {
-                            return true;
-                        }
+pub fn is_variable_reassignment(
+    tree: &Tree,
+    var_name: &str,
+    use_range: Range,
+    code: &str,
+) -> AnalysisResult<bool> {
+    let node = node_at(tree, use_range.start)?;
+    if let Some(parent) = node.parent() {
+        match parent.kind() {
+            "assignment_statement" => {
+                // x = value
+                if let Some(left) = parent.child_by_field_name("left") {
+                    if contains_variable_name(left, var_name, code) {
+                        return Ok(true);
                     }
                 }
-                "inc_statement" | "dec_statement" => {
-                    // x++ or x-- are reassignments
-                    return true;
-                }
-                "short_var_declaration" => {
-                    // For := declarations, check if this is a redeclaration
-                    // In Go, x := can be reassignment if x already exists in scope
-                    if let Some(left) = parent.child_by_field_name("left") {
-                        if contains_variable_name(left, var_name, code) {
-                            return false;
-                        }
+            }
+            "inc_statement" | "dec_statement" => {
+                // x++ or x-- are reassignments
+                return Ok(true);
+            }
+            "short_var_declaration" => {
+                // For := declarations, check if this is a redeclaration
+                // In Go, x := can be reassignment if x already exists in scope
+                if let Some(left) = parent.child_by_field_name("left") {
+                    if contains_variable_name(left, var_name, code) {
+                        return Ok(false);
                     }
                 }
-                _ => {}
             }
+            _ => {}
         }
     }
-    false
+    Ok(false)
 }
 
 fn contains_variable_name(node: tree_sitter::Node, var_name: &str, code: &str) -> bool {
-    match node.kind() {
-        "identifier" => {
-            let node_text = tree_sitter_text(node, code);
-            node_text == var_name
-        }
-        "expression_list" | "identifier_list" => {
-            for i in 0..node.child_count() {
-                if let Some(child) = node.child(i) {
-                    if contains_variable_name(child, var_name, code) {
-                        return true;
-                    }
-                }
-            }
-            false
-        }
-        _ => {
-            for i in 0..node.child_count() {
-                if let Some(child) = node.child(i) {
-                    if contains_variable_name(child, var_name, code) {
-                        return true;
-                    }
-                }
-            }
-            false
-        }
-    }
+    find_node(node, |node| {
+        node.kind() == "identifier" && tree_sitter_text(node, code) == var_name
+    })
+    .is_some()
 }
 
 fn tree_sitter_text(node: tree_sitter::Node, code: &str) -> String {
@@ -1482,21 +2207,10 @@ pub fn is_variable_captured(
     var_name: &str,
     use_range: Range,
     declaration_range: Range,
-) -> bool {
-    let target_point = Point {
-        row: use_range.start.line as usize,
-        column: use_range.start.character as usize,
-    };
-    let decl_point = Point {
-        row: declaration_range.start.line as usize,
-        column: declaration_range.start.character as usize,
-    };
-    if let Some(use_node) = find_node_at_position(tree.root_node(), target_point) {
-        if let Some(decl_node) = find_node_at_position(tree.root_node(), decl_point) {
-            return is_captured_in_closure(use_node, decl_node, var_name);
-        }
-    }
-    false
+) -> AnalysisResult<bool> {
+    let use_node = node_at(tree, use_range.start)?;
+    let decl_node = node_at(tree, declaration_range.start)?;
+    Ok(is_captured_in_closure(use_node, decl_node, var_name))
 }
 
 fn is_captured_in_closure(
@@ -1524,13 +2238,13 @@ fn find_enclosing_closure_or_goroutine(node: tree_sitter::Node) -> Option<tree_s
     let mut current = Some(node);
     while let Some(node) = current {
         match node.kind() {
-            "function_literal" => {
+            "func_literal" if !is_immediately_invoked(node) => {
                 return Some(node);
             }
             "go_statement" => {
                 return Some(node);
             }
-            "function_declaration" => {
+            "function_declaration" | "method_declaration" => {
                 return None;
             }
             _ => {
@@ -1541,6 +2255,42 @@ fn find_enclosing_closure_or_goroutine(node: tree_sitter::Node) -> Option<tree_s
     None
 }
 
+/// Reports whether `node` is a `func(){...}()` literal that is called in place,
+/// outside of `go`/`defer`. Such literals run synchronously on the caller's goroutine.
+pub fn is_immediately_invoked(node: tree_sitter::Node) -> bool {
+    if node.kind() != "func_literal" {
+        return false;
+    }
+    let call = match node.parent() {
+        Some(p) if p.kind() == "call_expression" => p,
+        _ => return false,
+    };
+    let is_callee = call
+        .child_by_field_name("function")
+        .map(|f| f.id() == node.id())
+        .unwrap_or(false);
+    if !is_callee {
+        return false;
+    }
+    !matches!(
+        call.parent().map(|p| p.kind()),
+        Some("go_statement" | "defer_statement")
+    )
+}
+
+/// Like [`is_in_goroutine`], also counting functions started with an errgroup's `Go`.
+pub fn is_in_goroutine_or_group(tree: &Tree, range: Range, code: &str) -> bool {
+    if is_in_goroutine(tree, range) {
+        return true;
+    }
+    let target_point = Point {
+        row: range.start.line as usize,
+        column: range.start.character as usize,
+    };
+    find_node_at_position(tree.root_node(), target_point)
+        .is_some_and(|node| spawning_go_statement(node, code).is_some())
+}
+
 pub fn is_in_goroutine(tree: &Tree, range: Range) -> bool {
     let target_point = Point {
         row: range.start.line as usize,
@@ -1557,13 +2307,13 @@ fn find_goroutine_context(
         return None;
     }
     match node.kind() {
-        "go_statement" => {
-            // go func() {}
-            if node.start_position() <= target_point && target_point <= node.end_position() {
-                return Some(node);
-            }
+        // go func() {}
+        "go_statement"
+            if node.start_position() <= target_point && target_point <= node.end_position() =>
+        {
+            return Some(node);
         }
-        "function_literal" => {
+        "func_literal" => {
             if let Some(parent) = node.parent() {
                 if parent.kind() == "go_statement" {
                     if node.start_position() <= target_point && target_point <= node.end_position()
@@ -1596,63 +2346,446 @@ fn find_goroutine_context(
     None
 }
 
-pub fn count_entities(tree: &Tree, code: &str) -> EntityCount {
-    fn traverse(node: Node, _code: &str, counts: &mut EntityCount) {
-        match node.kind() {
-            "var_spec" | "short_var_declaration" => {
-                let mut cursor = node.walk();
-                if cursor.goto_first_child() {
-                    loop {
-                        let child = cursor.node();
-                        if child.kind() == "identifier" {
-                            counts.variables += 1;
-                        } else {
-                            let mut sub_cursor = child.walk();
-                            if sub_cursor.goto_first_child() {
-                                loop {
-                                    let sub_child = sub_cursor.node();
-                                    if sub_child.kind() == "identifier" {
-                                        counts.variables += 1;
-                                    }
-                                    if !sub_cursor.goto_next_sibling() {
-                                        break;
-                                    }
-                                }
-                            }
-                        }
-                        if !cursor.goto_next_sibling() {
-                            break;
-                        }
-                    }
-                }
-            }
-            "function_declaration" => counts.functions += 1,
-            "go_statement" => counts.goroutines += 1,
-            "channel_type" => counts.channels += 1,
-            _ => {}
-        }
-        let mut cursor = node.walk();
-        if cursor.goto_first_child() {
-            loop {
-                traverse(cursor.node(), _code, counts);
-                if !cursor.goto_next_sibling() {
-                    break;
-                }
+pub fn goroutine_type(go_stmt: Node) -> Option<GoroutineType> {
+    if go_stmt.kind() == "call_expression" {
+        // An errgroup `Go` call, which always takes a function value.
+        return Some(GoroutineType::AnonymousClosure);
+    }
+    let call = go_stmt.named_child(0)?;
+    let function = call.child_by_field_name("function")?;
+    Some(match function.kind() {
+        "func_literal" => GoroutineType::AnonymousClosure,
+        "selector_expression" => GoroutineType::MethodCall,
+        _ => GoroutineType::NamedFunction,
+    })
+}
+
+/// Finds the `go` statement (or errgroup `Go` call) whose goroutine executes `node`. Arguments
+/// of the spawned call are evaluated by the spawning goroutine, so they are skipped.
+pub(crate) fn spawning_go_statement<'a>(node: Node<'a>, code: &str) -> Option<Node<'a>> {
+    let mut child = node;
+    let mut current = node.parent();
+    while let Some(candidate) = current {
+        if let Some(call) = errgroup_launch(candidate, code) {
+            return Some(call);
+        }
+        if candidate.kind() == "go_statement" {
+            let via_arguments = child.kind() == "call_expression"
+                && child
+                    .child_by_field_name("arguments")
+                    .map(|args| {
+                        args.start_byte() <= node.start_byte() && node.end_byte() <= args.end_byte()
+                    })
+                    .unwrap_or(false);
+            if !via_arguments {
+                return Some(candidate);
+            }
+        }
+        child = candidate;
+        current = candidate.parent();
+    }
+    None
+}
+
+/// Go memory model: a `go` statement happens before the goroutine it starts.
+/// A read inside a goroutine is safe when the variable is never written
+/// after the spawn point, including later iterations of an enclosing loop.
+pub fn is_published_before_spawn(
+    tree: &Tree,
+    code: &str,
+    var_info: &VariableInfo,
+    use_range: Range,
+) -> AnalysisResult<bool> {
+    let use_node = node_at(tree, use_range.start)?;
+    let go_stmt = match spawning_go_statement(use_node, code) {
+        Some(go_stmt) => go_stmt,
+        None => return Ok(false),
+    };
+    if is_variable_reassignment(tree, &var_info.name, use_range, code)? {
+        return Ok(false);
+    }
+    let decl_byte = var_info.var_id.start_byte;
+    let mut spawner = go_stmt.parent();
+    while let Some(node) = spawner {
+        if matches!(node.kind(), "function_declaration" | "method_declaration") {
+            break;
+        }
+        spawner = node.parent();
+    }
+    let spawner = match spawner {
+        Some(node) => node,
+        None => return Ok(false),
+    };
+    for other in &var_info.uses {
+        let other_node = node_at(tree, other.start)?;
+        if is_under_address_of(other_node, code) {
+            return Ok(false);
+        }
+        if !is_variable_reassignment(tree, &var_info.name, *other, code)? {
+            continue;
+        }
+        if other_node.start_byte() >= go_stmt.start_byte()
+            || other_node.start_byte() < spawner.start_byte()
+            || other_node.end_byte() > spawner.end_byte()
+        {
+            return Ok(false);
+        }
+        let mut current = go_stmt.parent();
+        while let Some(node) = current {
+            if node.kind() == "for_statement"
+                && node.start_byte() <= other_node.start_byte()
+                && other_node.end_byte() <= node.end_byte()
+                && !(node.start_byte() <= decl_byte && decl_byte < node.end_byte())
+            {
+                return Ok(false);
+            }
+            if matches!(
+                node.kind(),
+                "function_declaration" | "method_declaration" | "func_literal"
+            ) {
+                break;
             }
+            current = node.parent();
+        }
+    }
+    Ok(true)
+}
+
+/// Go memory model: the completion of `f` in `once.Do(f)` is synchronized before the return of
+/// any `once.Do(f)` call. A read is safe when every write to the variable happens inside the
+/// function passed to `Do` of one `sync.Once`, and the reading function calls `Do` on that Once
+/// before the read.
+pub fn is_published_by_once(
+    tree: &Tree,
+    code: &str,
+    var_info: &VariableInfo,
+    use_range: Range,
+) -> AnalysisResult<bool> {
+    if is_variable_reassignment(tree, &var_info.name, use_range, code)? {
+        return Ok(false);
+    }
+    let use_node = node_at(tree, use_range.start)?;
+    let onces = once_names(tree.root_node(), code);
+    let mut once = None;
+    for other in &var_info.uses {
+        let other_node = node_at(tree, other.start)?;
+        if is_under_address_of(other_node, code) {
+            return Ok(false);
+        }
+        if !is_variable_reassignment(tree, &var_info.name, *other, code)? {
+            continue;
+        }
+        let Some(writer) = once_initializer(tree.root_node(), other_node, code, &onces) else {
+            return Ok(false);
+        };
+        if once.get_or_insert_with(|| writer.clone()) != &writer {
+            return Ok(false);
         }
     }
-    let mut counts = EntityCount {
-        variables: 0,
-        functions: 0,
-        channels: 0,
-        goroutines: 0,
+    let Some(once) = once else {
+        return Ok(false);
+    };
+    let Some(reader) = find_execution_context(use_node) else {
+        return Ok(false);
+    };
+    let mut calls = Vec::new();
+    collect_kind(reader, "call_expression", &mut calls);
+    Ok(calls.iter().any(|call| {
+        call.end_byte() <= use_node.start_byte()
+            && find_execution_context(*call).map(|ctx| ctx.id()) == Some(reader.id())
+            && once_do_operand(*call, code).as_deref() == Some(once.as_str())
+    }))
+}
+
+/// `use_range` runs in a function started with an errgroup's `Go`, and every other use of the
+/// variable is ordered with it: before the `Go` call, after the group's `Wait` returns, or in the
+/// same function when it is not started repeatedly from a loop. After `g.SetLimit(1)` the group
+/// runs one function at a time, so uses in any of its functions are ordered with each other.
+pub fn is_joined_by_group_wait(
+    tree: &Tree,
+    code: &str,
+    var_info: &VariableInfo,
+    use_range: Range,
+) -> AnalysisResult<bool> {
+    let use_node = node_at(tree, use_range.start)?;
+    let Some(launch) = spawning_go_statement(use_node, code) else {
+        return Ok(false);
+    };
+    if launch.kind() != "call_expression" {
+        return Ok(false);
+    }
+    let group_of = |launch: Node| {
+        launch
+            .child_by_field_name("function")
+            .and_then(|function| function.child_by_field_name("operand"))
+            .map(|operand| text(code, operand))
+    };
+    let Some(group) = group_of(launch) else {
+        return Ok(false);
     };
-    traverse(tree.root_node(), code, &mut counts);
+    let Some(spawner) = find_execution_context(launch) else {
+        return Ok(false);
+    };
+    let mut calls = Vec::new();
+    collect_kind(spawner, "call_expression", &mut calls);
+    let Some(wait) = calls.into_iter().find(|call| {
+        call.start_byte() >= launch.end_byte()
+            && find_execution_context(*call).map(|ctx| ctx.id()) == Some(spawner.id())
+            && call
+                .child_by_field_name("function")
+                .filter(|function| function.kind() == "selector_expression")
+                .is_some_and(|function| {
+                    function
+                        .child_by_field_name("operand")
+                        .is_some_and(|operand| text(code, operand) == group)
+                        && function
+                            .child_by_field_name("field")
+                            .is_some_and(|field| text(code, field) == "Wait")
+                })
+    }) else {
+        return Ok(false);
+    };
+    let mut loop_ancestor = launch.parent();
+    let mut in_loop = false;
+    while let Some(ancestor) = loop_ancestor {
+        if ancestor.id() == spawner.id() {
+            break;
+        }
+        in_loop |= ancestor.kind() == "for_statement";
+        loop_ancestor = ancestor.parent();
+    }
+    let same_context = |node: Node| find_execution_context(node).map(|ctx| ctx.id());
+    let serialized = group_limit(spawner, group, launch, code) == Some("1");
+    let ordered = |other: &Range| -> AnalysisResult<bool> {
+        let other_node = node_at(tree, other.start)?;
+        if let Some(other_launch) = spawning_go_statement(other_node, code) {
+            if other_launch.id() == launch.id() {
+                return Ok(serialized
+                    || !in_loop
+                    || !is_variable_reassignment(tree, &var_info.name, *other, code)?);
+            }
+            if serialized
+                && other_launch.kind() == "call_expression"
+                && group_of(other_launch) == Some(group)
+                && same_context(other_launch) == Some(spawner.id())
+            {
+                return Ok(true);
+            }
+        }
+        if same_context(other_node) != Some(spawner.id()) {
+            return Ok(false);
+        }
+        Ok(other_node.start_byte() >= wait.end_byte()
+            || (!in_loop && other_node.end_byte() <= launch.start_byte()))
+    };
+    for other in &var_info.uses {
+        if !ordered(other)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// The argument of the last `group.SetLimit(n)` in `spawner` before `launch`.
+fn group_limit<'a>(spawner: Node, group: &str, launch: Node, code: &'a str) -> Option<&'a str> {
+    let mut calls = Vec::new();
+    collect_kind(spawner, "call_expression", &mut calls);
+    calls
+        .into_iter()
+        .filter(|call| call.end_byte() <= launch.start_byte())
+        .filter(|call| {
+            call.child_by_field_name("function")
+                .filter(|function| function.kind() == "selector_expression")
+                .is_some_and(|function| {
+                    function
+                        .child_by_field_name("operand")
+                        .is_some_and(|operand| text(code, operand) == group)
+                        && function
+                            .child_by_field_name("field")
+                            .is_some_and(|field| text(code, field) == "SetLimit")
+                })
+        })
+        .max_by_key(|call| call.start_byte())
+        .and_then(|call| call.child_by_field_name("arguments")?.named_child(0))
+        .map(|limit| text(code, limit))
+}
+
+/// Names of variables and struct fields declared as `sync.Once`.
+fn once_names(root: Node, code: &str) -> HashSet<String> {
+    let mut declarations = Vec::new();
+    collect_kind(root, "var_spec", &mut declarations);
+    collect_kind(root, "field_declaration", &mut declarations);
+    let mut names = HashSet::new();
+    for declaration in declarations {
+        let is_once = declaration
+            .child_by_field_name("type")
+            .is_some_and(|typ| text(code, typ) == "sync.Once");
+        if !is_once {
+            continue;
+        }
+        let mut cursor = declaration.walk();
+        for name in declaration.children_by_field_name("name", &mut cursor) {
+            names.insert(text(code, name).to_string());
+        }
+    }
+    names
+}
+
+/// The operand of `x.Do(...)`, keyed by its last selector, when `x` is a `sync.Once`.
+fn once_do_operand(call: Node, code: &str) -> Option<String> {
+    let function = call.child_by_field_name("function")?;
+    if function.kind() != "selector_expression"
+        || text(code, function.child_by_field_name("field")?) != "Do"
+    {
+        return None;
+    }
+    let operand = text(code, function.child_by_field_name("operand")?);
+    Some(operand.rsplit('.').next().unwrap_or(operand).to_string())
+}
+
+/// The Once whose `Do` runs the function containing `write`: a func literal passed to `Do`
+/// directly, or a declared function passed by name.
+fn once_initializer(
+    root: Node,
+    write: Node,
+    code: &str,
+    onces: &HashSet<String>,
+) -> Option<String> {
+    let function = find_execution_context(write)?;
+    let once_of_call = |call: Node| once_do_operand(call, code).filter(|once| onces.contains(once));
+    if function.kind() == "func_literal" {
+        let call = function.parent()?.parent()?;
+        return once_of_call(call);
+    }
+    let name = text(code, function.child_by_field_name("name")?);
+    let mut calls = Vec::new();
+    collect_kind(root, "call_expression", &mut calls);
+    calls.into_iter().find_map(|call| {
+        let passes_function = call
+            .child_by_field_name("arguments")
+            .and_then(|args| args.named_child(0))
+            .is_some_and(|arg| text(code, arg) == name);
+        if passes_function {
+            once_of_call(call)
+        } else {
+            None
+        }
+    })
+}
+
+pub fn analyze_goroutine_usage(
+    tree: &Tree,
+    code: &str,
+    var_info: &VariableInfo,
+) -> Vec<GoroutineUsage> {
+    let mut usages: Vec<GoroutineUsage> = Vec::new();
+    for use_range in &var_info.uses {
+        let point = Point {
+            row: use_range.start.line as usize,
+            column: use_range.start.character as usize,
+        };
+        let node = match find_node_at_position(tree.root_node(), point) {
+            Some(node) => node,
+            None => continue,
+        };
+        let go_stmt = match spawning_go_statement(node, code) {
+            Some(go_stmt) => go_stmt,
+            None => continue,
+        };
+        let launch = node_to_range(go_stmt);
+        let is_write =
+            is_variable_reassignment(tree, &var_info.name, *use_range, code).unwrap_or(false);
+        let idx = match usages.iter().position(|u| u.launch == launch) {
+            Some(idx) => idx,
+            None => {
+                usages.push(GoroutineUsage {
+                    launch,
+                    goroutine_type: goroutine_type(go_stmt).unwrap_or(GoroutineType::NamedFunction),
+                    reads: 0,
+                    writes: 0,
+                });
+                usages.len() - 1
+            }
+        };
+        if is_write {
+            usages[idx].writes += 1;
+        } else {
+            usages[idx].reads += 1;
+        }
+    }
+    usages.sort_by_key(|u| (u.launch.start.line, u.launch.start.character));
+    usages
+}
+
+pub fn count_entities(tree: &Tree, code: &str) -> EntityCount {
+    let mut counts = EntityCount::default();
+    ENTITIES.for_each_match(tree.root_node(), code, |captures| {
+        if captures.get("variable").is_some() {
+            counts.variables += 1;
+        } else if captures.get("function").is_some() {
+            counts.functions += 1;
+        } else if captures.get("goroutine").is_some() {
+            counts.goroutines += 1;
+        } else if captures.get("channel").is_some() {
+            counts.channels += 1;
+        }
+        true
+    });
     counts
 }
 
+/// Names declared by the `type_parameters` of a function or type declaration.
+pub(crate) fn type_parameter_names<'a>(declaration: Node, code: &'a str) -> Vec<&'a str> {
+    let Some(list) = declaration.child_by_field_name("type_parameters") else {
+        return Vec::new();
+    };
+    let mut names = Vec::new();
+    let mut cursor = list.walk();
+    for parameter in list.named_children(&mut cursor) {
+        let mut name_cursor = parameter.walk();
+        names.extend(
+            parameter
+                .children_by_field_name("name", &mut name_cursor)
+                .map(|name| text(code, name)),
+        );
+    }
+    names
+}
+
+/// Type name of a method receiver list `(c *Counter)`, without the pointer.
+pub(crate) fn receiver_type_name(receiver: Node) -> Option<Node> {
+    let param = receiver.named_child(0)?;
+    base_type_name(param.child_by_field_name("type")?)
+}
+
+/// `T` of `T`, `*T`, `(T)` or `T[K]`.
+fn base_type_name(typ: Node) -> Option<Node> {
+    let mut typ = typ;
+    while matches!(
+        typ.kind(),
+        "pointer_type" | "parenthesized_type" | "generic_type"
+    ) {
+        typ = if typ.kind() == "generic_type" {
+            typ.child_by_field_name("type")?
+        } else {
+            typ.named_child(0)?
+        };
+    }
+    Some(typ)
+}
+
+/// Reports whether the method's receiver is a pointer, so writes through it are shared.
+pub(crate) fn has_pointer_receiver(method: Node) -> bool {
+    method
+        .child_by_field_name("receiver")
+        .and_then(|receiver| receiver.named_child(0))
+        .and_then(|param| param.child_by_field_name("type"))
+        .map(|typ| typ.kind() == "pointer_type")
+        .unwrap_or(false)
+}
+
 #[inline]
-fn text<'a>(code: &'a str, node: Node) -> &'a str {
+pub(crate) fn text<'a>(code: &'a str, node: Node) -> &'a str {
     let bytes = code.as_bytes();
     if let Some(slice) = bytes.get(node.start_byte()..node.end_byte()) {
         unsafe { std::str::from_utf8_unchecked(slice) }
@@ -1661,25 +2794,734 @@ fn text<'a>(code: &'a str, node: Node) -> &'a str {
     }
 }
 
+/// The highest race severity among the variable's accesses from goroutines, directly or through
+/// aliases; `None` when no goroutine touches it. `writes` flags which of `var_info.uses` assign.
+pub fn variable_race_severity(
+    tree: &Tree,
+    code: &str,
+    var_info: &VariableInfo,
+    writes: &[bool],
+) -> AnalysisResult<Option<RaceSeverity>> {
+    let sync_funcs = crate::analysis::collect_sync_functions(tree, code);
+    let rank = |severity: &RaceSeverity| match severity {
+        RaceSeverity::High => 2,
+        RaceSeverity::Medium => 1,
+        RaceSeverity::Low => 0,
+    };
+    let through_aliases = crate::aliases::alias_accesses(tree, code, var_info)
+        .into_iter()
+        .filter(|alias| is_in_goroutine_or_group(tree, alias.range, code))
+        .map(|alias| determine_race_severity(tree, alias.range, code, alias.write, &sync_funcs));
+    let mut direct = Vec::new();
+    for (use_range, is_write) in var_info.uses.iter().zip(writes) {
+        if !is_in_goroutine_or_group(tree, *use_range, code)
+            || is_published_before_spawn(tree, code, var_info, *use_range)?
+        {
+            continue;
+        }
+        direct.push(
+            if (!*is_write && is_published_by_once(tree, code, var_info, *use_range)?)
+                || is_joined_by_group_wait(tree, code, var_info, *use_range)?
+            {
+                RaceSeverity::Low
+            } else {
+                determine_race_severity(tree, *use_range, code, *is_write, &sync_funcs)
+            },
+        );
+    }
+    Ok(direct.into_iter().chain(through_aliases).max_by_key(rank))
+}
+
+/// `extra` of a variable declaration node: `potential_race`, plus `race_severity` when a
+/// goroutine accesses the variable and `guarded_by` when a mutex is held around its uses.
+fn race_annotation(tree: &Tree, code: &str, declaration: Position) -> Option<serde_json::Value> {
+    let var_info = find_variable_at_position(tree, code, declaration).ok()??;
+    let writes = var_info
+        .uses
+        .iter()
+        .map(|use_range| is_variable_reassignment(tree, &var_info.name, *use_range, code))
+        .collect::<AnalysisResult<Vec<bool>>>()
+        .ok()?;
+    let severity = variable_race_severity(tree, code, &var_info, &writes).ok()?;
+    let mut extra = json!({
+        "potential_race": matches!(severity, Some(RaceSeverity::High | RaceSeverity::Medium)),
+    });
+    if let Some(severity) = severity {
+        extra["race_severity"] = json!(severity);
+    }
+    if let Some(mutex) = var_info
+        .uses
+        .iter()
+        .find_map(|use_range| guarding_mutex(tree, *use_range, code))
+    {
+        extra["guarded_by"] = json!(mutex);
+    }
+    Some(extra)
+}
+
+/// Functions and methods declared in a file, by the ids of their graph nodes, so `Call` edges
+/// can point at the declaration rather than at the call site.
+#[derive(Default)]
+pub(crate) struct FunctionSymbols {
+    functions: HashMap<String, String>,
+    /// Receiver type name, then method name.
+    methods: HashMap<String, HashMap<String, String>>,
+    /// Import names, which are never receivers.
+    packages: HashSet<String>,
+    /// `pkg.Func` of imported packages in the workspace graph.
+    qualified: HashMap<String, String>,
+    /// Package-level variables declared in other files, which locals may shadow.
+    shared_variables: HashSet<String>,
+}
+
+impl FunctionSymbols {
+    pub(crate) fn collect(
+        root: Node,
+        code: &str,
+        ids: &mut GraphIds,
+        external: &ExternalSymbols,
+    ) -> Self {
+        let mut symbols = Self::default();
+        for (name, id) in &external.functions {
+            if name.contains('.') {
+                symbols.qualified.insert(name.clone(), id.clone());
+            } else {
+                symbols.functions.insert(name.clone(), id.clone());
+            }
+        }
+        symbols.shared_variables = external.variables.keys().cloned().collect();
+        let mut cursor = root.walk();
+        for decl in root.named_children(&mut cursor) {
+            if decl.kind() == "import_declaration" {
+                let mut specs = Vec::new();
+                collect_kind(decl, "import_spec", &mut specs);
+                for spec in specs {
+                    let name = match spec.child_by_field_name("name") {
+                        Some(alias) => text(code, alias),
+                        None => spec.child_by_field_name("path").map_or("", |path| {
+                            let path = text(code, path).trim_matches('"');
+                            path.rsplit('/').next().unwrap_or(path)
+                        }),
+                    };
+                    symbols.packages.insert(name.to_string());
+                }
+                continue;
+            }
+            let Some(ident) = decl.child_by_field_name("name") else {
+                continue;
+            };
+            let name = text(code, ident);
+            if !matches!(decl.kind(), "function_declaration" | "method_declaration") {
+                continue;
+            }
+            let id = ids.actor(decl);
+            match decl.kind() {
+                "function_declaration" => {
+                    symbols.functions.insert(name.to_string(), id);
+                }
+                "method_declaration" => {
+                    if let Some(receiver) = decl
+                        .child_by_field_name("receiver")
+                        .and_then(receiver_type_name)
+                    {
+                        symbols
+                            .methods
+                            .entry(text(code, receiver).to_string())
+                            .or_default()
+                            .insert(name.to_string(), id);
+                    }
+                }
+                _ => {}
+            }
+        }
+        symbols
+    }
+
+    /// Node id of the function or method `call` invokes, when the file declares it. A method
+    /// is found through the operand's declared type, or by its name when only one type in the
+    /// file has a method of that name.
+    pub(crate) fn resolve(&self, call: Node, code: &str) -> Option<&str> {
+        let callee = call.child_by_field_name("function")?;
+        match callee.kind() {
+            "identifier" => {
+                let name = text(code, callee);
+                if enclosing_declaration(call)
+                    .is_some_and(|function| local_names(function, code).contains(name))
+                {
+                    return None;
+                }
+                self.functions.get(name).map(String::as_str)
+            }
+            "selector_expression" => {
+                let method = text(code, callee.child_by_field_name("field")?);
+                let operand = callee.child_by_field_name("operand")?;
+                if operand.kind() == "identifier" && self.packages.contains(text(code, operand)) {
+                    return self.qualified.get(text(code, callee)).map(String::as_str);
+                }
+                if let Some(typ) = operand_type(operand, code) {
+                    return self.methods.get(typ)?.get(method).map(String::as_str);
+                }
+                let mut candidates = self
+                    .methods
+                    .values()
+                    .filter_map(|methods| methods.get(method));
+                match (candidates.next(), candidates.next()) {
+                    (Some(id), None) => Some(id),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The function or method declaration containing `node`, through any function literals.
+pub(crate) fn enclosing_declaration(node: Node) -> Option<Node> {
+    let mut current = node.parent();
+    while let Some(ancestor) = current {
+        if matches!(
+            ancestor.kind(),
+            "function_declaration" | "method_declaration"
+        ) {
+            return Some(ancestor);
+        }
+        current = ancestor.parent();
+    }
+    None
+}
+
+/// The innermost function literal containing `node`.
+pub(crate) fn enclosing_literal(node: Node) -> Option<Node> {
+    let mut current = node.parent();
+    while let Some(ancestor) = current {
+        if ancestor.kind() == "func_literal" {
+            return Some(ancestor);
+        }
+        current = ancestor.parent();
+    }
+    None
+}
+
+/// Graph id of what runs `node`: the innermost goroutine (`go` statement or errgroup `Go` call)
+/// or declared function around it. Calls spawning a goroutine, like `worker` in `go worker()`,
+/// run in that goroutine, so spawns and calls form a tree rooted at the declared functions.
+fn enclosing_actor(node: Node, code: &str, ids: &mut GraphIds) -> Option<String> {
+    let mut current = node.parent();
+    while let Some(ancestor) = current {
+        let is_actor = match ancestor.kind() {
+            "go_statement" | "function_declaration" | "method_declaration" => true,
+            "call_expression" => is_errgroup_go(ancestor, code),
+            _ => false,
+        };
+        if is_actor {
+            return Some(ids.actor(ancestor));
+        }
+        current = ancestor.parent();
+    }
+    None
+}
+
+/// Declared type name of the identifier `operand`: a receiver, parameter or `var` of type `T`
+/// or `*T`, or a local initialized with `T{}`, `&T{}` or `new(T)`.
+fn operand_type<'a>(operand: Node, code: &'a str) -> Option<&'a str> {
+    if operand.kind() != "identifier" {
+        return None;
+    }
+    let name = text(code, operand);
+    let function = enclosing_declaration(operand)?;
+    let mut declarations = Vec::new();
+    for kind in ["parameter_declaration", "var_spec", "short_var_declaration"] {
+        collect_kind(function, kind, &mut declarations);
+    }
+    declarations.sort_by_key(|decl| decl.start_byte());
+    declarations
+        .into_iter()
+        .rev()
+        .filter(|decl| decl.start_byte() < operand.start_byte())
+        .find_map(|decl| {
+            if decl.kind() == "short_var_declaration" {
+                let left = decl.child_by_field_name("left")?;
+                let right = decl.child_by_field_name("right")?;
+                let mut cursor = left.walk();
+                let index = left
+                    .named_children(&mut cursor)
+                    .position(|ident| text(code, ident) == name)?;
+                let value = right.named_child(index)?;
+                let value = if value.kind() == "unary_expression" {
+                    value.child_by_field_name("operand")?
+                } else {
+                    value
+                };
+                let typ = match value.kind() {
+                    "composite_literal" => value.child_by_field_name("type")?,
+                    "call_expression"
+                        if value
+                            .child_by_field_name("function")
+                            .is_some_and(|function| text(code, function) == "new") =>
+                    {
+                        value.child_by_field_name("arguments")?.named_child(0)?
+                    }
+                    _ => return None,
+                };
+                return base_type_name(typ).map(|typ| text(code, typ));
+            }
+            let mut cursor = decl.walk();
+            let declares = decl
+                .children_by_field_name("name", &mut cursor)
+                .any(|ident| text(code, ident) == name);
+            if !declares {
+                return None;
+            }
+            base_type_name(decl.child_by_field_name("type")?).map(|typ| text(code, typ))
+        })
+}
+
+/// Graph id `file::scope::kind::name#index`. `scope` is the declared function around the entity
+/// (`main`, `Server.run`); an empty file or scope is left out. `index` counts the earlier
+/// entities with the same scope, kind and name, so ids survive edits elsewhere in the file.
+pub fn graph_id(file: &str, scope: &str, kind: &str, name: &str, index: usize) -> String {
+    let mut id = String::new();
+    for part in [file, scope] {
+        if !part.is_empty() {
+            id.push_str(part);
+            id.push_str("::");
+        }
+    }
+    format!("{}{}::{}#{}", id, kind, name, index)
+}
+
+/// The `kind` part of a [`graph_id`], such as `var`, `use`, `fn` or `go`.
+pub fn graph_id_kind(id: &str) -> Option<&str> {
+    id.rsplit("::").nth(1)
+}
+
+/// The `name` part of a [`graph_id`], without the index.
+pub fn graph_id_name(id: &str) -> &str {
+    let last = id.rsplit("::").next().unwrap_or(id);
+    last.rsplit_once('#').map_or(last, |(name, _)| name)
+}
+
+/// Hands out [`graph_id`]s, the same one each time a node is asked for. Declarations and
+/// goroutines are numbered up front in document order, so every graph of the file agrees on
+/// their ids; other entities are numbered in the order they are first asked for.
+pub(crate) struct GraphIds<'a> {
+    code: &'a str,
+    file: String,
+    assigned: HashMap<(String, usize), String>,
+    occurrences: HashMap<String, usize>,
+}
+
+impl<'a> GraphIds<'a> {
+    pub(crate) fn new(root: Node, code: &'a str, file: &str) -> Self {
+        let mut ids = Self {
+            code,
+            file: file.to_string(),
+            assigned: HashMap::new(),
+            occurrences: HashMap::new(),
+        };
+        let mut actors = Vec::new();
+        for kind in ["function_declaration", "method_declaration", "go_statement"] {
+            collect_kind(root, kind, &mut actors);
+        }
+        let mut calls = Vec::new();
+        collect_kind(root, "call_expression", &mut calls);
+        actors.extend(calls.into_iter().filter(|call| is_errgroup_go(*call, code)));
+        actors.sort_by_key(|actor| actor.start_byte());
+        for actor in actors {
+            ids.actor(actor);
+        }
+        ids
+    }
+
+    /// Id of `node` as the `kind` entity `name`.
+    pub(crate) fn id(&mut self, kind: &str, name: &str, node: Node) -> String {
+        let key = (format!("{}::{}", kind, name), node.id());
+        if let Some(id) = self.assigned.get(&key) {
+            return id.clone();
+        }
+        let scope = enclosing_declaration(node)
+            .map(|decl| declaration_label(decl, self.code))
+            .unwrap_or_default();
+        let occurrence = self
+            .occurrences
+            .entry(graph_id(&self.file, &scope, kind, name, 0))
+            .or_insert(0);
+        let id = graph_id(&self.file, &scope, kind, name, *occurrence);
+        *occurrence += 1;
+        self.assigned.insert(key, id.clone());
+        id
+    }
+
+    /// Id of something outside the file, such as a function of another package.
+    pub(crate) fn unscoped(&self, kind: &str, name: &str) -> String {
+        graph_id(&self.file, "", kind, name, 0)
+    }
+
+    /// Id of a function or method declaration, `go` statement or errgroup `Go` call.
+    pub(crate) fn actor(&mut self, actor: Node) -> String {
+        if matches!(actor.kind(), "function_declaration" | "method_declaration") {
+            let label = declaration_label(actor, self.code);
+            self.id("fn", &label, actor)
+        } else {
+            self.id("go", "goroutine", actor)
+        }
+    }
+}
+
+/// Label of the function or method declaration around `position`, as in its graph node.
+pub fn function_label_at(tree: &Tree, code: &str, position: Position) -> Option<String> {
+    let point = Point::new(position.line as usize, position.character as usize);
+    let node = tree
+        .root_node()
+        .named_descendant_for_point_range(point, point)?;
+    let declaration = if matches!(node.kind(), "function_declaration" | "method_declaration") {
+        node
+    } else {
+        enclosing_declaration(node)?
+    };
+    Some(declaration_label(declaration, code))
+}
+
+/// `name` of a function declaration, `Type.name` of a method.
+fn declaration_label(decl: Node, code: &str) -> String {
+    let name = decl
+        .child_by_field_name("name")
+        .map_or("", |name| text(code, name));
+    match decl
+        .child_by_field_name("receiver")
+        .and_then(receiver_type_name)
+    {
+        Some(receiver) => format!("{}.{}", text(code, receiver), name),
+        None => name.to_string(),
+    }
+}
+
+/// Links each mutex to the goroutines and functions acquiring it (`Acquires`) and to the
+/// variables used while it is held (`Guards`). A mutex declared as a variable of the file is
+/// its variable node; any other (`s.mu`, a parameter) gets a `SyncBlock` node of its own.
+/// Every `Lock()`…`Unlock()` region is a `SyncBlock` node too, with a `Sync` edge to its mutex.
+fn add_lock_layer(
+    tree: &Tree,
+    code: &str,
+    ids: &mut GraphIds,
+    var_decl_ids: &HashMap<String, String>,
+    nodes: &mut Vec<GraphNode>,
+    edges: &mut Vec<GraphEdge>,
+) {
+    let mut calls = Vec::new();
+    collect_kind(tree.root_node(), "call_expression", &mut calls);
+    let mut lock_ids: HashMap<String, String> = HashMap::new();
+    let mut lock_id = |mutex: &str, site: Node, ids: &mut GraphIds, nodes: &mut Vec<GraphNode>| {
+        if let Some(id) = lock_ids.get(mutex).or_else(|| var_decl_ids.get(mutex)) {
+            return id.clone();
+        }
+        let id = ids.unscoped("lock", mutex);
+        nodes.push(GraphNode {
+            id: id.clone(),
+            label: mutex.to_string(),
+            entity_type: GraphEntityType::SyncBlock,
+            range: crate::util::node_to_range(site),
+            extra: Some(json!({ "mutex": mutex })),
+            bytes: None,
+        });
+        lock_ids.insert(mutex.to_string(), id.clone());
+        id
+    };
+    let mut layer: Vec<GraphEdge> = Vec::new();
+    let mut push = |edge: GraphEdge| {
+        if !layer.contains(&edge) {
+            layer.push(edge);
+        }
+    };
+    for call in calls {
+        let Some((mutex, 1, _)) = lock_event(call, code) else {
+            continue;
+        };
+        let Some(actor) = enclosing_actor(call, code, ids) else {
+            continue;
+        };
+        let lock = lock_id(&mutex, call, ids, nodes);
+        push(GraphEdge {
+            from: actor,
+            to: lock.clone(),
+            edge_type: GraphEdgeType::Acquires,
+        });
+        if let Some((range, unlocked)) = critical_section(call, &mutex, code) {
+            let region = ids.id("critical", &mutex, call);
+            nodes.push(GraphNode {
+                id: region.clone(),
+                label: mutex.clone(),
+                entity_type: GraphEntityType::SyncBlock,
+                range,
+                extra: Some(
+                    json!({ "mutex": mutex, "critical_section": true, "unlocked": unlocked }),
+                ),
+                bytes: None,
+            });
+            push(GraphEdge {
+                from: region,
+                to: lock,
+                edge_type: GraphEdgeType::Sync,
+            });
+        }
+    }
+    let uses: HashMap<&str, Range> = nodes
+        .iter()
+        .filter(|node| graph_id_kind(&node.id) == Some("use"))
+        .map(|node| (node.id.as_str(), node.range))
+        .collect();
+    let mut guarded = Vec::new();
+    for edge in edges
+        .iter()
+        .filter(|edge| edge.edge_type == GraphEdgeType::Use)
+    {
+        let Some(range) = uses.get(edge.to.as_str()) else {
+            continue;
+        };
+        for mutex in held_mutexes(tree, *range, code) {
+            if graph_id_name(&edge.from) != mutex {
+                guarded.push((mutex, edge.from.clone(), *range));
+            }
+        }
+    }
+    for (mutex, variable, range) in guarded {
+        let point =
+            |position: Position| Point::new(position.line as usize, position.character as usize);
+        let site = tree
+            .root_node()
+            .named_descendant_for_point_range(point(range.start), point(range.end))
+            .unwrap_or(tree.root_node());
+        push(GraphEdge {
+            from: lock_id(&mutex, site, ids, nodes),
+            to: variable,
+            edge_type: GraphEdgeType::Guards,
+        });
+    }
+    edges.extend(layer);
+}
+
+/// The statements from the `Lock()` call statement `lock` through the matching `Unlock()` in
+/// the same block, or through the end of the block when the unlock is deferred or missing,
+/// and whether an unlock (deferred or not) was found.
+fn critical_section(lock: Node, mutex: &str, code: &str) -> Option<(Range, bool)> {
+    let statement = lock
+        .parent()
+        .filter(|parent| parent.kind() == "expression_statement")?;
+    let unlocks = |node: Node| {
+        let mut calls = Vec::new();
+        collect_kind(node, "call_expression", &mut calls);
+        calls
+            .into_iter()
+            .any(|call| matches!(lock_event(call, code), Some((key, -1, _)) if key == mutex))
+    };
+    let mut end = statement;
+    let mut unlocked = false;
+    let mut next = statement.next_named_sibling();
+    while let Some(sibling) = next {
+        end = sibling;
+        if sibling.kind() == "defer_statement" {
+            unlocked |= unlocks(sibling);
+        } else if sibling.kind() == "expression_statement" && unlocks(sibling) {
+            unlocked = true;
+            break;
+        }
+        next = sibling.next_named_sibling();
+    }
+    let start = node_to_range(statement).start;
+    Some((Range::new(start, node_to_range(end).end), unlocked))
+}
+
+/// Adds a `Struct` or `Interface` node for every struct and interface type declared in the
+/// file, `HasMethod` edges from a type to the methods declared on it, and `Implements` edges
+/// from a struct whose methods (pointer receivers included) cover an interface's. Interfaces
+/// without methods, or embedding one not declared in the file, get no `Implements` edges.
+fn add_type_layer(
+    tree: &Tree,
+    code: &str,
+    ids: &mut GraphIds,
+    nodes: &mut Vec<GraphNode>,
+    edges: &mut Vec<GraphEdge>,
+) {
+    let mut specs = Vec::new();
+    collect_kind(tree.root_node(), "type_spec", &mut specs);
+    let mut structs: Vec<(&str, String)> = Vec::new();
+    let mut interfaces: HashMap<&str, (String, Node)> = HashMap::new();
+    let mut interface_order = Vec::new();
+    for spec in specs {
+        let (Some(name), Some(typ)) = (
+            spec.child_by_field_name("name"),
+            spec.child_by_field_name("type"),
+        ) else {
+            continue;
+        };
+        let entity_type = match typ.kind() {
+            "struct_type" => GraphEntityType::Struct,
+            "interface_type" => GraphEntityType::Interface,
+            _ => continue,
+        };
+        let label = text(code, name);
+        let id = ids.id("type", label, spec);
+        let extra = (entity_type == GraphEntityType::Interface)
+            .then(|| json!({ "methods": interface_methods(typ, code) }));
+        nodes.push(GraphNode {
+            id: id.clone(),
+            label: label.to_string(),
+            entity_type: entity_type.clone(),
+            range: node_to_range(name),
+            extra,
+            bytes: None,
+        });
+        if entity_type == GraphEntityType::Struct {
+            structs.push((label, id));
+        } else {
+            interfaces.insert(label, (id, typ));
+            interface_order.push(label);
+        }
+    }
+
+    let mut methods = Vec::new();
+    collect_kind(tree.root_node(), "method_declaration", &mut methods);
+    let mut method_sets: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for method in methods {
+        let (Some(receiver), Some(name)) = (
+            method
+                .child_by_field_name("receiver")
+                .and_then(receiver_type_name),
+            method.child_by_field_name("name"),
+        ) else {
+            continue;
+        };
+        let receiver = text(code, receiver);
+        method_sets
+            .entry(receiver)
+            .or_default()
+            .insert(text(code, name));
+        let owner = structs
+            .iter()
+            .find(|(label, _)| *label == receiver)
+            .map(|(_, id)| id.clone());
+        if let Some(owner) = owner {
+            edges.push(GraphEdge {
+                from: owner,
+                to: ids.actor(method),
+                edge_type: GraphEdgeType::HasMethod,
+            });
+        }
+    }
+
+    for interface in interface_order {
+        let mut required = HashSet::new();
+        if !interface_method_set(interface, &interfaces, code, &mut required, &mut Vec::new())
+            || required.is_empty()
+        {
+            continue;
+        }
+        let Some((interface_id, _)) = interfaces.get(interface) else {
+            continue;
+        };
+        for (label, id) in &structs {
+            let implements = method_sets
+                .get(label)
+                .is_some_and(|set| required.iter().all(|method| set.contains(method)));
+            if implements {
+                edges.push(GraphEdge {
+                    from: id.clone(),
+                    to: interface_id.clone(),
+                    edge_type: GraphEdgeType::Implements,
+                });
+            }
+        }
+    }
+}
+
+/// Names of the methods an interface type lists itself.
+fn interface_methods<'a>(interface: Node, code: &'a str) -> Vec<&'a str> {
+    let mut cursor = interface.walk();
+    interface
+        .named_children(&mut cursor)
+        .filter(|child| child.kind() == "method_spec")
+        .filter_map(|spec| Some(text(code, spec.child_by_field_name("name")?)))
+        .collect()
+}
+
+/// Adds the methods of the interface `name` and the interfaces it embeds to `out`. False when
+/// it embeds a type not declared as an interface in the file, or a type union.
+fn interface_method_set<'a>(
+    name: &'a str,
+    interfaces: &HashMap<&'a str, (String, Node)>,
+    code: &'a str,
+    out: &mut HashSet<&'a str>,
+    visiting: &mut Vec<&'a str>,
+) -> bool {
+    if visiting.contains(&name) {
+        return true;
+    }
+    let Some((_, interface)) = interfaces.get(name) else {
+        return false;
+    };
+    visiting.push(name);
+    out.extend(interface_methods(*interface, code));
+    let mut cursor = interface.walk();
+    let embedded: Vec<Node> = interface
+        .named_children(&mut cursor)
+        .filter(|child| child.kind() != "method_spec")
+        .collect();
+    embedded.into_iter().all(|element| {
+        element.named_child_count() == 1
+            && element.named_child(0).is_some_and(|embedded| {
+                embedded.kind() == "type_identifier"
+                    && interface_method_set(text(code, embedded), interfaces, code, out, visiting)
+            })
+    })
+}
+
+/// Declarations in the other files of a workspace graph, by their graph ids.
+#[derive(Debug, Clone, Default)]
+pub struct ExternalSymbols {
+    /// Functions of the same package by name, and of imported packages as `pkg.Func`.
+    pub functions: HashMap<String, String>,
+    /// Package-level variables of the same package.
+    pub variables: HashMap<String, String>,
+}
+
 pub fn build_graph_data(tree: &Tree, code: &str) -> GraphData {
+    build_file_graph(tree, code, "", &ExternalSymbols::default())
+}
+
+/// The graph of one file of a larger graph: ids start with `file`, and calls and variable
+/// uses may resolve to the `external` declarations of other files.
+pub fn build_file_graph(
+    tree: &Tree,
+    code: &str,
+    file: &str,
+    external: &ExternalSymbols,
+) -> GraphData {
+    build_file_graph_within(tree, code, file, external, Deadline::NONE)
+}
+
+/// [`build_file_graph`] that stops at `deadline`, returning the nodes and edges found so far
+/// marked `truncated`.
+pub fn build_file_graph_within(
+    tree: &Tree,
+    code: &str,
+    file: &str,
+    external: &ExternalSymbols,
+    deadline: Deadline,
+) -> GraphData {
     let mut nodes = Vec::new();
     let mut edges = Vec::new();
     use std::collections::HashMap;
-    let mut var_decl_ids = HashMap::new();
-
-    fn make_id(kind: &str, name: &str, range: &Range) -> String {
-        format!(
-            "{}:{}:{}:{}:{}",
-            kind, name, range.start.line, range.start.character, range.end.character
-        )
-    }
+    let mut var_decl_ids = external.variables.clone();
 
-    fn traverse(
+    /// Nodes and edges contributed by `node` itself; [`walk_tree`] visits its children.
+    fn visit(
         node: Node,
         code: &str,
         nodes: &mut Vec<GraphNode>,
         edges: &mut Vec<GraphEdge>,
         var_decl_ids: &mut HashMap<String, String>,
+        symbols: &FunctionSymbols,
+        ids: &mut GraphIds,
     ) {
         match node.kind() {
             "var_spec" | "short_var_declaration" => {
@@ -1688,7 +3530,7 @@ pub fn build_graph_data(tree: &Tree, code: &str) -> GraphData {
                         if child.kind() == "identifier" {
                             let name = crate::analysis::text(code, child);
                             let range = crate::util::node_to_range(child);
-                            let id = make_id("var", name, &range);
+                            let id = ids.id("var", name, child);
                             var_decl_ids.insert(name.to_string(), id.clone());
                             let node_info = GraphNode {
                                 id: id.clone(),
@@ -1696,6 +3538,7 @@ pub fn build_graph_data(tree: &Tree, code: &str) -> GraphData {
                                 entity_type: GraphEntityType::Variable,
                                 range: range.clone(),
                                 extra: None,
+                                bytes: None,
                             };
                             nodes.push(node_info);
                         }
@@ -1706,56 +3549,108 @@ pub fn build_graph_data(tree: &Tree, code: &str) -> GraphData {
                 if let Some(ident) = node.child_by_field_name("name") {
                     let name = crate::analysis::text(code, ident);
                     let range = crate::util::node_to_range(ident);
-                    let id = make_id("fn", name, &range);
+                    let id = ids.actor(node);
+                    let type_parameters = type_parameter_names(node, code);
                     let node_info = GraphNode {
                         id: id.clone(),
                         label: name.to_string(),
                         entity_type: GraphEntityType::Function,
                         range: range.clone(),
-                        extra: None,
+                        extra: (!type_parameters.is_empty())
+                            .then(|| json!({"type_parameters": type_parameters})),
+                        bytes: None,
                     };
                     nodes.push(node_info);
                 }
             }
+            "method_declaration" => {
+                if let Some(ident) = node.child_by_field_name("name") {
+                    let name = crate::analysis::text(code, ident);
+                    let receiver = node
+                        .child_by_field_name("receiver")
+                        .and_then(receiver_type_name)
+                        .map(|t| crate::analysis::text(code, t))
+                        .unwrap_or("");
+                    let range = crate::util::node_to_range(ident);
+                    let id = ids.actor(node);
+                    let label = if receiver.is_empty() {
+                        name.to_string()
+                    } else {
+                        format!("{}.{}", receiver, name)
+                    };
+                    nodes.push(GraphNode {
+                        id,
+                        label,
+                        entity_type: GraphEntityType::Method,
+                        range,
+                        extra: Some(json!({"method": true, "receiver": receiver})),
+                        bytes: None,
+                    });
+                }
+            }
             "go_statement" => {
                 let range = crate::util::node_to_range(node);
-                let id = make_id("go", "goroutine", &range);
+                let id = ids.actor(node);
                 let node_info = GraphNode {
                     id: id.clone(),
                     label: "goroutine".to_string(),
                     entity_type: GraphEntityType::Goroutine,
                     range: range.clone(),
                     extra: None,
+                    bytes: None,
                 };
                 nodes.push(node_info);
             }
+            "call_expression" if is_errgroup_go(node, code) => {
+                let range = crate::util::node_to_range(node);
+                let group = node
+                    .child_by_field_name("function")
+                    .and_then(|function| function.child_by_field_name("operand"))
+                    .map(|operand| crate::analysis::text(code, operand))
+                    .unwrap_or("");
+                nodes.push(GraphNode {
+                    id: ids.actor(node),
+                    label: "goroutine".to_string(),
+                    entity_type: GraphEntityType::Goroutine,
+                    range,
+                    extra: Some(json!({"errgroup": group})),
+                    bytes: None,
+                });
+            }
             "channel_type" => {
                 let range = crate::util::node_to_range(node);
-                let id = make_id("chan", "channel", &range);
+                let id = ids.id("chan", "channel", node);
                 let node_info = GraphNode {
                     id: id.clone(),
                     label: "channel".to_string(),
                     entity_type: GraphEntityType::Channel,
                     range: range.clone(),
                     extra: None,
+                    bytes: None,
                 };
                 nodes.push(node_info);
             }
             _ => {}
         }
-        if node.kind() == "identifier" {
+        if node.kind() == "identifier" && !is_type_parameter(node) {
             let name = crate::analysis::text(code, node);
             let range = crate::util::node_to_range(node);
             if let Some(parent) = node.parent() {
+                let shadowed = || {
+                    symbols.shared_variables.contains(name)
+                        && enclosing_declaration(node)
+                            .is_some_and(|function| local_names(function, code).contains(name))
+                };
                 if parent.kind() != "var_spec" && parent.kind() != "short_var_declaration" {
-                    if let Some(decl_id) = var_decl_ids.get(name) {
-                        let use_id = make_id("use", name, &range);
+                    if let Some(decl_id) = var_decl_ids.get(name).filter(|_| !shadowed()) {
+                        let use_id = ids.id("use", name, node);
                         nodes.push(GraphNode {
                             id: use_id.clone(),
                             label: name.to_string(),
                             entity_type: GraphEntityType::Variable,
                             range: range.clone(),
                             extra: Some(json!({"use": true})),
+                            bytes: None,
                         });
                         edges.push(GraphEdge {
                             from: decl_id.clone(),
@@ -1769,18 +3664,25 @@ pub fn build_graph_data(tree: &Tree, code: &str) -> GraphData {
         if node.kind() == "call_expression" {
             if let Some(func_node) = node.child_by_field_name("function") {
                 let func_name = crate::analysis::text(code, func_node);
-                let range = crate::util::node_to_range(func_node);
-                let to_id = make_id("fn", func_name, &range);
-                let from_id = make_id("callsite", func_name, &crate::util::node_to_range(node));
+                let to_id = symbols
+                    .resolve(node, code)
+                    .map(str::to_string)
+                    .unwrap_or_else(|| ids.unscoped("fn", func_name));
+                let from_id = enclosing_actor(node, code, ids)
+                    .unwrap_or_else(|| ids.id("callsite", func_name, node));
                 edges.push(GraphEdge {
                     from: from_id,
                     to: to_id,
                     edge_type: GraphEdgeType::Call,
                 });
             }
-            if is_mutex_call(node, code) || is_atomic_call(node, code) {
-                let sync_id = make_id("sync", "sync", &crate::util::node_to_range(node));
-                let from_id = make_id("callsite", "sync", &crate::util::node_to_range(node));
+            if is_mutex_call(node, code)
+                || is_atomic_call(node, code)
+                || is_sync_map_call(node, code)
+                || crate::locks::is_cond_signal(node, code)
+            {
+                let sync_id = ids.id("sync", "sync", node);
+                let from_id = ids.id("callsite", "sync", node);
                 edges.push(GraphEdge {
                     from: from_id,
                     to: sync_id,
@@ -1791,9 +3693,8 @@ pub fn build_graph_data(tree: &Tree, code: &str) -> GraphData {
         if node.kind() == "send_statement" {
             if let Some(chan_node) = node.child_by_field_name("channel") {
                 let chan_name = crate::analysis::text(code, chan_node);
-                let range = crate::util::node_to_range(chan_node);
-                let to_id = make_id("chan", chan_name, &range);
-                let from_id = make_id("send", chan_name, &crate::util::node_to_range(node));
+                let to_id = ids.id("chan", chan_name, chan_node);
+                let from_id = ids.id("send", chan_name, node);
                 edges.push(GraphEdge {
                     from: from_id,
                     to: to_id,
@@ -1803,11 +3704,10 @@ pub fn build_graph_data(tree: &Tree, code: &str) -> GraphData {
         }
         if node.kind() == "unary_expression" && crate::analysis::text(code, node).starts_with("<-")
         {
-            if let Some(chan_node) = node.child(0) {
+            if let Some(chan_node) = node.child_by_field_name("operand") {
                 let chan_name = crate::analysis::text(code, chan_node);
-                let range = crate::util::node_to_range(chan_node);
-                let to_id = make_id("chan", chan_name, &range);
-                let from_id = make_id("recv", chan_name, &crate::util::node_to_range(node));
+                let to_id = ids.id("chan", chan_name, chan_node);
+                let from_id = ids.id("recv", chan_name, node);
                 edges.push(GraphEdge {
                     from: from_id,
                     to: to_id,
@@ -1815,32 +3715,148 @@ pub fn build_graph_data(tree: &Tree, code: &str) -> GraphData {
                 });
             }
         }
-        if node.kind() == "go_statement" {
-            let range = crate::util::node_to_range(node);
-            let from_id = make_id("spawnsite", "go", &range);
-            let to_id = make_id("go", "goroutine", &range);
+        if node.kind() == "select_statement" {
+            let id = ids.id("select", "select", node);
+            let mut cursor = node.walk();
+            let cases: Vec<Node> = node.named_children(&mut cursor).collect();
+            let mut channels = Vec::new();
+            for case in &cases {
+                let Some(communication) = case.child_by_field_name("communication") else {
+                    continue;
+                };
+                if communication.kind() == "send_statement" {
+                    if let Some(chan_node) = communication.child_by_field_name("channel") {
+                        channels.push((chan_node, GraphEdgeType::Send));
+                    }
+                    continue;
+                }
+                let mut receives = Vec::new();
+                collect_kind(communication, "unary_expression", &mut receives);
+                if let Some(chan_node) = receives
+                    .into_iter()
+                    .find(|unary| text(code, *unary).starts_with("<-"))
+                    .and_then(|unary| unary.child_by_field_name("operand"))
+                {
+                    channels.push((chan_node, GraphEdgeType::Receive));
+                }
+            }
+            nodes.push(GraphNode {
+                id: id.clone(),
+                label: "select".to_string(),
+                entity_type: GraphEntityType::Select,
+                range: crate::util::node_to_range(node),
+                extra: Some(json!({
+                    "cases": cases.iter().filter(|case| case.kind() == "communication_case").count(),
+                    "default": cases.iter().any(|case| case.kind() == "default_case"),
+                })),
+                bytes: None,
+            });
+            for (chan_node, edge_type) in channels {
+                let chan_name = crate::analysis::text(code, chan_node);
+                edges.push(GraphEdge {
+                    from: id.clone(),
+                    to: ids.id("chan", chan_name, chan_node),
+                    edge_type,
+                });
+            }
+        }
+        if node.kind() == "go_statement"
+            || (node.kind() == "call_expression" && is_errgroup_go(node, code))
+        {
+            let from_id =
+                enclosing_actor(node, code, ids).unwrap_or_else(|| ids.id("spawnsite", "go", node));
+            let to_id = ids.actor(node);
             edges.push(GraphEdge {
                 from: from_id,
                 to: to_id,
                 edge_type: GraphEdgeType::Spawn,
             });
         }
-        let mut cursor = node.walk();
-        if cursor.goto_first_child() {
-            loop {
-                traverse(cursor.node(), code, nodes, edges, var_decl_ids);
-                if !cursor.goto_next_sibling() {
-                    break;
-                }
+    }
+    let mut ids = GraphIds::new(tree.root_node(), code, file);
+    let symbols = FunctionSymbols::collect(tree.root_node(), code, &mut ids, external);
+    walk_tree(tree.root_node(), |step| {
+        if let Visit::Enter(node) = step {
+            if deadline.expired() {
+                return false;
+            }
+            visit(
+                node,
+                code,
+                &mut nodes,
+                &mut edges,
+                &mut var_decl_ids,
+                &symbols,
+                &mut ids,
+            );
+        }
+        true
+    });
+    if deadline.expired() {
+        return finish_graph(code, nodes, edges, true);
+    }
+    add_lock_layer(tree, code, &mut ids, &var_decl_ids, &mut nodes, &mut edges);
+    add_type_layer(tree, code, &mut ids, &mut nodes, &mut edges);
+    for issue in crate::waitgroup::analyze_waitgroups(tree, code).unwrap_or_default() {
+        let point =
+            |position: Position| Point::new(position.line as usize, position.character as usize);
+        let site = tree
+            .root_node()
+            .named_descendant_for_point_range(point(issue.range.start), point(issue.range.end))
+            .unwrap_or(tree.root_node());
+        let id = ids.id("sync", &issue.waitgroup, site);
+        nodes.push(GraphNode {
+            id,
+            label: issue.waitgroup.clone(),
+            entity_type: GraphEntityType::SyncBlock,
+            range: issue.range,
+            extra: Some(json!({
+                "waitgroup": issue.waitgroup,
+                "issue": issue.code(),
+                "message": issue.message(),
+            })),
+            bytes: None,
+        });
+    }
+    for decl in sync_map_declarations(tree.root_node(), code) {
+        let name = crate::analysis::text(code, decl);
+        let range = crate::util::node_to_range(decl);
+        nodes.push(GraphNode {
+            id: ids.id("syncmap", name, decl),
+            label: name.to_string(),
+            entity_type: GraphEntityType::SyncMap,
+            range,
+            extra: None,
+            bytes: None,
+        });
+    }
+    let mut truncated = false;
+    for node in nodes.iter_mut() {
+        if graph_id_kind(&node.id) == Some("var") {
+            if deadline.expired() {
+                truncated = true;
+                break;
             }
+            node.extra = race_annotation(tree, code, node.range.start);
         }
     }
-    traverse(
-        tree.root_node(),
-        code,
-        &mut nodes,
-        &mut edges,
-        &mut var_decl_ids,
-    );
-    GraphData { nodes, edges }
+    finish_graph(code, nodes, edges, truncated)
+}
+
+/// The graph of `nodes` and `edges`, with the byte range of every node.
+fn finish_graph(
+    code: &str,
+    mut nodes: Vec<GraphNode>,
+    edges: Vec<GraphEdge>,
+    truncated: bool,
+) -> GraphData {
+    let lines = LineIndex::new(code);
+    for node in nodes.iter_mut() {
+        node.bytes = Some(lines.bytes(node.range));
+    }
+    GraphData {
+        nodes,
+        edges,
+        truncated,
+    }
 }
@@ -0,0 +1,240 @@
+use ropey::Rope;
+use tower_lsp::lsp_types::{Diagnostic, Position, Range, TextDocumentContentChangeEvent, Url};
+use tree_sitter::{InputEdit, Point, Tree};
+
+use crate::types::{ByteRange, Decoration};
+
+/// One applied edit in server coordinates (byte columns, as in tree-sitter points): the replaced
+/// range of the old text and where the inserted text ends in the new one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EditDelta {
+    pub old: Range,
+    pub new_end: Position,
+    pub old_bytes: ByteRange,
+    pub new_end_byte: usize,
+}
+
+/// Applies a `didChange` content change to `text`. Returns the edit's delta, or `None` for a
+/// full-text replacement, after which earlier results cannot be re-anchored.
+pub fn apply_content_change(
+    text: &mut Rope,
+    change: &TextDocumentContentChangeEvent,
+) -> Option<EditDelta> {
+    let Some(range) = change.range else {
+        *text = Rope::from_str(&change.text);
+        return None;
+    };
+    let start_byte = utf16_offset(text, range.start);
+    let end_byte = utf16_offset(text, range.end).max(start_byte);
+    let start = byte_position(text, start_byte);
+    let old_end = byte_position(text, end_byte);
+    let start_char = text.byte_to_char(start_byte);
+    text.remove(start_char..text.byte_to_char(end_byte));
+    text.insert(start_char, &change.text);
+    let new_end = match change.text.rfind('\n') {
+        Some(last) => Position::new(
+            start.line + change.text.matches('\n').count() as u32,
+            (change.text.len() - last - 1) as u32,
+        ),
+        None => Position::new(start.line, start.character + change.text.len() as u32),
+    };
+    Some(EditDelta {
+        old: Range::new(start, old_end),
+        new_end,
+        old_bytes: ByteRange {
+            start_byte,
+            end_byte,
+        },
+        new_end_byte: start_byte + change.text.len(),
+    })
+}
+
+/// Byte offset of an LSP position, whose character counts UTF-16 code units.
+fn utf16_offset(text: &Rope, position: Position) -> usize {
+    let line = position.line as usize;
+    if line >= text.len_lines() {
+        return text.len_bytes();
+    }
+    let mut offset = text.line_to_byte(line);
+    let mut units = 0;
+    for ch in text.line(line).chars() {
+        if ch == '\n' || units >= position.character as usize {
+            break;
+        }
+        units += ch.len_utf16();
+        offset += ch.len_utf8();
+    }
+    offset
+}
+
+/// An LSP position whose character counts UTF-16 code units, in byte columns.
+pub fn from_utf16(text: &Rope, position: Position) -> Position {
+    byte_position(text, utf16_offset(text, position))
+}
+
+/// A byte-column position with its character in UTF-16 code units, for peers that count those.
+pub fn to_utf16(text: &Rope, position: Position) -> Position {
+    let line = position.line as usize;
+    if line >= text.len_lines() {
+        return position;
+    }
+    let line_start = text.line_to_byte(line);
+    let line_end = line_start + text.line(line).len_bytes();
+    let offset = (line_start + position.character as usize).min(line_end);
+    let units = text.char_to_utf16_cu(text.byte_to_char(offset))
+        - text.char_to_utf16_cu(text.line_to_char(line));
+    Position::new(position.line, units as u32)
+}
+
+fn byte_position(text: &Rope, offset: usize) -> Position {
+    let line = text.byte_to_line(offset);
+    Position::new(line as u32, (offset - text.line_to_byte(line)) as u32)
+}
+
+impl EditDelta {
+    fn shift_position(&self, position: Position) -> Position {
+        if position.line != self.old.end.line {
+            let line = position.line as i64 + self.new_end.line as i64 - self.old.end.line as i64;
+            return Position::new(line as u32, position.character);
+        }
+        let character = position.character as i64 + self.new_end.character as i64
+            - self.old.end.character as i64;
+        Position::new(self.new_end.line, character as u32)
+    }
+
+    /// `range` after this edit; `None` when the edit touches it.
+    pub fn shift_range(&self, range: Range) -> Option<Range> {
+        if range.end <= self.old.start {
+            return Some(range);
+        }
+        if range.start >= self.old.end {
+            return Some(Range::new(
+                self.shift_position(range.start),
+                self.shift_position(range.end),
+            ));
+        }
+        None
+    }
+
+    pub fn shift_bytes(&self, bytes: ByteRange) -> ByteRange {
+        if bytes.end_byte <= self.old_bytes.start_byte {
+            return bytes;
+        }
+        let shift = |offset: usize| offset + self.new_end_byte - self.old_bytes.end_byte;
+        ByteRange {
+            start_byte: shift(bytes.start_byte),
+            end_byte: shift(bytes.end_byte),
+        }
+    }
+
+    /// This edit as tree-sitter sees it, for [`Tree::edit`].
+    pub fn input_edit(&self) -> InputEdit {
+        let point = |position: Position| Point {
+            row: position.line as usize,
+            column: position.character as usize,
+        };
+        InputEdit {
+            start_byte: self.old_bytes.start_byte,
+            old_end_byte: self.old_bytes.end_byte,
+            new_end_byte: self.new_end_byte,
+            start_position: point(self.old.start),
+            old_end_position: point(self.old.end),
+            new_end_position: point(self.new_end),
+        }
+    }
+
+    pub fn shift_cursor(&self, position: Position) -> Position {
+        if position < self.old.start {
+            position
+        } else if position <= self.old.end {
+            self.new_end
+        } else {
+            self.shift_position(position)
+        }
+    }
+}
+
+/// Applies `deltas` to `tree` in order, so parsing the edited text with it as the old tree
+/// reuses every subtree the edits left alone.
+pub fn edit_tree(tree: &mut Tree, deltas: &[EditDelta]) {
+    for delta in deltas {
+        tree.edit(&delta.input_edit());
+    }
+}
+
+/// Moves decorations past `deltas`, dropping those an edit touched, and marks the rest stale.
+pub fn reanchor_decorations(decorations: &mut Vec<Decoration>, deltas: &[EditDelta]) {
+    decorations.retain_mut(|decoration| {
+        for delta in deltas {
+            let Some(range) = delta.shift_range(decoration.range) else {
+                return false;
+            };
+            decoration.range = range;
+            decoration.bytes = decoration.bytes.map(|bytes| delta.shift_bytes(bytes));
+        }
+        decoration.stale = true;
+        true
+    });
+}
+
+/// Like [`reanchor_decorations`] for the diagnostics of the edited document `uri`; the byte
+/// offsets and the stale mark live in `data`. Related locations in other files stay as they are.
+pub fn reanchor_diagnostics(uri: &Url, diagnostics: &mut Vec<Diagnostic>, deltas: &[EditDelta]) {
+    diagnostics.retain_mut(|diagnostic| {
+        let mut bytes = diagnostic
+            .data
+            .as_ref()
+            .and_then(|data| serde_json::from_value::<ByteRange>(data.clone()).ok());
+        for delta in deltas {
+            let Some(range) = delta.shift_range(diagnostic.range) else {
+                return false;
+            };
+            diagnostic.range = range;
+            bytes = bytes.map(|bytes| delta.shift_bytes(bytes));
+            if let Some(related) = diagnostic.related_information.as_mut() {
+                related.retain_mut(|info| {
+                    if info.location.uri != *uri {
+                        return true;
+                    }
+                    match delta.shift_range(info.location.range) {
+                        Some(range) => {
+                            info.location.range = range;
+                            true
+                        }
+                        None => false,
+                    }
+                });
+            }
+        }
+        store_anchor(diagnostic, bytes, true);
+        true
+    });
+}
+
+/// Records the byte offsets of `diagnostic` and whether it is stale in its `data` object, next
+/// to whatever else the producer put there, such as what its code actions need. `data` that is
+/// not an object is the producer's own and is left alone.
+pub fn store_anchor(diagnostic: &mut Diagnostic, bytes: Option<ByteRange>, stale: bool) {
+    let data = diagnostic.data.get_or_insert_with(|| serde_json::json!({}));
+    if data.is_null() {
+        *data = serde_json::json!({});
+    }
+    let Some(object) = data.as_object_mut() else {
+        return;
+    };
+    match bytes {
+        Some(bytes) => {
+            object.insert("start_byte".to_string(), bytes.start_byte.into());
+            object.insert("end_byte".to_string(), bytes.end_byte.into());
+        }
+        None => {
+            object.remove("start_byte");
+            object.remove("end_byte");
+        }
+    }
+    if stale {
+        object.insert("stale".to_string(), serde_json::Value::Bool(true));
+    } else {
+        object.remove("stale");
+    }
+}
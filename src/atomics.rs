@@ -0,0 +1,269 @@
+use tower_lsp::lsp_types::Range;
+use tree_sitter::{Node, Tree};
+
+use crate::analysis::{
+    check_tree, enclosing_declaration, is_atomic_call, is_in_goroutine_or_group, text,
+    typed_atomic_receiver,
+};
+use crate::callgraph::{collect_kind, local_names};
+use crate::error::AnalysisResult;
+use crate::util::node_to_range;
+
+/// A variable updated through a `sync/atomic` function such as `atomic.AddInt64(&x, 1)` and
+/// accessed without one elsewhere. Atomic operations only synchronize with other atomic
+/// operations, so the plain access still races with the atomic one.
+#[derive(Debug, Clone)]
+pub struct MixedAtomicAccess {
+    pub variable: String,
+    /// The plain read or write.
+    pub plain: Range,
+    pub plain_is_write: bool,
+    /// The `atomic.*` call taking the variable's address.
+    pub atomic: Range,
+}
+
+impl MixedAtomicAccess {
+    pub fn message(&self) -> String {
+        format!(
+            "`{}` is {} without sync/atomic here but accessed atomically elsewhere; the atomic operation does not make this access safe",
+            self.variable,
+            if self.plain_is_write { "written" } else { "read" }
+        )
+    }
+}
+
+/// Two `Store` calls on the same `atomic.Value` in one function storing values of different
+/// concrete types. `atomic.Value` panics when a store's type differs from the first one's.
+#[derive(Debug, Clone)]
+pub struct InconsistentAtomicStore {
+    pub variable: String,
+    /// The value stored with the second type.
+    pub range: Range,
+    pub stored_type: String,
+    /// The earlier `Store` call's value.
+    pub first: Range,
+    pub first_type: String,
+}
+
+impl InconsistentAtomicStore {
+    pub fn message(&self) -> String {
+        format!(
+            "`{}.Store` stores a `{}` here but a `{}` earlier; atomic.Value panics when a stored value's type differs from the first one's",
+            self.variable, self.stored_type, self.first_type
+        )
+    }
+}
+
+/// `Store` calls on variables and fields declared as `atomic.Value`, compared within each
+/// function. Only values whose type is evident from the syntax are compared: literals, composite
+/// literals, their addresses and `new(T)`.
+pub fn find_inconsistent_atomic_stores(
+    tree: &Tree,
+    code: &str,
+) -> AnalysisResult<Vec<InconsistentAtomicStore>> {
+    check_tree(tree, code)?;
+    let mut calls = Vec::new();
+    collect_kind(tree.root_node(), "call_expression", &mut calls);
+    let mut first_stores: Vec<(Option<Node>, &str, Node, String)> = Vec::new();
+    let mut findings = Vec::new();
+    for call in calls {
+        let Some(function) = call.child_by_field_name("function") else {
+            continue;
+        };
+        if function.kind() != "selector_expression"
+            || function
+                .child_by_field_name("field")
+                .is_none_or(|field| text(code, field) != "Store")
+            || typed_atomic_receiver(call, code) != Some("Value")
+        {
+            continue;
+        }
+        let (Some(receiver), Some(value)) = (
+            function.child_by_field_name("operand"),
+            call.child_by_field_name("arguments")
+                .and_then(|arguments| arguments.named_child(0)),
+        ) else {
+            continue;
+        };
+        let Some(stored_type) = evident_type(value, code) else {
+            continue;
+        };
+        let scope = enclosing_declaration(call);
+        let variable = text(code, receiver);
+        match first_stores
+            .iter()
+            .find(|(s, v, _, _)| *s == scope && *v == variable)
+        {
+            Some((_, _, first, first_type)) if *first_type != stored_type => {
+                findings.push(InconsistentAtomicStore {
+                    variable: variable.to_string(),
+                    range: node_to_range(value),
+                    stored_type,
+                    first: node_to_range(*first),
+                    first_type: first_type.clone(),
+                });
+            }
+            Some(_) => {}
+            None => first_stores.push((scope, variable, value, stored_type)),
+        }
+    }
+    Ok(findings)
+}
+
+/// The type of a value when the syntax alone gives it.
+fn evident_type(value: Node, code: &str) -> Option<String> {
+    match value.kind() {
+        "interpreted_string_literal" | "raw_string_literal" => Some("string".to_string()),
+        "int_literal" => Some("int".to_string()),
+        "float_literal" => Some("float64".to_string()),
+        "rune_literal" => Some("rune".to_string()),
+        "true" | "false" => Some("bool".to_string()),
+        "composite_literal" => value
+            .child_by_field_name("type")
+            .map(|typ| text(code, typ).to_string()),
+        "unary_expression" if text(code, value).starts_with('&') => value
+            .child_by_field_name("operand")
+            .filter(|operand| operand.kind() == "composite_literal")
+            .and_then(|operand| evident_type(operand, code))
+            .map(|typ| format!("*{}", typ)),
+        "call_expression" => {
+            let function = value.child_by_field_name("function")?;
+            if text(code, function) != "new" {
+                return None;
+            }
+            let argument = value.child_by_field_name("arguments")?.named_child(0)?;
+            Some(format!("*{}", text(code, argument)))
+        }
+        _ => None,
+    }
+}
+
+/// Plain accesses of variables that are also passed by address to `atomic.*` functions. A pair
+/// is reported when either side runs in a goroutine; plain reads only when they do themselves,
+/// since reads after the goroutines are joined are the common, safe case.
+pub fn find_mixed_atomic_accesses(
+    tree: &Tree,
+    code: &str,
+) -> AnalysisResult<Vec<MixedAtomicAccess>> {
+    check_tree(tree, code)?;
+    let root = tree.root_node();
+    let mut calls = Vec::new();
+    collect_kind(root, "call_expression", &mut calls);
+    let atomic_sites: Vec<(&str, Node)> = calls
+        .into_iter()
+        .filter(|call| is_atomic_call(*call, code))
+        .filter_map(|call| Some((text(code, atomic_operand(call)?), call)))
+        .collect();
+    if atomic_sites.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut candidates = Vec::new();
+    collect_kind(root, "identifier", &mut candidates);
+    collect_kind(root, "selector_expression", &mut candidates);
+    candidates.sort_by_key(|node| node.start_byte());
+
+    let mut findings = Vec::new();
+    for node in candidates {
+        let name = text(code, node);
+        if is_declaration(node) || is_atomic_operand(node, code) {
+            continue;
+        }
+        let Some((_, call)) = atomic_sites
+            .iter()
+            .find(|(variable, call)| *variable == name && same_variable(node, *call, name, code))
+        else {
+            continue;
+        };
+        let plain = node_to_range(node);
+        let plain_in_goroutine = is_in_goroutine_or_group(tree, plain, code);
+        let plain_is_write = is_write(node);
+        let concurrent = plain_in_goroutine
+            || (plain_is_write && is_in_goroutine_or_group(tree, node_to_range(*call), code));
+        if !concurrent {
+            continue;
+        }
+        findings.push(MixedAtomicAccess {
+            variable: name.to_string(),
+            plain,
+            plain_is_write,
+            atomic: node_to_range(*call),
+        });
+    }
+    Ok(findings)
+}
+
+/// `x` in `atomic.AddInt64(&x, 1)`.
+pub fn atomic_operand(call: Node) -> Option<Node> {
+    let first = call.child_by_field_name("arguments")?.named_child(0)?;
+    if first.kind() != "unary_expression" {
+        return None;
+    }
+    let operator = first.child_by_field_name("operator")?;
+    if operator.kind() != "&" {
+        return None;
+    }
+    first
+        .child_by_field_name("operand")
+        .filter(|operand| matches!(operand.kind(), "identifier" | "selector_expression"))
+}
+
+/// `name` is the target of an assignment or `++`/`--` statement inside `scope`.
+pub fn is_written_in(scope: Node, name: &str, code: &str) -> bool {
+    let mut targets = Vec::new();
+    collect_kind(scope, "identifier", &mut targets);
+    collect_kind(scope, "selector_expression", &mut targets);
+    targets
+        .into_iter()
+        .any(|target| text(code, target) == name && is_write(target))
+}
+
+fn is_atomic_operand(node: Node, code: &str) -> bool {
+    let call = node
+        .parent()
+        .and_then(|unary| unary.parent())
+        .and_then(|arguments| arguments.parent());
+    call.is_some_and(|call| {
+        call.kind() == "call_expression"
+            && is_atomic_call(call, code)
+            && atomic_operand(call) == Some(node)
+    })
+}
+
+pub(crate) fn is_write(node: Node) -> bool {
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+    match parent.kind() {
+        "inc_statement" | "dec_statement" => true,
+        "expression_list" => parent.parent().is_some_and(|statement| {
+            statement.kind() == "assignment_statement"
+                && statement.child_by_field_name("left") == Some(parent)
+        }),
+        _ => false,
+    }
+}
+
+fn is_declaration(node: Node) -> bool {
+    match node.parent().map(|parent| parent.kind()) {
+        Some("var_spec" | "const_spec" | "parameter_declaration") => true,
+        Some("expression_list") => node.parent().is_some_and(|list| {
+            list.parent().is_some_and(|decl| {
+                decl.kind() == "short_var_declaration"
+                    && decl.child_by_field_name("left") == Some(list)
+            })
+        }),
+        _ => false,
+    }
+}
+
+/// Plain identifiers in different functions only name the same variable when neither function
+/// declares it locally. Selectors such as `c.count` are matched by text across methods.
+fn same_variable(plain: Node, atomic: Node, name: &str, code: &str) -> bool {
+    if name.contains('.') {
+        return true;
+    }
+    let (Some(a), Some(b)) = (enclosing_declaration(plain), enclosing_declaration(atomic)) else {
+        return true;
+    };
+    a == b || !(local_names(a, code).contains(name) || local_names(b, code).contains(name))
+}
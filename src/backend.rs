@@ -1,15 +1,90 @@
+use crate::aliases::alias_accesses;
 use crate::analysis::{
-    access_context_key, build_graph_data, count_entities, detect_retention_pattern,
-    determine_race_severity, field_type_kind_at_declaration, find_variable_at_position,
-    find_variable_at_position_enhanced, is_access_in_atomic_context, is_access_synchronized_at,
-    is_heavy_work_in_call_context, is_in_goroutine, is_struct_field_declaration,
-    is_value_copy_context, FieldTypeKind,
+    access_context_key, analyze_goroutine_usage, build_file_graph, build_file_graph_within,
+    count_entities, detect_retention_pattern, determine_race_severity,
+    field_type_kind_at_declaration, find_variable_at_position, find_variable_at_position_enhanced,
+    function_label_at, guarding_mutex, infer_variable_type, is_access_in_atomic_context,
+    is_access_synchronized_at, is_heavy_work_in_call_context, is_in_goroutine_or_group,
+    is_joined_by_group_wait, is_published_before_spawn, is_published_by_once,
+    is_struct_field_declaration, is_value_copy_context, is_variable_captured,
+    is_variable_reassignment, variable_race_severity, FieldTypeKind,
 };
-use crate::semantic::{resolve_semantic_variable, SemanticConfig};
+use crate::anchor::{reanchor_diagnostics, store_anchor, EditDelta};
+use crate::atomics::{
+    find_inconsistent_atomic_stores, find_mixed_atomic_accesses, InconsistentAtomicStore,
+    MixedAtomicAccess,
+};
+use crate::bootstrap::bootstrap_helper;
+use crate::budget::{AnalysisBudget, Deadline};
+use crate::callgraph::{find_interprocedural_races, InterproceduralRace};
+use crate::channels::{
+    channel_flow, find_busy_wait_selects, find_channel_deadlocks, find_close_responsibility_issues,
+    find_double_closes, find_goroutine_leaks, find_nil_channel_ops, find_sends_after_close,
+    BusyWaitSelect, ChannelDeadlock, CloseByProducer, DoubleClose, GoroutineLeak, NilChannelOp,
+    SendAfterClose, UnclosedRange,
+};
+use crate::compile::{
+    build_args, compiler_diagnostics, is_test_file, parse_build_output, run_go_build,
+    BuildOnSaveConfig, PackageBuilds, COMPILE_ERROR,
+};
+use crate::diskcache::{cache_key, AnalysisCache, AnalysisCacheConfig, FileArtifacts};
+use crate::doctor::run_doctor;
+use crate::documents::{parse_sources, CacheLimits, DocumentStore};
+use crate::error::AnalysisResult;
+use crate::findings::{
+    DecorationPushConfig, DecorationStore, DecorationsDeltaNotification, DecorationsNotification,
+    DecorationsParams,
+};
+use crate::globals::{find_global_races, GlobalRace};
+use crate::gopls::{GoplsClient, GoplsConfig};
+use crate::goversion::{file_go_version, module_go_version, GoVersion};
+use crate::graphdelta::{GraphDeltaNotification, GraphStore};
+use crate::graphpage::{GraphPageCache, GraphPageParams, DEFAULT_PAGE_SIZE};
+use crate::hover::{render_hover, HoverInput};
+use crate::joins::{find_unjoined_goroutines, Escape, UnjoinedGoroutine};
+use crate::locks::{
+    find_cond_misuses, find_double_locks, find_lock_copies, find_lock_order_cycles,
+    find_lock_value_returns, find_unlock_issues, CondMisuse, DoubleLock, LockCopy, LockOrderCycle,
+    LockValueReturn, UnlockIssue,
+};
+use crate::loopvar::{find_loop_variable_captures, LoopVariableCapture};
+use crate::maps::{find_concurrent_map_accesses, ConcurrentMapAccess};
+use crate::mermaid::{graph_to_mermaid, MermaidParams};
+use crate::panics::{find_goroutine_panics, GoroutinePanic};
+use crate::perf::{DocumentSample, PerfConfig, PerfNotification};
+use crate::protocol::protocol_schema;
+use crate::query::{
+    function_subgraph, has_concurrency, run_query, FunctionGraphParams, GraphQueryParams,
+};
+use crate::queue::AnalysisQueue;
+use crate::racedetector::{
+    output_tail, parse_race_output, race_diagnostics, run_go_test_race, RaceDetectorConfig,
+    RaceDetectorParams, RaceDetectorReport, CONFIRMED_RACE,
+};
+use crate::record::{document_hash, now_ms, RecordConfig, RecordEntry, RecordWriter};
+use crate::rule_api::{RuleInput, RuleRegistry};
+use crate::rules::RuleDocsConfig;
+use crate::semantic::{SemanticCoalescer, SemanticConfig};
+use crate::sleeps::{find_sleep_synchronization, SleepSynchronization};
+use crate::spawns::{find_unbounded_spawns, UnboundedSpawn};
+use crate::stats::{AnalysisTimings, CacheStats, ServerStats};
+use crate::summary::{
+    collect_go_files, summarize, synchronized_goroutines, FileReport, SummarySort,
+    WorkspaceSummary, WorkspaceSummaryParams,
+};
+use crate::sync_config::sync_config;
+use crate::timers::{find_time_after_in_loops, TimeAfterInLoop};
+use crate::trace::RequestContext;
 use crate::types::{
-    Decoration, DecorationDiagnostic, DecorationDiagnosticSeverity, DecorationType,
-    ProgressNotification, RaceSeverity,
+    CursorCommandParams, Decoration, DecorationDiagnostic, DecorationDiagnosticSeverity,
+    DecorationType, GraphData, GraphEntityType, IndexingStatusNotification, IndexingStatusParams,
+    ParseInfoNotification, ParseInfoParams, ProgressNotification, ProgressParams, RaceSeverity,
+    VariableInfo,
 };
+use crate::util::LineIndex;
+use crate::waitgroup::{analyze_waitgroups, WaitGroupIssue};
+use crate::workers::WorkerPool;
+use crate::workspace::{build_workspace_graph_within, GraphParams, GraphScope, SourceFile};
 
 fn decoration_label(kind: &DecorationType) -> &'static str {
     match kind {
@@ -35,45 +110,18 @@ fn decoration_color_key(kind: &DecorationType) -> &'static str {
     }
 }
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Instant;
-use std::time::{Duration, SystemTime};
 use tokio::sync::Mutex;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer};
+use tracing::Instrument;
 use tree_sitter::{Parser, Point, Tree};
 use tree_sitter_go::language;
 
-pub struct IndexingStatusNotification;
-impl tower_lsp::lsp_types::notification::Notification for IndexingStatusNotification {
-    const METHOD: &'static str = "goanalyzer/indexingStatus";
-    type Params = IndexingStatusParams;
-}
-
-#[derive(Serialize, Deserialize)]
-pub struct IndexingStatusParams {
-    pub uri: String,
-    pub variables: usize,
-    pub functions: usize,
-    pub channels: usize,
-    pub goroutines: usize,
-}
-
-pub struct ParseInfoNotification;
-impl tower_lsp::lsp_types::notification::Notification for ParseInfoNotification {
-    const METHOD: &'static str = "goanalyzer/parseInfo";
-    type Params = ParseInfoParams;
-}
-
-#[derive(Serialize, Deserialize)]
-pub struct ParseInfoParams {
-    pub uri: String,
-    pub source: Option<String>,
-    pub cache_hit: bool,
-    pub parse_ms: Option<u128>,
-    pub code_len: usize,
-}
-
 pub struct LifecycleDumpNotification;
 impl tower_lsp::lsp_types::notification::Notification for LifecycleDumpNotification {
     const METHOD: &'static str = "goanalyzer/lifecycleDump";
@@ -111,6 +159,45 @@ pub struct LifecycleExpected {
     pub color_key: String,
 }
 
+/// The document's file name, which starts the ids of its graph.
+fn graph_file_name(uri: &Url) -> &str {
+    uri.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .unwrap_or("")
+}
+
+/// The file is compiled with Go 1.22 or later, per its module's `go.mod` and `//go:build` line,
+/// so loop variables cannot be captured across iterations.
+fn per_iteration_loop_variables(uri: &Url, code: &str) -> bool {
+    let module = uri
+        .to_file_path()
+        .ok()
+        .and_then(|path| module_go_version(&path));
+    file_go_version(module, code).is_some_and(GoVersion::has_per_iteration_loop_variables)
+}
+
+enum CursorAnalysis {
+    Found {
+        decorations: Vec<Decoration>,
+        lifecycle_points: Vec<LifecyclePoint>,
+        /// The analysis ran out of its time budget before classifying every use.
+        truncated: bool,
+    },
+    NoVariable,
+}
+
+/// The variable at `position`, by cursor context first and then by the plain lookup.
+fn variable_at(
+    tree: &Tree,
+    code: &str,
+    position: Position,
+) -> AnalysisResult<Option<VariableInfo>> {
+    match find_variable_at_position_enhanced(tree, code, position)? {
+        Some(var_info) => Ok(Some(var_info)),
+        None => find_variable_at_position(tree, code, position),
+    }
+}
+
 #[derive(Clone)]
 struct UseMeta {
     range: Range,
@@ -118,6 +205,86 @@ struct UseMeta {
     captured: bool,
 }
 
+/// A quick fix for a file diagnostic, offered when a code action request touches one of
+/// `targets`.
+#[derive(Clone)]
+struct QuickFix {
+    title: String,
+    targets: Vec<Range>,
+    diagnostic: Diagnostic,
+    edits: Vec<TextEdit>,
+    preferred: bool,
+}
+
+/// The quick fixes of the file analysis of one version of a document.
+struct VersionedFixes {
+    version: Option<i32>,
+    fixes: Vec<QuickFix>,
+}
+
+/// What the file-level passes found in one version of a document.
+#[derive(Default)]
+pub(crate) struct FileFindings {
+    pub(crate) diagnostics: Vec<Diagnostic>,
+    fixes: Vec<QuickFix>,
+}
+
+/// Findings of `go test -race` and `go build` rather than of the server's own analysis.
+fn from_go_toolchain(diagnostic: &Diagnostic) -> bool {
+    matches!(&diagnostic.code, Some(NumberOrString::String(code)) if code == CONFIRMED_RACE || code == COMPILE_ERROR)
+}
+
+/// Replaces the `code` diagnostics in `published` with `found`, in the files a Go toolchain run
+/// reported on and in those `rerun` says it checked again, and republishes them. Returns how many
+/// were published.
+async fn replace_published_diagnostics(
+    client: &Client,
+    published: &Mutex<HashMap<Url, Vec<Diagnostic>>>,
+    code: &str,
+    code_description: Option<CodeDescription>,
+    mut found: BTreeMap<Url, Vec<Diagnostic>>,
+    rerun: impl Fn(&Url) -> bool,
+) -> usize {
+    let is_code =
+        |diagnostic: &Diagnostic| diagnostic.code == Some(NumberOrString::String(code.to_string()));
+    let mut published = published.lock().await;
+    let mut uris: BTreeSet<Url> = published
+        .iter()
+        .filter(|(uri, diagnostics)| rerun(uri) && diagnostics.iter().any(is_code))
+        .map(|(uri, _)| uri.clone())
+        .collect();
+    uris.extend(found.keys().cloned());
+    let mut count = 0;
+    let mut updates = Vec::new();
+    for uri in uris {
+        let replacements = found.remove(&uri).unwrap_or_default();
+        count += replacements.len();
+        let diagnostics = published.entry(uri.clone()).or_default();
+        diagnostics.retain(|diagnostic| !is_code(diagnostic));
+        diagnostics.extend(replacements.into_iter().map(|mut diagnostic| {
+            diagnostic.code_description = code_description.clone();
+            diagnostic
+        }));
+        updates.push((uri, diagnostics.clone()));
+    }
+    drop(published);
+    for (uri, diagnostics) in updates {
+        client.publish_diagnostics(uri, diagnostics, None).await;
+    }
+    count
+}
+
+/// Replacements of a pass's fix as LSP edits.
+fn text_edits(edits: &[(Range, String)]) -> Vec<TextEdit> {
+    edits
+        .iter()
+        .map(|(range, text)| TextEdit {
+            range: *range,
+            new_text: text.clone(),
+        })
+        .collect()
+}
+
 fn make_diagnostic(
     severity: DecorationDiagnosticSeverity,
     code: &str,
@@ -127,387 +294,2572 @@ fn make_diagnostic(
         severity,
         code: code.to_string(),
         message,
-    }
-}
-
-const MAX_CACHED_TREES: usize = 20;
-const MAX_CACHED_DOCUMENTS: usize = 50;
-const CACHE_TTL_SECONDS: u64 = 300;
-
-#[derive(Clone)]
-pub struct CacheEntry<T> {
-    data: T,
-    timestamp: SystemTime,
-}
-
-impl<T> CacheEntry<T> {
-    fn new(data: T) -> Self {
-        Self {
-            data,
-            timestamp: SystemTime::now(),
-        }
-    }
-
-    fn touch(&mut self) {
-        self.timestamp = SystemTime::now();
-    }
-
-    fn is_expired(&self) -> bool {
-        self.timestamp.elapsed().unwrap_or(Duration::from_secs(0))
-            > Duration::from_secs(CACHE_TTL_SECONDS)
+        href: None,
     }
 }
 
 pub struct Backend {
     pub client: Client,
-    pub documents: Mutex<HashMap<Url, CacheEntry<String>>>,
-    pub parser: Mutex<Parser>,
-    pub trees: Mutex<HashMap<Url, CacheEntry<Tree>>>,
-    pub semantic: SemanticConfig,
+    /// Open documents with their parsed trees, each behind its own lock.
+    pub documents: Arc<DocumentStore>,
+    pub semantic: SemanticCoalescer,
+    /// Types, definitions and implementations from gopls, when enabled.
+    pub gopls: GoplsClient,
+    pub decoration_store: Mutex<DecorationStore>,
+    /// Last file graph sent per document, diffed into `goanalyzer/graphDelta` on edits.
+    pub graph_store: Mutex<GraphStore>,
+    /// Full graphs served page by page through `goanalyzer/graph.page`.
+    pub graph_pages: Mutex<GraphPageCache>,
+    pub push: DecorationPushConfig,
+    pub recorder: RecordWriter,
+    cursor_positions: Mutex<HashMap<Url, Position>>,
+    /// Debounces the analysis after edits, once per burst per document.
+    analysis: AnalysisQueue,
+    /// Last file diagnostics published per document, re-anchored on edits.
+    published_diagnostics: Arc<Mutex<HashMap<Url, Vec<Diagnostic>>>>,
+    /// Quick fixes of the last file diagnostics per document, with the version they are for.
+    quick_fixes: Mutex<HashMap<Url, VersionedFixes>>,
+    /// Workspace folders from `initialize`, scanned by `goanalyzer/workspaceSummary`.
+    workspace_roots: Mutex<Vec<PathBuf>>,
+    /// The file-level passes, with the rule documentation and custom rules they report with.
+    pub analyzer: Arc<FileAnalyzer>,
+    /// Per-file workspace summary results from earlier sessions, by content hash.
+    analysis_cache: Arc<AnalysisCache>,
+    /// Runs CPU-bound analysis off the event loop, across files in parallel.
+    workers: WorkerPool,
+    /// How long each analysis took, reported by `goanalyzer/stats`.
+    timings: Arc<AnalysisTimings>,
+    /// How long one request's analyses may run before answering with partial results.
+    budget: AnalysisBudget,
+    /// Whether each request's measurements are sent as `goanalyzer/perf`.
+    perf: PerfConfig,
+    race_detector: RaceDetectorConfig,
+    build_on_save: BuildOnSaveConfig,
+    /// The package builds started on save, one per package directory.
+    package_builds: Arc<PackageBuilds>,
 }
 
 impl Backend {
     pub fn new(client: Client) -> Self {
-        let mut parser = Parser::new();
-        parser.set_language(language()).unwrap_or_else(|e| {
-            eprintln!("Failed to set Go language: {:?}", e);
+        Self::with_rules(client, RuleRegistry::from_env())
+    }
+
+    /// A server that also runs the custom rules in `custom_rules` on every file.
+    pub fn with_rules(client: Client, custom_rules: RuleRegistry) -> Self {
+        Parser::new().set_language(language()).unwrap_or_else(|e| {
+            tracing::error!("Failed to set Go language: {:?}", e);
             std::process::exit(1);
         });
         Backend {
             client,
-            documents: Mutex::new(HashMap::new()),
-            parser: Mutex::new(parser),
-            trees: Mutex::new(HashMap::new()),
-            semantic: SemanticConfig::from_env(),
+            documents: Arc::new(DocumentStore::new(CacheLimits::from_env())),
+            semantic: SemanticCoalescer::new(SemanticConfig::from_env()),
+            gopls: GoplsClient::new(GoplsConfig::from_env()),
+            decoration_store: Mutex::new(DecorationStore::default()),
+            graph_store: Mutex::new(GraphStore::default()),
+            graph_pages: Mutex::new(GraphPageCache::default()),
+            push: DecorationPushConfig::from_env(),
+            recorder: RecordWriter::spawn(RecordConfig::from_env()),
+            cursor_positions: Mutex::new(HashMap::new()),
+            analysis: AnalysisQueue::from_env(),
+            published_diagnostics: Arc::default(),
+            quick_fixes: Mutex::new(HashMap::new()),
+            workspace_roots: Mutex::new(Vec::new()),
+            analyzer: Arc::new(FileAnalyzer {
+                rule_docs: RuleDocsConfig::from_env(),
+                custom_rules,
+            }),
+            analysis_cache: Arc::new(AnalysisCache::new(AnalysisCacheConfig::from_env())),
+            workers: WorkerPool::from_env(),
+            timings: Arc::default(),
+            budget: AnalysisBudget::from_env(),
+            perf: PerfConfig::from_env(),
+            race_detector: RaceDetectorConfig::from_env(),
+            build_on_save: BuildOnSaveConfig::from_env(),
+            package_builds: Arc::default(),
         }
     }
 
-    async fn cleanup_expired_cache(&self) {
-        {
-            let mut docs = self.documents.lock().await;
-            docs.retain(|_, entry| !entry.is_expired());
-        }
+    pub async fn parse_document_with_cache(&self, uri: &Url, code: &Arc<str>) -> Option<Tree> {
+        self.reparse_document(uri, code, Some(&[])).await
+    }
 
-        {
-            let mut trees = self.trees.lock().await;
-            trees.retain(|_, entry| !entry.is_expired());
-        }
+    /// Parses `code`, the cached text of `uri` after `deltas`, re-parsing incrementally from the
+    /// cached tree; `None` (a full-text replacement) parses from scratch. The parse runs on the
+    /// worker pool, so awaiting it never blocks the event loop.
+    pub async fn reparse_document(
+        &self,
+        uri: &Url,
+        code: &Arc<str>,
+        deltas: Option<&[EditDelta]>,
+    ) -> Option<Tree> {
+        self.documents.evict();
+        let (documents, timings) = (self.documents.clone(), self.timings.clone());
+        let (uri, code, deltas) = (uri.clone(), code.clone(), deltas.map(<[EditDelta]>::to_vec));
+        self.workers
+            .run(move || timings.time("parse", || documents.parse(&uri, &code, deltas.as_deref())))
+            .await
     }
 
-    async fn enforce_cache_limits(&self) {
-        {
-            let mut docs = self.documents.lock().await;
-            if docs.len() > MAX_CACHED_DOCUMENTS {
-                let mut entries: Vec<_> =
-                    docs.iter().map(|(k, v)| (k.clone(), v.timestamp)).collect();
-                entries.sort_by_key(|(_, timestamp)| *timestamp);
-                let to_remove = entries.len() - MAX_CACHED_DOCUMENTS;
-                for (uri, _) in entries.into_iter().take(to_remove) {
-                    docs.remove(&uri);
-                }
-            }
-        }
-        {
-            let mut trees = self.trees.lock().await;
-            if trees.len() > MAX_CACHED_TREES {
-                let mut entries: Vec<_> = trees
-                    .iter()
-                    .map(|(k, v)| (k.clone(), v.timestamp))
-                    .collect();
-                entries.sort_by_key(|(_, timestamp)| *timestamp);
-                let to_remove = entries.len() - MAX_CACHED_TREES;
-                for (uri, _) in entries.into_iter().take(to_remove) {
-                    trees.remove(&uri);
-                }
-            }
-        }
+    pub async fn get_document(&self, uri: &Url) -> Option<Arc<str>> {
+        self.documents.text(uri)
     }
 
-    pub async fn parse_document_with_cache(&self, uri: &Url, code: &str) -> Option<Tree> {
-        self.cleanup_expired_cache().await;
-        let mut parser = self.parser.lock().await;
-        let mut trees = self.trees.lock().await;
-        let prev_tree = trees.get(uri).map(|entry| &entry.data);
-        let new_tree = match if let Some(prev) = prev_tree {
-            parser.parse(code, Some(prev))
-        } else {
-            parser.parse(code, None)
-        } {
-            Some(tree) => tree,
-            None => {
-                eprintln!("Failed to parse document: {}", uri);
-                return None;
-            }
-        };
-        trees.insert(uri.clone(), CacheEntry::new(new_tree.clone()));
-        drop(trees);
-        drop(parser);
-        self.enforce_cache_limits().await;
-        Some(new_tree)
-    }
-
-    pub async fn get_document(&self, uri: &Url) -> Option<String> {
-        let mut docs = self.documents.lock().await;
-        match docs.get_mut(uri) {
-            Some(entry) if !entry.is_expired() => {
-                entry.touch();
-                Some(entry.data.clone())
-            }
-            _ => None,
-        }
+    pub async fn document_version(&self, uri: &Url) -> Option<i32> {
+        self.documents.version(uri)
     }
 
     pub async fn get_tree_from_cache(&self, uri: &Url) -> Option<Tree> {
-        let trees = self.trees.lock().await;
-        if let Some(entry) = trees.get(uri) {
-            if !entry.is_expired() {
-                Some(entry.data.clone())
-            } else {
-                None
-            }
-        } else {
-            None
-        }
+        self.documents.tree(uri)
     }
 
-    pub async fn send_indexing_status(&self, uri: &Url) {
-        let code = match self.get_document(uri).await {
-            Some(code) => code,
-            None => {
-                eprintln!("Document cache entry expired or missing for: {}", uri);
-                return;
-            }
-        };
-        let tree = match self.parse_document_with_cache(uri, &code).await {
-            Some(tree) => tree,
-            None => {
-                eprintln!("Failed to parse document for indexing status: {}", uri);
-                return;
-            }
-        };
-        let counts = match std::panic::catch_unwind(|| count_entities(&tree, &code)) {
-            Ok(counts) => counts,
-            Err(e) => {
-                eprintln!("Panic occurred while counting entities: {:?}", e);
-                return;
-            }
+    /// The cached tree of `uri`, or `code` (its current text) parsed and cached.
+    pub async fn document_tree(
+        &self,
+        ctx: &RequestContext,
+        uri: &Url,
+        code: &Arc<str>,
+    ) -> Option<Tree> {
+        let started = Instant::now();
+        let (tree, parsed) = match self.get_tree_from_cache(uri).await {
+            Some(tree) => (tree, false),
+            None => (self.parse_document_with_cache(uri, code).await?, true),
         };
-        let params = IndexingStatusParams {
+        ctx.perf.document(DocumentSample {
             uri: uri.to_string(),
-            variables: counts.variables,
-            functions: counts.functions,
-            channels: counts.channels,
-            goroutines: counts.goroutines,
-        };
-        self.client
-            .send_notification::<IndexingStatusNotification>(params)
-            .await;
+            code_len: code.len(),
+            tree: tree.clone(),
+            parse: parsed.then(|| started.elapsed()),
+        });
+        Some(tree)
     }
-}
 
-#[tower_lsp::async_trait]
-impl LanguageServer for Backend {
-    async fn initialize(
+    /// Sends the `goanalyzer/perf` report of the request `ctx`; a no-op unless enabled.
+    async fn report_perf(&self, ctx: &RequestContext) {
+        if self.perf.enabled {
+            self.client
+                .send_notification::<PerfNotification>(ctx.perf_report())
+                .await;
+        }
+    }
+
+    /// Records the new decoration set for `uri` and sends only what changed since the last one.
+    pub async fn publish_decorations_delta(
         &self,
-        _: InitializeParams,
-    ) -> tower_lsp::jsonrpc::Result<InitializeResult> {
-        Ok(InitializeResult {
-            capabilities: ServerCapabilities {
-                hover_provider: Some(HoverProviderCapability::Simple(true)),
-                execute_command_provider: Some(ExecuteCommandOptions {
-                    commands: vec![
-                        "goanalyzer/cursor".to_string(),
-                        "goanalyzer/graph".to_string(),
-                        "goanalyzer/ast".to_string(),
-                    ],
-                    ..Default::default()
-                }),
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
-                )),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
+        uri: &Url,
+        version: Option<i32>,
+        decorations: &mut Vec<Decoration>,
+        truncated: bool,
+    ) {
+        let mut delta = self
+            .decoration_store
+            .lock()
+            .await
+            .update(uri, version, decorations);
+        delta.truncated = truncated;
+        if delta.full || delta.truncated || !delta.added.is_empty() || !delta.removed.is_empty() {
+            self.client
+                .send_notification::<DecorationsDeltaNotification>(delta)
+                .await;
+        }
     }
 
-    async fn initialized(&self, _: InitializedParams) {
-        self.client
-            .log_message(MessageType::INFO, "Go Analyzer initialized")
-            .await;
-        self.client
-            .send_notification::<ProgressNotification>("Server initialized".to_string())
-            .await;
+    /// Appends an analysis result to the record-mode session log; a no-op unless record mode is on.
+    async fn record_analysis(
+        &self,
+        ctx: &RequestContext,
+        uri: &Url,
+        code: &str,
+        position: Option<Position>,
+        findings: impl Serialize,
+    ) {
+        if !self.recorder.is_enabled() {
+            return;
+        }
+        let entry = RecordEntry {
+            timestamp_ms: now_ms(),
+            trace_id: ctx.trace_id.clone(),
+            method: ctx.method.clone(),
+            uri: uri.to_string(),
+            version: self.document_version(uri).await,
+            document_hash: document_hash(code),
+            position,
+            findings: serde_json::to_value(findings).unwrap_or_default(),
+        };
+        self.recorder.record(entry);
     }
 
-    async fn shutdown(&self) -> tower_lsp::jsonrpc::Result<()> {
-        self.client
-            .log_message(MessageType::INFO, "Go Analyzer server shutdown initiated")
+    /// Decorations (and lifecycle points when `dump_json` is set) for the variable at `position`,
+    /// covering only the uses classified before the request's time budget ran out.
+    async fn analyze_cursor(
+        &self,
+        ctx: &RequestContext,
+        uri: &Url,
+        code: &str,
+        tree: &Tree,
+        position: Position,
+        dump_json: bool,
+    ) -> AnalysisResult<CursorAnalysis> {
+        let started = Instant::now();
+        let deadline = ctx.deadline(&self.budget);
+        let analysis = self
+            .cursor_analysis(uri, code, tree, position, dump_json, deadline)
             .await;
-
-        {
-            let mut docs = self.documents.lock().await;
-            let docs_count = docs.len();
-            docs.clear();
-            eprintln!("Cleared {} document cache entries", docs_count);
-        }
-        {
-            let mut trees = self.trees.lock().await;
-            let trees_count = trees.len();
-            trees.clear();
-            eprintln!("Cleared {} AST tree cache entries", trees_count);
+        self.timings.record("cursor", started.elapsed());
+        match &analysis {
+            Ok(CursorAnalysis::Found {
+                truncated: true, ..
+            }) => ctx.perf.truncated(),
+            Err(e) => tracing::error!(parent: &ctx.span, "Cursor analysis failed: {}", e),
+            _ => {}
         }
+        analysis
+    }
 
+    async fn cursor_analysis(
+        &self,
+        uri: &Url,
+        code: &str,
+        tree: &Tree,
+        position: Position,
+        dump_json: bool,
+        deadline: Deadline,
+    ) -> AnalysisResult<CursorAnalysis> {
+        let mut semantic_uses = None;
+        // Decorations are drawn in this document, so a variable declared in another file of the
+        // package is left to the syntax-only lookup.
+        let mut var_info = if let Some(semantic) = self
+            .semantic
+            .resolve("cursor", uri, position, code)
+            .await
+            .filter(|semantic| semantic.info.declared_in.is_none())
         {
-            let _parser = self.parser.lock().await;
-            eprintln!("Released tree-sitter parser resources");
-        }
+            semantic_uses = Some(semantic.uses);
+            semantic.info
+        } else {
+            match variable_at(tree, code, position)? {
+                Some(var_info) => var_info,
+                None => return Ok(CursorAnalysis::NoVariable),
+            }
+        };
 
-        self.client
-            .log_message(MessageType::INFO, "Go Analyzer server shutdown completed")
-            .await;
+        let mut decorations = vec![];
+        let mut lifecycle_points: Vec<LifecyclePoint> = Vec::new();
+        let sync_funcs = crate::analysis::collect_sync_functions(tree, code);
+        let is_decl_global = {
+            let mut is_global = true;
+            let decl_point = Point {
+                row: var_info.declaration.start.line as usize,
+                column: var_info.declaration.start.character as usize,
+            };
+            if let Some(mut node) = tree
+                .root_node()
+                .descendant_for_point_range(decl_point, decl_point)
+            {
+                loop {
+                    let kind = node.kind();
+                    if kind == "function_declaration"
+                        || kind == "method_declaration"
+                        || kind == "func_literal"
+                    {
+                        is_global = false;
+                        break;
+                    }
+                    if let Some(parent) = node.parent() {
+                        node = parent;
+                    } else {
+                        break;
+                    }
+                }
+            }
+            is_global
+        };
 
-        #[cfg(target_os = "windows")]
-        {
-            tokio::spawn(async {
-                eprintln!("Windows: Initiating graceful shutdown in 100ms...");
-                tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-                eprintln!("Windows: Forcing process exit");
-                std::process::exit(0);
+        decorations.push(Decoration {
+            id: String::new(),
+            range: var_info.declaration,
+            kind: DecorationType::Declaration,
+            hover_text: format!("Declaration of `{}`", var_info.name),
+            diagnostic: None,
+            bytes: None,
+            stale: false,
+        });
+
+        if dump_json {
+            let decl_kind = DecorationType::Declaration;
+            lifecycle_points.push(LifecyclePoint {
+                name: format!("{}_decl", var_info.name),
+                file: uri.to_string(),
+                pos: LifecyclePos {
+                    line: var_info.declaration.start.line,
+                    col: var_info.declaration.start.character,
+                },
+                expected: LifecycleExpected {
+                    var: var_info.name.clone(),
+                    kind: "decl".to_string(),
+                    pointer: var_info.is_pointer,
+                    reassign: false,
+                    captured: false,
+                    decoration: decoration_label(&decl_kind).to_string(),
+                    color_key: decoration_color_key(&decl_kind).to_string(),
+                },
             });
         }
 
-        Ok(())
-    }
-
-    async fn did_open(&self, params: DidOpenTextDocumentParams) {
-        let mut docs = self.documents.lock().await;
-        docs.insert(
-            params.text_document.uri.clone(),
-            CacheEntry::new(params.text_document.text.clone()),
-        );
-        drop(docs);
-        self.enforce_cache_limits().await;
-        self.parse_document_with_cache(&params.text_document.uri, &params.text_document.text)
-            .await;
-        self.send_indexing_status(&params.text_document.uri).await;
-    }
-
-    async fn did_change(&self, params: DidChangeTextDocumentParams) {
-        let mut docs = self.documents.lock().await;
-        if let Some(doc) = docs.get_mut(&params.text_document.uri) {
-            if let Some(change) = params.content_changes.into_iter().next_back() {
-                *doc = CacheEntry::new(change.text.clone());
-                let new_text = change.text.clone();
-                drop(docs);
-                self.parse_document_with_cache(&params.text_document.uri, &new_text)
-                    .await;
-                self.send_indexing_status(&params.text_document.uri).await;
-                return;
+        let mut truncated = false;
+        let mut use_metas: Vec<UseMeta> = if let Some(uses) = semantic_uses.take() {
+            uses.into_iter()
+                .map(|u| UseMeta {
+                    range: u.range,
+                    reassign: u.reassign,
+                    captured: u.captured,
+                })
+                .collect()
+        } else {
+            let mut use_metas = Vec::new();
+            for use_range in &var_info.uses {
+                if deadline.expired() {
+                    truncated = true;
+                    break;
+                }
+                let reassign = is_variable_reassignment(tree, &var_info.name, *use_range, code)?;
+                let captured = !reassign
+                    && is_variable_captured(
+                        tree,
+                        &var_info.name,
+                        *use_range,
+                        var_info.declaration,
+                    )?;
+                use_metas.push(UseMeta {
+                    range: *use_range,
+                    reassign,
+                    captured,
+                });
             }
-        }
-        drop(docs);
-    }
+            use_metas
+        };
 
-    async fn hover(&self, params: HoverParams) -> tower_lsp::jsonrpc::Result<Option<Hover>> {
-        let uri = params.text_document_position_params.text_document.uri;
-        let position = params.text_document_position_params.position;
-        let code = match self.get_document(&uri).await {
-            Some(code) => code,
-            None => return Ok(None),
+        let is_field_symbol = is_struct_field_declaration(tree, var_info.declaration);
+        let field_type_kind = if is_field_symbol {
+            field_type_kind_at_declaration(tree, var_info.declaration, code)
+        } else {
+            FieldTypeKind::Other
         };
+        let mut atomic_map: HashMap<String, bool> = HashMap::new();
+        let mut sync_map: HashMap<String, bool> = HashMap::new();
+        let mut heavy_map: HashMap<String, bool> = HashMap::new();
+        let mut saw_atomic = false;
+        let mut saw_non_atomic = false;
+        let mut saw_sync = false;
+        let mut saw_unsync = false;
 
-        // go/types
-        if let Some(semantic) =
-            resolve_semantic_variable(&self.semantic, &uri, position, &code).await
-        {
-            let var_info = &semantic.info;
-            return Ok(Some(Hover {
-                contents: HoverContents::Markup(MarkupContent {
-                    kind: MarkupKind::Markdown,
-                    value: format!(
-                        "**Variable**: `{}`\n\n**Declared at**: line {}\n**Type**: {}\n**Uses**: {}\n",
-                        var_info.name,
-                        var_info.declaration.start.line + 1,
-                        if var_info.is_pointer { "Pointer" } else { "Value" },
-                        var_info.uses.len()
-                    ),
-                }),
-                range: Some(var_info.declaration),
-            }));
-        }
-        let tree = match self.get_tree_from_cache(&uri).await {
-            Some(tree) => tree,
-            None => match self.parse_document_with_cache(&uri, &code).await {
-                Some(tree) => tree,
-                None => {
-                    eprintln!("Failed to parse document for hover: {}", uri);
-                    return Ok(None);
+        if is_field_symbol {
+            let mut analyzed = use_metas.len();
+            for (index, use_meta) in use_metas.iter().enumerate() {
+                if deadline.expired() {
+                    analyzed = index;
+                    truncated = true;
+                    break;
+                }
+                let key = format!(
+                    "{}:{}:{}:{}",
+                    use_meta.range.start.line,
+                    use_meta.range.start.character,
+                    use_meta.range.end.line,
+                    use_meta.range.end.character
+                );
+                let in_atomic = is_access_in_atomic_context(tree, use_meta.range, code)?;
+                let in_sync = is_access_synchronized_at(tree, use_meta.range, code, &sync_funcs);
+                let heavy_under_lock =
+                    in_sync && is_heavy_work_in_call_context(tree, use_meta.range, code)?;
+
+                atomic_map.insert(key.clone(), in_atomic);
+                sync_map.insert(key.clone(), in_sync);
+                heavy_map.insert(key, heavy_under_lock);
+
+                if in_atomic {
+                    saw_atomic = true;
+                } else {
+                    saw_non_atomic = true;
+                }
+                if in_sync {
+                    saw_sync = true;
+                } else {
+                    saw_unsync = true;
                 }
-            },
-        };
-        let var_info = match std::panic::catch_unwind(|| {
-            find_variable_at_position_enhanced(&tree, &code, position)
-                .or_else(|| find_variable_at_position(&tree, &code, position))
-        }) {
-            Ok(Some(var_info)) => var_info,
-            Ok(None) => return Ok(None),
-            Err(e) => {
-                eprintln!("Panic occurred in find_variable_at_position: {:?}", e);
-                return Ok(None);
             }
-        };
-        let mut markdown = format!(
-            "**Variable**: `{}`\n\n**Declared at**: line {}\n**Type**: {}\n**Uses**: {}\n",
-            var_info.name,
-            var_info.declaration.start.line + 1,
-            if var_info.is_pointer {
-                "Pointer"
-            } else {
-                "Value"
-            },
-            var_info.uses.len()
-        );
-        if var_info.potential_race {
-            markdown.push_str("**Warning**: Potential data race detected!\n");
+            use_metas.truncate(analyzed);
         }
-        Ok(Some(Hover {
-            contents: HoverContents::Markup(MarkupContent {
-                kind: MarkupKind::Markdown,
-                value: markdown,
-            }),
-            range: Some(var_info.declaration),
-        }))
-    }
 
-    async fn execute_command(
-        &self,
+        let has_mixed_atomic = is_field_symbol && saw_atomic && saw_non_atomic;
+        let has_lock_coverage_violation = is_field_symbol && saw_sync && saw_unsync;
+        let mut read_before_write_keys: HashSet<String> = HashSet::new();
+        if is_field_symbol {
+            let mut by_context: HashMap<(u32, u32, u32, u32), Vec<UseMeta>> = HashMap::new();
+            for use_meta in &use_metas {
+                if let Some(ctx) = access_context_key(tree, use_meta.range) {
+                    by_context.entry(ctx).or_default().push(use_meta.clone());
+                }
+            }
+            for items in by_context.values_mut() {
+                items.sort_by_key(|u| (u.range.start.line, u.range.start.character));
+                let first_write_idx = items.iter().position(|u| u.reassign);
+                if let Some(write_idx) = first_write_idx {
+                    if items.iter().any(|u| !u.reassign) {
+                        for item in items.iter().take(write_idx) {
+                            if !item.reassign {
+                                let key = format!(
+                                    "{}:{}:{}:{}",
+                                    item.range.start.line,
+                                    item.range.start.character,
+                                    item.range.end.line,
+                                    item.range.end.character
+                                );
+                                read_before_write_keys.insert(key);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        let field_write_only =
+            is_field_symbol && use_metas.len() >= 2 && use_metas.iter().all(|u| u.reassign);
+        let has_read_before_write = !read_before_write_keys.is_empty();
+        let is_struct_value_candidate = !is_field_symbol && !var_info.is_pointer;
+        let mut emitted_mixed_atomic = false;
+        let mut emitted_lock_coverage = false;
+        let mut emitted_heavy_under_lock = false;
+        let mut emitted_retention = false;
+        let mut emitted_large_copy = false;
+        let mut emitted_read_before_write = false;
+        let mut emitted_write_only = false;
+        for use_meta in use_metas {
+            let use_range = use_meta.range;
+            let is_reassignment = use_meta.reassign;
+            let is_captured = use_meta.captured;
+            let key = format!(
+                "{}:{}:{}:{}",
+                use_range.start.line,
+                use_range.start.character,
+                use_range.end.line,
+                use_range.end.character
+            );
+            let in_atomic = atomic_map.get(&key).copied().unwrap_or(false);
+            let in_sync = sync_map.get(&key).copied().unwrap_or(false);
+            let heavy_under_lock = heavy_map.get(&key).copied().unwrap_or(false);
+            let mut decoration_kind = if var_info.is_pointer {
+                DecorationType::Pointer
+            } else {
+                DecorationType::Use
+            };
+            let mut hover_text = format!("Use of `{}`", var_info.name);
+            let mut diagnostic: Option<DecorationDiagnostic> = None;
+            if is_reassignment {
+                decoration_kind = DecorationType::AliasReassigned;
+                hover_text = format!("Reassignment of `{}`", var_info.name);
+            } else if is_captured {
+                decoration_kind = DecorationType::AliasCaptured;
+                hover_text = format!("Captured `{}` in closure/goroutine", var_info.name);
+            }
+            let is_in_goroutine_result = is_in_goroutine_or_group(tree, use_range, code);
+
+            let published_before_spawn = !is_reassignment
+                && is_in_goroutine_result
+                && (is_decl_global || is_field_symbol)
+                && is_published_before_spawn(tree, code, &var_info, use_range)?;
+            if published_before_spawn {
+                hover_text = format!(
+                    "{} | read-only in goroutine, not written after spawn (publish before spawn)",
+                    hover_text
+                );
+            }
+            if !is_captured
+                && is_in_goroutine_result
+                && (is_decl_global || is_field_symbol)
+                && !published_before_spawn
+            {
+                let race_access = if is_reassignment {
+                    "write access"
+                } else {
+                    "read access"
+                };
+                let published_by_once =
+                    !is_reassignment && is_published_by_once(tree, code, &var_info, use_range)?;
+                let joined_by_group_wait =
+                    is_joined_by_group_wait(tree, code, &var_info, use_range)?;
+                let race_severity = if published_by_once || joined_by_group_wait {
+                    RaceSeverity::Low
+                } else {
+                    determine_race_severity(tree, use_range, code, is_reassignment, &sync_funcs)
+                };
+                var_info.race_severity = race_severity.clone();
+                match race_severity {
+                    crate::types::RaceSeverity::High => {
+                        decoration_kind = DecorationType::Race;
+                        hover_text = format!(
+                            "Use of `{}` in goroutine - HIGH PRIORITY data race ({})",
+                            var_info.name, race_access
+                        );
+                        diagnostic = Some(make_diagnostic(
+                            DecorationDiagnosticSeverity::Warning,
+                            "field-race-high",
+                            format!(
+                                "Potential data race on `{}` in goroutine ({})",
+                                var_info.name, race_access
+                            ),
+                        ));
+                    }
+                    crate::types::RaceSeverity::Medium => {
+                        decoration_kind = DecorationType::Race;
+                        hover_text = format!(
+                            "Use of `{}` in goroutine - potential data race ({})",
+                            var_info.name, race_access
+                        );
+                    }
+                    crate::types::RaceSeverity::Low if published_by_once => {
+                        decoration_kind = DecorationType::RaceLow;
+                        hover_text = format!(
+                            "Use of `{}` in goroutine - LOW PRIORITY (initialized in sync.Once.Do, which happens before this read, {})",
+                            var_info.name, race_access
+                        );
+                    }
+                    crate::types::RaceSeverity::Low if joined_by_group_wait => {
+                        decoration_kind = DecorationType::RaceLow;
+                        hover_text = format!(
+                            "Use of `{}` in errgroup function - LOW PRIORITY (other uses are ordered by the group's Wait, {})",
+                            var_info.name, race_access
+                        );
+                    }
+                    crate::types::RaceSeverity::Low => {
+                        decoration_kind = DecorationType::RaceLow;
+                        hover_text = format!(
+                            "Use of `{}` in goroutine - LOW PRIORITY (sync detected, {})",
+                            var_info.name, race_access
+                        );
+                    }
+                }
+                var_info.potential_race = true;
+            }
+            if is_field_symbol {
+                if has_mixed_atomic {
+                    hover_text = format!(
+                        "{} | mixed atomic/non-atomic access detected for field `{}`",
+                        hover_text, var_info.name
+                    );
+                    if !in_atomic && !emitted_mixed_atomic && diagnostic.is_none() {
+                        diagnostic = Some(make_diagnostic(
+                            DecorationDiagnosticSeverity::Warning,
+                            "field-mixed-atomic",
+                            format!(
+                                "Field `{}` is accessed both atomically and non-atomically",
+                                var_info.name
+                            ),
+                        ));
+                        emitted_mixed_atomic = true;
+                    }
+                }
+                if has_lock_coverage_violation
+                    && !in_sync
+                    && !emitted_lock_coverage
+                    && diagnostic.is_none()
+                {
+                    hover_text = format!(
+                        "{} | lock coverage violation for field `{}`",
+                        hover_text, var_info.name
+                    );
+                    diagnostic = Some(make_diagnostic(
+                        DecorationDiagnosticSeverity::Warning,
+                        "field-lock-coverage",
+                        format!(
+                            "Field `{}` has mixed synchronized/unsynchronized access",
+                            var_info.name
+                        ),
+                    ));
+                    emitted_lock_coverage = true;
+                }
+                if heavy_under_lock && !emitted_heavy_under_lock && diagnostic.is_none() {
+                    hover_text = format!(
+                        "{} | heavy call under lock while touching `{}`",
+                        hover_text, var_info.name
+                    );
+                    diagnostic = Some(make_diagnostic(
+                        DecorationDiagnosticSeverity::Information,
+                        "field-heavy-under-lock",
+                        format!(
+                            "Heavy operation under lock for field `{}` may hurt throughput",
+                            var_info.name
+                        ),
+                    ));
+                    emitted_heavy_under_lock = true;
+                }
+                if is_in_goroutine_result && !in_sync {
+                    hover_text = format!(
+                        "{} | captured field access in goroutine without active lock",
+                        hover_text
+                    );
+                }
+                if !emitted_retention {
+                    if let Some(retention_msg) =
+                        detect_retention_pattern(tree, use_range, field_type_kind)
+                    {
+                        hover_text = format!("{} | {}", hover_text, retention_msg);
+                        if diagnostic.is_none() {
+                            diagnostic = Some(make_diagnostic(
+                                DecorationDiagnosticSeverity::Information,
+                                "field-retention",
+                                format!("{}: `{}`", retention_msg, var_info.name),
+                            ));
+                            emitted_retention = true;
+                        }
+                    }
+                }
+                if field_write_only {
+                    hover_text = format!(
+                        "{} | field appears write-only in current file scope",
+                        hover_text
+                    );
+                    if !emitted_write_only && diagnostic.is_none() {
+                        diagnostic = Some(make_diagnostic(
+                            DecorationDiagnosticSeverity::Information,
+                            "field-write-only",
+                            format!("Field `{}` appears write-only", var_info.name),
+                        ));
+                        emitted_write_only = true;
+                    }
+                } else if has_read_before_write
+                    && read_before_write_keys.contains(&key)
+                    && !is_reassignment
+                {
+                    hover_text = format!(
+                        "{} | read-before-write pattern detected in current file scope",
+                        hover_text
+                    );
+                    if !emitted_read_before_write && diagnostic.is_none() {
+                        diagnostic = Some(make_diagnostic(
+                            DecorationDiagnosticSeverity::Warning,
+                            "field-read-before-write",
+                            format!(
+                                "Field `{}` is read before first write in this execution context",
+                                var_info.name
+                            ),
+                        ));
+                        emitted_read_before_write = true;
+                    }
+                }
+            }
+            if is_struct_value_candidate
+                && !is_reassignment
+                && !emitted_large_copy
+                && is_value_copy_context(tree, use_range, code)?
+            {
+                hover_text = format!("{} | potential large struct copy by value", hover_text);
+                if diagnostic.is_none() {
+                    diagnostic = Some(make_diagnostic(
+                        DecorationDiagnosticSeverity::Information,
+                        "struct-large-copy",
+                        format!(
+                            "Potential large struct copy by value for `{}`",
+                            var_info.name
+                        ),
+                    ));
+                    emitted_large_copy = true;
+                }
+            }
+            let decoration_label_text = decoration_label(&decoration_kind).to_string();
+            let decoration_color = decoration_color_key(&decoration_kind).to_string();
+            decorations.push(Decoration {
+                id: String::new(),
+                range: use_range,
+                kind: decoration_kind,
+                hover_text,
+                diagnostic,
+                bytes: None,
+                stale: false,
+            });
+            if dump_json {
+                lifecycle_points.push(LifecyclePoint {
+                    name: format!("{}_use_{}", var_info.name, lifecycle_points.len()),
+                    file: uri.to_string(),
+                    pos: LifecyclePos {
+                        line: use_range.start.line,
+                        col: use_range.start.character,
+                    },
+                    expected: LifecycleExpected {
+                        var: var_info.name.clone(),
+                        kind: "use".to_string(),
+                        pointer: var_info.is_pointer,
+                        reassign: is_reassignment,
+                        captured: is_captured,
+                        decoration: decoration_label_text,
+                        color_key: decoration_color,
+                    },
+                });
+            }
+        }
+        for alias in alias_accesses(tree, code, &var_info) {
+            let in_goroutine = is_in_goroutine_or_group(tree, alias.range, code);
+            let access = if alias.write { "write" } else { "read" };
+            if !in_goroutine {
+                decorations.push(Decoration {
+                    id: String::new(),
+                    range: alias.range,
+                    kind: DecorationType::Pointer,
+                    hover_text: format!(
+                        "{} of `{}` through pointer `{}`",
+                        if alias.write { "Write" } else { "Read" },
+                        var_info.name,
+                        alias.pointer
+                    ),
+                    diagnostic: None,
+                    bytes: None,
+                    stale: false,
+                });
+                continue;
+            }
+            let severity =
+                determine_race_severity(tree, alias.range, code, alias.write, &sync_funcs);
+            let (kind, hover_text, diagnostic) = match severity {
+                RaceSeverity::High => (
+                    DecorationType::Race,
+                    format!(
+                        "{} of `{}` through pointer `{}` in goroutine - HIGH PRIORITY data race",
+                        access, var_info.name, alias.pointer
+                    ),
+                    Some(make_diagnostic(
+                        DecorationDiagnosticSeverity::Warning,
+                        "alias-race",
+                        format!(
+                            "Potential data race on `{}` through pointer `{}` in goroutine ({} access)",
+                            var_info.name, alias.pointer, access
+                        ),
+                    )),
+                ),
+                RaceSeverity::Medium => (
+                    DecorationType::Race,
+                    format!(
+                        "{} of `{}` through pointer `{}` in goroutine - potential data race",
+                        access, var_info.name, alias.pointer
+                    ),
+                    None,
+                ),
+                RaceSeverity::Low => (
+                    DecorationType::RaceLow,
+                    format!(
+                        "{} of `{}` through pointer `{}` in goroutine - LOW PRIORITY (sync detected)",
+                        access, var_info.name, alias.pointer
+                    ),
+                    None,
+                ),
+            };
+            var_info.potential_race = true;
+            decorations.push(Decoration {
+                id: String::new(),
+                range: alias.range,
+                kind,
+                hover_text,
+                diagnostic,
+                bytes: None,
+                stale: false,
+            });
+        }
+        let lines = LineIndex::new(code);
+        for decoration in decorations.iter_mut() {
+            if let Some(diagnostic) = decoration.diagnostic.as_mut() {
+                diagnostic.href = self.analyzer.rule_docs.href(&diagnostic.code);
+            }
+            decoration.bytes = Some(lines.bytes(decoration.range));
+        }
+        Ok(CursorAnalysis::Found {
+            decorations,
+            lifecycle_points,
+            truncated,
+        })
+    }
+
+    /// Shifts the diagnostics and decorations last sent for `uri` past `deltas` and republishes
+    /// them marked stale, so they stay on the right lines until re-analysis replaces them.
+    /// `None` (a full-text change) clears them instead.
+    async fn reanchor_after_change(
+        &self,
+        uri: &Url,
+        version: Option<i32>,
+        deltas: Option<&[EditDelta]>,
+    ) {
+        let diagnostics = {
+            let mut published = self.published_diagnostics.lock().await;
+            let Some(diagnostics) = published.get_mut(uri) else {
+                return;
+            };
+            match deltas {
+                Some(deltas) => reanchor_diagnostics(uri, diagnostics, deltas),
+                None => diagnostics.clear(),
+            }
+            diagnostics.clone()
+        };
+        self.client
+            .publish_diagnostics(uri.clone(), diagnostics, version)
+            .await;
+        if let Some(position) = self.cursor_positions.lock().await.get_mut(uri) {
+            for delta in deltas.unwrap_or_default() {
+                *position = delta.shift_cursor(*position);
+            }
+        }
+        if !self.push.enabled {
+            return;
+        }
+        let decorations = self
+            .decoration_store
+            .lock()
+            .await
+            .reanchor(uri, version, deltas);
+        if let Some(decorations) = decorations {
+            self.client
+                .send_notification::<DecorationsNotification>(DecorationsParams {
+                    uri: uri.to_string(),
+                    version,
+                    decorations,
+                    truncated: false,
+                })
+                .await;
+        }
+    }
+
+    /// Waits for edits to `uri` to settle, then republishes file diagnostics and, when pushing
+    /// is enabled, decorations for the last cursor position. Superseded by any later change to
+    /// the same document.
+    async fn analyze_after_change(&self, ctx: &RequestContext, uri: &Url) {
+        let Some(ticket) = self.analysis.settle(uri).await else {
+            // A later change in the same burst runs the analysis.
+            return;
+        };
+        ctx.perf.restart();
+        self.send_indexing_status(uri).await;
+        if !self.analysis.is_current(uri, ticket).await {
+            return;
+        }
+        self.publish_file_diagnostics(ctx, uri).await;
+        if self.push.enabled && self.analysis.is_current(uri, ticket).await {
+            self.push_decorations(ctx, uri).await;
+        }
+        if self.analysis.is_current(uri, ticket).await {
+            self.push_graph_delta(ctx, uri).await;
+        }
+        self.report_perf(ctx).await;
+    }
+
+    /// Rebuilds the file graph of a document whose graph the client holds and pushes what
+    /// changed.
+    async fn push_graph_delta(&self, ctx: &RequestContext, uri: &Url) {
+        if !self.graph_store.lock().await.is_tracked(uri) {
+            return;
+        }
+        let version = self.document_version(uri).await;
+        let Some(graph) = self.scoped_graph(ctx, uri, GraphScope::File).await else {
+            return;
+        };
+        if self.document_version(uri).await != version {
+            // A newer change is already queued and will push its own delta.
+            return;
+        }
+        let delta = self.graph_store.lock().await.update(uri, version, graph);
+        if let Some(delta) = delta {
+            self.client
+                .send_notification::<GraphDeltaNotification>(delta)
+                .await;
+        }
+    }
+
+    /// Recomputes decorations for the last cursor position and pushes the full set.
+    async fn push_decorations(&self, ctx: &RequestContext, uri: &Url) {
+        let position = match self.cursor_positions.lock().await.get(uri) {
+            Some(position) => *position,
+            None => return,
+        };
+        let version = self.document_version(uri).await;
+        let code = match self.get_document(uri).await {
+            Some(code) => code,
+            None => return,
+        };
+        let tree = match self.document_tree(ctx, uri, &code).await {
+            Some(tree) => tree,
+            None => return,
+        };
+        let (mut decorations, truncated) = match self
+            .analyze_cursor(ctx, uri, &code, &tree, position, false)
+            .await
+        {
+            Ok(CursorAnalysis::Found {
+                decorations,
+                truncated,
+                ..
+            }) => (decorations, truncated),
+            Ok(CursorAnalysis::NoVariable) => (Vec::new(), false),
+            Err(_) => return,
+        };
+        if self.document_version(uri).await != version {
+            // A newer change is already queued and will push its own set.
+            return;
+        }
+        self.decoration_store
+            .lock()
+            .await
+            .update(uri, version, &mut decorations);
+        self.record_analysis(ctx, uri, &code, Some(position), &decorations)
+            .await;
+        self.client
+            .send_notification::<DecorationsNotification>(DecorationsParams {
+                uri: uri.to_string(),
+                version,
+                decorations,
+                truncated,
+            })
+            .await;
+    }
+
+    pub async fn send_indexing_status(&self, uri: &Url) {
+        let code = match self.get_document(uri).await {
+            Some(code) => code,
+            None => {
+                tracing::debug!("Document cache entry expired or missing for: {}", uri);
+                return;
+            }
+        };
+        let tree = match self.parse_document_with_cache(uri, &code).await {
+            Some(tree) => tree,
+            None => {
+                tracing::warn!("Failed to parse document for indexing status: {}", uri);
+                return;
+            }
+        };
+        let timings = self.timings.clone();
+        let counts = self
+            .workers
+            .run(move || timings.time("count_entities", || count_entities(&tree, &code)))
+            .await;
+        let params = IndexingStatusParams {
+            uri: uri.to_string(),
+            variables: counts.variables,
+            functions: counts.functions,
+            channels: counts.channels,
+            goroutines: counts.goroutines,
+        };
+        self.client
+            .send_notification::<IndexingStatusNotification>(params)
+            .await;
+    }
+
+    /// Publishes file-wide diagnostics that do not depend on the cursor position.
+    pub async fn publish_file_diagnostics(&self, ctx: &RequestContext, uri: &Url) {
+        let code = match self.get_document(uri).await {
+            Some(code) => code,
+            None => return,
+        };
+        let tree = match self.document_tree(ctx, uri, &code).await {
+            Some(tree) => tree,
+            None => return,
+        };
+        let version = self.document_version(uri).await;
+        let (analyzer, timings) = (self.analyzer.clone(), self.timings.clone());
+        let (analyzed_uri, analyzed_code) = (uri.clone(), code.clone());
+        let findings = self
+            .workers
+            .run(move || {
+                timings.time("file_diagnostics", || {
+                    analyzer.file_diagnostics(&analyzed_uri, &tree, &analyzed_code)
+                })
+            })
+            .await;
+        let Ok(FileFindings { diagnostics, fixes }) = ctx.logged("file_diagnostics", findings)
+        else {
+            return;
+        };
+        self.record_analysis(ctx, uri, &code, None, &diagnostics)
+            .await;
+        self.quick_fixes
+            .lock()
+            .await
+            .insert(uri.clone(), VersionedFixes { version, fixes });
+        let diagnostics = {
+            let mut published = self.published_diagnostics.lock().await;
+            // What `go test -race` and `go build` found stays until their next run, moved along
+            // with edits.
+            let toolchain = published
+                .get(uri)
+                .into_iter()
+                .flatten()
+                .filter(|diagnostic| from_go_toolchain(diagnostic))
+                .cloned();
+            let diagnostics: Vec<Diagnostic> = diagnostics.into_iter().chain(toolchain).collect();
+            published.insert(uri.clone(), diagnostics.clone());
+            diagnostics
+        };
+        self.client
+            .publish_diagnostics(uri.clone(), diagnostics, None)
+            .await;
+    }
+
+    /// Replaces the `code` diagnostics of a Go toolchain run with `found`, in the files it
+    /// reported on and in those `rerun` says it checked again, and republishes them. Returns how
+    /// many were published.
+    async fn replace_toolchain_diagnostics(
+        &self,
+        code: &str,
+        found: BTreeMap<Url, Vec<Diagnostic>>,
+        rerun: impl Fn(&Url) -> bool,
+    ) -> usize {
+        replace_published_diagnostics(
+            &self.client,
+            &self.published_diagnostics,
+            code,
+            self.code_description(code),
+            found,
+            rerun,
+        )
+        .await
+    }
+
+    /// The link to the documentation of the diagnostic `code`, when it has one.
+    fn code_description(&self, code: &str) -> Option<CodeDescription> {
+        self.analyzer
+            .rule_docs
+            .href(code)
+            .and_then(|href| Url::parse(&href).ok())
+            .map(|href| CodeDescription { href })
+    }
+
+    /// Starts building the package of the saved `uri`, superseding a build of the same package
+    /// still running, and returns; the build publishes the compiler's errors when it finishes,
+    /// replacing those of the package's previous build.
+    fn build_saved_package(&self, ctx: &RequestContext, uri: &Url) {
+        let Some((dir, args, with_tests)) = uri.to_file_path().ok().and_then(|path| {
+            let dir = path.parent()?.to_path_buf();
+            Some((dir, build_args(&path), is_test_file(&path)))
+        }) else {
+            return;
+        };
+        let client = self.client.clone();
+        let published = self.published_diagnostics.clone();
+        let code_description = self.code_description(COMPILE_ERROR);
+        let config = self.build_on_save.clone();
+        let builds = self.package_builds.clone();
+        let ctx = ctx.clone();
+        self.package_builds
+            .start(dir.clone(), move |generation| async move {
+                let output = run_go_build(&dir, &args, &config).await;
+                if !builds.finish(&dir, generation) {
+                    return;
+                }
+                let output = match output {
+                    Ok(output) => output,
+                    Err(e) => {
+                        tracing::warn!(parent: &ctx.span, "Failed to run go {} in {}: {}", args[0], dir.display(), e);
+                        return;
+                    }
+                };
+                let errors = parse_build_output(&output, &dir);
+                if errors.is_empty() && !output.is_empty() {
+                    tracing::debug!(parent: &ctx.span, "go {} failed without compiler errors: {}", args[0], output.trim());
+                }
+                let found =
+                    compiler_diagnostics(&errors, |path| std::fs::read_to_string(path).ok());
+                // A plain build leaves the errors of the test files it did not compile.
+                let in_package = |uri: &Url| {
+                    uri.to_file_path().is_ok_and(|path| {
+                        path.parent() == Some(dir.as_path()) && (with_tests || !is_test_file(&path))
+                    })
+                };
+                let count = replace_published_diagnostics(
+                    &client,
+                    &published,
+                    COMPILE_ERROR,
+                    code_description,
+                    found,
+                    in_package,
+                )
+                .await;
+                tracing::debug!(parent: &ctx.span, "go {} reported {} error(s) in {}", args[0], count, dir.display());
+            });
+    }
+
+    /// Runs the file-level passes over every Go file under `roots` and aggregates them per
+    /// directory. Open documents are analyzed from their buffers.
+    async fn workspace_summary(
+        &self,
+        ctx: &RequestContext,
+        roots: &[PathBuf],
+        sort_by: SummarySort,
+    ) -> WorkspaceSummary {
+        let files: Vec<PathBuf> = roots
+            .iter()
+            .flat_map(|root| collect_go_files(root))
+            .collect();
+        self.progress(ctx, &format!("Summarizing {} files", files.len()))
+            .await;
+        let started = Instant::now();
+        let mut reports = Vec::new();
+        let mut keys = HashMap::new();
+        let mut changed = Vec::new();
+        for (path, code) in self.read_texts(files).await {
+            let key = cache_key(&path, &code, &self.analysis_fingerprint(&path));
+            match self.analysis_cache.load(&key, &path) {
+                Some(artifacts) => reports.push(artifacts.into_report()),
+                None => {
+                    keys.insert(path.clone(), key);
+                    changed.push((path, code));
+                }
+            }
+        }
+        let sources = parse_sources(changed, &self.workers).await;
+        let (analyzer, cache, task_ctx) = (
+            self.analyzer.clone(),
+            self.analysis_cache.clone(),
+            ctx.clone(),
+        );
+        let analyzed = self
+            .workers
+            .map(sources, move |SourceFile { path, code, tree }| {
+                let uri = Url::from_file_path(&path).ok()?;
+                let findings = analyzer.file_diagnostics(&uri, &tree, &code);
+                let report = FileReport {
+                    diagnostics: task_ctx
+                        .logged("file_diagnostics", findings)
+                        .ok()?
+                        .diagnostics,
+                    counts: count_entities(&tree, &code),
+                    synchronized_goroutines: synchronized_goroutines(&tree, &code),
+                    path,
+                };
+                if let Some(key) = keys.get(&report.path) {
+                    cache.store(key, &FileArtifacts::from_report(&report));
+                }
+                Some(report)
+            })
+            .await;
+        reports.extend(analyzed.into_iter().flatten());
+        let summary = summarize(roots, reports, sort_by);
+        self.timings.record("workspace_summary", started.elapsed());
+        summary
+    }
+
+    /// The merged entity graph of `files`, preferring open documents over the files on disk.
+    async fn workspace_graph(&self, ctx: &RequestContext, files: Vec<PathBuf>) -> GraphData {
+        self.progress(ctx, &format!("Building graph of {} files", files.len()))
+            .await;
+        let sources = self.read_sources(files).await;
+        let deadline = ctx.deadline(&self.budget);
+        let timings = self.timings.clone();
+        let graph = self
+            .workers
+            .run(move || {
+                timings.time("workspace_graph", || {
+                    build_workspace_graph_within(&sources, deadline)
+                })
+            })
+            .await;
+        if graph.truncated {
+            ctx.perf.truncated();
+        }
+        graph
+    }
+
+    /// `files` parsed in parallel, taking open documents from their buffers and the rest from
+    /// disk. Parsed trees are not cached.
+    async fn read_sources(&self, files: Vec<PathBuf>) -> Vec<SourceFile> {
+        parse_sources(self.read_texts(files).await, &self.workers).await
+    }
+
+    /// Text of `files`, from open documents' buffers or from disk.
+    async fn read_texts(&self, files: Vec<PathBuf>) -> Vec<(PathBuf, String)> {
+        let mut texts = Vec::new();
+        for path in files {
+            let Ok(uri) = Url::from_file_path(&path) else {
+                continue;
+            };
+            let code = match self.get_document(&uri).await {
+                Some(code) => code.to_string(),
+                None => match tokio::fs::read_to_string(&path).await {
+                    Ok(code) => code,
+                    Err(_) => continue,
+                },
+            };
+            texts.push((path, code));
+        }
+        texts
+    }
+
+    /// Everything besides a file's path and content that its file diagnostics depend on, for
+    /// [`cache_key`].
+    fn analysis_fingerprint(&self, path: &Path) -> String {
+        let rules: Vec<(&str, &serde_json::Value)> = self
+            .analyzer
+            .custom_rules
+            .rules()
+            .iter()
+            .map(|rule| rule.meta().code)
+            .map(|code| (code, self.analyzer.custom_rules.settings(code)))
+            .collect();
+        format!(
+            "{:?}|{:?}|{}|{:?}",
+            module_go_version(path),
+            sync_config().functions,
+            self.analyzer.rule_docs.base_url,
+            rules
+        )
+    }
+
+    /// The entity graph of the document, its package or the workspace. Reports why through
+    /// progress when there is none.
+    async fn scoped_graph(
+        &self,
+        ctx: &RequestContext,
+        uri: &Url,
+        scope: GraphScope,
+    ) -> Option<GraphData> {
+        if scope == GraphScope::File {
+            let Some(code) = self.get_document(uri).await else {
+                self.progress(ctx, "No document found or expired").await;
+                return None;
+            };
+            let tree = match self.document_tree(ctx, uri, &code).await {
+                Some(tree) => tree,
+                None => {
+                    self.progress(ctx, "Failed to parse document").await;
+                    return None;
+                }
+            };
+            let deadline = ctx.deadline(&self.budget);
+            let timings = self.timings.clone();
+            let (graphed_uri, graphed_code) = (uri.clone(), code.clone());
+            let mut graph = self
+                .workers
+                .run(move || {
+                    timings.time("file_graph", || {
+                        build_file_graph_within(
+                            &tree,
+                            &graphed_code,
+                            graph_file_name(&graphed_uri),
+                            &Default::default(),
+                            deadline,
+                        )
+                    })
+                })
+                .await;
+            self.gopls
+                .enrich_graph(uri, &code, &mut graph, deadline)
+                .await;
+            if graph.truncated {
+                ctx.perf.truncated();
+            }
+            return Some(graph);
+        }
+        let files = match (scope, uri.to_file_path()) {
+            (GraphScope::Package, Ok(path)) => {
+                let dir = path.parent().map(PathBuf::from).unwrap_or_default();
+                collect_go_files(&dir)
+                    .into_iter()
+                    .filter(|file| file.parent() == Some(dir.as_path()))
+                    .collect()
+            }
+            (GraphScope::Package, Err(_)) => {
+                self.progress(ctx, "Package graphs need a file URI").await;
+                return None;
+            }
+            _ => self
+                .workspace_roots
+                .lock()
+                .await
+                .iter()
+                .flat_map(|root| collect_go_files(root))
+                .collect(),
+        };
+        Some(self.workspace_graph(ctx, files).await)
+    }
+}
+
+/// The file-level passes and the diagnostics and quick fixes made of their findings. Shared with
+/// the worker pool, which runs them off the event loop.
+pub struct FileAnalyzer {
+    pub rule_docs: RuleDocsConfig,
+    pub custom_rules: RuleRegistry,
+}
+
+impl FileAnalyzer {
+    /// Diagnostics of every file-level pass, with byte offsets in `data`, and the quick fixes
+    /// for them.
+    pub(crate) fn file_diagnostics(
+        &self,
+        uri: &Url,
+        tree: &Tree,
+        code: &str,
+    ) -> AnalysisResult<FileFindings> {
+        let lock_returns = find_lock_value_returns(tree, code)?;
+        let global_races = find_global_races(tree, code)?;
+        let races = find_interprocedural_races(tree, code)?;
+        let mut diagnostics: Vec<Diagnostic> = lock_returns
+            .iter()
+            .map(|finding| self.lock_value_return_diagnostic(finding))
+            .collect();
+        let mut fixes: Vec<QuickFix> = lock_returns
+            .iter()
+            .map(|finding| self.lock_value_return_fix(finding))
+            .collect();
+        let lock_copies = find_lock_copies(tree, code)?;
+        diagnostics.extend(
+            lock_copies
+                .iter()
+                .map(|finding| self.lock_copy_diagnostic(finding)),
+        );
+        // A package-level variable written through a call is reported once, by the global race
+        // that lists every write and conflicting access.
+        for race in races.iter().filter(|race| {
+            !global_races.iter().any(|global| {
+                global.variable == race.variable
+                    && global.writes.iter().any(|(write, _)| *write == race.write)
+            })
+        }) {
+            diagnostics.extend(self.interprocedural_race_diagnostics(uri, race));
+        }
+        let mixed_atomics = find_mixed_atomic_accesses(tree, code)?;
+        diagnostics.extend(
+            mixed_atomics
+                .iter()
+                .map(|finding| self.mixed_atomic_diagnostic(uri, finding)),
+        );
+        let atomic_stores = find_inconsistent_atomic_stores(tree, code)?;
+        diagnostics.extend(
+            atomic_stores
+                .iter()
+                .map(|finding| self.atomic_store_diagnostic(uri, finding)),
+        );
+        let deadlocks = find_channel_deadlocks(tree, code)?;
+        diagnostics.extend(
+            deadlocks
+                .iter()
+                .map(|deadlock| self.channel_deadlock_diagnostic(uri, deadlock)),
+        );
+        let sends_after_close = find_sends_after_close(tree, code)?;
+        diagnostics.extend(
+            sends_after_close
+                .iter()
+                .map(|finding| self.send_after_close_diagnostic(uri, finding)),
+        );
+        let double_closes = find_double_closes(tree, code)?;
+        diagnostics.extend(
+            double_closes
+                .iter()
+                .map(|finding| self.double_close_diagnostic(uri, finding)),
+        );
+        let nil_channel_ops = find_nil_channel_ops(tree, code)?;
+        diagnostics.extend(
+            nil_channel_ops
+                .iter()
+                .map(|finding| self.nil_channel_diagnostic(uri, finding)),
+        );
+        let (unclosed_ranges, producer_closes) = find_close_responsibility_issues(tree, code)?;
+        diagnostics.extend(
+            unclosed_ranges
+                .iter()
+                .map(|finding| self.unclosed_range_diagnostic(uri, finding)),
+        );
+        diagnostics.extend(
+            producer_closes
+                .iter()
+                .map(|finding| self.close_by_producer_diagnostic(uri, finding)),
+        );
+        let leaks = find_goroutine_leaks(tree, code)?;
+        diagnostics.extend(
+            leaks
+                .iter()
+                .map(|leak| self.goroutine_leak_diagnostic(uri, leak)),
+        );
+        let unlock_issues = find_unlock_issues(tree, code)?;
+        diagnostics.extend(
+            unlock_issues
+                .iter()
+                .map(|issue| self.unlock_issue_diagnostic(uri, issue)),
+        );
+        fixes.extend(
+            unlock_issues
+                .iter()
+                .filter_map(|issue| self.unlock_issue_fix(uri, issue)),
+        );
+        let cond_misuses = find_cond_misuses(tree, code)?;
+        diagnostics.extend(
+            cond_misuses
+                .iter()
+                .map(|finding| self.cond_misuse_diagnostic(finding)),
+        );
+        let double_locks = find_double_locks(tree, code)?;
+        diagnostics.extend(
+            double_locks
+                .iter()
+                .map(|finding| self.double_lock_diagnostic(uri, finding)),
+        );
+        let lock_cycles = find_lock_order_cycles(tree, code)?;
+        diagnostics.extend(
+            lock_cycles
+                .iter()
+                .map(|finding| self.lock_order_diagnostic(uri, finding)),
+        );
+        let loop_captures = if per_iteration_loop_variables(uri, code) {
+            Vec::new()
+        } else {
+            find_loop_variable_captures(tree, code)?
+        };
+        diagnostics.extend(
+            loop_captures
+                .iter()
+                .map(|finding| self.loop_capture_diagnostic(uri, finding)),
+        );
+        fixes.extend(
+            loop_captures
+                .iter()
+                .filter_map(|finding| self.loop_capture_fix(uri, finding)),
+        );
+        diagnostics.extend(
+            global_races
+                .iter()
+                .map(|race| self.global_race_diagnostic(uri, race)),
+        );
+        let map_accesses = find_concurrent_map_accesses(tree, code)?;
+        diagnostics.extend(
+            map_accesses
+                .iter()
+                .map(|finding| self.concurrent_map_diagnostic(uri, finding)),
+        );
+        fixes.extend(
+            map_accesses
+                .iter()
+                .map(|finding| self.concurrent_map_fix(uri, finding)),
+        );
+        let busy_waits = find_busy_wait_selects(tree);
+        diagnostics.extend(
+            busy_waits
+                .iter()
+                .map(|finding| self.busy_wait_diagnostic(uri, finding)),
+        );
+        let timer_leaks = find_time_after_in_loops(tree, code)?;
+        diagnostics.extend(
+            timer_leaks
+                .iter()
+                .map(|finding| self.time_after_diagnostic(uri, finding)),
+        );
+        fixes.extend(
+            timer_leaks
+                .iter()
+                .flat_map(|finding| self.time_after_fixes(uri, finding)),
+        );
+        let unbounded_spawns = find_unbounded_spawns(tree, code)?;
+        diagnostics.extend(
+            unbounded_spawns
+                .iter()
+                .map(|finding| self.unbounded_spawn_diagnostic(uri, finding)),
+        );
+        fixes.extend(
+            unbounded_spawns
+                .iter()
+                .filter_map(|finding| self.unbounded_spawn_fix(uri, finding)),
+        );
+        let unjoined = find_unjoined_goroutines(tree, code)?;
+        diagnostics.extend(
+            unjoined
+                .iter()
+                .map(|finding| self.unjoined_goroutine_diagnostic(uri, finding)),
+        );
+        let goroutine_panics = find_goroutine_panics(tree, code)?;
+        diagnostics.extend(
+            goroutine_panics
+                .iter()
+                .map(|finding| self.goroutine_panic_diagnostic(uri, finding)),
+        );
+        fixes.extend(
+            goroutine_panics
+                .iter()
+                .map(|finding| self.goroutine_panic_fix(uri, finding)),
+        );
+        let sleeps = find_sleep_synchronization(tree, code)?;
+        diagnostics.extend(
+            sleeps
+                .iter()
+                .map(|finding| self.sleep_synchronization_diagnostic(uri, finding)),
+        );
+        let waitgroup_issues = analyze_waitgroups(tree, code)?;
+        diagnostics.extend(
+            waitgroup_issues
+                .iter()
+                .map(|issue| self.waitgroup_diagnostic(issue)),
+        );
+        for rule in self.custom_rules.rules() {
+            let meta = rule.meta();
+            let input = RuleInput::new(uri, tree, code, self.custom_rules.settings(meta.code));
+            let findings = rule.check(&input);
+            let href = rule.docs_url().and_then(|href| Url::parse(&href).ok());
+            diagnostics.extend(findings.into_iter().map(|finding| Diagnostic {
+                range: finding.range,
+                severity: Some(finding.severity),
+                code: Some(NumberOrString::String(meta.code.to_string())),
+                code_description: href.clone().map(|href| CodeDescription { href }),
+                source: Some("go-analyzer".to_string()),
+                message: finding.message,
+                related_information: (!finding.related.is_empty()).then(|| {
+                    finding
+                        .related
+                        .into_iter()
+                        .map(|(range, message)| DiagnosticRelatedInformation {
+                            location: Location::new(uri.clone(), range),
+                            message,
+                        })
+                        .collect()
+                }),
+                ..Default::default()
+            }));
+        }
+        let lines = LineIndex::new(code);
+        for diagnostic in diagnostics.iter_mut() {
+            store_anchor(diagnostic, Some(lines.bytes(diagnostic.range)), false);
+        }
+        Ok(FileFindings { diagnostics, fixes })
+    }
+
+    /// One diagnostic at the goroutine's call site and one at the write it reaches, linked to
+    /// each other.
+    fn interprocedural_race_diagnostics(
+        &self,
+        uri: &Url,
+        race: &InterproceduralRace,
+    ) -> [Diagnostic; 2] {
+        let code = "interprocedural-race";
+        let code_description = self
+            .rule_docs
+            .href(code)
+            .and_then(|href| Url::parse(&href).ok())
+            .map(|href| CodeDescription { href });
+        let related = |range: Range, message: String| {
+            Some(vec![DiagnosticRelatedInformation {
+                location: Location::new(uri.clone(), range),
+                message,
+            }])
+        };
+        let call_site = Diagnostic {
+            range: race.call_site,
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: code_description.clone(),
+            source: Some("go-analyzer".to_string()),
+            message: match &race.receiver {
+                Some(receiver) => format!(
+                    "Goroutine calls `{}` on `{}`, which other goroutines share; it writes `{}` without synchronization",
+                    race.function, receiver, race.variable
+                ),
+                None => format!(
+                    "Call from goroutine writes `{}` without synchronization (in `{}`)",
+                    race.variable, race.function
+                ),
+            },
+            related_information: Some(vec![
+                DiagnosticRelatedInformation {
+                    location: Location::new(uri.clone(), race.write),
+                    message: format!("unsynchronized write to `{}`", race.variable),
+                },
+                DiagnosticRelatedInformation {
+                    location: Location::new(uri.clone(), race.go_statement),
+                    message: "goroutine started here".to_string(),
+                },
+            ]),
+            ..Default::default()
+        };
+        let write = Diagnostic {
+            range: race.write,
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description,
+            source: Some("go-analyzer".to_string()),
+            message: match &race.receiver {
+                Some(receiver) => format!(
+                    "Receiver field `{}` is shared state: `{}` runs in a goroutine on `{}` (line {}) and writes it without synchronization",
+                    race.variable,
+                    race.function,
+                    receiver,
+                    race.call_site.start.line + 1
+                ),
+                None => format!(
+                    "`{}` is written without synchronization and `{}` runs in a goroutine (line {})",
+                    race.variable,
+                    race.function,
+                    race.call_site.start.line + 1
+                ),
+            },
+            related_information: related(race.call_site, "goroutine call site".to_string()),
+            ..Default::default()
+        };
+        [call_site, write]
+    }
+
+    fn channel_deadlock_diagnostic(&self, uri: &Url, deadlock: &ChannelDeadlock) -> Diagnostic {
+        let code = "channel-deadlock";
+        Diagnostic {
+            range: deadlock.range,
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: deadlock.message(),
+            related_information: Some(vec![DiagnosticRelatedInformation {
+                location: Location::new(uri.clone(), deadlock.creation),
+                message: format!("`{}` created unbuffered here", deadlock.channel),
+            }]),
+            ..Default::default()
+        }
+    }
+
+    fn send_after_close_diagnostic(&self, uri: &Url, finding: &SendAfterClose) -> Diagnostic {
+        let code = "channel-send-after-close";
+        Diagnostic {
+            range: finding.send,
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: format!(
+                "Send on closed channel `{}` panics (closed on line {})",
+                finding.channel,
+                finding.close.start.line + 1
+            ),
+            related_information: Some(vec![DiagnosticRelatedInformation {
+                location: Location::new(uri.clone(), finding.close),
+                message: format!("`{}` closed here", finding.channel),
+            }]),
+            ..Default::default()
+        }
+    }
+
+    fn unclosed_range_diagnostic(&self, uri: &Url, finding: &UnclosedRange) -> Diagnostic {
+        let code = "channel-range-not-closed";
+        let mut related = vec![DiagnosticRelatedInformation {
+            location: Location::new(uri.clone(), finding.creation),
+            message: format!("`{}` created here", finding.channel),
+        }];
+        related.extend(
+            finding
+                .sends
+                .iter()
+                .map(|send| DiagnosticRelatedInformation {
+                    location: Location::new(uri.clone(), *send),
+                    message: "producer sends here".to_string(),
+                }),
+        );
+        Diagnostic {
+            range: finding.range,
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: finding.message(),
+            related_information: Some(related),
+            ..Default::default()
+        }
+    }
+
+    fn close_by_producer_diagnostic(&self, uri: &Url, finding: &CloseByProducer) -> Diagnostic {
+        let code = "channel-close-by-producer";
+        Diagnostic {
+            range: finding.close,
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: finding.message(),
+            related_information: Some(
+                finding
+                    .other_sends
+                    .iter()
+                    .map(|send| DiagnosticRelatedInformation {
+                        location: Location::new(uri.clone(), *send),
+                        message: "another producer sends here".to_string(),
+                    })
+                    .collect(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    fn goroutine_leak_diagnostic(&self, uri: &Url, leak: &GoroutineLeak) -> Diagnostic {
+        let code = "goroutine-leak";
+        Diagnostic {
+            range: leak.range,
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: leak.message(),
+            related_information: Some(
+                leak.creations
+                    .iter()
+                    .map(|creation| DiagnosticRelatedInformation {
+                        location: Location::new(uri.clone(), *creation),
+                        message: "channel made here".to_string(),
+                    })
+                    .collect(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    fn global_race_diagnostic(&self, uri: &Url, race: &GlobalRace) -> Diagnostic {
+        let code = "global-race";
+        let writes = race
+            .writes
+            .iter()
+            .map(|(range, who)| (*range, format!("written in {}", who)));
+        let conflicts = race
+            .conflicts
+            .iter()
+            .map(|(range, who)| (*range, format!("accessed in {}", who)));
+        Diagnostic {
+            range: race.declaration,
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: race.message(),
+            related_information: Some(
+                writes
+                    .chain(conflicts)
+                    .map(|(range, message)| DiagnosticRelatedInformation {
+                        location: Location::new(uri.clone(), range),
+                        message,
+                    })
+                    .collect(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    fn busy_wait_diagnostic(&self, uri: &Url, finding: &BusyWaitSelect) -> Diagnostic {
+        let code = "select-busy-wait";
+        Diagnostic {
+            range: finding.range,
+            severity: Some(if finding.in_loop.is_some() {
+                DiagnosticSeverity::WARNING
+            } else {
+                DiagnosticSeverity::INFORMATION
+            }),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: finding.message(),
+            related_information: finding.in_loop.map(|range| {
+                vec![DiagnosticRelatedInformation {
+                    location: Location::new(uri.clone(), range),
+                    message: "loop repeating the select".to_string(),
+                }]
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn unbounded_spawn_diagnostic(&self, uri: &Url, finding: &UnboundedSpawn) -> Diagnostic {
+        let code = "unbounded-goroutines";
+        Diagnostic {
+            range: finding.range,
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: finding.message(),
+            related_information: Some(vec![DiagnosticRelatedInformation {
+                location: Location::new(uri.clone(), finding.loop_range),
+                message: "loop starting a goroutine per iteration".to_string(),
+            }]),
+            ..Default::default()
+        }
+    }
+
+    fn unjoined_goroutine_diagnostic(&self, uri: &Url, finding: &UnjoinedGoroutine) -> Diagnostic {
+        let code = "unjoined-goroutine";
+        let escape = match finding.via {
+            Escape::Returned => format!("`{}` reaches the caller here", finding.variable),
+            Escape::Parameter => format!("`{}` is the caller's", finding.variable),
+        };
+        Diagnostic {
+            range: finding.range,
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: finding.message(),
+            related_information: Some(vec![
+                DiagnosticRelatedInformation {
+                    location: Location::new(uri.clone(), finding.write),
+                    message: "written in the goroutine".to_string(),
+                },
+                DiagnosticRelatedInformation {
+                    location: Location::new(uri.clone(), finding.escape),
+                    message: escape,
+                },
+            ]),
+            ..Default::default()
+        }
+    }
+
+    fn goroutine_panic_diagnostic(&self, uri: &Url, finding: &GoroutinePanic) -> Diagnostic {
+        let code = "goroutine-panic";
+        let certain = finding.causes.iter().any(|(_, cause)| cause.is_certain());
+        Diagnostic {
+            range: finding.range,
+            severity: Some(if certain {
+                DiagnosticSeverity::WARNING
+            } else {
+                DiagnosticSeverity::INFORMATION
+            }),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: finding.message(),
+            related_information: Some(
+                finding
+                    .causes
+                    .iter()
+                    .map(|(range, cause)| DiagnosticRelatedInformation {
+                        location: Location::new(uri.clone(), *range),
+                        message: cause.describe().to_string(),
+                    })
+                    .collect(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    fn sleep_synchronization_diagnostic(
+        &self,
+        uri: &Url,
+        finding: &SleepSynchronization,
+    ) -> Diagnostic {
+        let code = "sleep-synchronization";
+        Diagnostic {
+            range: finding.range,
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: finding.message(),
+            related_information: Some(vec![
+                DiagnosticRelatedInformation {
+                    location: Location::new(uri.clone(), finding.write),
+                    message: format!("`{}` written in the goroutine", finding.variable),
+                },
+                DiagnosticRelatedInformation {
+                    location: Location::new(uri.clone(), finding.read),
+                    message: format!("`{}` read after sleeping", finding.variable),
+                },
+            ]),
+            ..Default::default()
+        }
+    }
+
+    fn time_after_diagnostic(&self, uri: &Url, finding: &TimeAfterInLoop) -> Diagnostic {
+        let code = "time-after-in-loop";
+        Diagnostic {
+            range: finding.range,
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: finding.message(),
+            related_information: Some(vec![DiagnosticRelatedInformation {
+                location: Location::new(uri.clone(), finding.loop_range),
+                message: "loop repeating the select".to_string(),
+            }]),
+            ..Default::default()
+        }
+    }
+
+    fn mixed_atomic_diagnostic(&self, uri: &Url, finding: &MixedAtomicAccess) -> Diagnostic {
+        let code = "mixed-atomic-access";
+        Diagnostic {
+            range: finding.plain,
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: finding.message(),
+            related_information: Some(vec![DiagnosticRelatedInformation {
+                location: Location::new(uri.clone(), finding.atomic),
+                message: format!("`{}` is accessed atomically here", finding.variable),
+            }]),
+            ..Default::default()
+        }
+    }
+
+    fn atomic_store_diagnostic(&self, uri: &Url, finding: &InconsistentAtomicStore) -> Diagnostic {
+        let code = "atomic-value-type";
+        Diagnostic {
+            range: finding.range,
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: finding.message(),
+            related_information: Some(vec![DiagnosticRelatedInformation {
+                location: Location::new(uri.clone(), finding.first),
+                message: format!("first stores a `{}`", finding.first_type),
+            }]),
+            ..Default::default()
+        }
+    }
+
+    fn double_close_diagnostic(&self, uri: &Url, finding: &DoubleClose) -> Diagnostic {
+        let code = "channel-double-close";
+        Diagnostic {
+            range: finding.close,
+            severity: Some(if finding.conditional {
+                DiagnosticSeverity::WARNING
+            } else {
+                DiagnosticSeverity::ERROR
+            }),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: finding.message(),
+            related_information: finding.first_close.map(|first| {
+                vec![DiagnosticRelatedInformation {
+                    location: Location::new(uri.clone(), first),
+                    message: format!("`{}` first closed here", finding.channel),
+                }]
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn nil_channel_diagnostic(&self, uri: &Url, finding: &NilChannelOp) -> Diagnostic {
+        let code = "channel-nil";
+        Diagnostic {
+            range: finding.range,
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: finding.message(),
+            related_information: Some(vec![DiagnosticRelatedInformation {
+                location: Location::new(uri.clone(), finding.declaration),
+                message: format!("`{}` declared as a nil channel here", finding.channel),
+            }]),
+            ..Default::default()
+        }
+    }
+
+    fn loop_capture_diagnostic(&self, uri: &Url, finding: &LoopVariableCapture) -> Diagnostic {
+        let code = "loop-variable-capture";
+        Diagnostic {
+            range: finding.range,
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: finding.message(),
+            related_information: Some(vec![DiagnosticRelatedInformation {
+                location: Location::new(uri.clone(), finding.declaration),
+                message: format!("loop variable `{}` declared here", finding.variable),
+            }]),
+            ..Default::default()
+        }
+    }
+
+    fn concurrent_map_diagnostic(&self, uri: &Url, finding: &ConcurrentMapAccess) -> Diagnostic {
+        let code = "concurrent-map-access";
+        let mut related = vec![DiagnosticRelatedInformation {
+            location: Location::new(uri.clone(), finding.declaration),
+            message: format!("map `{}` declared here", finding.map),
+        }];
+        related.extend(
+            finding
+                .others
+                .iter()
+                .map(|other| DiagnosticRelatedInformation {
+                    location: Location::new(uri.clone(), other.range),
+                    message: format!(
+                        "{} concurrently{}",
+                        if other.write { "written" } else { "read" },
+                        if other.guarded { " (under a lock)" } else { "" }
+                    ),
+                }),
+        );
+        Diagnostic {
+            range: finding.access.range,
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: finding.message(),
+            related_information: Some(related),
+            ..Default::default()
+        }
+    }
+
+    fn waitgroup_diagnostic(&self, issue: &WaitGroupIssue) -> Diagnostic {
+        let code = issue.code();
+        Diagnostic {
+            range: issue.range,
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: issue.message(),
+            ..Default::default()
+        }
+    }
+
+    fn lock_value_return_diagnostic(&self, finding: &LockValueReturn) -> Diagnostic {
+        let code = "lock-value-constructor";
+        Diagnostic {
+            range: finding.name_range,
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: format!(
+                "`{}` returns `{}` by value, copying its mutex; return `*{}` instead",
+                finding.function, finding.struct_name, finding.struct_name
+            ),
+            ..Default::default()
+        }
+    }
+
+    fn lock_copy_diagnostic(&self, finding: &LockCopy) -> Diagnostic {
+        let code = "lock-copy";
+        Diagnostic {
+            range: finding.range,
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: finding.message(),
+            ..Default::default()
+        }
+    }
+
+    fn unlock_issue_diagnostic(&self, uri: &Url, issue: &UnlockIssue) -> Diagnostic {
+        let code = issue.code();
+        Diagnostic {
+            range: issue.lock,
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: issue.message(),
+            related_information: issue.exit.map(|exit| {
+                vec![DiagnosticRelatedInformation {
+                    location: Location::new(uri.clone(), exit),
+                    message: format!("leaves with `{}` still locked", issue.mutex),
+                }]
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn cond_misuse_diagnostic(&self, finding: &CondMisuse) -> Diagnostic {
+        let code = finding.code();
+        Diagnostic {
+            range: finding.range,
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: finding.message(),
+            ..Default::default()
+        }
+    }
+
+    fn double_lock_diagnostic(&self, uri: &Url, finding: &DoubleLock) -> Diagnostic {
+        let code = "lock-double-lock";
+        let mut related = vec![DiagnosticRelatedInformation {
+            location: Location::new(uri.clone(), finding.first),
+            message: format!("`{}` locked here", finding.mutex),
+        }];
+        if let Some((callee, lock)) = &finding.callee {
+            related.push(DiagnosticRelatedInformation {
+                location: Location::new(uri.clone(), *lock),
+                message: format!("locked again in `{}`", callee),
+            });
+        }
+        Diagnostic {
+            range: finding.second,
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: finding.message(),
+            related_information: Some(related),
+            ..Default::default()
+        }
+    }
+
+    fn lock_order_diagnostic(&self, uri: &Url, finding: &LockOrderCycle) -> Diagnostic {
+        let code = "lock-order-cycle";
+        let mut related = Vec::new();
+        for edge in &finding.edges {
+            related.push(DiagnosticRelatedInformation {
+                location: Location::new(uri.clone(), edge.held),
+                message: format!("`{}` locks `{}`", edge.function, edge.from),
+            });
+            related.push(DiagnosticRelatedInformation {
+                location: Location::new(uri.clone(), edge.acquired),
+                message: format!("then `{}` while holding `{}`", edge.to, edge.from),
+            });
+            if let Some((callee, lock)) = &edge.callee {
+                related.push(DiagnosticRelatedInformation {
+                    location: Location::new(uri.clone(), *lock),
+                    message: format!("`{}` locked in `{}`", edge.to, callee),
+                });
+            }
+        }
+        let range = finding
+            .edges
+            .first()
+            .map_or_else(Range::default, |edge| edge.acquired);
+        Diagnostic {
+            range,
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(code.to_string())),
+            code_description: self
+                .rule_docs
+                .href(code)
+                .and_then(|href| Url::parse(&href).ok())
+                .map(|href| CodeDescription { href }),
+            source: Some("go-analyzer".to_string()),
+            message: finding.message(),
+            related_information: Some(related),
+            ..Default::default()
+        }
+    }
+
+    fn lock_value_return_fix(&self, finding: &LockValueReturn) -> QuickFix {
+        let mut edits = vec![TextEdit {
+            range: Range::new(finding.result_type.start, finding.result_type.start),
+            new_text: "*".to_string(),
+        }];
+        edits.extend(finding.fixable_returns.iter().map(|expr| TextEdit {
+            range: Range::new(expr.start, expr.start),
+            new_text: "&".to_string(),
+        }));
+        QuickFix {
+            title: format!(
+                "Return `*{}` instead of `{}`",
+                finding.struct_name, finding.struct_name
+            ),
+            targets: vec![finding.name_range, finding.result_type],
+            diagnostic: self.lock_value_return_diagnostic(finding),
+            edits,
+            preferred: true,
+        }
+    }
+
+    fn unlock_issue_fix(&self, uri: &Url, issue: &UnlockIssue) -> Option<QuickFix> {
+        let fix = issue.fix.as_ref()?;
+        let mut edits = vec![TextEdit {
+            range: Range::new(fix.insert_at, fix.insert_at),
+            new_text: fix.insert.clone(),
+        }];
+        edits.extend(fix.removals.iter().map(|range| TextEdit {
+            range: *range,
+            new_text: String::new(),
+        }));
+        Some(QuickFix {
+            title: format!("Use `defer {}.{}()`", issue.mutex, issue.unlock_method),
+            targets: vec![issue.lock],
+            diagnostic: self.unlock_issue_diagnostic(uri, issue),
+            edits,
+            preferred: true,
+        })
+    }
+
+    fn loop_capture_fix(&self, uri: &Url, finding: &LoopVariableCapture) -> Option<QuickFix> {
+        let fix = finding.fix.as_ref()?;
+        Some(QuickFix {
+            title: format!(
+                "Pass `{}` to the goroutine as a parameter",
+                finding.variable
+            ),
+            targets: vec![finding.range],
+            diagnostic: self.loop_capture_diagnostic(uri, finding),
+            edits: vec![
+                TextEdit {
+                    range: Range::new(fix.parameter_at, fix.parameter_at),
+                    new_text: fix.parameter.clone(),
+                },
+                TextEdit {
+                    range: Range::new(fix.argument_at, fix.argument_at),
+                    new_text: fix.argument.clone(),
+                },
+            ],
+            preferred: true,
+        })
+    }
+
+    fn concurrent_map_fix(&self, uri: &Url, finding: &ConcurrentMapAccess) -> QuickFix {
+        QuickFix {
+            title: format!("Guard `{}` with mutex `{}`", finding.map, finding.fix.mutex),
+            targets: vec![finding.access.range],
+            diagnostic: self.concurrent_map_diagnostic(uri, finding),
+            edits: finding
+                .fix
+                .insertions
+                .iter()
+                .map(|(at, text)| TextEdit {
+                    range: Range::new(*at, *at),
+                    new_text: text.clone(),
+                })
+                .collect(),
+            preferred: true,
+        }
+    }
+
+    /// A `time.NewTimer` fix, preferred, and a `time.NewTicker` one, when they apply.
+    fn time_after_fixes(&self, uri: &Url, finding: &TimeAfterInLoop) -> Vec<QuickFix> {
+        let fixes = [
+            (&finding.timer_fix, "Timer", true),
+            (&finding.ticker_fix, "Ticker", false),
+        ];
+        fixes
+            .into_iter()
+            .filter_map(|(fix, kind, preferred)| {
+                let fix = fix.as_ref()?;
+                Some(QuickFix {
+                    title: format!(
+                        "Create `{}` with `time.New{}` before the loop",
+                        fix.name, kind
+                    ),
+                    targets: vec![finding.range],
+                    diagnostic: self.time_after_diagnostic(uri, finding),
+                    edits: text_edits(&fix.edits),
+                    preferred,
+                })
+            })
+            .collect()
+    }
+
+    fn unbounded_spawn_fix(&self, uri: &Url, finding: &UnboundedSpawn) -> Option<QuickFix> {
+        let fix = finding.fix.as_ref()?;
+        Some(QuickFix {
+            title: format!("Limit the goroutines with a `{}` semaphore", fix.name),
+            targets: vec![finding.range],
+            diagnostic: self.unbounded_spawn_diagnostic(uri, finding),
+            edits: text_edits(&fix.edits),
+            preferred: true,
+        })
+    }
+
+    fn goroutine_panic_fix(&self, uri: &Url, finding: &GoroutinePanic) -> QuickFix {
+        QuickFix {
+            title: "Recover and log panics in the goroutine".to_string(),
+            targets: vec![finding.range],
+            diagnostic: self.goroutine_panic_diagnostic(uri, finding),
+            edits: text_edits(&finding.fix),
+            preferred: true,
+        }
+    }
+}
+
+impl Backend {
+    /// What the server caches and how long its analyses have taken, for `goanalyzer/stats`.
+    pub async fn stats(&self) -> ServerStats {
+        let mut caches = self.documents.stats().to_vec();
+        caches.push(CacheStats::new(
+            "decorations",
+            self.decoration_store.lock().await.tracked_documents(),
+        ));
+        caches.push(CacheStats::new(
+            "graphs",
+            self.graph_store.lock().await.tracked_documents(),
+        ));
+        caches.push(CacheStats::new(
+            "graph_pages",
+            self.graph_pages.lock().await.cached_graphs(),
+        ));
+        caches.push(self.analysis_cache.stats());
+        ServerStats {
+            uptime_ms: self
+                .timings
+                .uptime()
+                .as_millis()
+                .try_into()
+                .unwrap_or(u64::MAX),
+            caches,
+            timings: self.timings.snapshot(),
+        }
+    }
+
+    /// Progress notification tagged with the request's trace id.
+    async fn progress(&self, ctx: &RequestContext, message: &str) {
+        self.client
+            .send_notification::<ProgressNotification>(ProgressParams {
+                message: message.to_string(),
+                trace_id: Some(ctx.trace_id.clone()),
+            })
+            .await;
+    }
+
+    async fn log(&self, ctx: &RequestContext, typ: MessageType, message: impl Display) {
+        self.client.log_message(typ, ctx.tag(message)).await;
+    }
+
+    /// `$/setTrace`: the client's trace setting raises or restores the server's log level.
+    pub async fn set_trace(&self, params: SetTraceParams) {
+        crate::logging::set_trace(params.value);
+    }
+
+    /// `textDocument/hover` for the request `ctx`.
+    async fn hover_at(
+        &self,
+        ctx: &RequestContext,
+        params: HoverParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Hover>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let version = self.document_version(&uri).await;
+        let code = match self.get_document(&uri).await {
+            Some(code) => code,
+            None => return Ok(None),
+        };
+
+        let tree = match self.document_tree(ctx, &uri, &code).await {
+            Some(tree) => tree,
+            None => {
+                tracing::warn!(parent: &ctx.span, "Failed to parse document: {}", uri);
+                return Ok(None);
+            }
+        };
+        // The `go` keyword of a goroutine started on every loop iteration explains the risk.
+        let unbounded_spawns = ctx
+            .logged("find_unbounded_spawns", find_unbounded_spawns(&tree, &code))
+            .map_err(|e| ctx.error(e.to_rpc_error()))?;
+        if let Some(spawn) = unbounded_spawns
+            .iter()
+            .find(|spawn| spawn.range.start <= position && position <= spawn.range.end)
+        {
+            let markdown = spawn.hover();
+            self.record_analysis(ctx, &uri, &code, Some(position), &markdown)
+                .await;
+            return Ok(Some(Hover {
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: markdown,
+                }),
+                range: Some(spawn.range),
+            }));
+        }
+        // go/types
+        let (var_info, semantic_type) =
+            if let Some(semantic) = self.semantic.resolve("hover", &uri, position, &code).await {
+                (semantic.info, semantic.type_name)
+            } else {
+                let found = ctx.logged(
+                    "find_variable_at_position",
+                    variable_at(&tree, &code, position),
+                );
+                match found.map_err(|e| ctx.error(e.to_rpc_error()))? {
+                    Some(var_info) => (
+                        self.gopls_declared(&uri, &tree, &code, var_info, position)
+                            .await,
+                        None,
+                    ),
+                    None => return Ok(None),
+                }
+            };
+        // Both look the declaration up in this document.
+        let gopls_type = match semantic_type {
+            Some(_) => None,
+            None if var_info.declared_in.is_some() => None,
+            None => {
+                self.gopls
+                    .type_at(&uri, var_info.declaration.start, &code)
+                    .await
+            }
+        };
+        let type_name = semantic_type.or(gopls_type).or_else(|| {
+            var_info
+                .declared_in
+                .is_none()
+                .then(|| infer_variable_type(&tree, &code, var_info.declaration))
+                .flatten()
+        });
+        let goroutines = analyze_goroutine_usage(&tree, &code, &var_info);
+        let guards: Vec<Option<String>> = var_info
+            .uses
+            .iter()
+            .map(|use_range| guarding_mutex(&tree, *use_range, &code))
+            .collect();
+        let race_severity = || {
+            let writes = var_info
+                .uses
+                .iter()
+                .map(|use_range| is_variable_reassignment(&tree, &var_info.name, *use_range, &code))
+                .collect::<AnalysisResult<Vec<bool>>>()?;
+            let severity = variable_race_severity(&tree, &code, &var_info, &writes)?;
+            Ok((writes, severity))
+        };
+        let (writes, severity) = ctx
+            .logged("variable_race_severity", race_severity())
+            .map_err(|e| ctx.error(e.to_rpc_error()))?;
+        let mut markdown = render_hover(&HoverInput {
+            uri: &uri,
+            code: &code,
+            var_info: &var_info,
+            type_name: type_name.as_deref(),
+            writes: &writes,
+            goroutines: &goroutines,
+            guards: &guards,
+            severity,
+        });
+        if var_info.potential_race {
+            markdown.push_str("**Warning**: Potential data race detected!\n");
+        }
+        if self.document_version(&uri).await != version {
+            // The buffer changed while analyzing; these ranges no longer match it.
+            return Err(ctx.error(tower_lsp::jsonrpc::Error::content_modified()));
+        }
+        self.record_analysis(ctx, &uri, &code, Some(position), &markdown)
+            .await;
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: markdown,
+            }),
+            range: Some(var_info.declaration),
+        }))
+    }
+
+    /// `var_info` as declared where gopls says the identifier at `position` is, when the
+    /// syntax-only lookup picked another variable of the same name, such as a shadowed one.
+    async fn gopls_declared(
+        &self,
+        uri: &Url,
+        tree: &Tree,
+        code: &str,
+        var_info: VariableInfo,
+        position: Position,
+    ) -> VariableInfo {
+        let Some(declaration) = self.gopls.declaration(uri, position, code).await else {
+            return var_info;
+        };
+        if declaration == var_info.declaration.start {
+            return var_info;
+        }
+        match variable_at(tree, code, declaration) {
+            Ok(Some(declared)) if declared.name == var_info.name => declared,
+            _ => var_info,
+        }
+    }
+
+    async fn run_command(
+        &self,
+        ctx: &RequestContext,
         params: ExecuteCommandParams,
     ) -> tower_lsp::jsonrpc::Result<Option<serde_json::Value>> {
         if params.command == "goanalyzer/cursor" {
-            self.client
-                .log_message(MessageType::INFO, "Executing goanalyzer/cursor")
-                .await;
-            self.client
-                .send_notification::<ProgressNotification>("Starting analysis...".to_string())
-                .await;
+            self.progress(ctx, "Starting analysis...").await;
 
             if params.arguments.is_empty() {
-                self.client
-                    .send_notification::<ProgressNotification>("No arguments provided".to_string())
-                    .await;
+                self.progress(ctx, "No arguments provided").await;
                 return Ok(None);
             }
 
-            #[derive(Deserialize)]
-            struct CursorCommandParams {
-                #[serde(rename = "textDocument")]
-                text_document: TextDocumentIdentifier,
-                position: Position,
-                source: Option<String>,
-                dump_json: Option<bool>,
-            }
-
             let args: CursorCommandParams = match params
                 .arguments
                 .first()
@@ -524,9 +2876,7 @@ impl LanguageServer for Backend {
                 }) {
                 Ok(args) => args,
                 Err(e) => {
-                    self.client
-                        .send_notification::<ProgressNotification>("Invalid arguments".to_string())
-                        .await;
+                    self.progress(ctx, "Invalid arguments").await;
                     return Err(e);
                 }
             };
@@ -535,36 +2885,43 @@ impl LanguageServer for Backend {
             let position = args.position;
             let source = args.source;
             let dump_json = args.dump_json.unwrap_or(false);
+            let delta = args.delta.unwrap_or(false);
+            let version = self.document_version(&uri).await;
+            if args.version.is_some() && args.version != version {
+                return Err(tower_lsp::jsonrpc::Error::content_modified());
+            }
+            self.cursor_positions
+                .lock()
+                .await
+                .insert(uri.clone(), position);
             let code = match self.get_document(&uri).await {
                 Some(code) => code,
                 None => {
-                    self.client
-                        .send_notification::<ProgressNotification>(
-                            "No document found or expired".to_string(),
-                        )
-                        .await;
+                    self.progress(ctx, "No document found or expired").await;
                     return Ok(None);
                 }
             };
 
-            let (tree, cache_hit, parse_ms) = match self.get_tree_from_cache(&uri).await {
+            let (tree, cache_hit, parse) = match self.get_tree_from_cache(&uri).await {
                 Some(tree) => (tree, true, None),
                 None => {
                     let start = Instant::now();
                     let parsed = match self.parse_document_with_cache(&uri, &code).await {
                         Some(tree) => tree,
                         None => {
-                            self.client
-                                .send_notification::<ProgressNotification>(
-                                    "Failed to parse document".to_string(),
-                                )
-                                .await;
+                            self.progress(ctx, "Failed to parse document").await;
                             return Ok(None);
                         }
                     };
-                    (parsed, false, Some(start.elapsed().as_millis()))
+                    (parsed, false, Some(start.elapsed()))
                 }
             };
+            ctx.perf.document(DocumentSample {
+                uri: uri.to_string(),
+                code_len: code.len(),
+                tree: tree.clone(),
+                parse,
+            });
 
             let _ = self
                 .client
@@ -572,519 +2929,218 @@ impl LanguageServer for Backend {
                     uri: uri.to_string(),
                     source,
                     cache_hit,
-                    parse_ms,
+                    parse_ms: parse.map(|parse| parse.as_millis()),
                     code_len: code.len(),
                 })
                 .await;
 
-            let mut semantic_uses = None;
-            let mut var_info = if let Some(semantic) =
-                resolve_semantic_variable(&self.semantic, &uri, position, &code).await
+            let (mut decorations, lifecycle_points, truncated) = match self
+                .analyze_cursor(ctx, &uri, &code, &tree, position, dump_json)
+                .await
             {
-                semantic_uses = Some(semantic.uses);
-                semantic.info
-            } else {
-                match std::panic::catch_unwind(|| {
-                    find_variable_at_position_enhanced(&tree, &code, position)
-                        .or_else(|| find_variable_at_position(&tree, &code, position))
-                }) {
-                    Ok(Some(var_info)) => var_info,
-                    Ok(None) => {
-                        self.client
-                            .send_notification::<ProgressNotification>(
-                                "No variable found".to_string(),
-                            )
+                Ok(CursorAnalysis::Found {
+                    decorations,
+                    lifecycle_points,
+                    truncated,
+                }) => (decorations, lifecycle_points, truncated),
+                Ok(CursorAnalysis::NoVariable) => {
+                    self.progress(ctx, "No variable found").await;
+                    if delta {
+                        self.publish_decorations_delta(&uri, version, &mut Vec::new(), false)
                             .await;
-                        return Ok(None);
-                    }
-                    Err(e) => {
-                        eprintln!("Panic occurred in find_variable_at_position: {:?}", e);
-                        self.client
-                            .send_notification::<ProgressNotification>("Analysis error".to_string())
-                            .await;
-                        return Ok(None);
                     }
+                    return Ok(None);
                 }
-            };
-
-            let mut decorations = vec![];
-            let mut lifecycle_points: Vec<LifecyclePoint> = Vec::new();
-            let sync_funcs = crate::analysis::collect_sync_functions(&tree, &code);
-            let is_decl_global = {
-                let mut is_global = true;
-                let decl_point = Point {
-                    row: var_info.declaration.start.line as usize,
-                    column: var_info.declaration.start.character as usize,
-                };
-                if let Some(mut node) = tree
-                    .root_node()
-                    .descendant_for_point_range(decl_point, decl_point)
-                {
-                    loop {
-                        let kind = node.kind();
-                        if kind == "function_declaration"
-                            || kind == "method_declaration"
-                            || kind == "func_literal"
-                        {
-                            is_global = false;
-                            break;
-                        }
-                        if let Some(parent) = node.parent() {
-                            node = parent;
-                        } else {
-                            break;
-                        }
-                    }
+                Err(e) => {
+                    self.progress(ctx, "Analysis error").await;
+                    return Err(e.to_rpc_error());
                 }
-                is_global
             };
-
-            decorations.push(Decoration {
-                range: var_info.declaration,
-                kind: DecorationType::Declaration,
-                hover_text: format!("Declaration of `{}`", var_info.name),
-                diagnostic: None,
-            });
-
-            if dump_json {
-                let decl_kind = DecorationType::Declaration;
-                lifecycle_points.push(LifecyclePoint {
-                    name: format!("{}_decl", var_info.name),
-                    file: uri.to_string(),
-                    pos: LifecyclePos {
-                        line: var_info.declaration.start.line,
-                        col: var_info.declaration.start.character,
-                    },
-                    expected: LifecycleExpected {
-                        var: var_info.name.clone(),
-                        kind: "decl".to_string(),
-                        pointer: var_info.is_pointer,
-                        reassign: false,
-                        captured: false,
-                        decoration: decoration_label(&decl_kind).to_string(),
-                        color_key: decoration_color_key(&decl_kind).to_string(),
-                    },
-                });
+            if self.document_version(&uri).await != version {
+                return Err(tower_lsp::jsonrpc::Error::content_modified());
             }
-
-            let use_metas: Vec<UseMeta> = if let Some(uses) = semantic_uses.take() {
-                uses.into_iter()
-                    .map(|u| UseMeta {
-                        range: u.range,
-                        reassign: u.reassign,
-                        captured: u.captured,
-                    })
-                    .collect()
+            if delta {
+                self.publish_decorations_delta(&uri, version, &mut decorations, truncated)
+                    .await;
             } else {
-                var_info
-                    .uses
-                    .iter()
-                    .map(|use_range| {
-                        let reassign = match std::panic::catch_unwind(|| {
-                            crate::analysis::is_variable_reassignment(
-                                &tree,
-                                &var_info.name,
-                                *use_range,
-                                &code,
-                            )
-                        }) {
-                            Ok(result) => result,
-                            Err(e) => {
-                                eprintln!("Panic occurred in is_variable_reassignment: {:?}", e);
-                                false
-                            }
-                        };
-                        let captured = if reassign {
-                            false
-                        } else {
-                            match std::panic::catch_unwind(|| {
-                                crate::analysis::is_variable_captured(
-                                    &tree,
-                                    &var_info.name,
-                                    *use_range,
-                                    var_info.declaration,
-                                )
-                            }) {
-                                Ok(result) => result,
-                                Err(e) => {
-                                    eprintln!("Panic occurred in is_variable_captured: {:?}", e);
-                                    false
-                                }
-                            }
-                        };
-                        UseMeta {
-                            range: *use_range,
-                            reassign,
-                            captured,
-                        }
-                    })
-                    .collect()
+                self.decoration_store
+                    .lock()
+                    .await
+                    .update(&uri, version, &mut decorations);
+            }
+            self.record_analysis(ctx, &uri, &code, Some(position), &decorations)
+                .await;
+            let value = match serde_json::to_value(&decorations) {
+                Ok(value) => value,
+                Err(e) => {
+                    tracing::error!(parent: &ctx.span, "Failed to serialize decorations: {}", e);
+                    self.progress(ctx, "Serialization error").await;
+                    return Err(tower_lsp::jsonrpc::Error::internal_error());
+                }
             };
-
-            let is_field_symbol = is_struct_field_declaration(&tree, var_info.declaration);
-            let field_type_kind = if is_field_symbol {
-                field_type_kind_at_declaration(&tree, var_info.declaration, &code)
+            if truncated {
+                self.progress(ctx, "Analysis truncated: time budget exceeded")
+                    .await;
             } else {
-                FieldTypeKind::Other
-            };
-            let mut atomic_map: HashMap<String, bool> = HashMap::new();
-            let mut sync_map: HashMap<String, bool> = HashMap::new();
-            let mut heavy_map: HashMap<String, bool> = HashMap::new();
-            let mut saw_atomic = false;
-            let mut saw_non_atomic = false;
-            let mut saw_sync = false;
-            let mut saw_unsync = false;
-
-            if is_field_symbol {
-                for use_meta in &use_metas {
-                    let key = format!(
-                        "{}:{}:{}:{}",
-                        use_meta.range.start.line,
-                        use_meta.range.start.character,
-                        use_meta.range.end.line,
-                        use_meta.range.end.character
-                    );
-                    let in_atomic: bool = std::panic::catch_unwind(|| {
-                        is_access_in_atomic_context(&tree, use_meta.range, &code)
-                    })
-                    .unwrap_or_default();
-                    let in_sync: bool = std::panic::catch_unwind(|| {
-                        is_access_synchronized_at(&tree, use_meta.range, &code, &sync_funcs)
-                    })
-                    .unwrap_or_default();
-                    let heavy_under_lock = in_sync
-                        && std::panic::catch_unwind(|| {
-                            is_heavy_work_in_call_context(&tree, use_meta.range, &code)
-                        })
-                        .unwrap_or_default();
-
-                    atomic_map.insert(key.clone(), in_atomic);
-                    sync_map.insert(key.clone(), in_sync);
-                    heavy_map.insert(key, heavy_under_lock);
-
-                    if in_atomic {
-                        saw_atomic = true;
-                    } else {
-                        saw_non_atomic = true;
-                    }
-                    if in_sync {
-                        saw_sync = true;
-                    } else {
-                        saw_unsync = true;
-                    }
-                }
+                self.progress(ctx, "Analysis complete").await;
             }
-
-            let has_mixed_atomic = is_field_symbol && saw_atomic && saw_non_atomic;
-            let has_lock_coverage_violation = is_field_symbol && saw_sync && saw_unsync;
-            let mut read_before_write_keys: HashSet<String> = HashSet::new();
-            if is_field_symbol {
-                let mut by_context: HashMap<(u32, u32, u32, u32), Vec<UseMeta>> = HashMap::new();
-                for use_meta in &use_metas {
-                    if let Some(ctx) = access_context_key(&tree, use_meta.range) {
-                        by_context.entry(ctx).or_default().push(use_meta.clone());
-                    }
-                }
-                for items in by_context.values_mut() {
-                    items.sort_by_key(|u| (u.range.start.line, u.range.start.character));
-                    let first_write_idx = items.iter().position(|u| u.reassign);
-                    if let Some(write_idx) = first_write_idx {
-                        if items.iter().any(|u| !u.reassign) {
-                            for item in items.iter().take(write_idx) {
-                                if !item.reassign {
-                                    let key = format!(
-                                        "{}:{}:{}:{}",
-                                        item.range.start.line,
-                                        item.range.start.character,
-                                        item.range.end.line,
-                                        item.range.end.character
-                                    );
-                                    read_before_write_keys.insert(key);
-                                }
-                            }
-                        }
-                    }
-                }
+            if dump_json {
+                let _ = self
+                    .client
+                    .send_notification::<LifecycleDumpNotification>(LifecycleDumpParams {
+                        uri: uri.to_string(),
+                        points: lifecycle_points,
+                    })
+                    .await;
             }
-            let field_write_only =
-                is_field_symbol && use_metas.len() >= 2 && use_metas.iter().all(|u| u.reassign);
-            let has_read_before_write = !read_before_write_keys.is_empty();
-            let is_struct_value_candidate = !is_field_symbol && !var_info.is_pointer;
-            let mut emitted_mixed_atomic = false;
-            let mut emitted_lock_coverage = false;
-            let mut emitted_heavy_under_lock = false;
-            let mut emitted_retention = false;
-            let mut emitted_large_copy = false;
-            let mut emitted_read_before_write = false;
-            let mut emitted_write_only = false;
-            for use_meta in use_metas {
-                let use_range = use_meta.range;
-                let is_reassignment = use_meta.reassign;
-                let is_captured = use_meta.captured;
-                let key = format!(
-                    "{}:{}:{}:{}",
-                    use_range.start.line,
-                    use_range.start.character,
-                    use_range.end.line,
-                    use_range.end.character
-                );
-                let in_atomic = atomic_map.get(&key).copied().unwrap_or(false);
-                let in_sync = sync_map.get(&key).copied().unwrap_or(false);
-                let heavy_under_lock = heavy_map.get(&key).copied().unwrap_or(false);
-                let mut decoration_kind = if var_info.is_pointer {
-                    DecorationType::Pointer
-                } else {
-                    DecorationType::Use
-                };
-                let mut hover_text = format!("Use of `{}`", var_info.name);
-                let mut diagnostic: Option<DecorationDiagnostic> = None;
-                if is_reassignment {
-                    decoration_kind = DecorationType::AliasReassigned;
-                    hover_text = format!("Reassignment of `{}`", var_info.name);
-                } else if is_captured {
-                    decoration_kind = DecorationType::AliasCaptured;
-                    hover_text = format!("Captured `{}` in closure/goroutine", var_info.name);
-                }
-                let is_in_goroutine_result: bool =
-                    std::panic::catch_unwind(|| is_in_goroutine(&tree, use_range))
-                        .unwrap_or_default();
-
-                if !is_captured && is_in_goroutine_result && (is_decl_global || is_field_symbol) {
-                    let race_access = if is_reassignment {
-                        "write access"
-                    } else {
-                        "read access"
-                    };
-                    let race_severity = match std::panic::catch_unwind(|| {
-                        determine_race_severity(
-                            &tree,
-                            use_range,
-                            &code,
-                            is_reassignment,
-                            &sync_funcs,
-                        )
-                    }) {
-                        Ok(severity) => severity,
-                        Err(_) => RaceSeverity::Medium,
-                    };
-                    var_info.race_severity = race_severity.clone();
-                    match race_severity {
-                        crate::types::RaceSeverity::High => {
-                            decoration_kind = DecorationType::Race;
-                            hover_text = format!(
-                                "Use of `{}` in goroutine - HIGH PRIORITY data race ({})",
-                                var_info.name, race_access
-                            );
-                            diagnostic = Some(make_diagnostic(
-                                DecorationDiagnosticSeverity::Warning,
-                                "field-race-high",
-                                format!(
-                                    "Potential data race on `{}` in goroutine ({})",
-                                    var_info.name, race_access
-                                ),
-                            ));
-                        }
-                        crate::types::RaceSeverity::Medium => {
-                            decoration_kind = DecorationType::Race;
-                            hover_text = format!(
-                                "Use of `{}` in goroutine - potential data race ({})",
-                                var_info.name, race_access
-                            );
-                        }
-                        crate::types::RaceSeverity::Low => {
-                            decoration_kind = DecorationType::RaceLow;
-                            hover_text = format!(
-                                "Use of `{}` in goroutine - LOW PRIORITY (sync detected, {})",
-                                var_info.name, race_access
-                            );
-                        }
-                    }
-                    var_info.potential_race = true;
-                }
-                if is_field_symbol {
-                    if has_mixed_atomic {
-                        hover_text = format!(
-                            "{} | mixed atomic/non-atomic access detected for field `{}`",
-                            hover_text, var_info.name
-                        );
-                        if !in_atomic && !emitted_mixed_atomic && diagnostic.is_none() {
-                            diagnostic = Some(make_diagnostic(
-                                DecorationDiagnosticSeverity::Warning,
-                                "field-mixed-atomic",
-                                format!(
-                                    "Field `{}` is accessed both atomically and non-atomically",
-                                    var_info.name
-                                ),
-                            ));
-                            emitted_mixed_atomic = true;
-                        }
-                    }
-                    if has_lock_coverage_violation
-                        && !in_sync
-                        && !emitted_lock_coverage
-                        && diagnostic.is_none()
-                    {
-                        hover_text = format!(
-                            "{} | lock coverage violation for field `{}`",
-                            hover_text, var_info.name
-                        );
-                        diagnostic = Some(make_diagnostic(
-                            DecorationDiagnosticSeverity::Warning,
-                            "field-lock-coverage",
-                            format!(
-                                "Field `{}` has mixed synchronized/unsynchronized access",
-                                var_info.name
-                            ),
-                        ));
-                        emitted_lock_coverage = true;
-                    }
-                    if heavy_under_lock && !emitted_heavy_under_lock && diagnostic.is_none() {
-                        hover_text = format!(
-                            "{} | heavy call under lock while touching `{}`",
-                            hover_text, var_info.name
-                        );
-                        diagnostic = Some(make_diagnostic(
-                            DecorationDiagnosticSeverity::Information,
-                            "field-heavy-under-lock",
-                            format!(
-                                "Heavy operation under lock for field `{}` may hurt throughput",
-                                var_info.name
-                            ),
-                        ));
-                        emitted_heavy_under_lock = true;
-                    }
-                    if is_in_goroutine_result && !in_sync {
-                        hover_text = format!(
-                            "{} | captured field access in goroutine without active lock",
-                            hover_text
-                        );
-                    }
-                    if !emitted_retention {
-                        if let Some(retention_msg) =
-                            detect_retention_pattern(&tree, use_range, field_type_kind)
-                        {
-                            hover_text = format!("{} | {}", hover_text, retention_msg);
-                            if diagnostic.is_none() {
-                                diagnostic = Some(make_diagnostic(
-                                    DecorationDiagnosticSeverity::Information,
-                                    "field-retention",
-                                    format!("{}: `{}`", retention_msg, var_info.name),
-                                ));
-                                emitted_retention = true;
-                            }
-                        }
-                    }
-                    if field_write_only {
-                        hover_text = format!(
-                            "{} | field appears write-only in current file scope",
-                            hover_text
-                        );
-                        if !emitted_write_only && diagnostic.is_none() {
-                            diagnostic = Some(make_diagnostic(
-                                DecorationDiagnosticSeverity::Information,
-                                "field-write-only",
-                                format!("Field `{}` appears write-only", var_info.name),
-                            ));
-                            emitted_write_only = true;
-                        }
-                    } else if has_read_before_write
-                        && read_before_write_keys.contains(&key)
-                        && !is_reassignment
-                    {
-                        hover_text = format!(
-                            "{} | read-before-write pattern detected in current file scope",
-                            hover_text
-                        );
-                        if !emitted_read_before_write && diagnostic.is_none() {
-                            diagnostic = Some(make_diagnostic(
-                                DecorationDiagnosticSeverity::Warning,
-                                "field-read-before-write",
-                                format!(
-                                    "Field `{}` is read before first write in this execution context",
-                                    var_info.name
-                                ),
-                            ));
-                            emitted_read_before_write = true;
-                        }
-                    }
-                }
-                if is_struct_value_candidate
-                    && !is_reassignment
-                    && !emitted_large_copy
-                    && std::panic::catch_unwind(|| is_value_copy_context(&tree, use_range, &code))
-                        .unwrap_or_default()
-                {
-                    hover_text = format!("{} | potential large struct copy by value", hover_text);
-                    if diagnostic.is_none() {
-                        diagnostic = Some(make_diagnostic(
-                            DecorationDiagnosticSeverity::Information,
-                            "struct-large-copy",
-                            format!(
-                                "Potential large struct copy by value for `{}`",
-                                var_info.name
-                            ),
-                        ));
-                        emitted_large_copy = true;
-                    }
-                }
-                let decoration_label_text = decoration_label(&decoration_kind).to_string();
-                let decoration_color = decoration_color_key(&decoration_kind).to_string();
-                decorations.push(Decoration {
-                    range: use_range,
-                    kind: decoration_kind,
-                    hover_text,
-                    diagnostic,
-                });
-                if dump_json {
-                    lifecycle_points.push(LifecyclePoint {
-                        name: format!("{}_use_{}", var_info.name, lifecycle_points.len()),
-                        file: uri.to_string(),
-                        pos: LifecyclePos {
-                            line: use_range.start.line,
-                            col: use_range.start.character,
-                        },
-                        expected: LifecycleExpected {
-                            var: var_info.name.clone(),
-                            kind: "use".to_string(),
-                            pointer: var_info.is_pointer,
-                            reassign: is_reassignment,
-                            captured: is_captured,
-                            decoration: decoration_label_text,
-                            color_key: decoration_color,
-                        },
-                    });
-                }
+            if delta {
+                return Ok(None);
             }
-            let value = match serde_json::to_value(&decorations) {
-                Ok(value) => value,
+            return Ok(Some(value));
+        } else if params.command == "goanalyzer/resyncDecorations" {
+            let args: TextDocumentIdentifier = params
+                .arguments
+                .first()
+                .ok_or_else(|| {
+                    tower_lsp::jsonrpc::Error::invalid_params("Missing arguments".to_string())
+                })
+                .and_then(|arg| {
+                    serde_json::from_value(arg.clone()).map_err(|e| {
+                        tower_lsp::jsonrpc::Error::invalid_params(format!(
+                            "Invalid arguments: {}",
+                            e
+                        ))
+                    })
+                })?;
+            let snapshot = self.decoration_store.lock().await.snapshot(&args.uri);
+            self.client
+                .send_notification::<DecorationsDeltaNotification>(snapshot)
+                .await;
+            return Ok(None);
+        } else if params.command == "goanalyzer/debug.dumpSession" {
+            let dump = self.recorder.dump().await;
+            let value = serde_json::to_value(dump)
+                .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+            return Ok(Some(value));
+        } else if params.command == "goanalyzer/workspaceSummary" {
+            let args: WorkspaceSummaryParams = match params.arguments.first() {
+                Some(arg) => serde_json::from_value(arg.clone()).map_err(|e| {
+                    tower_lsp::jsonrpc::Error::invalid_params(format!("Invalid arguments: {}", e))
+                })?,
+                None => WorkspaceSummaryParams::default(),
+            };
+            let roots = match args.root {
+                Some(root) => vec![PathBuf::from(root)],
+                None => self.workspace_roots.lock().await.clone(),
+            };
+            let summary = self
+                .workspace_summary(ctx, &roots, args.sort_by.unwrap_or_default())
+                .await;
+            let value = serde_json::to_value(summary)
+                .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+            return Ok(Some(value));
+        } else if params.command == "goanalyzer/doctor" {
+            let roots = self.workspace_roots.lock().await.clone();
+            let cache_dir = self.recorder.dir().to_path_buf();
+            let report = run_doctor(&self.semantic.config, &roots, &cache_dir);
+            let value = serde_json::to_value(report)
+                .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+            return Ok(Some(value));
+        } else if params.command == "goanalyzer/runRaceDetector" {
+            let args: RaceDetectorParams = match params.arguments.first() {
+                Some(arg) => serde_json::from_value(arg.clone()).map_err(|e| {
+                    tower_lsp::jsonrpc::Error::invalid_params(format!("Invalid arguments: {}", e))
+                })?,
+                None => RaceDetectorParams::default(),
+            };
+            let root = match args.root {
+                Some(root) => Some(PathBuf::from(root)),
+                None => self.workspace_roots.lock().await.first().cloned(),
+            };
+            let Some(root) = root else {
+                self.progress(ctx, "No workspace folder to test").await;
+                return Ok(None);
+            };
+            let package = args.package.unwrap_or_else(|| "./...".to_string());
+            self.progress(ctx, &format!("Running go test -race {}...", package))
+                .await;
+            let run = match run_go_test_race(&root, &package, &self.race_detector).await {
+                Ok(run) => run,
                 Err(e) => {
-                    eprintln!("Failed to serialize decorations: {}", e);
-                    self.client
-                        .send_notification::<ProgressNotification>(
-                            "Serialization error".to_string(),
-                        )
+                    self.progress(ctx, &format!("Failed to run go test -race: {}", e))
                         .await;
-                    return Err(tower_lsp::jsonrpc::Error::internal_error());
+                    return Ok(None);
                 }
             };
-            self.client
-                .send_notification::<ProgressNotification>("Analysis complete".to_string())
+            let races = parse_race_output(&run.output);
+            let diagnostics = self
+                .replace_toolchain_diagnostics(
+                    CONFIRMED_RACE,
+                    race_diagnostics(&races, &root),
+                    |_| true,
+                )
                 .await;
-            if dump_json {
-                let _ = self
-                    .client
-                    .send_notification::<LifecycleDumpNotification>(LifecycleDumpParams {
-                        uri: uri.to_string(),
-                        points: lifecycle_points,
+            self.progress(
+                ctx,
+                &format!("Race detector: {} race(s) found", races.len()),
+            )
+            .await;
+            let report = RaceDetectorReport {
+                passed: run.passed,
+                output: (!run.passed && races.is_empty()).then(|| output_tail(&run.output)),
+                races,
+                diagnostics,
+            };
+            let value = serde_json::to_value(report)
+                .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+            return Ok(Some(value));
+        } else if params.command == "goanalyzer/stats" {
+            let value = serde_json::to_value(self.stats().await)
+                .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+            return Ok(Some(value));
+        } else if params.command == "goanalyzer/protocolSchema" {
+            return Ok(Some(protocol_schema()));
+        } else if params.command == "goanalyzer/graph"
+            || params.command == "goanalyzer/graph.export"
+        {
+            let args: GraphParams = params
+                .arguments
+                .first()
+                .ok_or_else(|| {
+                    tower_lsp::jsonrpc::Error::invalid_params("Missing arguments".to_string())
+                })
+                .and_then(|arg| {
+                    serde_json::from_value(arg.clone()).map_err(|e| {
+                        tower_lsp::jsonrpc::Error::invalid_params(format!(
+                            "Invalid arguments: {}",
+                            e
+                        ))
                     })
+                })?;
+            let Some(graph) = self.scoped_graph(ctx, &args.uri, args.scope).await else {
+                return Ok(None);
+            };
+            if params.command == "goanalyzer/graph" && args.scope == GraphScope::File {
+                self.graph_store
+                    .lock()
+                    .await
+                    .track(&args.uri, graph.clone());
+            }
+            if params.command == "goanalyzer/graph.export" {
+                self.progress(ctx, "Graph exported").await;
+                return Ok(Some(serde_json::Value::String(args.format.render(&graph))));
+            }
+            let value = serde_json::to_value(&graph)
+                .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+            if graph.truncated {
+                self.progress(ctx, "Graph truncated: time budget exceeded")
                     .await;
+            } else {
+                self.progress(ctx, "Graph built").await;
             }
             return Ok(Some(value));
-        } else if params.command == "goanalyzer/graph" {
-            self.client
-                .log_message(MessageType::INFO, "Executing goanalyzer/graph")
-                .await;
-            let args: TextDocumentIdentifier = params
+        } else if params.command == "goanalyzer/graph.mermaid" {
+            let args: MermaidParams = params
                 .arguments
                 .first()
                 .ok_or_else(|| {
@@ -1102,39 +3158,172 @@ impl LanguageServer for Backend {
             let code = match self.get_document(&uri).await {
                 Some(code) => code,
                 None => {
-                    self.client
-                        .send_notification::<ProgressNotification>(
-                            "No document found or expired".to_string(),
-                        )
-                        .await;
+                    self.progress(ctx, "No document found or expired").await;
+                    return Ok(None);
+                }
+            };
+            let tree = match self.document_tree(ctx, &uri, &code).await {
+                Some(tree) => tree,
+                None => {
+                    self.progress(ctx, "Failed to parse document").await;
+                    return Ok(None);
+                }
+            };
+            let graph = args.filter.apply(&build_file_graph(
+                &tree,
+                &code,
+                graph_file_name(&uri),
+                &Default::default(),
+            ));
+            self.progress(ctx, "Graph exported").await;
+            return Ok(Some(serde_json::Value::String(graph_to_mermaid(&graph))));
+        } else if params.command == "goanalyzer/graph.page" {
+            let args: GraphPageParams = params
+                .arguments
+                .first()
+                .ok_or_else(|| {
+                    tower_lsp::jsonrpc::Error::invalid_params("Missing arguments".to_string())
+                })
+                .and_then(|arg| {
+                    serde_json::from_value(arg.clone()).map_err(|e| {
+                        tower_lsp::jsonrpc::Error::invalid_params(format!(
+                            "Invalid arguments: {}",
+                            e
+                        ))
+                    })
+                })?;
+            let limit = args.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+            let version = self.document_version(&args.uri).await;
+            let page = match &args.cursor {
+                Some(cursor) => self
+                    .graph_pages
+                    .lock()
+                    .await
+                    .page(&args.uri, args.scope, version, cursor, limit)
+                    .map_err(tower_lsp::jsonrpc::Error::invalid_params)?,
+                None => {
+                    let Some(graph) = self.scoped_graph(ctx, &args.uri, args.scope).await else {
+                        return Ok(None);
+                    };
+                    self.graph_pages
+                        .lock()
+                        .await
+                        .first_page(&args.uri, args.scope, version, graph, limit)
+                }
+            };
+            let value = serde_json::to_value(&page)
+                .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+            return Ok(Some(value));
+        } else if params.command == "goanalyzer/graphForFunction" {
+            let args: FunctionGraphParams = params
+                .arguments
+                .first()
+                .ok_or_else(|| {
+                    tower_lsp::jsonrpc::Error::invalid_params("Missing arguments".to_string())
+                })
+                .and_then(|arg| {
+                    serde_json::from_value(arg.clone()).map_err(|e| {
+                        tower_lsp::jsonrpc::Error::invalid_params(format!(
+                            "Invalid arguments: {}",
+                            e
+                        ))
+                    })
+                })?;
+            let uri = args.uri;
+            let code = match self.get_document(&uri).await {
+                Some(code) => code,
+                None => {
+                    self.progress(ctx, "No document found or expired").await;
+                    return Ok(None);
+                }
+            };
+            let tree = match self.document_tree(ctx, &uri, &code).await {
+                Some(tree) => tree,
+                None => {
+                    self.progress(ctx, "Failed to parse document").await;
                     return Ok(None);
                 }
             };
-            let tree = self.get_tree_from_cache(&uri).await.or_else(|| {
-                futures::executor::block_on(self.parse_document_with_cache(&uri, &code))
+            let label = match (args.name, args.position) {
+                (Some(name), _) => Some(name),
+                (None, Some(position)) => function_label_at(&tree, &code, position),
+                (None, None) => None,
+            };
+            let subgraph = label.and_then(|label| {
+                let graph =
+                    build_file_graph(&tree, &code, graph_file_name(&uri), &Default::default());
+                function_subgraph(&graph, &label)
             });
-            let tree = match tree {
+            let Some(subgraph) = subgraph else {
+                self.progress(ctx, "No function found").await;
+                return Ok(None);
+            };
+            let value = serde_json::to_value(&subgraph)
+                .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+            self.progress(ctx, "Function graph built").await;
+            return Ok(Some(value));
+        } else if params.command == "goanalyzer/graphQuery" {
+            let args: GraphQueryParams = params
+                .arguments
+                .first()
+                .ok_or_else(|| {
+                    tower_lsp::jsonrpc::Error::invalid_params("Missing arguments".to_string())
+                })
+                .and_then(|arg| {
+                    serde_json::from_value(arg.clone()).map_err(|e| {
+                        tower_lsp::jsonrpc::Error::invalid_params(format!(
+                            "Invalid arguments: {}",
+                            e
+                        ))
+                    })
+                })?;
+            let Some(graph) = self.scoped_graph(ctx, &args.uri, args.scope).await else {
+                return Ok(None);
+            };
+            let result = run_query(&graph, &args.query);
+            let value = serde_json::to_value(&result)
+                .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+            self.progress(ctx, "Graph query answered").await;
+            return Ok(Some(value));
+        } else if params.command == "goanalyzer/channelFlow" {
+            let args: TextDocumentPositionParams = params
+                .arguments
+                .first()
+                .ok_or_else(|| {
+                    tower_lsp::jsonrpc::Error::invalid_params("Missing arguments".to_string())
+                })
+                .and_then(|arg| {
+                    serde_json::from_value(arg.clone()).map_err(|e| {
+                        tower_lsp::jsonrpc::Error::invalid_params(format!(
+                            "Invalid arguments: {}",
+                            e
+                        ))
+                    })
+                })?;
+            let uri = args.text_document.uri;
+            let code = match self.get_document(&uri).await {
+                Some(code) => code,
+                None => {
+                    self.progress(ctx, "No document found or expired").await;
+                    return Ok(None);
+                }
+            };
+            let tree = match self.document_tree(ctx, &uri, &code).await {
                 Some(tree) => tree,
                 None => {
-                    self.client
-                        .send_notification::<ProgressNotification>(
-                            "Failed to parse document".to_string(),
-                        )
-                        .await;
+                    self.progress(ctx, "Failed to parse document").await;
                     return Ok(None);
                 }
             };
-            let graph = build_graph_data(&tree, &code);
-            let value = serde_json::to_value(&graph)
+            let Some(flow) = channel_flow(&tree, &code, args.position) else {
+                self.progress(ctx, "No channel at the cursor").await;
+                return Ok(None);
+            };
+            let value = serde_json::to_value(&flow)
                 .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
-            self.client
-                .send_notification::<ProgressNotification>("Graph built".to_string())
-                .await;
+            self.progress(ctx, "Channel flow built").await;
             return Ok(Some(value));
         } else if params.command == "goanalyzer/ast" {
-            self.client
-                .log_message(MessageType::INFO, "Executing goanalyzer/ast")
-                .await;
             let args: TextDocumentIdentifier = params
                 .arguments
                 .first()
@@ -1153,27 +3342,16 @@ impl LanguageServer for Backend {
             let code = match self.get_document(&uri).await {
                 Some(code) => code,
                 None => {
-                    self.client
-                        .send_notification::<ProgressNotification>(
-                            "No document found or expired".to_string(),
-                        )
-                        .await;
+                    self.progress(ctx, "No document found or expired").await;
                     return Ok(None);
                 }
             };
-            let tree = match self.get_tree_from_cache(&uri).await {
+            let tree = match self.document_tree(ctx, &uri, &code).await {
                 Some(tree) => tree,
-                None => match self.parse_document_with_cache(&uri, &code).await {
-                    Some(tree) => tree,
-                    None => {
-                        self.client
-                            .send_notification::<ProgressNotification>(
-                                "Failed to parse document".to_string(),
-                            )
-                            .await;
-                        return Ok(None);
-                    }
-                },
+                None => {
+                    self.progress(ctx, "Failed to parse document").await;
+                    return Ok(None);
+                }
             };
             let sexp = tree.root_node().to_sexp();
             let value = serde_json::to_value(sexp)
@@ -1183,3 +3361,328 @@ impl LanguageServer for Backend {
         Ok(None)
     }
 }
+
+/// A session that ends without `shutdown`, on a signal or a dropped connection, still leaves
+/// the session record flushed: dropping the recorder flushes it.
+impl Drop for Backend {
+    fn drop(&mut self) {
+        self.analysis.close();
+        self.package_builds.cancel_all();
+    }
+}
+
+#[tower_lsp::async_trait]
+impl LanguageServer for Backend {
+    async fn initialize(
+        &self,
+        params: InitializeParams,
+    ) -> tower_lsp::jsonrpc::Result<InitializeResult> {
+        if let Some(trace) = params.trace {
+            crate::logging::set_trace(trace);
+        }
+        let folders = params
+            .workspace_folders
+            .map(|folders| folders.into_iter().map(|folder| folder.uri).collect())
+            .or_else(|| params.root_uri.map(|root| vec![root]))
+            .unwrap_or_default();
+        self.gopls.set_root(folders.first().cloned());
+        *self.workspace_roots.lock().await = folders
+            .iter()
+            .filter_map(|uri: &Url| uri.to_file_path().ok())
+            .collect();
+        Ok(InitializeResult {
+            capabilities: ServerCapabilities {
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+                code_lens_provider: Some(CodeLensOptions {
+                    resolve_provider: Some(false),
+                }),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: vec![
+                        "goanalyzer/cursor".to_string(),
+                        "goanalyzer/graph".to_string(),
+                        "goanalyzer/graph.export".to_string(),
+                        "goanalyzer/graph.mermaid".to_string(),
+                        "goanalyzer/graph.page".to_string(),
+                        "goanalyzer/graphQuery".to_string(),
+                        "goanalyzer/graphForFunction".to_string(),
+                        "goanalyzer/channelFlow".to_string(),
+                        "goanalyzer/ast".to_string(),
+                        "goanalyzer/resyncDecorations".to_string(),
+                        "goanalyzer/protocolSchema".to_string(),
+                        "goanalyzer/debug.dumpSession".to_string(),
+                        "goanalyzer/workspaceSummary".to_string(),
+                        "goanalyzer/doctor".to_string(),
+                        "goanalyzer/stats".to_string(),
+                        "goanalyzer/runRaceDetector".to_string(),
+                    ],
+                    ..Default::default()
+                }),
+                text_document_sync: Some(TextDocumentSyncCapability::Options(
+                    TextDocumentSyncOptions {
+                        open_close: Some(true),
+                        change: Some(TextDocumentSyncKind::INCREMENTAL),
+                        save: self
+                            .build_on_save
+                            .enabled
+                            .then_some(TextDocumentSyncSaveOptions::Supported(true)),
+                        ..Default::default()
+                    },
+                )),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    }
+
+    async fn initialized(&self, _: InitializedParams) {
+        self.client
+            .log_message(MessageType::INFO, "Go Analyzer initialized")
+            .await;
+        self.client
+            .send_notification::<ProgressNotification>(ProgressParams {
+                message: "Server initialized".to_string(),
+                trace_id: None,
+            })
+            .await;
+        if self.semantic.config.enabled {
+            tokio::spawn(bootstrap_helper(
+                self.client.clone(),
+                self.semantic.helper(),
+            ));
+        }
+    }
+
+    async fn shutdown(&self) -> tower_lsp::jsonrpc::Result<()> {
+        self.client
+            .log_message(MessageType::INFO, "Go Analyzer server shutdown initiated")
+            .await;
+
+        // Debounced analyses and package builds still waiting or running would publish to a
+        // client that is leaving; `exit` then ends the session once their responses are written.
+        self.analysis.close();
+        self.package_builds.cancel_all();
+        if let Err(e) = self.recorder.flush().await {
+            tracing::warn!("Failed to flush the session record: {}", e);
+        }
+        self.semantic.stop().await;
+        self.gopls.stop().await;
+
+        let (docs_count, trees_count) = self.documents.clear();
+        tracing::info!(
+            "Cleared {} document and {} AST tree cache entries",
+            docs_count,
+            trees_count
+        );
+
+        self.client
+            .log_message(MessageType::INFO, "Go Analyzer server shutdown completed")
+            .await;
+        Ok(())
+    }
+
+    async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        let ctx = RequestContext::new("textDocument/didOpen");
+        self.documents.open(
+            &params.text_document.uri,
+            params.text_document.text,
+            params.text_document.version,
+        );
+        self.documents.evict();
+        self.send_indexing_status(&params.text_document.uri).await;
+        self.publish_file_diagnostics(&ctx, &params.text_document.uri)
+            .await;
+        self.report_perf(&ctx).await;
+    }
+
+    async fn did_change(&self, params: DidChangeTextDocumentParams) {
+        let ctx = RequestContext::new("textDocument/didChange");
+        let uri = params.text_document.uri;
+        let version = params.text_document.version;
+        // Unknown documents, out-of-order changes for an older buffer state and empty changes
+        // leave the document as it is.
+        let Some((_, deltas)) = self
+            .documents
+            .change(&uri, version, &params.content_changes)
+        else {
+            return;
+        };
+        self.reanchor_after_change(&uri, Some(version), deltas.as_deref())
+            .await;
+        self.analyze_after_change(&ctx, &uri).await;
+    }
+
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        if !self.build_on_save.enabled {
+            return;
+        }
+        let ctx = RequestContext::new("textDocument/didSave");
+        self.build_saved_package(&ctx, &params.text_document.uri);
+    }
+
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        self.documents.close(&params.text_document.uri);
+        self.decoration_store
+            .lock()
+            .await
+            .remove(&params.text_document.uri);
+        self.graph_store
+            .lock()
+            .await
+            .remove(&params.text_document.uri);
+        self.graph_pages
+            .lock()
+            .await
+            .remove(&params.text_document.uri);
+        self.cursor_positions
+            .lock()
+            .await
+            .remove(&params.text_document.uri);
+        self.analysis.remove(&params.text_document.uri).await;
+        self.published_diagnostics
+            .lock()
+            .await
+            .remove(&params.text_document.uri);
+        self.quick_fixes
+            .lock()
+            .await
+            .remove(&params.text_document.uri);
+        self.gopls.close(&params.text_document.uri).await;
+    }
+
+    async fn hover(&self, params: HoverParams) -> tower_lsp::jsonrpc::Result<Option<Hover>> {
+        let ctx = RequestContext::new("textDocument/hover");
+        let result = self.hover_at(&ctx, params).await;
+        self.report_perf(&ctx).await;
+        result
+    }
+
+    /// A "Show concurrency graph" lens over every function that spawns goroutines, uses
+    /// channels or takes locks, running the client's `goanalyzer.showFunctionGraph`.
+    async fn code_lens(
+        &self,
+        params: CodeLensParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<CodeLens>>> {
+        let ctx = RequestContext::new("textDocument/codeLens");
+        let uri = params.text_document.uri;
+        let code = match self.get_document(&uri).await {
+            Some(code) => code,
+            None => return Ok(None),
+        };
+        let tree = match self.document_tree(&ctx, &uri, &code).await {
+            Some(tree) => tree,
+            None => return Ok(None),
+        };
+        let graph = build_file_graph(&tree, &code, graph_file_name(&uri), &Default::default());
+        let lenses = graph
+            .nodes
+            .iter()
+            .filter(|node| {
+                matches!(
+                    node.entity_type,
+                    GraphEntityType::Function | GraphEntityType::Method
+                )
+            })
+            .filter(|node| {
+                function_subgraph(&graph, &node.label).is_some_and(|sub| has_concurrency(&sub))
+            })
+            .map(|node| CodeLens {
+                range: node.range,
+                command: Some(Command {
+                    title: "Show concurrency graph".to_string(),
+                    command: "goanalyzer.showFunctionGraph".to_string(),
+                    arguments: Some(vec![serde_json::json!({
+                        "uri": uri,
+                        "name": node.label,
+                    })]),
+                }),
+                data: None,
+            })
+            .collect();
+        Ok(Some(lenses))
+    }
+
+    /// Quick fixes for the file diagnostics in the requested range, as found by the last file
+    /// analysis of the document's current version.
+    async fn code_action(
+        &self,
+        params: CodeActionParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<CodeActionResponse>> {
+        let ctx = RequestContext::new("textDocument/codeAction");
+        let uri = params.text_document.uri;
+        let Some(version) = self.documents.version(&uri) else {
+            return Ok(None);
+        };
+        let current = |fixes: &HashMap<Url, VersionedFixes>| {
+            fixes
+                .get(&uri)
+                .filter(|analyzed| analyzed.version == Some(version))
+                .map(|analyzed| analyzed.fixes.clone())
+        };
+        let cached = current(&*self.quick_fixes.lock().await);
+        let fixes = match cached {
+            Some(fixes) => fixes,
+            None => {
+                // Edits since the last analysis moved the fixes; analyze the current text now
+                // instead of waiting for the debounced run.
+                self.publish_file_diagnostics(&ctx, &uri).await;
+                current(&*self.quick_fixes.lock().await).unwrap_or_default()
+            }
+        };
+        let overlaps = |a: Range, b: Range| a.start <= b.end && b.start <= a.end;
+        let actions = fixes
+            .into_iter()
+            .filter(|fix| {
+                fix.targets
+                    .iter()
+                    .any(|target| overlaps(*target, params.range))
+            })
+            .map(|fix| {
+                CodeActionOrCommand::CodeAction(CodeAction {
+                    title: fix.title,
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    diagnostics: Some(vec![fix.diagnostic]),
+                    edit: Some(WorkspaceEdit {
+                        changes: Some(HashMap::from([(uri.clone(), fix.edits)])),
+                        ..Default::default()
+                    }),
+                    is_preferred: Some(fix.preferred),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        Ok(Some(actions))
+    }
+
+    async fn execute_command(
+        &self,
+        params: ExecuteCommandParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<serde_json::Value>> {
+        let ctx = RequestContext::new(&params.command);
+        self.log(&ctx, MessageType::INFO, "started").await;
+        let result = self
+            .run_command(&ctx, params)
+            .instrument(ctx.span.clone())
+            .await;
+        self.report_perf(&ctx).await;
+        match &result {
+            Ok(_) => {
+                self.log(
+                    &ctx,
+                    MessageType::INFO,
+                    format!("completed in {} ms", ctx.elapsed_ms()),
+                )
+                .await
+            }
+            Err(e) => {
+                self.log(
+                    &ctx,
+                    MessageType::ERROR,
+                    format!("failed after {} ms: {}", ctx.elapsed_ms(), e.message),
+                )
+                .await
+            }
+        }
+        result.map_err(|e| ctx.error(e))
+    }
+}
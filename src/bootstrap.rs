@@ -0,0 +1,224 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use tower_lsp::lsp_types::{MessageActionItem, MessageType};
+use tower_lsp::Client;
+
+use crate::diskcache::AnalysisCacheConfig;
+use crate::semantic::SemanticHelper;
+
+/// The helper's Go sources, built into the server so it can build the helper itself.
+pub const HELPER_SOURCES: &[(&str, &str)] = &[
+    (
+        "go.mod",
+        include_str!("../tools/goanalyzer-semantic/go.mod"),
+    ),
+    (
+        "main.go",
+        include_str!("../tools/goanalyzer-semantic/main.go"),
+    ),
+    (
+        "daemon.go",
+        include_str!("../tools/goanalyzer-semantic/daemon.go"),
+    ),
+];
+
+/// The helper's command name, without the platform's executable suffix.
+pub const HELPER_NAME: &str = "goanalyzer-semantic";
+
+/// Whether the server may build the helper with `go build` when it finds none:
+/// `GO_ANALYZER_SEMANTIC_BUILD` is `ask` (the default), `always` or `never`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BuildConsent {
+    #[default]
+    Ask,
+    Always,
+    Never,
+}
+
+impl BuildConsent {
+    pub fn from_env() -> Self {
+        match std::env::var("GO_ANALYZER_SEMANTIC_BUILD") {
+            Ok(v) => Self::parse(&v),
+            Err(_) => Self::Ask,
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value.trim() {
+            "always" | "ALWAYS" | "1" | "true" | "TRUE" | "yes" | "YES" => Self::Always,
+            "never" | "NEVER" | "0" | "false" | "FALSE" | "no" | "NO" => Self::Never,
+            _ => Self::Ask,
+        }
+    }
+}
+
+/// The version a current helper reports in its handshake: a hash of the bundled sources, which
+/// [`build_helper`] stamps into the binary.
+pub fn helper_version() -> String {
+    let sources: String = HELPER_SOURCES.iter().map(|(_, text)| *text).collect();
+    crate::record::document_hash(&sources)
+}
+
+/// Where a built helper goes: a directory of the analysis cache named after the bundled sources,
+/// so a server with different sources builds its own helper instead of reusing a stale one.
+pub fn default_build_dir() -> PathBuf {
+    AnalysisCacheConfig::from_env()
+        .dir
+        .join("semantic-helper")
+        .join(helper_version())
+}
+
+/// The helper to run for the configured `GO_ANALYZER_SEMANTIC_PATH`. A path is used as is; a
+/// command name is looked up next to the server binary, then on `PATH`, then in `build_dir`.
+/// Helpers in `rejected`, which failed the version handshake, are passed over.
+pub fn locate_helper(configured: &str, build_dir: &Path, rejected: &[PathBuf]) -> Option<PathBuf> {
+    let usable = |path: &PathBuf| !rejected.contains(path);
+    if is_path(configured) {
+        return resolve_executable(configured).filter(usable);
+    }
+    let file_name = format!("{}{}", configured, std::env::consts::EXE_SUFFIX);
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join(&file_name)))
+        .filter(|beside| beside.is_file() && usable(beside))
+        .or_else(|| resolve_executable(configured).filter(usable))
+        .or_else(|| Some(built_helper(build_dir)).filter(|built| built.is_file() && usable(built)))
+}
+
+fn built_helper(build_dir: &Path) -> PathBuf {
+    build_dir.join(format!("{}{}", HELPER_NAME, std::env::consts::EXE_SUFFIX))
+}
+
+/// Builds the bundled sources with `go` into `build_dir` and returns the binary, stamped with
+/// [`helper_version`]. The binary is written under a temporary name and renamed, so a build that
+/// fails halfway leaves nothing a later [`locate_helper`] would pick up.
+pub fn build_helper(go: &str, build_dir: &Path) -> std::io::Result<PathBuf> {
+    let sources = build_dir.join("src");
+    std::fs::create_dir_all(&sources)?;
+    for (name, text) in HELPER_SOURCES {
+        std::fs::write(sources.join(name), text)?;
+    }
+    let binary = built_helper(build_dir);
+    let partial = binary.with_extension("partial");
+    let output = std::process::Command::new(go)
+        .arg("build")
+        .arg(format!(
+            "-ldflags=-X main.helperVersion={}",
+            helper_version()
+        ))
+        .arg("-o")
+        .arg(&partial)
+        .arg(".")
+        .current_dir(&sources)
+        .output()?;
+    if !output.status.success() {
+        let _ = std::fs::remove_file(&partial);
+        return Err(std::io::Error::other(format!(
+            "`{} build` failed: {}",
+            go,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    std::fs::rename(&partial, &binary)?;
+    Ok(binary)
+}
+
+/// Makes sure semantic analysis has a helper to run: when no current one is found, builds one from the
+/// bundled sources if Go is installed and the user agrees, as `GO_ANALYZER_SEMANTIC_BUILD` says.
+pub async fn bootstrap_helper(client: Client, helper: Arc<SemanticHelper>) {
+    let config = helper.config();
+    // Starting the helper checks its version, so a stale one is built over too.
+    if !config.enabled || helper.start().await {
+        return;
+    }
+    // A configured path is what the user wants run; only a missing command is built.
+    if is_path(&config.helper_path)
+        || config.build == BuildConsent::Never
+        || resolve_executable("go").is_none()
+    {
+        tracing::info!(
+            "Semantic helper `{}` not found; semantic analysis is off",
+            config.helper_path
+        );
+        return;
+    }
+    if config.build == BuildConsent::Ask {
+        let build = MessageActionItem {
+            title: "Build".to_string(),
+            properties: Default::default(),
+        };
+        let answer = client
+            .show_message_request(
+                MessageType::INFO,
+                "No current Go semantic helper was found. Build it now with `go build`?",
+                Some(vec![
+                    build.clone(),
+                    MessageActionItem {
+                        title: "Not now".to_string(),
+                        properties: Default::default(),
+                    },
+                ]),
+            )
+            .await;
+        if !matches!(answer, Ok(Some(ref chosen)) if chosen.title == build.title) {
+            return;
+        }
+    }
+    let build_dir = config.build_dir.clone();
+    let built = tokio::task::spawn_blocking(move || build_helper("go", &build_dir))
+        .await
+        .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+    match built {
+        Ok(path) => {
+            tracing::info!("Built the semantic helper at {}", path.display());
+            helper.set_path(path);
+            client
+                .show_message(MessageType::INFO, "Built the Go semantic helper")
+                .await;
+        }
+        Err(e) => {
+            tracing::warn!("Failed to build the semantic helper: {}", e);
+            client
+                .show_message(
+                    MessageType::WARNING,
+                    format!("Failed to build the Go semantic helper: {}", e),
+                )
+                .await;
+        }
+    }
+}
+
+/// Whether `command` names a file rather than a command to look up.
+pub fn is_path(command: &str) -> bool {
+    let path = Path::new(command);
+    path.components().count() > 1 || path.is_absolute()
+}
+
+/// `command` itself when it contains a path separator, otherwise its first match on `PATH`.
+pub fn resolve_executable(command: &str) -> Option<PathBuf> {
+    if is_path(command) {
+        let path = Path::new(command);
+        return path.is_file().then(|| path.to_path_buf());
+    }
+    let extensions: &[&str] = if cfg!(windows) { &["", ".exe"] } else { &[""] };
+    std::env::split_paths(&std::env::var_os("PATH")?).find_map(|dir| {
+        extensions
+            .iter()
+            .map(|ext| dir.join(format!("{}{}", command, ext)))
+            .find(|candidate| candidate.is_file())
+    })
+}
+
+#[cfg(unix)]
+pub fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .map(|meta| meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+pub fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
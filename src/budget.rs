@@ -0,0 +1,57 @@
+use std::time::{Duration, Instant};
+
+/// How long one request's analyses may run: `GO_ANALYZER_ANALYSIS_BUDGET_MS`, 2000 ms by
+/// default, `0` for no limit. Analyses past it stop and answer with what they have found so far,
+/// marked `truncated`, so a pathological file slows the editor down instead of hanging it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnalysisBudget {
+    pub limit: Option<Duration>,
+}
+
+impl Default for AnalysisBudget {
+    fn default() -> Self {
+        Self {
+            limit: Some(Duration::from_millis(2000)),
+        }
+    }
+}
+
+impl AnalysisBudget {
+    pub fn from_env() -> Self {
+        match std::env::var("GO_ANALYZER_ANALYSIS_BUDGET_MS")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+        {
+            Some(0) => Self { limit: None },
+            Some(ms) => Self {
+                limit: Some(Duration::from_millis(ms)),
+            },
+            None => Self::default(),
+        }
+    }
+
+    /// The deadline of a request that started at `started`.
+    pub fn deadline(&self, started: Instant) -> Deadline {
+        Deadline {
+            at: self.limit.and_then(|limit| started.checked_add(limit)),
+        }
+    }
+}
+
+/// When an analysis has to stop; [`Deadline::NONE`] never expires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Deadline {
+    at: Option<Instant>,
+}
+
+impl Deadline {
+    pub const NONE: Self = Self { at: None };
+
+    pub fn at(at: Instant) -> Self {
+        Self { at: Some(at) }
+    }
+
+    pub fn expired(&self) -> bool {
+        self.at.is_some_and(|at| Instant::now() >= at)
+    }
+}
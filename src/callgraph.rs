@@ -0,0 +1,480 @@
+use std::collections::HashSet;
+
+use tower_lsp::lsp_types::Range;
+use tree_sitter::{Node, Tree};
+
+use crate::analysis::{
+    check_tree, guarding_mutex, has_pointer_receiver, is_access_in_atomic_context, text,
+};
+use crate::error::AnalysisResult;
+use crate::util::{for_each_node, for_each_node_pruned, node_to_range};
+
+/// How deep call chains are followed from a goroutine before giving up.
+pub const MAX_CALL_DEPTH: usize = 8;
+
+/// A goroutine reaches an unsynchronized write through a call to a function declared in the file.
+#[derive(Debug, Clone)]
+pub struct InterproceduralRace {
+    pub variable: String,
+    /// Function containing the write.
+    pub function: String,
+    /// Call in the goroutine that (transitively) reaches the write.
+    pub call_site: Range,
+    pub write: Range,
+    pub go_statement: Range,
+    /// The receiver expression at the call site when the write is to a field of the receiver of
+    /// the method the goroutine calls, such as `c` for `go c.Increment()`.
+    pub receiver: Option<String>,
+}
+
+pub struct Callable<'a> {
+    pub name: String,
+    pub node: Node<'a>,
+    /// Methods are matched by selector calls `x.name()`, functions by plain `name()`.
+    pub method: bool,
+}
+
+/// Follows calls made from goroutines into functions (and func-literal variables) declared in
+/// the file, reporting writes to package-level or captured variables that hold no lock.
+pub fn find_interprocedural_races(
+    tree: &Tree,
+    code: &str,
+) -> AnalysisResult<Vec<InterproceduralRace>> {
+    check_tree(tree, code)?;
+    let root = tree.root_node();
+    let callables = collect_callables(root, code);
+    if callables.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut go_statements = Vec::new();
+    collect_kind(root, "go_statement", &mut go_statements);
+    let mut all_calls = Vec::new();
+    collect_kind(root, "call_expression", &mut all_calls);
+
+    let mut races = Vec::new();
+    let mut reported: Vec<(Range, Range)> = Vec::new();
+    for go_stmt in go_statements {
+        let Some(spawned) = go_stmt.named_child(0) else {
+            continue;
+        };
+        let mut calls = Vec::new();
+        if spawned.kind() == "call_expression" {
+            calls.push(spawned);
+            if let Some(function) = spawned.child_by_field_name("function") {
+                if function.kind() == "func_literal" {
+                    calls.pop();
+                    collect_calls(function, &mut calls);
+                }
+            }
+        }
+        for call in calls {
+            let call_range = node_to_range(call);
+            if guarding_mutex(tree, call_range, code).is_some() {
+                continue;
+            }
+            let Some(target) = call_target(call, code, &callables) else {
+                continue;
+            };
+            let mut visited = HashSet::new();
+            let mut writes = Vec::new();
+            let called_elsewhere = all_calls.iter().any(|other| {
+                other.id() != call.id()
+                    && call_target(*other, code, &callables)
+                        .map(|callable| callable.node.id() == target.node.id())
+                        .unwrap_or(false)
+            });
+            collect_unsynchronized_writes(
+                tree,
+                code,
+                target.node,
+                &callables,
+                0,
+                &mut visited,
+                &mut writes,
+            );
+            let receiver = call_receiver(call, target.node, code);
+            for (variable, function, write) in writes {
+                if reported.contains(&(call_range, write)) {
+                    continue;
+                }
+                reported.push((call_range, write));
+                // Only writes in the called method itself go through the call-site receiver.
+                let field_receiver = receiver.filter(|_| {
+                    let lines = target.node.start_position().row..=target.node.end_position().row;
+                    lines.contains(&(write.start.line as usize))
+                });
+                let shared = field_receiver.is_some_and(|(expression, receiver_name)| {
+                    let field = variable
+                        .strip_prefix(receiver_name)
+                        .and_then(|rest| rest.strip_prefix('.'));
+                    field.is_some_and(|field| {
+                        is_receiver_shared(root, code, go_stmt, expression, field)
+                    })
+                });
+                if !called_elsewhere
+                    && !shared
+                    && !is_concurrent(root, code, go_stmt, &variable, write)
+                {
+                    continue;
+                }
+                races.push(InterproceduralRace {
+                    variable,
+                    function,
+                    call_site: call_range,
+                    write,
+                    go_statement: node_to_range(go_stmt),
+                    receiver: field_receiver.map(|(expression, _)| expression.to_string()),
+                });
+            }
+        }
+    }
+    Ok(races)
+}
+
+pub fn collect_callables<'a>(root: Node<'a>, code: &str) -> Vec<Callable<'a>> {
+    let mut callables = Vec::new();
+    let mut cursor = root.walk();
+    for child in root.named_children(&mut cursor) {
+        if matches!(child.kind(), "function_declaration" | "method_declaration") {
+            if let Some(name) = child.child_by_field_name("name") {
+                callables.push(Callable {
+                    name: text(code, name).to_string(),
+                    node: child,
+                    method: child.kind() == "method_declaration",
+                });
+            }
+        }
+    }
+    // `increment := func() { ... }` is callable by name as well.
+    let mut declarations = Vec::new();
+    collect_kind(root, "short_var_declaration", &mut declarations);
+    for decl in declarations {
+        let (Some(left), Some(right)) = (
+            decl.child_by_field_name("left"),
+            decl.child_by_field_name("right"),
+        ) else {
+            continue;
+        };
+        if left.named_child_count() != 1 || right.named_child_count() != 1 {
+            continue;
+        }
+        if let (Some(name), Some(value)) = (left.named_child(0), right.named_child(0)) {
+            if value.kind() == "func_literal" {
+                callables.push(Callable {
+                    name: text(code, name).to_string(),
+                    node: value,
+                    method: false,
+                });
+            }
+        }
+    }
+    callables
+}
+
+pub fn call_target<'a, 'tree>(
+    call: Node,
+    code: &str,
+    callables: &'a [Callable<'tree>],
+) -> Option<&'a Callable<'tree>> {
+    let function = call.child_by_field_name("function")?;
+    let (name, method) = match function.kind() {
+        "identifier" => (text(code, function), false),
+        "selector_expression" => (text(code, function.child_by_field_name("field")?), true),
+        _ => return None,
+    };
+    callables
+        .iter()
+        .find(|callable| callable.name == name && callable.method == method)
+}
+
+fn collect_unsynchronized_writes(
+    tree: &Tree,
+    code: &str,
+    function: Node,
+    callables: &[Callable],
+    depth: usize,
+    visited: &mut HashSet<usize>,
+    out: &mut Vec<(String, String, Range)>,
+) {
+    if depth > MAX_CALL_DEPTH || !visited.insert(function.id()) {
+        return;
+    }
+    let Some(body) = function.child_by_field_name("body") else {
+        return;
+    };
+    let function_name = callables
+        .iter()
+        .find(|callable| callable.node.id() == function.id())
+        .map(|callable| callable.name.clone())
+        .unwrap_or_else(|| "func literal".to_string());
+    let locals = local_names(function, code);
+    // Fields written through a pointer receiver are shared with every caller.
+    let shared_receiver = (function.kind() == "method_declaration"
+        && has_pointer_receiver(function))
+    .then(|| receiver_name(function, code))
+    .flatten();
+
+    let mut targets = Vec::new();
+    collect_write_targets(body, &mut targets);
+    for target in targets {
+        let name = text(code, target);
+        if target.kind() == "selector_expression" {
+            let operand = target
+                .child_by_field_name("operand")
+                .map(|operand| text(code, operand));
+            if operand.is_none() || operand != shared_receiver {
+                continue;
+            }
+        } else if name == "_" || locals.contains(name) {
+            continue;
+        }
+        let range = node_to_range(target);
+        if guarding_mutex(tree, range, code).is_some()
+            || is_access_in_atomic_context(tree, range, code).unwrap_or(false)
+        {
+            continue;
+        }
+        out.push((name.to_string(), function_name.clone(), range));
+    }
+
+    let mut calls = Vec::new();
+    collect_calls(body, &mut calls);
+    for call in calls {
+        if guarding_mutex(tree, node_to_range(call), code).is_some() {
+            continue;
+        }
+        if let Some(callee) = call_target(call, code, callables) {
+            collect_unsynchronized_writes(
+                tree,
+                code,
+                callee.node,
+                callables,
+                depth + 1,
+                visited,
+                out,
+            );
+        }
+    }
+}
+
+pub fn receiver_name<'a>(method: Node, code: &'a str) -> Option<&'a str> {
+    let receiver = method.child_by_field_name("receiver")?;
+    let name = receiver.named_child(0)?.child_by_field_name("name")?;
+    Some(text(code, name))
+}
+
+/// Names declared by the function itself: parameters, receivers and locals.
+pub fn local_names(function: Node, code: &str) -> HashSet<String> {
+    let mut names = HashSet::new();
+    let mut declarations = Vec::new();
+    for kind in [
+        "parameter_declaration",
+        "variadic_parameter_declaration",
+        "var_spec",
+        "const_spec",
+    ] {
+        collect_kind(function, kind, &mut declarations);
+    }
+    for decl in declarations {
+        if decl
+            .parent()
+            .is_some_and(|list| list.kind() == "type_parameter_list")
+        {
+            continue;
+        }
+        let mut cursor = decl.walk();
+        for name in decl.children_by_field_name("name", &mut cursor) {
+            names.insert(text(code, name).to_string());
+        }
+    }
+    let mut short_decls = Vec::new();
+    collect_kind(function, "short_var_declaration", &mut short_decls);
+    collect_kind(function, "range_clause", &mut short_decls);
+    for decl in short_decls {
+        if decl.kind() == "range_clause" && !text(code, decl).contains(":=") {
+            continue;
+        }
+        if let Some(left) = decl.child_by_field_name("left") {
+            let mut cursor = left.walk();
+            for name in left.named_children(&mut cursor) {
+                if name.kind() == "identifier" {
+                    names.insert(text(code, name).to_string());
+                }
+            }
+        }
+    }
+    names
+}
+
+/// Identifiers and selectors written by assignments and `++`/`--` in `node`, outside nested
+/// func literals.
+fn collect_write_targets<'a>(node: Node<'a>, out: &mut Vec<Node<'a>>) {
+    for_each_node_pruned(node, |node| {
+        match node.kind() {
+            "func_literal" => return false,
+            "assignment_statement" => {
+                if let Some(left) = node.child_by_field_name("left") {
+                    let mut cursor = left.walk();
+                    for target in left.named_children(&mut cursor) {
+                        if matches!(target.kind(), "identifier" | "selector_expression") {
+                            out.push(target);
+                        }
+                    }
+                }
+            }
+            "inc_statement" | "dec_statement" => {
+                if let Some(target) = node
+                    .named_child(0)
+                    .filter(|n| matches!(n.kind(), "identifier" | "selector_expression"))
+                {
+                    out.push(target);
+                }
+            }
+            _ => {}
+        }
+        true
+    });
+}
+
+fn collect_calls<'a>(node: Node<'a>, out: &mut Vec<Node<'a>>) {
+    for_each_node_pruned(node, |node| {
+        if node.kind() == "call_expression" {
+            out.push(node);
+        }
+        // Calls in a nested goroutine are analyzed from that go statement.
+        node.kind() != "go_statement"
+    });
+}
+
+pub fn collect_kind<'a>(node: Node<'a>, kind: &str, out: &mut Vec<Node<'a>>) {
+    for_each_node(node, |node| {
+        if node.kind() == kind {
+            out.push(node);
+        }
+    });
+}
+
+/// The write can overlap another access when several goroutines are spawned (the go statement
+/// sits in a loop) or when the variable is also used outside the spawned goroutine. Callers also
+/// treat a target function that is called from elsewhere as concurrent.
+fn is_concurrent(root: Node, code: &str, go_stmt: Node, variable: &str, write: Range) -> bool {
+    let mut ancestor = go_stmt.parent();
+    while let Some(node) = ancestor {
+        if node.kind() == "for_statement" {
+            return true;
+        }
+        if matches!(node.kind(), "function_declaration" | "method_declaration") {
+            break;
+        }
+        ancestor = node.parent();
+    }
+    let mut identifiers = Vec::new();
+    collect_kind(root, "identifier", &mut identifiers);
+    let go_range = go_stmt.byte_range();
+    identifiers.into_iter().any(|ident| {
+        text(code, ident) == variable
+            && node_to_range(ident) != write
+            && !(go_range.start <= ident.start_byte() && ident.end_byte() <= go_range.end)
+            && !is_declaration_name(ident)
+            && !in_same_function_as_write(ident, root, write)
+    })
+}
+
+/// For `x.m(...)` calling a pointer-receiver method, the receiver expression `x` and the
+/// receiver's name inside the method.
+fn call_receiver<'a>(call: Node, method: Node, code: &'a str) -> Option<(&'a str, &'a str)> {
+    if method.kind() != "method_declaration" || !has_pointer_receiver(method) {
+        return None;
+    }
+    let operand = call
+        .child_by_field_name("function")
+        .filter(|function| function.kind() == "selector_expression")?
+        .child_by_field_name("operand")?;
+    Some((text(code, operand), receiver_name(method, code)?))
+}
+
+/// The value `receiver` names in the function starting `go_stmt` is used by another goroutine
+/// too: another `go` statement calls a method on it or reads `receiver.field`, or the spawning
+/// function does so after the `go` statement without waiting for the goroutine.
+fn is_receiver_shared(root: Node, code: &str, go_stmt: Node, receiver: &str, field: &str) -> bool {
+    let mut spawner = go_stmt;
+    while !matches!(
+        spawner.kind(),
+        "function_declaration" | "method_declaration"
+    ) {
+        match spawner.parent() {
+            Some(parent) => spawner = parent,
+            None => {
+                spawner = root;
+                break;
+            }
+        }
+    }
+    let mut selectors = Vec::new();
+    collect_kind(spawner, "selector_expression", &mut selectors);
+    let go_range = go_stmt.byte_range();
+    selectors.into_iter().any(|selector| {
+        if go_range.contains(&selector.start_byte())
+            || selector
+                .child_by_field_name("operand")
+                .map(|operand| text(code, operand))
+                != Some(receiver)
+        {
+            return false;
+        }
+        let is_call = selector.parent().is_some_and(|parent| {
+            parent.kind() == "call_expression"
+                && parent.child_by_field_name("function") == Some(selector)
+        });
+        let is_field = selector
+            .child_by_field_name("field")
+            .is_some_and(|name| text(code, name) == field);
+        if !is_call && !is_field {
+            return false;
+        }
+        let mut ancestor = selector.parent();
+        while let Some(node) = ancestor {
+            if node.kind() == "go_statement" {
+                return true;
+            }
+            if node == spawner {
+                break;
+            }
+            ancestor = node.parent();
+        }
+        crate::maps::runs_alongside(go_stmt, selector, code)
+    })
+}
+
+fn is_declaration_name(ident: Node) -> bool {
+    ident
+        .parent()
+        .map(|parent| matches!(parent.kind(), "var_spec" | "const_spec"))
+        .unwrap_or(false)
+}
+
+/// Accesses inside the writing function run on the same goroutine as the write itself.
+fn in_same_function_as_write(ident: Node, root: Node, write: Range) -> bool {
+    let function_of = |node: Node| {
+        let mut current = Some(node);
+        while let Some(candidate) = current {
+            if matches!(
+                candidate.kind(),
+                "function_declaration" | "method_declaration" | "func_literal"
+            ) {
+                return Some(candidate.id());
+            }
+            current = candidate.parent();
+        }
+        None
+    };
+    let write_point = tree_sitter::Point {
+        row: write.start.line as usize,
+        column: write.start.character as usize,
+    };
+    let Some(write_node) = root.descendant_for_point_range(write_point, write_point) else {
+        return false;
+    };
+    let write_function = function_of(write_node);
+    write_function.is_some() && write_function == function_of(ident)
+}
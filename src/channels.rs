@@ -0,0 +1,1845 @@
+use std::collections::HashSet;
+
+use serde_json::json;
+use tower_lsp::lsp_types::{Position, Range};
+use tree_sitter::{Node, Tree};
+
+use crate::analysis::{check_tree, enclosing_declaration, text, GraphIds};
+use crate::callgraph::{call_target, collect_callables, collect_kind, Callable, MAX_CALL_DEPTH};
+use crate::detect::CHANNEL_OPS;
+use crate::error::AnalysisResult;
+use crate::types::{GraphData, GraphEdge, GraphEdgeType, GraphEntityType, GraphNode};
+use crate::util::{
+    find_node, for_each_node, for_each_node_pruned, node_to_range, walk_tree, Visit,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelOp {
+    Send,
+    Receive,
+}
+
+/// A blocking operation on a local unbuffered channel that no other goroutine can complete.
+#[derive(Debug, Clone)]
+pub struct ChannelDeadlock {
+    pub channel: String,
+    pub op: ChannelOp,
+    pub range: Range,
+    /// The `make(chan ...)` creating the channel.
+    pub creation: Range,
+}
+
+impl ChannelDeadlock {
+    pub fn message(&self) -> String {
+        match self.op {
+            ChannelOp::Send => format!(
+                "Send on unbuffered channel `{}` blocks forever: no other goroutine receives from it",
+                self.channel
+            ),
+            ChannelOp::Receive => format!(
+                "Receive from unbuffered channel `{}` blocks forever: no other goroutine sends to it",
+                self.channel
+            ),
+        }
+    }
+}
+
+/// Finds the first send or receive on each unbuffered channel created in a function when the
+/// channel never reaches another goroutine (no `go` statement, closure, call argument, return or
+/// assignment mentions it).
+pub fn find_channel_deadlocks(tree: &Tree, code: &str) -> AnalysisResult<Vec<ChannelDeadlock>> {
+    check_tree(tree, code)?;
+    let root = tree.root_node();
+    let mut deadlocks = Vec::new();
+    let mut cursor = root.walk();
+    for function in root.named_children(&mut cursor) {
+        if !matches!(
+            function.kind(),
+            "function_declaration" | "method_declaration"
+        ) {
+            continue;
+        }
+        let Some(body) = function.child_by_field_name("body") else {
+            continue;
+        };
+        let mut channels = Vec::new();
+        collect_unbuffered_channels(body, code, &mut channels);
+        for (name, creation) in channels {
+            if shared_with_other_goroutine(body, code, &name, creation) {
+                continue;
+            }
+            let mut ops = Vec::new();
+            collect_blocking_ops(body, code, &name, &mut ops);
+            // Receiving from a closed channel returns immediately.
+            let closed = is_closed(body, code, &name);
+            if let Some((op, node)) = ops
+                .into_iter()
+                .filter(|(op, _)| !(closed && *op == ChannelOp::Receive))
+                .min_by_key(|(_, node)| node.start_byte())
+            {
+                deadlocks.push(ChannelDeadlock {
+                    channel: name,
+                    op,
+                    range: node_to_range(node),
+                    creation: node_to_range(creation),
+                });
+            }
+        }
+    }
+    Ok(deadlocks)
+}
+
+/// `ch := make(chan T)`, `ch := make(chan T, 0)` and `var ch = make(chan T)` in the function.
+fn collect_unbuffered_channels<'a>(node: Node<'a>, code: &str, out: &mut Vec<(String, Node<'a>)>) {
+    for_each_node_pruned(node, |node| {
+        if node.kind() == "func_literal" {
+            return false;
+        }
+        for (name, value) in declared_values(node) {
+            if is_unbuffered_make(value, code) {
+                out.push((text(code, name).to_string(), value));
+            }
+        }
+        true
+    });
+}
+
+/// (name, value) pairs declared by a `short_var_declaration` or `var_spec`.
+fn declared_values(node: Node) -> Vec<(Node, Node)> {
+    match node.kind() {
+        "short_var_declaration" => node
+            .child_by_field_name("left")
+            .zip(node.child_by_field_name("right"))
+            .map(|(left, right)| {
+                (0..left.named_child_count())
+                    .filter_map(|i| Some((left.named_child(i)?, right.named_child(i)?)))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default(),
+        "var_spec" => {
+            let mut cursor = node.walk();
+            let names: Vec<Node> = node.children_by_field_name("name", &mut cursor).collect();
+            node.child_by_field_name("value")
+                .map(|values| {
+                    names
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(i, name)| Some((*name, values.named_child(i)?)))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default()
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn is_unbuffered_make(value: Node, code: &str) -> bool {
+    is_channel_make(value, code)
+        && value
+            .child_by_field_name("arguments")
+            .map(|args| match args.named_child(1) {
+                None => true,
+                Some(size) => text(code, size) == "0",
+            })
+            .unwrap_or(false)
+}
+
+/// `make(chan T)` with or without a buffer size.
+fn is_channel_make(value: Node, code: &str) -> bool {
+    if value.kind() != "call_expression" {
+        return false;
+    }
+    let is_make = value
+        .child_by_field_name("function")
+        .map(|f| text(code, f) == "make")
+        .unwrap_or(false);
+    let is_chan = value
+        .child_by_field_name("arguments")
+        .and_then(|args| args.named_child(0))
+        .map(|t| t.kind() == "channel_type")
+        .unwrap_or(false);
+    is_make && is_chan
+}
+
+/// Any mention of the channel other than direct sends, receives, `close`, `len` and `cap` may
+/// hand it to another goroutine.
+fn shared_with_other_goroutine(body: Node, code: &str, name: &str, creation: Node) -> bool {
+    let mut stack = vec![body];
+    while let Some(node) = stack.pop() {
+        if node.kind() == "identifier"
+            && text(code, node) == name
+            && (inside_other_goroutine(node, body) || !is_local_use(node, code, creation))
+        {
+            return true;
+        }
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            stack.push(child);
+        }
+    }
+    false
+}
+
+fn inside_other_goroutine(node: Node, body: Node) -> bool {
+    let mut current = node.parent();
+    while let Some(candidate) = current {
+        if candidate.id() == body.id() {
+            return false;
+        }
+        if matches!(candidate.kind(), "go_statement" | "func_literal") {
+            return true;
+        }
+        current = candidate.parent();
+    }
+    false
+}
+
+fn is_local_use(ident: Node, code: &str, creation: Node) -> bool {
+    let Some(parent) = ident.parent() else {
+        return true;
+    };
+    match parent.kind() {
+        "send_statement" => parent
+            .child_by_field_name("channel")
+            .map(|c| c.id() == ident.id())
+            .unwrap_or(false),
+        "unary_expression" => text(code, parent).starts_with("<-"),
+        "range_clause" => parent
+            .child_by_field_name("right")
+            .map(|r| r.id() == ident.id())
+            .unwrap_or(false),
+        "argument_list" => parent
+            .parent()
+            .and_then(|call| call.child_by_field_name("function"))
+            .map(|f| matches!(text(code, f), "close" | "len" | "cap"))
+            .unwrap_or(false),
+        "var_spec" => true,
+        "expression_list" => parent
+            .parent()
+            .filter(|decl| {
+                decl.kind() == "short_var_declaration"
+                    && decl.start_byte() <= creation.start_byte()
+                    && creation.end_byte() <= decl.end_byte()
+            })
+            .and_then(|decl| decl.child_by_field_name("left"))
+            .map(|left| left.id() == parent.id())
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+fn collect_blocking_ops<'a>(
+    node: Node<'a>,
+    code: &str,
+    name: &str,
+    out: &mut Vec<(ChannelOp, Node<'a>)>,
+) {
+    for_each_node_pruned(node, |node| {
+        // A select may pick another case or its default.
+        if matches!(
+            node.kind(),
+            "select_statement" | "func_literal" | "go_statement"
+        ) {
+            return false;
+        }
+        if let Some(op) = channel_op(node, code, name) {
+            out.push((op, node));
+        }
+        true
+    });
+}
+
+/// The send or receive on `name` performed by `node` itself (`ch <- v`, `<-ch`, `range ch`).
+fn channel_op(node: Node, code: &str, name: &str) -> Option<ChannelOp> {
+    let operand_is = |field: &str| {
+        node.child_by_field_name(field)
+            .map(|operand| text(code, operand) == name)
+            .unwrap_or(false)
+    };
+    match node.kind() {
+        "send_statement" if operand_is("channel") => Some(ChannelOp::Send),
+        "unary_expression" if operand_is("operand") && text(code, node).starts_with("<-") => {
+            Some(ChannelOp::Receive)
+        }
+        "range_clause" if operand_is("right") => Some(ChannelOp::Receive),
+        _ => None,
+    }
+}
+
+fn is_closed(body: Node, code: &str, name: &str) -> bool {
+    find_node(body, |node| {
+        closed_channel(node, code).is_some_and(|arg| text(code, arg) == name)
+    })
+    .is_some()
+}
+
+/// A send on a channel that an earlier `close` in the same block has already closed.
+#[derive(Debug, Clone)]
+pub struct SendAfterClose {
+    pub channel: String,
+    pub send: Range,
+    pub close: Range,
+}
+
+/// Flags `ch <- v` sends that follow `close(ch)` in the same block, including sends inside
+/// goroutines spawned after the close.
+pub fn find_sends_after_close(tree: &Tree, code: &str) -> AnalysisResult<Vec<SendAfterClose>> {
+    check_tree(tree, code)?;
+    let mut closes = Vec::new();
+    collect_close_calls(tree.root_node(), code, &mut closes);
+    let mut findings = Vec::new();
+    for (channel, close_call) in closes {
+        // `close(ch)` as a statement of a block; closes inside expressions are too unusual to track.
+        let Some(statement) = close_call
+            .parent()
+            .filter(|p| p.kind() == "expression_statement")
+        else {
+            continue;
+        };
+        let mut next = statement.next_named_sibling();
+        while let Some(sibling) = next {
+            if reassigns(sibling, code, &channel) {
+                break;
+            }
+            let mut sends = Vec::new();
+            collect_sends_after_close(sibling, code, &channel, &mut sends);
+            findings.extend(sends.into_iter().map(|send| SendAfterClose {
+                channel: channel.clone(),
+                send: node_to_range(send),
+                close: node_to_range(close_call),
+            }));
+            next = sibling.next_named_sibling();
+        }
+    }
+    Ok(findings)
+}
+
+fn collect_close_calls<'a>(node: Node<'a>, code: &str, out: &mut Vec<(String, Node<'a>)>) {
+    for_each_node(node, |node| {
+        if let Some(channel) = closed_channel(node, code) {
+            out.push((text(code, channel).to_string(), node));
+        }
+    });
+}
+
+/// The argument of `close(x)` when `node` is that call.
+fn closed_channel<'a>(node: Node<'a>, code: &str) -> Option<Node<'a>> {
+    if node.kind() != "call_expression"
+        || node
+            .child_by_field_name("function")
+            .map(|f| text(code, f) != "close")
+            .unwrap_or(true)
+    {
+        return None;
+    }
+    node.child_by_field_name("arguments")
+        .and_then(|args| args.named_child(0))
+}
+
+fn reassigns(statement: Node, code: &str, channel: &str) -> bool {
+    matches!(
+        statement.kind(),
+        "assignment_statement" | "short_var_declaration"
+    ) && statement
+        .child_by_field_name("left")
+        .map(|left| {
+            let mut cursor = left.walk();
+            let reassigned = left
+                .named_children(&mut cursor)
+                .any(|target| text(code, target) == channel);
+            reassigned
+        })
+        .unwrap_or(false)
+}
+
+fn collect_sends_after_close<'a>(
+    node: Node<'a>,
+    code: &str,
+    channel: &str,
+    out: &mut Vec<Node<'a>>,
+) {
+    for_each_node_pruned(node, |node| {
+        match node.kind() {
+            // A func literal only runs after the close when it is spawned (or called) right here;
+            // stored callbacks may run anywhere.
+            "func_literal"
+                if !node
+                    .parent()
+                    .map(|p| matches!(p.kind(), "call_expression"))
+                    .unwrap_or(false) =>
+            {
+                return false
+            }
+            "send_statement"
+                if node
+                    .child_by_field_name("channel")
+                    .map(|c| text(code, c) == channel)
+                    .unwrap_or(false) =>
+            {
+                out.push(node)
+            }
+            _ => {}
+        }
+        true
+    });
+}
+
+/// `close` called again on a channel that may already be closed.
+#[derive(Debug, Clone)]
+pub struct DoubleClose {
+    pub channel: String,
+    pub close: Range,
+    /// The earlier `close`; `None` when the same `close` runs in every goroutine started in a loop.
+    pub first_close: Option<Range>,
+    /// One of the closes sits in a conditional or loop, so only some paths close twice.
+    pub conditional: bool,
+}
+
+impl DoubleClose {
+    pub fn message(&self) -> String {
+        match (self.first_close, self.conditional) {
+            (None, _) => format!(
+                "`{}` is closed by every goroutine started in the loop; closing a closed channel panics",
+                self.channel
+            ),
+            (Some(first), false) => format!(
+                "`{}` is already closed on line {}; closing a closed channel panics",
+                self.channel,
+                first.start.line + 1
+            ),
+            (Some(first), true) => format!(
+                "`{}` may already be closed on line {}: both closes run on some paths",
+                self.channel,
+                first.start.line + 1
+            ),
+        }
+    }
+}
+
+/// Finds channels closed more than once within a function, counting closes in goroutines and
+/// deferred or immediately invoked func literals. Closes in exclusive branches of the same
+/// `if`/`switch`/`select`, or separated by a `return` or an assignment to the channel, are not
+/// reported; a name declared again in an inner scope is another channel.
+pub fn find_double_closes(tree: &Tree, code: &str) -> AnalysisResult<Vec<DoubleClose>> {
+    check_tree(tree, code)?;
+    let root = tree.root_node();
+    let mut findings = Vec::new();
+    let mut cursor = root.walk();
+    for function in root.named_children(&mut cursor) {
+        if !matches!(
+            function.kind(),
+            "function_declaration" | "method_declaration"
+        ) {
+            continue;
+        }
+        let Some(body) = function.child_by_field_name("body") else {
+            continue;
+        };
+        let mut closes = Vec::new();
+        collect_executed_closes(body, code, &mut closes);
+        // The same name declared in an inner scope is another channel.
+        let keyed: Vec<((&str, usize), Node)> = closes
+            .iter()
+            .map(|(name, site)| {
+                let scope = declaring_scope(*site, name, code, function);
+                ((name.as_str(), scope), *site)
+            })
+            .collect();
+        let mut channels: Vec<(&str, usize)> = keyed.iter().map(|(key, _)| *key).collect();
+        channels.sort_unstable();
+        channels.dedup();
+        for (channel, scope) in channels {
+            let sites: Vec<Node> = keyed
+                .iter()
+                .filter(|(key, _)| *key == (channel, scope))
+                .map(|(_, node)| *node)
+                .collect();
+            for site in &sites {
+                if spawned_in_loop(*site, body, scope) {
+                    findings.push(DoubleClose {
+                        channel: channel.to_string(),
+                        close: node_to_range(*site),
+                        first_close: None,
+                        conditional: false,
+                    });
+                }
+            }
+            for (i, later) in sites.iter().enumerate().skip(1) {
+                let earlier = sites[..i].iter().find(|earlier| {
+                    !exclusive_paths(**earlier, *later, body)
+                        && !reassigned_between(**earlier, *later, channel, scope, code, function)
+                });
+                if let Some(earlier) = earlier {
+                    findings.push(DoubleClose {
+                        channel: channel.to_string(),
+                        close: node_to_range(*later),
+                        first_close: Some(node_to_range(*earlier)),
+                        conditional: is_conditional(*earlier, body) || is_conditional(*later, body),
+                    });
+                }
+            }
+        }
+    }
+    Ok(findings)
+}
+
+/// `close(x)` calls that run when the function does: func literals only count when spawned,
+/// deferred or invoked in place (not when handed to `sync.Once.Do` or stored).
+fn collect_executed_closes<'a>(node: Node<'a>, code: &str, out: &mut Vec<(String, Node<'a>)>) {
+    for_each_node_pruned(node, |node| {
+        if node.kind() == "func_literal" {
+            let invoked = node
+                .parent()
+                .and_then(|call| call.child_by_field_name("function"))
+                .map(|function| function.id() == node.id())
+                .unwrap_or(false);
+            if !invoked {
+                return false;
+            }
+        }
+        if let Some(channel) = closed_channel(node, code) {
+            out.push((text(code, channel).to_string(), node));
+        }
+        true
+    });
+}
+
+/// Id of the node whose scope declares `name` as seen from `site`: the innermost block,
+/// statement initializer or func literal declaring it up to `site`, or else `function`, whose
+/// parameters or package the name comes from.
+fn declaring_scope(site: Node, name: &str, code: &str, function: Node) -> usize {
+    let mut current = site.parent();
+    while let Some(scope) = current {
+        if scope.id() == function.id() {
+            break;
+        }
+        if declares_up_to(scope, name, code, site) {
+            return scope.id();
+        }
+        current = scope.parent();
+    }
+    function.id()
+}
+
+fn declares_up_to(scope: Node, name: &str, code: &str, site: Node) -> bool {
+    let declares = |statement: Node| {
+        statement.start_byte() <= site.start_byte() && declares_name(statement, code, name)
+    };
+    match scope.kind() {
+        "block" => {
+            let mut cursor = scope.walk();
+            let declared = scope.named_children(&mut cursor).any(declares);
+            declared
+        }
+        "if_statement" | "expression_switch_statement" | "type_switch_statement" => scope
+            .child_by_field_name("initializer")
+            .is_some_and(declares),
+        "for_statement" => {
+            let mut cursor = scope.walk();
+            let declared = scope
+                .named_children(&mut cursor)
+                .any(|clause| match clause.kind() {
+                    "for_clause" => clause
+                        .child_by_field_name("initializer")
+                        .is_some_and(declares),
+                    "range_clause" => {
+                        clause.child(1).is_some_and(|op| op.kind() == ":=")
+                            && clause.child_by_field_name("left").is_some_and(|left| {
+                                let mut cursor = left.walk();
+                                let named = left
+                                    .named_children(&mut cursor)
+                                    .any(|target| text(code, target) == name);
+                                named
+                            })
+                    }
+                    _ => false,
+                });
+            declared
+        }
+        "func_literal" => scope
+            .child_by_field_name("parameters")
+            .is_some_and(|params| {
+                let mut found = false;
+                for_each_node(params, |node| {
+                    found |= node.kind() == "identifier"
+                        && node
+                            .parent()
+                            .is_some_and(|p| p.kind() == "parameter_declaration")
+                        && text(code, node) == name;
+                });
+                found
+            }),
+        _ => false,
+    }
+}
+
+/// Whether `statement` is a `:=` or `var` declaration of `name`.
+fn declares_name(statement: Node, code: &str, name: &str) -> bool {
+    match statement.kind() {
+        "short_var_declaration" => reassigns(statement, code, name),
+        "var_declaration" => {
+            let mut found = false;
+            for_each_node(statement, |node| {
+                found |= node.kind() == "var_spec" && {
+                    let mut cursor = node.walk();
+                    let named = node
+                        .children_by_field_name("name", &mut cursor)
+                        .any(|ident| text(code, ident) == name);
+                    named
+                };
+            });
+            found
+        }
+        _ => false,
+    }
+}
+
+/// Whether the channel `name` declared in `scope` is assigned again after `earlier` and before
+/// `later`, so the two closes are of different channels.
+fn reassigned_between(
+    earlier: Node,
+    later: Node,
+    name: &str,
+    scope: usize,
+    code: &str,
+    function: Node,
+) -> bool {
+    let mut found = false;
+    for_each_node(function, |node| {
+        found |= node.start_byte() >= earlier.end_byte()
+            && node.end_byte() <= later.start_byte()
+            && reassigns(node, code, name)
+            && declaring_scope(node, name, code, function) == scope;
+    });
+    found
+}
+
+fn ancestors_within<'a>(node: Node<'a>, body: Node<'a>) -> Vec<Node<'a>> {
+    let mut chain = Vec::new();
+    let mut current = node.parent();
+    while let Some(candidate) = current {
+        if candidate.id() == body.id() {
+            break;
+        }
+        chain.push(candidate);
+        current = candidate.parent();
+    }
+    chain
+}
+
+/// Whether `close` runs in a goroutine started in a loop that keeps the channel declared in
+/// `scope`; a channel declared in the loop body is a new one every iteration.
+fn spawned_in_loop(close: Node, body: Node, scope: usize) -> bool {
+    let chain = ancestors_within(close, body);
+    let declared = chain
+        .iter()
+        .position(|n| n.id() == scope)
+        .unwrap_or(chain.len());
+    chain
+        .iter()
+        .position(|n| n.kind() == "go_statement")
+        .map(|go| {
+            chain[go + 1..]
+                .iter()
+                .enumerate()
+                .any(|(i, n)| n.kind() == "for_statement" && go + 1 + i < declared)
+        })
+        .unwrap_or(false)
+}
+
+fn is_conditional(close: Node, body: Node) -> bool {
+    ancestors_within(close, body).iter().any(|n| {
+        matches!(
+            n.kind(),
+            "if_statement"
+                | "for_statement"
+                | "expression_case"
+                | "type_case"
+                | "default_case"
+                | "communication_case"
+        )
+    })
+}
+
+fn exclusive_paths(earlier: Node, later: Node, body: Node) -> bool {
+    let earlier_chain = ancestors_within(earlier, body);
+    let later_chain = ancestors_within(later, body);
+    let common = earlier_chain
+        .iter()
+        .find(|a| later_chain.iter().any(|b| b.id() == a.id()));
+    if let Some(common) = common {
+        let branch_of = |chain: &[Node]| {
+            chain
+                .iter()
+                .position(|n| n.id() == common.id())
+                .and_then(|i| i.checked_sub(1))
+                .map(|i| chain[i].id())
+        };
+        let branches_differ = branch_of(&earlier_chain) != branch_of(&later_chain);
+        let branching = matches!(
+            common.kind(),
+            "if_statement"
+                | "expression_switch_statement"
+                | "type_switch_statement"
+                | "select_statement"
+        );
+        if branching && branches_differ {
+            return true;
+        }
+    }
+    // `if failed { close(ch); return }` never reaches a later close.
+    earlier_chain
+        .iter()
+        .filter(|n| n.kind() == "block")
+        .filter(|block| !later_chain.iter().any(|b| b.id() == block.id()))
+        .any(|block| {
+            let mut cursor = block.walk();
+            let returns = block
+                .named_children(&mut cursor)
+                .any(|statement| statement.kind() == "return_statement");
+            returns
+        })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NilChannelOpKind {
+    Send,
+    Receive,
+    Close,
+}
+
+/// An operation on a channel declared `var ch chan T` before anything is assigned to it.
+#[derive(Debug, Clone)]
+pub struct NilChannelOp {
+    pub channel: String,
+    pub kind: NilChannelOpKind,
+    pub range: Range,
+    pub declaration: Range,
+}
+
+impl NilChannelOp {
+    pub fn message(&self) -> String {
+        match self.kind {
+            NilChannelOpKind::Send => format!(
+                "Send on nil channel `{}` blocks forever: it is never assigned with `make` before this point",
+                self.channel
+            ),
+            NilChannelOpKind::Receive => format!(
+                "Receive from nil channel `{}` blocks forever: it is never assigned with `make` before this point",
+                self.channel
+            ),
+            NilChannelOpKind::Close => format!(
+                "Closing nil channel `{}` panics: it is never assigned with `make` before this point",
+                self.channel
+            ),
+        }
+    }
+}
+
+/// Sends, receives, `range` loops and `close` calls on a local `var ch chan T` that run before
+/// the first assignment to it. Operations inside `select` are skipped (a nil channel is the usual
+/// way to disable a case), as are channels assigned from closures or whose address is taken.
+pub fn find_nil_channel_ops(tree: &Tree, code: &str) -> AnalysisResult<Vec<NilChannelOp>> {
+    check_tree(tree, code)?;
+    let root = tree.root_node();
+    let mut findings = Vec::new();
+    let mut cursor = root.walk();
+    for function in root.named_children(&mut cursor) {
+        if !matches!(
+            function.kind(),
+            "function_declaration" | "method_declaration"
+        ) {
+            continue;
+        }
+        let Some(body) = function.child_by_field_name("body") else {
+            continue;
+        };
+        let mut declarations = Vec::new();
+        collect_nil_channel_declarations(body, code, &mut declarations);
+        for (name, declaration) in declarations {
+            let mut assignments = Vec::new();
+            let mut escapes = false;
+            collect_channel_assignments(body, code, &name, &mut assignments, &mut escapes);
+            if escapes {
+                continue;
+            }
+            let mut ops = Vec::new();
+            collect_nil_channel_ops(body, code, &name, &mut ops);
+            for (kind, op) in ops {
+                if op.start_byte() < declaration.start_byte() {
+                    continue;
+                }
+                let assigned_before = assignments.iter().any(|assignment| {
+                    assignment.start_byte() < op.start_byte() || shares_loop(*assignment, op, body)
+                });
+                if !assigned_before {
+                    findings.push(NilChannelOp {
+                        channel: name.clone(),
+                        kind,
+                        range: node_to_range(op),
+                        declaration: node_to_range(declaration),
+                    });
+                }
+            }
+        }
+    }
+    Ok(findings)
+}
+
+/// `var ch chan T` (no initializer) declared directly in the function, not in a closure.
+fn collect_nil_channel_declarations<'a>(
+    node: Node<'a>,
+    code: &str,
+    out: &mut Vec<(String, Node<'a>)>,
+) {
+    for_each_node_pruned(node, |node| {
+        if node.kind() == "func_literal" {
+            return false;
+        }
+        if node.kind() == "var_spec"
+            && node.child_by_field_name("value").is_none()
+            && node
+                .child_by_field_name("type")
+                .map(|t| t.kind() == "channel_type")
+                .unwrap_or(false)
+        {
+            let mut cursor = node.walk();
+            for name in node.children_by_field_name("name", &mut cursor) {
+                out.push((text(code, name).to_string(), name));
+            }
+        }
+        true
+    });
+}
+
+fn collect_channel_assignments<'a>(
+    node: Node<'a>,
+    code: &str,
+    name: &str,
+    out: &mut Vec<Node<'a>>,
+    escapes: &mut bool,
+) {
+    for_each_node(node, |node| match node.kind() {
+        "assignment_statement" => {
+            let assigns = node
+                .child_by_field_name("left")
+                .map(|left| {
+                    let mut cursor = left.walk();
+                    let assigns = left
+                        .named_children(&mut cursor)
+                        .any(|target| text(code, target) == name);
+                    assigns
+                })
+                .unwrap_or(false);
+            if assigns {
+                if inside_func_literal(node) {
+                    *escapes = true;
+                }
+                out.push(node);
+            }
+        }
+        "unary_expression"
+            if text(code, node).starts_with('&')
+                && node
+                    .child_by_field_name("operand")
+                    .map(|operand| text(code, operand) == name)
+                    .unwrap_or(false) =>
+        {
+            *escapes = true
+        }
+        _ => {}
+    });
+}
+
+fn inside_func_literal(node: Node) -> bool {
+    let mut current = node.parent();
+    while let Some(candidate) = current {
+        match candidate.kind() {
+            "func_literal" => return true,
+            "function_declaration" | "method_declaration" => return false,
+            _ => current = candidate.parent(),
+        }
+    }
+    false
+}
+
+fn collect_nil_channel_ops<'a>(
+    node: Node<'a>,
+    code: &str,
+    name: &str,
+    out: &mut Vec<(NilChannelOpKind, Node<'a>)>,
+) {
+    for_each_node_pruned(node, |node| {
+        if matches!(node.kind(), "select_statement" | "func_literal") {
+            return false;
+        }
+        match channel_op(node, code, name) {
+            Some(ChannelOp::Send) => out.push((NilChannelOpKind::Send, node)),
+            Some(ChannelOp::Receive) => out.push((NilChannelOpKind::Receive, node)),
+            None => {}
+        }
+        if closed_channel(node, code).is_some_and(|arg| text(code, arg) == name) {
+            out.push((NilChannelOpKind::Close, node));
+        }
+        true
+    });
+}
+
+/// A later iteration of a loop around both nodes sees the assignment.
+fn shares_loop(assignment: Node, op: Node, body: Node) -> bool {
+    let op_loops: Vec<usize> = ancestors_within(op, body)
+        .iter()
+        .filter(|n| n.kind() == "for_statement")
+        .map(|n| n.id())
+        .collect();
+    ancestors_within(assignment, body)
+        .iter()
+        .any(|n| n.kind() == "for_statement" && op_loops.contains(&n.id()))
+}
+
+/// A `for v := range ch` loop over a channel that producers send to but none of them closes, so
+/// the loop never ends once they are done.
+#[derive(Debug, Clone)]
+pub struct UnclosedRange {
+    pub channel: String,
+    /// The `range` clause.
+    pub range: Range,
+    pub creation: Range,
+    pub sends: Vec<Range>,
+}
+
+impl UnclosedRange {
+    pub fn message(&self) -> String {
+        format!(
+            "`range {}` never ends: producers send to `{}` but none of them closes it",
+            self.channel, self.channel
+        )
+    }
+}
+
+/// A producer closing a channel that producers in other goroutines may still send to.
+#[derive(Debug, Clone)]
+pub struct CloseByProducer {
+    pub channel: String,
+    pub close: Range,
+    /// Sends on the channel from other goroutines.
+    pub other_sends: Vec<Range>,
+}
+
+impl CloseByProducer {
+    pub fn message(&self) -> String {
+        format!(
+            "`{}` is closed by one of its producers while other goroutines may still send to it; a send on a closed channel panics",
+            self.channel
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChannelRole {
+    Send,
+    /// `<-ch`, including select cases.
+    Receive,
+    Range,
+    Close,
+}
+
+/// A send, range or close on the tracked channel and the goroutine performing it: the innermost
+/// `go` statement it runs under, or `None` for the creating function itself.
+struct RoleUse<'a> {
+    role: ChannelRole,
+    node: Node<'a>,
+    goroutine: Option<Node<'a>>,
+}
+
+/// Follows each channel made in a function through the goroutines and calls it is handed to
+/// (functions declared in the file, by parameter position) and classifies every function
+/// touching it as producer (sends), consumer (ranges) or closer. Channels that escape (returned,
+/// stored, or passed to functions outside the file) are skipped, since their other users are
+/// unknown.
+pub fn find_close_responsibility_issues(
+    tree: &Tree,
+    code: &str,
+) -> AnalysisResult<(Vec<UnclosedRange>, Vec<CloseByProducer>)> {
+    check_tree(tree, code)?;
+    let root = tree.root_node();
+    let callables = collect_callables(root, code);
+    let mut unclosed = Vec::new();
+    let mut early_closes = Vec::new();
+    let mut cursor = root.walk();
+    for function in root.named_children(&mut cursor) {
+        if !matches!(
+            function.kind(),
+            "function_declaration" | "method_declaration"
+        ) {
+            continue;
+        }
+        let Some(body) = function.child_by_field_name("body") else {
+            continue;
+        };
+        let mut channels = Vec::new();
+        collect_made_channels(body, code, &mut channels);
+        for (name, creation) in channels {
+            let mut uses = Vec::new();
+            let mut visited = HashSet::new();
+            if !collect_role_uses(
+                body,
+                code,
+                &name,
+                None,
+                &callables,
+                0,
+                &mut visited,
+                &mut uses,
+            ) {
+                continue;
+            }
+            let sends: Vec<&RoleUse> = uses
+                .iter()
+                .filter(|u| u.role == ChannelRole::Send)
+                .collect();
+            if sends.is_empty() {
+                continue;
+            }
+            let closes: Vec<&RoleUse> = uses
+                .iter()
+                .filter(|u| u.role == ChannelRole::Close)
+                .collect();
+            if closes.is_empty() {
+                unclosed.extend(uses.iter().filter(|u| u.role == ChannelRole::Range).map(
+                    |range| UnclosedRange {
+                        channel: name.clone(),
+                        range: node_to_range(range.node),
+                        creation: node_to_range(creation),
+                        sends: sends.iter().map(|send| node_to_range(send.node)).collect(),
+                    },
+                ));
+            }
+            for close in closes {
+                let is_producer = sends.iter().any(|send| send.goroutine == close.goroutine);
+                let other_sends: Vec<Range> = sends
+                    .iter()
+                    .filter(|send| send.goroutine != close.goroutine)
+                    .map(|send| node_to_range(send.node))
+                    .collect();
+                if is_producer && !other_sends.is_empty() {
+                    early_closes.push(CloseByProducer {
+                        channel: name.clone(),
+                        close: node_to_range(close.node),
+                        other_sends,
+                    });
+                }
+            }
+        }
+    }
+    Ok((unclosed, early_closes))
+}
+
+/// The producers and consumers of the channel named at `position`: the channel (at its `make`
+/// when the enclosing function creates it), every goroutine or function that sends to, receives
+/// from, ranges over or closes it, with those sites in the actor's `extra`, and `Spawn` edges
+/// from the functions starting the goroutines. Uses are followed into functions of the file the
+/// channel is passed to; `extra.escapes` is set when it is handed somewhere they cannot be.
+pub fn channel_flow(tree: &Tree, code: &str, position: Position) -> Option<GraphData> {
+    let (ident, function) = accessed_variable(
+        tree,
+        Range {
+            start: position,
+            end: position,
+        },
+    )?;
+    if ident.kind() != "identifier" {
+        return None;
+    }
+    let name = text(code, ident);
+    let body = function.child_by_field_name("body")?;
+    let mut channels = Vec::new();
+    collect_made_channels(body, code, &mut channels);
+    let creation = channels
+        .into_iter()
+        .find(|(channel, _)| channel == name)
+        .map(|(_, creation)| creation);
+    let callables = collect_callables(tree.root_node(), code);
+    let mut uses = Vec::new();
+    let mut visited = HashSet::new();
+    let complete = collect_role_uses(
+        body,
+        code,
+        name,
+        None,
+        &callables,
+        0,
+        &mut visited,
+        &mut uses,
+    );
+    if creation.is_none() && uses.is_empty() {
+        return None;
+    }
+
+    let channel_range = node_to_range(creation.unwrap_or(ident));
+    let mut ids = GraphIds::new(tree.root_node(), code, "");
+    let channel_id = ids.id("chan", name, creation.unwrap_or(ident));
+    let mut graph = GraphData {
+        nodes: vec![GraphNode {
+            id: channel_id.clone(),
+            label: name.to_string(),
+            entity_type: GraphEntityType::Channel,
+            range: channel_range,
+            extra: Some(json!({"created": creation.is_some(), "escapes": !complete})),
+            bytes: None,
+        }],
+        ..Default::default()
+    };
+    let mut actors: Vec<(Node, serde_json::Value)> = Vec::new();
+    for role_use in &uses {
+        let actor = role_use.goroutine.unwrap_or(function);
+        let index = match actors.iter().position(|(node, _)| *node == actor) {
+            Some(index) => index,
+            None => {
+                actors.push((
+                    actor,
+                    json!({"sends": [], "receives": [], "ranges": [], "closes": []}),
+                ));
+                actors.len() - 1
+            }
+        };
+        let (key, edge_type) = match role_use.role {
+            ChannelRole::Send => ("sends", GraphEdgeType::Send),
+            ChannelRole::Receive => ("receives", GraphEdgeType::Receive),
+            ChannelRole::Range => ("ranges", GraphEdgeType::Receive),
+            ChannelRole::Close => ("closes", GraphEdgeType::Close),
+        };
+        if let Some(sites) = actors[index].1[key].as_array_mut() {
+            sites.push(json!(node_to_range(role_use.node)));
+        }
+        let actor_id = ids.actor(actor);
+        let edge = if edge_type == GraphEdgeType::Receive {
+            GraphEdge {
+                from: channel_id.clone(),
+                to: actor_id,
+                edge_type,
+            }
+        } else {
+            GraphEdge {
+                from: actor_id,
+                to: channel_id.clone(),
+                edge_type,
+            }
+        };
+        if !graph.edges.contains(&edge) {
+            graph.edges.push(edge);
+        }
+    }
+    let mut spawners = Vec::new();
+    for (actor, extra) in actors {
+        if actor.kind() == "go_statement" {
+            if let Some(spawner) = enclosing_declaration(actor) {
+                graph.edges.push(GraphEdge {
+                    from: ids.actor(spawner),
+                    to: ids.actor(actor),
+                    edge_type: GraphEdgeType::Spawn,
+                });
+                spawners.push(spawner);
+            }
+        }
+        graph
+            .nodes
+            .push(actor_node(actor, code, ids.actor(actor), Some(extra)));
+    }
+    for spawner in spawners {
+        let id = ids.actor(spawner);
+        if !graph.nodes.iter().any(|node| node.id == id) {
+            graph.nodes.push(actor_node(spawner, code, id, None));
+        }
+    }
+    Some(graph)
+}
+
+fn actor_node(actor: Node, code: &str, id: String, extra: Option<serde_json::Value>) -> GraphNode {
+    let (label, entity_type, range) = match actor.child_by_field_name("name") {
+        Some(name) if actor.kind() != "go_statement" => (
+            text(code, name).to_string(),
+            if actor.kind() == "method_declaration" {
+                GraphEntityType::Method
+            } else {
+                GraphEntityType::Function
+            },
+            node_to_range(name),
+        ),
+        _ => (
+            "goroutine".to_string(),
+            GraphEntityType::Goroutine,
+            node_to_range(actor),
+        ),
+    };
+    GraphNode {
+        id,
+        label,
+        entity_type,
+        range,
+        extra,
+        bytes: None,
+    }
+}
+
+/// `make(chan T)` (buffered or not) assigned to a name anywhere in the function.
+fn collect_made_channels<'a>(node: Node<'a>, code: &str, out: &mut Vec<(String, Node<'a>)>) {
+    for_each_node(node, |node| {
+        for (name, value) in declared_values(node) {
+            if is_channel_make(value, code) {
+                out.push((text(code, name).to_string(), value));
+            }
+        }
+    });
+}
+
+/// Collects the roles `name` plays under `node`; `false` when the channel escapes.
+#[allow(clippy::too_many_arguments)]
+fn collect_role_uses<'a>(
+    node: Node<'a>,
+    code: &str,
+    name: &str,
+    goroutine: Option<Node<'a>>,
+    callables: &[Callable<'a>],
+    depth: usize,
+    visited: &mut HashSet<(usize, Option<usize>)>,
+    out: &mut Vec<RoleUse<'a>>,
+) -> bool {
+    let mut goroutines: Vec<Node<'a>> = goroutine.into_iter().collect();
+    let mut contained = true;
+    walk_tree(node, |visit| {
+        let node = match visit {
+            Visit::Enter(node) => node,
+            Visit::Leave(node) => {
+                if node.kind() == "go_statement" {
+                    goroutines.pop();
+                }
+                return true;
+            }
+        };
+        if node.kind() == "go_statement" {
+            goroutines.push(node);
+        }
+        let goroutine = goroutines.last().copied();
+        contained = role_use(node, code, name, goroutine, callables, depth, visited, out);
+        contained
+    });
+    contained
+}
+
+/// The role `node` itself gives `name`, following the channel into called functions; `false`
+/// when the channel escapes.
+#[allow(clippy::too_many_arguments)]
+fn role_use<'a>(
+    node: Node<'a>,
+    code: &str,
+    name: &str,
+    goroutine: Option<Node<'a>>,
+    callables: &[Callable<'a>],
+    depth: usize,
+    visited: &mut HashSet<(usize, Option<usize>)>,
+    out: &mut Vec<RoleUse<'a>>,
+) -> bool {
+    let is_channel = |field: &str| {
+        node.child_by_field_name(field)
+            .is_some_and(|operand| text(code, operand) == name)
+    };
+    match node.kind() {
+        "send_statement" if is_channel("channel") => out.push(RoleUse {
+            role: ChannelRole::Send,
+            node,
+            goroutine,
+        }),
+        "range_clause" if is_channel("right") => out.push(RoleUse {
+            role: ChannelRole::Range,
+            node,
+            goroutine,
+        }),
+        "unary_expression" if is_channel("operand") && text(code, node).starts_with("<-") => out
+            .push(RoleUse {
+                role: ChannelRole::Receive,
+                node,
+                goroutine,
+            }),
+        "call_expression" => {
+            let function = node
+                .child_by_field_name("function")
+                .map(|f| text(code, f))
+                .unwrap_or_default();
+            let Some(arguments) = node.child_by_field_name("arguments") else {
+                return true;
+            };
+            let mut cursor = arguments.walk();
+            let positions: Vec<usize> = arguments
+                .named_children(&mut cursor)
+                .enumerate()
+                .filter(|(_, arg)| text(code, *arg) == name)
+                .map(|(index, _)| index)
+                .collect();
+            if !positions.is_empty() {
+                match function {
+                    "close" => out.push(RoleUse {
+                        role: ChannelRole::Close,
+                        node,
+                        goroutine,
+                    }),
+                    "len" | "cap" => {}
+                    _ => {
+                        if depth >= MAX_CALL_DEPTH {
+                            return false;
+                        }
+                        let Some(target) = call_target(node, code, callables) else {
+                            return false;
+                        };
+                        let parameters = parameter_names(target.node, code);
+                        let Some(body) = target.node.child_by_field_name("body") else {
+                            return false;
+                        };
+                        for position in positions {
+                            let Some(parameter) = parameters.get(position) else {
+                                return false;
+                            };
+                            if visited.insert((target.node.id(), goroutine.map(|g| g.id())))
+                                && !collect_role_uses(
+                                    body,
+                                    code,
+                                    parameter,
+                                    goroutine,
+                                    callables,
+                                    depth + 1,
+                                    visited,
+                                    out,
+                                )
+                            {
+                                return false;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        "identifier" if text(code, node) == name && escapes(node) => return false,
+        _ => {}
+    }
+    true
+}
+
+/// A mention that stores or hands over the channel value itself.
+fn escapes(ident: Node) -> bool {
+    let Some(parent) = ident.parent() else {
+        return false;
+    };
+    match parent.kind() {
+        "literal_element" | "keyed_element" => true,
+        "send_statement" => parent
+            .child_by_field_name("value")
+            .is_some_and(|value| value.id() == ident.id()),
+        "expression_list" => parent.parent().is_some_and(|statement| {
+            statement.kind() == "return_statement"
+                || (matches!(
+                    statement.kind(),
+                    "assignment_statement" | "short_var_declaration"
+                ) && statement
+                    .child_by_field_name("right")
+                    .is_some_and(|right| right.id() == parent.id()))
+        }),
+        _ => false,
+    }
+}
+
+/// Parameter names of a function or func literal in declaration order.
+fn parameter_names(function: Node, code: &str) -> Vec<String> {
+    let Some(parameters) = function.child_by_field_name("parameters") else {
+        return Vec::new();
+    };
+    let mut names = Vec::new();
+    let mut cursor = parameters.walk();
+    for declaration in parameters.named_children(&mut cursor) {
+        let mut names_cursor = declaration.walk();
+        let declared: Vec<String> = declaration
+            .children_by_field_name("name", &mut names_cursor)
+            .map(|name| text(code, name).to_string())
+            .collect();
+        // `func(int, chan T)` declares one unnamed parameter per declaration.
+        if declared.is_empty() {
+            names.push("_".to_string());
+        }
+        names.extend(declared);
+    }
+    names
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GoroutineLeakKind {
+    /// A receive or `range` on a channel nothing sends to or closes.
+    ReceiveWithoutSender,
+    /// A send on an unbuffered channel nothing receives from.
+    SendWithoutReceiver,
+    /// A `select` without `default` whose cases all block forever.
+    SelectBlocked,
+}
+
+/// A statement in a goroutine that can never proceed, so the goroutine and everything it
+/// references stay alive until the program exits.
+#[derive(Debug, Clone)]
+pub struct GoroutineLeak {
+    pub kind: GoroutineLeakKind,
+    /// The blocking statement.
+    pub range: Range,
+    /// The channel it blocks on; empty for a `select`.
+    pub channel: String,
+    /// Where the channels involved are made.
+    pub creations: Vec<Range>,
+}
+
+impl GoroutineLeak {
+    pub fn message(&self) -> String {
+        match self.kind {
+            GoroutineLeakKind::ReceiveWithoutSender => format!(
+                "Possible goroutine leak: receiving from `{}` blocks forever, nothing sends to or closes it",
+                self.channel
+            ),
+            GoroutineLeakKind::SendWithoutReceiver => format!(
+                "Possible goroutine leak: sending on unbuffered `{}` blocks forever, nothing receives from it",
+                self.channel
+            ),
+            GoroutineLeakKind::SelectBlocked => {
+                "Possible goroutine leak: no case of this `select` can ever proceed".to_string()
+            }
+        }
+    }
+}
+
+/// Blocking operations in goroutines on channels made in the enclosing function, tracked like
+/// [`find_close_responsibility_issues`] through the goroutines and file-local functions the
+/// channel is passed to. Operations in a `select` are judged together: the `select` leaks when
+/// it has no `default` and every case is a dead operation on a tracked channel (or it has no
+/// cases). Escaping channels are skipped.
+pub fn find_goroutine_leaks(tree: &Tree, code: &str) -> AnalysisResult<Vec<GoroutineLeak>> {
+    check_tree(tree, code)?;
+    let root = tree.root_node();
+    let callables = collect_callables(root, code);
+    let mut leaks = Vec::new();
+    let mut cursor = root.walk();
+    for function in root.named_children(&mut cursor) {
+        if !matches!(
+            function.kind(),
+            "function_declaration" | "method_declaration"
+        ) {
+            continue;
+        }
+        let Some(body) = function.child_by_field_name("body") else {
+            continue;
+        };
+        let mut channels = Vec::new();
+        collect_made_channels(body, code, &mut channels);
+        // (channel, creation, receive is dead, send is dead)
+        let mut dead: Vec<(String, Node, bool, bool)> = Vec::new();
+        for (name, creation) in channels {
+            let mut uses = Vec::new();
+            let mut visited = HashSet::new();
+            if !collect_role_uses(
+                body,
+                code,
+                &name,
+                None,
+                &callables,
+                0,
+                &mut visited,
+                &mut uses,
+            ) {
+                continue;
+            }
+            let has = |role: ChannelRole| uses.iter().any(|u| u.role == role);
+            let receive_dead = !has(ChannelRole::Send) && !has(ChannelRole::Close);
+            let send_dead = is_unbuffered_make(creation, code)
+                && !has(ChannelRole::Receive)
+                && !has(ChannelRole::Range);
+            for blocked in &uses {
+                let kind = match blocked.role {
+                    ChannelRole::Receive | ChannelRole::Range if receive_dead => {
+                        GoroutineLeakKind::ReceiveWithoutSender
+                    }
+                    ChannelRole::Send if send_dead => GoroutineLeakKind::SendWithoutReceiver,
+                    _ => continue,
+                };
+                if blocked.goroutine.is_none() || select_case(blocked.node).is_some() {
+                    continue;
+                }
+                leaks.push(GoroutineLeak {
+                    kind,
+                    range: node_to_range(blocked.node),
+                    channel: name.clone(),
+                    creations: vec![node_to_range(creation)],
+                });
+            }
+            dead.push((name, creation, receive_dead, send_dead));
+        }
+        let mut selects = Vec::new();
+        collect_kind(body, "select_statement", &mut selects);
+        for select in selects {
+            if !spawned(select, body) {
+                continue;
+            }
+            let mut cursor = select.walk();
+            let cases: Vec<Node> = select.named_children(&mut cursor).collect();
+            if cases.iter().any(|case| case.kind() == "default_case") {
+                continue;
+            }
+            let mut creations = Vec::new();
+            let all_dead = cases.iter().all(|case| {
+                let Some((name, is_send)) = case_operation(*case, code) else {
+                    return false;
+                };
+                match dead.iter().find(|(channel, ..)| *channel == name) {
+                    Some((_, creation, receive_dead, send_dead))
+                        if (is_send && *send_dead) || (!is_send && *receive_dead) =>
+                    {
+                        creations.push(node_to_range(*creation));
+                        true
+                    }
+                    _ => false,
+                }
+            });
+            if all_dead {
+                leaks.push(GoroutineLeak {
+                    kind: GoroutineLeakKind::SelectBlocked,
+                    range: node_to_range(select),
+                    channel: String::new(),
+                    creations,
+                });
+            }
+        }
+    }
+    leaks.sort_by_key(|leak| (leak.range.start.line, leak.range.start.character));
+    Ok(leaks)
+}
+
+/// `node` runs in a goroutine started by a `go` statement inside `body`.
+fn spawned(node: Node, body: Node) -> bool {
+    let mut current = node.parent();
+    while let Some(candidate) = current {
+        if candidate.id() == body.id() {
+            return false;
+        }
+        if candidate.kind() == "go_statement" {
+            return true;
+        }
+        current = candidate.parent();
+    }
+    false
+}
+
+/// The `select` case whose communication `node` is.
+fn select_case(node: Node) -> Option<Node> {
+    let mut current = node.parent();
+    while let Some(candidate) = current {
+        match candidate.kind() {
+            "communication_case" => {
+                return candidate
+                    .child_by_field_name("communication")
+                    .filter(|comm| {
+                        comm.start_byte() <= node.start_byte() && node.end_byte() <= comm.end_byte()
+                    })
+                    .map(|_| candidate);
+            }
+            "block" | "func_literal" => return None,
+            _ => current = candidate.parent(),
+        }
+    }
+    None
+}
+
+/// The channel a `select` case communicates on and whether it sends.
+fn case_operation(case: Node, code: &str) -> Option<(String, bool)> {
+    let communication = case.child_by_field_name("communication")?;
+    if communication.kind() == "send_statement" {
+        let channel = communication.child_by_field_name("channel")?;
+        return Some((text(code, channel).to_string(), true));
+    }
+    let mut receives = Vec::new();
+    collect_kind(communication, "unary_expression", &mut receives);
+    let receive = receives
+        .into_iter()
+        .find(|unary| text(code, *unary).starts_with("<-"))?;
+    let channel = receive.child_by_field_name("operand")?;
+    Some((text(code, channel).to_string(), false))
+}
+
+/// A `select` whose only case is `default`. It never waits on a channel, so inside a loop it
+/// polls: the loop spins at full CPU instead of blocking until there is work.
+#[derive(Debug, Clone)]
+pub struct BusyWaitSelect {
+    /// The `select` keyword.
+    pub range: Range,
+    /// The enclosing `for` loop, when the select repeats.
+    pub in_loop: Option<Range>,
+}
+
+impl BusyWaitSelect {
+    pub fn message(&self) -> String {
+        if self.in_loop.is_some() {
+            "Busy wait: this `select` has only a `default` case, so the loop spins without blocking; add a case receiving the event to wait for".to_string()
+        } else {
+            "This `select` has only a `default` case and never waits on a channel; it is equivalent to running the default body".to_string()
+        }
+    }
+}
+
+/// `select` statements with a `default` case and no communication case.
+pub fn find_busy_wait_selects(tree: &Tree) -> Vec<BusyWaitSelect> {
+    let mut selects = Vec::new();
+    collect_kind(tree.root_node(), "select_statement", &mut selects);
+    selects
+        .into_iter()
+        .filter_map(|select| {
+            let mut cursor = select.walk();
+            let cases: Vec<Node> = select
+                .named_children(&mut cursor)
+                .filter(|case| case.kind() != "comment")
+                .collect();
+            if cases.is_empty() || cases.iter().any(|case| case.kind() != "default_case") {
+                return None;
+            }
+            let mut in_loop = None;
+            let mut current = select.parent();
+            while let Some(candidate) = current {
+                match candidate.kind() {
+                    "for_statement" => {
+                        in_loop = Some(node_to_range(candidate.child(0).unwrap_or(candidate)));
+                        break;
+                    }
+                    "func_literal" | "function_declaration" | "method_declaration" => break,
+                    _ => current = candidate.parent(),
+                }
+            }
+            Some(BusyWaitSelect {
+                range: node_to_range(select.child(0).unwrap_or(select)),
+                in_loop,
+            })
+        })
+        .collect()
+}
+
+/// Every other access of the variable at `range` in the same top-level function is ordered with
+/// it: by running in the same goroutine, by preceding the `go` statement that starts the other
+/// goroutine, or through a channel, where one side's access precedes a send on (or `close` of) a
+/// channel and the other side's access follows a receive from the same channel. Channels are
+/// matched by name, and only buffered-style pairing (send before receive) is modeled. At least
+/// one access must be ordered through a channel; the others alone are not evidence of one.
+pub fn is_ordered_by_channel(tree: &Tree, range: Range, code: &str) -> bool {
+    let Some((access, function)) = accessed_variable(tree, range) else {
+        return false;
+    };
+    let name = text(code, access);
+    let mut candidates = Vec::new();
+    collect_kind(function, access.kind(), &mut candidates);
+    let others: Vec<Node> = candidates
+        .into_iter()
+        .filter(|other| *other != access && text(code, *other) == name)
+        .collect();
+    let mut sends = Vec::new();
+    let mut receives = Vec::new();
+    collect_channel_sync_sites(function, code, &mut sends, &mut receives);
+    let mut paired = false;
+    for other in others {
+        let (Some(a), Some(b)) = (execution_context(access), execution_context(other)) else {
+            return false;
+        };
+        if a == b {
+            continue;
+        }
+        let spawned_after = |earlier: Node, context: Node, later_context: Node| {
+            earlier.end_byte() <= later_context.start_byte()
+                && later_context.start_byte() >= context.start_byte()
+                && later_context.end_byte() <= context.end_byte()
+        };
+        let via_channel =
+            |earlier: Node, earlier_context: Node, later: Node, later_context: Node| {
+                sends.iter().any(|(channel, send)| {
+                    send.start_byte() >= earlier.end_byte()
+                        && execution_context(*send) == Some(earlier_context)
+                        && receives.iter().any(|(received, receive)| {
+                            received == channel
+                                && receive.end_byte() <= later.start_byte()
+                                && execution_context(*receive) == Some(later_context)
+                        })
+                })
+            };
+        if spawned_after(access, a, b) || spawned_after(other, b, a) {
+            continue;
+        }
+        if !(via_channel(access, a, other, b) || via_channel(other, b, access, a)) {
+            return false;
+        }
+        paired = true;
+    }
+    paired
+}
+
+/// The variable at `range` is handed off over a channel: one goroutine sends it (`ch <- x` or
+/// `ch <- &x`) after its last access, and every access elsewhere in the top-level function either
+/// precedes the `go` statement starting the sender or follows a receive from that channel. The
+/// receiver usually works on the received copy of the pointer, so often nothing follows at all. A
+/// send in a loop only counts when the variable is declared inside the loop; otherwise the next
+/// iteration touches what it just sent.
+pub fn is_ownership_transferred(tree: &Tree, range: Range, code: &str) -> bool {
+    let Some((access, function)) = accessed_variable(tree, range) else {
+        return false;
+    };
+    let name = text(code, access);
+    let mut occurrences = Vec::new();
+    collect_kind(function, access.kind(), &mut occurrences);
+    occurrences.retain(|occurrence| text(code, *occurrence) == name);
+    let mut sends = Vec::new();
+    let mut receives = Vec::new();
+    collect_channel_sync_sites(function, code, &mut sends, &mut receives);
+    sends
+        .iter()
+        .filter(|(_, send)| sends_variable(*send, name, code))
+        .any(|(channel, send)| {
+            let Some(sender) = execution_context(*send) else {
+                return false;
+            };
+            if reused_in_loop(*send, sender, name, code) {
+                return false;
+            }
+            occurrences.iter().all(|occurrence| {
+                if send.start_byte() <= occurrence.start_byte()
+                    && occurrence.end_byte() <= send.end_byte()
+                {
+                    return true;
+                }
+                let Some(context) = execution_context(*occurrence) else {
+                    return false;
+                };
+                if context == sender {
+                    return occurrence.end_byte() <= send.start_byte();
+                }
+                let before_spawn = context.start_byte() <= sender.start_byte()
+                    && sender.end_byte() <= context.end_byte()
+                    && occurrence.end_byte() <= sender.start_byte();
+                before_spawn
+                    || receives.iter().any(|(received, receive)| {
+                        received == channel
+                            && receive.end_byte() <= occurrence.start_byte()
+                            && execution_context(*receive) == Some(context)
+                    })
+            })
+        })
+}
+
+/// The identifier or selector at `range` and the top-level function containing it.
+fn accessed_variable(tree: &Tree, range: Range) -> Option<(Node<'_>, Node<'_>)> {
+    let point = |position: tower_lsp::lsp_types::Position| tree_sitter::Point {
+        row: position.line as usize,
+        column: position.character as usize,
+    };
+    let access = tree
+        .root_node()
+        .named_descendant_for_point_range(point(range.start), point(range.end))?;
+    if !matches!(access.kind(), "identifier" | "selector_expression") {
+        return None;
+    }
+    let mut function = access;
+    while !matches!(
+        function.kind(),
+        "function_declaration" | "method_declaration"
+    ) {
+        function = function.parent()?;
+    }
+    Some((access, function))
+}
+
+/// `ch <- name` or `ch <- &name`.
+fn sends_variable(send: Node, name: &str, code: &str) -> bool {
+    if send.kind() != "send_statement" {
+        return false;
+    }
+    let Some(mut value) = send.child_by_field_name("value") else {
+        return false;
+    };
+    if value.kind() == "unary_expression"
+        && value
+            .child_by_field_name("operator")
+            .is_some_and(|operator| operator.kind() == "&")
+    {
+        match value.child_by_field_name("operand") {
+            Some(operand) => value = operand,
+            None => return false,
+        }
+    }
+    text(code, value) == name
+}
+
+/// `send` repeats in a loop of its goroutine that does not declare `name` afresh each iteration.
+fn reused_in_loop(send: Node, sender: Node, name: &str, code: &str) -> bool {
+    let mut current = send.parent();
+    while let Some(candidate) = current {
+        if candidate == sender {
+            return false;
+        }
+        if candidate.kind() == "for_statement" {
+            let mut declarations = Vec::new();
+            for kind in ["short_var_declaration", "var_spec"] {
+                collect_kind(candidate, kind, &mut declarations);
+            }
+            let mut names = Vec::new();
+            for declaration in declarations {
+                let mut cursor = declaration.walk();
+                match declaration.kind() {
+                    "var_spec" => {
+                        names.extend(declaration.children_by_field_name("name", &mut cursor))
+                    }
+                    _ => {
+                        if let Some(left) = declaration.child_by_field_name("left") {
+                            let mut cursor = left.walk();
+                            names.extend(left.named_children(&mut cursor));
+                        }
+                    }
+                }
+            }
+            return !names.iter().any(|declared| text(code, *declared) == name);
+        }
+        current = candidate.parent();
+    }
+    false
+}
+
+/// Sends and `close` calls, and receives (including `range` over a channel), by channel name.
+fn collect_channel_sync_sites<'a>(
+    node: Node<'a>,
+    code: &'a str,
+    sends: &mut Vec<(&'a str, Node<'a>)>,
+    receives: &mut Vec<(&'a str, Node<'a>)>,
+) {
+    CHANNEL_OPS.for_each_match(node, code, |captures| {
+        let Some(channel) = captures.get("channel") else {
+            return true;
+        };
+        let name = text(code, channel);
+        if let Some(site) = captures.get("send").or_else(|| captures.get("close")) {
+            sends.push((name, site));
+        } else if let Some(site) = captures.get("receive") {
+            receives.push((name, site));
+        } else if captures.get("range").is_some() {
+            receives.push((name, channel));
+        }
+        true
+    });
+}
+
+/// The function or function literal whose goroutine executes `node`.
+fn execution_context(node: Node) -> Option<Node> {
+    let mut current = Some(node);
+    while let Some(candidate) = current {
+        if matches!(
+            candidate.kind(),
+            "function_declaration" | "method_declaration" | "func_literal"
+        ) {
+            return Some(candidate);
+        }
+        current = candidate.parent();
+    }
+    None
+}
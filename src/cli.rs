@@ -0,0 +1,224 @@
+use std::path::{Path, PathBuf};
+
+use tower_lsp::lsp_types::Url;
+
+use crate::analysis::count_entities;
+use crate::backend::FileAnalyzer;
+use crate::documents::parse_go;
+use crate::interchange::GraphFormat;
+use crate::mermaid::{graph_to_mermaid, parse_entity_type, GraphFilter};
+use crate::rule_api::RuleRegistry;
+use crate::rules::RuleDocsConfig;
+use crate::summary::{
+    collect_go_files, summarize, synchronized_goroutines, FileReport, SummarySort,
+};
+use crate::transport::Transport;
+
+const USAGE: &str = "Usage:
+  go-analyzer                            run the language server on stdin/stdout
+  go-analyzer --listen <addr>            run the language server on TCP, e.g. 127.0.0.1:9257
+  go-analyzer --pipe <name>              run the language server over the editor's pipe
+                                         (a Unix socket path or a Windows named pipe)
+  go-analyzer --export-graph <file.go> [--format dot|graphml|jgf]
+                                         print the file's entity graph, as Graphviz DOT by default
+  go-analyzer mermaid <file.go> [--root <name>] [--depth <n>] [--entities <type,...>]
+                                         print the entity graph as a Mermaid flowchart
+  go-analyzer summary [<dir>] [--sort findings|errors|goroutines|channels|sync_coverage|package]
+                      [--json]           print findings, entity counts and sync coverage per
+                                         package of the Go files under <dir> (default: .)";
+
+/// Runs a command-line mode when the arguments ask for one, printing its output to stdout.
+/// Returns the exit code, or `None` to start the language server.
+pub fn run(args: &[String]) -> Option<i32> {
+    if let Some(index) = args.iter().position(|arg| arg == "--export-graph") {
+        let Some(path) = args.get(index + 1) else {
+            eprintln!("{}", USAGE);
+            return Some(2);
+        };
+        let format = match args.iter().position(|arg| arg == "--format") {
+            Some(index) => match args
+                .get(index + 1)
+                .and_then(|name| GraphFormat::parse(name))
+            {
+                Some(format) => format,
+                None => {
+                    eprintln!("--format needs one of dot, graphml, jgf\n{}", USAGE);
+                    return Some(2);
+                }
+            },
+            None => GraphFormat::Dot,
+        };
+        return Some(export(path, |code| {
+            let tree = parse_go(code, None)?;
+            Some(format.render(&crate::analysis::build_graph_data(&tree, code)))
+        }));
+    }
+    match args.first().map(String::as_str) {
+        Some("mermaid") => {
+            let (path, filter) = match mermaid_args(&args[1..]) {
+                Ok(parsed) => parsed,
+                Err(message) => {
+                    eprintln!("{}\n{}", message, USAGE);
+                    return Some(2);
+                }
+            };
+            Some(export(&path, |code| {
+                let tree = parse_go(code, None)?;
+                let graph = crate::analysis::build_graph_data(&tree, code);
+                Some(graph_to_mermaid(&filter.apply(&graph)))
+            }))
+        }
+        Some("summary") => match summary_args(&args[1..]) {
+            Ok((root, sort_by, json)) => Some(print_summary(&root, sort_by, json)),
+            Err(message) => {
+                eprintln!("{}\n{}", message, USAGE);
+                Some(2)
+            }
+        },
+        Some("--help" | "-h") => {
+            println!("{}", USAGE);
+            Some(0)
+        }
+        _ => match Transport::from_args(args) {
+            Ok(_) => None,
+            Err(message) => {
+                eprintln!("{}\n{}", message, USAGE);
+                Some(2)
+            }
+        },
+    }
+}
+
+fn export(path: &str, render: impl Fn(&str) -> Option<String>) -> i32 {
+    let code = match std::fs::read_to_string(path) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", path, e);
+            return 1;
+        }
+    };
+    match render(&code) {
+        Some(output) => {
+            print!("{}", output);
+            0
+        }
+        None => {
+            eprintln!("Failed to parse {}", path);
+            1
+        }
+    }
+}
+
+fn mermaid_args(args: &[String]) -> Result<(String, GraphFilter), String> {
+    let mut path = None;
+    let mut filter = GraphFilter::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--root" => filter.root = Some(value()?),
+            "--depth" => {
+                let depth = value()?;
+                filter.depth = Some(
+                    depth
+                        .parse()
+                        .map_err(|_| format!("invalid depth `{}`", depth))?,
+                );
+            }
+            "--entities" => {
+                for name in value()?.split(',').filter(|name| !name.trim().is_empty()) {
+                    let entity = parse_entity_type(name)
+                        .ok_or_else(|| format!("unknown entity type `{}`", name))?;
+                    filter.entities.push(entity);
+                }
+            }
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument `{}`", arg)),
+        }
+    }
+    let path = path.ok_or_else(|| "missing <file.go>".to_string())?;
+    Ok((path, filter))
+}
+
+fn summary_args(args: &[String]) -> Result<(PathBuf, SummarySort, bool), String> {
+    let mut root = None;
+    let mut sort_by = SummarySort::default();
+    let mut json = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--sort" => {
+                let name = args
+                    .next()
+                    .ok_or_else(|| format!("{} needs a value", arg))?;
+                sort_by = serde_json::from_value(serde_json::json!(name))
+                    .map_err(|_| format!("unknown sort `{}`", name))?;
+            }
+            "--json" => json = true,
+            _ if root.is_none() && !arg.starts_with("--") => root = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument `{}`", arg)),
+        }
+    }
+    Ok((root.unwrap_or_else(|| PathBuf::from(".")), sort_by, json))
+}
+
+/// Analyzes the Go files under `root` as `goanalyzer/workspaceSummary` does, without the disk
+/// cache, and prints the per-package totals as a table or as the command's JSON result.
+fn print_summary(root: &Path, sort_by: SummarySort, json: bool) -> i32 {
+    let root = match root.canonicalize() {
+        Ok(root) if root.is_dir() => root,
+        Ok(root) => {
+            eprintln!("{} is not a directory", root.display());
+            return 1;
+        }
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", root.display(), e);
+            return 1;
+        }
+    };
+    let analyzer = FileAnalyzer {
+        rule_docs: RuleDocsConfig::from_env(),
+        custom_rules: RuleRegistry::from_env(),
+    };
+    let mut reports = Vec::new();
+    for path in collect_go_files(&root) {
+        let Ok(code) = std::fs::read_to_string(&path) else {
+            eprintln!("Skipping {}: not readable", path.display());
+            continue;
+        };
+        let (Some(tree), Ok(uri)) = (parse_go(&code, None), Url::from_file_path(&path)) else {
+            eprintln!("Skipping {}: failed to parse", path.display());
+            continue;
+        };
+        let diagnostics = match analyzer.file_diagnostics(&uri, &tree, &code) {
+            Ok(findings) => findings.diagnostics,
+            Err(e) => {
+                eprintln!("Skipping {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        reports.push(FileReport {
+            counts: count_entities(&tree, &code),
+            synchronized_goroutines: synchronized_goroutines(&tree, &code),
+            diagnostics,
+            path,
+        });
+    }
+    let summary = summarize(&[root], reports, sort_by);
+    if json {
+        match serde_json::to_string_pretty(&summary) {
+            Ok(text) => println!("{}", text),
+            Err(e) => {
+                eprintln!("Failed to write the summary: {}", e);
+                return 1;
+            }
+        }
+    } else {
+        print!("{}", summary.to_table());
+    }
+    0
+}
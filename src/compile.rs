@@ -0,0 +1,252 @@
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use tokio::task::AbortHandle;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString, Position, Range};
+use url::Url;
+
+/// Code of the diagnostics published from `go build` errors.
+pub const COMPILE_ERROR: &str = "compile-error";
+
+/// Whether saving a file builds its package to report compiler errors: `GO_ANALYZER_BUILD_ON_SAVE`
+/// (off by default), each build bounded by `GO_ANALYZER_BUILD_TIMEOUT_SECS` (60 by default).
+#[derive(Clone, Debug)]
+pub struct BuildOnSaveConfig {
+    pub enabled: bool,
+    pub timeout: Duration,
+}
+
+impl BuildOnSaveConfig {
+    pub fn from_env() -> Self {
+        let enabled = match std::env::var("GO_ANALYZER_BUILD_ON_SAVE") {
+            Ok(v) => matches!(v.as_str(), "1" | "true" | "TRUE" | "yes" | "YES"),
+            Err(_) => false,
+        };
+        let secs = std::env::var("GO_ANALYZER_BUILD_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(60);
+        Self {
+            enabled,
+            timeout: Duration::from_secs(secs),
+        }
+    }
+}
+
+/// One error `go build` printed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompilerError {
+    pub file: PathBuf,
+    /// 1-based, as printed.
+    pub line: u32,
+    /// 1-based byte column; absent for errors about a whole line.
+    pub column: Option<u32>,
+    pub message: String,
+}
+
+/// The `go` command checking the package of the saved file, with `-gcflags=-e` so the compiler
+/// reports every error instead of stopping after ten. `go build` never compiles `_test.go`
+/// files, so saving one runs `go test` instead, which compiles the package with its tests and
+/// runs none of them (a `TestMain` still runs).
+pub fn build_args(saved: &Path) -> Vec<&'static str> {
+    if is_test_file(saved) {
+        vec!["test", "-gcflags=-e", "-run", "^$", "-count=1", "."]
+    } else {
+        let null = if cfg!(windows) { "NUL" } else { "/dev/null" };
+        vec!["build", "-gcflags=-e", "-o", null, "."]
+    }
+}
+
+pub fn is_test_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().ends_with("_test.go"))
+}
+
+/// Runs `go` with `args`, from [`build_args`], in the package directory `dir`, discarding what
+/// it builds. Returns the errors it printed; empty when it succeeded.
+pub async fn run_go_build(
+    dir: &Path,
+    args: &[&str],
+    config: &BuildOnSaveConfig,
+) -> std::io::Result<String> {
+    let child = tokio::process::Command::new("go")
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let output = tokio::time::timeout(config.timeout, child.wait_with_output())
+        .await
+        .map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!(
+                    "`go {}` did not finish within {}s",
+                    args.first().copied().unwrap_or_default(),
+                    config.timeout.as_secs()
+                ),
+            )
+        })??;
+    if output.status.success() {
+        return Ok(String::new());
+    }
+    Ok(String::from_utf8_lossy(&output.stderr).into_owned())
+}
+
+/// The builds started on save, at most one per package directory. Each start supersedes the
+/// directory's running build: its task is aborted, which kills `go`, and its generation is no
+/// longer the latest, so nothing it computed gets published.
+#[derive(Debug, Default)]
+pub struct PackageBuilds {
+    running: Mutex<HashMap<PathBuf, (u64, AbortHandle)>>,
+    next_generation: AtomicU64,
+}
+
+impl PackageBuilds {
+    /// Spawns `build`, given its generation, as the build of `dir`, and returns without waiting.
+    pub fn start<F, Fut>(&self, dir: PathBuf, build: F)
+    where
+        F: FnOnce(u64) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let generation = self.next_generation.fetch_add(1, Ordering::Relaxed) + 1;
+        // Held across the spawn, so the task cannot finish before it is registered.
+        let mut running = self.running.lock().unwrap_or_else(PoisonError::into_inner);
+        let task = tokio::spawn(build(generation));
+        if let Some((_, previous)) = running.insert(dir, (generation, task.abort_handle())) {
+            previous.abort();
+        }
+    }
+
+    /// Ends the build `generation` of `dir` once `go` is done. `true` when it is still the
+    /// latest, so its errors may be published: a later start no longer aborts it.
+    pub fn finish(&self, dir: &Path, generation: u64) -> bool {
+        let mut running = self.running.lock().unwrap_or_else(PoisonError::into_inner);
+        let latest = running
+            .get(dir)
+            .is_some_and(|(latest, _)| *latest == generation);
+        if latest {
+            running.remove(dir);
+        }
+        latest
+    }
+
+    /// Aborts every running build.
+    pub fn cancel_all(&self) {
+        let mut running = self.running.lock().unwrap_or_else(PoisonError::into_inner);
+        for (_, (_, task)) in running.drain() {
+            task.abort();
+        }
+    }
+}
+
+/// The errors in `go build` output, with paths resolved against `dir`, the directory it ran in.
+/// Indented lines continue the message before them; `# package` headers and lines without a
+/// position are skipped.
+pub fn parse_build_output(output: &str, dir: &Path) -> Vec<CompilerError> {
+    let mut errors: Vec<CompilerError> = Vec::new();
+    for line in output.lines() {
+        if line.starts_with('\t') || line.starts_with("    ") {
+            if let Some(error) = errors.last_mut() {
+                error.message.push('\n');
+                error.message.push_str(line.trim());
+            }
+            continue;
+        }
+        if let Some(error) = parse_error_line(line, dir) {
+            errors.push(error);
+        }
+    }
+    errors
+}
+
+/// `./main.go:12:5: undefined: foo` or `main.go:12: missing return`.
+fn parse_error_line(line: &str, dir: &Path) -> Option<CompilerError> {
+    let (file, rest) = line.split_at(line.find(".go:")? + ".go".len());
+    let (line_number, rest) = rest[1..].split_once(':')?;
+    let line_number = line_number.parse().ok()?;
+    let (column, message) = match rest.split_once(':') {
+        Some((column, message)) if column.parse::<u32>().is_ok() => (column.parse().ok(), message),
+        _ => (None, rest),
+    };
+    let message = message.trim();
+    if message.is_empty() {
+        return None;
+    }
+    let path = Path::new(file.trim_start_matches("./"));
+    Some(CompilerError {
+        file: if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            dir.join(path)
+        },
+        line: line_number,
+        column,
+        message: message.to_string(),
+    })
+}
+
+/// One error diagnostic per compiler error, on the word at its column (or the whole line),
+/// grouped by file, in byte columns. `text` gives a file's content, to find the word.
+pub fn compiler_diagnostics(
+    errors: &[CompilerError],
+    text: impl Fn(&Path) -> Option<String>,
+) -> BTreeMap<Url, Vec<Diagnostic>> {
+    let mut by_file: BTreeMap<Url, Vec<Diagnostic>> = BTreeMap::new();
+    for error in errors {
+        let Ok(uri) = Url::from_file_path(&error.file) else {
+            continue;
+        };
+        let Some(line) = error.line.checked_sub(1) else {
+            continue;
+        };
+        let content = text(&error.file).unwrap_or_default();
+        let line_text = content.lines().nth(line as usize).unwrap_or_default();
+        let (start, end) = match error.column {
+            Some(column) => {
+                let start = floor_char_boundary(line_text, column.saturating_sub(1) as usize);
+                let word = line_text[start..]
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(line_text.len() - start);
+                let end = floor_char_boundary(line_text, start + word.max(1));
+                (start, end)
+            }
+            None => (
+                line_text.len() - line_text.trim_start().len(),
+                line_text.trim_end().len(),
+            ),
+        };
+        let diagnostic = Diagnostic {
+            range: Range::new(
+                Position::new(line, start as u32),
+                Position::new(line, end.max(start) as u32),
+            ),
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: Some(NumberOrString::String(COMPILE_ERROR.to_string())),
+            source: Some("go-analyzer".to_string()),
+            message: error.message.clone(),
+            ..Default::default()
+        };
+        let diagnostics = by_file.entry(uri).or_default();
+        if !diagnostics.contains(&diagnostic) {
+            diagnostics.push(diagnostic);
+        }
+    }
+    by_file
+}
+
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
@@ -0,0 +1,102 @@
+use std::sync::OnceLock;
+
+use tree_sitter::{Node, Query, QueryCursor};
+
+/// Declarations, goroutines and channel types, for [`crate::analysis::count_entities`].
+pub static ENTITIES: Detector = Detector::new("entities", include_str!("queries/entities.scm"));
+
+/// Calls and `select` statements that may synchronize a block.
+pub static SYNC_CALLS: Detector =
+    Detector::new("sync_calls", include_str!("queries/sync_calls.scm"));
+
+/// Channel sends, receives, `range` loops and `close` calls.
+pub static CHANNEL_OPS: Detector =
+    Detector::new("channel_ops", include_str!("queries/channel_ops.scm"));
+
+/// A tree-sitter query over the Go grammar, compiled on first use. A detector is a query in
+/// `src/queries` plus the handler its caller passes to [`Detector::for_each_match`].
+pub struct Detector {
+    name: &'static str,
+    source: &'static str,
+    query: OnceLock<Option<Query>>,
+}
+
+/// The captures of one match, by capture name.
+pub struct Captures<'t> {
+    names: Vec<(&'static str, Node<'t>)>,
+}
+
+impl<'t> Captures<'t> {
+    pub fn get(&self, name: &str) -> Option<Node<'t>> {
+        self.names
+            .iter()
+            .find(|(capture, _)| *capture == name)
+            .map(|(_, node)| *node)
+    }
+}
+
+impl Detector {
+    pub const fn new(name: &'static str, source: &'static str) -> Self {
+        Self {
+            name,
+            source,
+            query: OnceLock::new(),
+        }
+    }
+
+    /// The compiled query; `None`, reported once, when it does not compile.
+    fn query(&'static self) -> Option<&'static Query> {
+        self.query
+            .get_or_init(
+                || match Query::new(tree_sitter_go::language(), self.source) {
+                    Ok(query) => Some(query),
+                    Err(e) => {
+                        tracing::error!("Failed to compile the {} query: {:?}", self.name, e);
+                        None
+                    }
+                },
+            )
+            .as_ref()
+    }
+
+    /// Calls `handler` for every match within `node` until it returns `false`.
+    pub fn for_each_match<'t>(
+        &'static self,
+        node: Node<'t>,
+        code: &str,
+        mut handler: impl FnMut(&Captures<'t>) -> bool,
+    ) {
+        let Some(query) = self.query() else {
+            return;
+        };
+        let names = query.capture_names();
+        let mut cursor = QueryCursor::new();
+        for found in cursor.matches(query, node, code.as_bytes()) {
+            let captures = Captures {
+                names: found
+                    .captures
+                    .iter()
+                    .map(|capture| (names[capture.index as usize].as_str(), capture.node))
+                    .collect(),
+            };
+            if !handler(&captures) {
+                return;
+            }
+        }
+    }
+
+    /// Whether `predicate` holds for any match within `node`.
+    pub fn any_match<'t>(
+        &'static self,
+        node: Node<'t>,
+        code: &str,
+        mut predicate: impl FnMut(&Captures<'t>) -> bool,
+    ) -> bool {
+        let mut found = false;
+        self.for_each_match(node, code, |captures| {
+            found = predicate(captures);
+            !found
+        });
+        found
+    }
+}
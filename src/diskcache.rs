@@ -0,0 +1,211 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::Diagnostic;
+
+use crate::record::stable_hash;
+use crate::stats::{CacheStats, HitCounter};
+use crate::summary::FileReport;
+use crate::types::EntityCount;
+
+/// Bumped whenever [`FileArtifacts`] or the analyses behind it change shape.
+const FORMAT_VERSION: u32 = 1;
+
+/// Stores between two prunings of the cache directory; the first store of a session prunes.
+const PRUNE_INTERVAL: usize = 64;
+
+/// Per-file analysis results kept on disk between sessions, so summarizing a workspace again
+/// only parses and analyzes files whose content changed. Enabled unless `GO_ANALYZER_CACHE` is
+/// `0`/`false`; stored under `GO_ANALYZER_CACHE_DIR` (default: a `go-analyzer-cache` directory
+/// in the system temp directory). Entries older than `GO_ANALYZER_CACHE_MAX_AGE_DAYS` (default
+/// 30) are removed, then the oldest ones until the entries take at most `GO_ANALYZER_CACHE_MAX_MB`
+/// (default 256); entries of older analyzer versions are never hit again and age out.
+#[derive(Clone, Debug)]
+pub struct AnalysisCacheConfig {
+    pub enabled: bool,
+    pub dir: PathBuf,
+    pub max_bytes: u64,
+    pub max_age: Duration,
+}
+
+impl AnalysisCacheConfig {
+    pub fn from_env() -> Self {
+        let enabled = match std::env::var("GO_ANALYZER_CACHE") {
+            Ok(v) => !matches!(v.as_str(), "0" | "false" | "FALSE" | "no" | "NO"),
+            Err(_) => true,
+        };
+        let dir = std::env::var("GO_ANALYZER_CACHE_DIR")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| std::env::temp_dir().join("go-analyzer-cache"));
+        let max_mb = std::env::var("GO_ANALYZER_CACHE_MAX_MB")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(256);
+        let max_age_days = std::env::var("GO_ANALYZER_CACHE_MAX_AGE_DAYS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(30);
+        Self {
+            enabled,
+            dir,
+            max_bytes: max_mb * 1024 * 1024,
+            max_age: Duration::from_secs(max_age_days * 24 * 60 * 60),
+        }
+    }
+}
+
+/// What the workspace summary needs from one file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FileArtifacts {
+    /// File the artifacts were computed for; a key collision with another file is a miss.
+    pub path: PathBuf,
+    pub counts: EntityCount,
+    pub synchronized_goroutines: usize,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl FileArtifacts {
+    pub fn from_report(report: &FileReport) -> Self {
+        Self {
+            path: report.path.clone(),
+            counts: report.counts.clone(),
+            synchronized_goroutines: report.synchronized_goroutines,
+            diagnostics: report.diagnostics.clone(),
+        }
+    }
+
+    pub fn into_report(self) -> FileReport {
+        FileReport {
+            path: self.path,
+            counts: self.counts,
+            synchronized_goroutines: self.synchronized_goroutines,
+            diagnostics: self.diagnostics,
+        }
+    }
+}
+
+/// On-disk store of [`FileArtifacts`], one JSON file per [`cache_key`]. Read and write errors
+/// only cost a cache miss.
+pub struct AnalysisCache {
+    config: AnalysisCacheConfig,
+    lookups: HitCounter,
+    stores: AtomicUsize,
+}
+
+impl AnalysisCache {
+    pub fn new(config: AnalysisCacheConfig) -> Self {
+        Self {
+            config,
+            lookups: HitCounter::default(),
+            stores: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn load(&self, key: &str, path: &Path) -> Option<FileArtifacts> {
+        if !self.config.enabled {
+            return None;
+        }
+        let artifacts = fs::read_to_string(self.entry_path(key))
+            .ok()
+            .and_then(|raw| serde_json::from_str::<FileArtifacts>(&raw).ok())
+            .filter(|artifacts| artifacts.path == path);
+        self.lookups.record(artifacts)
+    }
+
+    /// Writes to a temporary file first, so a concurrent reader never sees half an entry.
+    pub fn store(&self, key: &str, artifacts: &FileArtifacts) {
+        if !self.config.enabled || fs::create_dir_all(&self.config.dir).is_err() {
+            return;
+        }
+        let Ok(raw) = serde_json::to_string(artifacts) else {
+            return;
+        };
+        let entry = self.entry_path(key);
+        let partial = entry.with_extension(format!("{}.tmp", std::process::id()));
+        if fs::write(&partial, raw).is_ok() && fs::rename(&partial, &entry).is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+        if self
+            .stores
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(PRUNE_INTERVAL)
+        {
+            self.prune();
+        }
+    }
+
+    /// Removes entries older than `max_age`, then the least recently written ones until the rest
+    /// fit in `max_bytes`.
+    pub fn prune(&self) {
+        let now = SystemTime::now();
+        let mut entries: Vec<(PathBuf, u64, SystemTime)> = Vec::new();
+        for entry in fs::read_dir(&self.config.dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+        {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let modified = metadata.modified().unwrap_or(now);
+            let age = now.duration_since(modified).unwrap_or_default();
+            if age > self.config.max_age {
+                let _ = fs::remove_file(&path);
+            } else {
+                entries.push((path, metadata.len(), modified));
+            }
+        }
+        entries.sort_by_key(|(_, _, modified)| std::cmp::Reverse(*modified));
+        let mut kept = 0;
+        for (path, len, _) in entries {
+            kept += len;
+            if kept > self.config.max_bytes {
+                let _ = fs::remove_file(&path);
+            }
+        }
+    }
+
+    /// Entries on disk, their total size and ages, and how often [`AnalysisCache::load`] hit.
+    pub fn stats(&self) -> CacheStats {
+        let entries: Vec<fs::Metadata> = fs::read_dir(&self.config.dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|entry| entry.metadata().ok())
+            .collect();
+        let bytes = entries.iter().map(|entry| entry.len() as usize).sum();
+        CacheStats::new("analysis_cache", entries.len())
+            .with_ages(entries.iter().filter_map(|entry| entry.modified().ok()))
+            .with_bytes(bytes)
+            .with_lookups(&self.lookups)
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.config.dir.join(format!("{}.json", key))
+    }
+}
+
+/// Key of a file's artifacts: its path and content plus `fingerprint`, everything else the
+/// results depend on (analyzer version, settings, the module's Go version).
+/// The hash is stable across builds, so entries written by an earlier run are found again.
+pub fn cache_key(path: &Path, code: &str, fingerprint: &str) -> String {
+    let path = path.to_string_lossy();
+    let hash = stable_hash([
+        &FORMAT_VERSION.to_le_bytes()[..],
+        env!("CARGO_PKG_VERSION").as_bytes(),
+        fingerprint.as_bytes(),
+        path.as_bytes(),
+        code.as_bytes(),
+    ]);
+    format!("{:016x}-{}", hash, code.len())
+}
@@ -0,0 +1,221 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::bootstrap::{is_executable, is_path, locate_helper, resolve_executable, BuildConsent};
+use crate::semantic::SemanticConfig;
+
+/// Ordered from best to worst.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    /// Works, but some analysis is degraded or off.
+    Warning,
+    Error,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct DoctorCheck {
+    /// Stable identifier: `go_toolchain`, `semantic_helper`, `go_mod`, `grammar` or `cache_dir`.
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// What to change to fix a failed check.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+/// Result of `goanalyzer/doctor`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct DoctorReport {
+    /// Worst status of all checks.
+    pub status: CheckStatus,
+    pub checks: Vec<DoctorCheck>,
+}
+
+impl DoctorCheck {
+    fn new(name: &str, status: CheckStatus, detail: String, hint: Option<&str>) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            detail,
+            hint: hint.map(str::to_string),
+        }
+    }
+}
+
+pub fn run_doctor(semantic: &SemanticConfig, roots: &[PathBuf], cache_dir: &Path) -> DoctorReport {
+    let checks = vec![
+        check_go_toolchain(),
+        check_semantic_helper(semantic),
+        check_go_mod(roots),
+        check_grammar(),
+        check_cache_dir(cache_dir),
+    ];
+    let status = checks
+        .iter()
+        .map(|check| check.status)
+        .max()
+        .unwrap_or(CheckStatus::Ok);
+    DoctorReport { status, checks }
+}
+
+pub fn check_go_toolchain() -> DoctorCheck {
+    match Command::new("go").arg("version").output() {
+        Ok(output) if output.status.success() => DoctorCheck::new(
+            "go_toolchain",
+            CheckStatus::Ok,
+            String::from_utf8_lossy(&output.stdout).trim().to_string(),
+            None,
+        ),
+        Ok(output) => DoctorCheck::new(
+            "go_toolchain",
+            CheckStatus::Warning,
+            format!(
+                "`go version` failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Some("Check the Go installation (GOROOT, GOTOOLCHAIN) by running `go version` in a terminal."),
+        ),
+        Err(e) => DoctorCheck::new(
+            "go_toolchain",
+            CheckStatus::Warning,
+            format!("`go` not found: {}", e),
+            Some("Install Go from https://go.dev/dl/ and make sure `go` is on the PATH of the editor process."),
+        ),
+    }
+}
+
+pub fn check_semantic_helper(config: &SemanticConfig) -> DoctorCheck {
+    if !config.enabled {
+        return DoctorCheck::new(
+            "semantic_helper",
+            CheckStatus::Warning,
+            "Semantic analysis is disabled; type-aware resolution of uses is off".to_string(),
+            Some("Enable goAnalyzer.semanticEnable (GO_ANALYZER_SEMANTIC=1)."),
+        );
+    }
+    match locate_helper(&config.helper_path, &config.build_dir, &[]) {
+        Some(path) if is_executable(&path) => DoctorCheck::new(
+            "semantic_helper",
+            CheckStatus::Ok,
+            format!("Using {}", path.display()),
+            None,
+        ),
+        Some(path) => DoctorCheck::new(
+            "semantic_helper",
+            CheckStatus::Error,
+            format!("{} is not executable", path.display()),
+            Some("Make the helper executable (chmod +x) or point goAnalyzer.semanticHelperPath at a working build."),
+        ),
+        None if !is_path(&config.helper_path)
+            && config.build != BuildConsent::Never
+            && resolve_executable("go").is_some() =>
+        {
+            DoctorCheck::new(
+                "semantic_helper",
+                CheckStatus::Warning,
+                format!(
+                    "Helper `{}` not found; it is built from the bundled sources on first use",
+                    config.helper_path
+                ),
+                Some("Accept the prompt to build it, or set goAnalyzer.semanticBuild to `always`."),
+            )
+        }
+        None => DoctorCheck::new(
+            "semantic_helper",
+            CheckStatus::Error,
+            format!("Helper `{}` not found", config.helper_path),
+            Some("Set goAnalyzer.semanticHelperPath (GO_ANALYZER_SEMANTIC_PATH) to the goanalyzer-semantic binary, put it on PATH, or install Go so the server can build it."),
+        ),
+    }
+}
+
+/// Every workspace root must be inside a module: `go.mod` in the root or one of its ancestors.
+pub fn check_go_mod(roots: &[PathBuf]) -> DoctorCheck {
+    if roots.is_empty() {
+        return DoctorCheck::new(
+            "go_mod",
+            CheckStatus::Warning,
+            "No workspace folder is open".to_string(),
+            Some("Open the folder containing go.mod instead of single files."),
+        );
+    }
+    let missing: Vec<String> = roots
+        .iter()
+        .filter(|root| !root.ancestors().any(|dir| dir.join("go.mod").is_file()))
+        .map(|root| root.display().to_string())
+        .collect();
+    if missing.is_empty() {
+        DoctorCheck::new(
+            "go_mod",
+            CheckStatus::Ok,
+            format!("{} workspace folder(s) inside a module", roots.len()),
+            None,
+        )
+    } else {
+        DoctorCheck::new(
+            "go_mod",
+            CheckStatus::Warning,
+            format!("No go.mod for {}", missing.join(", ")),
+            Some("Run `go mod init <module>` in the workspace root; the semantic helper needs a module to type-check."),
+        )
+    }
+}
+
+pub fn check_grammar() -> DoctorCheck {
+    let version = tree_sitter_go::language().version();
+    if (tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION..=tree_sitter::LANGUAGE_VERSION)
+        .contains(&version)
+    {
+        DoctorCheck::new(
+            "grammar",
+            CheckStatus::Ok,
+            format!(
+                "tree-sitter-go ABI {} (runtime supports {}..={})",
+                version,
+                tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION,
+                tree_sitter::LANGUAGE_VERSION
+            ),
+            None,
+        )
+    } else {
+        DoctorCheck::new(
+            "grammar",
+            CheckStatus::Error,
+            format!(
+                "tree-sitter-go ABI {} is outside the supported {}..={}",
+                version,
+                tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION,
+                tree_sitter::LANGUAGE_VERSION
+            ),
+            Some("Rebuild the server with matching tree-sitter and tree-sitter-go versions."),
+        )
+    }
+}
+
+/// The session-recording directory must be creatable and writable.
+pub fn check_cache_dir(dir: &Path) -> DoctorCheck {
+    let probe = dir.join(".doctor-probe");
+    let written = std::fs::create_dir_all(dir).and_then(|_| std::fs::write(&probe, b"ok"));
+    let _ = std::fs::remove_file(&probe);
+    match written {
+        Ok(()) => DoctorCheck::new(
+            "cache_dir",
+            CheckStatus::Ok,
+            format!("{} is writable", dir.display()),
+            None,
+        ),
+        Err(e) => DoctorCheck::new(
+            "cache_dir",
+            CheckStatus::Warning,
+            format!("{} is not writable: {}", dir.display(), e),
+            Some("Set GO_ANALYZER_RECORD_DIR to a writable directory."),
+        ),
+    }
+}
@@ -0,0 +1,426 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, TryLockError};
+use std::time::{Duration, SystemTime};
+
+use ropey::Rope;
+use tower_lsp::lsp_types::{TextDocumentContentChangeEvent, Url};
+use tree_sitter::{Parser, Tree};
+use tree_sitter_go::language;
+
+use crate::anchor::{apply_content_change, edit_tree, EditDelta};
+use crate::stats::{CacheStats, HitCounter, ESTIMATED_BYTES_PER_NODE};
+use crate::util::for_each_node;
+use crate::workers::WorkerPool;
+use crate::workspace::SourceFile;
+
+pub const DEFAULT_MAX_CACHED_TREES: usize = 20;
+pub const DEFAULT_MAX_CACHED_DOCUMENTS: usize = 50;
+pub const DEFAULT_CACHE_TTL_SECONDS: u64 = 300;
+
+/// How much [`DocumentStore`] keeps. Documents open in the editor are never evicted; closed
+/// ones expire `ttl` after their last use and go least recently used first once there are more
+/// than `max_documents`. At most `max_trees` parsed trees are kept, dropping the least recently
+/// used (they are re-parsed on demand). Read from `GO_ANALYZER_MAX_CACHED_TREES`,
+/// `GO_ANALYZER_MAX_CACHED_DOCUMENTS` and `GO_ANALYZER_CACHE_TTL_SECONDS`.
+#[derive(Clone, Debug)]
+pub struct CacheLimits {
+    pub max_trees: usize,
+    pub max_documents: usize,
+    pub ttl: Duration,
+}
+
+impl Default for CacheLimits {
+    fn default() -> Self {
+        Self {
+            max_trees: DEFAULT_MAX_CACHED_TREES,
+            max_documents: DEFAULT_MAX_CACHED_DOCUMENTS,
+            ttl: Duration::from_secs(DEFAULT_CACHE_TTL_SECONDS),
+        }
+    }
+}
+
+impl CacheLimits {
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok()?.trim().parse::<u64>().ok();
+        let defaults = Self::default();
+        Self {
+            max_trees: var("GO_ANALYZER_MAX_CACHED_TREES")
+                .map_or(defaults.max_trees, |v| v as usize),
+            max_documents: var("GO_ANALYZER_MAX_CACHED_DOCUMENTS")
+                .map_or(defaults.max_documents, |v| v as usize),
+            ttl: var("GO_ANALYZER_CACHE_TTL_SECONDS").map_or(defaults.ttl, Duration::from_secs),
+        }
+    }
+}
+
+thread_local! {
+    /// Parsers are not `Sync`; one per thread lets documents parse in parallel.
+    static PARSER: RefCell<Option<Parser>> = RefCell::new(go_parser());
+}
+
+fn go_parser() -> Option<Parser> {
+    let mut parser = Parser::new();
+    parser.set_language(language()).ok()?;
+    Some(parser)
+}
+
+/// Parses `code` with this thread's Go parser, reusing `old` (already edited to match `code`).
+pub fn parse_go(code: &str, old: Option<&Tree>) -> Option<Tree> {
+    PARSER.with(|parser| parser.borrow_mut().as_mut()?.parse(code, old))
+}
+
+/// Parses `files` on the worker pool, keeping their order and skipping files that fail to parse.
+pub async fn parse_sources(files: Vec<(PathBuf, String)>, workers: &WorkerPool) -> Vec<SourceFile> {
+    workers
+        .map(files, |(path, code)| {
+            let tree = parse_go(&code, None)?;
+            Some(SourceFile { path, code, tree })
+        })
+        .await
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+#[derive(Clone)]
+pub struct CacheEntry<T> {
+    pub data: T,
+    timestamp: SystemTime,
+    /// `TextDocumentItem.version` the data was produced from (0 when unversioned).
+    pub version: i32,
+}
+
+impl<T> CacheEntry<T> {
+    fn new(data: T) -> Self {
+        Self::versioned(data, 0)
+    }
+
+    fn versioned(data: T, version: i32) -> Self {
+        Self {
+            data,
+            timestamp: SystemTime::now(),
+            version,
+        }
+    }
+
+    fn touch(&mut self) {
+        self.timestamp = SystemTime::now();
+    }
+
+    fn is_expired(&self, ttl: Duration) -> bool {
+        self.timestamp.elapsed().unwrap_or(Duration::from_secs(0)) > ttl
+    }
+}
+
+/// Everything held for one open document. Its text and tree change together under the
+/// document's own lock.
+pub struct Document {
+    /// A rope, so an edit costs the size of the edit rather than of the file.
+    pub text: CacheEntry<Rope>,
+    /// `text` as one string for analysis, built on the first read after a change and shared
+    /// by every reader until the next one.
+    snapshot: Option<Arc<str>>,
+    pub tree: Option<CacheEntry<Tree>>,
+    /// The tree was edited to match `text` but not re-parsed yet; the next parse reuses it.
+    pub edited: bool,
+    /// Open in the editor, so never expired or evicted.
+    pub open: bool,
+}
+
+impl Document {
+    fn is_expired(&self, ttl: Duration) -> bool {
+        !self.open && self.text.is_expired(ttl)
+    }
+
+    fn snapshot(&mut self) -> Arc<str> {
+        let text = &self.text.data;
+        self.snapshot
+            .get_or_insert_with(|| Arc::from(text.to_string()))
+            .clone()
+    }
+}
+
+/// Open documents by URI. The map lock is only held to find or insert an entry; reads and
+/// edits then lock just that document, so work on one file never waits for another.
+#[derive(Default)]
+pub struct DocumentStore {
+    documents: RwLock<HashMap<Url, Arc<Mutex<Document>>>>,
+    limits: CacheLimits,
+    tree_lookups: HitCounter,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl DocumentStore {
+    pub fn new(limits: CacheLimits) -> Self {
+        Self {
+            limits,
+            ..Default::default()
+        }
+    }
+
+    fn entry(&self, uri: &Url) -> Option<Arc<Mutex<Document>>> {
+        self.documents
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(uri)
+            .cloned()
+    }
+
+    /// Stores `text` as `uri` at `version`, parsed from scratch.
+    pub fn open(&self, uri: &Url, text: String, version: i32) -> Option<Tree> {
+        let tree = parse_go(&text, None);
+        let document = Document {
+            text: CacheEntry::versioned(Rope::from_str(&text), version),
+            snapshot: Some(Arc::from(text)),
+            tree: tree.clone().map(CacheEntry::new),
+            edited: false,
+            open: true,
+        };
+        self.documents
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(uri.clone(), Arc::new(Mutex::new(document)));
+        if tree.is_none() {
+            tracing::warn!("Failed to parse document: {}", uri);
+        }
+        tree
+    }
+
+    /// Applies `changes` to `uri` and edits its tree to match, leaving the re-parse to the next
+    /// [`DocumentStore::parse`]. Returns the new text, which shares its storage with the
+    /// document's, and the edit deltas (`None` after a full-text change), or `None` when the
+    /// document is unknown or the change is for an older version or empty.
+    pub fn change(
+        &self,
+        uri: &Url,
+        version: i32,
+        changes: &[TextDocumentContentChangeEvent],
+    ) -> Option<(Rope, Option<Vec<EditDelta>>)> {
+        let entry = self.entry(uri)?;
+        let mut document = lock(&entry);
+        if version < document.text.version || changes.is_empty() {
+            return None;
+        }
+        let mut text = document.text.data.clone();
+        // `None` once a full-text change makes earlier results unanchorable.
+        let mut deltas = Some(Vec::new());
+        for change in changes {
+            match apply_content_change(&mut text, change) {
+                Some(delta) => deltas.iter_mut().for_each(|d| d.push(delta)),
+                None => deltas = None,
+            }
+        }
+        document.text = CacheEntry::versioned(text.clone(), version);
+        document.snapshot = None;
+        match (&deltas, document.tree.as_mut()) {
+            (Some(deltas), Some(tree)) => {
+                edit_tree(&mut tree.data, deltas);
+                document.edited = true;
+            }
+            _ => document.tree = None,
+        }
+        Some((text, deltas))
+    }
+
+    /// Marks `uri` closed in the editor; it stays cached until it expires or is evicted.
+    pub fn close(&self, uri: &Url) {
+        if let Some(entry) = self.entry(uri) {
+            let mut document = lock(&entry);
+            document.open = false;
+            document.text.touch();
+        }
+    }
+
+    /// Text of `uri`, unless it was closed and expired. Readers of the same version share one
+    /// copy.
+    pub fn text(&self, uri: &Url) -> Option<Arc<str>> {
+        let entry = self.entry(uri)?;
+        let mut document = lock(&entry);
+        if document.is_expired(self.limits.ttl) {
+            return None;
+        }
+        document.text.touch();
+        Some(document.snapshot())
+    }
+
+    pub fn version(&self, uri: &Url) -> Option<i32> {
+        let entry = self.entry(uri)?;
+        let version = lock(&entry).text.version;
+        Some(version)
+    }
+
+    /// Cached tree of `uri`, unless it was evicted or expired with its closed document, or
+    /// awaits a re-parse after an edit.
+    pub fn tree(&self, uri: &Url) -> Option<Tree> {
+        let entry = self.entry(uri);
+        let document = entry.as_deref().map(lock);
+        let tree = document.as_ref().and_then(|document| {
+            document
+                .tree
+                .as_ref()
+                .filter(|tree| {
+                    !document.edited && (document.open || !tree.is_expired(self.limits.ttl))
+                })
+                .map(|tree| tree.data.clone())
+        });
+        self.tree_lookups.record(tree)
+    }
+
+    /// Parses `code`, the text of `uri` after `deltas`, and caches the tree when `uri` is open.
+    pub fn parse(&self, uri: &Url, code: &str, deltas: Option<&[EditDelta]>) -> Option<Tree> {
+        match self.entry(uri) {
+            Some(entry) => reparse(uri, &mut lock(&entry), code, deltas),
+            None => parse_go(code, None),
+        }
+    }
+
+    /// Drops expired closed documents and their trees, then the least recently used closed
+    /// documents and trees over the limits. Open documents, and documents locked by other work,
+    /// are kept as they are; their trees still count toward the tree limit.
+    pub fn evict(&self) {
+        let entries: Vec<(Url, Arc<Mutex<Document>>)> = self
+            .documents
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(uri, entry)| (uri.clone(), entry.clone()))
+            .collect();
+        let ttl = self.limits.ttl;
+        let mut expired = Vec::new();
+        let mut kept = 0;
+        let mut closed = Vec::new();
+        let mut trees = Vec::new();
+        for (uri, entry) in entries {
+            let mut document = match entry.try_lock() {
+                Ok(document) => document,
+                Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+                Err(TryLockError::WouldBlock) => {
+                    kept += 1;
+                    continue;
+                }
+            };
+            if document.is_expired(ttl) {
+                expired.push(uri);
+                continue;
+            }
+            if !document.open && document.tree.as_ref().is_some_and(|t| t.is_expired(ttl)) {
+                document.tree = None;
+                document.edited = false;
+            }
+            if let Some(tree) = &document.tree {
+                trees.push((uri.clone(), entry.clone(), tree.timestamp));
+            }
+            if document.open {
+                kept += 1;
+            } else {
+                closed.push((uri, document.text.timestamp));
+            }
+        }
+        closed.sort_by_key(|(_, timestamp)| *timestamp);
+        let excess = (kept + closed.len())
+            .saturating_sub(self.limits.max_documents)
+            .min(closed.len());
+        expired.extend(closed.drain(..excess).map(|(uri, _)| uri));
+        trees.retain(|(uri, _, _)| !expired.contains(uri));
+        trees.sort_by_key(|(_, _, timestamp)| *timestamp);
+        let excess = trees.len().saturating_sub(self.limits.max_trees);
+        for (_, entry, _) in trees.drain(..excess) {
+            let mut document = lock(&entry);
+            document.tree = None;
+            document.edited = false;
+        }
+        if !expired.is_empty() {
+            let mut documents = self
+                .documents
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
+            for uri in expired {
+                documents.remove(&uri);
+            }
+        }
+    }
+
+    /// Sizes and ages of the cached documents and trees, and how often [`DocumentStore::tree`]
+    /// found a tree.
+    pub fn stats(&self) -> [CacheStats; 2] {
+        let entries: Vec<Arc<Mutex<Document>>> = self
+            .documents
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values()
+            .cloned()
+            .collect();
+        let (mut texts, mut text_bytes) = (Vec::new(), 0);
+        let (mut trees, mut tree_nodes) = (Vec::new(), 0);
+        for entry in entries {
+            let document = lock(&entry);
+            texts.push(document.text.timestamp);
+            text_bytes += document.text.data.len_bytes()
+                + document
+                    .snapshot
+                    .as_ref()
+                    .map_or(0, |snapshot| snapshot.len());
+            if let Some(tree) = &document.tree {
+                trees.push(tree.timestamp);
+                for_each_node(tree.data.root_node(), |_| tree_nodes += 1);
+            }
+        }
+        [
+            CacheStats::new("documents", texts.len())
+                .with_ages(texts)
+                .with_bytes(text_bytes),
+            CacheStats::new("trees", trees.len())
+                .with_ages(trees)
+                .with_bytes(tree_nodes * ESTIMATED_BYTES_PER_NODE)
+                .with_lookups(&self.tree_lookups),
+        ]
+    }
+
+    /// Drops every document, returning how many there were and how many had a tree.
+    pub fn clear(&self) -> (usize, usize) {
+        let mut documents = self
+            .documents
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let trees = documents
+            .values()
+            .filter(|entry| lock(entry).tree.is_some())
+            .count();
+        let count = documents.len();
+        documents.clear();
+        (count, trees)
+    }
+}
+
+/// Edits the cached tree with `deltas` so tree-sitter re-parses only around them; `None` (a
+/// full-text replacement) parses from scratch.
+fn reparse(
+    uri: &Url,
+    document: &mut Document,
+    code: &str,
+    deltas: Option<&[EditDelta]>,
+) -> Option<Tree> {
+    let previous = match (deltas, document.tree.as_mut()) {
+        (Some(deltas), Some(tree)) => {
+            edit_tree(&mut tree.data, deltas);
+            Some(&tree.data)
+        }
+        _ => None,
+    };
+    match parse_go(code, previous) {
+        Some(tree) => {
+            document.tree = Some(CacheEntry::new(tree.clone()));
+            document.edited = false;
+            Some(tree)
+        }
+        None => {
+            tracing::warn!("Failed to parse document: {}", uri);
+            None
+        }
+    }
+}
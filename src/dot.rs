@@ -0,0 +1,115 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use crate::analysis::build_graph_data;
+use crate::documents::parse_go;
+use crate::types::{GraphData, GraphEdgeType, GraphEntityType};
+
+/// Renders the entity graph as a Graphviz digraph, for `dot -Tsvg` and other tools outside the
+/// editor. Nodes are shaped and colored by entity type and edges styled by edge type; endpoints
+/// that are not entities themselves (call sites, sync points) are drawn as small points. Racy
+/// variables get a thick red outline.
+pub fn graph_to_dot(graph: &GraphData) -> String {
+    let mut dot = String::from("digraph go_analyzer {\n");
+    dot.push_str("    rankdir=LR;\n");
+    dot.push_str(
+        "    node [fontname=\"Helvetica\", fontsize=10, style=filled, fillcolor=white];\n",
+    );
+    dot.push_str("    edge [fontname=\"Helvetica\", fontsize=9];\n");
+    let mut known = HashSet::new();
+    for node in &graph.nodes {
+        if !known.insert(node.id.as_str()) {
+            continue;
+        }
+        let (shape, color) = node_style(&node.entity_type);
+        let racy = node.is_racy();
+        let color = if racy { RACE_COLOR } else { color };
+        let _ = writeln!(
+            dot,
+            "    \"{}\" [label=\"{}\\nline {}\", shape={}, color=\"{}\", fillcolor=\"{}22\"{}];",
+            escape(&node.id),
+            escape(&node.label),
+            node.range.start.line + 1,
+            shape,
+            color,
+            color,
+            if racy { ", penwidth=2" } else { "" }
+        );
+    }
+    for edge in &graph.edges {
+        for endpoint in [&edge.from, &edge.to] {
+            if known.insert(endpoint.as_str()) {
+                let _ = writeln!(
+                    dot,
+                    "    \"{}\" [label=\"\", shape=point, width=0.08];",
+                    escape(endpoint)
+                );
+            }
+        }
+    }
+    for edge in &graph.edges {
+        let (style, color, label) = edge_style(&edge.edge_type);
+        let _ = writeln!(
+            dot,
+            "    \"{}\" -> \"{}\" [label=\"{}\", style={}, color=\"{}\"];",
+            escape(&edge.from),
+            escape(&edge.to),
+            label,
+            style,
+            color
+        );
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// Parses `code` and renders its entity graph; `None` when the Go grammar cannot be loaded or
+/// the source does not parse.
+pub fn source_to_dot(code: &str) -> Option<String> {
+    let tree = parse_go(code, None)?;
+    Some(graph_to_dot(&build_graph_data(&tree, code)))
+}
+
+/// Outline of variables the race analysis flagged.
+const RACE_COLOR: &str = "#d62728";
+
+/// (shape, color) for an entity type.
+fn node_style(entity_type: &GraphEntityType) -> (&'static str, &'static str) {
+    match entity_type {
+        GraphEntityType::Variable => ("ellipse", "#4e79a7"),
+        GraphEntityType::Function => ("box", "#59a14f"),
+        GraphEntityType::Channel => ("cds", "#f28e2b"),
+        GraphEntityType::Goroutine => ("hexagon", "#e15759"),
+        GraphEntityType::SyncBlock => ("octagon", "#b07aa1"),
+        GraphEntityType::SyncMap => ("folder", "#76b7b2"),
+        GraphEntityType::Struct => ("record", "#9c755f"),
+        GraphEntityType::Interface => ("component", "#9c755f"),
+        GraphEntityType::Method => ("box", "#8cd17d"),
+        GraphEntityType::Package => ("tab", "#79706e"),
+        GraphEntityType::Select => ("diamond", "#f28e2b"),
+    }
+}
+
+/// (style, color, label) for an edge type.
+fn edge_style(edge_type: &GraphEdgeType) -> (&'static str, &'static str, &'static str) {
+    match edge_type {
+        GraphEdgeType::Use => ("solid", "#9c9c9c", "use"),
+        GraphEdgeType::Call => ("solid", "#333333", "call"),
+        GraphEdgeType::Send => ("bold", "#f28e2b", "send"),
+        GraphEdgeType::Receive => ("dashed", "#f28e2b", "receive"),
+        GraphEdgeType::Spawn => ("bold", "#e15759", "go"),
+        GraphEdgeType::Sync => ("dotted", "#b07aa1", "sync"),
+        GraphEdgeType::Close => ("bold", "#59a14f", "close"),
+        GraphEdgeType::Guards => ("dashed", "#b07aa1", "guards"),
+        GraphEdgeType::Acquires => ("solid", "#b07aa1", "lock"),
+        GraphEdgeType::Implements => ("dashed", "#9c755f", "implements"),
+        GraphEdgeType::HasMethod => ("solid", "#9c755f", "method"),
+        GraphEdgeType::Imports => ("solid", "#79706e", "imports"),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
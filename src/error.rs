@@ -0,0 +1,74 @@
+use std::fmt;
+
+use serde_json::json;
+use tower_lsp::jsonrpc;
+use tower_lsp::lsp_types::Position;
+
+/// Why an analysis could not answer, as opposed to answering "no". Analyses return these for
+/// inputs they cannot make sense of, such as a stale position or a tree that no longer matches
+/// the text, and leave panics to broken invariants.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AnalysisError {
+    /// The position lies outside the document.
+    InvalidPosition(Position),
+    /// No syntax node covers the position.
+    NoNodeAt(Position),
+    /// A node's byte range reaches past the text it was parsed from.
+    ByteRangeOutOfBounds {
+        start: usize,
+        end: usize,
+        len: usize,
+    },
+}
+
+pub type AnalysisResult<T> = Result<T, AnalysisError>;
+
+impl AnalysisError {
+    /// Stable identifier, sent as `data.kind` of error responses.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::InvalidPosition(_) => "invalid_position",
+            Self::NoNodeAt(_) => "no_node_at",
+            Self::ByteRangeOutOfBounds { .. } => "byte_range_out_of_bounds",
+        }
+    }
+
+    /// The LSP error response: invalid params for a position the client got wrong, an internal
+    /// error otherwise.
+    pub fn to_rpc_error(&self) -> jsonrpc::Error {
+        let mut error = match self {
+            Self::InvalidPosition(_) => jsonrpc::Error::invalid_params(self.to_string()),
+            _ => {
+                let mut error = jsonrpc::Error::internal_error();
+                error.message = self.to_string().into();
+                error
+            }
+        };
+        error.data = Some(json!({"kind": self.kind()}));
+        error
+    }
+}
+
+impl fmt::Display for AnalysisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidPosition(position) => write!(
+                f,
+                "position {}:{} is outside the document",
+                position.line, position.character
+            ),
+            Self::NoNodeAt(position) => write!(
+                f,
+                "no syntax node at {}:{}",
+                position.line, position.character
+            ),
+            Self::ByteRangeOutOfBounds { start, end, len } => write!(
+                f,
+                "byte range {}..{} is outside the {}-byte document",
+                start, end, len
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AnalysisError {}
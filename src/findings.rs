@@ -0,0 +1,189 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::Url;
+
+use crate::anchor::{reanchor_decorations, EditDelta};
+use crate::types::Decoration;
+
+pub struct DecorationsDeltaNotification;
+impl tower_lsp::lsp_types::notification::Notification for DecorationsDeltaNotification {
+    const METHOD: &'static str = "goanalyzer/decorationsDelta";
+    type Params = DecorationsDelta;
+}
+
+pub struct DecorationsNotification;
+impl tower_lsp::lsp_types::notification::Notification for DecorationsNotification {
+    const METHOD: &'static str = "goanalyzer/decorations";
+    type Params = DecorationsParams;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct DecorationsParams {
+    pub uri: String,
+    /// Document version the decorations were computed for.
+    pub version: Option<i32>,
+    pub decorations: Vec<Decoration>,
+    /// The analysis ran out of its time budget; the decorations cover the uses found by then.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct DecorationsDelta {
+    pub uri: String,
+    pub version: Option<i32>,
+    /// When set, the client drops its current set before applying `added`.
+    pub full: bool,
+    pub added: Vec<Decoration>,
+    pub removed: Vec<String>,
+    /// See [`DecorationsParams::truncated`].
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+/// Server-driven decoration updates: once a burst of edits settles (see
+/// [`crate::queue::AnalysisQueue`]), decorations for the last cursor position are recomputed and
+/// pushed without a `goanalyzer/cursor` request. File diagnostics are recomputed then whether or
+/// not pushing is enabled.
+#[derive(Clone, Debug)]
+pub struct DecorationPushConfig {
+    pub enabled: bool,
+}
+
+impl DecorationPushConfig {
+    pub fn from_env() -> Self {
+        let enabled = match std::env::var("GO_ANALYZER_PUSH_DECORATIONS") {
+            Ok(v) => matches!(v.as_str(), "1" | "true" | "TRUE" | "yes" | "YES"),
+            Err(_) => false,
+        };
+        Self { enabled }
+    }
+}
+
+/// Stable id of a decoration: identical kind, range and content always map to the same id,
+/// so an unchanged decoration is never re-sent.
+pub fn decoration_id(decoration: &Decoration) -> String {
+    let mut hasher = DefaultHasher::new();
+    decoration.hover_text.hash(&mut hasher);
+    if let Some(diagnostic) = &decoration.diagnostic {
+        diagnostic.code.hash(&mut hasher);
+        diagnostic.message.hash(&mut hasher);
+        format!("{:?}", diagnostic.severity).hash(&mut hasher);
+        diagnostic.href.hash(&mut hasher);
+    }
+    // Text inserted earlier on a line shifts offsets without moving the range.
+    decoration.bytes.hash(&mut hasher);
+    decoration.stale.hash(&mut hasher);
+    let range = decoration.range;
+    format!(
+        "{:?}@{}:{}-{}:{}#{:016x}",
+        decoration.kind,
+        range.start.line,
+        range.start.character,
+        range.end.line,
+        range.end.character,
+        hasher.finish()
+    )
+}
+
+/// Last decoration set sent to the client, per document.
+#[derive(Default)]
+pub struct DecorationStore {
+    sent: HashMap<Url, SentDecorations>,
+}
+
+#[derive(Default)]
+struct SentDecorations {
+    version: Option<i32>,
+    decorations: Vec<Decoration>,
+}
+
+impl DecorationStore {
+    /// Records `decorations` as the current set for `uri` and returns what changed since the
+    /// previous set. Ids are assigned here; duplicate ids keep the first occurrence.
+    pub fn update(
+        &mut self,
+        uri: &Url,
+        version: Option<i32>,
+        decorations: &mut Vec<Decoration>,
+    ) -> DecorationsDelta {
+        for decoration in decorations.iter_mut() {
+            decoration.id = decoration_id(decoration);
+        }
+        let mut seen = HashSet::new();
+        decorations.retain(|decoration| seen.insert(decoration.id.clone()));
+
+        let previous = self.sent.remove(uri).unwrap_or_default().decorations;
+        let previous_ids: HashSet<&str> = previous.iter().map(|old| old.id.as_str()).collect();
+        let removed = previous
+            .iter()
+            .filter(|old| !seen.contains(&old.id))
+            .map(|old| old.id.clone())
+            .collect();
+        let added = decorations
+            .iter()
+            .filter(|new| !previous_ids.contains(new.id.as_str()))
+            .cloned()
+            .collect();
+        self.sent.insert(
+            uri.clone(),
+            SentDecorations {
+                version,
+                decorations: decorations.clone(),
+            },
+        );
+        DecorationsDelta {
+            uri: uri.to_string(),
+            version,
+            full: false,
+            added,
+            removed,
+            truncated: false,
+        }
+    }
+
+    /// Full resync: the whole current set for `uri`, to replace whatever the client holds.
+    pub fn snapshot(&self, uri: &Url) -> DecorationsDelta {
+        let sent = self.sent.get(uri);
+        DecorationsDelta {
+            uri: uri.to_string(),
+            version: sent.and_then(|sent| sent.version),
+            full: true,
+            added: sent
+                .map(|sent| sent.decorations.clone())
+                .unwrap_or_default(),
+            removed: Vec::new(),
+            truncated: false,
+        }
+    }
+
+    /// Shifts the set last sent for `uri` past `deltas` (clearing it for a full-text change),
+    /// marks it stale and records it as the current set. `None` when nothing was sent.
+    pub fn reanchor(
+        &mut self,
+        uri: &Url,
+        version: Option<i32>,
+        deltas: Option<&[EditDelta]>,
+    ) -> Option<Vec<Decoration>> {
+        let mut decorations = self.sent.get(uri)?.decorations.clone();
+        match deltas {
+            Some(deltas) => reanchor_decorations(&mut decorations, deltas),
+            None => decorations.clear(),
+        }
+        self.update(uri, version, &mut decorations);
+        Some(decorations)
+    }
+
+    /// Number of documents whose decorations the client holds.
+    pub fn tracked_documents(&self) -> usize {
+        self.sent.len()
+    }
+
+    pub fn remove(&mut self, uri: &Url) {
+        self.sent.remove(uri);
+    }
+}
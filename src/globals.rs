@@ -0,0 +1,371 @@
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types::Range;
+use tree_sitter::{Node, Tree};
+
+use crate::analysis::{access_guard, check_tree, collect_sync_functions, text, AccessGuard};
+use crate::atomics::is_write;
+use crate::callgraph::{call_target, collect_callables, collect_kind, local_names, MAX_CALL_DEPTH};
+use crate::channels::is_ordered_by_channel;
+use crate::error::AnalysisResult;
+use crate::maps::runs_alongside;
+use crate::util::node_to_range;
+
+/// A package-level variable written without synchronization by code running in a goroutine and
+/// accessed, also without synchronization, by code that can run at the same time: another
+/// goroutine, the spawning function after the `go` statement, or another instance of the same
+/// goroutine.
+#[derive(Debug, Clone)]
+pub struct GlobalRace {
+    pub variable: String,
+    pub declaration: Range,
+    /// Unsynchronized writes in goroutines, with the function or goroutine performing them.
+    pub writes: Vec<(Range, String)>,
+    /// Unsynchronized accesses that can overlap one of the writes.
+    pub conflicts: Vec<(Range, String)>,
+}
+
+impl GlobalRace {
+    pub fn message(&self) -> String {
+        let mut writers: Vec<&str> = self.writes.iter().map(|(_, who)| who.as_str()).collect();
+        writers.dedup();
+        let mut others: Vec<&str> = self.conflicts.iter().map(|(_, who)| who.as_str()).collect();
+        others.sort_unstable();
+        others.dedup();
+        format!(
+            "Package-level variable `{}` is written in {} and accessed in {} without synchronization",
+            self.variable,
+            writers.join(", "),
+            others.join(", ")
+        )
+    }
+}
+
+/// Code run by `go` statements: goroutine literals and the file-local functions they start or
+/// call, up to [`MAX_CALL_DEPTH`] calls deep.
+struct Region<'a> {
+    node: Node<'a>,
+    label: String,
+    /// The `go` statements that start it.
+    spawns: Vec<Node<'a>>,
+    /// Several instances can run at once: a `go` in a loop, several `go` statements, or a
+    /// function that is also called outside goroutines.
+    repeated: bool,
+}
+
+struct Access<'a> {
+    node: Node<'a>,
+    write: bool,
+    /// Index into the regions; `None` outside goroutines.
+    region: Option<usize>,
+}
+
+/// Package-level `var`s of the file checked across every function and method in it.
+pub fn find_global_races(tree: &Tree, code: &str) -> AnalysisResult<Vec<GlobalRace>> {
+    check_tree(tree, code)?;
+    let root = tree.root_node();
+    let globals = package_variables(root, code);
+    if globals.is_empty() {
+        return Ok(Vec::new());
+    }
+    let regions = goroutine_regions(root, code);
+    if regions.is_empty() {
+        return Ok(Vec::new());
+    }
+    let sync_funcs = collect_sync_functions(tree, code);
+    let unguarded = |node: Node| {
+        let range = node_to_range(node);
+        access_guard(tree, range, code, &sync_funcs) == AccessGuard::None
+            && !is_ordered_by_channel(tree, range, code)
+    };
+
+    let mut identifiers = Vec::new();
+    collect_kind(root, "identifier", &mut identifiers);
+    let mut accesses: HashMap<&str, Vec<Access>> = HashMap::new();
+    let mut locals = HashMap::new();
+    for ident in identifiers {
+        let name = text(code, ident);
+        if !globals.contains_key(name) || is_declaration(ident) {
+            continue;
+        }
+        let Some(function) = top_level_function(ident) else {
+            continue;
+        };
+        let shadowed = locals
+            .entry(function.id())
+            .or_insert_with(|| local_names(function, code))
+            .contains(name);
+        if shadowed || !unguarded(ident) {
+            continue;
+        }
+        let region = regions
+            .iter()
+            .enumerate()
+            .filter(|(_, region)| {
+                region.node.start_byte() <= ident.start_byte()
+                    && ident.end_byte() <= region.node.end_byte()
+            })
+            .min_by_key(|(_, region)| region.node.byte_range().len())
+            .map(|(index, _)| index);
+        accesses.entry(name).or_default().push(Access {
+            node: ident,
+            write: is_write(written_expression(ident)),
+            region,
+        });
+    }
+
+    let mut races = Vec::new();
+    for (name, declaration) in &globals {
+        let Some(accesses) = accesses.get(name) else {
+            continue;
+        };
+        let writes: Vec<&Access> = accesses
+            .iter()
+            .filter(|access| access.write && access.region.is_some())
+            .collect();
+        let mut conflicts = Vec::new();
+        for access in accesses {
+            let overlaps = writes.iter().any(|write| {
+                let Some(index) = write.region else {
+                    return false;
+                };
+                let region = &regions[index];
+                if access.region == Some(index) {
+                    return region.repeated;
+                }
+                access.region.is_some() || overlaps_spawn(access.node, region, code)
+            });
+            if overlaps {
+                conflicts.push(access);
+            }
+        }
+        let conflicting_writes: Vec<(Range, String)> = writes
+            .iter()
+            .filter(|write| conflicts.iter().any(|access| access.node == write.node))
+            .map(|write| (node_to_range(write.node), label(write, &regions, code)))
+            .collect();
+        if conflicting_writes.is_empty() {
+            continue;
+        }
+        let others: Vec<(Range, String)> = conflicts
+            .iter()
+            .filter(|access| {
+                !conflicting_writes
+                    .iter()
+                    .any(|(r, _)| *r == node_to_range(access.node))
+            })
+            .map(|access| (node_to_range(access.node), label(access, &regions, code)))
+            .collect();
+        let others = if others.is_empty() {
+            // A repeated goroutine racing with its own instances.
+            conflicting_writes.clone()
+        } else {
+            others
+        };
+        races.push(GlobalRace {
+            variable: name.to_string(),
+            declaration: node_to_range(*declaration),
+            writes: conflicting_writes,
+            conflicts: others,
+        });
+    }
+    races.sort_by_key(|race| {
+        (
+            race.declaration.start.line,
+            race.declaration.start.character,
+        )
+    });
+    Ok(races)
+}
+
+fn package_variables<'a>(root: Node<'a>, code: &'a str) -> HashMap<&'a str, Node<'a>> {
+    let mut globals = HashMap::new();
+    let mut cursor = root.walk();
+    for declaration in root.named_children(&mut cursor) {
+        if declaration.kind() != "var_declaration" {
+            continue;
+        }
+        let mut specs = Vec::new();
+        collect_kind(declaration, "var_spec", &mut specs);
+        for spec in specs {
+            let mut name_cursor = spec.walk();
+            for name in spec.children_by_field_name("name", &mut name_cursor) {
+                if text(code, name) != "_" {
+                    globals.insert(text(code, name), name);
+                }
+            }
+        }
+    }
+    globals
+}
+
+fn goroutine_regions<'a>(root: Node<'a>, code: &str) -> Vec<Region<'a>> {
+    let callables = collect_callables(root, code);
+    let mut go_statements = Vec::new();
+    collect_kind(root, "go_statement", &mut go_statements);
+    let mut regions: Vec<Region> = Vec::new();
+    let add = |regions: &mut Vec<Region<'a>>, node: Node<'a>, label: String, spawn: Node<'a>| {
+        match regions.iter_mut().find(|region| region.node == node) {
+            Some(region) => {
+                if !region.spawns.contains(&spawn) {
+                    region.spawns.push(spawn);
+                    region.repeated = true;
+                }
+                false
+            }
+            None => {
+                regions.push(Region {
+                    node,
+                    label,
+                    spawns: vec![spawn],
+                    repeated: in_loop(spawn),
+                });
+                true
+            }
+        }
+    };
+    for go_stmt in go_statements {
+        let Some(call) = go_stmt
+            .named_child(0)
+            .filter(|call| call.kind() == "call_expression")
+        else {
+            continue;
+        };
+        let mut pending = Vec::new();
+        match call.child_by_field_name("function") {
+            Some(literal) if literal.kind() == "func_literal" => {
+                let line = node_to_range(go_stmt).start.line + 1;
+                if add(
+                    &mut regions,
+                    literal,
+                    format!("the goroutine at line {}", line),
+                    go_stmt,
+                ) {
+                    pending.push((literal, 0));
+                }
+            }
+            _ => {
+                if let Some(target) = call_target(call, code, &callables) {
+                    if add(
+                        &mut regions,
+                        target.node,
+                        format!("`{}`", target.name),
+                        go_stmt,
+                    ) {
+                        pending.push((target.node, 0));
+                    }
+                }
+            }
+        }
+        while let Some((node, depth)) = pending.pop() {
+            if depth >= MAX_CALL_DEPTH {
+                continue;
+            }
+            let mut calls = Vec::new();
+            collect_kind(node, "call_expression", &mut calls);
+            for call in calls {
+                let Some(target) = call_target(call, code, &callables) else {
+                    continue;
+                };
+                if add(
+                    &mut regions,
+                    target.node,
+                    format!("`{}`", target.name),
+                    go_stmt,
+                ) {
+                    pending.push((target.node, depth + 1));
+                }
+            }
+        }
+    }
+    // A function reached from a goroutine and also called outside goroutines runs in both.
+    let mut calls = Vec::new();
+    collect_kind(root, "call_expression", &mut calls);
+    for call in calls {
+        let Some(target) = call_target(call, code, &callables) else {
+            continue;
+        };
+        let outside = !regions.iter().any(|region| {
+            region.node.start_byte() <= call.start_byte()
+                && call.end_byte() <= region.node.end_byte()
+        });
+        let spawned = call
+            .parent()
+            .is_some_and(|parent| parent.kind() == "go_statement");
+        if outside && !spawned {
+            if let Some(region) = regions.iter_mut().find(|region| region.node == target.node) {
+                region.repeated = true;
+            }
+        }
+    }
+    regions
+}
+
+fn label(access: &Access, regions: &[Region], code: &str) -> String {
+    match access.region {
+        Some(index) => regions[index].label.clone(),
+        None => top_level_function(access.node)
+            .and_then(|function| function.child_by_field_name("name"))
+            .map(|name| format!("`{}`", text(code, name)))
+            .unwrap_or_else(|| "package scope".to_string()),
+    }
+}
+
+/// `access`, outside goroutines, can run while `region` does: it is in another function than a
+/// `go` statement starting the region, follows one with nothing waiting for the goroutine in
+/// between, or precedes one that sits in a loop.
+fn overlaps_spawn(access: Node, region: &Region, code: &str) -> bool {
+    region.spawns.iter().any(|spawn| {
+        top_level_function(*spawn) != top_level_function(access)
+            || runs_alongside(*spawn, access, code)
+            || (in_loop(*spawn) && access.start_byte() < spawn.start_byte())
+    })
+}
+
+fn in_loop(node: Node) -> bool {
+    let mut current = node.parent();
+    while let Some(candidate) = current {
+        match candidate.kind() {
+            "for_statement" => return true,
+            "function_declaration" | "method_declaration" | "func_literal" => return false,
+            _ => current = candidate.parent(),
+        }
+    }
+    false
+}
+
+fn top_level_function(node: Node) -> Option<Node> {
+    let mut current = node.parent();
+    while let Some(candidate) = current {
+        if matches!(
+            candidate.kind(),
+            "function_declaration" | "method_declaration"
+        ) {
+            return Some(candidate);
+        }
+        current = candidate.parent();
+    }
+    None
+}
+
+fn is_declaration(ident: Node) -> bool {
+    ident
+        .parent()
+        .is_some_and(|parent| matches!(parent.kind(), "var_spec" | "const_spec"))
+}
+
+/// The expression an assignment to `ident` writes: `x`, `x.field` or `x[i]`.
+fn written_expression(ident: Node) -> Node {
+    let mut node = ident;
+    while let Some(parent) = node.parent() {
+        let operand = match parent.kind() {
+            "selector_expression" | "index_expression" => parent.child_by_field_name("operand"),
+            _ => None,
+        };
+        if operand != Some(node) {
+            break;
+        }
+        node = parent;
+    }
+    node
+}
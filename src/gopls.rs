@@ -0,0 +1,664 @@
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex, PoisonError, Weak};
+use std::time::Duration;
+
+use ropey::Rope;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::{oneshot, Mutex};
+use tower_lsp::lsp_types::{
+    GotoDefinitionResponse, Hover, HoverContents, Location, MarkedString, Position,
+};
+use url::Url;
+
+use crate::anchor::{from_utf16, to_utf16};
+use crate::budget::Deadline;
+use crate::types::{GraphData, GraphEdge, GraphEdgeType, GraphEntityType, GraphNode};
+
+/// Graph nodes looked up in gopls per graph, so a large file costs a bounded number of queries.
+const MAX_GRAPH_QUERIES: usize = 200;
+
+/// An optional gopls instance asked for types, definitions and implementations where the
+/// tree-sitter heuristics guess: `GO_ANALYZER_GOPLS` enables it, `GO_ANALYZER_GOPLS_PATH` is the
+/// command (default `gopls`) and `GO_ANALYZER_GOPLS_TIMEOUT_MS` bounds each query.
+#[derive(Clone, Debug)]
+pub struct GoplsConfig {
+    pub enabled: bool,
+    pub path: String,
+    pub timeout_ms: u64,
+}
+
+impl GoplsConfig {
+    pub fn from_env() -> Self {
+        let enabled = match std::env::var("GO_ANALYZER_GOPLS") {
+            Ok(v) => matches!(v.as_str(), "1" | "true" | "TRUE" | "yes" | "YES"),
+            Err(_) => false,
+        };
+        let path = std::env::var("GO_ANALYZER_GOPLS_PATH")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .unwrap_or_else(|| "gopls".to_string());
+        let timeout_ms = std::env::var("GO_ANALYZER_GOPLS_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(2000);
+        Self {
+            enabled,
+            path,
+            timeout_ms,
+        }
+    }
+}
+
+/// The server's own LSP client of gopls. gopls starts with the first query, sees each document
+/// as the server last had it, and starts again with the next query after it exits. Every query
+/// answers `None` or nothing when gopls is off, missing, failing or slower than the timeout, and
+/// callers fall back to their own analysis.
+pub struct GoplsClient {
+    pub config: GoplsConfig,
+    /// The workspace gopls loads, from `initialize`.
+    root: StdMutex<Option<Url>>,
+    process: Mutex<Option<Arc<GoplsProcess>>>,
+    /// Set when gopls could not be started; it is not tried again until the workspace changes.
+    spawn_failed: AtomicBool,
+}
+
+impl GoplsClient {
+    pub fn new(config: GoplsConfig) -> Self {
+        Self {
+            config,
+            root: StdMutex::new(None),
+            process: Mutex::new(None),
+            spawn_failed: AtomicBool::new(false),
+        }
+    }
+
+    pub fn set_root(&self, root: Option<Url>) {
+        let mut current = self.root.lock().unwrap_or_else(PoisonError::into_inner);
+        if *current != root {
+            self.spawn_failed.store(false, Ordering::Relaxed);
+        }
+        *current = root;
+    }
+
+    /// Tells a running gopls that `uri` was closed, so it stops tracking the server's copy.
+    pub async fn close(&self, uri: &Url) {
+        let process = self.process.lock().await.clone();
+        let Some(process) = process.filter(|process| !process.has_exited()) else {
+            return;
+        };
+        if let Err(e) = process.close(uri).await {
+            tracing::warn!("Failed to write to gopls: {}", e);
+        }
+    }
+
+    /// The type of the identifier at `position`, as gopls' hover declares it.
+    pub async fn type_at(&self, uri: &Url, position: Position, code: &str) -> Option<String> {
+        let (result, _) = self
+            .query("textDocument/hover", uri, position, code)
+            .await?;
+        let hover: Hover = serde_json::from_value(result).ok()?;
+        type_from_hover(&hover_markdown(hover.contents))
+    }
+
+    pub async fn definition(&self, uri: &Url, position: Position, code: &str) -> Vec<Location> {
+        self.locations("textDocument/definition", uri, position, code)
+            .await
+    }
+
+    /// For a type, the interfaces it implements; for an interface, the types implementing it.
+    pub async fn implementations(
+        &self,
+        uri: &Url,
+        position: Position,
+        code: &str,
+    ) -> Vec<Location> {
+        self.locations("textDocument/implementation", uri, position, code)
+            .await
+    }
+
+    /// Where the identifier at `position` is declared, when that is in the same document.
+    pub async fn declaration(&self, uri: &Url, position: Position, code: &str) -> Option<Position> {
+        self.definition(uri, position, code)
+            .await
+            .into_iter()
+            .find(|location| location.uri == *uri)
+            .map(|location| location.range.start)
+    }
+
+    /// Adds what gopls knows to a file graph: the `type` of each declared variable and, for
+    /// each struct, `Implements` edges to the interfaces it satisfies. Interfaces declared in
+    /// other files are listed in the struct's `implements`. Stops at `deadline`.
+    pub async fn enrich_graph(
+        &self,
+        uri: &Url,
+        code: &str,
+        graph: &mut GraphData,
+        deadline: Deadline,
+    ) {
+        if !self.config.enabled {
+            return;
+        }
+        let interfaces: Vec<(String, Position)> = graph
+            .nodes
+            .iter()
+            .filter(|node| node.entity_type == GraphEntityType::Interface)
+            .map(|node| (node.id.clone(), node.range.start))
+            .collect();
+        let mut queries = 0;
+        for index in 0..graph.nodes.len() {
+            if queries >= MAX_GRAPH_QUERIES || deadline.expired() {
+                break;
+            }
+            let node = &graph.nodes[index];
+            let start = node.range.start;
+            match node.entity_type {
+                GraphEntityType::Variable if !is_use(node) => {
+                    queries += 1;
+                    if let Some(type_name) = self.type_at(uri, start, code).await {
+                        set_extra(&mut graph.nodes[index], "type", json!(type_name));
+                    }
+                }
+                GraphEntityType::Struct => {
+                    queries += 1;
+                    let from = node.id.clone();
+                    let mut elsewhere = Vec::new();
+                    for location in self.implementations(uri, start, code).await {
+                        let local = (location.uri == *uri)
+                            .then(|| {
+                                interfaces
+                                    .iter()
+                                    .find(|(_, at)| *at == location.range.start)
+                            })
+                            .flatten();
+                        match local {
+                            Some((to, _)) => {
+                                let edge = GraphEdge {
+                                    from: from.clone(),
+                                    to: to.clone(),
+                                    edge_type: GraphEdgeType::Implements,
+                                };
+                                if !graph.edges.contains(&edge) {
+                                    graph.edges.push(edge);
+                                }
+                            }
+                            None => elsewhere.push(json!({
+                                "uri": location.uri,
+                                "line": location.range.start.line,
+                            })),
+                        }
+                    }
+                    if !elsewhere.is_empty() {
+                        set_extra(&mut graph.nodes[index], "implements", json!(elsewhere));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Shuts gopls down; the next query starts it again.
+    pub async fn stop(&self) {
+        let Some(process) = self.process.lock().await.take() else {
+            return;
+        };
+        let timeout = Duration::from_millis(self.config.timeout_ms);
+        let _ = process.request("shutdown", Value::Null, timeout).await;
+        let _ = process.notify("exit", Value::Null).await;
+    }
+
+    async fn locations(
+        &self,
+        method: &str,
+        uri: &Url,
+        position: Position,
+        code: &str,
+    ) -> Vec<Location> {
+        let Some((result, encoding)) = self.query(method, uri, position, code).await else {
+            return Vec::new();
+        };
+        let mut locations = match serde_json::from_value::<Option<GotoDefinitionResponse>>(result) {
+            Ok(Some(GotoDefinitionResponse::Scalar(location))) => vec![location],
+            Ok(Some(GotoDefinitionResponse::Array(locations))) => locations,
+            Ok(Some(GotoDefinitionResponse::Link(links))) => links
+                .into_iter()
+                .map(|link| Location::new(link.target_uri, link.target_selection_range))
+                .collect(),
+            Ok(None) | Err(_) => Vec::new(),
+        };
+        if encoding == PositionEncoding::Utf16 {
+            // Ranges in files other than `uri` are converted against their text on disk; one
+            // that cannot be read keeps gopls' columns.
+            let mut texts: HashMap<Url, Option<Rope>> = HashMap::new();
+            texts.insert(uri.clone(), Some(Rope::from_str(code)));
+            for location in &mut locations {
+                if !texts.contains_key(&location.uri) {
+                    let text = match location.uri.to_file_path() {
+                        Ok(path) => tokio::fs::read_to_string(path).await.ok(),
+                        Err(()) => None,
+                    };
+                    texts.insert(location.uri.clone(), text.as_deref().map(Rope::from_str));
+                }
+                if let Some(Some(text)) = texts.get(&location.uri) {
+                    location.range.start = from_utf16(text, location.range.start);
+                    location.range.end = from_utf16(text, location.range.end);
+                }
+            }
+        }
+        locations
+    }
+
+    /// The result of a `textDocument/*` request at `position`, after gopls has been sent `code`,
+    /// with the encoding of the positions in it.
+    async fn query(
+        &self,
+        method: &str,
+        uri: &Url,
+        position: Position,
+        code: &str,
+    ) -> Option<(Value, PositionEncoding)> {
+        if !self.config.enabled {
+            return None;
+        }
+        let process = self.process().await?;
+        if let Err(e) = process.sync(uri, code).await {
+            tracing::warn!("Failed to write to gopls: {}", e);
+            return None;
+        }
+        let position = match process.encoding {
+            PositionEncoding::Utf8 => position,
+            PositionEncoding::Utf16 => to_utf16(&Rope::from_str(code), position),
+        };
+        let params = json!({
+            "textDocument": { "uri": uri },
+            "position": position,
+        });
+        process
+            .request(
+                method,
+                params,
+                Duration::from_millis(self.config.timeout_ms),
+            )
+            .await
+            .filter(|result| !result.is_null())
+            .map(|result| (result, process.encoding))
+    }
+
+    /// The running gopls, started and initialized now if it is not running.
+    async fn process(&self) -> Option<Arc<GoplsProcess>> {
+        let mut process = self.process.lock().await;
+        if let Some(running) = process.as_ref().filter(|running| !running.has_exited()) {
+            return Some(running.clone());
+        }
+        if self.spawn_failed.load(Ordering::Relaxed) {
+            return None;
+        }
+        let restart = process.take().is_some();
+        let mut started = match GoplsProcess::spawn(&self.config.path) {
+            Ok(started) => started,
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to start gopls {}: {}; not retrying until the workspace changes",
+                    self.config.path,
+                    e
+                );
+                self.spawn_failed.store(true, Ordering::Relaxed);
+                return None;
+            }
+        };
+        let root = self
+            .root
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        let params = json!({
+            "processId": std::process::id(),
+            "rootUri": root,
+            "workspaceFolders": root.as_ref().map(|root| vec![json!({ "uri": root, "name": "workspace" })]),
+            "capabilities": {
+                // Byte columns, as the server uses; a gopls that cannot send them counts UTF-16
+                // code units, the LSP default.
+                "general": { "positionEncodings": ["utf-8", "utf-16"] },
+                "textDocument": {
+                    "hover": { "contentFormat": ["markdown", "plaintext"] },
+                    "definition": { "linkSupport": true },
+                    "implementation": { "linkSupport": true },
+                },
+            },
+        });
+        let timeout = Duration::from_millis(self.config.timeout_ms);
+        let initialized = started.request("initialize", params, timeout).await?;
+        if initialized["capabilities"]["positionEncoding"] == "utf-8" {
+            started.encoding = PositionEncoding::Utf8;
+        }
+        if let Err(e) = started.notify("initialized", json!({})).await {
+            tracing::warn!("Failed to initialize gopls: {}", e);
+            return None;
+        }
+        if restart {
+            tracing::info!("Restarted gopls");
+        }
+        Some(process.insert(Arc::new(started)).clone())
+    }
+}
+
+/// The markdown of a hover, whatever shape gopls sent it in.
+fn hover_markdown(contents: HoverContents) -> String {
+    let marked = |marked: MarkedString| match marked {
+        MarkedString::String(text) => text,
+        MarkedString::LanguageString(code) => {
+            format!("```{}\n{}\n```", code.language, code.value)
+        }
+    };
+    match contents {
+        HoverContents::Markup(markup) => markup.value,
+        HoverContents::Scalar(scalar) => marked(scalar),
+        HoverContents::Array(items) => items.into_iter().map(marked).collect::<Vec<_>>().join("\n"),
+    }
+}
+
+/// The type in gopls' hover of a variable, field or constant: `int64` from
+/// ```` ```go\nvar total int64\n``` ````. `None` for other identifiers.
+pub fn type_from_hover(markdown: &str) -> Option<String> {
+    let block = markdown.split("```go").nth(1)?.split("```").next()?;
+    let line = block.trim().lines().next()?.trim();
+    let declared = ["var ", "field ", "const "]
+        .iter()
+        .find_map(|keyword| line.strip_prefix(keyword))?;
+    let (_, type_name) = declared.split_once(' ')?;
+    let type_name = type_name.split(" = ").next()?.trim();
+    (!type_name.is_empty()).then(|| type_name.to_string())
+}
+
+fn is_use(node: &GraphNode) -> bool {
+    node.extra
+        .as_ref()
+        .and_then(|extra| extra.get("use"))
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+fn set_extra(node: &mut GraphNode, key: &str, value: Value) {
+    match node.extra.as_mut().and_then(Value::as_object_mut) {
+        Some(extra) => {
+            extra.insert(key.to_string(), value);
+        }
+        None => node.extra = Some(json!({ key: value })),
+    }
+}
+
+/// Requests waiting for an answer, by id.
+#[derive(Default)]
+struct Pending {
+    waiters: HashMap<u64, oneshot::Sender<Value>>,
+    /// Set when gopls' stdout closed; nothing will be answered anymore.
+    exited: bool,
+}
+
+/// How the characters of the positions exchanged with gopls count, as negotiated in `initialize`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PositionEncoding {
+    Utf8,
+    Utf16,
+}
+
+struct GoplsProcess {
+    /// Dropping the last reference closes stdin, which stops gopls.
+    input: Arc<Mutex<GoplsInput>>,
+    pending: Arc<StdMutex<Pending>>,
+    next_id: AtomicU64,
+    encoding: PositionEncoding,
+}
+
+struct GoplsInput {
+    stdin: ChildStdin,
+    /// Version and content hash of each document gopls was sent.
+    open: HashMap<Url, (i32, String)>,
+}
+
+impl GoplsInput {
+    /// Writes one message with its `Content-Length` header.
+    async fn write(&mut self, message: &Value) -> std::io::Result<()> {
+        let body = serde_json::to_vec(message)?;
+        self.stdin
+            .write_all(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes())
+            .await?;
+        self.stdin.write_all(&body).await?;
+        self.stdin.flush().await
+    }
+}
+
+impl GoplsProcess {
+    fn spawn(path: &str) -> std::io::Result<Self> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let missing = || std::io::Error::other("gopls stdio is not piped");
+        let stdin = child.stdin.take().ok_or_else(missing)?;
+        let stdout = child.stdout.take().ok_or_else(missing)?;
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    tracing::debug!("gopls: {}", line);
+                }
+            });
+        }
+        let input = Arc::new(Mutex::new(GoplsInput {
+            stdin,
+            open: HashMap::new(),
+        }));
+        let pending = Arc::new(StdMutex::new(Pending::default()));
+        tokio::spawn(read_messages(
+            child,
+            stdout,
+            Arc::downgrade(&input),
+            pending.clone(),
+        ));
+        Ok(Self {
+            input,
+            pending,
+            next_id: AtomicU64::new(1),
+            encoding: PositionEncoding::Utf16,
+        })
+    }
+
+    fn has_exited(&self) -> bool {
+        self.pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .exited
+    }
+
+    /// Sends `code` as the text of `uri` unless gopls already has it.
+    async fn sync(&self, uri: &Url, code: &str) -> std::io::Result<()> {
+        let hash = crate::record::document_hash(code);
+        let mut input = self.input.lock().await;
+        let message = match input.open.get(uri) {
+            Some((_, sent)) if *sent == hash => return Ok(()),
+            Some((version, _)) => {
+                let version = version + 1;
+                input.open.insert(uri.clone(), (version, hash));
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/didChange",
+                    "params": {
+                        "textDocument": { "uri": uri, "version": version },
+                        "contentChanges": [{ "text": code }],
+                    },
+                })
+            }
+            None => {
+                input.open.insert(uri.clone(), (1, hash));
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/didOpen",
+                    "params": {
+                        "textDocument": { "uri": uri, "languageId": "go", "version": 1, "text": code },
+                    },
+                })
+            }
+        };
+        let written = input.write(&message).await;
+        if written.is_err() {
+            input.open.remove(uri);
+        }
+        written
+    }
+
+    /// Sends `textDocument/didClose` for `uri` if gopls was sent the document.
+    async fn close(&self, uri: &Url) -> std::io::Result<()> {
+        let mut input = self.input.lock().await;
+        if input.open.remove(uri).is_none() {
+            return Ok(());
+        }
+        input
+            .write(&json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didClose",
+                "params": { "textDocument": { "uri": uri } },
+            }))
+            .await
+    }
+
+    /// The result of `method`; `None` on an error, a timeout or when gopls exited.
+    async fn request(&self, method: &str, params: Value, timeout: Duration) -> Option<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, answer) = oneshot::channel();
+        {
+            let mut pending = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+            if pending.exited {
+                return None;
+            }
+            pending.waiters.insert(id, sender);
+        }
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        if let Err(e) = self.input.lock().await.write(&message).await {
+            tracing::warn!("Failed to write to gopls: {}", e);
+            self.forget(id);
+            return None;
+        }
+        let mut response = match tokio::time::timeout(timeout, answer).await {
+            Ok(Ok(response)) => response,
+            // gopls exited, or is still busy; a late answer is dropped.
+            _ => {
+                self.forget(id);
+                return None;
+            }
+        };
+        if let Some(error) = response.get("error") {
+            tracing::debug!("gopls failed {}: {}", method, error);
+            return None;
+        }
+        Some(
+            response
+                .get_mut("result")
+                .map(Value::take)
+                .unwrap_or_default(),
+        )
+    }
+
+    async fn notify(&self, method: &str, params: Value) -> std::io::Result<()> {
+        let message = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        self.input.lock().await.write(&message).await
+    }
+
+    fn forget(&self, id: u64) {
+        self.pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .waiters
+            .remove(&id);
+    }
+}
+
+/// Reads one message; `None` at the end of the stream.
+async fn read_message(reader: &mut BufReader<ChildStdout>) -> std::io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() && length.is_some() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let mut body = vec![0; length.unwrap_or_default()];
+    reader.read_exact(&mut body).await?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(std::io::Error::other)
+}
+
+/// Hands each answer to the request waiting for it and answers gopls' own requests, until
+/// gopls closes its stdout.
+async fn read_messages(
+    mut child: Child,
+    stdout: ChildStdout,
+    input: Weak<Mutex<GoplsInput>>,
+    pending: Arc<StdMutex<Pending>>,
+) {
+    let mut reader = BufReader::new(stdout);
+    loop {
+        let message = match read_message(&mut reader).await {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(e) => {
+                tracing::debug!("Ignoring unreadable gopls output: {}", e);
+                continue;
+            }
+        };
+        let id = message.get("id").cloned();
+        match (message.get("method"), id) {
+            // A request from gopls, such as `workspace/configuration`: answered with defaults.
+            (Some(method), Some(id)) => {
+                let result = if method == "workspace/configuration" {
+                    let items = message["params"]["items"].as_array().map_or(0, Vec::len);
+                    json!(vec![Value::Null; items])
+                } else {
+                    Value::Null
+                };
+                let Some(input) = input.upgrade() else {
+                    continue;
+                };
+                let reply = json!({ "jsonrpc": "2.0", "id": id, "result": result });
+                let written = input.lock().await.write(&reply).await;
+                if let Err(e) = written {
+                    tracing::debug!("Failed to answer gopls: {}", e);
+                }
+            }
+            (None, Some(id)) => {
+                let waiter = id.as_u64().and_then(|id| {
+                    pending
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .waiters
+                        .remove(&id)
+                });
+                if let Some(waiter) = waiter {
+                    let _ = waiter.send(message);
+                }
+            }
+            // Notifications: diagnostics, progress and log messages.
+            _ => {}
+        }
+    }
+    {
+        let mut pending = pending.lock().unwrap_or_else(PoisonError::into_inner);
+        pending.exited = true;
+        // Dropping the senders fails every request still waiting.
+        pending.waiters.clear();
+    }
+    let _ = child.wait().await;
+}
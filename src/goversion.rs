@@ -0,0 +1,83 @@
+use std::path::Path;
+
+/// A Go language version such as `1.22`. Patch releases do not change language semantics and are
+/// dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GoVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl GoVersion {
+    pub const fn new(major: u32, minor: u32) -> Self {
+        Self { major, minor }
+    }
+
+    /// `1.22`, `1.22.3` or `1.21rc1`.
+    pub fn parse(version: &str) -> Option<Self> {
+        let mut parts = version.split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor: String = parts
+            .next()?
+            .chars()
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        Some(Self::new(major, minor.parse().ok()?))
+    }
+
+    /// Go 1.22 declares `for` loop variables once per iteration instead of once per loop.
+    pub fn has_per_iteration_loop_variables(self) -> bool {
+        self >= Self::new(1, 22)
+    }
+}
+
+/// The `go` directive of a `go.mod` file.
+pub fn parse_go_mod(go_mod: &str) -> Option<GoVersion> {
+    go_mod.lines().find_map(|line| {
+        let line = line.split("//").next().unwrap_or("").trim();
+        let version = line.strip_prefix("go")?;
+        if !version.starts_with(char::is_whitespace) {
+            return None;
+        }
+        GoVersion::parse(version.trim())
+    })
+}
+
+/// The `go` directive of the nearest `go.mod` in `file`'s directory or one of its ancestors.
+pub fn module_go_version(file: &Path) -> Option<GoVersion> {
+    let go_mod = file
+        .ancestors()
+        .skip(1)
+        .map(|dir| dir.join("go.mod"))
+        .find(|path| path.is_file())?;
+    parse_go_mod(&std::fs::read_to_string(go_mod).ok()?)
+}
+
+/// The minimum Go version a file's `//go:build` line requires: `go1.22 && linux` requires 1.22,
+/// `go1.21 || go1.22` only 1.21. Negated versions (`!go1.22`) are ignored.
+pub fn build_constraint_version(code: &str) -> Option<GoVersion> {
+    let expression = code
+        .lines()
+        .map(str::trim)
+        .take_while(|line| line.is_empty() || line.starts_with("//"))
+        .find_map(|line| line.strip_prefix("//go:build"))?;
+    let versions: Vec<GoVersion> = expression
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '!'))
+        .filter_map(|term| GoVersion::parse(term.strip_prefix("go")?))
+        .collect();
+    if expression.contains("||") {
+        versions.into_iter().min()
+    } else {
+        versions.into_iter().max()
+    }
+}
+
+/// The language version `code` is compiled with. Since Go 1.21 a `//go:build` version constraint
+/// overrides the module's version for that file; without a `go.mod` only the constraint counts.
+pub fn file_go_version(module: Option<GoVersion>, code: &str) -> Option<GoVersion> {
+    match (module, build_constraint_version(code)) {
+        (Some(module), Some(file)) if module >= GoVersion::new(1, 21) => Some(file),
+        (Some(module), _) => Some(module),
+        (None, file) => file,
+    }
+}
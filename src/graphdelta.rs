@@ -0,0 +1,134 @@
+use std::collections::{HashMap, HashSet};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::Url;
+
+use crate::types::{GraphData, GraphEdge, GraphNode};
+
+pub struct GraphDeltaNotification;
+impl tower_lsp::lsp_types::notification::Notification for GraphDeltaNotification {
+    const METHOD: &'static str = "goanalyzer/graphDelta";
+    type Params = GraphDelta;
+}
+
+/// Changes to a document's entity graph since the last graph the client received. Nodes are
+/// matched by their stable id: a node in `nodes` is new or replaces the node with its id.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, JsonSchema)]
+pub struct GraphDelta {
+    pub uri: String,
+    pub version: Option<i32>,
+    pub nodes: Vec<GraphNode>,
+    pub removed_nodes: Vec<String>,
+    pub edges: Vec<GraphEdge>,
+    pub removed_edges: Vec<GraphEdge>,
+}
+
+impl GraphDelta {
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+            && self.removed_nodes.is_empty()
+            && self.edges.is_empty()
+            && self.removed_edges.is_empty()
+    }
+}
+
+/// Last file graph sent per document. A document is tracked once its graph has been requested
+/// with `goanalyzer/graph`, and only tracked documents get `goanalyzer/graphDelta` pushes.
+#[derive(Default)]
+pub struct GraphStore {
+    sent: HashMap<Url, GraphData>,
+}
+
+impl GraphStore {
+    /// Records `graph` as what the client holds for `uri`.
+    pub fn track(&mut self, uri: &Url, graph: GraphData) {
+        self.sent.insert(uri.clone(), graph);
+    }
+
+    /// Number of documents whose graph the client holds.
+    pub fn tracked_documents(&self) -> usize {
+        self.sent.len()
+    }
+
+    pub fn is_tracked(&self, uri: &Url) -> bool {
+        self.sent.contains_key(uri)
+    }
+
+    /// Records `graph` as the current graph of a tracked `uri` and returns what changed, or
+    /// `None` when the document is not tracked or nothing changed.
+    pub fn update(
+        &mut self,
+        uri: &Url,
+        version: Option<i32>,
+        graph: GraphData,
+    ) -> Option<GraphDelta> {
+        let previous = self.sent.get_mut(uri)?;
+        let mut delta = diff_graphs(previous, &graph);
+        *previous = graph;
+        delta.uri = uri.to_string();
+        delta.version = version;
+        (!delta.is_empty()).then_some(delta)
+    }
+
+    pub fn remove(&mut self, uri: &Url) {
+        self.sent.remove(uri);
+    }
+}
+
+/// Nodes added or changed and removed between `old` and `new`, by id, and edges added and
+/// removed, counting repeated edges. Duplicate node ids keep the first occurrence.
+pub fn diff_graphs(old: &GraphData, new: &GraphData) -> GraphDelta {
+    let old_nodes = nodes_by_id(old);
+    let new_nodes = nodes_by_id(new);
+    let mut seen = HashSet::new();
+    let nodes = new
+        .nodes
+        .iter()
+        .filter(|node| seen.insert(node.id.as_str()))
+        .filter(|node| old_nodes.get(node.id.as_str()) != Some(node))
+        .cloned()
+        .collect();
+    let mut seen = HashSet::new();
+    let removed_nodes = old
+        .nodes
+        .iter()
+        .filter(|node| seen.insert(node.id.as_str()))
+        .filter(|node| !new_nodes.contains_key(node.id.as_str()))
+        .map(|node| node.id.clone())
+        .collect();
+    GraphDelta {
+        nodes,
+        removed_nodes,
+        edges: edges_missing(&new.edges, &old.edges),
+        removed_edges: edges_missing(&old.edges, &new.edges),
+        ..GraphDelta::default()
+    }
+}
+
+fn nodes_by_id(graph: &GraphData) -> HashMap<&str, &GraphNode> {
+    let mut nodes = HashMap::new();
+    for node in &graph.nodes {
+        nodes.entry(node.id.as_str()).or_insert(node);
+    }
+    nodes
+}
+
+/// Edges of `edges` beyond those in `other`, in order.
+fn edges_missing(edges: &[GraphEdge], other: &[GraphEdge]) -> Vec<GraphEdge> {
+    let mut available: HashMap<&GraphEdge, usize> = HashMap::new();
+    for edge in other {
+        *available.entry(edge).or_insert(0) += 1;
+    }
+    edges
+        .iter()
+        .filter(|edge| match available.get_mut(edge) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                false
+            }
+            _ => true,
+        })
+        .cloned()
+        .collect()
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::Url;
+
+use crate::types::{GraphData, GraphEdge, GraphNode};
+use crate::workspace::GraphScope;
+
+/// Items (nodes, then edges) per page when the request sets no `limit`.
+pub const DEFAULT_PAGE_SIZE: usize = 2000;
+
+/// Arguments of `goanalyzer/graph.page`. Without `cursor` the graph is rebuilt and its first
+/// page returned; the `next_cursor` of a page asks for the one after it.
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct GraphPageParams {
+    pub uri: Url,
+    #[serde(default)]
+    pub scope: GraphScope,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+/// One page of a graph: all nodes come before the first edge, so a client that appends pages
+/// in order never sees an edge before its endpoints' nodes.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, JsonSchema)]
+pub struct GraphPage {
+    /// Document version the graph was built from.
+    pub version: Option<i32>,
+    pub total_nodes: usize,
+    pub total_edges: usize,
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+    /// Cursor of the next page; absent on the last one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    /// The graph ran out of its time budget; see [`GraphData::truncated`].
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+/// Full graphs being paged through, per document, kept until the document changes, a first
+/// page is asked for again or the document closes.
+#[derive(Default)]
+pub struct GraphPageCache {
+    graphs: HashMap<Url, CachedGraph>,
+    generation: u64,
+}
+
+struct CachedGraph {
+    scope: GraphScope,
+    version: Option<i32>,
+    generation: u64,
+    graph: GraphData,
+}
+
+impl GraphPageCache {
+    /// Caches `graph` for `uri` and returns its first page.
+    pub fn first_page(
+        &mut self,
+        uri: &Url,
+        scope: GraphScope,
+        version: Option<i32>,
+        graph: GraphData,
+        limit: usize,
+    ) -> GraphPage {
+        self.generation += 1;
+        let page = graph_page(&graph, self.generation, 0, limit, version);
+        self.graphs.insert(
+            uri.clone(),
+            CachedGraph {
+                scope,
+                version,
+                generation: self.generation,
+                graph,
+            },
+        );
+        page
+    }
+
+    /// The page at `cursor` of the graph cached for `uri`, if the cursor belongs to it and the
+    /// document is still at `version`.
+    pub fn page(
+        &self,
+        uri: &Url,
+        scope: GraphScope,
+        version: Option<i32>,
+        cursor: &str,
+        limit: usize,
+    ) -> Result<GraphPage, String> {
+        let (generation, offset) =
+            parse_cursor(cursor).ok_or_else(|| format!("Invalid graph cursor `{}`", cursor))?;
+        match self.graphs.get(uri) {
+            Some(cached)
+                if cached.generation == generation
+                    && cached.scope == scope
+                    && cached.version == version =>
+            {
+                Ok(graph_page(
+                    &cached.graph,
+                    generation,
+                    offset,
+                    limit,
+                    version,
+                ))
+            }
+            _ => Err("Graph cursor expired; request the first page again".to_string()),
+        }
+    }
+
+    /// Number of documents with a graph being paged through.
+    pub fn cached_graphs(&self) -> usize {
+        self.graphs.len()
+    }
+
+    pub fn remove(&mut self, uri: &Url) {
+        self.graphs.remove(uri);
+    }
+}
+
+/// The `limit` items of `graph` from `offset`, counting nodes before edges.
+pub fn graph_page(
+    graph: &GraphData,
+    generation: u64,
+    offset: usize,
+    limit: usize,
+    version: Option<i32>,
+) -> GraphPage {
+    let limit = limit.max(1);
+    let total_nodes = graph.nodes.len();
+    let total_edges = graph.edges.len();
+    let end = offset.saturating_add(limit).min(total_nodes + total_edges);
+    let nodes = graph
+        .nodes
+        .get(offset.min(total_nodes)..end.min(total_nodes))
+        .unwrap_or_default()
+        .to_vec();
+    let edges = graph
+        .edges
+        .get(offset.max(total_nodes) - total_nodes..end.max(total_nodes) - total_nodes)
+        .unwrap_or_default()
+        .to_vec();
+    GraphPage {
+        version,
+        total_nodes,
+        total_edges,
+        nodes,
+        edges,
+        next_cursor: (end < total_nodes + total_edges).then(|| format!("{}:{}", generation, end)),
+        truncated: graph.truncated,
+    }
+}
+
+fn parse_cursor(cursor: &str) -> Option<(u64, usize)> {
+    let (generation, offset) = cursor.split_once(':')?;
+    Some((generation.parse().ok()?, offset.parse().ok()?))
+}
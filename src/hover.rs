@@ -0,0 +1,161 @@
+use tower_lsp::lsp_types::{Location, Range, Url};
+
+use crate::types::{GoroutineUsage, RaceSeverity, VariableInfo};
+
+const MAX_LISTED_USES: usize = 20;
+
+pub struct HoverInput<'a> {
+    pub uri: &'a Url,
+    pub code: &'a str,
+    pub var_info: &'a VariableInfo,
+    pub type_name: Option<&'a str>,
+    /// Whether each use (same order as `var_info.uses`) writes the variable.
+    pub writes: &'a [bool],
+    pub goroutines: &'a [GoroutineUsage],
+    /// Guarding mutex per use (same order as `var_info.uses`).
+    pub guards: &'a [Option<String>],
+    pub severity: Option<RaceSeverity>,
+}
+
+pub fn render_hover(input: &HoverInput) -> String {
+    let var_info = input.var_info;
+    let mut markdown = String::new();
+    if let Some(severity) = &input.severity {
+        markdown.push_str(&format!("{}\n\n", severity_badge(severity)));
+    }
+    markdown.push_str(&format!("**Variable**: `{}`\n\n", var_info.name));
+    // The snippet comes from the document, which a declaration in another file is not in.
+    if let Some(snippet) = declaration_snippet(input.code, var_info.declaration)
+        .filter(|_| var_info.declared_in.is_none())
+    {
+        markdown.push_str(&format!("```go\n{}\n```\n\n", snippet));
+    }
+    let kind = if var_info.is_pointer {
+        "Pointer"
+    } else {
+        "Value"
+    };
+    let type_line = match input.type_name {
+        Some(type_name) => format!("`{}` ({})", type_name, kind),
+        None => kind.to_string(),
+    };
+    let declared_at = match &var_info.declared_in {
+        Some(uri) => file_link(&Location::new(uri.clone(), var_info.declaration)),
+        None => line_link(input.uri, var_info.declaration),
+    };
+    markdown.push_str(&format!(
+        "**Declared at**: {}\n**Type**: {}\n",
+        declared_at, type_line
+    ));
+    if let Some(guard_line) = guard_summary(input.guards, !input.goroutines.is_empty()) {
+        markdown.push_str(&format!("**Lock**: {}\n", guard_line));
+    }
+    let total_uses = var_info.uses.len() + var_info.package_uses.len();
+    markdown.push_str(&format!("\n**Uses** ({}):\n", total_uses));
+    for (idx, use_range) in var_info.uses.iter().take(MAX_LISTED_USES).enumerate() {
+        let access = if input.writes.get(idx).copied().unwrap_or(false) {
+            "write"
+        } else {
+            "read"
+        };
+        markdown.push_str(&format!(
+            "- {} {}\n",
+            line_link(input.uri, *use_range),
+            access
+        ));
+    }
+    let listed = var_info.uses.len().min(MAX_LISTED_USES);
+    for location in var_info.package_uses.iter().take(MAX_LISTED_USES - listed) {
+        markdown.push_str(&format!("- {}\n", file_link(location)));
+    }
+    if total_uses > MAX_LISTED_USES {
+        markdown.push_str(&format!("- … and {} more\n", total_uses - MAX_LISTED_USES));
+    }
+    if !input.goroutines.is_empty() {
+        markdown.push_str("\n**Goroutines**:\n");
+        for usage in input.goroutines {
+            let mut access = Vec::new();
+            if usage.reads > 0 {
+                access.push(format!("{} read(s)", usage.reads));
+            }
+            if usage.writes > 0 {
+                access.push(format!("{} write(s)", usage.writes));
+            }
+            markdown.push_str(&format!(
+                "- {} (`{:?}`): {}\n",
+                line_link(input.uri, usage.launch),
+                usage.goroutine_type,
+                access.join(", ")
+            ));
+        }
+    }
+    markdown
+}
+
+pub fn severity_badge(severity: &RaceSeverity) -> &'static str {
+    match severity {
+        RaceSeverity::High => "🔴 **Race: HIGH**",
+        RaceSeverity::Medium => "🟠 **Race: MEDIUM**",
+        RaceSeverity::Low => "🟡 **Race: LOW** (sync detected)",
+    }
+}
+
+/// Markdown link to `uri` at the start of `range`, using the editor's `#L<line>,<col>` fragment.
+pub fn line_link(uri: &Url, range: Range) -> String {
+    labeled_link(uri, range, &format!("line {}", range.start.line + 1))
+}
+
+fn labeled_link(uri: &Url, range: Range, label: &str) -> String {
+    let mut target = uri.clone();
+    target.set_fragment(Some(&format!(
+        "L{},{}",
+        range.start.line + 1,
+        range.start.character + 1
+    )));
+    format!("[{}]({})", label, target)
+}
+
+/// Like [`line_link`], for a location in another file, which the link names.
+pub fn file_link(location: &Location) -> String {
+    let name = location
+        .uri
+        .to_file_path()
+        .ok()
+        .and_then(|path| Some(path.file_name()?.to_string_lossy().into_owned()))
+        .unwrap_or_default();
+    labeled_link(
+        &location.uri,
+        location.range,
+        &format!("{} line {}", name, location.range.start.line + 1),
+    )
+}
+
+fn declaration_snippet(code: &str, declaration: Range) -> Option<String> {
+    let line = code.lines().nth(declaration.start.line as usize)?.trim();
+    if line.is_empty() {
+        return None;
+    }
+    Some(line.replace("```", "`\u{200b}``"))
+}
+
+fn guard_summary(guards: &[Option<String>], in_goroutines: bool) -> Option<String> {
+    let guarded = guards.iter().filter(|g| g.is_some()).count();
+    if guarded == 0 {
+        return in_goroutines.then(|| "unguarded".to_string());
+    }
+    let mut mutexes: Vec<&str> = guards.iter().flatten().map(|m| m.as_str()).collect();
+    mutexes.sort_unstable();
+    mutexes.dedup();
+    let names: Vec<String> = mutexes.iter().map(|m| format!("`{}`", m)).collect();
+    if guarded == guards.len() {
+        Some(format!("guarded by {}", names.join(", ")))
+    } else {
+        Some(format!(
+            "guarded by {} ({} of {} accesses, {} unguarded)",
+            names.join(", "),
+            guarded,
+            guards.len(),
+            guards.len() - guarded
+        ))
+    }
+}
@@ -0,0 +1,472 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tower_lsp::lsp_types::{Position, Range};
+
+use crate::analysis::graph_id_name;
+use crate::dot::graph_to_dot;
+use crate::types::{ByteRange, GraphData, GraphEdge, GraphEdgeType, GraphNode};
+
+/// Output format of `goanalyzer/graph.export` and `--export-graph`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphFormat {
+    /// Graphviz DOT.
+    #[default]
+    Dot,
+    /// GraphML, for Gephi, Cytoscape and yEd.
+    Graphml,
+    /// JSON Graph Format.
+    Jgf,
+}
+
+impl GraphFormat {
+    pub fn parse(name: &str) -> Option<GraphFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "dot" | "gv" => Some(GraphFormat::Dot),
+            "graphml" => Some(GraphFormat::Graphml),
+            "jgf" | "json" => Some(GraphFormat::Jgf),
+            _ => None,
+        }
+    }
+
+    pub fn render(self, graph: &GraphData) -> String {
+        match self {
+            GraphFormat::Dot => graph_to_dot(graph),
+            GraphFormat::Graphml => graph_to_graphml(graph),
+            GraphFormat::Jgf => graph_to_jgf(graph),
+        }
+    }
+}
+
+/// GraphML attribute keys: id, owning element and type.
+const GRAPHML_KEYS: &[(&str, &str, &str)] = &[
+    ("label", "node", "string"),
+    ("entity_type", "node", "string"),
+    ("start_line", "node", "int"),
+    ("start_character", "node", "int"),
+    ("end_line", "node", "int"),
+    ("end_character", "node", "int"),
+    ("start_byte", "node", "long"),
+    ("end_byte", "node", "long"),
+    ("extra", "node", "string"),
+    ("edge_type", "edge", "string"),
+];
+
+/// Renders the entity graph as GraphML. Every node field becomes a `data` element, with
+/// `extra` kept as a JSON string. Edge endpoints that are not entities (call sites, sync
+/// points) are declared as nodes carrying only a label, since GraphML requires every edge
+/// endpoint to exist; [`graph_from_graphml`] drops them again.
+pub fn graph_to_graphml(graph: &GraphData) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+    for (id, owner, kind) in GRAPHML_KEYS {
+        let _ = writeln!(
+            xml,
+            "  <key id=\"{}\" for=\"{}\" attr.name=\"{}\" attr.type=\"{}\"/>",
+            id, owner, id, kind
+        );
+    }
+    xml.push_str("  <graph id=\"go_analyzer\" edgedefault=\"directed\">\n");
+    let mut known = HashSet::new();
+    for node in &graph.nodes {
+        if !known.insert(node.id.as_str()) {
+            continue;
+        }
+        let _ = writeln!(xml, "    <node id=\"{}\">", escape(&node.id));
+        let mut data = vec![
+            ("label", node.label.clone()),
+            ("entity_type", variant_name(&node.entity_type)),
+            ("start_line", node.range.start.line.to_string()),
+            ("start_character", node.range.start.character.to_string()),
+            ("end_line", node.range.end.line.to_string()),
+            ("end_character", node.range.end.character.to_string()),
+        ];
+        if let Some(bytes) = node.bytes {
+            data.push(("start_byte", bytes.start_byte.to_string()));
+            data.push(("end_byte", bytes.end_byte.to_string()));
+        }
+        if let Some(extra) = &node.extra {
+            data.push(("extra", extra.to_string()));
+        }
+        for (key, value) in data {
+            let _ = writeln!(xml, "      <data key=\"{}\">{}</data>", key, escape(&value));
+        }
+        xml.push_str("    </node>\n");
+    }
+    for edge in &graph.edges {
+        for endpoint in [&edge.from, &edge.to] {
+            if known.insert(endpoint.as_str()) {
+                let _ = writeln!(
+                    xml,
+                    "    <node id=\"{}\"><data key=\"label\">{}</data></node>",
+                    escape(endpoint),
+                    escape(graph_id_name(endpoint))
+                );
+            }
+        }
+    }
+    for edge in &graph.edges {
+        let _ = writeln!(
+            xml,
+            "    <edge source=\"{}\" target=\"{}\"><data key=\"edge_type\">{}</data></edge>",
+            escape(&edge.from),
+            escape(&edge.to),
+            variant_name(&edge.edge_type)
+        );
+    }
+    xml.push_str("  </graph>\n</graphml>\n");
+    xml
+}
+
+/// Reads a graph written by [`graph_to_graphml`]. Keys are matched by `attr.name`, so files
+/// re-saved by other tools with renumbered key ids still load; nodes without an
+/// `entity_type` are treated as plain edge endpoints and dropped. `None` when the document
+/// is not well-formed enough to read.
+pub fn graph_from_graphml(xml: &str) -> Option<GraphData> {
+    let mut key_names = HashMap::new();
+    let mut graph = GraphData::default();
+    let mut element: Option<(String, HashMap<String, String>)> = None;
+    let mut fields: HashMap<String, String> = HashMap::new();
+    let mut data_key: Option<String> = None;
+    for token in xml_tokens(xml)? {
+        match token {
+            XmlToken::Open {
+                name,
+                attributes,
+                closed,
+            } => match name.as_str() {
+                "key" => {
+                    let id = attributes.get("id")?.clone();
+                    let name = attributes.get("attr.name").cloned().unwrap_or(id.clone());
+                    key_names.insert(id, name);
+                }
+                "node" | "edge" => {
+                    fields.clear();
+                    element = Some((name, attributes));
+                    if closed {
+                        finish_element(&mut graph, element.take()?, &fields)?;
+                    }
+                }
+                "data" if !closed => {
+                    let key = attributes.get("key")?;
+                    data_key = Some(key_names.get(key).unwrap_or(key).clone());
+                }
+                _ => {}
+            },
+            XmlToken::Text(text) => {
+                if let Some(key) = &data_key {
+                    fields.entry(key.clone()).or_default().push_str(&text);
+                }
+            }
+            XmlToken::Close(name) => match name.as_str() {
+                "data" => data_key = None,
+                "node" | "edge" => finish_element(&mut graph, element.take()?, &fields)?,
+                _ => {}
+            },
+        }
+    }
+    Some(graph)
+}
+
+fn finish_element(
+    graph: &mut GraphData,
+    (name, attributes): (String, HashMap<String, String>),
+    fields: &HashMap<String, String>,
+) -> Option<()> {
+    if name == "edge" {
+        graph.edges.push(GraphEdge {
+            from: attributes.get("source")?.clone(),
+            to: attributes.get("target")?.clone(),
+            edge_type: match fields.get("edge_type") {
+                Some(edge_type) => from_variant_name(edge_type)?,
+                None => GraphEdgeType::Use,
+            },
+        });
+        return Some(());
+    }
+    let Some(entity_type) = fields.get("entity_type") else {
+        return Some(());
+    };
+    let number = |key: &str| {
+        fields
+            .get(key)
+            .and_then(|value| value.trim().parse::<u32>().ok())
+    };
+    let bytes = |key: &str| {
+        fields
+            .get(key)
+            .and_then(|value| value.trim().parse::<usize>().ok())
+    };
+    graph.nodes.push(GraphNode {
+        id: attributes.get("id")?.clone(),
+        label: fields.get("label").cloned().unwrap_or_default(),
+        entity_type: from_variant_name(entity_type)?,
+        range: Range::new(
+            Position::new(number("start_line")?, number("start_character")?),
+            Position::new(number("end_line")?, number("end_character")?),
+        ),
+        extra: match fields.get("extra") {
+            Some(extra) => Some(serde_json::from_str(extra).ok()?),
+            None => None,
+        },
+        bytes: match (bytes("start_byte"), bytes("end_byte")) {
+            (Some(start_byte), Some(end_byte)) => Some(ByteRange {
+                start_byte,
+                end_byte,
+            }),
+            _ => None,
+        },
+    });
+    Some(())
+}
+
+/// Renders the entity graph in JSON Graph Format: one directed graph whose nodes carry the
+/// entity type, range, byte range and `extra` as metadata, and whose edges carry the edge type
+/// as their relation.
+pub fn graph_to_jgf(graph: &GraphData) -> String {
+    let mut known = HashSet::new();
+    let nodes: Vec<Value> = graph
+        .nodes
+        .iter()
+        .filter(|node| known.insert(node.id.as_str()))
+        .map(|node| {
+            let mut metadata = json!({
+                "entity_type": node.entity_type,
+                "range": node.range,
+            });
+            if let Some(bytes) = node.bytes {
+                metadata["bytes"] = json!(bytes);
+            }
+            if let Some(extra) = &node.extra {
+                metadata["extra"] = extra.clone();
+            }
+            json!({ "id": node.id, "label": node.label, "metadata": metadata })
+        })
+        .collect();
+    let edges: Vec<Value> = graph
+        .edges
+        .iter()
+        .map(|edge| {
+            json!({
+                "source": edge.from,
+                "target": edge.to,
+                "relation": edge.edge_type,
+                "directed": true,
+            })
+        })
+        .collect();
+    let document = json!({
+        "graph": {
+            "id": "go_analyzer",
+            "type": "go-analyzer entity graph",
+            "directed": true,
+            "nodes": nodes,
+            "edges": edges,
+        }
+    });
+    serde_json::to_string_pretty(&document).unwrap_or_default()
+}
+
+/// Reads a graph written by [`graph_to_jgf`]. Nodes may be given as a list or, as in JGF 2,
+/// as an object keyed by id; nodes without an entity type in their metadata are dropped.
+pub fn graph_from_jgf(text: &str) -> Option<GraphData> {
+    let document: Value = serde_json::from_str(text).ok()?;
+    let graph = document.get("graph")?;
+    let nodes: Vec<(String, &Value)> = match graph.get("nodes") {
+        Some(Value::Array(nodes)) => nodes
+            .iter()
+            .map(|node| Some((node.get("id")?.as_str()?.to_string(), node)))
+            .collect::<Option<_>>()?,
+        Some(Value::Object(nodes)) => nodes.iter().map(|(id, node)| (id.clone(), node)).collect(),
+        _ => Vec::new(),
+    };
+    let mut data = GraphData::default();
+    for (id, node) in nodes {
+        let metadata = node.get("metadata");
+        let Some(entity_type) = metadata.and_then(|metadata| metadata.get("entity_type")) else {
+            continue;
+        };
+        data.nodes.push(GraphNode {
+            id,
+            label: node
+                .get("label")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            entity_type: serde_json::from_value(entity_type.clone()).ok()?,
+            range: serde_json::from_value(metadata?.get("range")?.clone()).ok()?,
+            extra: metadata?.get("extra").cloned(),
+            bytes: match metadata?.get("bytes") {
+                Some(bytes) => Some(serde_json::from_value(bytes.clone()).ok()?),
+                None => None,
+            },
+        });
+    }
+    for edge in graph
+        .get("edges")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        data.edges.push(GraphEdge {
+            from: edge.get("source")?.as_str()?.to_string(),
+            to: edge.get("target")?.as_str()?.to_string(),
+            edge_type: match edge.get("relation") {
+                Some(relation) => serde_json::from_value(relation.clone()).ok()?,
+                None => GraphEdgeType::Use,
+            },
+        });
+    }
+    Some(data)
+}
+
+/// The serde name of a unit enum variant, such as `Send`.
+fn variant_name<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
+
+fn from_variant_name<T: for<'de> Deserialize<'de>>(name: &str) -> Option<T> {
+    serde_json::from_value(Value::String(name.trim().to_string())).ok()
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        let end = start + rest[start..].find(';')?;
+        let entity = &rest[start + 1..end];
+        let c = match entity {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code = match entity.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => entity.strip_prefix('#')?.parse().ok()?,
+                };
+                char::from_u32(code)?
+            }
+        };
+        unescaped.push(c);
+        rest = &rest[end + 1..];
+    }
+    unescaped.push_str(rest);
+    Some(unescaped)
+}
+
+enum XmlToken {
+    Open {
+        name: String,
+        attributes: HashMap<String, String>,
+        closed: bool,
+    },
+    Close(String),
+    Text(String),
+}
+
+/// Splits an XML document into tags and text, skipping the declaration, comments and
+/// doctype. Namespace prefixes are dropped from tag names. Enough XML for GraphML, not a
+/// validating parser.
+fn xml_tokens(xml: &str) -> Option<Vec<XmlToken>> {
+    let mut tokens = Vec::new();
+    let mut rest = xml;
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            tokens.push(XmlToken::Text(unescape(rest)?));
+            break;
+        };
+        if start > 0 {
+            tokens.push(XmlToken::Text(unescape(&rest[..start])?));
+        }
+        rest = &rest[start..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = &comment[comment.find("-->")? + 3..];
+            continue;
+        }
+        if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+            let end = cdata.find("]]>")?;
+            tokens.push(XmlToken::Text(cdata[..end].to_string()));
+            rest = &cdata[end + 3..];
+            continue;
+        }
+        let end = tag_end(rest)?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            tokens.push(XmlToken::Close(local_name(name.trim()).to_string()));
+            continue;
+        }
+        let closed = tag.ends_with('/');
+        let tag = tag.trim_end_matches('/');
+        let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+        tokens.push(XmlToken::Open {
+            name: local_name(&tag[..name_end]).to_string(),
+            attributes: attributes(&tag[name_end..])?,
+            closed,
+        });
+    }
+    Some(tokens)
+}
+
+/// Index of the `>` closing the tag at the start of `text`, skipping quoted values.
+fn tag_end(text: &str) -> Option<usize> {
+    let mut quote = None;
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if open == c => quote = None,
+            (None, '>') => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+fn attributes(mut text: &str) -> Option<HashMap<String, String>> {
+    let mut attributes = HashMap::new();
+    loop {
+        text = text.trim_start();
+        if text.is_empty() {
+            return Some(attributes);
+        }
+        let eq = text.find('=')?;
+        let name = text[..eq].trim().to_string();
+        let value = text[eq + 1..].trim_start();
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let end = value[1..].find(quote)? + 1;
+        attributes.insert(name, unescape(&value[1..end])?);
+        text = &value[end + 1..];
+    }
+}
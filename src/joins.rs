@@ -0,0 +1,298 @@
+use std::collections::HashSet;
+
+use tower_lsp::lsp_types::Range;
+use tree_sitter::{Node, Tree};
+
+use crate::analysis::{check_tree, enclosing_declaration, enclosing_literal, text};
+use crate::atomics::is_write;
+use crate::callgraph::{collect_kind, local_names};
+use crate::error::AnalysisResult;
+use crate::util::node_to_range;
+
+/// A goroutine that writes something the launching function hands back to its caller, started
+/// by a function that returns without waiting for it. The caller can read the value before the
+/// goroutine writes it, or while it does.
+#[derive(Debug, Clone)]
+pub struct UnjoinedGoroutine {
+    /// The `go` keyword.
+    pub range: Range,
+    pub function: String,
+    /// The variable, or the parameter whose target is written.
+    pub variable: String,
+    /// The write in the goroutine.
+    pub write: Range,
+    /// How the caller reaches it: a `return` of the variable or the parameter's declaration.
+    pub escape: Range,
+    pub via: Escape,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Escape {
+    /// The variable is returned or is a named result.
+    Returned,
+    /// The goroutine writes through a parameter or the receiver, which the caller holds.
+    Parameter,
+}
+
+impl UnjoinedGoroutine {
+    pub fn message(&self) -> String {
+        let how = match self.via {
+            Escape::Returned => format!("`{}` is returned", self.variable),
+            Escape::Parameter => format!("`{}` belongs to the caller", self.variable),
+        };
+        format!(
+            "Goroutine writes `{}` but `{}` returns without waiting for it ({}); the caller may see the value before or during the write. Join it with a WaitGroup, a channel receive or errgroup's Wait",
+            self.variable, self.function, how
+        )
+    }
+}
+
+/// `go func() { ... }()` statements of top-level functions and methods with no `Wait()`, channel
+/// receive, `range` over a channel or communicating `select` after them in the function (a
+/// deferred `Wait()` anywhere counts), whose literal writes a variable of the function that is
+/// returned, or writes through a parameter or receiver.
+pub fn find_unjoined_goroutines(tree: &Tree, code: &str) -> AnalysisResult<Vec<UnjoinedGoroutine>> {
+    check_tree(tree, code)?;
+    let mut go_statements = Vec::new();
+    collect_kind(tree.root_node(), "go_statement", &mut go_statements);
+    let mut findings = Vec::new();
+    for go_stmt in go_statements {
+        let Some(function) = own_function(go_stmt) else {
+            continue;
+        };
+        let Some(literal) = go_stmt
+            .named_child(0)
+            .and_then(|call| call.child_by_field_name("function"))
+            .filter(|function| function.kind() == "func_literal")
+        else {
+            continue;
+        };
+        if is_joined(function, go_stmt, code) {
+            continue;
+        }
+        let Some(name) = function.child_by_field_name("name") else {
+            continue;
+        };
+        let parameters = parameter_names(function, code);
+        let results = result_names(function, code);
+        let function_locals = local_names(function, code);
+        let goroutine_locals = local_names(literal, code);
+        let mut reported = HashSet::new();
+        for write in writes_in(literal) {
+            let Some(root) = root_identifier(write) else {
+                continue;
+            };
+            let variable = text(code, root);
+            if goroutine_locals.contains(variable)
+                || !function_locals.contains(variable)
+                || reported.contains(variable)
+            {
+                continue;
+            }
+            let through_target = write.kind() != "identifier";
+            let escape = if let Some(result) = results.iter().find(|(n, _)| *n == variable) {
+                Some((result.1, Escape::Returned))
+            } else if let Some(ret) = returned_after(function, go_stmt, variable, code) {
+                Some((ret, Escape::Returned))
+            } else {
+                parameters
+                    .iter()
+                    .find(|(n, pointer, _)| *n == variable && (through_target || *pointer))
+                    .map(|(_, _, declaration)| (*declaration, Escape::Parameter))
+            };
+            let Some((escape, via)) = escape else {
+                continue;
+            };
+            reported.insert(variable);
+            findings.push(UnjoinedGoroutine {
+                range: node_to_range(go_stmt.child(0).unwrap_or(go_stmt)),
+                function: text(code, name).to_string(),
+                variable: variable.to_string(),
+                write: node_to_range(write),
+                escape,
+                via,
+            });
+        }
+    }
+    Ok(findings)
+}
+
+/// The function or method declaration whose own code `node` is; `None` inside a function
+/// literal.
+pub(crate) fn own_function(node: Node) -> Option<Node> {
+    enclosing_declaration(node).filter(|_| enclosing_literal(node).is_none())
+}
+
+/// The function waits after `go_stmt`, outside the goroutine, or defers a `Wait()`.
+pub(crate) fn is_joined(function: Node, go_stmt: Node, code: &str) -> bool {
+    let outside_after = |node: Node| {
+        node.start_byte() >= go_stmt.end_byte() && own_function(node) == Some(function)
+    };
+    let mut calls = Vec::new();
+    collect_kind(function, "call_expression", &mut calls);
+    let waits = calls.into_iter().any(|call| {
+        let is_wait = call
+            .child_by_field_name("function")
+            .filter(|f| f.kind() == "selector_expression")
+            .and_then(|f| f.child_by_field_name("field"))
+            .is_some_and(|field| text(code, field) == "Wait");
+        let deferred = call
+            .parent()
+            .is_some_and(|parent| parent.kind() == "defer_statement");
+        is_wait && (deferred || outside_after(call))
+    });
+    if waits {
+        return true;
+    }
+    let mut receives = Vec::new();
+    collect_kind(function, "unary_expression", &mut receives);
+    if receives
+        .into_iter()
+        .any(|unary| text(code, unary).starts_with("<-") && outside_after(unary))
+    {
+        return true;
+    }
+    let channels = channel_names(function, code);
+    let mut ranges = Vec::new();
+    collect_kind(function, "range_clause", &mut ranges);
+    if ranges.into_iter().any(|clause| {
+        outside_after(clause)
+            && clause
+                .child_by_field_name("right")
+                .is_some_and(|right| channels.contains(text(code, right)))
+    }) {
+        return true;
+    }
+    let mut selects = Vec::new();
+    collect_kind(function, "select_statement", &mut selects);
+    selects.into_iter().any(|select| {
+        let mut cursor = select.walk();
+        let communicates = select
+            .named_children(&mut cursor)
+            .any(|case| case.kind() == "communication_case");
+        communicates && outside_after(select)
+    })
+}
+
+/// Variables and parameters of `function` holding channels.
+fn channel_names<'a>(function: Node, code: &'a str) -> HashSet<&'a str> {
+    let mut names = HashSet::new();
+    let mut declarations = Vec::new();
+    for kind in ["parameter_declaration", "var_spec", "short_var_declaration"] {
+        collect_kind(function, kind, &mut declarations);
+    }
+    for declaration in declarations {
+        let typed = declaration
+            .child_by_field_name("type")
+            .is_some_and(|typ| typ.kind() == "channel_type");
+        let made = declaration
+            .child_by_field_name("value")
+            .or_else(|| declaration.child_by_field_name("right"))
+            .is_some_and(|value| text(code, value).replace(' ', "").starts_with("make(chan"));
+        if !(typed || made) {
+            continue;
+        }
+        match declaration.child_by_field_name("left") {
+            Some(left) => {
+                let mut cursor = left.walk();
+                names.extend(left.named_children(&mut cursor).map(|n| text(code, n)));
+            }
+            None => {
+                let mut cursor = declaration.walk();
+                names.extend(
+                    declaration
+                        .children_by_field_name("name", &mut cursor)
+                        .map(|n| text(code, n)),
+                );
+            }
+        }
+    }
+    names
+}
+
+/// Identifiers, selectors and index expressions assigned or incremented in `literal`.
+pub(crate) fn writes_in(literal: Node) -> Vec<Node> {
+    let mut candidates = Vec::new();
+    for kind in ["identifier", "selector_expression", "index_expression"] {
+        collect_kind(literal, kind, &mut candidates);
+    }
+    candidates.sort_by_key(|node| node.start_byte());
+    candidates
+        .into_iter()
+        .filter(|node| is_write(*node))
+        .collect()
+}
+
+/// `x` in `x`, `x.f.g` or `x[i].f`.
+pub(crate) fn root_identifier(node: Node) -> Option<Node> {
+    let mut current = node;
+    loop {
+        match current.kind() {
+            "identifier" => return Some(current),
+            "selector_expression" | "index_expression" => {
+                current = current.child_by_field_name("operand")?;
+            }
+            "parenthesized_expression" => current = current.named_child(0)?,
+            "unary_expression" => current = current.child_by_field_name("operand")?,
+            _ => return None,
+        }
+    }
+}
+
+/// Parameter and receiver names, whether their type is a pointer, map or slice (so writes to the
+/// parameter itself are seen by the caller too), and their declarations.
+fn parameter_names<'a>(function: Node, code: &'a str) -> Vec<(&'a str, bool, Range)> {
+    let mut names = Vec::new();
+    for field in ["receiver", "parameters"] {
+        let Some(list) = function.child_by_field_name(field) else {
+            continue;
+        };
+        let mut cursor = list.walk();
+        for parameter in list.named_children(&mut cursor) {
+            let pointer = parameter.child_by_field_name("type").is_some_and(|typ| {
+                matches!(typ.kind(), "pointer_type" | "map_type" | "slice_type")
+            });
+            let mut name_cursor = parameter.walk();
+            for name in parameter.children_by_field_name("name", &mut name_cursor) {
+                names.push((text(code, name), pointer, node_to_range(name)));
+            }
+        }
+    }
+    names
+}
+
+/// Named results.
+fn result_names<'a>(function: Node, code: &'a str) -> Vec<(&'a str, Range)> {
+    let Some(result) = function
+        .child_by_field_name("result")
+        .filter(|result| result.kind() == "parameter_list")
+    else {
+        return Vec::new();
+    };
+    let mut names = Vec::new();
+    let mut cursor = result.walk();
+    for parameter in result.named_children(&mut cursor) {
+        let mut name_cursor = parameter.walk();
+        for name in parameter.children_by_field_name("name", &mut name_cursor) {
+            names.push((text(code, name), node_to_range(name)));
+        }
+    }
+    names
+}
+
+/// A `return` after `go_stmt` in the function itself whose results mention `variable`.
+fn returned_after(function: Node, go_stmt: Node, variable: &str, code: &str) -> Option<Range> {
+    let mut returns = Vec::new();
+    collect_kind(function, "return_statement", &mut returns);
+    returns.into_iter().find_map(|ret| {
+        if ret.start_byte() < go_stmt.end_byte() || own_function(ret) != Some(function) {
+            return None;
+        }
+        let mut identifiers = Vec::new();
+        collect_kind(ret, "identifier", &mut identifiers);
+        identifiers
+            .into_iter()
+            .find(|ident| text(code, *ident) == variable)
+            .map(node_to_range)
+    })
+}
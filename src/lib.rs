@@ -1,5 +1,55 @@
+pub mod aliases;
 pub mod analysis;
+pub mod anchor;
+pub mod atomics;
+pub mod backend;
+pub mod bootstrap;
+pub mod budget;
+pub mod callgraph;
+pub mod channels;
+pub mod cli;
+pub mod compile;
+pub mod detect;
+pub mod diskcache;
+pub mod doctor;
+pub mod documents;
+pub mod dot;
+pub mod error;
+pub mod findings;
+pub mod globals;
+pub mod gopls;
+pub mod goversion;
+pub mod graphdelta;
+pub mod graphpage;
+pub mod hover;
+pub mod interchange;
+pub mod joins;
+pub mod locks;
+pub mod logging;
+pub mod loopvar;
+pub mod maps;
+pub mod mermaid;
+pub mod panics;
+pub mod perf;
+pub mod protocol;
+pub mod query;
+pub mod queue;
+pub mod racedetector;
+pub mod record;
+pub mod rule_api;
+pub mod rules;
 pub mod semantic;
+pub mod sleeps;
+pub mod spawns;
+pub mod stats;
+pub mod summary;
+pub mod sync_config;
 mod test;
+pub mod timers;
+pub mod trace;
+pub mod transport;
 pub mod types;
 pub mod util;
+pub mod waitgroup;
+pub mod workers;
+pub mod workspace;
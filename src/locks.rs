@@ -0,0 +1,1480 @@
+use std::collections::{HashMap, HashSet};
+
+use tower_lsp::lsp_types::{Position, Range};
+use tree_sitter::{Node, Tree};
+
+use crate::analysis::{check_tree, text};
+use crate::callgraph::{
+    call_target, collect_callables, collect_kind, local_names, receiver_name, Callable,
+    MAX_CALL_DEPTH,
+};
+use crate::error::AnalysisResult;
+use crate::timers::enclosing_loop;
+use crate::util::{for_each_node, for_each_node_pruned, node_to_range};
+
+const LOCK_TYPES: &[&str] = &["sync.Mutex", "sync.RWMutex"];
+
+/// Types that must not be copied once used, as checked by `go vet`'s copylocks.
+const NO_COPY_TYPES: &[&str] = &["sync.Mutex", "sync.RWMutex", "sync.WaitGroup"];
+
+/// A function or method that returns a lock-holding struct by value, so every caller gets a copy of the lock.
+#[derive(Debug, Clone)]
+pub struct LockValueReturn {
+    pub function: String,
+    pub struct_name: String,
+    pub name_range: Range,
+    /// The by-value result type; the fix prefixes it with `*`.
+    pub result_type: Range,
+    /// Returned expressions in the result's position that the fix can prefix with `&`
+    /// (composite literals and plain identifiers).
+    pub fixable_returns: Vec<Range>,
+}
+
+/// Struct types declared in the file that hold a `sync.Mutex`/`sync.RWMutex` by value,
+/// directly or through another such struct.
+pub fn lock_holding_structs(tree: &Tree, code: &str) -> HashSet<String> {
+    structs_holding(tree, code, LOCK_TYPES)
+}
+
+fn structs_holding(tree: &Tree, code: &str, types: &[&str]) -> HashSet<String> {
+    let mut structs: Vec<(String, Vec<String>)> = Vec::new();
+    collect_struct_field_types(tree.root_node(), code, &mut structs);
+    let mut holders: HashSet<String> = HashSet::new();
+    loop {
+        let before = holders.len();
+        for (name, field_types) in &structs {
+            if holders.contains(name) {
+                continue;
+            }
+            if field_types
+                .iter()
+                .any(|t| types.contains(&t.as_str()) || holders.contains(t))
+            {
+                holders.insert(name.clone());
+            }
+        }
+        if holders.len() == before {
+            return holders;
+        }
+    }
+}
+
+fn collect_struct_field_types(node: Node, code: &str, out: &mut Vec<(String, Vec<String>)>) {
+    for_each_node(node, |node| {
+        if node.kind() != "type_spec" {
+            return;
+        }
+        if let (Some(name), Some(typ)) = (
+            node.child_by_field_name("name"),
+            node.child_by_field_name("type"),
+        ) {
+            if typ.kind() == "struct_type" {
+                let mut field_types = Vec::new();
+                collect_field_types(typ, code, &mut field_types);
+                out.push((text(code, name).to_string(), field_types));
+            }
+        }
+    });
+}
+
+fn collect_field_types(struct_type: Node, code: &str, out: &mut Vec<String>) {
+    let mut cursor = struct_type.walk();
+    for list in struct_type.named_children(&mut cursor) {
+        if list.kind() != "field_declaration_list" {
+            continue;
+        }
+        let mut list_cursor = list.walk();
+        for field in list.named_children(&mut list_cursor) {
+            if field.kind() != "field_declaration" {
+                continue;
+            }
+            if let Some(typ) = field.child_by_field_name("type") {
+                // Pointers share the lock instead of copying it.
+                if matches!(typ.kind(), "qualified_type" | "type_identifier") {
+                    out.push(text(code, typ).to_string());
+                }
+            }
+        }
+    }
+}
+
+pub fn find_lock_value_returns(tree: &Tree, code: &str) -> AnalysisResult<Vec<LockValueReturn>> {
+    check_tree(tree, code)?;
+    let holders = lock_holding_structs(tree, code);
+    let mut results = Vec::new();
+    if holders.is_empty() {
+        return Ok(results);
+    }
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    for func in root.named_children(&mut cursor) {
+        if !matches!(func.kind(), "function_declaration" | "method_declaration") {
+            continue;
+        }
+        let (Some(name), Some(result)) = (
+            func.child_by_field_name("name"),
+            func.child_by_field_name("result"),
+        ) else {
+            continue;
+        };
+        for (index, result_type) in result_types(result).into_iter().enumerate() {
+            let struct_name = text(code, result_type);
+            if result_type.kind() != "type_identifier" || !holders.contains(struct_name) {
+                continue;
+            }
+            let mut fixable_returns = Vec::new();
+            if let Some(body) = func.child_by_field_name("body") {
+                collect_fixable_returns(body, code, index, &mut fixable_returns);
+            }
+            results.push(LockValueReturn {
+                function: text(code, name).to_string(),
+                struct_name: struct_name.to_string(),
+                name_range: node_to_range(name),
+                result_type: node_to_range(result_type),
+                fixable_returns,
+            });
+        }
+    }
+    Ok(results)
+}
+
+fn result_types(result: Node) -> Vec<Node> {
+    if result.kind() != "parameter_list" {
+        return vec![result];
+    }
+    let mut types = Vec::new();
+    let mut cursor = result.walk();
+    for param in result.named_children(&mut cursor) {
+        if param.kind() != "parameter_declaration" {
+            continue;
+        }
+        if let Some(typ) = param.child_by_field_name("type") {
+            // Named results `(a, b T)` occupy one position per name.
+            let mut name_cursor = param.walk();
+            let names = param
+                .children_by_field_name("name", &mut name_cursor)
+                .count();
+            for _ in 0..names.max(1) {
+                types.push(typ);
+            }
+        }
+    }
+    types
+}
+
+fn collect_fixable_returns(node: Node, code: &str, index: usize, out: &mut Vec<Range>) {
+    for_each_node_pruned(node, |node| {
+        if node.kind() == "func_literal" {
+            return false;
+        }
+        if node.kind() != "return_statement" {
+            return true;
+        }
+        let values = node
+            .named_child(0)
+            .filter(|list| list.kind() == "expression_list");
+        if let Some(expr) = values.and_then(|list| list.named_child(index)) {
+            let fixable = match expr.kind() {
+                "composite_literal" => true,
+                "identifier" => text(code, expr) != "nil",
+                _ => false,
+            };
+            if fixable {
+                out.push(node_to_range(expr));
+            }
+        }
+        false
+    });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockCopyKind {
+    /// A method declared on the struct value rather than a pointer.
+    ValueReceiver,
+    Parameter,
+    /// The right-hand side of an assignment or declaration.
+    Assignment,
+    /// An argument of a call.
+    Argument,
+    /// The value variable of a `range` over a slice, array or map of such structs.
+    RangeValue,
+}
+
+/// A `sync.Mutex`, `sync.RWMutex` or `sync.WaitGroup`, or a struct holding one by value, copied
+/// by value. The copy is a separate lock or counter that shares no state with the original.
+#[derive(Debug, Clone)]
+pub struct LockCopy {
+    pub kind: LockCopyKind,
+    pub range: Range,
+    /// The copied variable, parameter or method.
+    pub subject: String,
+    pub type_name: String,
+}
+
+impl LockCopy {
+    pub fn message(&self) -> String {
+        if self.type_name == "sync.WaitGroup" && self.kind != LockCopyKind::RangeValue {
+            let what = match self.kind {
+                LockCopyKind::Parameter => format!("Parameter `{}` receives", self.subject),
+                LockCopyKind::Assignment => format!("Assigning `{}` makes", self.subject),
+                _ => format!("Passing `{}` makes", self.subject),
+            };
+            return format!(
+                "{} a copy of the `sync.WaitGroup`; `Done()` on the copy never releases `Wait()` on the original, which then hangs. Use `*sync.WaitGroup`",
+                what
+            );
+        }
+        match self.kind {
+            LockCopyKind::ValueReceiver => format!(
+                "`{}` has a value receiver of type `{}`, which contains a lock; every call copies it",
+                self.subject, self.type_name
+            ),
+            LockCopyKind::Parameter => format!(
+                "Parameter `{}` passes `{}` by value, copying its lock; use `*{}`",
+                self.subject, self.type_name, self.type_name
+            ),
+            LockCopyKind::Assignment => format!(
+                "Assignment copies `{}` of type `{}`, which contains a lock",
+                self.subject, self.type_name
+            ),
+            LockCopyKind::Argument => format!(
+                "Call passes `{}` of type `{}` by value, copying its lock",
+                self.subject, self.type_name
+            ),
+            LockCopyKind::RangeValue => format!(
+                "Range variable `{}` copies each `{}` element, including its lock; range over indices or pointers",
+                self.subject, self.type_name
+            ),
+        }
+    }
+}
+
+/// Value receivers, parameters, assignments, call arguments and range variables copying a lock
+/// value. Variables are typed from their declarations in the file: package-level `var`s and the
+/// parameters, `var`s and composite-literal `:=` of the enclosing function.
+pub fn find_lock_copies(tree: &Tree, code: &str) -> AnalysisResult<Vec<LockCopy>> {
+    check_tree(tree, code)?;
+    let holders = structs_holding(tree, code, NO_COPY_TYPES);
+    let no_copy = |typ: Node| {
+        matches!(typ.kind(), "type_identifier" | "qualified_type")
+            && (NO_COPY_TYPES.contains(&text(code, typ)) || holders.contains(text(code, typ)))
+    };
+    let root = tree.root_node();
+    let mut globals: HashMap<&str, Node> = HashMap::new();
+    let mut cursor = root.walk();
+    for decl in root.named_children(&mut cursor) {
+        if decl.kind() == "var_declaration" {
+            collect_declared_types(decl, code, &mut globals);
+        }
+    }
+    let mut copies = Vec::new();
+    let mut cursor = root.walk();
+    for func in root.named_children(&mut cursor) {
+        if !matches!(func.kind(), "function_declaration" | "method_declaration") {
+            continue;
+        }
+        let mut types = globals.clone();
+        let mut params = Vec::new();
+        if let Some(receiver) = func.child_by_field_name("receiver") {
+            collect_kind(receiver, "parameter_declaration", &mut params);
+            for param in &params {
+                let Some(typ) = param.child_by_field_name("type").filter(|t| no_copy(*t)) else {
+                    continue;
+                };
+                let method = func
+                    .child_by_field_name("name")
+                    .map(|name| text(code, name))
+                    .unwrap_or_default();
+                copies.push(LockCopy {
+                    kind: LockCopyKind::ValueReceiver,
+                    range: node_to_range(typ),
+                    subject: format!("{}.{}", text(code, typ), method),
+                    type_name: text(code, typ).to_string(),
+                });
+            }
+            params.clear();
+        }
+        if let Some(parameters) = func.child_by_field_name("parameters") {
+            collect_kind(parameters, "parameter_declaration", &mut params);
+            for param in &params {
+                let Some(typ) = param.child_by_field_name("type").filter(|t| no_copy(*t)) else {
+                    continue;
+                };
+                let mut name_cursor = param.walk();
+                for name in param.children_by_field_name("name", &mut name_cursor) {
+                    copies.push(LockCopy {
+                        kind: LockCopyKind::Parameter,
+                        range: node_to_range(typ),
+                        subject: text(code, name).to_string(),
+                        type_name: text(code, typ).to_string(),
+                    });
+                }
+            }
+        }
+        collect_declared_types(func, code, &mut types);
+        let Some(body) = func.child_by_field_name("body") else {
+            continue;
+        };
+        // Parameters of function literals, such as `go func(wg sync.WaitGroup) { ... }(wg)`.
+        let mut literals = Vec::new();
+        collect_kind(body, "func_literal", &mut literals);
+        for literal in literals {
+            let mut literal_params = Vec::new();
+            if let Some(parameters) = literal.child_by_field_name("parameters") {
+                collect_kind(parameters, "parameter_declaration", &mut literal_params);
+            }
+            for param in literal_params {
+                let Some(typ) = param.child_by_field_name("type").filter(|t| no_copy(*t)) else {
+                    continue;
+                };
+                let mut name_cursor = param.walk();
+                for name in param.children_by_field_name("name", &mut name_cursor) {
+                    copies.push(LockCopy {
+                        kind: LockCopyKind::Parameter,
+                        range: node_to_range(typ),
+                        subject: text(code, name).to_string(),
+                        type_name: text(code, typ).to_string(),
+                    });
+                }
+            }
+        }
+        let value_type = |expr: Node| -> Option<Node> {
+            if expr.kind() != "identifier" {
+                return None;
+            }
+            types
+                .get(text(code, expr))
+                .copied()
+                .filter(|typ| no_copy(*typ))
+        };
+        let mut sources = Vec::new();
+        for kind in ["assignment_statement", "short_var_declaration", "var_spec"] {
+            collect_kind(body, kind, &mut sources);
+        }
+        for statement in sources {
+            let values = statement
+                .child_by_field_name("right")
+                .or_else(|| statement.child_by_field_name("value"));
+            let Some(values) = values else {
+                continue;
+            };
+            let mut value_cursor = values.walk();
+            for value in values.named_children(&mut value_cursor) {
+                if let Some(typ) = value_type(value) {
+                    copies.push(LockCopy {
+                        kind: LockCopyKind::Assignment,
+                        range: node_to_range(value),
+                        subject: text(code, value).to_string(),
+                        type_name: text(code, typ).to_string(),
+                    });
+                }
+            }
+        }
+        let mut calls = Vec::new();
+        collect_kind(body, "call_expression", &mut calls);
+        for call in calls {
+            let Some(arguments) = call.child_by_field_name("arguments") else {
+                continue;
+            };
+            let mut argument_cursor = arguments.walk();
+            for argument in arguments.named_children(&mut argument_cursor) {
+                if let Some(typ) = value_type(argument) {
+                    copies.push(LockCopy {
+                        kind: LockCopyKind::Argument,
+                        range: node_to_range(argument),
+                        subject: text(code, argument).to_string(),
+                        type_name: text(code, typ).to_string(),
+                    });
+                }
+            }
+        }
+        let mut ranges = Vec::new();
+        collect_kind(body, "range_clause", &mut ranges);
+        for clause in ranges {
+            let value = clause
+                .child_by_field_name("left")
+                .and_then(|left| left.named_child(1))
+                .filter(|value| text(code, *value) != "_");
+            let collection =
+                clause
+                    .child_by_field_name("right")
+                    .and_then(|right| match right.kind() {
+                        "identifier" => types.get(text(code, right)).copied(),
+                        "composite_literal" => right.child_by_field_name("type"),
+                        _ => None,
+                    });
+            let element = collection.and_then(|typ| match typ.kind() {
+                "slice_type" | "array_type" => typ.child_by_field_name("element"),
+                "map_type" => typ.child_by_field_name("value"),
+                _ => None,
+            });
+            if let (Some(value), Some(element)) = (value, element.filter(|e| no_copy(*e))) {
+                copies.push(LockCopy {
+                    kind: LockCopyKind::RangeValue,
+                    range: node_to_range(value),
+                    subject: text(code, value).to_string(),
+                    type_name: text(code, element).to_string(),
+                });
+            }
+        }
+    }
+    copies.sort_by_key(|copy| (copy.range.start.line, copy.range.start.character));
+    Ok(copies)
+}
+
+/// Declared types of variables and parameters under `node`: `var x T`, `x T` parameters and
+/// `x := T{...}`.
+fn collect_declared_types<'a>(node: Node<'a>, code: &'a str, out: &mut HashMap<&'a str, Node<'a>>) {
+    let mut declarations = Vec::new();
+    for kind in ["var_spec", "parameter_declaration"] {
+        collect_kind(node, kind, &mut declarations);
+    }
+    for decl in declarations {
+        if let Some(typ) = decl.child_by_field_name("type") {
+            let mut cursor = decl.walk();
+            for name in decl.children_by_field_name("name", &mut cursor) {
+                out.insert(text(code, name), typ);
+            }
+        }
+    }
+    let mut short_decls = Vec::new();
+    collect_kind(node, "short_var_declaration", &mut short_decls);
+    for decl in short_decls {
+        let (Some(left), Some(right)) = (
+            decl.child_by_field_name("left"),
+            decl.child_by_field_name("right"),
+        ) else {
+            continue;
+        };
+        for i in 0..left.named_child_count() {
+            let (Some(name), Some(value)) = (left.named_child(i), right.named_child(i)) else {
+                continue;
+            };
+            if value.kind() == "composite_literal" {
+                if let Some(typ) = value.child_by_field_name("type") {
+                    out.insert(text(code, name), typ);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnlockIssueKind {
+    /// The function never unlocks the mutex it locks.
+    Missing,
+    /// A `return` or `panic` between the lock and its unlock leaves the mutex locked.
+    EarlyExit,
+}
+
+/// A `Lock()`/`RLock()` whose unlock does not run on every path out of the function.
+#[derive(Debug, Clone)]
+pub struct UnlockIssue {
+    pub kind: UnlockIssueKind,
+    /// The locked expression, e.g. `s.mu`.
+    pub mutex: String,
+    /// `Unlock` or `RUnlock`.
+    pub unlock_method: &'static str,
+    pub function: String,
+    pub lock: Range,
+    /// The `return`/`panic` leaving with the mutex held, for [`UnlockIssueKind::EarlyExit`].
+    pub exit: Option<Range>,
+    pub fix: Option<DeferUnlockFix>,
+}
+
+/// Replaces the manual unlocks following a lock with `defer <mutex>.Unlock()` after it.
+#[derive(Debug, Clone)]
+pub struct DeferUnlockFix {
+    pub insert_at: Position,
+    pub insert: String,
+    pub removals: Vec<Range>,
+}
+
+impl UnlockIssue {
+    pub fn code(&self) -> &'static str {
+        match self.kind {
+            UnlockIssueKind::Missing => "lock-missing-unlock",
+            UnlockIssueKind::EarlyExit => "lock-unlock-not-deferred",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self.kind {
+            UnlockIssueKind::Missing => format!(
+                "`{}` is locked but never unlocked in `{}`: it stays locked after the function returns",
+                self.mutex, self.function
+            ),
+            UnlockIssueKind::EarlyExit => format!(
+                "`{}` stays locked on an early exit from `{}`; use `defer {}.{}()` right after locking",
+                self.mutex, self.function, self.mutex, self.unlock_method
+            ),
+        }
+    }
+}
+
+/// Checks every `Lock()`/`RLock()` statement against the unlocks of the same mutex in its
+/// function. Functions whose name mentions "lock" are assumed to hand the mutex over on purpose.
+pub fn find_unlock_issues(tree: &Tree, code: &str) -> AnalysisResult<Vec<UnlockIssue>> {
+    check_tree(tree, code)?;
+    let mut functions = Vec::new();
+    collect_functions(tree.root_node(), &mut functions);
+    let mut issues = Vec::new();
+    for function in functions {
+        let name = match function.child_by_field_name("name") {
+            Some(name) => text(code, name).to_string(),
+            None => "func literal".to_string(),
+        };
+        if name.to_lowercase().contains("lock") {
+            continue;
+        }
+        let Some(body) = function.child_by_field_name("body") else {
+            continue;
+        };
+        let mut events = Vec::new();
+        collect_lock_events(body, code, &mut events);
+        for (index, event) in events.iter().enumerate() {
+            let LockEvent::Lock {
+                mutex,
+                unlock_method,
+            } = &event.kind
+            else {
+                continue;
+            };
+            let later = &events[index + 1..];
+            let next_lock = later
+                .iter()
+                .position(|e| matches!(&e.kind, LockEvent::Lock { mutex: m, .. } if m == mutex))
+                .unwrap_or(later.len());
+            let deferred = later.iter().any(|e| {
+                matches!(&e.kind, LockEvent::Unlock { mutex: m, method, deferred: true }
+                    if m == mutex && method == unlock_method)
+            });
+            if deferred {
+                continue;
+            }
+            let unlocks: Vec<Node> = later[..next_lock]
+                .iter()
+                .filter(|e| {
+                    matches!(&e.kind, LockEvent::Unlock { mutex: m, method, deferred: false }
+                        if m == mutex && method == unlock_method)
+                })
+                .map(|e| e.statement)
+                .collect();
+            let lock = event.statement;
+            let mut issue = UnlockIssue {
+                kind: UnlockIssueKind::Missing,
+                mutex: mutex.clone(),
+                unlock_method,
+                function: name.clone(),
+                lock: node_to_range(lock),
+                exit: None,
+                fix: None,
+            };
+            if unlocks.is_empty() {
+                issues.push(issue);
+                continue;
+            }
+            // The unlock every fall-through path reaches: one in the lock's block or an
+            // enclosing block. Unlocks only inside branches are too ambiguous to judge.
+            let Some(covering) = unlocks
+                .iter()
+                .find(|unlock| encloses(unlock.parent(), lock))
+            else {
+                continue;
+            };
+            let mut exits = Vec::new();
+            collect_exits(body, code, &mut exits);
+            let unhandled = exits.into_iter().find(|exit| {
+                exit.start_byte() > lock.end_byte()
+                    && exit.start_byte() < covering.start_byte()
+                    && !unlocks.iter().any(|unlock| {
+                        unlock.start_byte() < exit.start_byte() && encloses(unlock.parent(), *exit)
+                    })
+            });
+            let Some(exit) = unhandled else {
+                continue;
+            };
+            issue.kind = UnlockIssueKind::EarlyExit;
+            issue.exit = Some(node_to_range(exit));
+            // A deferred unlock would hold the mutex across a later re-lock, or pile up in a loop.
+            if next_lock == later.len() && !inside_loop(lock, body) {
+                issue.fix = Some(defer_fix(code, lock, mutex, unlock_method, &unlocks));
+            }
+            issues.push(issue);
+        }
+    }
+    Ok(issues)
+}
+
+#[derive(Debug)]
+enum LockEvent {
+    Lock {
+        mutex: String,
+        unlock_method: &'static str,
+    },
+    Unlock {
+        mutex: String,
+        method: &'static str,
+        deferred: bool,
+    },
+}
+
+#[derive(Debug)]
+struct LockEventAt<'a> {
+    kind: LockEvent,
+    /// The `expression_statement` or `defer_statement` holding the call.
+    statement: Node<'a>,
+}
+
+fn collect_functions<'a>(node: Node<'a>, out: &mut Vec<Node<'a>>) {
+    for_each_node(node, |node| {
+        if matches!(
+            node.kind(),
+            "function_declaration" | "method_declaration" | "func_literal"
+        ) {
+            out.push(node);
+        }
+    });
+}
+
+/// Lock and unlock statements of the function in source order, skipping nested function
+/// literals (except deferred ones, which run when the function returns).
+fn collect_lock_events<'a>(node: Node<'a>, code: &str, out: &mut Vec<LockEventAt<'a>>) {
+    for_each_node_pruned(node, |node| match node.kind() {
+        "func_literal" => false,
+        "expression_statement" => {
+            if let Some((mutex, method)) = node.named_child(0).and_then(|c| mutex_call(c, code)) {
+                let kind = match method {
+                    "Lock" => LockEvent::Lock {
+                        mutex,
+                        unlock_method: "Unlock",
+                    },
+                    "RLock" => LockEvent::Lock {
+                        mutex,
+                        unlock_method: "RUnlock",
+                    },
+                    _ => LockEvent::Unlock {
+                        mutex,
+                        method,
+                        deferred: false,
+                    },
+                };
+                out.push(LockEventAt {
+                    kind,
+                    statement: node,
+                });
+            }
+            false
+        }
+        "defer_statement" => {
+            let mut calls = Vec::new();
+            collect_kind(node, "call_expression", &mut calls);
+            for call in calls {
+                if let Some((mutex, method @ ("Unlock" | "RUnlock"))) = mutex_call(call, code) {
+                    out.push(LockEventAt {
+                        kind: LockEvent::Unlock {
+                            mutex,
+                            method,
+                            deferred: true,
+                        },
+                        statement: node,
+                    });
+                }
+            }
+            false
+        }
+        _ => true,
+    });
+}
+
+/// `x.Lock()`, `x.RLock()`, `x.Unlock()` or `x.RUnlock()` as (x, method).
+fn mutex_call(call: Node, code: &str) -> Option<(String, &'static str)> {
+    if call.kind() != "call_expression" {
+        return None;
+    }
+    let function = call.child_by_field_name("function")?;
+    if function.kind() != "selector_expression" {
+        return None;
+    }
+    let method = match text(code, function.child_by_field_name("field")?) {
+        "Lock" => "Lock",
+        "RLock" => "RLock",
+        "Unlock" => "Unlock",
+        "RUnlock" => "RUnlock",
+        _ => return None,
+    };
+    let operand = function.child_by_field_name("operand")?;
+    Some((text(code, operand).trim().to_string(), method))
+}
+
+/// `return` statements and `panic(...)` calls of the function, outside nested function literals.
+fn collect_exits<'a>(node: Node<'a>, code: &str, out: &mut Vec<Node<'a>>) {
+    for_each_node_pruned(node, |node| match node.kind() {
+        "func_literal" => false,
+        "return_statement" => {
+            out.push(node);
+            false
+        }
+        "call_expression"
+            if node
+                .child_by_field_name("function")
+                .is_some_and(|f| text(code, f) == "panic") =>
+        {
+            out.push(node);
+            false
+        }
+        _ => true,
+    });
+}
+
+/// Whether `block` contains `node`.
+fn encloses(block: Option<Node>, node: Node) -> bool {
+    block.is_some_and(|block| {
+        block.start_byte() <= node.start_byte() && node.end_byte() <= block.end_byte()
+    })
+}
+
+/// `node` runs in a `for` loop inside `scope`, in the same function.
+pub(crate) fn inside_loop(node: Node, scope: Node) -> bool {
+    enclosing_loop(node).is_some_and(|found| encloses(Some(scope), found))
+}
+
+fn defer_fix(
+    code: &str,
+    lock: Node,
+    mutex: &str,
+    unlock_method: &str,
+    unlocks: &[Node],
+) -> DeferUnlockFix {
+    let line_start = code[..lock.start_byte()].rfind('\n').map_or(0, |i| i + 1);
+    let indent: String = code[line_start..]
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .collect();
+    let removals = unlocks
+        .iter()
+        .map(|unlock| {
+            let range = node_to_range(*unlock);
+            let start = code[..unlock.start_byte()].rfind('\n').map_or(0, |i| i + 1);
+            let end = code[unlock.end_byte()..]
+                .find('\n')
+                .map_or(code.len(), |i| unlock.end_byte() + i);
+            let alone = code[start..unlock.start_byte()].trim().is_empty()
+                && code[unlock.end_byte()..end].trim().is_empty();
+            if alone && end < code.len() {
+                // Drop the whole line, including its newline.
+                Range::new(
+                    Position::new(range.start.line, 0),
+                    Position::new(range.start.line + 1, 0),
+                )
+            } else {
+                range
+            }
+        })
+        .collect();
+    DeferUnlockFix {
+        insert_at: node_to_range(lock).end,
+        insert: format!("\n{}defer {}.{}()", indent, mutex, unlock_method),
+        removals,
+    }
+}
+
+/// A `Lock()` (or `RLock()`) on a mutex the same goroutine already holds. Go mutexes are not
+/// reentrant, so the second lock waits for an unlock that never comes.
+#[derive(Debug, Clone)]
+pub struct DoubleLock {
+    pub mutex: String,
+    pub function: String,
+    /// The lock still held.
+    pub first: Range,
+    /// The second lock, or the call leading to it.
+    pub second: Range,
+    /// When the second lock is in a called function: its name and the lock inside it.
+    pub callee: Option<(String, Range)>,
+}
+
+impl DoubleLock {
+    pub fn message(&self) -> String {
+        match &self.callee {
+            Some((callee, _)) => format!(
+                "`{}` locks `{}` again while it is held here; Go mutexes are not reentrant, so this deadlocks",
+                callee, self.mutex
+            ),
+            None => format!(
+                "`{}` is locked again while already held in `{}`; Go mutexes are not reentrant, so this deadlocks",
+                self.mutex, self.function
+            ),
+        }
+    }
+}
+
+/// Finds locks reached while the same mutex is held: later in the same block chain of the
+/// function, or inside a function declared in the file that is called there (methods on the
+/// same receiver and functions locking a package-level mutex). Two read locks are allowed.
+pub fn find_double_locks(tree: &Tree, code: &str) -> AnalysisResult<Vec<DoubleLock>> {
+    check_tree(tree, code)?;
+    let root = tree.root_node();
+    let callables = collect_callables(root, code);
+    let mut functions = Vec::new();
+    collect_functions(root, &mut functions);
+    let mut findings = Vec::new();
+    for function in functions {
+        let name = match function.child_by_field_name("name") {
+            Some(name) => text(code, name).to_string(),
+            None => "func literal".to_string(),
+        };
+        let Some(body) = function.child_by_field_name("body") else {
+            continue;
+        };
+        let mut events = Vec::new();
+        collect_lock_events(body, code, &mut events);
+        for (index, event) in events.iter().enumerate() {
+            let LockEvent::Lock {
+                mutex,
+                unlock_method,
+            } = &event.kind
+            else {
+                continue;
+            };
+            let read = *unlock_method == "RUnlock";
+            let first = event.statement;
+            let Some(block) = first.parent() else {
+                continue;
+            };
+            // Held until the next unlock of the mutex on any path, or the end of the block.
+            let released = events[index + 1..]
+                .iter()
+                .find(|e| {
+                    matches!(&e.kind, LockEvent::Unlock { mutex: m, deferred: false, .. } if m == mutex)
+                })
+                .map_or(block.end_byte(), |e| e.statement.start_byte());
+            let held = |node: Node| {
+                node.start_byte() > first.end_byte()
+                    && node.start_byte() < released
+                    && encloses(Some(block), node)
+            };
+            let relock = events[index + 1..].iter().find(|e| {
+                held(e.statement)
+                    && matches!(&e.kind, LockEvent::Lock { mutex: m, unlock_method: u }
+                        if m == mutex && !(read && *u == "RUnlock"))
+            });
+            if let Some(relock) = relock {
+                findings.push(DoubleLock {
+                    mutex: mutex.clone(),
+                    function: name.clone(),
+                    first: node_to_range(first),
+                    second: node_to_range(relock.statement),
+                    callee: None,
+                });
+                continue;
+            }
+            let mut calls = Vec::new();
+            collect_synchronous_calls(body, &mut calls);
+            for call in calls.into_iter().filter(|call| held(*call)) {
+                let mut visited = HashSet::new();
+                if let Some((callee, lock)) = lock_in_callee(
+                    function,
+                    call,
+                    code,
+                    mutex,
+                    read,
+                    &callables,
+                    0,
+                    &mut visited,
+                ) {
+                    findings.push(DoubleLock {
+                        mutex: mutex.clone(),
+                        function: name.clone(),
+                        first: node_to_range(first),
+                        second: node_to_range(call),
+                        callee: Some((callee, node_to_range(lock))),
+                    });
+                    break;
+                }
+            }
+        }
+    }
+    Ok(findings)
+}
+
+/// Calls that run on the current goroutine before the function returns: not in `go` or
+/// `defer` statements, nor in nested func literals.
+fn collect_synchronous_calls<'a>(node: Node<'a>, out: &mut Vec<Node<'a>>) {
+    for_each_node_pruned(node, |node| {
+        if matches!(
+            node.kind(),
+            "func_literal" | "go_statement" | "defer_statement"
+        ) {
+            return false;
+        }
+        if node.kind() == "call_expression" {
+            out.push(node);
+        }
+        true
+    });
+}
+
+/// The lock of `mutex` (in the caller's terms) taken by the function `call` invokes, directly or
+/// through further calls, with the name of the function taking it.
+#[allow(clippy::too_many_arguments)]
+fn lock_in_callee<'a>(
+    caller: Node,
+    call: Node,
+    code: &str,
+    mutex: &str,
+    read: bool,
+    callables: &[Callable<'a>],
+    depth: usize,
+    visited: &mut HashSet<usize>,
+) -> Option<(String, Node<'a>)> {
+    if depth >= MAX_CALL_DEPTH {
+        return None;
+    }
+    let target = call_target(call, code, callables)?;
+    if !visited.insert(target.node.id()) {
+        return None;
+    }
+    // `s.mu` held by the caller is `r.mu` inside a method called as `s.m()` with receiver `r`.
+    let through_receiver = if target.method {
+        let operand = call
+            .child_by_field_name("function")?
+            .child_by_field_name("operand")?;
+        mutex
+            .strip_prefix(text(code, operand))
+            .and_then(|rest| rest.strip_prefix('.'))
+            .zip(receiver_name(target.node, code))
+            .map(|(field, receiver)| format!("{}.{}", receiver, field))
+    } else {
+        None
+    };
+    let callee_mutex = match through_receiver {
+        Some(callee_mutex) => callee_mutex,
+        None => {
+            // Otherwise only a package-level mutex is the same one in both functions.
+            let root = mutex.split('.').next().unwrap_or(mutex);
+            if local_names(caller, code).contains(root)
+                || local_names(target.node, code).contains(root)
+            {
+                return None;
+            }
+            mutex.to_string()
+        }
+    };
+    let body = target.node.child_by_field_name("body")?;
+    let mut events = Vec::new();
+    collect_lock_events(body, code, &mut events);
+    let lock = events.iter().find(|e| {
+        matches!(&e.kind, LockEvent::Lock { mutex: m, unlock_method: u }
+            if *m == callee_mutex && !(read && *u == "RUnlock"))
+    });
+    if let Some(lock) = lock {
+        return Some((target.name.clone(), lock.statement));
+    }
+    let mut calls = Vec::new();
+    collect_synchronous_calls(body, &mut calls);
+    calls.into_iter().find_map(|inner| {
+        lock_in_callee(
+            target.node,
+            inner,
+            code,
+            &callee_mutex,
+            read,
+            callables,
+            depth + 1,
+            visited,
+        )
+    })
+}
+
+/// Longest lock-order cycle searched for.
+const MAX_CYCLE_LENGTH: usize = 4;
+
+/// Mutex `to` acquired while `from` is held.
+#[derive(Debug, Clone)]
+pub struct LockOrderEdge {
+    pub from: String,
+    pub to: String,
+    pub function: String,
+    /// The lock of `from` still held.
+    pub held: Range,
+    /// The lock of `to`, or the call leading to it.
+    pub acquired: Range,
+    /// When `to` is locked in a called function: its name and the lock inside it.
+    pub callee: Option<(String, Range)>,
+}
+
+/// Mutexes acquired in conflicting orders: each edge's `to` is the next edge's `from`, and the
+/// last edge closes the cycle. Goroutines running these sequences concurrently can each hold one
+/// mutex while waiting for the next.
+#[derive(Debug, Clone)]
+pub struct LockOrderCycle {
+    pub edges: Vec<LockOrderEdge>,
+}
+
+impl LockOrderCycle {
+    pub fn message(&self) -> String {
+        let sequences: Vec<String> = self
+            .edges
+            .iter()
+            .map(|edge| {
+                format!(
+                    "`{}` locks `{}` then `{}`",
+                    edge.function, edge.from, edge.to
+                )
+            })
+            .collect();
+        format!(
+            "Lock order inversion: {}; goroutines running these concurrently can deadlock",
+            sequences.join(", ")
+        )
+    }
+}
+
+/// Builds the graph of mutexes acquired while another is held (directly, or in a function
+/// declared in the file called while holding it) and reports its cycles. Mutexes are identified
+/// across functions by receiver type (`s.mu` in a method on `*Store` is `Store.mu`) or as
+/// package-level variables; local mutexes are only compared within their declaring function and
+/// the func literals inside it.
+pub fn find_lock_order_cycles(tree: &Tree, code: &str) -> AnalysisResult<Vec<LockOrderCycle>> {
+    check_tree(tree, code)?;
+    let root = tree.root_node();
+    let callables = collect_callables(root, code);
+    let mut functions = Vec::new();
+    collect_functions(root, &mut functions);
+    // Keyed by (from, to): the first acquisition seen in source order.
+    let mut edges: Vec<(String, String, LockOrderEdge)> = Vec::new();
+    for function in functions {
+        let name = match function.child_by_field_name("name") {
+            Some(name) => text(code, name).to_string(),
+            None => "func literal".to_string(),
+        };
+        let Some(body) = function.child_by_field_name("body") else {
+            continue;
+        };
+        let mut events = Vec::new();
+        collect_lock_events(body, code, &mut events);
+        let mut calls = Vec::new();
+        collect_synchronous_calls(body, &mut calls);
+        for (index, event) in events.iter().enumerate() {
+            let LockEvent::Lock { mutex, .. } = &event.kind else {
+                continue;
+            };
+            let from = mutex_key(function, mutex, code);
+            let first = event.statement;
+            let Some(block) = first.parent() else {
+                continue;
+            };
+            let released = events[index + 1..]
+                .iter()
+                .find(|e| {
+                    matches!(&e.kind, LockEvent::Unlock { mutex: m, deferred: false, .. } if m == mutex)
+                })
+                .map_or(block.end_byte(), |e| e.statement.start_byte());
+            let held = |node: Node| {
+                node.start_byte() > first.end_byte()
+                    && node.start_byte() < released
+                    && encloses(Some(block), node)
+            };
+            let mut add = |to: String, edge: LockOrderEdge| {
+                if to != from && !edges.iter().any(|(f, t, _)| *f == from && *t == to) {
+                    edges.push((from.clone(), to, edge));
+                }
+            };
+            for next in events[index + 1..].iter().filter(|e| held(e.statement)) {
+                let LockEvent::Lock {
+                    mutex: next_mutex, ..
+                } = &next.kind
+                else {
+                    continue;
+                };
+                add(
+                    mutex_key(function, next_mutex, code),
+                    LockOrderEdge {
+                        from: mutex.clone(),
+                        to: next_mutex.clone(),
+                        function: name.clone(),
+                        held: node_to_range(first),
+                        acquired: node_to_range(next.statement),
+                        callee: None,
+                    },
+                );
+            }
+            for call in calls.iter().filter(|call| held(**call)) {
+                let mut visited = HashSet::new();
+                let mut locks = Vec::new();
+                locks_in_callee(*call, code, &callables, 0, &mut visited, &mut locks);
+                for (to, callee, callee_mutex, lock) in locks {
+                    add(
+                        to,
+                        LockOrderEdge {
+                            from: mutex.clone(),
+                            to: callee_mutex,
+                            function: name.clone(),
+                            held: node_to_range(first),
+                            acquired: node_to_range(*call),
+                            callee: Some((callee, node_to_range(lock))),
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    let mut keys: Vec<&String> = edges.iter().map(|(from, _, _)| from).collect();
+    keys.sort();
+    keys.dedup();
+    let mut cycles = Vec::new();
+    for start in keys {
+        let mut path = Vec::new();
+        find_cycles(start, start, &edges, &mut path, &mut cycles);
+    }
+    Ok(cycles
+        .into_iter()
+        .map(|path| LockOrderCycle {
+            edges: path
+                .into_iter()
+                .map(|index| edges[index].2.clone())
+                .collect(),
+        })
+        .collect())
+}
+
+/// Extends `path` (edge indices from `start`) towards `start`, visiting only keys greater than
+/// `start` so each cycle is found once, from its smallest key.
+fn find_cycles(
+    start: &str,
+    at: &str,
+    edges: &[(String, String, LockOrderEdge)],
+    path: &mut Vec<usize>,
+    out: &mut Vec<Vec<usize>>,
+) {
+    if path.len() >= MAX_CYCLE_LENGTH {
+        return;
+    }
+    for (index, (from, to, _)) in edges.iter().enumerate() {
+        if from != at {
+            continue;
+        }
+        if to == start {
+            let mut cycle = path.clone();
+            cycle.push(index);
+            out.push(cycle);
+        } else if to.as_str() > start && !path.iter().any(|&i| edges[i].0 == *to) {
+            path.push(index);
+            find_cycles(start, to, edges, path, out);
+            path.pop();
+        }
+    }
+}
+
+/// Identity of `mutex` as written in `function`, comparable across functions: receiver fields
+/// become `Type.field`, captured or local mutexes are scoped to the declaring function, and
+/// anything else is taken to be package-level.
+fn mutex_key(function: Node, mutex: &str, code: &str) -> String {
+    let (root, rest) = match mutex.find('.') {
+        Some(dot) => (&mutex[..dot], &mutex[dot..]),
+        None => (mutex, ""),
+    };
+    let mut scope = Some(function);
+    while let Some(node) = scope {
+        if matches!(
+            node.kind(),
+            "function_declaration" | "method_declaration" | "func_literal"
+        ) {
+            let receiver_type = node
+                .child_by_field_name("receiver")
+                .and_then(|receiver| receiver.named_child(0))
+                .filter(|_| receiver_name(node, code) == Some(root))
+                .and_then(|receiver| receiver.child_by_field_name("type"));
+            if let Some(typ) = receiver_type {
+                return format!("{}{}", text(code, typ).trim_start_matches('*'), rest);
+            }
+            if local_names(node, code).contains(root) {
+                return format!("{}@{}", mutex, node.start_byte());
+            }
+        }
+        scope = node.parent();
+    }
+    mutex.to_string()
+}
+
+/// Every mutex locked by the function `call` invokes, directly or through further calls, as
+/// (key, function taking it, mutex as written there, lock statement).
+fn locks_in_callee<'a>(
+    call: Node,
+    code: &str,
+    callables: &[Callable<'a>],
+    depth: usize,
+    visited: &mut HashSet<usize>,
+    out: &mut Vec<(String, String, String, Node<'a>)>,
+) {
+    if depth >= MAX_CALL_DEPTH {
+        return;
+    }
+    let Some(target) = call_target(call, code, callables) else {
+        return;
+    };
+    if !visited.insert(target.node.id()) {
+        return;
+    }
+    let Some(body) = target.node.child_by_field_name("body") else {
+        return;
+    };
+    let mut events = Vec::new();
+    collect_lock_events(body, code, &mut events);
+    for event in events {
+        if let LockEvent::Lock { mutex, .. } = event.kind {
+            let key = mutex_key(target.node, &mutex, code);
+            out.push((key, target.name.clone(), mutex, event.statement));
+        }
+    }
+    let mut calls = Vec::new();
+    collect_synchronous_calls(body, &mut calls);
+    for inner in calls {
+        locks_in_callee(inner, code, callables, depth + 1, visited, out);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CondMisuseKind {
+    /// `Wait` is not inside a `for` loop re-checking the condition.
+    WaitOutsideLoop,
+    /// `Wait` runs without the cond's lock held.
+    WaitWithoutLock,
+}
+
+/// A `sync.Cond` `Wait` call used in a way that misses wakeups or panics. `Wait` unlocks `c.L`,
+/// suspends, and locks it again before returning; the condition may have changed again by then
+/// (or never held: wakeups can be spurious for the caller's condition), and unlocking a lock the
+/// goroutine does not hold is a fatal error.
+#[derive(Debug, Clone)]
+pub struct CondMisuse {
+    pub kind: CondMisuseKind,
+    /// The cond as written, e.g. `s.cond`.
+    pub cond: String,
+    /// The `Wait()` call.
+    pub range: Range,
+    /// The lock the cond was created with, when the file shows it.
+    pub lock: Option<String>,
+}
+
+impl CondMisuse {
+    pub fn code(&self) -> &'static str {
+        match self.kind {
+            CondMisuseKind::WaitOutsideLoop => "cond-wait-outside-loop",
+            CondMisuseKind::WaitWithoutLock => "cond-wait-without-lock",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self.kind {
+            CondMisuseKind::WaitOutsideLoop => format!(
+                "`{}.Wait()` is not in a loop re-checking the condition; the condition may not hold when Wait returns, so wait in `for !condition {{ {}.Wait() }}`",
+                self.cond, self.cond
+            ),
+            CondMisuseKind::WaitWithoutLock => format!(
+                "`{}.Wait()` is called without holding `{}`; Wait unlocks it first, which is a fatal error when it is not locked",
+                self.cond,
+                self.lock
+                    .clone()
+                    .unwrap_or_else(|| format!("{}.L", self.cond))
+            ),
+        }
+    }
+}
+
+/// `Wait` calls on conds declared in the file (`sync.Cond` variables, fields and parameters,
+/// `sync.NewCond(...)` results). The lock check accepts `c.L` or the mutex passed to `NewCond`,
+/// compared by its last segment so `&s.mu` matches in every method; when the file does not show
+/// the lock any held mutex counts. Functions whose name mentions "lock" are assumed to be called
+/// with it held.
+pub fn find_cond_misuses(tree: &Tree, code: &str) -> AnalysisResult<Vec<CondMisuse>> {
+    check_tree(tree, code)?;
+    let root = tree.root_node();
+    let conds = cond_declarations(root, code);
+    if conds.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut calls = Vec::new();
+    collect_kind(root, "call_expression", &mut calls);
+    let mut findings = Vec::new();
+    for call in calls {
+        let Some((cond, lock)) = cond_method_call(call, code, &conds, &["Wait"]) else {
+            continue;
+        };
+        let range = node_to_range(call);
+        let mut boundary = None;
+        let mut in_loop = false;
+        let mut current = call.parent();
+        while let Some(ancestor) = current {
+            match ancestor.kind() {
+                "for_statement" => in_loop = true,
+                "func_literal" | "function_declaration" | "method_declaration" => {
+                    boundary = Some(ancestor);
+                    break;
+                }
+                _ => {}
+            }
+            current = ancestor.parent();
+        }
+        if !in_loop {
+            findings.push(CondMisuse {
+                kind: CondMisuseKind::WaitOutsideLoop,
+                cond: cond.to_string(),
+                range,
+                lock: lock.clone(),
+            });
+        }
+        let named_lock = boundary
+            .and_then(|function| function.child_by_field_name("name"))
+            .is_some_and(|name| text(code, name).to_lowercase().contains("lock"));
+        if named_lock {
+            continue;
+        }
+        let held = crate::analysis::held_mutexes(tree, range, code);
+        let cond_lock = format!("{}.L", cond);
+        let holds = held.iter().any(|mutex| {
+            *mutex == cond_lock
+                || match &lock {
+                    Some(lock) => last_segment(mutex) == last_segment(lock),
+                    None => true,
+                }
+        });
+        if !holds {
+            findings.push(CondMisuse {
+                kind: CondMisuseKind::WaitWithoutLock,
+                cond: cond.to_string(),
+                range,
+                lock,
+            });
+        }
+    }
+    Ok(findings)
+}
+
+/// `Signal()` or `Broadcast()` on a cond declared in the file: the waiting goroutines resume
+/// after it.
+pub fn is_cond_signal(call: Node, code: &str) -> bool {
+    let mut root = call;
+    while let Some(parent) = root.parent() {
+        root = parent;
+    }
+    let conds = cond_declarations(root, code);
+    cond_method_call(call, code, &conds, &["Signal", "Broadcast"]).is_some()
+}
+
+/// The cond as written and its lock, when `call` is one of `methods` on a declared cond.
+fn cond_method_call<'a>(
+    call: Node,
+    code: &'a str,
+    conds: &HashMap<&str, Option<String>>,
+    methods: &[&str],
+) -> Option<(&'a str, Option<String>)> {
+    let function = call.child_by_field_name("function")?;
+    if function.kind() != "selector_expression" {
+        return None;
+    }
+    let method = text(code, function.child_by_field_name("field")?);
+    if !methods.contains(&method) {
+        return None;
+    }
+    let operand = function.child_by_field_name("operand")?;
+    let cond = text(code, operand);
+    let lock = conds.get(last_segment(cond))?;
+    Some((cond, lock.clone()))
+}
+
+/// Conds by the last segment of their name, with the lock passed to `sync.NewCond` or set as
+/// `L` in a `sync.Cond{...}` literal.
+fn cond_declarations<'a>(root: Node, code: &'a str) -> HashMap<&'a str, Option<String>> {
+    let mut conds = HashMap::new();
+    let mut declarations = Vec::new();
+    for kind in ["var_spec", "field_declaration", "parameter_declaration"] {
+        collect_kind(root, kind, &mut declarations);
+    }
+    for declaration in declarations {
+        let is_cond = declaration
+            .child_by_field_name("type")
+            .is_some_and(|typ| text(code, typ).trim_start_matches('*') == "sync.Cond");
+        if !is_cond {
+            continue;
+        }
+        let mut cursor = declaration.walk();
+        for name in declaration.children_by_field_name("name", &mut cursor) {
+            conds.entry(text(code, name)).or_insert(None);
+        }
+    }
+    let mut values = Vec::new();
+    collect_kind(root, "call_expression", &mut values);
+    collect_kind(root, "composite_literal", &mut values);
+    for value in values {
+        let Some(lock) = cond_lock(value, code) else {
+            continue;
+        };
+        let mut node = value;
+        if let Some(parent) = node
+            .parent()
+            .filter(|parent| parent.kind() == "unary_expression")
+        {
+            node = parent;
+        }
+        let Some(list) = node
+            .parent()
+            .filter(|parent| parent.kind() == "expression_list")
+        else {
+            continue;
+        };
+        let target = list.parent().and_then(|statement| match statement.kind() {
+            "var_spec" => statement.child_by_field_name("name"),
+            "short_var_declaration" | "assignment_statement" => statement
+                .child_by_field_name("left")
+                .and_then(|left| left.named_child(0)),
+            _ => None,
+        });
+        if let Some(target) = target {
+            conds.insert(last_segment(text(code, target)), lock);
+        }
+    }
+    conds
+}
+
+/// `Some(lock)` for `sync.NewCond(&mu)` and `sync.Cond{L: &mu}`; `Some(None)` for a cond
+/// whose lock is not given there.
+fn cond_lock(value: Node, code: &str) -> Option<Option<String>> {
+    let lock = match value.kind() {
+        "call_expression" => {
+            if value
+                .child_by_field_name("function")
+                .is_none_or(|function| text(code, function) != "sync.NewCond")
+            {
+                return None;
+            }
+            value
+                .child_by_field_name("arguments")
+                .and_then(|arguments| arguments.named_child(0))
+        }
+        _ => {
+            if value
+                .child_by_field_name("type")
+                .is_none_or(|typ| text(code, typ) != "sync.Cond")
+            {
+                return None;
+            }
+            let mut elements = Vec::new();
+            collect_kind(value, "keyed_element", &mut elements);
+            elements
+                .into_iter()
+                .find(|element| {
+                    element
+                        .named_child(0)
+                        .is_some_and(|key| text(code, key) == "L")
+                })
+                .and_then(|element| element.named_child(1))
+        }
+    };
+    Some(lock.map(|lock| text(code, lock).trim_start_matches('&').to_string()))
+}
+
+fn last_segment(expression: &str) -> &str {
+    expression.rsplit('.').next().unwrap_or(expression)
+}
@@ -0,0 +1,122 @@
+use std::fs::OpenOptions;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+use tower_lsp::lsp_types::TraceValue;
+use tracing::level_filters::LevelFilter;
+use tracing::Subscriber;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::{reload, Registry};
+
+/// How much the server logs and where: `GO_ANALYZER_LOG_LEVEL` (`off`, `error`, `warn`, `info`,
+/// `debug` or `trace`; default `info`) to stderr, which the editor shows in its output channel,
+/// or appended to `GO_ANALYZER_LOG_FILE` when set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogConfig {
+    pub level: LevelFilter,
+    pub file: Option<PathBuf>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: LevelFilter::INFO,
+            file: None,
+        }
+    }
+}
+
+impl LogConfig {
+    pub fn from_env() -> Self {
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        Self {
+            level: var("GO_ANALYZER_LOG_LEVEL")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(LevelFilter::INFO),
+            file: var("GO_ANALYZER_LOG_FILE").map(PathBuf::from),
+        }
+    }
+}
+
+/// The configured level and the handle that changes it on `$/setTrace`.
+struct Levels {
+    configured: LevelFilter,
+    handle: reload::Handle<LevelFilter, Registry>,
+}
+
+static LEVELS: OnceLock<Levels> = OnceLock::new();
+
+/// A subscriber logging events at `level` and above to `writer`, with a line when each request
+/// span closes giving its duration. The handle changes the level while it runs.
+pub fn subscriber(
+    level: LevelFilter,
+    writer: BoxMakeWriter,
+) -> (
+    impl Subscriber + Send + Sync,
+    reload::Handle<LevelFilter, Registry>,
+) {
+    let (filter, handle) = reload::Layer::new(level);
+    let subscriber = tracing_subscriber::registry().with(filter).with(
+        tracing_subscriber::fmt::layer()
+            .with_writer(writer)
+            .with_ansi(false)
+            .with_span_events(FmtSpan::CLOSE),
+    );
+    (subscriber, handle)
+}
+
+/// Installs the process-wide subscriber. Does nothing when one is already installed.
+pub fn init(config: &LogConfig) {
+    let file = config
+        .file
+        .as_ref()
+        .map(|path| OpenOptions::new().create(true).append(true).open(path));
+    let (writer, open_error) = match file {
+        Some(Ok(file)) => (BoxMakeWriter::new(Mutex::new(file)), None),
+        Some(Err(e)) => (BoxMakeWriter::new(std::io::stderr), Some(e)),
+        None => (BoxMakeWriter::new(std::io::stderr), None),
+    };
+    let (subscriber, handle) = subscriber(config.level, writer);
+    if tracing::subscriber::set_global_default(subscriber).is_err() {
+        return;
+    }
+    let _ = LEVELS.set(Levels {
+        configured: config.level,
+        handle,
+    });
+    if let (Some(e), Some(path)) = (open_error, &config.file) {
+        tracing::warn!(
+            "Failed to open log file {}, logging to stderr: {}",
+            path.display(),
+            e
+        );
+    }
+}
+
+/// The level for the client's trace setting: `messages` adds debug output and `verbose` adds
+/// everything to the configured level; `off` returns to it.
+pub fn trace_level(trace: TraceValue, configured: LevelFilter) -> LevelFilter {
+    match trace {
+        TraceValue::Off => configured,
+        TraceValue::Messages => configured.max(LevelFilter::DEBUG),
+        TraceValue::Verbose => LevelFilter::TRACE,
+    }
+}
+
+/// Applies the client's trace setting, from `initialize` or `$/setTrace`.
+pub fn set_trace(trace: TraceValue) {
+    let Some(levels) = LEVELS.get() else {
+        return;
+    };
+    let level = trace_level(trace, levels.configured);
+    if let Err(e) = levels.handle.reload(level) {
+        tracing::warn!("Failed to change the log level to {}: {}", level, e);
+    }
+}
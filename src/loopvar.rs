@@ -0,0 +1,340 @@
+use tower_lsp::lsp_types::{Position, Range};
+use tree_sitter::{Node, Tree};
+
+use crate::analysis::{check_tree, infer_variable_type, text};
+use crate::error::AnalysisResult;
+use crate::util::{find_node, for_each_node, node_to_range};
+
+/// A `go func() { ... }()` inside a `for` loop whose body refers to a loop variable declared by
+/// the loop itself. Before Go 1.22 every iteration shares one variable, so the goroutine may
+/// observe a later iteration's value.
+#[derive(Debug, Clone)]
+pub struct LoopVariableCapture {
+    pub variable: String,
+    /// First use of the variable inside the goroutine.
+    pub range: Range,
+    /// The loop variable in the `for` clause.
+    pub declaration: Range,
+    pub fix: Option<CaptureFix>,
+}
+
+/// Edits turning the captured variable into a parameter of the goroutine's function literal.
+#[derive(Debug, Clone)]
+pub struct CaptureFix {
+    pub parameter_at: Position,
+    pub parameter: String,
+    pub argument_at: Position,
+    pub argument: String,
+}
+
+impl LoopVariableCapture {
+    pub fn message(&self) -> String {
+        format!(
+            "Goroutine captures loop variable `{}` by reference: before Go 1.22 all iterations share it, so the goroutine may see a later value",
+            self.variable
+        )
+    }
+}
+
+pub fn find_loop_variable_captures(
+    tree: &Tree,
+    code: &str,
+) -> AnalysisResult<Vec<LoopVariableCapture>> {
+    check_tree(tree, code)?;
+    let mut findings = Vec::new();
+    collect_captures(tree.root_node(), tree, code, &mut findings);
+    Ok(findings)
+}
+
+fn collect_captures(node: Node, tree: &Tree, code: &str, out: &mut Vec<LoopVariableCapture>) {
+    for_each_node(node, |node| {
+        if node.kind() != "for_statement" {
+            return;
+        }
+        let Some(body) = node.child_by_field_name("body") else {
+            return;
+        };
+        let variables = loop_variables(node, tree, code);
+        if variables.is_empty() {
+            return;
+        }
+        let mut spawns = Vec::new();
+        collect_spawned_literals(body, &mut spawns);
+        for (call, literal) in spawns {
+            for (name, declaration, typ) in &variables {
+                if copied_before(body, call, code, name) {
+                    continue;
+                }
+                if let Some(first_use) = first_capture(literal, code, name) {
+                    out.push(LoopVariableCapture {
+                        variable: name.clone(),
+                        range: node_to_range(first_use),
+                        declaration: node_to_range(*declaration),
+                        fix: typ
+                            .as_ref()
+                            .and_then(|typ| capture_fix(call, literal, name, typ)),
+                    });
+                }
+            }
+        }
+    });
+}
+
+/// Variables declared with `:=` by a `for` clause or `range` clause, with their type when it can
+/// be inferred from the source.
+fn loop_variables<'a>(
+    for_statement: Node<'a>,
+    tree: &Tree,
+    code: &str,
+) -> Vec<(String, Node<'a>, Option<String>)> {
+    let mut variables = Vec::new();
+    let mut cursor = for_statement.walk();
+    for clause in for_statement.named_children(&mut cursor) {
+        match clause.kind() {
+            "for_clause" => {
+                let Some(init) = clause.child_by_field_name("initializer") else {
+                    continue;
+                };
+                if init.kind() != "short_var_declaration" {
+                    continue;
+                }
+                let Some(left) = init.child_by_field_name("left") else {
+                    continue;
+                };
+                let mut names = left.walk();
+                for ident in left.named_children(&mut names) {
+                    if ident.kind() == "identifier" && text(code, ident) != "_" {
+                        let typ = infer_variable_type(tree, code, node_to_range(ident));
+                        variables.push((text(code, ident).to_string(), ident, typ));
+                    }
+                }
+            }
+            "range_clause" if declares(clause) => {
+                let Some(left) = clause.child_by_field_name("left") else {
+                    continue;
+                };
+                let element_types = clause
+                    .child_by_field_name("right")
+                    .and_then(|right| range_expression_type(right, for_statement, tree, code))
+                    .map(|typ| range_element_types(&typ))
+                    .unwrap_or_default();
+                let mut names = left.walk();
+                for (index, ident) in left.named_children(&mut names).enumerate() {
+                    if ident.kind() == "identifier" && text(code, ident) != "_" {
+                        let typ = element_types.get(index).cloned();
+                        variables.push((text(code, ident).to_string(), ident, typ));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    variables
+}
+
+fn declares(range_clause: Node) -> bool {
+    let mut cursor = range_clause.walk();
+    let declares = range_clause
+        .children(&mut cursor)
+        .any(|child| child.kind() == ":=");
+    declares
+}
+
+/// Type of the ranged-over expression: a composite literal's type, or the inferred type of the
+/// closest preceding declaration of an identifier in the enclosing function.
+fn range_expression_type(
+    right: Node,
+    for_statement: Node,
+    tree: &Tree,
+    code: &str,
+) -> Option<String> {
+    match right.kind() {
+        "composite_literal" => right
+            .child_by_field_name("type")
+            .map(|typ| text(code, typ).to_string()),
+        "identifier" => {
+            let mut function = for_statement.parent();
+            while let Some(node) = function {
+                if matches!(
+                    node.kind(),
+                    "function_declaration" | "method_declaration" | "func_literal"
+                ) {
+                    break;
+                }
+                function = node.parent();
+            }
+            let mut declarations = Vec::new();
+            collect_declarations(function?, code, text(code, right), &mut declarations);
+            let declaration = declarations
+                .into_iter()
+                .filter(|ident| ident.start_byte() < for_statement.start_byte())
+                .max_by_key(|ident| ident.start_byte())?;
+            infer_variable_type(tree, code, node_to_range(declaration))
+        }
+        _ => None,
+    }
+}
+
+fn collect_declarations<'a>(node: Node<'a>, code: &str, name: &str, out: &mut Vec<Node<'a>>) {
+    for_each_node(node, |node| match node.kind() {
+        "parameter_declaration" | "variadic_parameter_declaration" | "var_spec" => {
+            let mut cursor = node.walk();
+            out.extend(
+                node.children_by_field_name("name", &mut cursor)
+                    .filter(|ident| text(code, *ident) == name),
+            );
+        }
+        "short_var_declaration" => {
+            if let Some(left) = node.child_by_field_name("left") {
+                let mut cursor = left.walk();
+                out.extend(
+                    left.named_children(&mut cursor)
+                        .filter(|ident| ident.kind() == "identifier" && text(code, *ident) == name),
+                );
+            }
+        }
+        _ => {}
+    });
+}
+
+/// Key and value types produced by ranging over a value of type `typ`.
+fn range_element_types(typ: &str) -> Vec<String> {
+    let typ = typ.trim();
+    if typ == "string" {
+        return vec!["int".to_string(), "rune".to_string()];
+    }
+    if let Some(rest) = typ.strip_prefix("map[") {
+        let mut depth = 1;
+        for (index, ch) in rest.char_indices() {
+            match ch {
+                '[' => depth += 1,
+                ']' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return vec![
+                            rest[..index].trim().to_string(),
+                            rest[index + 1..].trim().to_string(),
+                        ];
+                    }
+                }
+                _ => {}
+            }
+        }
+        return Vec::new();
+    }
+    if let Some(element) = typ
+        .strip_prefix("<-chan ")
+        .or_else(|| typ.strip_prefix("chan "))
+    {
+        return vec![element.trim().to_string()];
+    }
+    if let Some(rest) = typ.strip_prefix('[') {
+        if let Some(close) = rest.find(']') {
+            return vec!["int".to_string(), rest[close + 1..].trim().to_string()];
+        }
+    }
+    Vec::new()
+}
+
+/// `go` statements in the loop body calling a function literal directly, as (call, literal).
+fn collect_spawned_literals<'a>(node: Node<'a>, out: &mut Vec<(Node<'a>, Node<'a>)>) {
+    // A nested loop gets its own variables; its goroutines still see the outer ones.
+    for_each_node(node, |node| {
+        if node.kind() != "go_statement" {
+            return;
+        }
+        if let Some(call) = node
+            .named_child(0)
+            .filter(|call| call.kind() == "call_expression")
+        {
+            if let Some(literal) = call
+                .child_by_field_name("function")
+                .filter(|function| function.kind() == "func_literal")
+            {
+                out.push((call, literal));
+            }
+        }
+    });
+}
+
+/// `name := name` in a block of the loop body enclosing `call`, before it.
+fn copied_before(body: Node, call: Node, code: &str, name: &str) -> bool {
+    let mut current = call.parent();
+    while let Some(node) = current {
+        if node.kind() == "block" {
+            let mut cursor = node.walk();
+            let copied = node.named_children(&mut cursor).any(|statement| {
+                statement.kind() == "short_var_declaration"
+                    && statement.start_byte() < call.start_byte()
+                    && statement
+                        .child_by_field_name("left")
+                        .map(|left| {
+                            let mut names = left.walk();
+                            let declared = left
+                                .named_children(&mut names)
+                                .any(|ident| text(code, ident) == name);
+                            declared
+                        })
+                        .unwrap_or(false)
+            });
+            if copied {
+                return true;
+            }
+        }
+        if node.id() == body.id() {
+            break;
+        }
+        current = node.parent();
+    }
+    false
+}
+
+/// First identifier in the literal's body referring to `name`, unless the literal declares a
+/// parameter of that name.
+fn first_capture<'a>(literal: Node<'a>, code: &str, name: &str) -> Option<Node<'a>> {
+    if let Some(parameters) = literal.child_by_field_name("parameters") {
+        let mut declarations = Vec::new();
+        collect_declarations(parameters, code, name, &mut declarations);
+        if !declarations.is_empty() {
+            return None;
+        }
+    }
+    first_identifier(literal.child_by_field_name("body")?, code, name)
+}
+
+fn first_identifier<'a>(node: Node<'a>, code: &str, name: &str) -> Option<Node<'a>> {
+    find_node(node, |node| {
+        node.kind() == "identifier" && text(code, node) == name
+    })
+}
+
+fn capture_fix(call: Node, literal: Node, name: &str, typ: &str) -> Option<CaptureFix> {
+    let parameters = literal.child_by_field_name("parameters")?;
+    let arguments = call.child_by_field_name("arguments")?;
+    let variadic = parameters
+        .named_child_count()
+        .checked_sub(1)
+        .and_then(|last| parameters.named_child(last))
+        .is_some_and(|last| last.kind() == "variadic_parameter_declaration");
+    if variadic {
+        return None;
+    }
+    // Insert just before the closing parenthesis of each list.
+    let before_close = |list: Node| {
+        let end = node_to_range(list).end;
+        Position::new(end.line, end.character.saturating_sub(1))
+    };
+    let separator = |list: Node| {
+        if list.named_child_count() > 0 {
+            ", "
+        } else {
+            ""
+        }
+    };
+    Some(CaptureFix {
+        parameter_at: before_close(parameters),
+        parameter: format!("{}{} {}", separator(parameters), name, typ),
+        argument_at: before_close(arguments),
+        argument: format!("{}{}", separator(arguments), name),
+    })
+}
@@ -1,49 +1,88 @@
-mod analysis;
-mod backend;
-mod semantic;
-mod types;
-mod util;
+use std::process::ExitCode;
+use std::time::Duration;
 
-use backend::Backend;
-use tower_lsp::{LspService, Server};
+use go_analyzer::transport::{self, Transport};
 
-#[tokio::main]
-async fn main() {
-    eprintln!("Starting Go Analyzer LSP server...");
-    #[cfg(target_os = "windows")]
-    {
-        tokio::spawn(async {
-            tokio::signal::ctrl_c().await.ok();
-            eprintln!("Received shutdown signal, terminating Go Analyzer server...");
-            std::process::exit(0);
-        });
+/// How long a signalled server waits for the requests it has already read to be answered.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(code) = go_analyzer::cli::run(&args) {
+        return ExitCode::from(u8::try_from(code).unwrap_or(1));
     }
-    #[cfg(not(target_os = "windows"))]
-    {
-        tokio::spawn(async {
-            let sigterm_result =
-                tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate());
-            let sigint_result =
-                tokio::signal::unix::signal(tokio::signal::unix::SignalKind::interrupt());
-            match (sigterm_result, sigint_result) {
-                (Ok(mut sigterm), Ok(mut sigint)) => {
-                    tokio::select! {
-                        _ = sigterm.recv() => eprintln!("Received SIGTERM, terminating Go Analyzer server..."),
-                        _ = sigint.recv() => eprintln!("Received SIGINT, terminating Go Analyzer server..."),
-                    }
-                    std::process::exit(0);
-                }
-                _ => {
-                    eprintln!(
-                        "Failed to setup signal handlers, continuing without signal handling"
-                    );
-                }
-            }
-        });
+    go_analyzer::logging::init(&go_analyzer::logging::LogConfig::from_env());
+    tracing::info!("Starting Go Analyzer LSP server...");
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            tracing::error!("Failed to start the async runtime: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    // `cli::run` has already rejected malformed transport flags.
+    let transport = Transport::from_args(&args).unwrap_or_default();
+    let code = runtime.block_on(serve_until_signal(transport));
+    // A stdin read blocks a runtime thread until the client closes its end; the session is over
+    // either way, so it is not waited for.
+    runtime.shutdown_timeout(Duration::from_secs(1));
+    tracing::info!("Go Analyzer LSP server shutdown complete");
+    code
+}
+
+/// Serves until the client ends the session (`shutdown`, `exit`, then closing the stream) or a
+/// signal arrives. A signal stops the server reading input; it then answers the requests it has
+/// already read, for up to [`SHUTDOWN_GRACE`], and is dropped, flushing the session record.
+async fn serve_until_signal(transport: Transport) -> ExitCode {
+    tracing::info!("Go Analyzer LSP server ready for connections");
+    let (stop, stopped) = tokio::sync::watch::channel(false);
+    let serve = transport::serve(transport, stopped);
+    tokio::pin!(serve);
+    let result = tokio::select! {
+        result = &mut serve => result,
+        signal = shutdown_signal() => {
+            tracing::info!("Received {}, shutting down Go Analyzer server...", signal);
+            stop.send_replace(true);
+            tokio::time::timeout(SHUTDOWN_GRACE, &mut serve)
+                .await
+                .unwrap_or_else(|_| {
+                    tracing::warn!("Requests still running after {:?} are abandoned", SHUTDOWN_GRACE);
+                    Ok(())
+                })
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            tracing::error!("Go Analyzer LSP server transport failed: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+async fn shutdown_signal() -> &'static str {
+    if tokio::signal::ctrl_c().await.is_err() {
+        tracing::warn!("Failed to setup signal handlers, continuing without signal handling");
+        std::future::pending::<()>().await;
+    }
+    "Ctrl+C"
+}
+
+#[cfg(not(target_os = "windows"))]
+async fn shutdown_signal() -> &'static str {
+    use tokio::signal::unix::{signal, SignalKind};
+    match (
+        signal(SignalKind::terminate()),
+        signal(SignalKind::interrupt()),
+    ) {
+        (Ok(mut sigterm), Ok(mut sigint)) => tokio::select! {
+            _ = sigterm.recv() => "SIGTERM",
+            _ = sigint.recv() => "SIGINT",
+        },
+        _ => {
+            tracing::warn!("Failed to setup signal handlers, continuing without signal handling");
+            std::future::pending().await
+        }
     }
-    let (stdin, stdout) = (tokio::io::stdin(), tokio::io::stdout());
-    let (service, socket) = LspService::new(Backend::new);
-    eprintln!("Go Analyzer LSP server ready for connections");
-    Server::new(stdin, stdout, socket).serve(service).await;
-    eprintln!("Go Analyzer LSP server shutdown complete");
 }
@@ -0,0 +1,350 @@
+use tower_lsp::lsp_types::{Position, Range};
+use tree_sitter::{Node, Tree};
+
+use crate::analysis::{
+    access_guard, check_tree, collect_sync_functions, spawning_go_statement, text, AccessGuard,
+};
+use crate::callgraph::collect_kind;
+use crate::error::AnalysisResult;
+use crate::locks::inside_loop;
+use crate::timers::line_indent;
+use crate::util::node_to_range;
+
+/// One read or write of a map: an index expression, `delete`, `len` or `range`.
+#[derive(Debug, Clone)]
+pub struct MapAccess {
+    pub range: Range,
+    pub write: bool,
+    /// A held mutex covers the access (a read lock is enough for reads).
+    pub guarded: bool,
+}
+
+/// A plain `map` used from several goroutines, at least one of them writing, without a mutex
+/// around every access. The runtime detects concurrent map writes (and reads during a write) and
+/// aborts the program.
+#[derive(Debug, Clone)]
+pub struct ConcurrentMapAccess {
+    pub map: String,
+    pub declaration: Range,
+    /// The first unguarded access in a goroutine, preferring writes.
+    pub access: MapAccess,
+    /// Accesses of the map that run concurrently with it.
+    pub others: Vec<MapAccess>,
+    pub fix: MapMutexFix,
+}
+
+/// Declares a `sync.Mutex` next to the map and locks it around every unguarded access.
+#[derive(Debug, Clone)]
+pub struct MapMutexFix {
+    pub mutex: String,
+    pub insertions: Vec<(Position, String)>,
+}
+
+impl ConcurrentMapAccess {
+    pub fn message(&self) -> String {
+        format!(
+            "Map `{}` is {} here while other goroutines use it without a common mutex; concurrent map writes crash the program. Guard it with a `sync.Mutex` or use `sync.Map`",
+            self.map,
+            if self.access.write { "written" } else { "read" }
+        )
+    }
+}
+
+struct Access<'a> {
+    node: Node<'a>,
+    access: MapAccess,
+    /// The `go` statement whose goroutine performs the access; `None` for the spawning function.
+    goroutine: Option<Node<'a>>,
+}
+
+/// Maps declared with a map type, `make(map[K]V)` or a map literal, whose accesses run in two or
+/// more goroutines (a `go` statement in a loop counts as several). Accesses in the spawning
+/// function count when they follow the `go` statement with no `Wait()` or channel receive in
+/// between. Maps passed to other functions are only followed through the goroutine literals.
+pub fn find_concurrent_map_accesses(
+    tree: &Tree,
+    code: &str,
+) -> AnalysisResult<Vec<ConcurrentMapAccess>> {
+    check_tree(tree, code)?;
+    let root = tree.root_node();
+    let sync_funcs = collect_sync_functions(tree, code);
+    let mut findings = Vec::new();
+    for (name, declaration) in map_declarations(root, code) {
+        let scope = execution_body(declaration).unwrap_or(root);
+        let mut identifiers = Vec::new();
+        collect_kind(scope, "identifier", &mut identifiers);
+        let mut accesses: Vec<Access> = identifiers
+            .into_iter()
+            .filter(|ident| *ident != declaration && text(code, *ident) == name)
+            .filter_map(|ident| {
+                let write = access_kind(ident, code)?;
+                let range = node_to_range(ident);
+                let guarded = match access_guard(tree, range, code, &sync_funcs) {
+                    AccessGuard::Exclusive => true,
+                    AccessGuard::Shared => !write,
+                    AccessGuard::None => false,
+                };
+                Some(Access {
+                    node: ident,
+                    access: MapAccess {
+                        range,
+                        write,
+                        guarded,
+                    },
+                    // Only goroutines started inside the map's scope.
+                    goroutine: spawning_go_statement(ident, code)
+                        .filter(|go| go.start_byte() >= scope.start_byte()),
+                })
+            })
+            .collect();
+        let spawns: Vec<Node> = accesses.iter().filter_map(|a| a.goroutine).collect();
+        accesses.retain(|a| {
+            a.goroutine.is_some() || spawns.iter().any(|go| runs_alongside(*go, a.node, code))
+        });
+        let mut goroutines: Vec<usize> = accesses
+            .iter()
+            .filter_map(|a| a.goroutine.map(|go| go.start_byte()))
+            .collect();
+        goroutines.sort_unstable();
+        goroutines.dedup();
+        let in_loop = accesses
+            .iter()
+            .filter_map(|a| a.goroutine)
+            .any(|go| inside_loop(go, scope));
+        let spawner = accesses.iter().any(|a| a.goroutine.is_none());
+        let contexts = goroutines.len() + usize::from(spawner) + usize::from(in_loop);
+        if contexts < 2 || !accesses.iter().any(|a| a.access.write) {
+            continue;
+        }
+        let unguarded = |a: &&Access| !a.access.guarded;
+        let Some(reported) = accesses
+            .iter()
+            .filter(unguarded)
+            .filter(|a| a.goroutine.is_some())
+            .min_by_key(|a| (!a.access.write, a.node.start_byte()))
+            .or_else(|| accesses.iter().find(unguarded))
+        else {
+            continue;
+        };
+        let others = accesses
+            .iter()
+            .filter(|a| {
+                a.node != reported.node
+                    && (a.goroutine != reported.goroutine
+                        || a.goroutine.is_some_and(|go| inside_loop(go, scope)))
+            })
+            .map(|a| a.access.clone())
+            .collect();
+        findings.push(ConcurrentMapAccess {
+            map: name.to_string(),
+            declaration: node_to_range(declaration),
+            access: reported.access.clone(),
+            others,
+            fix: mutex_fix(root, code, name, declaration, &accesses),
+        });
+    }
+    Ok(findings)
+}
+
+/// Identifiers declared as maps, in `var` specs and `:=` declarations.
+fn map_declarations<'a>(root: Node<'a>, code: &'a str) -> Vec<(&'a str, Node<'a>)> {
+    let mut decls = Vec::new();
+    for kind in ["var_spec", "short_var_declaration"] {
+        collect_kind(root, kind, &mut decls);
+    }
+    let mut maps = Vec::new();
+    for decl in decls {
+        let (names, values): (Vec<Node>, Option<Node>) = if decl.kind() == "var_spec" {
+            let mut cursor = decl.walk();
+            let names: Vec<Node> = decl.children_by_field_name("name", &mut cursor).collect();
+            if decl
+                .child_by_field_name("type")
+                .is_some_and(|t| t.kind() == "map_type")
+            {
+                maps.extend(names.into_iter().map(|n| (text(code, n), n)));
+                continue;
+            }
+            (names, decl.child_by_field_name("value"))
+        } else {
+            let names = decl
+                .child_by_field_name("left")
+                .map(|left| {
+                    let mut cursor = left.walk();
+                    left.named_children(&mut cursor).collect()
+                })
+                .unwrap_or_default();
+            (names, decl.child_by_field_name("right"))
+        };
+        let Some(values) = values else {
+            continue;
+        };
+        for (i, name) in names.into_iter().enumerate() {
+            if values.named_child(i).is_some_and(|v| is_map_value(v, code)) {
+                maps.push((text(code, name), name));
+            }
+        }
+    }
+    maps
+}
+
+fn is_map_value(value: Node, code: &str) -> bool {
+    match value.kind() {
+        "composite_literal" => value
+            .child_by_field_name("type")
+            .is_some_and(|t| t.kind() == "map_type"),
+        "call_expression" => {
+            value
+                .child_by_field_name("function")
+                .is_some_and(|f| text(code, f) == "make")
+                && value
+                    .child_by_field_name("arguments")
+                    .and_then(|args| args.named_child(0))
+                    .is_some_and(|t| t.kind() == "map_type")
+        }
+        _ => false,
+    }
+}
+
+/// `Some(is_write)` when `ident` is indexed, deleted from, measured or ranged over.
+fn access_kind(ident: Node, code: &str) -> Option<bool> {
+    let parent = ident.parent()?;
+    match parent.kind() {
+        "index_expression" if parent.child_by_field_name("operand") == Some(ident) => {
+            let target = parent.parent()?;
+            Some(match target.kind() {
+                "inc_statement" | "dec_statement" => true,
+                "expression_list" => target.parent().is_some_and(|statement| {
+                    statement.kind() == "assignment_statement"
+                        && statement.child_by_field_name("left") == Some(target)
+                }),
+                _ => false,
+            })
+        }
+        "argument_list" if parent.named_child(0) == Some(ident) => {
+            let function = parent.parent()?.child_by_field_name("function")?;
+            match text(code, function) {
+                "delete" => Some(true),
+                "len" => Some(false),
+                _ => None,
+            }
+        }
+        "range_clause" if parent.child_by_field_name("right") == Some(ident) => Some(false),
+        _ => None,
+    }
+}
+
+fn execution_body(node: Node) -> Option<Node> {
+    let mut current = node.parent();
+    while let Some(candidate) = current {
+        if matches!(
+            candidate.kind(),
+            "function_declaration" | "method_declaration" | "func_literal"
+        ) {
+            return candidate.child_by_field_name("body");
+        }
+        current = candidate.parent();
+    }
+    None
+}
+
+/// `node` follows the `go` statement in the same function with nothing waiting for the goroutine
+/// in between.
+pub(crate) fn runs_alongside(go: Node, node: Node, code: &str) -> bool {
+    if node.start_byte() < go.end_byte() || execution_body(go) != execution_body(node) {
+        return false;
+    }
+    let between = &code[go.end_byte()..node.start_byte()];
+    !between.contains(".Wait()") && !between.contains("<-")
+}
+
+fn mutex_fix(
+    root: Node,
+    code: &str,
+    map: &str,
+    declaration: Node,
+    accesses: &[Access],
+) -> MapMutexFix {
+    let mutex = format!("{}Mu", map);
+    let mut insertions = Vec::new();
+    if let Some(decl) = enclosing_statement(declaration) {
+        let indent = line_indent(code, decl);
+        let grouped = decl.kind() == "var_declaration"
+            && (0..decl.child_count()).any(|i| decl.child(i).is_some_and(|c| c.kind() == "("));
+        let (after, text) = if grouped {
+            let spec = declaration.parent().unwrap_or(declaration);
+            (
+                node_to_range(spec).end,
+                format!("\n{}{} sync.Mutex", line_indent(code, spec), mutex),
+            )
+        } else {
+            (
+                node_to_range(decl).end,
+                format!("\n{}var {} sync.Mutex", indent, mutex),
+            )
+        };
+        insertions.push((after, text));
+    }
+    let mut statements: Vec<Node> = accesses
+        .iter()
+        .filter(|a| !a.access.guarded)
+        .filter_map(|a| enclosing_statement(a.node))
+        .collect();
+    statements.sort_by_key(|s| s.start_byte());
+    statements.dedup();
+    let outermost: Vec<Node> = statements
+        .iter()
+        .filter(|s| {
+            !statements.iter().any(|other| {
+                other != *s
+                    && other.start_byte() <= s.start_byte()
+                    && s.end_byte() <= other.end_byte()
+            })
+        })
+        .copied()
+        .collect();
+    for statement in outermost {
+        let indent = line_indent(code, statement);
+        let range = node_to_range(statement);
+        insertions.push((range.start, format!("{}.Lock()\n{}", mutex, indent)));
+        insertions.push((range.end, format!("\n{}{}.Unlock()", indent, mutex)));
+    }
+    if let Some(import) = import_insertion(root, code, "sync") {
+        insertions.push(import);
+    }
+    MapMutexFix { mutex, insertions }
+}
+
+/// The statement (a child of a block or the source file) containing `node`.
+fn enclosing_statement(node: Node) -> Option<Node> {
+    let mut current = node;
+    loop {
+        let parent = current.parent()?;
+        if matches!(parent.kind(), "block" | "source_file") {
+            return Some(current);
+        }
+        current = parent;
+    }
+}
+
+/// Where to add `import "<package>"`, or `None` when the file already imports it.
+pub(crate) fn import_insertion(
+    root: Node,
+    code: &str,
+    package: &str,
+) -> Option<(Position, String)> {
+    let path = format!("\"{}\"", package);
+    let mut specs = Vec::new();
+    collect_kind(root, "import_spec", &mut specs);
+    if specs.iter().any(|spec| {
+        spec.child_by_field_name("path")
+            .is_some_and(|spec_path| text(code, spec_path) == path)
+    }) {
+        return None;
+    }
+    let mut cursor = root.walk();
+    let last = root
+        .named_children(&mut cursor)
+        .filter(|n| matches!(n.kind(), "package_clause" | "import_declaration"))
+        .last()?;
+    Some((node_to_range(last).end, format!("\n\nimport {}", path)))
+}
@@ -0,0 +1,213 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Write;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::Url;
+
+use crate::analysis::graph_id_name;
+use crate::types::{GraphData, GraphEdgeType, GraphEntityType};
+
+/// Narrows an entity graph before it is exported. Every field is optional; the default keeps
+/// the whole graph.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, JsonSchema)]
+pub struct GraphFilter {
+    /// Entity types to keep; empty keeps all of them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entities: Vec<GraphEntityType>,
+    /// Label of the entity to center on, such as `handle` or `Server.run`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<String>,
+    /// Edges to follow from `root` (from every function without one), in either direction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depth: Option<usize>,
+}
+
+/// Arguments of `goanalyzer/graph.mermaid`.
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct MermaidParams {
+    pub uri: Url,
+    #[serde(flatten)]
+    pub filter: GraphFilter,
+}
+
+impl GraphFilter {
+    /// The nodes of the kept entity types within `depth` edges of the roots, and the edges
+    /// between them. Edge endpoints that are not entities (call sites, sync points) are kept
+    /// when reached.
+    pub fn apply(&self, graph: &GraphData) -> GraphData {
+        let removed: HashSet<&str> = graph
+            .nodes
+            .iter()
+            .filter(|node| !self.entities.is_empty() && !self.entities.contains(&node.entity_type))
+            .map(|node| node.id.as_str())
+            .collect();
+        let edges: Vec<_> = graph
+            .edges
+            .iter()
+            .filter(|edge| {
+                !removed.contains(edge.from.as_str()) && !removed.contains(edge.to.as_str())
+            })
+            .collect();
+        let reached = self
+            .depth
+            .or(self.root.as_ref().map(|_| usize::MAX))
+            .map(|depth| {
+                let roots: Vec<&str> = graph
+                    .nodes
+                    .iter()
+                    .filter(|node| !removed.contains(node.id.as_str()))
+                    .filter(|node| match &self.root {
+                        Some(root) => {
+                            node.label == *root || node.label.ends_with(&format!(".{}", root))
+                        }
+                        None => matches!(
+                            node.entity_type,
+                            GraphEntityType::Function | GraphEntityType::Method
+                        ),
+                    })
+                    .map(|node| node.id.as_str())
+                    .collect();
+                let mut neighbours: HashMap<&str, Vec<&str>> = HashMap::new();
+                for edge in &edges {
+                    neighbours.entry(&edge.from).or_default().push(&edge.to);
+                    neighbours.entry(&edge.to).or_default().push(&edge.from);
+                }
+                let mut reached: HashSet<&str> = roots.iter().copied().collect();
+                let mut queue: VecDeque<(&str, usize)> =
+                    roots.into_iter().map(|id| (id, 0)).collect();
+                while let Some((id, distance)) = queue.pop_front() {
+                    if distance >= depth {
+                        continue;
+                    }
+                    for next in neighbours.get(id).into_iter().flatten() {
+                        if reached.insert(next) {
+                            queue.push_back((next, distance + 1));
+                        }
+                    }
+                }
+                reached
+            });
+        let kept = |id: &str| reached.as_ref().is_none_or(|reached| reached.contains(id));
+        GraphData {
+            nodes: graph
+                .nodes
+                .iter()
+                .filter(|node| !removed.contains(node.id.as_str()) && kept(&node.id))
+                .cloned()
+                .collect(),
+            edges: edges
+                .into_iter()
+                .filter(|edge| kept(&edge.from) && kept(&edge.to))
+                .cloned()
+                .collect(),
+            truncated: graph.truncated,
+        }
+    }
+}
+
+/// `Variable`, `function`, `sync_block` or `SyncBlock` as a [`GraphEntityType`].
+pub fn parse_entity_type(name: &str) -> Option<GraphEntityType> {
+    match name.trim().to_lowercase().replace('_', "").as_str() {
+        "variable" => Some(GraphEntityType::Variable),
+        "function" => Some(GraphEntityType::Function),
+        "channel" => Some(GraphEntityType::Channel),
+        "goroutine" => Some(GraphEntityType::Goroutine),
+        "syncblock" => Some(GraphEntityType::SyncBlock),
+        "syncmap" => Some(GraphEntityType::SyncMap),
+        "struct" => Some(GraphEntityType::Struct),
+        "interface" => Some(GraphEntityType::Interface),
+        "method" => Some(GraphEntityType::Method),
+        "package" => Some(GraphEntityType::Package),
+        "select" => Some(GraphEntityType::Select),
+        _ => None,
+    }
+}
+
+/// Renders the entity graph as a Mermaid `flowchart`, for markdown files and PR descriptions.
+/// Node shapes follow the entity type and arrows the edge type; endpoints that are not entities
+/// are drawn as small circles named after what they stand for, and racy variables get a red
+/// outline.
+pub fn graph_to_mermaid(graph: &GraphData) -> String {
+    let mut out = String::from("flowchart LR\n");
+    let mut ids: HashMap<&str, String> = HashMap::new();
+    let mut racy = Vec::new();
+    for node in &graph.nodes {
+        if ids.contains_key(node.id.as_str()) {
+            continue;
+        }
+        let id = format!("n{}", ids.len());
+        let label = format!(
+            "{}<br/>line {}",
+            escape(&node.label),
+            node.range.start.line + 1
+        );
+        let (open, close) = node_shape(&node.entity_type);
+        let _ = writeln!(out, "    {}{}\"{}\"{}", id, open, label, close);
+        if node.is_racy() {
+            racy.push(id.clone());
+        }
+        ids.insert(&node.id, id);
+    }
+    for edge in &graph.edges {
+        for endpoint in [&edge.from, &edge.to] {
+            if ids.contains_key(endpoint.as_str()) {
+                continue;
+            }
+            let id = format!("n{}", ids.len());
+            let name = graph_id_name(endpoint);
+            let _ = writeln!(out, "    {}((\"{}\"))", id, escape(name));
+            ids.insert(endpoint, id);
+        }
+    }
+    for edge in &graph.edges {
+        let (Some(from), Some(to)) = (ids.get(edge.from.as_str()), ids.get(edge.to.as_str()))
+        else {
+            continue;
+        };
+        let _ = writeln!(out, "    {} {} {}", from, edge_arrow(&edge.edge_type), to);
+    }
+    if !racy.is_empty() {
+        out.push_str("    classDef race stroke:#d62728,stroke-width:2px\n");
+        let _ = writeln!(out, "    class {} race", racy.join(","));
+    }
+    out
+}
+
+fn node_shape(entity_type: &GraphEntityType) -> (&'static str, &'static str) {
+    match entity_type {
+        GraphEntityType::Variable => ("(", ")"),
+        GraphEntityType::Function => ("[", "]"),
+        GraphEntityType::Channel => ("[/", "/]"),
+        GraphEntityType::Goroutine => ("{{", "}}"),
+        GraphEntityType::SyncBlock => ("[[", "]]"),
+        GraphEntityType::SyncMap => ("[(", ")]"),
+        GraphEntityType::Struct => ("[\\", "\\]"),
+        GraphEntityType::Interface => ("[/", "\\]"),
+        GraphEntityType::Method => ("([", "])"),
+        GraphEntityType::Package => (">", "]"),
+        GraphEntityType::Select => ("{", "}"),
+    }
+}
+
+fn edge_arrow(edge_type: &GraphEdgeType) -> &'static str {
+    match edge_type {
+        GraphEdgeType::Use => "-->",
+        GraphEdgeType::Call => "-->|call|",
+        GraphEdgeType::Send => "==>|send|",
+        GraphEdgeType::Receive => "-.->|receive|",
+        GraphEdgeType::Spawn => "==>|go|",
+        GraphEdgeType::Sync => "-.->|sync|",
+        GraphEdgeType::Close => "--x|close|",
+        GraphEdgeType::Guards => "-.->|guards|",
+        GraphEdgeType::Acquires => "-->|lock|",
+        GraphEdgeType::Implements => "-.->|implements|",
+        GraphEdgeType::HasMethod => "-->|method|",
+        GraphEdgeType::Imports => "-->|imports|",
+    }
+}
+
+/// Quotes would end the label; Mermaid reads `#quot;` as one.
+fn escape(text: &str) -> String {
+    text.replace('"', "#quot;").replace('\n', " ")
+}
@@ -0,0 +1,398 @@
+use std::collections::HashSet;
+
+use tower_lsp::lsp_types::{Position, Range};
+use tree_sitter::{Node, Tree};
+
+use crate::analysis::{check_tree, enclosing_declaration, enclosing_literal, text};
+use crate::callgraph::{call_target, collect_callables, collect_kind, Callable};
+use crate::error::AnalysisResult;
+use crate::maps::import_insertion;
+use crate::timers::line_indent;
+use crate::util::node_to_range;
+
+/// A goroutine whose body can panic with no deferred `recover()`. An unrecovered panic in any
+/// goroutine terminates the whole program, not just the goroutine.
+#[derive(Debug, Clone)]
+pub struct GoroutinePanic {
+    /// The `go` keyword.
+    pub range: Range,
+    pub causes: Vec<(Range, PanicCause)>,
+    /// Adds a deferred recover at the top of the goroutine's function.
+    pub fix: Vec<(Range, String)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanicCause {
+    /// `panic(...)` or `log.Panic...`.
+    Explicit,
+    /// A single-value type assertion `x.(T)`.
+    TypeAssertion,
+    /// Indexing a slice or array with a computed index and no length check.
+    Index,
+    /// Dereferencing a pointer that can be nil and is never compared with `nil`.
+    NilDereference,
+}
+
+impl PanicCause {
+    pub fn describe(self) -> &'static str {
+        match self {
+            PanicCause::Explicit => "explicit panic",
+            PanicCause::TypeAssertion => "type assertion without `, ok` panics on a mismatch",
+            PanicCause::Index => "index may be out of range",
+            PanicCause::NilDereference => "pointer may be nil",
+        }
+    }
+
+    /// Explicit panics and unchecked assertions are certain to panic on some input; the index
+    /// and nil checks are heuristics.
+    pub fn is_certain(self) -> bool {
+        matches!(self, PanicCause::Explicit | PanicCause::TypeAssertion)
+    }
+}
+
+impl GoroutinePanic {
+    pub fn message(&self) -> String {
+        let mut kinds: Vec<&str> = Vec::new();
+        for (_, cause) in &self.causes {
+            if !kinds.contains(&cause.describe()) {
+                kinds.push(cause.describe());
+            }
+        }
+        format!(
+            "Goroutine can panic without a deferred recover ({}); an unrecovered panic in a goroutine crashes the whole process",
+            kinds.join(", ")
+        )
+    }
+}
+
+pub fn find_goroutine_panics(tree: &Tree, code: &str) -> AnalysisResult<Vec<GoroutinePanic>> {
+    check_tree(tree, code)?;
+    let root = tree.root_node();
+    let callables = collect_callables(root, code);
+    let maps = map_names(root, code);
+    let mut go_statements = Vec::new();
+    collect_kind(root, "go_statement", &mut go_statements);
+    let mut findings = Vec::new();
+    for go_stmt in go_statements {
+        let Some(call) = go_stmt
+            .named_child(0)
+            .filter(|call| call.kind() == "call_expression")
+        else {
+            continue;
+        };
+        let function = match call.child_by_field_name("function") {
+            Some(literal) if literal.kind() == "func_literal" => literal,
+            _ => match call_target(call, code, &callables) {
+                Some(target) => target.node,
+                None => continue,
+            },
+        };
+        let Some(body) = function.child_by_field_name("body") else {
+            continue;
+        };
+        if recovers(body, code, &callables) {
+            continue;
+        }
+        let causes = panic_causes(function, body, code, &maps);
+        if causes.is_empty() {
+            continue;
+        }
+        findings.push(GoroutinePanic {
+            range: node_to_range(go_stmt.child(0).unwrap_or(go_stmt)),
+            causes,
+            fix: recover_fix(root, body, code),
+        });
+    }
+    Ok(findings)
+}
+
+/// The goroutine's own frame defers a function that calls `recover()`: a literal, a function of
+/// the file, or a helper named like one (`defer handleRecover()`). `defer recover()` does not
+/// count; `recover` only stops a panic when a deferred function calls it.
+fn recovers(body: Node, code: &str, callables: &[Callable]) -> bool {
+    let mut defers = Vec::new();
+    collect_kind(body, "defer_statement", &mut defers);
+    defers.into_iter().any(|defer| {
+        if enclosing_literal(defer) != enclosing_literal(body) {
+            return false;
+        }
+        let Some(call) = defer.named_child(0) else {
+            return false;
+        };
+        let Some(function) = call.child_by_field_name("function") else {
+            return false;
+        };
+        match function.kind() {
+            "func_literal" => calls_recover(function, code),
+            _ => {
+                let name = text(code, function);
+                if name == "recover" {
+                    return false;
+                }
+                name.contains("ecover")
+                    || call_target(call, code, callables)
+                        .is_some_and(|target| calls_recover(target.node, code))
+            }
+        }
+    })
+}
+
+fn calls_recover(node: Node, code: &str) -> bool {
+    let mut calls = Vec::new();
+    collect_kind(node, "call_expression", &mut calls);
+    calls.into_iter().any(|call| {
+        call.child_by_field_name("function")
+            .is_some_and(|function| text(code, function) == "recover")
+    })
+}
+
+/// Operations in `function`'s body, outside nested function literals, that can panic.
+fn panic_causes(
+    function: Node,
+    body: Node,
+    code: &str,
+    maps: &HashSet<&str>,
+) -> Vec<(Range, PanicCause)> {
+    let own = |node: Node| {
+        let literal = enclosing_literal(node);
+        literal.is_none() || literal == Some(function)
+    };
+    let nullable = nullable_pointers(function, code);
+    let mut causes = Vec::new();
+    let mut nodes = Vec::new();
+    for kind in [
+        "call_expression",
+        "type_assertion_expression",
+        "index_expression",
+        "unary_expression",
+        "selector_expression",
+    ] {
+        collect_kind(body, kind, &mut nodes);
+    }
+    nodes.sort_by_key(|node| node.start_byte());
+    for node in nodes {
+        if !own(node) {
+            continue;
+        }
+        let cause = match node.kind() {
+            "call_expression" => node
+                .child_by_field_name("function")
+                .map(|function| text(code, function))
+                .filter(|callee| {
+                    *callee == "panic"
+                        || callee
+                            .strip_prefix("log.Panic")
+                            .is_some_and(|rest| matches!(rest, "" | "f" | "ln"))
+                })
+                .map(|_| PanicCause::Explicit),
+            "type_assertion_expression" => {
+                (!is_comma_ok(node)).then_some(PanicCause::TypeAssertion)
+            }
+            "index_expression" => is_unchecked_index(node, code, maps).then_some(PanicCause::Index),
+            "unary_expression" => node
+                .child_by_field_name("operand")
+                .filter(|_| text(code, node).starts_with('*'))
+                .filter(|operand| nullable.contains(text(code, *operand)))
+                .map(|_| PanicCause::NilDereference),
+            _ => node
+                .child_by_field_name("operand")
+                .filter(|operand| nullable.contains(text(code, *operand)))
+                .map(|_| PanicCause::NilDereference),
+        };
+        if let Some(cause) = cause {
+            causes.push((node_to_range(node), cause));
+        }
+    }
+    causes
+}
+
+/// `v, ok := x.(T)`, `v, ok = x.(T)` or `var v, ok = x.(T)`.
+fn is_comma_ok(assertion: Node) -> bool {
+    let Some(values) = assertion
+        .parent()
+        .filter(|parent| parent.kind() == "expression_list")
+    else {
+        return false;
+    };
+    if values.named_child_count() != 1 {
+        return false;
+    }
+    let Some(statement) = values.parent() else {
+        return false;
+    };
+    let targets = match statement.kind() {
+        "short_var_declaration" | "assignment_statement" => statement
+            .child_by_field_name("left")
+            .map_or(0, |left| left.named_child_count()),
+        "var_spec" => {
+            let mut cursor = statement.walk();
+            statement
+                .children_by_field_name("name", &mut cursor)
+                .count()
+        }
+        _ => 0,
+    };
+    targets == 2
+}
+
+/// `xs[i]` on something that is not a map, with a computed index that is neither a `range` key
+/// over the same collection nor guarded by an `if` mentioning `len(xs)`.
+fn is_unchecked_index(index: Node, code: &str, maps: &HashSet<&str>) -> bool {
+    let (Some(operand), Some(key)) = (
+        index.child_by_field_name("operand"),
+        index.child_by_field_name("index"),
+    ) else {
+        return false;
+    };
+    if key.kind() == "int_literal" {
+        return false;
+    }
+    let collection = text(code, operand);
+    let last = match operand.kind() {
+        "selector_expression" => operand
+            .child_by_field_name("field")
+            .map_or(collection, |field| text(code, field)),
+        _ => collection,
+    };
+    if maps.contains(last) {
+        return false;
+    }
+    let length = format!("len({})", collection);
+    let mut current = index.parent();
+    while let Some(ancestor) = current {
+        match ancestor.kind() {
+            "if_statement"
+                if ancestor
+                    .child_by_field_name("condition")
+                    .is_some_and(|condition| text(code, condition).contains(&length)) =>
+            {
+                return false;
+            }
+            "for_statement" => {
+                if let Some(clause) = ancestor.named_child(0) {
+                    let guarded = match clause.kind() {
+                        "range_clause" => {
+                            clause
+                                .child_by_field_name("right")
+                                .is_some_and(|right| text(code, right) == collection)
+                                && clause
+                                    .child_by_field_name("left")
+                                    .and_then(|left| left.named_child(0))
+                                    .is_some_and(|first| text(code, first) == text(code, key))
+                        }
+                        "for_clause" => clause
+                            .child_by_field_name("condition")
+                            .is_some_and(|condition| text(code, condition).contains(&length)),
+                        _ => false,
+                    };
+                    if guarded {
+                        return false;
+                    }
+                }
+            }
+            "func_literal" | "function_declaration" | "method_declaration" => break,
+            _ => {}
+        }
+        current = ancestor.parent();
+    }
+    true
+}
+
+/// Names of variables, parameters and struct fields declared with a map type or made as maps.
+fn map_names<'a>(root: Node, code: &'a str) -> HashSet<&'a str> {
+    let mut names = HashSet::new();
+    let mut declarations = Vec::new();
+    for kind in [
+        "var_spec",
+        "parameter_declaration",
+        "field_declaration",
+        "short_var_declaration",
+    ] {
+        collect_kind(root, kind, &mut declarations);
+    }
+    for declaration in declarations {
+        let is_map = declaration
+            .child_by_field_name("type")
+            .is_some_and(|typ| typ.kind() == "map_type")
+            || declaration
+                .child_by_field_name("value")
+                .or_else(|| declaration.child_by_field_name("right"))
+                .is_some_and(|value| {
+                    let value = text(code, value).replace(' ', "");
+                    value.starts_with("make(map[") || value.starts_with("map[")
+                });
+        if !is_map {
+            continue;
+        }
+        match declaration.child_by_field_name("left") {
+            Some(left) => {
+                let mut cursor = left.walk();
+                names.extend(left.named_children(&mut cursor).map(|n| text(code, n)));
+            }
+            None => {
+                let mut cursor = declaration.walk();
+                names.extend(
+                    declaration
+                        .children_by_field_name("name", &mut cursor)
+                        .map(|n| text(code, n)),
+                );
+            }
+        }
+    }
+    names
+}
+
+/// Pointer parameters of `function` and pointer variables declared without a value (`var p *T`),
+/// when nothing in `function` or around it compares them with `nil`.
+fn nullable_pointers<'a>(function: Node, code: &'a str) -> HashSet<&'a str> {
+    let scope = enclosing_declaration(function).unwrap_or(function);
+    let mut declarations = Vec::new();
+    collect_kind(scope, "parameter_declaration", &mut declarations);
+    collect_kind(scope, "var_spec", &mut declarations);
+    let scope_text = text(code, scope).replace(' ', "");
+    let mut names = HashSet::new();
+    for declaration in declarations {
+        let pointer = declaration
+            .child_by_field_name("type")
+            .is_some_and(|typ| typ.kind() == "pointer_type");
+        if !pointer || declaration.child_by_field_name("value").is_some() {
+            continue;
+        }
+        let mut cursor = declaration.walk();
+        for name in declaration.children_by_field_name("name", &mut cursor) {
+            let name = text(code, name);
+            let checked = scope_text.contains(&format!("{}!=nil", name))
+                || scope_text.contains(&format!("{}==nil", name));
+            if !checked {
+                names.insert(name);
+            }
+        }
+    }
+    names
+}
+
+/// `defer func() { if r := recover(); r != nil { log.Printf(...) } }()` as the first statement
+/// of `body`, importing `log` when the file does not.
+fn recover_fix(root: Node, body: Node, code: &str) -> Vec<(Range, String)> {
+    let open = node_to_range(body).start;
+    let after_brace = Position::new(open.line, open.character + 1);
+    let outer = line_indent(code, body);
+    let indent = match body.named_child(0) {
+        Some(first) => line_indent(code, first),
+        None => format!("{}\t", outer),
+    };
+    let unit = indent
+        .strip_prefix(outer.as_str())
+        .filter(|unit| !unit.is_empty())
+        .unwrap_or("\t");
+    let mut edits = vec![(
+        Range::new(after_brace, after_brace),
+        format!(
+            "\n{indent}defer func() {{\n{indent}{unit}if r := recover(); r != nil {{\n{indent}{unit}{unit}log.Printf(\"goroutine panic: %v\", r)\n{indent}{unit}}}\n{indent}}}()"
+        ),
+    )];
+    if let Some((position, import)) = import_insertion(root, code, "log") {
+        edits.insert(0, (Range::new(position, position), import));
+    }
+    edits
+}
@@ -0,0 +1,149 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tree_sitter::Tree;
+
+use crate::util::for_each_node;
+
+pub struct PerfNotification;
+impl tower_lsp::lsp_types::notification::Notification for PerfNotification {
+    const METHOD: &'static str = "goanalyzer/perf";
+    type Params = PerfParams;
+}
+
+/// Sent after each hover, command and post-edit analysis when `GO_ANALYZER_PERF` is set, so a
+/// slow file can be reported with numbers.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct PerfParams {
+    pub trace_id: String,
+    /// The LSP method, or the command name for `workspace/executeCommand`.
+    pub method: String,
+    /// The document the request analyzed, with its size and how its tree was obtained.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+    pub total_ms: f64,
+    /// Time spent parsing; absent when the tree came from the cache.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parse_ms: Option<f64>,
+    /// `total_ms` less `parse_ms`.
+    pub analysis_ms: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code_len: Option<usize>,
+    /// Syntax nodes in the document's tree.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nodes: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tree_cache_hit: Option<bool>,
+    /// The analysis ran out of its time budget; see [`crate::budget::AnalysisBudget`].
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct PerfConfig {
+    pub enabled: bool,
+}
+
+impl PerfConfig {
+    pub fn from_env() -> Self {
+        let enabled = match std::env::var("GO_ANALYZER_PERF") {
+            Ok(v) => matches!(v.as_str(), "1" | "true" | "TRUE" | "yes" | "YES"),
+            Err(_) => false,
+        };
+        Self { enabled }
+    }
+}
+
+/// The document a request analyzed.
+#[derive(Debug, Clone)]
+pub struct DocumentSample {
+    pub uri: String,
+    pub code_len: usize,
+    pub tree: Tree,
+    /// `None` when the tree came from the cache.
+    pub parse: Option<Duration>,
+}
+
+/// Measurements of one request, filled in as it runs and reported as [`PerfParams`].
+#[derive(Debug)]
+pub struct PerfRecorder {
+    started: Mutex<Instant>,
+    document: Mutex<Option<DocumentSample>>,
+    truncated: AtomicBool,
+}
+
+impl Default for PerfRecorder {
+    fn default() -> Self {
+        Self {
+            started: Mutex::new(Instant::now()),
+            document: Mutex::new(None),
+            truncated: AtomicBool::new(false),
+        }
+    }
+}
+
+impl PerfRecorder {
+    /// Measures from now on, leaving out time the request spent waiting, such as the debounce
+    /// before a post-edit analysis.
+    pub fn restart(&self) {
+        *self.started.lock().unwrap_or_else(PoisonError::into_inner) = Instant::now();
+    }
+
+    /// Records the document being analyzed. Parse times of a document fetched more than once
+    /// add up.
+    pub fn document(&self, mut sample: DocumentSample) {
+        let mut document = self.document.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(previous) = document
+            .as_ref()
+            .filter(|previous| previous.uri == sample.uri)
+        {
+            sample.parse = match (previous.parse, sample.parse) {
+                (Some(a), Some(b)) => Some(a + b),
+                (a, b) => a.or(b),
+            };
+        }
+        *document = Some(sample);
+    }
+
+    pub fn truncated(&self) {
+        self.truncated.store(true, Ordering::Relaxed);
+    }
+
+    /// What was measured so far, for the request `trace_id` of `method`.
+    pub fn report(&self, trace_id: &str, method: &str) -> PerfParams {
+        let total = self
+            .started
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .elapsed();
+        let document = self
+            .document
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        let parse = document.as_ref().and_then(|document| document.parse);
+        PerfParams {
+            trace_id: trace_id.to_string(),
+            method: method.to_string(),
+            uri: document.as_ref().map(|document| document.uri.clone()),
+            total_ms: ms(total),
+            parse_ms: parse.map(ms),
+            analysis_ms: ms(total.saturating_sub(parse.unwrap_or_default())),
+            code_len: document.as_ref().map(|document| document.code_len),
+            nodes: document.as_ref().map(|document| {
+                let mut nodes = 0;
+                for_each_node(document.tree.root_node(), |_| nodes += 1);
+                nodes
+            }),
+            tree_cache_hit: document.as_ref().map(|document| document.parse.is_none()),
+            truncated: self.truncated.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,128 @@
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::schema::{Schema, SchemaObject};
+use schemars::JsonSchema;
+use serde_json::{json, Map, Value};
+
+use crate::doctor::DoctorReport;
+use crate::findings::{DecorationsDelta, DecorationsParams};
+use crate::graphdelta::GraphDelta;
+use crate::graphpage::{GraphPage, GraphPageParams};
+use crate::mermaid::MermaidParams;
+use crate::perf::PerfParams;
+use crate::query::{FunctionGraphParams, GraphQueryParams, GraphQueryResult};
+use crate::racedetector::{RaceDetectorParams, RaceDetectorReport};
+use crate::record::SessionDump;
+use crate::stats::ServerStats;
+use crate::summary::{WorkspaceSummary, WorkspaceSummaryParams};
+use crate::types::{
+    CursorCommandParams, Decoration, GraphData, IndexingStatusParams, ParseInfoParams,
+    ProgressParams,
+};
+use crate::workspace::GraphParams;
+
+/// Schemas of the `lsp_types` the protocol carries, which do not implement `JsonSchema`. Fields
+/// of those types point here with `#[schemars(with = "...")]`.
+#[allow(dead_code)]
+pub mod lsp {
+    use schemars::JsonSchema;
+    use url::Url;
+
+    /// `character` is a byte column, as everywhere in the server.
+    #[derive(JsonSchema)]
+    pub struct Position {
+        line: u32,
+        character: u32,
+    }
+
+    #[derive(JsonSchema)]
+    pub struct Range {
+        start: Position,
+        end: Position,
+    }
+
+    #[derive(JsonSchema)]
+    pub struct TextDocumentIdentifier {
+        uri: Url,
+    }
+
+    #[derive(JsonSchema)]
+    pub struct TextDocumentPositionParams {
+        #[serde(rename = "textDocument")]
+        text_document: TextDocumentIdentifier,
+        position: Position,
+    }
+}
+
+/// JSON Schema (draft-07) for the custom notifications, command arguments and results exchanged
+/// with the client, generated from the `JsonSchema` derives of their Rust types, so it follows
+/// their serde representation. A copy is checked in as `docs/protocol.schema.json` for client
+/// code generation (`make schema` regenerates it).
+pub fn protocol_schema() -> Value {
+    let mut gen = SchemaSettings::draft07().into_generator();
+    let notifications = json!({
+        "goanalyzer/progress": notification::<ProgressParams>(&mut gen),
+        "goanalyzer/indexingStatus": notification::<IndexingStatusParams>(&mut gen),
+        "goanalyzer/parseInfo": notification::<ParseInfoParams>(&mut gen),
+        "goanalyzer/perf": notification::<PerfParams>(&mut gen),
+        "goanalyzer/decorations": notification::<DecorationsParams>(&mut gen),
+        "goanalyzer/decorationsDelta": notification::<DecorationsDelta>(&mut gen),
+        "goanalyzer/graphDelta": notification::<GraphDelta>(&mut gen),
+    });
+    let commands = json!({
+        "goanalyzer/cursor": command::<CursorCommandParams, Option<Vec<Decoration>>>(&mut gen),
+        "goanalyzer/graph": command::<GraphParams, Option<GraphData>>(&mut gen),
+        "goanalyzer/graph.export": command::<GraphParams, Option<String>>(&mut gen),
+        "goanalyzer/graph.mermaid": command::<MermaidParams, Option<String>>(&mut gen),
+        "goanalyzer/graph.page": command::<GraphPageParams, Option<GraphPage>>(&mut gen),
+        "goanalyzer/graphForFunction": command::<FunctionGraphParams, Option<GraphData>>(&mut gen),
+        "goanalyzer/graphQuery": command::<GraphQueryParams, Option<GraphQueryResult>>(&mut gen),
+        "goanalyzer/channelFlow":
+            command::<lsp::TextDocumentPositionParams, Option<GraphData>>(&mut gen),
+        "goanalyzer/ast": command::<lsp::TextDocumentIdentifier, Option<String>>(&mut gen),
+        "goanalyzer/resyncDecorations": command::<lsp::TextDocumentIdentifier, ()>(&mut gen),
+        "goanalyzer/protocolSchema": command_without_arguments::<Map<String, Value>>(&mut gen),
+        "goanalyzer/debug.dumpSession": command_without_arguments::<SessionDump>(&mut gen),
+        "goanalyzer/workspaceSummary":
+            command::<WorkspaceSummaryParams, WorkspaceSummary>(&mut gen),
+        "goanalyzer/doctor": command_without_arguments::<DoctorReport>(&mut gen),
+        "goanalyzer/stats": command_without_arguments::<ServerStats>(&mut gen),
+        "goanalyzer/runRaceDetector":
+            command::<RaceDetectorParams, Option<RaceDetectorReport>>(&mut gen),
+    });
+
+    let mut definitions = gen.take_definitions();
+    for schema in definitions.values_mut() {
+        // An object has exactly its fields, as with `#[serde(deny_unknown_fields)]`, which the
+        // types leave off to keep accepting fields from newer clients. One with a flattened enum
+        // stays open: the variants' fields are not among its properties.
+        if let Schema::Object(SchemaObject {
+            object: Some(object),
+            subschemas: None,
+            ..
+        }) = schema
+        {
+            if !object.properties.is_empty() {
+                object.additional_properties = Some(Box::new(Schema::Bool(false)));
+            }
+        }
+    }
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "go-analyzer protocol",
+        "definitions": definitions,
+        "notifications": notifications,
+        "commands": commands,
+    })
+}
+
+fn notification<P: JsonSchema>(gen: &mut SchemaGenerator) -> Value {
+    json!({"params": gen.subschema_for::<P>()})
+}
+
+fn command<A: JsonSchema, R: JsonSchema>(gen: &mut SchemaGenerator) -> Value {
+    json!({"arguments": [gen.subschema_for::<A>()], "result": gen.subschema_for::<R>()})
+}
+
+fn command_without_arguments<R: JsonSchema>(gen: &mut SchemaGenerator) -> Value {
+    json!({"arguments": [], "result": gen.subschema_for::<R>()})
+}
@@ -0,0 +1,18 @@
+; Sends and `close` calls, and receives including `range` over a channel.
+
+(send_statement
+  channel: (_) @channel) @send
+
+(call_expression
+  function: (identifier) @function
+  arguments: (argument_list
+    .
+    (_) @channel)
+  (#eq? @function "close")) @close
+
+(unary_expression
+  operator: "<-"
+  operand: (_) @channel) @receive
+
+(range_clause
+  right: (_) @channel) @range
@@ -0,0 +1,17 @@
+; Declarations, goroutines and channel types counted by `count_entities`. Only declared names
+; count as variables, not identifiers in the values such as `Map[int](xs)`.
+
+(var_spec
+  name: (identifier) @variable)
+
+(short_var_declaration
+  left: (expression_list
+    (identifier) @variable))
+
+(function_declaration) @function
+
+(method_declaration) @function
+
+(go_statement) @goroutine
+
+(channel_type) @channel
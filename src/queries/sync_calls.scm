@@ -0,0 +1,10 @@
+; Candidates for synchronization inside a block. The handler decides whether a call is a lock,
+; atomic, sync.Map, xsync or configured synchronization call, and whether a select communicates.
+
+(call_expression
+  function: [
+    (identifier)
+    (selector_expression)
+  ]) @call
+
+(select_statement) @select
@@ -0,0 +1,455 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{Position, Url};
+
+use crate::analysis::{graph_id_kind, graph_id_name};
+use crate::types::{GraphData, GraphEdge, GraphEdgeType, GraphEntityType, GraphNode};
+use crate::workspace::GraphScope;
+
+/// Arguments of `goanalyzer/graphQuery`.
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct GraphQueryParams {
+    pub uri: Url,
+    #[serde(default)]
+    pub scope: GraphScope,
+    #[serde(flatten)]
+    pub query: GraphQuery,
+}
+
+/// Which way edges are followed.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryDirection {
+    /// From `from` to `to`.
+    #[default]
+    Out,
+    /// From `to` back to `from`.
+    In,
+    Both,
+}
+
+/// A question about the entity graph. Nodes are named by graph id or, failing that, by
+/// label or id name (`worker`, `Server.run`, `ch`), which may match several start nodes. Besides its
+/// edges, the graph is read as if every function contained the sites declared in its body,
+/// so a goroutine reaches the channels its function sends on.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+#[serde(tag = "query", rename_all = "snake_case")]
+pub enum GraphQuery {
+    /// Nodes one edge away.
+    Neighbors {
+        node: String,
+        #[serde(default)]
+        direction: QueryDirection,
+        /// Edge types to follow; empty follows all of them.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        edge_types: Vec<GraphEdgeType>,
+    },
+    /// Nodes reachable within `depth` edges, or without limit.
+    Reachable {
+        node: String,
+        #[serde(default)]
+        direction: QueryDirection,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        edge_types: Vec<GraphEdgeType>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        depth: Option<usize>,
+    },
+    /// Variables read or written in the body of a function.
+    UsedVariables { node: String },
+    /// Shortest simple paths between two nodes, following edges either way.
+    Paths {
+        from: String,
+        to: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        edge_types: Vec<GraphEdgeType>,
+        /// At most this many paths; 10 when unset.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_paths: Option<usize>,
+    },
+}
+
+/// Answer of a [`GraphQuery`]: the matched ids in the order they were found, the entity
+/// nodes among them, the graph edges between them and, for `paths`, each path as a list of
+/// ids.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, JsonSchema)]
+pub struct GraphQueryResult {
+    pub ids: Vec<String>,
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<Vec<String>>,
+}
+
+/// Arguments of `goanalyzer/graphForFunction`: the function by label (`worker`,
+/// `Server.run`) or by a position inside its declaration.
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct FunctionGraphParams {
+    pub uri: Url,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<crate::protocol::lsp::Position>")]
+    pub position: Option<Position>,
+}
+
+const DEFAULT_MAX_PATHS: usize = 10;
+
+/// Adjacency of a graph, with a function's body sites linked from the function.
+struct Index<'a> {
+    graph: &'a GraphData,
+    outgoing: HashMap<&'a str, Vec<(&'a str, Option<&'a GraphEdgeType>)>>,
+    incoming: HashMap<&'a str, Vec<(&'a str, Option<&'a GraphEdgeType>)>>,
+}
+
+impl<'a> Index<'a> {
+    fn new(graph: &'a GraphData) -> Self {
+        let mut index = Index {
+            graph,
+            outgoing: HashMap::new(),
+            incoming: HashMap::new(),
+        };
+        for edge in &graph.edges {
+            index.link(&edge.from, &edge.to, Some(&edge.edge_type));
+        }
+        let mut seen = HashSet::new();
+        let ids: Vec<&str> = graph
+            .nodes
+            .iter()
+            .map(|node| node.id.as_str())
+            .chain(
+                graph
+                    .edges
+                    .iter()
+                    .flat_map(|e| [e.from.as_str(), e.to.as_str()]),
+            )
+            .filter(|id| seen.insert(*id))
+            .collect();
+        let bodies: HashMap<String, &str> = ids
+            .iter()
+            .filter_map(|id| Some((body_prefix(id)?, *id)))
+            .collect();
+        for id in &ids {
+            let Some(function) = body_owner(id, &bodies) else {
+                continue;
+            };
+            index.link(function, id, None);
+        }
+        index
+    }
+
+    fn link(&mut self, from: &'a str, to: &'a str, edge_type: Option<&'a GraphEdgeType>) {
+        self.outgoing.entry(from).or_default().push((to, edge_type));
+        self.incoming.entry(to).or_default().push((from, edge_type));
+    }
+
+    /// Ids matching `name`: the node with that id, or else every node with that label and
+    /// every edge endpoint with that name.
+    fn resolve(&self, name: &str) -> Vec<&'a str> {
+        let by_id = self
+            .outgoing
+            .get_key_value(name)
+            .or_else(|| self.incoming.get_key_value(name))
+            .map(|(id, _)| *id)
+            .or_else(|| {
+                let node = self.graph.nodes.iter().find(|node| node.id == name)?;
+                Some(node.id.as_str())
+            });
+        if let Some(id) = by_id {
+            return vec![id];
+        }
+        let labelled = self
+            .graph
+            .nodes
+            .iter()
+            .filter(|node| node.label == name)
+            .map(|node| node.id.as_str());
+        let named = self
+            .graph
+            .edges
+            .iter()
+            .flat_map(|edge| [edge.from.as_str(), edge.to.as_str()])
+            .filter(|id| graph_id_name(id) == name);
+        let mut seen = HashSet::new();
+        labelled
+            .chain(named)
+            .filter(|id| seen.insert(*id))
+            .collect()
+    }
+
+    fn step(
+        &self,
+        id: &str,
+        direction: QueryDirection,
+        edge_types: &[GraphEdgeType],
+    ) -> Vec<&'a str> {
+        let follows = |edge_type: &Option<&GraphEdgeType>| {
+            edge_types.is_empty() || edge_type.is_none_or(|t| edge_types.contains(t))
+        };
+        let mut next = Vec::new();
+        let sides = match direction {
+            QueryDirection::Out => [Some(&self.outgoing), None],
+            QueryDirection::In => [Some(&self.incoming), None],
+            QueryDirection::Both => [Some(&self.outgoing), Some(&self.incoming)],
+        };
+        for side in sides.into_iter().flatten() {
+            for (other, edge_type) in side.get(id).into_iter().flatten() {
+                if follows(edge_type) && !next.contains(other) {
+                    next.push(*other);
+                }
+            }
+        }
+        next
+    }
+
+    /// Breadth-first search from `starts`, which are not part of the result.
+    fn reach(
+        &self,
+        starts: &[&'a str],
+        direction: QueryDirection,
+        edge_types: &[GraphEdgeType],
+        depth: usize,
+    ) -> Vec<&'a str> {
+        let mut seen: HashSet<&str> = starts.iter().copied().collect();
+        let mut queue: VecDeque<(&str, usize)> = starts.iter().map(|id| (*id, 0)).collect();
+        let mut found = Vec::new();
+        while let Some((id, distance)) = queue.pop_front() {
+            if distance == depth {
+                continue;
+            }
+            for next in self.step(id, direction, edge_types) {
+                if seen.insert(next) {
+                    found.push(next);
+                    queue.push_back((next, distance + 1));
+                }
+            }
+        }
+        found
+    }
+
+    /// Shortest simple paths from any of `from` to any of `to`, shortest first, found by
+    /// breadth-first search over partial paths.
+    fn paths(
+        &self,
+        from: &[&'a str],
+        to: &[&'a str],
+        edge_types: &[GraphEdgeType],
+        max_paths: usize,
+    ) -> Vec<Vec<&'a str>> {
+        let mut paths = Vec::new();
+        let mut queue: VecDeque<Vec<&str>> = from.iter().map(|id| vec![*id]).collect();
+        // Each node is expanded at most `max_paths` times, which bounds the search on dense
+        // graphs while still finding that many distinct paths.
+        let mut expanded: HashMap<&str, usize> = HashMap::new();
+        while let Some(path) = queue.pop_front() {
+            if paths.len() >= max_paths {
+                break;
+            }
+            let Some(&last) = path.last() else {
+                continue;
+            };
+            if to.contains(&last) {
+                paths.push(path);
+                continue;
+            }
+            let count = expanded.entry(last).or_insert(0);
+            if *count >= max_paths {
+                continue;
+            }
+            *count += 1;
+            for next in self.step(last, QueryDirection::Both, edge_types) {
+                if !path.contains(&next) {
+                    let mut longer = path.clone();
+                    longer.push(next);
+                    queue.push_back(longer);
+                }
+            }
+        }
+        paths
+    }
+
+    fn result(&self, ids: Vec<&str>, paths: Vec<Vec<&str>>) -> GraphQueryResult {
+        let kept: HashSet<&str> = ids.iter().copied().collect();
+        let mut seen = HashSet::new();
+        GraphQueryResult {
+            nodes: self
+                .graph
+                .nodes
+                .iter()
+                .filter(|node| kept.contains(node.id.as_str()) && seen.insert(node.id.as_str()))
+                .cloned()
+                .collect(),
+            edges: self
+                .graph
+                .edges
+                .iter()
+                .filter(|edge| kept.contains(edge.from.as_str()) && kept.contains(edge.to.as_str()))
+                .cloned()
+                .collect(),
+            ids: ids.into_iter().map(str::to_string).collect(),
+            paths: paths
+                .into_iter()
+                .map(|path| path.into_iter().map(str::to_string).collect())
+                .collect(),
+        }
+    }
+}
+
+/// `file::name::` for the id of a function `file::fn::name#0`: the prefix of the ids of the
+/// sites in its body.
+fn body_prefix(id: &str) -> Option<String> {
+    if graph_id_kind(id) != Some("fn") || !id.ends_with("#0") {
+        return None;
+    }
+    let file = id
+        .rsplitn(3, "::")
+        .nth(2)
+        .map_or(String::new(), |file| format!("{}::", file));
+    Some(format!("{}{}::", file, graph_id_name(id)))
+}
+
+/// The function whose body the site `id` is in, by the longest matching body prefix.
+fn body_owner<'a>(id: &str, bodies: &HashMap<String, &'a str>) -> Option<&'a str> {
+    let mut parts: Vec<&str> = id.split("::").collect();
+    // Drop the kind and name, then try the remaining prefixes from longest to shortest.
+    parts.truncate(parts.len().saturating_sub(2));
+    (1..=parts.len())
+        .rev()
+        .find_map(|len| bodies.get(&format!("{}::", parts[..len].join("::"))))
+        .copied()
+}
+
+/// The part of `graph` about the function or method labelled `label`: its node, everything
+/// declared or happening in its body (locals, channel operations, goroutines it spawns, lock
+/// regions), and the edges touching those with their other ends, such as the functions it
+/// calls and the package variables it uses. `None` when no such function is in the graph.
+pub fn function_subgraph(graph: &GraphData, label: &str) -> Option<GraphData> {
+    let function = graph.nodes.iter().find(|node| {
+        node.label == label
+            && matches!(
+                node.entity_type,
+                GraphEntityType::Function | GraphEntityType::Method
+            )
+    })?;
+    let prefix = body_prefix(&function.id)?;
+    let inside = |id: &str| id == function.id || id.starts_with(&prefix);
+    let edges: Vec<GraphEdge> = graph
+        .edges
+        .iter()
+        .filter(|edge| inside(&edge.from) || inside(&edge.to))
+        .cloned()
+        .collect();
+    let endpoints: HashSet<&str> = edges
+        .iter()
+        .flat_map(|edge| [edge.from.as_str(), edge.to.as_str()])
+        .collect();
+    let mut seen = HashSet::new();
+    let nodes = graph
+        .nodes
+        .iter()
+        .filter(|node| inside(&node.id) || endpoints.contains(node.id.as_str()))
+        .filter(|node| seen.insert(node.id.as_str()))
+        .cloned()
+        .collect();
+    Some(GraphData {
+        nodes,
+        edges,
+        truncated: graph.truncated,
+    })
+}
+
+/// Whether a graph starts goroutines, touches channels or takes locks.
+pub fn has_concurrency(graph: &GraphData) -> bool {
+    graph.nodes.iter().any(|node| {
+        matches!(
+            node.entity_type,
+            GraphEntityType::Goroutine
+                | GraphEntityType::Channel
+                | GraphEntityType::Select
+                | GraphEntityType::SyncBlock
+        )
+    }) || graph.edges.iter().any(|edge| {
+        matches!(
+            edge.edge_type,
+            GraphEdgeType::Spawn
+                | GraphEdgeType::Send
+                | GraphEdgeType::Receive
+                | GraphEdgeType::Close
+                | GraphEdgeType::Acquires
+        )
+    })
+}
+
+/// Answers `query` on `graph`. Unknown node names give an empty result.
+pub fn run_query(graph: &GraphData, query: &GraphQuery) -> GraphQueryResult {
+    let index = Index::new(graph);
+    match query {
+        GraphQuery::Neighbors {
+            node,
+            direction,
+            edge_types,
+        } => {
+            let starts = index.resolve(node);
+            index.result(index.reach(&starts, *direction, edge_types, 1), Vec::new())
+        }
+        GraphQuery::Reachable {
+            node,
+            direction,
+            edge_types,
+            depth,
+        } => {
+            let starts = index.resolve(node);
+            let depth = depth.unwrap_or(usize::MAX);
+            index.result(
+                index.reach(&starts, *direction, edge_types, depth),
+                Vec::new(),
+            )
+        }
+        GraphQuery::UsedVariables { node } => {
+            let functions: Vec<&str> = index
+                .resolve(node)
+                .into_iter()
+                .filter(|id| graph_id_kind(id) == Some("fn"))
+                .collect();
+            let mut variables = Vec::new();
+            for site in index.reach(&functions, QueryDirection::Out, &[], 1) {
+                for declaration in index.step(site, QueryDirection::In, &[GraphEdgeType::Use]) {
+                    if !variables.contains(&declaration) {
+                        variables.push(declaration);
+                    }
+                }
+            }
+            variables.retain(|id| {
+                graph.nodes.iter().any(|node| {
+                    node.id == *id
+                        && matches!(
+                            node.entity_type,
+                            GraphEntityType::Variable | GraphEntityType::SyncMap
+                        )
+                })
+            });
+            index.result(variables, Vec::new())
+        }
+        GraphQuery::Paths {
+            from,
+            to,
+            edge_types,
+            max_paths,
+        } => {
+            let paths = index.paths(
+                &index.resolve(from),
+                &index.resolve(to),
+                edge_types,
+                max_paths.unwrap_or(DEFAULT_MAX_PATHS),
+            );
+            let mut ids = Vec::new();
+            for id in paths.iter().flatten() {
+                if !ids.contains(id) {
+                    ids.push(*id);
+                }
+            }
+            index.result(ids, paths)
+        }
+    }
+}
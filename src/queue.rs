@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use tokio::sync::{watch, Mutex};
+use tower_lsp::lsp_types::Url;
+
+/// Per-document debouncing of the analysis that follows `did_change`. Every change joins the
+/// document's current burst; only the change that is still the latest once the burst has been
+/// quiet for `delay` runs the analysis, so rapid typing triggers it once per burst.
+pub struct AnalysisQueue {
+    delay: Duration,
+    /// Ticket of the latest change per document.
+    bursts: Mutex<HashMap<Url, u64>>,
+    next_ticket: AtomicU64,
+    /// Set once by [`AnalysisQueue::close`] when the server shuts down.
+    closed: watch::Sender<bool>,
+}
+
+impl AnalysisQueue {
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            bursts: Mutex::new(HashMap::new()),
+            next_ticket: AtomicU64::new(1),
+            closed: watch::Sender::new(false),
+        }
+    }
+
+    /// Delay from `GO_ANALYZER_ANALYSIS_DEBOUNCE_MS`, or the older
+    /// `GO_ANALYZER_PUSH_DEBOUNCE_MS`, defaulting to 300 ms.
+    pub fn from_env() -> Self {
+        let delay_ms = [
+            "GO_ANALYZER_ANALYSIS_DEBOUNCE_MS",
+            "GO_ANALYZER_PUSH_DEBOUNCE_MS",
+        ]
+        .iter()
+        .find_map(|name| std::env::var(name).ok()?.parse::<u64>().ok())
+        .unwrap_or(300);
+        Self::new(Duration::from_millis(delay_ms))
+    }
+
+    /// Queues a change of `uri` and waits out the delay. Returns the change's ticket when no
+    /// later change arrived meanwhile, and `None` when a later one took over the burst or the
+    /// queue was closed.
+    pub async fn settle(&self, uri: &Url) -> Option<u64> {
+        let ticket = {
+            let mut bursts = self.bursts.lock().await;
+            let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+            bursts.insert(uri.clone(), ticket);
+            ticket
+        };
+        let mut closed = self.closed.subscribe();
+        tokio::select! {
+            _ = tokio::time::sleep(self.delay) => {}
+            _ = closed.wait_for(|closed| *closed) => return None,
+        }
+        self.is_current(uri, ticket).await.then_some(ticket)
+    }
+
+    /// Whether `ticket` is still the latest change of `uri`, i.e. results computed for it are
+    /// not already outdated and may be published. Nothing is current once the queue is closed.
+    pub async fn is_current(&self, uri: &Url, ticket: u64) -> bool {
+        !self.is_closed() && self.bursts.lock().await.get(uri) == Some(&ticket)
+    }
+
+    /// Cancels the analyses waiting out their delay and stops running ones at their next
+    /// [`AnalysisQueue::is_current`] check. Later changes are not analyzed.
+    pub fn close(&self) {
+        self.closed.send_replace(true);
+    }
+
+    pub fn is_closed(&self) -> bool {
+        *self.closed.borrow()
+    }
+
+    pub async fn remove(&self, uri: &Url) {
+        self.bursts.lock().await.remove(uri);
+    }
+}
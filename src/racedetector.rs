@@ -0,0 +1,324 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{
+    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, Location, NumberOrString,
+    Position, Range,
+};
+use url::Url;
+
+/// Code of the diagnostics published from `go test -race` findings.
+pub const CONFIRMED_RACE: &str = "confirmed-race";
+
+/// Output kept in a report when `go test` fails without finding a race.
+const OUTPUT_TAIL_BYTES: usize = 4000;
+
+/// Arguments of `goanalyzer/runRaceDetector`; every field is optional.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
+pub struct RaceDetectorParams {
+    /// Package pattern passed to `go test`; `./...` by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    /// Directory to run in instead of the first workspace folder.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<String>,
+}
+
+/// How long `go test -race` may run: `GO_ANALYZER_RACE_TIMEOUT_SECS`, 300 by default.
+#[derive(Clone, Debug)]
+pub struct RaceDetectorConfig {
+    pub timeout: Duration,
+}
+
+impl RaceDetectorConfig {
+    pub fn from_env() -> Self {
+        let secs = std::env::var("GO_ANALYZER_RACE_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(300);
+        Self {
+            timeout: Duration::from_secs(secs),
+        }
+    }
+}
+
+/// One function call in a race detector stack trace.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct RaceFrame {
+    pub function: String,
+    pub file: String,
+    /// 1-based, as printed.
+    pub line: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RaceSectionKind {
+    /// One of the two conflicting accesses.
+    Access,
+    /// Where a goroutine involved was started.
+    Creation,
+}
+
+/// A stack trace of a race report, innermost call first.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct RaceSection {
+    pub kind: RaceSectionKind,
+    /// The heading without the address, such as `Previous write by goroutine 6`.
+    pub description: String,
+    /// `None` for the main goroutine.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub goroutine: Option<u64>,
+    pub frames: Vec<RaceFrame>,
+}
+
+/// One `WARNING: DATA RACE` block.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct DataRace {
+    pub sections: Vec<RaceSection>,
+}
+
+/// Result of `goanalyzer/runRaceDetector`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct RaceDetectorReport {
+    /// Whether `go test` passed; a test that hits a race fails.
+    pub passed: bool,
+    pub races: Vec<DataRace>,
+    /// Diagnostics published, over all files.
+    pub diagnostics: usize,
+    /// The end of the output when `go test` failed without reporting a race, such as build
+    /// errors.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+/// Output of a `go test -race` run.
+pub struct GoTestRun {
+    pub passed: bool,
+    pub output: String,
+}
+
+/// Runs `go test -race` on `package` in `dir`. Races are printed to stderr, test results to
+/// stdout; both are returned together.
+pub async fn run_go_test_race(
+    dir: &Path,
+    package: &str,
+    config: &RaceDetectorConfig,
+) -> std::io::Result<GoTestRun> {
+    let child = tokio::process::Command::new("go")
+        .args(["test", "-race", "-count=1", package])
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let output = tokio::time::timeout(config.timeout, child.wait_with_output())
+        .await
+        .map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!(
+                    "`go test -race` did not finish within {}s",
+                    config.timeout.as_secs()
+                ),
+            )
+        })??;
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    Ok(GoTestRun {
+        passed: output.status.success(),
+        output: text,
+    })
+}
+
+/// The data races reported in `go test -race` output.
+pub fn parse_race_output(output: &str) -> Vec<DataRace> {
+    let mut races = Vec::new();
+    let mut race: Option<DataRace> = None;
+    let mut function: Option<&str> = None;
+    for line in output.lines() {
+        let trimmed = line.trim();
+        if trimmed == "WARNING: DATA RACE" {
+            races.extend(race.take());
+            race = Some(DataRace {
+                sections: Vec::new(),
+            });
+            continue;
+        }
+        let Some(current) = race.as_mut() else {
+            continue;
+        };
+        if trimmed.starts_with("==================") {
+            races.extend(race.take());
+            continue;
+        }
+        if trimmed.is_empty() {
+            continue;
+        }
+        if !line.starts_with(char::is_whitespace) {
+            // A heading; sections this does not know, such as `Location is global ...`, are
+            // skipped with their frames.
+            function = None;
+            match parse_heading(trimmed) {
+                Some(section) => current.sections.push(section),
+                None => current.sections.push(RaceSection {
+                    kind: RaceSectionKind::Access,
+                    description: String::new(),
+                    goroutine: None,
+                    frames: Vec::new(),
+                }),
+            }
+            continue;
+        }
+        let Some(section) = current.sections.last_mut() else {
+            continue;
+        };
+        match (function.take(), parse_location(trimmed)) {
+            (Some(name), Some((file, line))) => section.frames.push(RaceFrame {
+                function: name.to_string(),
+                file,
+                line,
+            }),
+            _ => function = Some(trimmed),
+        }
+    }
+    races.extend(race);
+    for race in &mut races {
+        race.sections
+            .retain(|section| !section.description.is_empty());
+    }
+    races.retain(|race| !race.sections.is_empty());
+    races
+}
+
+/// `Previous write at 0x00c00001c0f8 by goroutine 6:` or `Goroutine 7 (running) created at:`.
+fn parse_heading(heading: &str) -> Option<RaceSection> {
+    let heading = heading.trim_end_matches(':');
+    if let Some(rest) = heading.strip_prefix("Goroutine ") {
+        let (id, _) = rest.split_once(' ')?;
+        let id = id.parse().ok()?;
+        return heading.ends_with("created at").then(|| RaceSection {
+            kind: RaceSectionKind::Creation,
+            description: format!("Goroutine {} created", id),
+            goroutine: Some(id),
+            frames: Vec::new(),
+        });
+    }
+    let (access, by) = heading.split_once(" at 0x")?;
+    let (_, by) = by.split_once(" by ")?;
+    let goroutine = by.strip_prefix("goroutine ").and_then(|id| id.parse().ok());
+    Some(RaceSection {
+        kind: RaceSectionKind::Access,
+        description: format!("{} by {}", access, by),
+        goroutine,
+        frames: Vec::new(),
+    })
+}
+
+/// `/src/m/counter.go:12 +0x44` as the file and its line.
+fn parse_location(text: &str) -> Option<(String, u32)> {
+    let location = text.split(" +0x").next()?;
+    let (file, line) = location.rsplit_once(':')?;
+    if !file.ends_with(".go") {
+        return None;
+    }
+    Some((file.to_string(), line.parse().ok()?))
+}
+
+/// The innermost frame of a section in a file under `root`: the code that raced, rather than
+/// the runtime or test harness it was called from.
+fn user_frame<'a>(section: &'a RaceSection, root: &Path) -> Option<&'a RaceFrame> {
+    section
+        .frames
+        .iter()
+        .find(|frame| Path::new(&frame.file).starts_with(root))
+}
+
+fn frame_location(frame: &RaceFrame) -> Option<Location> {
+    let path = PathBuf::from(&frame.file);
+    let uri = Url::from_file_path(&path).ok()?;
+    let line = frame.line.checked_sub(1)?;
+    let text = std::fs::read_to_string(&path).ok();
+    let line_text = text
+        .as_deref()
+        .and_then(|text| text.lines().nth(line as usize))
+        .unwrap_or_default();
+    let indent = line_text.len() - line_text.trim_start().len();
+    Some(Location::new(
+        uri,
+        Range::new(
+            Position::new(line, indent as u32),
+            Position::new(line, line_text.trim_end().len() as u32),
+        ),
+    ))
+}
+
+/// One error per access of each race, at the innermost line under `root`, linked to the
+/// conflicting access and to where the goroutines involved started. Grouped by file.
+pub fn race_diagnostics(races: &[DataRace], root: &Path) -> BTreeMap<Url, Vec<Diagnostic>> {
+    let mut by_file: BTreeMap<Url, Vec<Diagnostic>> = BTreeMap::new();
+    for race in races {
+        let located: Vec<(&RaceSection, &RaceFrame, Location)> = race
+            .sections
+            .iter()
+            .filter_map(|section| {
+                let frame = user_frame(section, root)?;
+                Some((section, frame, frame_location(frame)?))
+            })
+            .collect();
+        for (section, frame, location) in &located {
+            if section.kind != RaceSectionKind::Access {
+                continue;
+            }
+            let related: Vec<DiagnosticRelatedInformation> = located
+                .iter()
+                .filter(|(other, _, _)| !std::ptr::eq(*other, *section))
+                .map(|(other, frame, location)| DiagnosticRelatedInformation {
+                    location: location.clone(),
+                    message: format!("{} in `{}`", other.description, frame.function),
+                })
+                .collect();
+            let diagnostic = Diagnostic {
+                range: location.range,
+                severity: Some(DiagnosticSeverity::ERROR),
+                code: Some(NumberOrString::String(CONFIRMED_RACE.to_string())),
+                source: Some("go-analyzer".to_string()),
+                message: format!(
+                    "Data race confirmed by `go test -race`: {} in `{}`",
+                    lowercase_first(&section.description),
+                    frame.function
+                ),
+                related_information: (!related.is_empty()).then_some(related),
+                ..Default::default()
+            };
+            let diagnostics = by_file.entry(location.uri.clone()).or_default();
+            if !diagnostics.contains(&diagnostic) {
+                diagnostics.push(diagnostic);
+            }
+        }
+    }
+    by_file
+}
+
+/// The end of `output`, for reports of runs that failed for other reasons than races.
+pub fn output_tail(output: &str) -> String {
+    let mut start = output.len().saturating_sub(OUTPUT_TAIL_BYTES);
+    while !output.is_char_boundary(start) {
+        start += 1;
+    }
+    output[start..].trim().to_string()
+}
+
+fn lowercase_first(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,355 @@
+use std::collections::VecDeque;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+use tower_lsp::lsp_types::Position;
+
+/// Session files kept in the record directory; older ones are removed when a session starts.
+const MAX_SESSION_FILES: usize = 8;
+
+/// Sessions started by this process, numbering their files apart.
+static SESSIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Opt-in record mode: each analysis request, a hash of the document it ran on and the findings
+/// it produced are kept in a bounded on-disk log, exported with `goanalyzer/debug.dumpSession`.
+#[derive(Clone, Debug)]
+pub struct RecordConfig {
+    pub enabled: bool,
+    pub dir: PathBuf,
+    /// Entries kept; older ones are dropped first.
+    pub capacity: usize,
+}
+
+impl RecordConfig {
+    pub fn from_env() -> Self {
+        let enabled = match std::env::var("GO_ANALYZER_RECORD") {
+            Ok(v) => matches!(v.as_str(), "1" | "true" | "TRUE" | "yes" | "YES"),
+            Err(_) => false,
+        };
+        let dir = std::env::var("GO_ANALYZER_RECORD_DIR")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| std::env::temp_dir().join("go-analyzer-record"));
+        let capacity = std::env::var("GO_ANALYZER_RECORD_CAPACITY")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(200);
+        Self {
+            enabled,
+            dir,
+            capacity,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct RecordEntry {
+    pub timestamp_ms: u128,
+    pub trace_id: String,
+    pub method: String,
+    pub uri: String,
+    pub version: Option<i32>,
+    /// [`document_hash`] of the buffer the analysis ran on.
+    pub document_hash: String,
+    #[schemars(with = "Option<crate::protocol::lsp::Position>")]
+    pub position: Option<Position>,
+    pub findings: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct SessionDump {
+    pub enabled: bool,
+    pub path: String,
+    pub capacity: usize,
+    pub entries: Vec<RecordEntry>,
+}
+
+pub fn document_hash(code: &str) -> String {
+    format!("{:016x}-{}", stable_hash([code.as_bytes()]), code.len())
+}
+
+/// 64-bit FNV-1a of `parts`, each followed by its length so that moving bytes from one part to
+/// the next changes the hash. Unlike `DefaultHasher`, whose algorithm can change between Rust
+/// releases, it is the same in every build, so hashes written to disk stay valid.
+pub fn stable_hash<'a>(parts: impl IntoIterator<Item = &'a [u8]>) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut write = |bytes: &[u8]| {
+        for byte in bytes {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+    for part in parts {
+        write(part);
+        write(&(part.len() as u64).to_le_bytes());
+    }
+    hash
+}
+
+pub fn now_ms() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default()
+}
+
+/// Ring buffer of [`RecordEntry`] mirrored to `<dir>/session-<pid>-<n>.jsonl`, a file of its
+/// own, so sessions served at the same time (`--listen`) do not write over each other. New
+/// entries are appended; the file is compacted to the last `capacity` entries once it holds twice
+/// that many lines.
+pub struct SessionRecorder {
+    config: RecordConfig,
+    file_name: String,
+    entries: VecDeque<RecordEntry>,
+    lines_on_disk: usize,
+}
+
+impl SessionRecorder {
+    /// Resumes from the most recent earlier session file so entries survive a server restart.
+    pub fn new(config: RecordConfig) -> Self {
+        let session = SESSIONS.fetch_add(1, Ordering::Relaxed);
+        let mut recorder = Self {
+            config,
+            file_name: format!("session-{}-{}.jsonl", std::process::id(), session),
+            entries: VecDeque::new(),
+            lines_on_disk: 0,
+        };
+        if !recorder.config.enabled {
+            return recorder;
+        }
+        let mut earlier: Vec<(SystemTime, PathBuf)> = fs::read_dir(&recorder.config.dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                name.starts_with("session") && name.ends_with(".jsonl")
+            })
+            .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+            .collect();
+        earlier.sort_by(|a, b| b.cmp(a));
+        for (_, stale) in earlier.iter().skip(MAX_SESSION_FILES - 1) {
+            let _ = fs::remove_file(stale);
+        }
+        if let Some(text) = earlier
+            .first()
+            .and_then(|(_, latest)| fs::read_to_string(latest).ok())
+        {
+            for entry in text
+                .lines()
+                .filter_map(|line| serde_json::from_str::<RecordEntry>(line).ok())
+            {
+                recorder.push(entry);
+            }
+        }
+        recorder
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.config.dir
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.config.dir.join(&self.file_name)
+    }
+
+    pub fn record(&mut self, entry: RecordEntry) -> std::io::Result<()> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.config.dir)?;
+        let line = serde_json::to_string(&entry)?;
+        self.push(entry);
+        if self.lines_on_disk >= self.config.capacity * 2 {
+            return self.compact().map(drop);
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path())?;
+        writeln!(file, "{}", line)?;
+        self.lines_on_disk += 1;
+        Ok(())
+    }
+
+    /// Rewrites the session file with the entries held in memory, resumed ones included, and
+    /// syncs it to disk, so the next server resumes from a bounded file.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        if !self.config.enabled || self.entries.is_empty() {
+            return Ok(());
+        }
+        let file = if self.lines_on_disk != self.entries.len() {
+            self.compact()?
+        } else {
+            // Syncing needs write access on some platforms (Windows refuses a read-only handle).
+            OpenOptions::new().append(true).open(self.path())?
+        };
+        file.sync_all()
+    }
+
+    /// Rewrites the session file with just the entries held in memory, returning the handle
+    /// that wrote it.
+    fn compact(&mut self) -> std::io::Result<fs::File> {
+        let mut text = String::new();
+        for entry in &self.entries {
+            text.push_str(&serde_json::to_string(entry)?);
+            text.push('\n');
+        }
+        let mut file = fs::File::create(self.path())?;
+        file.write_all(text.as_bytes())?;
+        self.lines_on_disk = self.entries.len();
+        Ok(file)
+    }
+
+    pub fn dump(&self) -> SessionDump {
+        SessionDump {
+            enabled: self.config.enabled,
+            path: self.path().display().to_string(),
+            capacity: self.config.capacity,
+            entries: self.entries.iter().cloned().collect(),
+        }
+    }
+
+    fn push(&mut self, entry: RecordEntry) {
+        self.entries.push_back(entry);
+        while self.entries.len() > self.config.capacity {
+            self.entries.pop_front();
+        }
+    }
+}
+
+enum RecordCommand {
+    Record(RecordEntry),
+    Dump(oneshot::Sender<SessionDump>),
+    Flush(oneshot::Sender<std::io::Result<()>>),
+}
+
+/// A [`SessionRecorder`] run on a thread of its own, so requests hand their entries over
+/// without waiting for the disk. Commands are served in order; dropping the writer flushes the
+/// session file and waits for the thread.
+pub struct RecordWriter {
+    config: RecordConfig,
+    commands: Option<mpsc::Sender<RecordCommand>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl RecordWriter {
+    /// Starts the writer thread when record mode is on.
+    pub fn spawn(config: RecordConfig) -> Self {
+        let mut writer = Self {
+            config,
+            commands: None,
+            thread: None,
+        };
+        if !writer.config.enabled {
+            return writer;
+        }
+        let (sender, receiver) = mpsc::channel();
+        let config = writer.config.clone();
+        let spawned = std::thread::Builder::new()
+            .name("go-analyzer-record".to_string())
+            .spawn(move || run_recorder(SessionRecorder::new(config), receiver));
+        match spawned {
+            Ok(thread) => {
+                writer.commands = Some(sender);
+                writer.thread = Some(thread);
+            }
+            Err(e) => tracing::warn!("Failed to start the session recorder: {}", e),
+        }
+        writer
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.commands.is_some()
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.config.dir
+    }
+
+    /// Queues `entry`; a failed write is logged by the writer thread.
+    pub fn record(&self, entry: RecordEntry) {
+        if let Some(commands) = &self.commands {
+            let _ = commands.send(RecordCommand::Record(entry));
+        }
+    }
+
+    /// The entries recorded so far, once the queued ones are in.
+    pub async fn dump(&self) -> SessionDump {
+        let (reply, dump) = oneshot::channel();
+        match &self.commands {
+            Some(commands) if commands.send(RecordCommand::Dump(reply)).is_ok() => {
+                if let Ok(dump) = dump.await {
+                    return dump;
+                }
+            }
+            _ => {}
+        }
+        SessionRecorder::new(RecordConfig {
+            enabled: false,
+            ..self.config.clone()
+        })
+        .dump()
+    }
+
+    /// Writes the queued entries and syncs the session file, see [`SessionRecorder::flush`].
+    pub async fn flush(&self) -> std::io::Result<()> {
+        let Some(commands) = &self.commands else {
+            return Ok(());
+        };
+        let (reply, flushed) = oneshot::channel();
+        if commands.send(RecordCommand::Flush(reply)).is_err() {
+            return Err(std::io::Error::other("the session recorder stopped"));
+        }
+        flushed
+            .await
+            .unwrap_or_else(|_| Err(std::io::Error::other("the session recorder stopped")))
+    }
+}
+
+impl Drop for RecordWriter {
+    fn drop(&mut self) {
+        // The thread flushes once the commands end.
+        self.commands.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run_recorder(mut recorder: SessionRecorder, commands: mpsc::Receiver<RecordCommand>) {
+    for command in commands {
+        match command {
+            RecordCommand::Record(entry) => {
+                let trace_id = entry.trace_id.clone();
+                if let Err(e) = recorder.record(entry) {
+                    tracing::warn!("Failed to record analysis {}: {}", trace_id, e);
+                }
+            }
+            RecordCommand::Dump(reply) => {
+                let _ = reply.send(recorder.dump());
+            }
+            RecordCommand::Flush(reply) => {
+                let _ = reply.send(recorder.flush());
+            }
+        }
+    }
+    if let Err(e) = recorder.flush() {
+        tracing::warn!("Failed to flush the session record: {}", e);
+    }
+}
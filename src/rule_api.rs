@@ -0,0 +1,120 @@
+use std::cell::OnceCell;
+use std::sync::Arc;
+
+use serde_json::{Map, Value};
+use tower_lsp::lsp_types::{DiagnosticSeverity, Range};
+use tree_sitter::Tree;
+use url::Url;
+
+use crate::analysis::build_graph_data;
+use crate::callgraph::{collect_callables, Callable};
+use crate::rules::{rule_meta, RuleMeta};
+use crate::types::GraphData;
+
+/// A custom analysis run on every file next to the built-in passes. Implementations live in
+/// downstream crates and are handed to the server through a [`RuleRegistry`].
+pub trait Rule: Send + Sync {
+    /// Code, title and memory-model note; the code must not clash with a built-in rule.
+    fn meta(&self) -> RuleMeta;
+
+    /// Documentation linked from the diagnostic code. Built-in rules link into `docs/rules.md`.
+    fn docs_url(&self) -> Option<String> {
+        None
+    }
+
+    fn check(&self, input: &RuleInput) -> Vec<RuleFinding>;
+}
+
+/// What a rule sees of one file: the parsed tree, the source, lazily built symbol and call
+/// tables, and the rule's settings.
+pub struct RuleInput<'a> {
+    pub uri: &'a Url,
+    pub tree: &'a Tree,
+    pub code: &'a str,
+    /// The rule's entry in `GO_ANALYZER_RULE_SETTINGS`, `null` when absent.
+    pub settings: &'a Value,
+    symbols: OnceCell<GraphData>,
+}
+
+impl<'a> RuleInput<'a> {
+    pub fn new(uri: &'a Url, tree: &'a Tree, code: &'a str, settings: &'a Value) -> Self {
+        Self {
+            uri,
+            tree,
+            code,
+            settings,
+            symbols: OnceCell::new(),
+        }
+    }
+
+    /// Variables, functions, channels, goroutines and lock blocks of the file with their use,
+    /// call, send/receive and spawn edges, as served by `goanalyzer/graph`.
+    pub fn symbols(&self) -> &GraphData {
+        self.symbols
+            .get_or_init(|| build_graph_data(self.tree, self.code))
+    }
+
+    /// Functions, methods and func-literal variables declared in the file, for resolving calls
+    /// with [`crate::callgraph::call_target`].
+    pub fn callables(&self) -> Vec<Callable<'a>> {
+        collect_callables(self.tree.root_node(), self.code)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleFinding {
+    pub range: Range,
+    pub severity: DiagnosticSeverity,
+    pub message: String,
+    /// Other locations shown with the diagnostic.
+    pub related: Vec<(Range, String)>,
+}
+
+/// Custom rules plus per-rule settings. The default registry is empty; a server binary built on
+/// this crate registers its rules before handing the registry to `Backend::with_rules`.
+#[derive(Clone, Default)]
+pub struct RuleRegistry {
+    rules: Vec<Arc<dyn Rule>>,
+    settings: Map<String, Value>,
+}
+
+impl RuleRegistry {
+    /// An empty registry with settings from `GO_ANALYZER_RULE_SETTINGS`, a JSON object keyed by
+    /// rule code.
+    pub fn from_env() -> Self {
+        let settings = std::env::var("GO_ANALYZER_RULE_SETTINGS")
+            .ok()
+            .and_then(|raw| serde_json::from_str::<Value>(&raw).ok())
+            .and_then(|value| value.as_object().cloned())
+            .unwrap_or_default();
+        Self {
+            rules: Vec::new(),
+            settings,
+        }
+    }
+
+    pub fn register(&mut self, rule: impl Rule + 'static) -> Result<(), String> {
+        let code = rule.meta().code;
+        if rule_meta(code).is_some() {
+            return Err(format!("rule code `{}` is used by a built-in rule", code));
+        }
+        if self.rules.iter().any(|other| other.meta().code == code) {
+            return Err(format!("rule `{}` is already registered", code));
+        }
+        self.rules.push(Arc::new(rule));
+        Ok(())
+    }
+
+    pub fn set_settings(&mut self, code: &str, settings: Value) {
+        self.settings.insert(code.to_string(), settings);
+    }
+
+    pub fn rules(&self) -> &[Arc<dyn Rule>] {
+        &self.rules
+    }
+
+    pub fn settings(&self, code: &str) -> &Value {
+        static NULL: Value = Value::Null;
+        self.settings.get(code).unwrap_or(&NULL)
+    }
+}
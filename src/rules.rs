@@ -0,0 +1,260 @@
+pub const DEFAULT_RULE_DOCS_URL: &str =
+    "https://github.com/vremyavnikuda/go-analyzer-rs/blob/main/docs/rules.md";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RuleMeta {
+    pub code: &'static str,
+    pub title: &'static str,
+    /// The Go memory model guarantee (or pitfall) the rule is built on.
+    pub memory_model: &'static str,
+}
+
+pub const RULES: &[RuleMeta] = &[
+    RuleMeta {
+        code: "field-race-high",
+        title: "Unsynchronized access in goroutine",
+        memory_model: "Conflicting accesses without a happens-before edge are a data race",
+    },
+    RuleMeta {
+        code: "field-mixed-atomic",
+        title: "Mixed atomic and non-atomic access",
+        memory_model: "Atomic operations only synchronize with other atomic operations",
+    },
+    RuleMeta {
+        code: "alias-race",
+        title: "Variable accessed through a pointer in a goroutine",
+        memory_model: "A write through a pointer conflicts with every unsynchronized access of the pointee",
+    },
+    RuleMeta {
+        code: "mixed-atomic-access",
+        title: "Plain access to an atomically updated variable",
+        memory_model: "Atomic operations only synchronize with other atomic operations",
+    },
+    RuleMeta {
+        code: "atomic-value-type",
+        title: "atomic.Value stores values of different types",
+        memory_model: "atomic.Value fixes its concrete type at the first Store",
+    },
+    RuleMeta {
+        code: "field-lock-coverage",
+        title: "Partial lock coverage",
+        memory_model: "A mutex orders only the accesses performed while it is held",
+    },
+    RuleMeta {
+        code: "field-heavy-under-lock",
+        title: "Heavy work under lock",
+        memory_model: "Critical sections serialize goroutines for their whole duration",
+    },
+    RuleMeta {
+        code: "field-retention",
+        title: "Memory retention through sub-slice or map reference",
+        memory_model: "Values stay reachable while any reference to their backing storage exists",
+    },
+    RuleMeta {
+        code: "field-write-only",
+        title: "Write-only field",
+        memory_model: "Writes that are never read cannot publish state to other goroutines",
+    },
+    RuleMeta {
+        code: "field-read-before-write",
+        title: "Read before first write",
+        memory_model: "Within one goroutine, program order defines what a read observes",
+    },
+    RuleMeta {
+        code: "interprocedural-race",
+        title: "Unsynchronized write reached from a goroutine call",
+        memory_model:
+            "Calling a function adds no happens-before edge: its writes race like inline ones",
+    },
+    RuleMeta {
+        code: "global-race",
+        title: "Package-level variable shared by goroutines without synchronization",
+        memory_model: "Conflicting accesses without a happens-before edge are a data race",
+    },
+    RuleMeta {
+        code: "lock-value-constructor",
+        title: "Lock-holding struct returned by value",
+        memory_model:
+            "A copied mutex is a separate lock: it orders nothing done under the original",
+    },
+    RuleMeta {
+        code: "lock-copy",
+        title: "Lock copied by value",
+        memory_model:
+            "A copied mutex is a separate lock: it orders nothing done under the original",
+    },
+    RuleMeta {
+        code: "lock-missing-unlock",
+        title: "Mutex locked but never unlocked",
+        memory_model:
+            "Only an Unlock lets the next Lock proceed; a mutex left locked blocks every later locker",
+    },
+    RuleMeta {
+        code: "lock-unlock-not-deferred",
+        title: "Early exit skips the Unlock",
+        memory_model:
+            "A return or panic between Lock and Unlock leaves the mutex held; a deferred Unlock runs on every exit",
+    },
+    RuleMeta {
+        code: "lock-double-lock",
+        title: "Mutex locked again while held",
+        memory_model:
+            "sync.Mutex is not reentrant: a Lock by the goroutine already holding it waits for itself",
+    },
+    RuleMeta {
+        code: "lock-order-cycle",
+        title: "Mutexes acquired in conflicting orders",
+        memory_model:
+            "A goroutine blocked in Lock keeps every mutex it holds, so two goroutines taking the same mutexes in opposite orders can wait on each other forever",
+    },
+    RuleMeta {
+        code: "cond-wait-outside-loop",
+        title: "sync.Cond Wait not in a loop re-checking the condition",
+        memory_model:
+            "Signal happens before the woken Wait returns, but other goroutines may run in between",
+    },
+    RuleMeta {
+        code: "cond-wait-without-lock",
+        title: "sync.Cond Wait without holding its lock",
+        memory_model: "Wait unlocks c.L before suspending and locks it again before returning",
+    },
+    RuleMeta {
+        code: "waitgroup-add-done-mismatch",
+        title: "WaitGroup Add/Done count mismatch",
+        memory_model: "Wait returns only when the counter reaches zero; each Add needs a Done",
+    },
+    RuleMeta {
+        code: "waitgroup-add-in-goroutine",
+        title: "WaitGroup Add inside the guarded goroutine",
+        memory_model: "Add must happen before Wait; inside the goroutine it is unordered with it",
+    },
+    RuleMeta {
+        code: "waitgroup-wait-unsatisfiable",
+        title: "WaitGroup Wait without Done",
+        memory_model: "Wait blocks until Done balances every Add, so it never returns",
+    },
+    RuleMeta {
+        code: "channel-deadlock",
+        title: "Unbuffered channel operation without a partner goroutine",
+        memory_model: "An unbuffered send completes only together with a matching receive",
+    },
+    RuleMeta {
+        code: "channel-send-after-close",
+        title: "Send on closed channel",
+        memory_model: "A close happens before any later operation on the channel; sends then panic",
+    },
+    RuleMeta {
+        code: "channel-double-close",
+        title: "Channel closed more than once",
+        memory_model:
+            "A channel can be closed once; a second close panics whichever goroutine runs it",
+    },
+    RuleMeta {
+        code: "channel-nil",
+        title: "Operation on a nil channel",
+        memory_model: "Sends and receives on a nil channel never proceed; closing it panics",
+    },
+    RuleMeta {
+        code: "channel-range-not-closed",
+        title: "Ranged-over channel never closed",
+        memory_model:
+            "A range over a channel ends only after close; receives otherwise wait for a send that never comes",
+    },
+    RuleMeta {
+        code: "channel-close-by-producer",
+        title: "Channel closed while other producers send",
+        memory_model:
+            "Nothing orders one producer's close before another goroutine's send, and a send on a closed channel panics",
+    },
+    RuleMeta {
+        code: "goroutine-leak",
+        title: "Goroutine blocked forever",
+        memory_model:
+            "A goroutine blocked on a channel no one else uses is never resumed or collected",
+    },
+    RuleMeta {
+        code: "loop-variable-capture",
+        title: "Goroutine captures a loop variable",
+        memory_model:
+            "Before Go 1.22 a loop variable is shared by all iterations; goroutines reading it race with the loop's next update",
+    },
+    RuleMeta {
+        code: "concurrent-map-access",
+        title: "Map used by several goroutines without a mutex",
+        memory_model:
+            "Maps are not safe for concurrent use; the runtime aborts on a write racing another access",
+    },
+    RuleMeta {
+        code: "time-after-in-loop",
+        title: "time.After in a select inside a loop",
+        memory_model:
+            "Timers are only released once they fire; the channel returned by time.After keeps its timer alive",
+    },
+    RuleMeta {
+        code: "select-busy-wait",
+        title: "select with only a default case",
+        memory_model: "A select without communication cases never blocks and orders nothing",
+    },
+    RuleMeta {
+        code: "unbounded-goroutines",
+        title: "Goroutine started on every loop iteration without a limit",
+        memory_model:
+            "Goroutines are cheap but not free; nothing in the language bounds how many run at once",
+    },
+    RuleMeta {
+        code: "unjoined-goroutine",
+        title: "Goroutine still writing after its function returns",
+        memory_model:
+            "A function's return is not synchronized with goroutines it started; only a join orders their writes",
+    },
+    RuleMeta {
+        code: "goroutine-panic",
+        title: "Goroutine can panic without a deferred recover",
+        memory_model:
+            "A panic unwinds only its own goroutine's stack; unrecovered, it terminates the program",
+    },
+    RuleMeta {
+        code: "sleep-synchronization",
+        title: "time.Sleep used to wait for a goroutine",
+        memory_model: "Elapsed time is not a synchronization event; sleeping orders nothing",
+    },
+    RuleMeta {
+        code: "struct-large-copy",
+        title: "Large struct copied by value",
+        memory_model: "Copies are independent: writes to the copy are invisible to the original",
+    },
+    RuleMeta {
+        code: "confirmed-race",
+        title: "Data race reported by go test -race",
+        memory_model: "Conflicting accesses without a happens-before edge are a data race",
+    },
+];
+
+pub fn rule_meta(code: &str) -> Option<&'static RuleMeta> {
+    RULES.iter().find(|rule| rule.code == code)
+}
+
+#[derive(Clone, Debug)]
+pub struct RuleDocsConfig {
+    pub base_url: String,
+}
+
+impl RuleDocsConfig {
+    pub fn from_env() -> Self {
+        let base_url = std::env::var("GO_ANALYZER_RULE_DOCS_URL")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_RULE_DOCS_URL.to_string());
+        Self { base_url }
+    }
+
+    /// Documentation link for a registered rule code, anchored at the rule's section.
+    pub fn href(&self, code: &str) -> Option<String> {
+        let rule = rule_meta(code)?;
+        Some(format!(
+            "{}#{}",
+            self.base_url.trim_end_matches('#'),
+            rule.code
+        ))
+    }
+}
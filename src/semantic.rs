@@ -1,19 +1,35 @@
+use futures::future::{BoxFuture, FutureExt, Shared};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex, PoisonError};
 use std::time::Duration;
-use tokio::io::AsyncWriteExt;
-use tokio::process::Command;
-use tower_lsp::lsp_types::{Position, Range};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::{oneshot, Mutex, Semaphore};
+use tower_lsp::lsp_types::{Location, Position, Range};
 use url::Url;
 
+use crate::bootstrap::{default_build_dir, helper_version, locate_helper, BuildConsent};
 use crate::types::{RaceSeverity, VarId, VariableInfo};
 
 #[derive(Clone, Debug)]
 pub struct SemanticConfig {
     pub enabled: bool,
+    /// A path to the helper, or a command name looked up as [`locate_helper`] describes.
     pub helper_path: String,
     pub timeout_ms: u64,
+    /// Requests the helper works on at once.
+    pub max_in_flight: usize,
+    /// How long a request waits for a newer one for the same document before running.
+    pub coalesce_ms: u64,
+    /// Whether a missing helper may be built from the bundled sources.
+    pub build: BuildConsent,
+    /// Where a built helper goes.
+    pub build_dir: PathBuf,
 }
 
 impl SemanticConfig {
@@ -30,22 +46,79 @@ impl SemanticConfig {
             .ok()
             .and_then(|v| v.parse::<u64>().ok())
             .unwrap_or(2000);
+        let max_in_flight = std::env::var("GO_ANALYZER_SEMANTIC_MAX_IN_FLIGHT")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(2);
+        let coalesce_ms = std::env::var("GO_ANALYZER_SEMANTIC_COALESCE_MS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(50);
         Self {
             enabled,
             helper_path,
             timeout_ms,
+            max_in_flight,
+            coalesce_ms,
+            build: BuildConsent::from_env(),
+            build_dir: default_build_dir(),
         }
     }
 }
 
+/// One JSON-RPC request to the helper daemon, written as a single line.
 #[derive(Serialize)]
-struct SemanticRequest {
-    file: String,
+struct RpcRequest<'a> {
+    jsonrpc: &'static str,
+    id: u64,
+    method: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    params: Option<ResolveParams<'a>>,
+}
+
+#[derive(Serialize)]
+struct ResolveParams<'a> {
+    file: &'a str,
     line: u32,
     col: u32,
-    content: String,
+    hash: &'a str,
+    /// Omitted when the daemon already has the content with this hash.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<&'a str>,
+    /// The package directory, so uses in the package's other files are returned too. The
+    /// helper reads those files from disk.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dir: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct RpcResponse {
+    id: u64,
+    #[serde(default)]
+    result: Option<serde_json::Value>,
+    #[serde(default)]
+    error: Option<RpcError>,
+}
+
+/// The answer to the `version` handshake.
+#[derive(Deserialize)]
+struct VersionResult {
+    version: String,
+}
+
+/// How long a starting helper has to answer the version handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
 }
 
+/// Error code of a request that referred to content the daemon was not sent.
+const UNKNOWN_CONTENT: i64 = -32001;
+
 #[derive(Deserialize)]
 struct SemanticPos {
     line: u32,
@@ -60,6 +133,9 @@ struct SemanticRange {
 
 #[derive(Deserialize)]
 struct SemanticUseEntry {
+    /// Set for a use in another file than the one requested.
+    #[serde(default)]
+    file: Option<String>,
     range: SemanticRange,
     reassign: bool,
     captured: bool,
@@ -69,8 +145,13 @@ struct SemanticUseEntry {
 struct SemanticResponse {
     name: String,
     decl: SemanticRange,
+    /// Set when the declaration is in another file than the one requested.
+    #[serde(default)]
+    decl_file: Option<String>,
     uses: Vec<SemanticUseEntry>,
     is_pointer: bool,
+    #[serde(default, rename = "type")]
+    type_name: Option<String>,
 }
 
 #[derive(Clone, Debug)]
@@ -83,7 +164,10 @@ pub struct SemanticUse {
 #[derive(Clone, Debug)]
 pub struct SemanticVariable {
     pub info: VariableInfo,
+    /// The uses in the requested document; those elsewhere in the package are in
+    /// `info.package_uses`.
     pub uses: Vec<SemanticUse>,
+    pub type_name: Option<String>,
 }
 
 fn map_range(range: SemanticRange) -> Range {
@@ -93,69 +177,509 @@ fn map_range(range: SemanticRange) -> Range {
     )
 }
 
-pub async fn resolve_semantic_variable(
-    config: &SemanticConfig,
-    uri: &Url,
-    position: Position,
-    code: &str,
-) -> Option<SemanticVariable> {
-    if !config.enabled {
-        return None;
-    }
-    let file_path = uri.to_file_path().ok()?;
-    let request = SemanticRequest {
-        file: path_to_string(&file_path),
-        line: position.line,
-        col: position.character,
-        content: code.to_string(),
-    };
-    let input = serde_json::to_vec(&request).ok()?;
-    let mut child = Command::new(&config.helper_path)
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .ok()?;
-    if let Some(stdin) = child.stdin.as_mut() {
-        if stdin.write_all(&input).await.is_err() {
+impl SemanticResponse {
+    fn into_variable(self) -> SemanticVariable {
+        let declaration = map_range(self.decl);
+        let mut uses = Vec::new();
+        let mut package_uses = Vec::new();
+        for entry in self.uses {
+            match entry.file.as_deref().map(file_url) {
+                None => uses.push(SemanticUse {
+                    range: map_range(entry.range),
+                    reassign: entry.reassign,
+                    captured: entry.captured,
+                }),
+                Some(Some(uri)) => package_uses.push(Location::new(uri, map_range(entry.range))),
+                Some(None) => {}
+            }
+        }
+        package_uses.sort_by(|a: &Location, b: &Location| {
+            (a.uri.as_str(), a.range.start).cmp(&(b.uri.as_str(), b.range.start))
+        });
+        let info = VariableInfo {
+            name: self.name,
+            declaration,
+            uses: uses.iter().map(|u| u.range).collect(),
+            is_pointer: self.is_pointer,
+            potential_race: false,
+            race_severity: RaceSeverity::Medium,
+            var_id: VarId {
+                start_byte: 0,
+                end_byte: 0,
+            },
+            declared_in: self.decl_file.as_deref().and_then(file_url),
+            package_uses,
+        };
+        SemanticVariable {
+            info,
+            uses,
+            type_name: self.type_name.filter(|t| !t.is_empty()),
+        }
+    }
+}
+
+/// The long-lived `goanalyzer-semantic --daemon` process. Requests are JSON-RPC messages, one
+/// per line on its stdin, answered on its stdout by id in whatever order they finish, so any
+/// number can be in flight. A document's text is sent only when it changed since the last
+/// request for that file. The helper starts with the first request and, after it exits or
+/// crashes, again with the next one. A helper whose version handshake does not report
+/// [`helper_version`] was built from other sources and is not run.
+pub struct SemanticHelper {
+    config: SemanticConfig,
+    /// The helper found or built; looked up again while there is none.
+    path: StdMutex<Option<PathBuf>>,
+    /// Helpers that failed the version handshake, passed over when looking one up.
+    rejected: StdMutex<Vec<PathBuf>>,
+    process: Mutex<Option<Arc<HelperProcess>>>,
+}
+
+impl SemanticHelper {
+    pub fn new(config: SemanticConfig) -> Self {
+        Self {
+            config,
+            path: StdMutex::new(None),
+            rejected: StdMutex::new(Vec::new()),
+            process: Mutex::new(None),
+        }
+    }
+
+    pub fn config(&self) -> &SemanticConfig {
+        &self.config
+    }
+
+    /// Starts the helper unless it is running; `false` when there is no current helper to run.
+    pub async fn start(&self) -> bool {
+        self.process().await.is_some()
+    }
+
+    /// Runs `path` from the next start on, such as a helper built after the server started.
+    pub fn set_path(&self, path: PathBuf) {
+        self.rejected
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|rejected| *rejected != path);
+        *self.path.lock().unwrap_or_else(PoisonError::into_inner) = Some(path);
+    }
+
+    fn path(&self) -> Option<PathBuf> {
+        let mut path = self.path.lock().unwrap_or_else(PoisonError::into_inner);
+        if path.is_none() {
+            let rejected = self.rejected.lock().unwrap_or_else(PoisonError::into_inner);
+            *path = locate_helper(&self.config.helper_path, &self.config.build_dir, &rejected);
+        }
+        path.clone()
+    }
+
+    /// Passes over `path` from now on and looks up another helper.
+    fn reject(&self, path: PathBuf) {
+        self.rejected
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(path);
+        *self.path.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    /// The variable at `position`, with its uses across the document's package; `None` when
+    /// semantic analysis is off, the helper finds nothing, fails or does not answer within the
+    /// timeout.
+    pub async fn resolve(
+        &self,
+        uri: &Url,
+        position: Position,
+        code: &str,
+    ) -> Option<SemanticVariable> {
+        if !self.config.enabled {
+            return None;
+        }
+        let path = uri.to_file_path().ok()?;
+        let file = path_to_string(&path);
+        let dir = path.parent().map(path_to_string);
+        let process = self.process().await?;
+        let (id, answer) = process.register()?;
+        if let Err(e) = process
+            .send(id, &file, dir.as_deref(), position, code)
+            .await
+        {
+            tracing::warn!("Failed to write to the semantic helper: {}", e);
+            process.forget(id);
+            return None;
+        }
+        let response =
+            match tokio::time::timeout(Duration::from_millis(self.config.timeout_ms), answer).await
+            {
+                Ok(Ok(response)) => response,
+                // The helper exited, or is still busy; a late answer is dropped.
+                _ => {
+                    process.forget(id);
+                    return None;
+                }
+            };
+        if let Some(error) = response.error {
+            tracing::debug!("Semantic helper failed: {}", error.message);
+            if error.code == UNKNOWN_CONTENT {
+                process.forget_content(&file).await;
+            }
             return None;
         }
+        match serde_json::from_value::<Option<SemanticResponse>>(response.result?) {
+            Ok(result) => result.map(SemanticResponse::into_variable),
+            Err(e) => {
+                tracing::warn!("Unexpected answer from the semantic helper: {}", e);
+                None
+            }
+        }
     }
-    let output = tokio::time::timeout(
-        Duration::from_millis(config.timeout_ms),
-        child.wait_with_output(),
-    )
-    .await
-    .ok()?
-    .ok()?;
-    if !output.status.success() {
-        return None;
-    }
-    let response: Option<SemanticResponse> = serde_json::from_slice(&output.stdout).ok()?;
-    let response = response?;
-    let declaration = map_range(response.decl);
-    let uses: Vec<SemanticUse> = response
-        .uses
-        .into_iter()
-        .map(|entry| SemanticUse {
-            range: map_range(entry.range),
-            reassign: entry.reassign,
-            captured: entry.captured,
+
+    /// Stops the helper; the next request starts it again.
+    pub async fn stop(&self) {
+        // Closing its stdin ends the daemon once the requests it is working on are answered.
+        self.process.lock().await.take();
+    }
+
+    /// The running helper, started now if it is not running.
+    async fn process(&self) -> Option<Arc<HelperProcess>> {
+        let mut process = self.process.lock().await;
+        if let Some(running) = process.as_ref().filter(|running| !running.has_exited()) {
+            return Some(running.clone());
+        }
+        let restart = process.take().is_some();
+        loop {
+            let Some(path) = self.path() else {
+                tracing::debug!("Semantic helper `{}` not found", self.config.helper_path);
+                return None;
+            };
+            match HelperProcess::start(&path).await {
+                Ok(started) => {
+                    if restart {
+                        tracing::info!("Restarted the semantic helper");
+                    }
+                    return Some(process.insert(Arc::new(started)).clone());
+                }
+                Err(e) if e.kind() == ErrorKind::InvalidData => {
+                    tracing::warn!(
+                        "Not running the stale semantic helper {}: {}",
+                        path.display(),
+                        e
+                    );
+                    self.reject(path);
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "Failed to start the semantic helper {}: {}",
+                        path.display(),
+                        e
+                    );
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// Requests waiting for an answer, by id.
+#[derive(Default)]
+struct Pending {
+    waiters: HashMap<u64, oneshot::Sender<RpcResponse>>,
+    /// Set when the helper's stdout closed; nothing will be answered anymore.
+    exited: bool,
+}
+
+struct HelperProcess {
+    /// Dropping the last reference closes stdin, which stops the daemon.
+    input: Mutex<HelperInput>,
+    pending: Arc<StdMutex<Pending>>,
+    next_id: AtomicU64,
+}
+
+struct HelperInput {
+    stdin: ChildStdin,
+    /// Hash of the content last sent per file.
+    sent: HashMap<String, String>,
+}
+
+impl HelperProcess {
+    /// Spawns the daemon and runs the version handshake; a helper that fails it is stopped and
+    /// reported with [`ErrorKind::InvalidData`].
+    async fn start(helper_path: &Path) -> std::io::Result<Self> {
+        let mut child = Command::new(helper_path)
+            .arg("--daemon")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let missing = || std::io::Error::other("helper stdio is not piped");
+        let mut stdin = child.stdin.take().ok_or_else(missing)?;
+        let mut stdout = BufReader::new(child.stdout.take().ok_or_else(missing)?);
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    tracing::debug!("semantic helper: {}", line);
+                }
+            });
+        }
+        handshake(&mut stdin, &mut stdout).await?;
+        let pending = Arc::new(StdMutex::new(Pending::default()));
+        tokio::spawn(read_responses(child, stdout, pending.clone()));
+        Ok(Self {
+            input: Mutex::new(HelperInput {
+                stdin,
+                sent: HashMap::new(),
+            }),
+            pending,
+            next_id: AtomicU64::new(1),
         })
-        .collect();
-    let info = VariableInfo {
-        name: response.name,
-        declaration,
-        uses: uses.iter().map(|u| u.range).collect(),
-        is_pointer: response.is_pointer,
-        potential_race: false,
-        race_severity: RaceSeverity::Medium,
-        var_id: VarId {
-            start_byte: 0,
-            end_byte: 0,
-        },
+    }
+
+    fn has_exited(&self) -> bool {
+        self.pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .exited
+    }
+
+    /// A new request id and the receiver of its answer; `None` once the helper has exited.
+    fn register(&self) -> Option<(u64, oneshot::Receiver<RpcResponse>)> {
+        let mut pending = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+        if pending.exited {
+            return None;
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = oneshot::channel();
+        pending.waiters.insert(id, sender);
+        Some((id, receiver))
+    }
+
+    fn forget(&self, id: u64) {
+        self.pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .waiters
+            .remove(&id);
+    }
+
+    async fn forget_content(&self, file: &str) {
+        self.input.lock().await.sent.remove(file);
+    }
+
+    async fn send(
+        &self,
+        id: u64,
+        file: &str,
+        dir: Option<&str>,
+        position: Position,
+        code: &str,
+    ) -> std::io::Result<()> {
+        let hash = crate::record::document_hash(code);
+        let mut input = self.input.lock().await;
+        let content = (input.sent.get(file) != Some(&hash)).then_some(code);
+        let mut line = serde_json::to_vec(&RpcRequest {
+            jsonrpc: "2.0",
+            id,
+            method: "resolve",
+            params: Some(ResolveParams {
+                file,
+                line: position.line,
+                col: position.character,
+                hash: &hash,
+                content,
+                dir,
+            }),
+        })?;
+        line.push(b'\n');
+        input.stdin.write_all(&line).await?;
+        input.stdin.flush().await?;
+        input.sent.insert(file.to_string(), hash);
+        Ok(())
+    }
+}
+
+/// Asks a helper that just started which sources it was built from, as request id 0. Anything
+/// but [`helper_version`] in answer, including an error or no answer before the helper exits,
+/// means it is stale.
+async fn handshake(
+    stdin: &mut ChildStdin,
+    stdout: &mut BufReader<ChildStdout>,
+) -> std::io::Result<()> {
+    let mut request = serde_json::to_vec(&RpcRequest {
+        jsonrpc: "2.0",
+        id: 0,
+        method: "version",
+        params: None,
+    })?;
+    request.push(b'\n');
+    let mut line = String::new();
+    let exchange = async {
+        stdin.write_all(&request).await?;
+        stdin.flush().await?;
+        stdout.read_line(&mut line).await
     };
-    Some(SemanticVariable { info, uses })
+    let stale = |reason: String| std::io::Error::new(ErrorKind::InvalidData, reason);
+    match tokio::time::timeout(HANDSHAKE_TIMEOUT, exchange).await {
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => return Err(stale(format!("the version handshake failed: {}", e))),
+        Err(_) => {
+            return Err(std::io::Error::new(
+                ErrorKind::TimedOut,
+                "no answer to the version handshake",
+            ))
+        }
+    }
+    let expected = helper_version();
+    let version = serde_json::from_str::<RpcResponse>(&line)
+        .ok()
+        .and_then(|response| response.result)
+        .and_then(|result| serde_json::from_value::<VersionResult>(result).ok())
+        .map(|result| result.version);
+    match version {
+        Some(version) if version == expected => Ok(()),
+        version => Err(stale(format!(
+            "it reports version {}, the server needs {}",
+            version.as_deref().unwrap_or("none"),
+            expected
+        ))),
+    }
+}
+
+/// Hands each answer on the helper's stdout to the request waiting for it. When stdout closes,
+/// the waiting requests are answered with nothing and the process is reaped.
+async fn read_responses(
+    mut child: Child,
+    stdout: BufReader<ChildStdout>,
+    pending: Arc<StdMutex<Pending>>,
+) {
+    let mut lines = stdout.lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let response: RpcResponse = match serde_json::from_str(&line) {
+            Ok(response) => response,
+            Err(e) => {
+                tracing::warn!("Unexpected output from the semantic helper: {}", e);
+                continue;
+            }
+        };
+        let waiter = pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .waiters
+            .remove(&response.id);
+        if let Some(waiter) = waiter {
+            let _ = waiter.send(response);
+        }
+    }
+    {
+        let mut pending = pending.lock().unwrap_or_else(PoisonError::into_inner);
+        pending.exited = true;
+        pending.waiters.clear();
+    }
+    match child.wait().await {
+        Ok(status) if status.success() => tracing::debug!("Semantic helper stopped"),
+        Ok(status) => tracing::warn!("Semantic helper exited with {}", status),
+        Err(e) => tracing::warn!("Failed to wait for the semantic helper: {}", e),
+    }
+}
+
+type SemanticKey = (Url, u32, u32, String);
+type SharedResolve = Shared<BoxFuture<'static, Option<SemanticVariable>>>;
+
+/// Front of [`SemanticHelper`] that keeps typing from sending it a request per keystroke:
+/// identical requests share one, a request superseded by a newer one for the same document and
+/// lane (`"hover"`, `"cursor"`, ...) during the coalescing window or while queued is dropped,
+/// and at most `max_in_flight` requests are in flight at once.
+pub struct SemanticCoalescer {
+    pub config: SemanticConfig,
+    helper: Arc<SemanticHelper>,
+    generations: Arc<Mutex<HashMap<(Url, String), u64>>>,
+    in_flight: Mutex<HashMap<SemanticKey, SharedResolve>>,
+    permits: Arc<Semaphore>,
+}
+
+impl SemanticCoalescer {
+    pub fn new(config: SemanticConfig) -> Self {
+        let permits = Arc::new(Semaphore::new(config.max_in_flight.max(1)));
+        Self {
+            helper: Arc::new(SemanticHelper::new(config.clone())),
+            config,
+            generations: Arc::new(Mutex::new(HashMap::new())),
+            in_flight: Mutex::new(HashMap::new()),
+            permits,
+        }
+    }
+
+    pub fn helper(&self) -> Arc<SemanticHelper> {
+        self.helper.clone()
+    }
+
+    /// `None` when semantic analysis is off, fails, or the request was superseded.
+    pub async fn resolve(
+        &self,
+        lane: &str,
+        uri: &Url,
+        position: Position,
+        code: &str,
+    ) -> Option<SemanticVariable> {
+        if !self.config.enabled {
+            return None;
+        }
+        let key: SemanticKey = (
+            uri.clone(),
+            position.line,
+            position.character,
+            crate::record::document_hash(code),
+        );
+        let joined = self.in_flight.lock().await.get(&key).cloned();
+        if let Some(shared) = joined {
+            let result = shared.await;
+            self.in_flight.lock().await.remove(&key);
+            return result;
+        }
+        let lane_key = (uri.clone(), lane.to_string());
+        let generation = {
+            let mut generations = self.generations.lock().await;
+            let generation = generations.entry(lane_key.clone()).or_insert(0);
+            *generation += 1;
+            *generation
+        };
+        tokio::time::sleep(Duration::from_millis(self.config.coalesce_ms)).await;
+
+        let generations = self.generations.clone();
+        let superseded = move || {
+            let generations = generations.clone();
+            let lane_key = lane_key.clone();
+            async move { generations.lock().await.get(&lane_key) != Some(&generation) }
+        };
+        if superseded().await {
+            return None;
+        }
+        let permits = self.permits.clone();
+        let helper = self.helper.clone();
+        let (uri, code) = (uri.clone(), code.to_string());
+        let shared = async move {
+            let _permit = permits.acquire_owned().await.ok()?;
+            if superseded().await {
+                return None;
+            }
+            helper.resolve(&uri, position, &code).await
+        }
+        .boxed()
+        .shared();
+        self.in_flight
+            .lock()
+            .await
+            .insert(key.clone(), shared.clone());
+        let result = shared.await;
+        self.in_flight.lock().await.remove(&key);
+        result
+    }
+
+    /// Stops the helper process; see [`SemanticHelper::stop`].
+    pub async fn stop(&self) {
+        self.helper.stop().await;
+    }
+}
+
+fn file_url(file: &str) -> Option<Url> {
+    Url::from_file_path(file).ok()
 }
 
 fn path_to_string(path: &Path) -> String {
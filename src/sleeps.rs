@@ -0,0 +1,155 @@
+use tower_lsp::lsp_types::Range;
+use tree_sitter::{Node, Tree};
+
+use crate::analysis::{check_tree, text};
+use crate::atomics::is_write;
+use crate::callgraph::{collect_kind, local_names};
+use crate::error::AnalysisResult;
+use crate::joins::{is_joined, own_function, root_identifier, writes_in};
+use crate::util::node_to_range;
+
+/// `time.Sleep` in the function that started a goroutine, followed by a read of something the
+/// goroutine writes, with nothing else waiting for it. Sleeping only makes the write likely to
+/// have happened; it creates no happens-before edge, so the read is still a data race.
+#[derive(Debug, Clone)]
+pub struct SleepSynchronization {
+    /// The `time.Sleep(...)` call.
+    pub range: Range,
+    pub variable: String,
+    /// The write in the goroutine.
+    pub write: Range,
+    /// The read after the sleep, or in the condition of a loop polling with it.
+    pub read: Range,
+}
+
+impl SleepSynchronization {
+    pub fn message(&self) -> String {
+        format!(
+            "`time.Sleep` does not wait for the goroutine writing `{}`: sleeping creates no happens-before edge, so the read after it is still a data race; use a WaitGroup or a channel",
+            self.variable
+        )
+    }
+}
+
+pub fn find_sleep_synchronization(
+    tree: &Tree,
+    code: &str,
+) -> AnalysisResult<Vec<SleepSynchronization>> {
+    check_tree(tree, code)?;
+    let mut go_statements = Vec::new();
+    collect_kind(tree.root_node(), "go_statement", &mut go_statements);
+    let mut findings: Vec<SleepSynchronization> = Vec::new();
+    for go_stmt in go_statements {
+        let Some(function) = own_function(go_stmt) else {
+            continue;
+        };
+        let Some(literal) = go_stmt
+            .named_child(0)
+            .and_then(|call| call.child_by_field_name("function"))
+            .filter(|function| function.kind() == "func_literal")
+        else {
+            continue;
+        };
+        if is_joined(function, go_stmt, code) {
+            continue;
+        }
+        let own = |node: Node| own_function(node) == Some(function);
+        let mut calls = Vec::new();
+        collect_kind(function, "call_expression", &mut calls);
+        let sleeps: Vec<Node> = calls
+            .into_iter()
+            .filter(|call| {
+                call.start_byte() >= go_stmt.end_byte()
+                    && own(*call)
+                    && call
+                        .child_by_field_name("function")
+                        .is_some_and(|callee| text(code, callee) == "time.Sleep")
+            })
+            .collect();
+        if sleeps.is_empty() {
+            continue;
+        }
+        let goroutine_locals = local_names(literal, code);
+        let mut identifiers = Vec::new();
+        collect_kind(function, "identifier", &mut identifiers);
+        for write in writes_in(literal) {
+            let Some(root) = root_identifier(write) else {
+                continue;
+            };
+            let variable = text(code, root);
+            if goroutine_locals.contains(variable) {
+                continue;
+            }
+            let found = sleeps.iter().find_map(|sleep| {
+                identifiers
+                    .iter()
+                    .find(|ident| {
+                        text(code, **ident) == variable
+                            && own(**ident)
+                            && !is_written(**ident)
+                            && (ident.start_byte() >= sleep.end_byte()
+                                || in_polling_condition(**ident, *sleep))
+                    })
+                    .map(|read| (*sleep, *read))
+            });
+            let Some((sleep, read)) = found else {
+                continue;
+            };
+            let range = node_to_range(sleep);
+            if findings.iter().any(|finding| finding.range == range) {
+                continue;
+            }
+            findings.push(SleepSynchronization {
+                range,
+                variable: variable.to_string(),
+                write: node_to_range(write),
+                read: node_to_range(read),
+            });
+        }
+    }
+    Ok(findings)
+}
+
+/// `x`, `x.f` or `x[i]` assigned, rather than read.
+fn is_written(ident: Node) -> bool {
+    let mut node = ident;
+    while let Some(parent) = node.parent() {
+        if !matches!(parent.kind(), "selector_expression" | "index_expression")
+            || parent.child_by_field_name("operand") != Some(node)
+        {
+            break;
+        }
+        node = parent;
+    }
+    is_write(node)
+}
+
+/// `for !done { time.Sleep(...) }`: the read in the condition runs again after every sleep.
+fn in_polling_condition(ident: Node, sleep: Node) -> bool {
+    let mut current = sleep.parent();
+    while let Some(ancestor) = current {
+        match ancestor.kind() {
+            "for_statement" => {
+                let condition = ancestor.named_child(0).and_then(|clause| {
+                    if clause.kind() == "for_clause" {
+                        clause.child_by_field_name("condition")
+                    } else if clause.kind() == "block" || clause.kind() == "range_clause" {
+                        None
+                    } else {
+                        Some(clause)
+                    }
+                });
+                if condition.is_some_and(|condition| {
+                    condition.start_byte() <= ident.start_byte()
+                        && ident.end_byte() <= condition.end_byte()
+                }) {
+                    return true;
+                }
+            }
+            "func_literal" | "function_declaration" | "method_declaration" => return false,
+            _ => {}
+        }
+        current = ancestor.parent();
+    }
+    false
+}
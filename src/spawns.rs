@@ -0,0 +1,173 @@
+use tower_lsp::lsp_types::{Position, Range};
+use tree_sitter::{Node, Tree};
+
+use crate::analysis::{check_tree, text};
+use crate::callgraph::collect_kind;
+use crate::error::AnalysisResult;
+use crate::timers::{enclosing_loop, line_indent, unused_name};
+use crate::util::node_to_range;
+
+/// Goroutines started at once when the quick fix bounds a loop with a semaphore.
+const SEMAPHORE_SIZE: usize = 8;
+
+/// A `go` statement in a loop whose iteration count depends on the input (a `range` over a
+/// collection or channel, a `len(...)` bound or a loop without one) and where nothing limits how
+/// many of the goroutines run at once.
+#[derive(Debug, Clone)]
+pub struct UnboundedSpawn {
+    /// The `go` keyword.
+    pub range: Range,
+    /// The `for` keyword of the loop.
+    pub loop_range: Range,
+    /// Bounds the loop with a buffered-channel semaphore; offered when the goroutine runs a
+    /// function literal, whose body can release the semaphore.
+    pub fix: Option<SemaphoreFix>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SemaphoreFix {
+    pub name: String,
+    pub edits: Vec<(Range, String)>,
+}
+
+impl UnboundedSpawn {
+    pub fn message(&self) -> String {
+        "Goroutine started on every iteration with no limit on how many run at once; bound it with a worker pool, a semaphore or errgroup's SetLimit".to_string()
+    }
+
+    pub fn hover(&self) -> String {
+        "**Unbounded goroutines**\n\nThis loop starts a goroutine on every iteration and nothing limits how many run at the same time. The number of goroutines grows with the input: a large slice or a busy channel can start thousands at once, exhausting memory, file descriptors or connections to a downstream service and starving other work of CPU.\n\nBound the concurrency with a fixed pool of workers reading from a channel, a buffered channel used as a semaphore (`sem <- struct{}{}` before `go`, `<-sem` when the goroutine ends), `semaphore.Weighted`, or an `errgroup.Group` with `SetLimit`.\n".to_string()
+    }
+}
+
+pub fn find_unbounded_spawns(tree: &Tree, code: &str) -> AnalysisResult<Vec<UnboundedSpawn>> {
+    check_tree(tree, code)?;
+    let mut go_statements = Vec::new();
+    collect_kind(tree.root_node(), "go_statement", &mut go_statements);
+    let mut findings = Vec::new();
+    for go_stmt in go_statements {
+        let Some(for_statement) = enclosing_loop(go_stmt) else {
+            continue;
+        };
+        if has_fixed_bound(for_statement, code) {
+            continue;
+        }
+        let Some(body) = for_statement.child_by_field_name("body") else {
+            continue;
+        };
+        if is_limited(body, go_stmt, code) {
+            continue;
+        }
+        findings.push(UnboundedSpawn {
+            range: node_to_range(go_stmt.child(0).unwrap_or(go_stmt)),
+            loop_range: node_to_range(for_statement.child(0).unwrap_or(for_statement)),
+            fix: semaphore_fix(for_statement, go_stmt, code),
+        });
+    }
+    Ok(findings)
+}
+
+/// `for i := 0; i < workers; i++` or `for range 4`: a fixed number of iterations, the usual shape
+/// of a worker pool. Bounds computed with `len(...)` follow the input.
+fn has_fixed_bound(for_statement: Node, code: &str) -> bool {
+    let Some(clause) = for_statement.named_child(0) else {
+        return false;
+    };
+    let bound = match clause.kind() {
+        "for_clause" => clause
+            .child_by_field_name("condition")
+            .filter(|condition| condition.kind() == "binary_expression")
+            .and_then(|condition| condition.child_by_field_name("right")),
+        "range_clause" => clause
+            .child_by_field_name("right")
+            .filter(|right| right.kind() == "int_literal"),
+        _ => None,
+    };
+    bound.is_some_and(|bound| {
+        matches!(
+            bound.kind(),
+            "int_literal" | "identifier" | "selector_expression"
+        ) || (bound.kind() == "binary_expression" && !text(code, bound).contains("len("))
+    })
+}
+
+/// Something in the loop body waits before the next iteration or before the goroutine's work
+/// runs: a send on a channel (`sem <- struct{}{}` or handing work to a pool), a receive outside
+/// the goroutine, a `Wait()` or a semaphore `Acquire`.
+fn is_limited(body: Node, go_stmt: Node, code: &str) -> bool {
+    let mut sends = Vec::new();
+    collect_kind(body, "send_statement", &mut sends);
+    let token = sends.iter().any(|send| {
+        let outside = !contains(go_stmt, *send) && send.end_byte() <= go_stmt.start_byte();
+        let is_token = send
+            .child_by_field_name("value")
+            .is_some_and(|value| text(code, value).replace(' ', "") == "struct{}{}");
+        outside || is_token
+    });
+    if token {
+        return true;
+    }
+    let mut receives = Vec::new();
+    collect_kind(body, "unary_expression", &mut receives);
+    if receives
+        .iter()
+        .any(|unary| !contains(go_stmt, *unary) && text(code, *unary).starts_with("<-"))
+    {
+        return true;
+    }
+    let mut calls = Vec::new();
+    collect_kind(body, "call_expression", &mut calls);
+    calls.iter().any(|call| {
+        let method = call
+            .child_by_field_name("function")
+            .filter(|function| function.kind() == "selector_expression")
+            .and_then(|function| function.child_by_field_name("field"))
+            .map(|field| text(code, field));
+        match method {
+            Some("Acquire" | "TryAcquire") => true,
+            Some("Wait") => !contains(go_stmt, *call),
+            _ => false,
+        }
+    })
+}
+
+fn contains(outer: Node, inner: Node) -> bool {
+    outer.start_byte() <= inner.start_byte() && inner.end_byte() <= outer.end_byte()
+}
+
+fn semaphore_fix(for_statement: Node, go_stmt: Node, code: &str) -> Option<SemaphoreFix> {
+    let call = go_stmt.named_child(0)?;
+    let literal = call.child_by_field_name("function")?;
+    if literal.kind() != "func_literal" {
+        return None;
+    }
+    let body = literal.child_by_field_name("body")?;
+    let name = unused_name(for_statement, code, "sem");
+    let loop_indent = line_indent(code, for_statement);
+    let go_indent = line_indent(code, go_stmt);
+    let loop_start = node_to_range(for_statement).start;
+    let go_start = node_to_range(go_stmt).start;
+    let open = node_to_range(body).start;
+    let after_brace = Position::new(open.line, open.character + 1);
+    let body_indent = match body.named_child(0) {
+        Some(first) => line_indent(code, first),
+        None => format!("{}\t", go_indent),
+    };
+    Some(SemaphoreFix {
+        edits: vec![
+            (
+                Range::new(loop_start, loop_start),
+                format!("{name} := make(chan struct{{}}, {SEMAPHORE_SIZE})\n{loop_indent}"),
+            ),
+            (
+                Range::new(go_start, go_start),
+                format!("{name} <- struct{{}}{{}}\n{go_indent}"),
+            ),
+            (
+                Range::new(after_brace, after_brace),
+                format!("\n{body_indent}defer func() {{ <-{name} }}()"),
+            ),
+        ],
+        name,
+    })
+}
@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Rough heap size of one tree-sitter node, for [`CacheStats::estimated_bytes`] of trees.
+pub const ESTIMATED_BYTES_PER_NODE: usize = 64;
+
+/// Result of `goanalyzer/stats`: what the server holds and how its analyses have performed
+/// since it started.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct ServerStats {
+    pub uptime_ms: u64,
+    pub caches: Vec<CacheStats>,
+    /// By analysis name.
+    pub timings: Vec<TimingStats>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default, JsonSchema)]
+pub struct CacheStats {
+    /// Stable identifier: `documents`, `trees`, `decorations`, `graphs`, `graph_pages` or
+    /// `analysis_cache`.
+    pub name: String,
+    pub entries: usize,
+    /// Time since the least and the most recently used entry was used, when tracked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oldest_entry_age_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub newest_entry_age_ms: Option<u64>,
+    /// Approximate memory (or, for the on-disk cache, disk) used by the entries.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_bytes: Option<usize>,
+    /// Lookups served from the cache and lookups that missed, when counted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hits: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub misses: Option<u64>,
+    /// `hits / (hits + misses)`; absent before the first lookup.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hit_ratio: Option<f64>,
+}
+
+impl CacheStats {
+    pub fn new(name: &str, entries: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            entries,
+            ..Default::default()
+        }
+    }
+
+    /// Ages of the entries last used at `timestamps`.
+    pub fn with_ages(mut self, timestamps: impl IntoIterator<Item = SystemTime>) -> Self {
+        let ages: Vec<u64> = timestamps.into_iter().map(age_ms).collect();
+        self.oldest_entry_age_ms = ages.iter().max().copied();
+        self.newest_entry_age_ms = ages.iter().min().copied();
+        self
+    }
+
+    pub fn with_bytes(mut self, bytes: usize) -> Self {
+        self.estimated_bytes = Some(bytes);
+        self
+    }
+
+    pub fn with_lookups(mut self, counter: &HitCounter) -> Self {
+        let hits = counter.hits.load(Ordering::Relaxed);
+        let misses = counter.misses.load(Ordering::Relaxed);
+        self.hits = Some(hits);
+        self.misses = Some(misses);
+        self.hit_ratio = (hits + misses > 0).then(|| hits as f64 / (hits + misses) as f64);
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct TimingStats {
+    pub name: String,
+    pub runs: u64,
+    pub total_ms: f64,
+    pub mean_ms: f64,
+    pub max_ms: f64,
+    pub last_ms: f64,
+}
+
+/// Hits and misses of one cache's lookups.
+#[derive(Default)]
+pub struct HitCounter {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl HitCounter {
+    /// Counts a lookup and passes its result through.
+    pub fn record<T>(&self, found: Option<T>) -> Option<T> {
+        let counter = if found.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        found
+    }
+}
+
+/// Durations of the analyses run so far, by name.
+pub struct AnalysisTimings {
+    started: Instant,
+    runs: Mutex<HashMap<&'static str, Runs>>,
+}
+
+#[derive(Default)]
+struct Runs {
+    count: u64,
+    total: Duration,
+    max: Duration,
+    last: Duration,
+}
+
+impl Default for AnalysisTimings {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            runs: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl AnalysisTimings {
+    pub fn record(&self, name: &'static str, elapsed: Duration) {
+        let mut runs = self.runs.lock().unwrap_or_else(PoisonError::into_inner);
+        let runs = runs.entry(name).or_default();
+        runs.count += 1;
+        runs.total += elapsed;
+        runs.max = runs.max.max(elapsed);
+        runs.last = elapsed;
+    }
+
+    /// Runs `analysis` and records how long it took under `name`.
+    pub fn time<T>(&self, name: &'static str, analysis: impl FnOnce() -> T) -> T {
+        let _span = tracing::debug_span!("analysis", name).entered();
+        let started = Instant::now();
+        let result = analysis();
+        self.record(name, started.elapsed());
+        result
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Statistics of every analysis run so far, by name.
+    pub fn snapshot(&self) -> Vec<TimingStats> {
+        let runs = self.runs.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut timings: Vec<TimingStats> = runs
+            .iter()
+            .map(|(name, runs)| TimingStats {
+                name: name.to_string(),
+                runs: runs.count,
+                total_ms: ms(runs.total),
+                mean_ms: ms(runs.total) / runs.count.max(1) as f64,
+                max_ms: ms(runs.max),
+                last_ms: ms(runs.last),
+            })
+            .collect();
+        timings.sort_by(|a, b| a.name.cmp(&b.name));
+        timings
+    }
+}
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn age_ms(timestamp: SystemTime) -> u64 {
+    timestamp
+        .elapsed()
+        .unwrap_or(Duration::ZERO)
+        .as_millis()
+        .try_into()
+        .unwrap_or(u64::MAX)
+}
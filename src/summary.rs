@@ -0,0 +1,244 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString};
+use tree_sitter::Tree;
+
+use crate::analysis::has_synchronization_in_block;
+use crate::types::EntityCount;
+use crate::util::{for_each_node, node_to_range};
+
+/// Directories never scanned for Go files.
+const SKIPPED_DIRS: &[&str] = &["vendor", "testdata", "node_modules"];
+
+/// Arguments of `goanalyzer/workspaceSummary`; every field is optional.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
+pub struct WorkspaceSummaryParams {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort_by: Option<SummarySort>,
+    /// Directory to scan instead of the workspace folders.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SummarySort {
+    /// Most findings first.
+    #[default]
+    Findings,
+    /// Most error-severity findings first.
+    Errors,
+    /// Most `go` statements first.
+    Goroutines,
+    /// Most channels first.
+    Channels,
+    /// Lowest share of synchronized goroutines first.
+    SyncCoverage,
+    /// By package path.
+    Package,
+}
+
+/// Analysis results of one file, as input to [`summarize`].
+pub struct FileReport {
+    pub path: PathBuf,
+    pub counts: EntityCount,
+    /// `go` statements whose enclosing function uses a lock, channel, WaitGroup or atomic.
+    pub synchronized_goroutines: usize,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Totals for one package, i.e. one directory of Go files.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, JsonSchema)]
+pub struct PackageSummary {
+    pub package: String,
+    pub files: usize,
+    pub variables: usize,
+    pub functions: usize,
+    pub channels: usize,
+    pub goroutines: usize,
+    pub synchronized_goroutines: usize,
+    /// `synchronized_goroutines / goroutines`, `None` without goroutines.
+    pub sync_coverage: Option<f64>,
+    pub findings: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    pub findings_by_code: BTreeMap<String, usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct WorkspaceSummary {
+    pub roots: Vec<String>,
+    pub sort_by: SummarySort,
+    pub packages: Vec<PackageSummary>,
+    /// All packages added up; `package` is empty.
+    pub totals: PackageSummary,
+}
+
+/// `.go` files under `root`, skipping hidden directories and [`SKIPPED_DIRS`].
+pub fn collect_go_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+            if path.is_dir() {
+                if !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_str()) {
+                    pending.push(path);
+                }
+            } else if name.ends_with(".go") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+pub fn synchronized_goroutines(tree: &Tree, code: &str) -> usize {
+    let mut count = 0;
+    for_each_node(tree.root_node(), |node| {
+        if node.kind() == "go_statement"
+            && has_synchronization_in_block(tree, node_to_range(node), code)
+        {
+            count += 1;
+        }
+    });
+    count
+}
+
+/// Groups file reports by directory (relative to the root containing it) and sorts packages.
+pub fn summarize(
+    roots: &[PathBuf],
+    reports: Vec<FileReport>,
+    sort_by: SummarySort,
+) -> WorkspaceSummary {
+    let mut packages: BTreeMap<String, PackageSummary> = BTreeMap::new();
+    let mut totals = PackageSummary::default();
+    for report in reports {
+        let dir = report.path.parent().unwrap_or(Path::new(""));
+        let package = roots
+            .iter()
+            .find_map(|root| dir.strip_prefix(root).ok())
+            .map(|relative| relative.display().to_string())
+            .filter(|relative| !relative.is_empty())
+            .unwrap_or_else(|| ".".to_string());
+        let summary = packages
+            .entry(package.clone())
+            .or_insert_with(|| PackageSummary {
+                package,
+                ..Default::default()
+            });
+        add_report(summary, &report);
+        add_report(&mut totals, &report);
+    }
+    let mut packages: Vec<PackageSummary> = packages.into_values().collect();
+    for summary in packages.iter_mut().chain(std::iter::once(&mut totals)) {
+        summary.sync_coverage = (summary.goroutines > 0)
+            .then(|| summary.synchronized_goroutines as f64 / summary.goroutines as f64);
+    }
+    // Stable sorts keep packages with equal keys in path order.
+    match sort_by {
+        SummarySort::Findings => packages.sort_by_key(|p| Reverse(p.findings)),
+        SummarySort::Errors => packages.sort_by_key(|p| Reverse(p.errors)),
+        SummarySort::Goroutines => packages.sort_by_key(|p| Reverse(p.goroutines)),
+        SummarySort::Channels => packages.sort_by_key(|p| Reverse(p.channels)),
+        SummarySort::SyncCoverage => packages.sort_by(|a, b| {
+            let coverage = |p: &PackageSummary| p.sync_coverage.unwrap_or(f64::INFINITY);
+            coverage(a).total_cmp(&coverage(b))
+        }),
+        SummarySort::Package => {}
+    }
+    WorkspaceSummary {
+        roots: roots
+            .iter()
+            .map(|root| root.display().to_string())
+            .collect(),
+        sort_by,
+        packages,
+        totals,
+    }
+}
+
+fn add_report(summary: &mut PackageSummary, report: &FileReport) {
+    summary.files += 1;
+    summary.variables += report.counts.variables;
+    summary.functions += report.counts.functions;
+    summary.channels += report.counts.channels;
+    summary.goroutines += report.counts.goroutines;
+    summary.synchronized_goroutines += report.synchronized_goroutines;
+    for diagnostic in &report.diagnostics {
+        summary.findings += 1;
+        match diagnostic.severity {
+            Some(DiagnosticSeverity::ERROR) => summary.errors += 1,
+            Some(DiagnosticSeverity::WARNING) => summary.warnings += 1,
+            _ => {}
+        }
+        if let Some(NumberOrString::String(code)) = &diagnostic.code {
+            *summary.findings_by_code.entry(code.clone()).or_insert(0) += 1;
+        }
+    }
+}
+
+impl WorkspaceSummary {
+    /// One row per package in summary order, then the totals, as the `summary` command prints
+    /// them.
+    pub fn to_table(&self) -> String {
+        let header = [
+            "package",
+            "files",
+            "goroutines",
+            "channels",
+            "sync",
+            "findings",
+            "errors",
+            "warnings",
+        ];
+        let row = |summary: &PackageSummary, package: &str| {
+            [
+                package.to_string(),
+                summary.files.to_string(),
+                summary.goroutines.to_string(),
+                summary.channels.to_string(),
+                summary
+                    .sync_coverage
+                    .map(|coverage| format!("{:.0}%", coverage * 100.0))
+                    .unwrap_or_else(|| "-".to_string()),
+                summary.findings.to_string(),
+                summary.errors.to_string(),
+                summary.warnings.to_string(),
+            ]
+        };
+        let mut rows = vec![header.map(str::to_string)];
+        rows.extend(
+            self.packages
+                .iter()
+                .map(|summary| row(summary, &summary.package)),
+        );
+        rows.push(row(&self.totals, "total"));
+        let mut widths = [0; 8];
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let mut table = String::new();
+        for row in rows {
+            // The package column is left-aligned, the numbers right-aligned.
+            let mut line = format!("{:<width$}", row[0], width = widths[0]);
+            for (cell, width) in row.iter().zip(widths).skip(1) {
+                line.push_str(&format!("  {:>width$}", cell, width = width));
+            }
+            table.push_str(&line);
+            table.push('\n');
+        }
+        table
+    }
+}
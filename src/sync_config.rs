@@ -0,0 +1,95 @@
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use serde_json::Value;
+
+const CONFIG_FILE: &str = ".go-analyzer.json";
+
+/// Project-specific functions and methods that count as synchronization, such as
+/// `s.mu.LockShared()` or a `WithLock(func())` helper. Names come from the
+/// `goAnalyzer.syncFunctions` setting (`GO_ANALYZER_SYNC_FUNCTIONS`, comma separated) and the
+/// `syncFunctions` array of a `.go-analyzer.json` file (`GO_ANALYZER_CONFIG`, default: the
+/// server's working directory). A name is either a bare method or function name (`LockShared`)
+/// or the full callee as written (`locks.With`).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SyncConfig {
+    pub functions: Vec<String>,
+}
+
+impl SyncConfig {
+    pub fn from_env() -> Self {
+        let mut functions = std::env::var("GO_ANALYZER_SYNC_FUNCTIONS")
+            .map(|raw| parse_list(&raw))
+            .unwrap_or_default();
+        let path = std::env::var("GO_ANALYZER_CONFIG")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(CONFIG_FILE));
+        for name in Self::from_file(&path).functions {
+            if !functions.contains(&name) {
+                functions.push(name);
+            }
+        }
+        Self { functions }
+    }
+
+    /// The `syncFunctions` array of a JSON config file; empty when the file is missing or invalid.
+    pub fn from_file(path: &Path) -> Self {
+        let functions = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|raw| serde_json::from_str::<Value>(&raw).ok())
+            .and_then(|config| config.get("syncFunctions").cloned())
+            .and_then(|names| names.as_array().cloned())
+            .map(|names| {
+                names
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self { functions }
+    }
+
+    /// `callee` is the call's function as written, `method` its last segment.
+    pub fn contains(&self, callee: &str, method: &str) -> bool {
+        self.functions
+            .iter()
+            .any(|name| name == method || name == callee)
+    }
+
+    /// How a configured method changes the lock it is called on: names containing `Unlock` or
+    /// `Release` release it, others acquire it, and names containing `RLock`, `RUnlock` or
+    /// `Shared` work like `RLock`/`RUnlock`. `None` for names that are not configured.
+    pub fn lock_event(&self, callee: &str, method: &str) -> Option<(i32, bool)> {
+        if !self.contains(callee, method) {
+            return None;
+        }
+        let delta = if method.contains("Unlock") || method.contains("Release") {
+            -1
+        } else {
+            1
+        };
+        let shared = ["RLock", "RUnlock", "Shared"]
+            .iter()
+            .any(|marker| method.contains(marker));
+        Some((delta, shared))
+    }
+}
+
+/// Comma or whitespace separated names.
+pub fn parse_list(raw: &str) -> Vec<String> {
+    raw.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// The configuration read from the environment when the server first needs it.
+pub fn sync_config() -> &'static SyncConfig {
+    static CONFIG: OnceLock<SyncConfig> = OnceLock::new();
+    CONFIG.get_or_init(SyncConfig::from_env)
+}
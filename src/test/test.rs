@@ -29,9 +29,11 @@ mod tests {
         access_context_key, count_entities, detect_retention_pattern, determine_race_severity,
         field_type_kind_at_declaration, find_node_at_cursor_with_context,
         find_variable_at_position, find_variable_at_position_enhanced,
-        has_synchronization_in_block, is_access_in_atomic_context, is_heavy_work_in_call_context,
-        is_in_goroutine, is_struct_field_declaration, is_value_copy_context, FieldTypeKind,
+        has_synchronization_in_block, infer_variable_type, is_access_in_atomic_context,
+        is_heavy_work_in_call_context, is_in_goroutine, is_struct_field_declaration,
+        is_value_copy_context, FieldTypeKind,
     };
+    use crate::error::AnalysisError;
     use crate::types::{CursorContextType, RaceSeverity};
     use std::collections::HashSet;
     use tower_lsp::lsp_types::{Position, Range};
@@ -53,8 +55,8 @@ func main() {
 
         let pos_decl = Position::new(2, 4);
         let var_info = match find_variable_at_position(&tree, code, pos_decl) {
-            Some(info) => info,
-            None => return,
+            Ok(Some(info)) => info,
+            _ => return,
         };
 
         assert_eq!(var_info.name, "x");
@@ -82,8 +84,8 @@ func main() {
         };
         let pos_object = Position::new(7, 4);
         let var_info_obj = match find_variable_at_position(&tree, code, pos_object) {
-            Some(info) => info,
-            None => return,
+            Ok(Some(info)) => info,
+            _ => return,
         };
 
         assert_eq!(var_info_obj.name, "user");
@@ -107,7 +109,7 @@ func main() {
         // Cursor on AddInt64
         let pos = Position::new(3, 13);
         let var_info = find_variable_at_position(&tree, code, pos);
-        assert!(var_info.is_none());
+        assert!(matches!(var_info, Ok(None)));
     }
 
     #[test]
@@ -126,7 +128,7 @@ func (s *S) f() {
         // Cursor on method symbol f() in call context.
         let pos = Position::new(3, 6);
         let var_info = find_variable_at_position(&tree, code, pos);
-        assert!(var_info.is_none());
+        assert!(matches!(var_info, Ok(None)));
     }
 
     #[test]
@@ -145,8 +147,8 @@ func process(data string) {
         };
         let pos_use = Position::new(4, 19);
         let var_info_use = match find_variable_at_position(&tree, code, pos_use) {
-            Some(info) => info,
-            None => return,
+            Ok(Some(info)) => info,
+            _ => return,
         };
         assert_eq!(var_info_use.name, "data");
         assert!(var_info_use.declaration.start.line <= 1);
@@ -169,8 +171,8 @@ func main() {
 
         let pos_index = Position::new(3, 8);
         let var_info_i = match find_variable_at_position(&tree, code, pos_index) {
-            Some(info) => info,
-            None => return,
+            Ok(Some(info)) => info,
+            _ => return,
         };
 
         assert_eq!(var_info_i.name, "i");
@@ -196,8 +198,8 @@ func main() {
 
         let pos_switch = Position::new(3, 11);
         let var_info = match find_variable_at_position(&tree, code, pos_switch) {
-            Some(info) => info,
-            None => return,
+            Ok(Some(info)) => info,
+            _ => return,
         };
 
         assert_eq!(var_info.name, "v");
@@ -221,8 +223,8 @@ func demo() {
         };
         let pos_x = Position::new(2, 8);
         let var_info = match find_variable_at_position(&tree, code, pos_x) {
-            Some(info) => info,
-            None => return,
+            Ok(Some(info)) => info,
+            _ => return,
         };
         assert_eq!(var_info.name, "x");
         assert!(var_info.declaration.start.line <= 2);
@@ -244,8 +246,8 @@ func main() {
         };
         let pos_addr = Position::new(3, 12);
         let var_info = match find_variable_at_position(&tree, code, pos_addr) {
-            Some(info) => info,
-            None => return,
+            Ok(Some(info)) => info,
+            _ => return,
         };
         assert_eq!(var_info.name, "x");
         assert!(var_info.uses.len() >= 1);
@@ -393,7 +395,7 @@ func main() {
         };
         let pos = Position::new(0, 0);
         let var_info = find_variable_at_position(&tree, code, pos);
-        assert!(var_info.is_none());
+        assert!(matches!(var_info, Ok(None)));
         let context = find_node_at_cursor_with_context(&tree, pos);
         if let Some(ctx) = context {
             assert!(matches!(ctx.context_type, CursorContextType::Unknown));
@@ -413,7 +415,7 @@ func main() {
         };
         let pos_outside = Position::new(100, 100);
         let var_info = find_variable_at_position(&tree, code, pos_outside);
-        assert!(var_info.is_none());
+        assert!(matches!(var_info, Err(AnalysisError::InvalidPosition(_))));
     }
 
     #[test]
@@ -508,7 +510,7 @@ func demo() {
             Err(_) => return,
         };
         let range = Range::new(Position::new(2, 29), Position::new(2, 29)); // total
-        assert!(is_access_in_atomic_context(&tree, range, code));
+        assert_eq!(is_access_in_atomic_context(&tree, range, code), Ok(true));
     }
 
     #[test]
@@ -538,7 +540,7 @@ func demo() {
             Err(_) => return,
         };
         let range = Range::new(Position::new(2, 22), Position::new(2, 22)); // total
-        assert!(is_heavy_work_in_call_context(&tree, range, code));
+        assert_eq!(is_heavy_work_in_call_context(&tree, range, code), Ok(true));
     }
 
     #[test]
@@ -582,7 +584,46 @@ func main() {
             Err(_) => return,
         };
         let range = Range::new(Position::new(7, 12), Position::new(7, 12)); // x in consume(x)
-        assert!(is_value_copy_context(&tree, range, code));
+        assert_eq!(is_value_copy_context(&tree, range, code), Ok(true));
+    }
+
+    #[test]
+    fn test_infer_variable_type_from_declarations() {
+        let code = r#"
+type User struct{}
+
+func demo(limit int) {
+    var names []string
+    ch := make(chan int)
+    u, n := &User{}, 3
+}
+        "#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let at =
+            |line: u32, col: u32| Range::new(Position::new(line, col), Position::new(line, col));
+        assert_eq!(
+            infer_variable_type(&tree, code, at(3, 10)).as_deref(),
+            Some("int")
+        );
+        assert_eq!(
+            infer_variable_type(&tree, code, at(4, 8)).as_deref(),
+            Some("[]string")
+        );
+        assert_eq!(
+            infer_variable_type(&tree, code, at(5, 4)).as_deref(),
+            Some("chan int")
+        );
+        assert_eq!(
+            infer_variable_type(&tree, code, at(6, 4)).as_deref(),
+            Some("*User")
+        );
+        assert_eq!(
+            infer_variable_type(&tree, code, at(6, 7)).as_deref(),
+            Some("int")
+        );
     }
 
     #[test]
@@ -666,8 +707,8 @@ func demo() {
         };
         let pos = Position::new(3, 9);
         let info = match crate::analysis::find_variable_at_position(&tree, code, pos) {
-            Some(info) => info,
-            None => return,
+            Ok(Some(info)) => info,
+            _ => return,
         };
         assert_eq!(info.name, "a");
         assert_eq!(info.declaration.start.line, 2);
@@ -747,16 +788,16 @@ func example() {
         let pos_goroutine = Position::new(8, 23);
         let var_info =
             match crate::analysis::find_variable_at_position_enhanced(&tree, code, pos_goroutine) {
-                Some(info) => info,
-                None => return,
+                Ok(Some(info)) => info,
+                _ => return,
             };
         assert_eq!(var_info.name, "user");
         let pos_declaration = Position::new(2, 8);
         let var_info_decl =
             match crate::analysis::find_variable_at_position_enhanced(&tree, code, pos_declaration)
             {
-                Some(info) => info,
-                None => return,
+                Ok(Some(info)) => info,
+                _ => return,
             };
         assert_eq!(var_info_decl.name, "user");
         assert!(var_info_decl.uses.len() >= 2);
@@ -782,8 +823,8 @@ func main() {
         };
         let pos_person = Position::new(9, 12);
         let var_info = match find_variable_at_position(&tree, code, pos_person) {
-            Some(info) => info,
-            None => return,
+            Ok(Some(info)) => info,
+            _ => return,
         };
         assert_eq!(var_info.name, "person");
         assert!(var_info.declaration.start.line <= 2);
@@ -806,8 +847,8 @@ func (c *Counter) Increment() {
         };
         let pos_ptr_receiver = Position::new(5, 6);
         let var_info = match find_variable_at_position(&tree, code, pos_ptr_receiver) {
-            Some(info) => info,
-            None => return,
+            Ok(Some(info)) => info,
+            _ => return,
         };
         assert_eq!(var_info.name, "c");
         assert!(var_info.declaration.start.line <= 5);
@@ -831,8 +872,8 @@ func process(w Writer) {
         };
         let pos_interface = Position::new(5, 13);
         let var_info = match find_variable_at_position(&tree, code, pos_interface) {
-            Some(info) => info,
-            None => return,
+            Ok(Some(info)) => info,
+            _ => return,
         };
         assert_eq!(var_info.name, "w");
         assert!(var_info.declaration.start.line <= 5);
@@ -876,8 +917,8 @@ func outer() {
         };
         let pos_x = Position::new(4, 13);
         let var_info = match find_variable_at_position(&tree, code, pos_x) {
-            Some(info) => info,
-            None => return,
+            Ok(Some(info)) => info,
+            _ => return,
         };
         assert_eq!(var_info.name, "x");
         assert!(var_info.declaration.start.line <= 2);
@@ -902,15 +943,15 @@ func getValues() (int, int) {
         };
         let pos_a = Position::new(2, 4);
         let var_info_a = match find_variable_at_position(&tree, code, pos_a) {
-            Some(info) => info,
-            None => return,
+            Ok(Some(info)) => info,
+            _ => return,
         };
         assert_eq!(var_info_a.name, "a");
         assert!(var_info_a.declaration.start.line <= 2);
         let pos_c = Position::new(3, 4);
         let var_info_c = match find_variable_at_position(&tree, code, pos_c) {
-            Some(info) => info,
-            None => return,
+            Ok(Some(info)) => info,
+            _ => return,
         };
 
         assert_eq!(var_info_c.name, "c");
@@ -940,8 +981,8 @@ func main() {
         assert!(counts.variables >= 2);
         let pos_ch = Position::new(2, 4);
         let var_info = match find_variable_at_position(&tree, code, pos_ch) {
-            Some(info) => info,
-            None => return,
+            Ok(Some(info)) => info,
+            _ => return,
         };
 
         assert_eq!(var_info.name, "ch");
@@ -963,7 +1004,7 @@ func broken( {
                 Err(_) => return true,
             };
             let pos = Position::new(2, 4);
-            find_variable_at_position(&tree, code, pos);
+            let _ = find_variable_at_position(&tree, code, pos);
             true
         });
         assert!(result.is_ok());
@@ -1023,12 +1064,17 @@ func main() {
         let reassign_range = Range::new(Position::new(3, 4), Position::new(3, 5));
         let is_reassign =
             crate::analysis::is_variable_reassignment(&tree, "x", reassign_range, code);
-        assert!(is_reassign, "Should detect x = 100 as reassignment");
+        assert_eq!(
+            is_reassign,
+            Ok(true),
+            "Should detect x = 100 as reassignment"
+        );
         let decl_range = Range::new(Position::new(2, 4), Position::new(2, 5));
         let is_not_reassign =
             crate::analysis::is_variable_reassignment(&tree, "x", decl_range, code);
-        assert!(
-            !is_not_reassign,
+        assert_eq!(
+            is_not_reassign,
+            Ok(false),
             "Should not detect declaration as reassignment"
         );
     }
@@ -1054,7 +1100,11 @@ func main() {
         let declaration_range = Range::new(Position::new(2, 4), Position::new(2, 5));
         let is_captured =
             crate::analysis::is_variable_captured(&tree, "x", capture_range, declaration_range);
-        assert!(is_captured, "Should detect x as captured in goroutine");
+        assert_eq!(
+            is_captured,
+            Ok(true),
+            "Should detect x as captured in goroutine"
+        );
 
         let non_capture_range = Range::new(Position::new(7, 12), Position::new(7, 13));
         let y_declaration_range = Range::new(Position::new(6, 4), Position::new(6, 5));
@@ -1064,11 +1114,14 @@ func main() {
             non_capture_range,
             y_declaration_range,
         );
-        assert!(!is_not_captured, "Should not detect y as captured");
+        assert_eq!(
+            is_not_captured,
+            Ok(false),
+            "Should not detect y as captured"
+        );
     }
 
     #[test]
-    #[ignore] // TODO: Fix function literal capture detection
     fn test_variable_capture_in_function_literal() {
         let code = r#"
 func main() {
@@ -1088,9 +1141,7730 @@ func main() {
         let declaration_range = Range::new(Position::new(2, 4), Position::new(2, 9));
         let is_captured =
             crate::analysis::is_variable_captured(&tree, "value", capture_range, declaration_range);
-        assert!(
+        assert_eq!(
             is_captured,
+            Ok(true),
             "Should detect value as captured in function literal"
         );
     }
+
+    #[test]
+    fn test_immediately_invoked_func_literal_is_not_a_capture() {
+        let code = r#"
+func main() {
+    total := 0
+    func() {
+        total++
+    }()
+    go func() {
+        total++
+    }()
+}
+        "#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let declaration_range = Range::new(Position::new(2, 4), Position::new(2, 9));
+        let iife_use = Range::new(Position::new(4, 8), Position::new(4, 13));
+        let goroutine_use = Range::new(Position::new(7, 8), Position::new(7, 13));
+        assert_eq!(
+            crate::analysis::is_variable_captured(&tree, "total", iife_use, declaration_range),
+            Ok(false)
+        );
+        assert!(!is_in_goroutine(&tree, iife_use));
+        assert_eq!(
+            crate::analysis::is_variable_captured(&tree, "total", goroutine_use, declaration_range),
+            Ok(true)
+        );
+        let var_info = match find_variable_at_position(&tree, code, Position::new(4, 8)) {
+            Ok(Some(info)) => info,
+            _ => return,
+        };
+        assert_eq!(var_info.declaration, declaration_range);
+        assert_eq!(var_info.uses.len(), 2);
+    }
+
+    #[test]
+    fn test_analyze_goroutine_usage_lists_launch_sites() {
+        let code = r#"
+func main() {
+    counter := 0
+    go func() {
+        counter++
+        println(counter)
+    }()
+    go report(counter)
+    go s.flush()
+}
+        "#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let var_info = match find_variable_at_position(&tree, code, Position::new(2, 4)) {
+            Ok(Some(info)) => info,
+            _ => return,
+        };
+        let usages = crate::analysis::analyze_goroutine_usage(&tree, code, &var_info);
+        assert_eq!(
+            usages.len(),
+            1,
+            "argument of `go report(..)` is evaluated by the caller"
+        );
+        assert_eq!(usages[0].launch.start.line, 3);
+        assert_eq!(
+            usages[0].goroutine_type,
+            crate::types::GoroutineType::AnonymousClosure
+        );
+        assert_eq!(usages[0].reads, 1);
+        assert_eq!(usages[0].writes, 1);
+    }
+
+    #[test]
+    fn test_guarding_mutex_reports_nearest_lock() {
+        let code = r#"
+func (s *Store) Put(v int) {
+    s.mu.Lock()
+    s.value = v
+    s.mu.Unlock()
+    s.value = 0
+    s.stats.Lock()
+    s.mu.Lock()
+    s.value = v
+}
+        "#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let at =
+            |line: u32, col: u32| Range::new(Position::new(line, col), Position::new(line, col));
+        assert_eq!(
+            crate::analysis::guarding_mutex(&tree, at(3, 6), code).as_deref(),
+            Some("s.mu")
+        );
+        assert_eq!(crate::analysis::guarding_mutex(&tree, at(5, 6), code), None);
+        assert_eq!(
+            crate::analysis::guarding_mutex(&tree, at(8, 6), code).as_deref(),
+            Some("s.mu")
+        );
+    }
+
+    #[test]
+    fn test_published_before_spawn_read_only_capture() {
+        let code = r#"
+func fanOut(items []int) {
+    limit := 10
+    for _, item := range items {
+        go func() {
+            process(item, limit)
+        }()
+    }
+    offset := 0
+    for range items {
+        offset = offset + 1
+        go func() {
+            println(offset)
+        }()
+    }
+}
+        "#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let limit = match find_variable_at_position(&tree, code, Position::new(2, 4)) {
+            Ok(Some(info)) => info,
+            _ => return,
+        };
+        let limit_read = Range::new(Position::new(5, 26), Position::new(5, 31));
+        assert_eq!(
+            crate::analysis::is_published_before_spawn(&tree, code, &limit, limit_read),
+            Ok(true)
+        );
+        let offset = match find_variable_at_position(&tree, code, Position::new(8, 4)) {
+            Ok(Some(info)) => info,
+            _ => return,
+        };
+        let offset_read = Range::new(Position::new(12, 20), Position::new(12, 26));
+        assert_eq!(
+            crate::analysis::is_published_before_spawn(&tree, code, &offset, offset_read),
+            Ok(false),
+            "write in the enclosing loop happens after earlier spawns"
+        );
+    }
+
+    #[test]
+    fn test_rule_registry_is_documented_and_linked() {
+        let docs = include_str!("../../docs/rules.md");
+        for rule in crate::rules::RULES {
+            assert!(
+                docs.contains(&format!("## {}\n", rule.code)),
+                "missing docs section for {}",
+                rule.code
+            );
+        }
+        let config = crate::rules::RuleDocsConfig {
+            base_url: "https://example.com/rules.md".to_string(),
+        };
+        assert_eq!(
+            config.href("field-race-high").as_deref(),
+            Some("https://example.com/rules.md#field-race-high")
+        );
+        assert_eq!(config.href("unknown-rule"), None);
+    }
+
+    #[test]
+    fn test_render_hover_fences_declaration_and_links_uses() {
+        let code = "package main\n\nfunc main() {\n\tcounter := 0\n\tgo func() {\n\t\tcounter++\n\t}()\n\tcounter = 2\n}\n";
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let var_info = match find_variable_at_position(&tree, code, Position::new(3, 1)) {
+            Ok(Some(info)) => info,
+            _ => return,
+        };
+        let uri = match tower_lsp::lsp_types::Url::parse("file:///tmp/main.go") {
+            Ok(uri) => uri,
+            Err(_) => return,
+        };
+        let writes = vec![true; var_info.uses.len()];
+        let guards = vec![None; var_info.uses.len()];
+        let markdown = crate::hover::render_hover(&crate::hover::HoverInput {
+            uri: &uri,
+            code,
+            var_info: &var_info,
+            type_name: Some("int"),
+            writes: &writes,
+            goroutines: &[],
+            guards: &guards,
+            severity: Some(RaceSeverity::High),
+        });
+        assert!(markdown.starts_with("🔴 **Race: HIGH**"));
+        assert!(markdown.contains("```go\ncounter := 0\n```"));
+        assert!(markdown.contains("[line 4](file:///tmp/main.go#L4,2)"));
+        assert!(markdown.contains("- [line 6](file:///tmp/main.go#L6,3) write"));
+        assert!(markdown.contains("**Type**: `int` (Value)"));
+        assert!(!markdown.contains("**Lock**"));
+    }
+
+    #[test]
+    fn test_decoration_store_sends_only_changes() {
+        use crate::findings::DecorationStore;
+        use crate::types::{Decoration, DecorationType};
+        let decoration = |line: u32, kind: DecorationType| Decoration {
+            id: String::new(),
+            range: Range::new(Position::new(line, 1), Position::new(line, 4)),
+            kind,
+            hover_text: format!("line {}", line),
+            diagnostic: None,
+            bytes: None,
+            stale: false,
+        };
+        let uri = match tower_lsp::lsp_types::Url::parse("file:///tmp/main.go") {
+            Ok(uri) => uri,
+            Err(_) => return,
+        };
+        let mut store = DecorationStore::default();
+        let mut first = vec![
+            decoration(1, DecorationType::Declaration),
+            decoration(2, DecorationType::Use),
+            decoration(2, DecorationType::Use),
+        ];
+        let delta = store.update(&uri, Some(1), &mut first);
+        assert_eq!(first.len(), 2, "duplicate decorations collapse to one id");
+        assert_eq!(delta.added.len(), 2);
+        assert!(delta.removed.is_empty());
+
+        let mut second = vec![
+            decoration(1, DecorationType::Declaration),
+            decoration(3, DecorationType::Race),
+        ];
+        let delta = store.update(&uri, Some(2), &mut second);
+        assert!(!delta.full);
+        assert_eq!(delta.added.len(), 1);
+        assert_eq!(delta.added[0].kind, DecorationType::Race);
+        assert_eq!(delta.removed, vec![first[1].id.clone()]);
+        assert_eq!(
+            second[0].id, first[0].id,
+            "unchanged decoration keeps its id"
+        );
+
+        let resync = store.snapshot(&uri);
+        assert!(resync.full);
+        assert_eq!(resync.version, Some(2));
+        assert_eq!(resync.added.len(), 2);
+    }
+
+    #[test]
+    fn test_lock_value_returns_flags_by_value_constructors() {
+        let code = r#"package main
+
+import "sync"
+
+type Counter struct {
+	mu sync.Mutex
+	n  int
+}
+
+type Registry struct {
+	counter Counter
+}
+
+type Shared struct {
+	mu *sync.Mutex
+}
+
+func NewCounter() Counter {
+	return Counter{}
+}
+
+func NewRegistry() (Registry, error) {
+	r := Registry{}
+	return r, nil
+}
+
+func NewCounterPtr() *Counter {
+	return &Counter{}
+}
+
+func NewShared() Shared {
+	return Shared{}
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let holders = crate::locks::lock_holding_structs(&tree, code);
+        assert!(holders.contains("Counter"));
+        assert!(holders.contains("Registry"), "nested lock holder");
+        assert!(
+            !holders.contains("Shared"),
+            "pointer to mutex is shared, not copied"
+        );
+
+        let Ok(findings) = crate::locks::find_lock_value_returns(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let names: Vec<&str> = findings.iter().map(|f| f.function.as_str()).collect();
+        assert_eq!(names, vec!["NewCounter", "NewRegistry"]);
+        assert_eq!(
+            findings[0].result_type,
+            Range::new(Position::new(17, 18), Position::new(17, 25))
+        );
+        assert_eq!(
+            findings[0].fixable_returns,
+            vec![Range::new(Position::new(18, 8), Position::new(18, 17))]
+        );
+        assert_eq!(
+            findings[1].fixable_returns,
+            vec![Range::new(Position::new(23, 8), Position::new(23, 9))]
+        );
+    }
+
+    #[test]
+    fn test_interprocedural_race_follows_goroutine_calls() {
+        let code = r#"package main
+
+import "sync"
+
+var counter int
+var total int
+var mu sync.Mutex
+
+func increment() {
+	counter++
+}
+
+func add(n int) {
+	mu.Lock()
+	total += n
+	mu.Unlock()
+}
+
+func bump() {
+	increment()
+}
+
+func main() {
+	for i := 0; i < 10; i++ {
+		go bump()
+		go add(i)
+	}
+	hits := 0
+	record := func() {
+		hits++
+	}
+	go func() {
+		record()
+	}()
+	record()
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok(races) = crate::callgraph::find_interprocedural_races(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let summary: Vec<(&str, &str, u32, u32)> = races
+            .iter()
+            .map(|race| {
+                (
+                    race.variable.as_str(),
+                    race.function.as_str(),
+                    race.call_site.start.line,
+                    race.write.start.line,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![("counter", "increment", 24, 9), ("hits", "record", 32, 29)],
+            "locked writes in `add` must not be reported"
+        );
+    }
+
+    #[test]
+    fn test_pointer_receiver_methods_are_first_class_scopes() {
+        let code = r#"package main
+
+import "sync"
+
+type Counter struct {
+	mu sync.Mutex
+	n  int
+}
+
+func (c *Counter) inc() {
+	c.n++
+}
+
+func (c *Counter) Start() {
+	for i := 0; i < 3; i++ {
+		go c.inc()
+	}
+	go func() {
+		c.mu.Lock()
+		c.n = 0
+		c.mu.Unlock()
+	}()
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let counts = count_entities(&tree, code);
+        assert_eq!(counts.functions, 2);
+        assert_eq!(counts.goroutines, 2);
+
+        let graph = crate::analysis::build_graph_data(&tree, code);
+        assert!(graph.nodes.iter().any(|node| node.label == "Counter.inc"
+            && node.entity_type == crate::types::GraphEntityType::Method));
+
+        let context = find_node_at_cursor_with_context(&tree, Position::new(9, 19));
+        assert_eq!(
+            context.map(|c| c.context_type),
+            Some(CursorContextType::FunctionName)
+        );
+
+        let Ok(races) = crate::callgraph::find_interprocedural_races(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let summary: Vec<(&str, &str, u32, u32)> = races
+            .iter()
+            .map(|race| {
+                (
+                    race.variable.as_str(),
+                    race.function.as_str(),
+                    race.call_site.start.line,
+                    race.write.start.line,
+                )
+            })
+            .collect();
+        assert_eq!(summary, vec![("c.n", "inc", 15, 10)]);
+    }
+
+    #[test]
+    fn test_atomic_value_wrappers_count_as_synchronized() {
+        let code = r#"package main
+
+import "sync/atomic"
+
+type Config struct {
+	v atomic.Value
+}
+
+func (c *Config) Get() string {
+	return c.v.Load().(string)
+}
+
+func (c *Config) Set(s string) {
+	c.v.Store(s)
+}
+
+type Hits struct {
+	atomic.Int64
+	last int
+}
+
+func (h *Hits) Inc() {
+	h.Add(1)
+}
+
+func (h *Hits) Touch() {
+	h.Add(1)
+	h.last = 1
+}
+
+var cfg Config
+
+func main() {
+	go func() {
+		cfg.Set("x")
+	}()
+	_ = cfg.Get()
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let wrappers = crate::analysis::collect_atomic_wrappers(&tree, code);
+        assert!(wrappers.contains("Get"));
+        assert!(wrappers.contains("Set"));
+        assert!(wrappers.contains("Inc"), "embedded typed atomic");
+        assert!(!wrappers.contains("Touch"), "writes a plain field too");
+
+        let sync_funcs = crate::analysis::collect_sync_functions(&tree, code);
+        let set_call = Range::new(Position::new(34, 2), Position::new(34, 5));
+        assert_eq!(
+            determine_race_severity(&tree, set_call, code, false, &sync_funcs),
+            RaceSeverity::Low
+        );
+    }
+
+    #[test]
+    fn test_waitgroup_misuse() {
+        let code = r#"package main
+
+import "sync"
+
+func mismatch() {
+	var wg sync.WaitGroup
+	wg.Add(2)
+	go func() {
+		defer wg.Done()
+	}()
+	wg.Wait()
+}
+
+func addInside() {
+	var wg sync.WaitGroup
+	for i := 0; i < 3; i++ {
+		go func() {
+			wg.Add(1)
+			defer wg.Done()
+		}()
+	}
+	wg.Wait()
+}
+
+func neverDone() {
+	wg := &sync.WaitGroup{}
+	wg.Add(1)
+	go func() {}()
+	wg.Wait()
+}
+
+func balanced(items []int) {
+	var wg sync.WaitGroup
+	for range items {
+		wg.Add(1)
+		go func() {
+			defer wg.Done()
+		}()
+	}
+	wg.Wait()
+}
+
+func handedOff() {
+	var wg sync.WaitGroup
+	wg.Add(1)
+	go worker(&wg)
+	wg.Wait()
+}
+
+func worker(wg *sync.WaitGroup) {
+	defer wg.Done()
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok(issues) = crate::waitgroup::analyze_waitgroups(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let found: Vec<(&str, u32)> = issues
+            .iter()
+            .map(|issue| (issue.code(), issue.range.start.line))
+            .collect();
+        assert!(found.contains(&("waitgroup-add-done-mismatch", 10)));
+        assert!(found.contains(&("waitgroup-add-in-goroutine", 17)));
+        assert!(found.contains(&("waitgroup-wait-unsatisfiable", 28)));
+        assert_eq!(found.len(), 3, "{:?}", found);
+
+        let graph = crate::analysis::build_graph_data(&tree, code);
+        assert_eq!(
+            graph
+                .nodes
+                .iter()
+                .filter(|node| node.entity_type == crate::types::GraphEntityType::SyncBlock)
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_unbuffered_channel_deadlock() {
+        let code = r#"package main
+
+func sendOnly() {
+	ch := make(chan int)
+	ch <- 1
+	<-ch
+}
+
+func receiveOnly() {
+	var done = make(chan struct{}, 0)
+	<-done
+}
+
+func buffered() {
+	ch := make(chan int, 1)
+	ch <- 1
+	<-ch
+}
+
+func paired() {
+	ch := make(chan int)
+	go func() {
+		ch <- 1
+	}()
+	<-ch
+}
+
+func handedOff() {
+	ch := make(chan int)
+	go produce(ch)
+	<-ch
+}
+
+func produce(ch chan int) {
+	ch <- 1
+}
+
+func selected() {
+	ch := make(chan int)
+	select {
+	case ch <- 1:
+	default:
+	}
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok(deadlocks) = crate::channels::find_channel_deadlocks(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let found: Vec<(&str, crate::channels::ChannelOp, u32)> = deadlocks
+            .iter()
+            .map(|d| (d.channel.as_str(), d.op, d.range.start.line))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("ch", crate::channels::ChannelOp::Send, 4),
+                ("done", crate::channels::ChannelOp::Receive, 10),
+            ]
+        );
+        assert_eq!(deadlocks[0].creation.start.line, 3);
+    }
+
+    #[test]
+    fn test_request_context_tags_errors_and_logs() {
+        let first = crate::trace::RequestContext::new("goanalyzer/cursor");
+        let second = crate::trace::RequestContext::new("goanalyzer/cursor");
+        assert_ne!(first.trace_id, second.trace_id);
+        assert!(first
+            .tag("started")
+            .starts_with(&format!("[{} goanalyzer/cursor]", first.trace_id)));
+
+        let error = first.error(tower_lsp::jsonrpc::Error::content_modified());
+        assert_eq!(error.code.code(), -32801);
+        assert!(error.message.contains(&first.trace_id));
+        assert_eq!(
+            error
+                .data
+                .and_then(|data| data["trace_id"].as_str().map(String::from)),
+            Some(first.trace_id.clone())
+        );
+        let failed: crate::error::AnalysisResult<u8> =
+            Err(AnalysisError::NoNodeAt(Position::new(0, 0)));
+        assert_eq!(first.logged("pass", failed.clone()), failed);
+    }
+
+    #[test]
+    fn test_send_on_closed_channel() {
+        let code = r#"package main
+
+func main() {
+	ch := make(chan int, 2)
+	ch <- 1
+	close(ch)
+	ch <- 2
+	go func() {
+		ch <- 3
+	}()
+	ch = make(chan int, 1)
+	ch <- 4
+}
+
+func deferred(out chan int) {
+	defer close(out)
+	out <- 1
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok(findings) = crate::channels::find_sends_after_close(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let sends: Vec<u32> = findings.iter().map(|f| f.send.start.line).collect();
+        assert_eq!(sends, vec![6, 8]);
+        assert!(findings
+            .iter()
+            .all(|f| f.close.start.line == 5 && f.channel == "ch"));
+    }
+
+    /// Minimal draft-07 validator covering the keywords `protocol_schema` uses.
+    fn validate_schema(
+        schema: &serde_json::Value,
+        value: &serde_json::Value,
+        root: &serde_json::Value,
+        path: &str,
+    ) -> Result<(), String> {
+        if let Some(accepts) = schema.as_bool() {
+            return match accepts {
+                true => Ok(()),
+                false => Err(format!("{}: {} is not allowed", path, value)),
+            };
+        }
+        if let Some(reference) = schema["$ref"].as_str() {
+            let name = reference.trim_start_matches("#/definitions/");
+            return validate_schema(&root["definitions"][name], value, root, path);
+        }
+        for option in schema["allOf"].as_array().into_iter().flatten() {
+            validate_schema(option, value, root, path)?;
+        }
+        let matching = |keyword: &str| {
+            schema[keyword].as_array().map(|options| {
+                options
+                    .iter()
+                    .filter(|option| validate_schema(option, value, root, path).is_ok())
+                    .count()
+            })
+        };
+        if matching("anyOf") == Some(0) {
+            return Err(format!("{}: no anyOf branch matches {}", path, value));
+        }
+        if matching("oneOf").is_some_and(|count| count != 1) {
+            return Err(format!(
+                "{}: not exactly one oneOf branch matches {}",
+                path, value
+            ));
+        }
+        if let Some(variants) = schema["enum"].as_array() {
+            if !variants.contains(value) {
+                return Err(format!("{}: {} is not in {:?}", path, value, variants));
+            }
+        }
+        let types: Vec<&str> = match &schema["type"] {
+            serde_json::Value::String(name) => vec![name.as_str()],
+            serde_json::Value::Array(names) => names.iter().filter_map(|n| n.as_str()).collect(),
+            _ => vec![],
+        };
+        let mut type_matches = types.is_empty();
+        for name in types {
+            type_matches |= match name {
+                "object" => value.is_object(),
+                "array" => value.is_array(),
+                "string" => value.is_string(),
+                "integer" => value.is_i64() || value.is_u64(),
+                "number" => value.is_number(),
+                "boolean" => value.is_boolean(),
+                "null" => value.is_null(),
+                other => return Err(format!("{}: unsupported type {}", path, other)),
+            };
+        }
+        if !type_matches {
+            return Err(format!(
+                "{}: expected {}, got {}",
+                path, schema["type"], value
+            ));
+        }
+        if let Some(items) = value.as_array() {
+            for (i, item) in items.iter().enumerate() {
+                validate_schema(&schema["items"], item, root, &format!("{}[{}]", path, i))?;
+            }
+        }
+        if let Some(object) = value.as_object() {
+            for required in schema["required"].as_array().into_iter().flatten() {
+                let key = required.as_str().unwrap_or_default();
+                if !object.contains_key(key) {
+                    return Err(format!("{}: missing required {}", path, key));
+                }
+            }
+            for (key, field) in object {
+                let field_path = format!("{}.{}", path, key);
+                match (
+                    schema["properties"].get(key),
+                    &schema["additionalProperties"],
+                ) {
+                    (Some(property), _) => validate_schema(property, field, root, &field_path)?,
+                    (None, serde_json::Value::Bool(false)) => {
+                        return Err(format!("{}: {} is not in the schema", path, key));
+                    }
+                    (None, serde_json::Value::Object(_)) => {
+                        validate_schema(&schema["additionalProperties"], field, root, &field_path)?
+                    }
+                    (None, _) => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_protocol_schema_matches_serde_types() {
+        use crate::types::*;
+        let schema = crate::protocol::protocol_schema();
+        let range = Range::new(Position::new(1, 2), Position::new(1, 5));
+        let decoration = Decoration {
+            id: "Use@1:2#0".to_string(),
+            range,
+            kind: DecorationType::Race,
+            hover_text: "race".to_string(),
+            diagnostic: Some(DecorationDiagnostic {
+                severity: DecorationDiagnosticSeverity::Warning,
+                code: "field-race-high".to_string(),
+                message: "race".to_string(),
+                href: Some("https://example.com".to_string()),
+            }),
+            bytes: Some(ByteRange {
+                start_byte: 14,
+                end_byte: 17,
+            }),
+            stale: true,
+        };
+        let plain = Decoration {
+            id: String::new(),
+            diagnostic: None,
+            bytes: None,
+            stale: false,
+            ..decoration.clone()
+        };
+        let code =
+            "package main\n\nfunc main() {\n\tch := make(chan int)\n\tgo func() { ch <- 1 }()\n}\n";
+        let graph = match parse_go(code) {
+            Ok(tree) => crate::analysis::build_graph_data(&tree, code),
+            Err(_) => return,
+        };
+        let samples = [
+            ("Decoration", serde_json::json!(decoration)),
+            ("Decoration", serde_json::json!(plain)),
+            ("GraphData", serde_json::json!(graph)),
+            (
+                "ProgressParams",
+                serde_json::json!(ProgressParams {
+                    message: "done".to_string(),
+                    trace_id: None,
+                }),
+            ),
+            (
+                "IndexingStatusParams",
+                serde_json::json!(IndexingStatusParams {
+                    uri: "file:///a.go".to_string(),
+                    variables: 1,
+                    functions: 1,
+                    channels: 0,
+                    goroutines: 0,
+                }),
+            ),
+            (
+                "ParseInfoParams",
+                serde_json::json!(ParseInfoParams {
+                    uri: "file:///a.go".to_string(),
+                    source: None,
+                    cache_hit: false,
+                    parse_ms: Some(3),
+                    code_len: 10,
+                }),
+            ),
+            (
+                "DecorationsDelta",
+                serde_json::json!(crate::findings::DecorationsDelta {
+                    uri: "file:///a.go".to_string(),
+                    version: Some(2),
+                    full: false,
+                    added: vec![plain.clone()],
+                    removed: vec!["x".to_string()],
+                    truncated: true,
+                }),
+            ),
+            (
+                "DecorationsParams",
+                serde_json::json!(crate::findings::DecorationsParams {
+                    uri: "file:///a.go".to_string(),
+                    version: None,
+                    decorations: vec![decoration],
+                    truncated: false,
+                }),
+            ),
+            (
+                "CursorCommandParams",
+                serde_json::json!({
+                    "textDocument": {"uri": "file:///a.go"},
+                    "position": {"line": 1, "character": 2},
+                    "delta": true,
+                }),
+            ),
+            (
+                "MermaidParams",
+                serde_json::json!(crate::mermaid::MermaidParams {
+                    uri: tower_lsp::lsp_types::Url::parse("file:///a.go")
+                        .unwrap_or_else(|_| panic!("invalid uri")),
+                    filter: crate::mermaid::GraphFilter {
+                        entities: vec![GraphEntityType::Channel],
+                        root: Some("main".to_string()),
+                        depth: Some(2),
+                    },
+                }),
+            ),
+            (
+                "GraphParams",
+                serde_json::json!(crate::workspace::GraphParams {
+                    uri: tower_lsp::lsp_types::Url::parse("file:///a.go")
+                        .unwrap_or_else(|_| panic!("invalid uri")),
+                    scope: crate::workspace::GraphScope::Package,
+                    format: crate::interchange::GraphFormat::Graphml,
+                }),
+            ),
+            (
+                "GraphDelta",
+                serde_json::json!(crate::graphdelta::GraphDelta {
+                    uri: "file:///a.go".to_string(),
+                    version: Some(3),
+                    removed_nodes: vec!["main.go::main::var::x#0".to_string()],
+                    removed_edges: vec![crate::types::GraphEdge {
+                        from: "main.go::fn::main#0".to_string(),
+                        to: "main.go::fn::worker#0".to_string(),
+                        edge_type: crate::types::GraphEdgeType::Call,
+                    }],
+                    ..Default::default()
+                }),
+            ),
+            (
+                "GraphQueryParams",
+                serde_json::json!(crate::query::GraphQueryParams {
+                    uri: tower_lsp::lsp_types::Url::parse("file:///a.go")
+                        .unwrap_or_else(|_| panic!("invalid uri")),
+                    scope: crate::workspace::GraphScope::File,
+                    query: crate::query::GraphQuery::Paths {
+                        from: "main.go::fn::main#0".to_string(),
+                        to: "main.go::fn::worker#0".to_string(),
+                        edge_types: vec![crate::types::GraphEdgeType::Call],
+                        max_paths: Some(3),
+                    },
+                }),
+            ),
+        ];
+        for (name, value) in &samples {
+            let definition = &schema["definitions"][*name];
+            assert!(definition.is_object(), "missing definition {}", name);
+            if let Err(e) = validate_schema(definition, value, &schema, name) {
+                panic!("{}", e);
+            }
+        }
+        let cursor_args: Result<CursorCommandParams, _> =
+            serde_json::from_value(samples[8].1.clone());
+        assert!(cursor_args.is_ok());
+        assert!(validate_schema(
+            &schema["definitions"]["Decoration"],
+            &serde_json::json!({"range": range, "kind": "Race", "hover_text": "", "extra": 1}),
+            &schema,
+            "Decoration"
+        )
+        .is_err());
+        // The flattened query must match one of its variants.
+        assert!(validate_schema(
+            &schema["definitions"]["GraphQueryParams"],
+            &serde_json::json!({"uri": "file:///a.go", "query": "paths", "from": "a"}),
+            &schema,
+            "GraphQueryParams"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_protocol_schema_artifact_is_current() {
+        let schema = crate::protocol::protocol_schema();
+        let path =
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("docs/protocol.schema.json");
+        if std::env::var_os("UPDATE_PROTOCOL_SCHEMA").is_some() {
+            let pretty = serde_json::to_string_pretty(&schema).unwrap_or_default();
+            assert!(std::fs::write(&path, pretty + "\n").is_ok());
+            return;
+        }
+        let artifact: Option<serde_json::Value> = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok());
+        assert_eq!(
+            artifact,
+            Some(schema),
+            "docs/protocol.schema.json is stale; regenerate it with `make schema`"
+        );
+    }
+
+    #[test]
+    fn test_session_recorder_ring_buffer_persists() {
+        use crate::record::{document_hash, RecordConfig, RecordEntry, SessionRecorder};
+        let dir =
+            std::env::temp_dir().join(format!("go-analyzer-record-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = RecordConfig {
+            enabled: true,
+            dir: dir.clone(),
+            capacity: 2,
+        };
+        let entry = |i: usize| RecordEntry {
+            timestamp_ms: i as u128,
+            trace_id: format!("t-{}", i),
+            method: "goanalyzer/cursor".to_string(),
+            uri: "file:///a.go".to_string(),
+            version: Some(i as i32),
+            document_hash: document_hash("package main\n"),
+            position: Some(Position::new(1, 1)),
+            findings: serde_json::json!([]),
+        };
+        let mut recorder = SessionRecorder::new(config.clone());
+        for i in 0..5 {
+            assert!(recorder.record(entry(i)).is_ok());
+        }
+        let traces = |dump: crate::record::SessionDump| -> Vec<String> {
+            dump.entries.into_iter().map(|e| e.trace_id).collect()
+        };
+        assert_eq!(traces(recorder.dump()), vec!["t-3", "t-4"]);
+        let lines = std::fs::read_to_string(recorder.path())
+            .map(|text| text.lines().count())
+            .unwrap_or_default();
+        assert!(lines <= 4, "session file is compacted, got {} lines", lines);
+
+        let resumed = SessionRecorder::new(config);
+        let dump = resumed.dump();
+        let schema = crate::protocol::protocol_schema();
+        assert!(validate_schema(
+            &schema["definitions"]["SessionDump"],
+            &serde_json::json!(dump),
+            &schema,
+            "SessionDump"
+        )
+        .is_ok());
+        assert_eq!(traces(dump), vec!["t-3", "t-4"]);
+
+        let disabled = SessionRecorder::new(crate::record::RecordConfig {
+            enabled: false,
+            dir: dir.join("disabled"),
+            capacity: 2,
+        });
+        assert!(disabled.dump().entries.is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_double_close_of_channel() {
+        let code = r#"package main
+
+import "sync"
+
+func twice(ch chan int) {
+	defer close(ch)
+	close(ch)
+}
+
+func branches(ch chan int, ok bool) {
+	if ok {
+		close(ch)
+	} else {
+		close(ch)
+	}
+}
+
+func early(ch chan int, failed bool) {
+	if failed {
+		close(ch)
+		return
+	}
+	close(ch)
+}
+
+func maybe(ch chan int, ok bool) {
+	if ok {
+		close(ch)
+	}
+	close(ch)
+}
+
+func workers(done chan struct{}) {
+	for i := 0; i < 3; i++ {
+		go func() {
+			close(done)
+		}()
+	}
+}
+
+func guarded(ch chan int, once *sync.Once) {
+	for i := 0; i < 3; i++ {
+		once.Do(func() { close(ch) })
+	}
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok(findings) = crate::channels::find_double_closes(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let summary: Vec<(u32, Option<u32>, bool)> = findings
+            .iter()
+            .map(|f| {
+                (
+                    f.close.start.line,
+                    f.first_close.map(|r| r.start.line),
+                    f.conditional,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![(6, Some(5), false), (29, Some(27), true), (35, None, false)]
+        );
+    }
+
+    #[test]
+    fn test_double_close_follows_reassignment_and_shadowing() {
+        let code = r#"package main
+
+func reassigned() {
+	ch := make(chan int)
+	close(ch)
+	ch = make(chan int)
+	close(ch)
+}
+
+func shadowed(ch chan int) {
+	close(ch)
+	if true {
+		ch := make(chan int)
+		close(ch)
+	}
+}
+
+func redeclaredInLoop() {
+	for i := 0; i < 3; i++ {
+		ch := make(chan int)
+		go func() { close(ch) }()
+		close(ch)
+	}
+}
+
+func sameChannel(ch chan int) {
+	close(ch)
+	if true {
+		x := make(chan int)
+		close(x)
+	}
+	close(ch)
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok(findings) = crate::channels::find_double_closes(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let summary: Vec<(u32, Option<u32>)> = findings
+            .iter()
+            .map(|f| (f.close.start.line, f.first_close.map(|r| r.start.line)))
+            .collect();
+        // The closes in the loop body close the same channel; reassigned and shadowed channels
+        // are other channels.
+        assert_eq!(summary, vec![(21, Some(20)), (31, Some(26))]);
+    }
+
+    #[test]
+    fn test_nil_channel_operations() {
+        let code = r#"package main
+
+func main() {
+	var ch chan int
+	ch <- 1
+	<-ch
+	close(ch)
+	ch = make(chan int, 1)
+	ch <- 2
+}
+
+func loop(n int) {
+	var results chan int
+	for i := 0; i < n; i++ {
+		if results != nil {
+			<-results
+		}
+		results = make(chan int, 1)
+	}
+}
+
+func disabled(in chan int) {
+	var out chan int
+	select {
+	case v := <-in:
+		_ = v
+	case out <- 1:
+	}
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        use crate::channels::NilChannelOpKind;
+        let Ok(findings) = crate::channels::find_nil_channel_ops(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let summary: Vec<(NilChannelOpKind, u32)> = findings
+            .iter()
+            .map(|f| (f.kind, f.range.start.line))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (NilChannelOpKind::Send, 4),
+                (NilChannelOpKind::Receive, 5),
+                (NilChannelOpKind::Close, 6),
+            ]
+        );
+        assert!(findings.iter().all(|f| f.declaration.start.line == 3));
+    }
+
+    /// Shell lines a fake semantic helper starts with to pass the version handshake.
+    #[cfg(unix)]
+    fn helper_handshake() -> String {
+        format!(
+            "IFS= read -r hello\nprintf '%s\\n' '{{\"jsonrpc\":\"2.0\",\"id\":0,\"result\":{{\"version\":\"{}\"}}}}'\n",
+            crate::bootstrap::helper_version()
+        )
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_semantic_coalescer_limits_helper_invocations() {
+        use crate::bootstrap::BuildConsent;
+        use crate::semantic::{SemanticCoalescer, SemanticConfig};
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("go-analyzer-semantic-{}", std::process::id()));
+        let _ = std::fs::create_dir_all(&dir);
+        let log = dir.join("calls.log");
+        let starts = dir.join("starts.log");
+        let helper = dir.join("helper.sh");
+        // A daemon answering every request with `null`, after logging it.
+        let script = format!(
+            r#"#!/bin/sh
+{}echo start >> '{}'
+while IFS= read -r line; do
+  printf '%s\n' "$line" >> '{}'
+  id=$(printf '%s\n' "$line" | sed 's/^{{"jsonrpc":"2.0","id":\([0-9]*\).*/\1/')
+  sleep 0.1
+  printf '{{"jsonrpc":"2.0","id":%s,"result":null}}\n' "$id"
+done
+"#,
+            helper_handshake(),
+            starts.display(),
+            log.display()
+        );
+        if std::fs::write(&helper, script).is_err()
+            || std::fs::set_permissions(&helper, std::fs::Permissions::from_mode(0o755)).is_err()
+        {
+            return;
+        }
+        let coalescer = SemanticCoalescer::new(SemanticConfig {
+            enabled: true,
+            helper_path: helper.display().to_string(),
+            timeout_ms: 2000,
+            max_in_flight: 1,
+            coalesce_ms: 30,
+            build: BuildConsent::Never,
+            build_dir: dir.join("build"),
+        });
+        let uri = match tower_lsp::lsp_types::Url::from_file_path(dir.join("main.go")) {
+            Ok(uri) => uri,
+            Err(_) => return,
+        };
+        let calls = || {
+            std::fs::read_to_string(&log)
+                .map(|text| text.lines().count())
+                .unwrap_or_default()
+        };
+
+        // A burst of edits: only the last request for the lane reaches the helper.
+        let code = "package main\n";
+        let burst = (0..5u32).map(|i| coalescer.resolve("cursor", &uri, Position::new(0, i), code));
+        let results = futures::future::join_all(burst).await;
+        assert!(results.iter().all(Option::is_none));
+        assert_eq!(calls(), 1);
+
+        // Identical concurrent requests share one invocation.
+        let first = coalescer.resolve("hover", &uri, Position::new(0, 1), code);
+        let second = async {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            coalescer
+                .resolve("hover", &uri, Position::new(0, 1), code)
+                .await
+        };
+        let _ = futures::join!(first, second);
+        assert_eq!(calls(), 2);
+
+        // One helper process served both, and the unchanged text was sent only once.
+        let count = |path: &std::path::Path, needle: &str| {
+            std::fs::read_to_string(path)
+                .map(|text| text.lines().filter(|line| line.contains(needle)).count())
+                .unwrap_or_default()
+        };
+        assert_eq!(count(&starts, "start"), 1);
+        assert_eq!(count(&log, "\"content\""), 1);
+        coalescer.stop().await;
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_a_stale_semantic_helper_is_not_run() {
+        use crate::bootstrap::BuildConsent;
+        use crate::semantic::{SemanticConfig, SemanticHelper};
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir =
+            std::env::temp_dir().join(format!("go-analyzer-semantic-stale-{}", std::process::id()));
+        let _ = std::fs::create_dir_all(&dir);
+        let asked = dir.join("asked.log");
+        let stale = dir.join("stale.sh");
+        let current = dir.join("current.sh");
+        // The stale helper was built from other sources and logs what it is sent; the current
+        // one answers every request with `null`.
+        let stale_script = format!(
+            r#"#!/bin/sh
+while IFS= read -r line; do
+  printf '%s\n' "$line" >> '{}'
+  printf '%s\n' '{{"jsonrpc":"2.0","id":0,"result":{{"version":"old"}}}}'
+done
+"#,
+            asked.display()
+        );
+        let current_script = format!(
+            r#"#!/bin/sh
+{}while IFS= read -r line; do
+  id=$(printf '%s\n' "$line" | sed 's/^{{"jsonrpc":"2.0","id":\([0-9]*\).*/\1/')
+  printf '{{"jsonrpc":"2.0","id":%s,"result":null}}\n' "$id"
+done
+"#,
+            helper_handshake()
+        );
+        for (path, script) in [(&stale, stale_script), (&current, current_script)] {
+            if std::fs::write(path, script).is_err()
+                || std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).is_err()
+            {
+                return;
+            }
+        }
+        let helper = SemanticHelper::new(SemanticConfig {
+            enabled: true,
+            helper_path: stale.display().to_string(),
+            timeout_ms: 2000,
+            max_in_flight: 1,
+            coalesce_ms: 0,
+            build: BuildConsent::Never,
+            build_dir: dir.join("build"),
+        });
+        let uri = match tower_lsp::lsp_types::Url::from_file_path(dir.join("main.go")) {
+            Ok(uri) => uri,
+            Err(_) => panic!("valid uri"),
+        };
+        let asked_lines = || {
+            std::fs::read_to_string(&asked)
+                .map(|text| text.lines().map(str::to_string).collect::<Vec<_>>())
+                .unwrap_or_default()
+        };
+
+        // The handshake rejects the stale helper, which is then neither sent requests nor
+        // started again.
+        assert!(!helper.start().await);
+        assert!(helper
+            .resolve(&uri, Position::new(0, 1), "package main\n")
+            .await
+            .is_none());
+        let lines = asked_lines();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].contains("\"method\":\"version\""));
+
+        // A current build is run once it is in place.
+        helper.set_path(current);
+        assert!(helper.start().await);
+        helper.stop().await;
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_semantic_helper_multiplexes_requests_and_restarts_after_a_crash() {
+        use crate::bootstrap::BuildConsent;
+        use crate::semantic::{SemanticConfig, SemanticHelper};
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::{Duration, Instant};
+
+        let dir = std::env::temp_dir().join(format!(
+            "go-analyzer-semantic-daemon-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::create_dir_all(&dir);
+        let starts = dir.join("starts.log");
+        let helper = dir.join("helper.sh");
+        // Reads two requests and answers the second first, naming each variable after the
+        // requested column; exits on a request for `crash`.
+        let script = format!(
+            r#"#!/bin/sh
+{}echo start >> '{}'
+answer() {{
+  id=$(printf '%s\n' "$1" | sed 's/^{{"jsonrpc":"2.0","id":\([0-9]*\).*/\1/')
+  col=$(printf '%s\n' "$1" | sed 's/.*"col":\([0-9]*\).*/\1/')
+  printf '{{"jsonrpc":"2.0","id":%s,"result":{{"name":"col%s","decl":{{"start":{{"line":0,"col":0}},"end":{{"line":0,"col":1}}}},"uses":[],"is_pointer":false}}}}\n' "$id" "$col"
+}}
+while IFS= read -r first; do
+  case "$first" in *crash*) exit 1;; esac
+  IFS= read -r second || exit 0
+  answer "$second"
+  answer "$first"
+done
+"#,
+            helper_handshake(),
+            starts.display()
+        );
+        if std::fs::write(&helper, script).is_err()
+            || std::fs::set_permissions(&helper, std::fs::Permissions::from_mode(0o755)).is_err()
+        {
+            return;
+        }
+        let helper = SemanticHelper::new(SemanticConfig {
+            enabled: true,
+            helper_path: helper.display().to_string(),
+            timeout_ms: 2000,
+            max_in_flight: 2,
+            coalesce_ms: 0,
+            build: BuildConsent::Never,
+            build_dir: dir.join("build"),
+        });
+        let uri = match tower_lsp::lsp_types::Url::from_file_path(dir.join("main.go")) {
+            Ok(uri) => uri,
+            Err(_) => panic!("valid uri"),
+        };
+        let started = || {
+            std::fs::read_to_string(&starts)
+                .map(|text| text.lines().count())
+                .unwrap_or_default()
+        };
+        let name = |variable: Option<crate::semantic::SemanticVariable>| {
+            variable.map(|variable| variable.info.name)
+        };
+
+        // Answers arriving out of order reach the request they belong to.
+        let code = "package main\n";
+        let (first, second) = futures::join!(
+            helper.resolve(&uri, Position::new(0, 1), code),
+            helper.resolve(&uri, Position::new(0, 2), code)
+        );
+        assert_eq!(name(first), Some("col1".to_string()));
+        assert_eq!(name(second), Some("col2".to_string()));
+        assert_eq!(started(), 1);
+
+        // A crash fails the request in flight without waiting out the timeout, and the next
+        // request starts a new helper.
+        let crash_started = Instant::now();
+        assert!(helper
+            .resolve(&uri, Position::new(0, 1), "package crash\n")
+            .await
+            .is_none());
+        assert!(crash_started.elapsed() < Duration::from_millis(1500));
+        let (first, second) = futures::join!(
+            helper.resolve(&uri, Position::new(0, 3), code),
+            helper.resolve(&uri, Position::new(0, 4), code)
+        );
+        assert_eq!(name(first), Some("col3".to_string()));
+        assert_eq!(name(second), Some("col4".to_string()));
+        assert_eq!(started(), 2);
+
+        helper.stop().await;
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_loop_variable_capture() {
+        let code = r#"package main
+
+func main() {
+	items := []string{"a", "b"}
+	for i, item := range items {
+		go func() {
+			println(i, item)
+		}()
+	}
+	for n := 0; n < 3; n++ {
+		go func(prefix string) {
+			println(prefix, n)
+		}("n")
+	}
+	for _, item := range items {
+		item := item
+		go func() {
+			println(item)
+		}()
+		go func(item string) {
+			println(item)
+		}(item)
+	}
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok(findings) = crate::loopvar::find_loop_variable_captures(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let summary: Vec<(&str, u32, u32)> = findings
+            .iter()
+            .map(|f| {
+                (
+                    f.variable.as_str(),
+                    f.range.start.line,
+                    f.declaration.start.line,
+                )
+            })
+            .collect();
+        assert_eq!(summary, vec![("i", 6, 4), ("item", 6, 4), ("n", 11, 9)]);
+        let fixes: Vec<(String, u32, u32, String, u32, u32)> = findings
+            .iter()
+            .filter_map(|f| f.fix.clone())
+            .map(|fix| {
+                (
+                    fix.parameter,
+                    fix.parameter_at.line,
+                    fix.parameter_at.character,
+                    fix.argument,
+                    fix.argument_at.line,
+                    fix.argument_at.character,
+                )
+            })
+            .collect();
+        assert_eq!(
+            fixes,
+            vec![
+                ("i int".to_string(), 5, 10, "i".to_string(), 7, 4),
+                ("item string".to_string(), 5, 10, "item".to_string(), 7, 4),
+                (", n int".to_string(), 10, 23, ", n".to_string(), 12, 7),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_graph_nodes_carry_byte_offsets() {
+        let code = "package main\n\nfunc main() {\n\tdone := make(chan bool)\n\tgo func() { done <- true }()\n\t<-done\n}\n";
+        let graph = match parse_go(code) {
+            Ok(tree) => crate::analysis::build_graph_data(&tree, code),
+            Err(_) => return,
+        };
+        assert!(!graph.nodes.is_empty());
+        let lines = crate::util::LineIndex::new(code);
+        for node in &graph.nodes {
+            let bytes = match node.bytes {
+                Some(bytes) => bytes,
+                None => panic!("node {} has no byte offsets", node.id),
+            };
+            assert_eq!(lines.offset(node.range.start), bytes.start_byte);
+            assert_eq!(lines.offset(node.range.end), bytes.end_byte);
+        }
+        let channel = graph
+            .nodes
+            .iter()
+            .find(|node| node.label == "done")
+            .and_then(|node| node.bytes);
+        if let Some(bytes) = channel {
+            assert_eq!(&code[bytes.start_byte..bytes.end_byte], "done");
+        }
+        let lines = crate::util::LineIndex::new("ab\ncd");
+        assert_eq!(lines.offset(Position::new(1, 1)), 4);
+        assert_eq!(lines.offset(Position::new(5, 0)), 5);
+    }
+
+    #[test]
+    fn test_unlock_issues() {
+        let code = r#"package main
+
+import "sync"
+
+type Store struct {
+	mu   sync.Mutex
+	data map[string]int
+}
+
+func (s *Store) Get(key string) (int, bool) {
+	s.mu.Lock()
+	v, ok := s.data[key]
+	if !ok {
+		return 0, false
+	}
+	s.mu.Unlock()
+	return v, true
+}
+
+func (s *Store) Set(key string, v int) {
+	s.mu.Lock()
+	s.data[key] = v
+}
+
+func (s *Store) Delete(key string) {
+	s.mu.Lock()
+	defer s.mu.Unlock()
+	delete(s.data, key)
+}
+
+func (s *Store) Checked(key string) int {
+	s.mu.Lock()
+	v, ok := s.data[key]
+	if !ok {
+		s.mu.Unlock()
+		return 0
+	}
+	s.mu.Unlock()
+	return v
+}
+
+func (s *Store) lockAll() {
+	s.mu.Lock()
+}
+
+func (s *Store) Must(key string) int {
+	s.mu.Lock()
+	v, ok := s.data[key]
+	if !ok {
+		panic("missing")
+	}
+	s.mu.Unlock()
+	return v
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        use crate::locks::UnlockIssueKind;
+        let Ok(issues) = crate::locks::find_unlock_issues(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let summary: Vec<(UnlockIssueKind, u32, Option<u32>)> = issues
+            .iter()
+            .map(|i| (i.kind, i.lock.start.line, i.exit.map(|e| e.start.line)))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (UnlockIssueKind::EarlyExit, 10, Some(13)),
+                (UnlockIssueKind::Missing, 20, None),
+                (UnlockIssueKind::EarlyExit, 46, Some(49)),
+            ]
+        );
+        let fix = match &issues[0].fix {
+            Some(fix) => fix,
+            None => panic!("expected a defer fix"),
+        };
+        assert_eq!(fix.insert_at, Position::new(10, 12));
+        assert_eq!(fix.insert, "\n\tdefer s.mu.Unlock()");
+        assert_eq!(
+            fix.removals,
+            vec![Range::new(Position::new(15, 0), Position::new(16, 0))]
+        );
+        assert!(issues[1].fix.is_none());
+    }
+
+    #[test]
+    fn test_reanchor_after_incremental_edits() {
+        use crate::anchor::{apply_content_change, reanchor_decorations, reanchor_diagnostics};
+        use crate::types::{ByteRange, Decoration, DecorationType};
+        use tower_lsp::lsp_types::{
+            Diagnostic, DiagnosticRelatedInformation, Location, TextDocumentContentChangeEvent, Url,
+        };
+
+        let mut text = ropey::Rope::from_str("x := \"é\"\nfoo(x)\nbar(x)\n");
+        let change = |range: Range, new_text: &str| TextDocumentContentChangeEvent {
+            range: Some(range),
+            range_length: None,
+            text: new_text.to_string(),
+        };
+        // UTF-16 column 8 is after the closing quote; `é` is two bytes but one code unit.
+        let edit = change(
+            Range::new(Position::new(0, 8), Position::new(0, 8)),
+            "\ny := 1",
+        );
+        let delta = match apply_content_change(&mut text, &edit) {
+            Some(delta) => delta,
+            None => panic!("ranged change has a delta"),
+        };
+        assert_eq!(text, "x := \"é\"\ny := 1\nfoo(x)\nbar(x)\n");
+        assert_eq!(delta.old.start, Position::new(0, 9));
+        assert_eq!(delta.new_end, Position::new(1, 6));
+
+        let rename = change(
+            Range::new(Position::new(2, 0), Position::new(2, 3)),
+            "fooBar",
+        );
+        let second = match apply_content_change(&mut text, &rename) {
+            Some(delta) => delta,
+            None => panic!("ranged change has a delta"),
+        };
+        assert_eq!(text, "x := \"é\"\ny := 1\nfooBar(x)\nbar(x)\n");
+
+        let decoration = |line: u32, start: u32, start_byte: usize| Decoration {
+            id: String::new(),
+            range: Range::new(Position::new(line, start), Position::new(line, start + 1)),
+            kind: DecorationType::Use,
+            hover_text: "x".to_string(),
+            diagnostic: None,
+            bytes: Some(ByteRange {
+                start_byte,
+                end_byte: start_byte + 1,
+            }),
+            stale: false,
+        };
+        // `x` in `foo(x)`, `x` in `bar(x)` and `foo` itself, as computed before both edits.
+        let mut decorations = vec![
+            decoration(1, 4, 14),
+            decoration(2, 4, 21),
+            decoration(1, 0, 10),
+        ];
+        reanchor_decorations(&mut decorations, &[delta, second]);
+        let anchored: Vec<(u32, u32, usize, bool)> = decorations
+            .iter()
+            .map(|d| {
+                (
+                    d.range.start.line,
+                    d.range.start.character,
+                    d.bytes.map(|b| b.start_byte).unwrap_or_default(),
+                    d.stale,
+                )
+            })
+            .collect();
+        assert_eq!(anchored, vec![(2, 7, 24, true), (3, 4, 31, true)]);
+        assert_eq!(text.byte_slice(24..25), "x");
+        assert_eq!(text.byte_slice(31..32), "x");
+
+        let edited = match Url::parse("file:///tmp/a.go") {
+            Ok(uri) => uri,
+            Err(_) => panic!("valid uri"),
+        };
+        let other = match Url::parse("file:///tmp/b.go") {
+            Ok(uri) => uri,
+            Err(_) => panic!("valid uri"),
+        };
+        let related = |uri: &Url| DiagnosticRelatedInformation {
+            location: Location::new(
+                uri.clone(),
+                Range::new(Position::new(2, 0), Position::new(2, 3)),
+            ),
+            message: "other access".to_string(),
+        };
+        let mut diagnostics = vec![Diagnostic {
+            range: Range::new(Position::new(2, 0), Position::new(2, 6)),
+            related_information: Some(vec![related(&edited), related(&other)]),
+            data: Some(serde_json::json!({"start_byte": 17, "end_byte": 23, "fix": "unlock"})),
+            ..Default::default()
+        }];
+        reanchor_diagnostics(&edited, &mut diagnostics, &[delta]);
+        assert_eq!(diagnostics[0].range.start, Position::new(3, 0));
+        // The producer's own data survives next to the anchor.
+        assert_eq!(
+            diagnostics[0].data,
+            Some(
+                serde_json::json!({"start_byte": 24, "end_byte": 30, "stale": true, "fix": "unlock"})
+            )
+        );
+        // Only the related location in the edited file moves.
+        let lines: Vec<u32> = diagnostics[0]
+            .related_information
+            .iter()
+            .flatten()
+            .map(|info| info.location.range.start.line)
+            .collect();
+        assert_eq!(lines, vec![3, 2]);
+
+        let full = TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: "package main\n".to_string(),
+        };
+        assert!(apply_content_change(&mut text, &full).is_none());
+        assert_eq!(text, "package main\n");
+    }
+
+    #[test]
+    fn test_double_locks() {
+        let code = r#"package main
+
+import "sync"
+
+var registryMu sync.Mutex
+
+type Cache struct {
+	mu    sync.RWMutex
+	items map[string]int
+}
+
+func (c *Cache) Get(key string) int {
+	c.mu.RLock()
+	defer c.mu.RUnlock()
+	return c.items[key]
+}
+
+func (c *Cache) Len() int {
+	c.mu.RLock()
+	defer c.mu.RUnlock()
+	return len(c.items)
+}
+
+func (c *Cache) Set(key string, v int) {
+	c.mu.Lock()
+	defer c.mu.Unlock()
+	if c.Len() > 100 {
+		return
+	}
+	c.items[key] = v
+}
+
+func (c *Cache) Sum(keys []string) int {
+	c.mu.RLock()
+	defer c.mu.RUnlock()
+	total := 0
+	for _, k := range keys {
+		total += c.Get(k)
+	}
+	return total
+}
+
+func register() {
+	registryMu.Lock()
+	registryMu.Lock()
+	registryMu.Unlock()
+}
+
+func reset() {
+	registryMu.Lock()
+	registryMu.Unlock()
+	registryMu.Lock()
+	registryMu.Unlock()
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok(findings) = crate::locks::find_double_locks(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let summary: Vec<(&str, u32, u32, Option<String>)> = findings
+            .iter()
+            .map(|f| {
+                (
+                    f.mutex.as_str(),
+                    f.first.start.line,
+                    f.second.start.line,
+                    f.callee.as_ref().map(|(name, _)| name.clone()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("c.mu", 24, 26, Some("Len".to_string())),
+                ("registryMu", 43, 44, None),
+            ]
+        );
+        assert_eq!(
+            findings[0].callee.as_ref().map(|(_, lock)| lock.start.line),
+            Some(18)
+        );
+    }
+
+    #[test]
+    fn test_workspace_summary_per_package() {
+        use crate::summary::{
+            collect_go_files, summarize, synchronized_goroutines, FileReport, SummarySort,
+        };
+        use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString};
+
+        let root = std::env::temp_dir().join(format!("go-analyzer-summary-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let files = [
+            (
+                "api/handler.go",
+                "package api\n\nfunc Serve() {\n\tgo work()\n\tgo work()\n}\n\nfunc work() {}\n",
+            ),
+            (
+                "store/store.go",
+                "package store\n\nimport \"sync\"\n\nfunc Run() {\n\tvar wg sync.WaitGroup\n\twg.Add(1)\n\tgo func() { defer wg.Done() }()\n\twg.Wait()\n}\n",
+            ),
+            ("store/util.go", "package store\n\nfunc helper() {}\n"),
+            ("vendor/dep/dep.go", "package dep\n\nfunc Dep() { go Dep() }\n"),
+        ];
+        for (path, code) in files {
+            let path = root.join(path);
+            if let Some(dir) = path.parent() {
+                let _ = std::fs::create_dir_all(dir);
+            }
+            if std::fs::write(&path, code).is_err() {
+                return;
+            }
+        }
+        let paths = collect_go_files(&root);
+        assert_eq!(paths.len(), 3);
+        let diagnostic = |code: &str, severity| Diagnostic {
+            severity: Some(severity),
+            code: Some(NumberOrString::String(code.to_string())),
+            ..Default::default()
+        };
+        let mut reports = Vec::new();
+        for path in paths {
+            let code = std::fs::read_to_string(&path).unwrap_or_default();
+            let tree = match parse_go(&code) {
+                Ok(tree) => tree,
+                Err(_) => return,
+            };
+            let diagnostics = if path.ends_with("handler.go") {
+                vec![
+                    diagnostic("channel-deadlock", DiagnosticSeverity::ERROR),
+                    diagnostic("loop-variable-capture", DiagnosticSeverity::WARNING),
+                ]
+            } else {
+                Vec::new()
+            };
+            reports.push(FileReport {
+                counts: crate::analysis::count_entities(&tree, &code),
+                synchronized_goroutines: synchronized_goroutines(&tree, &code),
+                diagnostics,
+                path,
+            });
+        }
+        let summary = summarize(
+            std::slice::from_ref(&root),
+            reports,
+            SummarySort::SyncCoverage,
+        );
+        // The command-line summary takes the same sort options.
+        let args = |list: &[&str]| list.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let dir = root.display().to_string();
+        assert_eq!(
+            crate::cli::run(&args(&["summary", &dir, "--sort", "sync_coverage"])),
+            Some(0)
+        );
+        assert_eq!(
+            crate::cli::run(&args(&["summary", &dir, "--sort", "size"])),
+            Some(2)
+        );
+        assert_eq!(
+            crate::cli::run(&args(&["summary", &format!("{}/missing", dir)])),
+            Some(1)
+        );
+        let _ = std::fs::remove_dir_all(&root);
+        let rows: Vec<(&str, usize, usize, usize, Option<f64>)> = summary
+            .packages
+            .iter()
+            .map(|p| {
+                (
+                    p.package.as_str(),
+                    p.files,
+                    p.goroutines,
+                    p.findings,
+                    p.sync_coverage,
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![("api", 1, 2, 2, Some(0.0)), ("store", 2, 1, 0, Some(1.0)),]
+        );
+        assert_eq!(summary.totals.files, 3);
+        assert_eq!(summary.totals.errors, 1);
+        assert_eq!(
+            summary.totals.findings_by_code.get("channel-deadlock"),
+            Some(&1)
+        );
+        assert_eq!(summary.totals.sync_coverage, Some(1.0 / 3.0));
+        assert_eq!(
+            summary.to_table(),
+            "package  files  goroutines  channels  sync  findings  errors  warnings\n\
+             api          1           2         0    0%         2       1         1\n\
+             store        2           1         0  100%         0       0         0\n\
+             total        3           3         0   33%         2       1         1\n"
+        );
+
+        let schema = crate::protocol::protocol_schema();
+        let value = serde_json::json!(summary);
+        if let Err(e) = validate_schema(
+            &schema["definitions"]["WorkspaceSummary"],
+            &value,
+            &schema,
+            "WorkspaceSummary",
+        ) {
+            panic!("{}", e);
+        }
+    }
+    #[cfg(unix)]
+    #[test]
+    fn test_doctor_reports_setup_problems() {
+        use crate::bootstrap::BuildConsent;
+        use crate::doctor::{check_go_mod, check_semantic_helper, run_doctor, CheckStatus};
+        use crate::semantic::SemanticConfig;
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("go-analyzer-doctor-{}", std::process::id()));
+        let module = dir.join("module");
+        let package = module.join("pkg");
+        let outside = dir.join("scratch");
+        let helper = dir.join("helper.sh");
+        if std::fs::create_dir_all(&package).is_err()
+            || std::fs::create_dir_all(&outside).is_err()
+            || std::fs::write(module.join("go.mod"), "module example.com/m\n").is_err()
+            || std::fs::write(&helper, "#!/bin/sh\n").is_err()
+            || std::fs::set_permissions(&helper, std::fs::Permissions::from_mode(0o644)).is_err()
+        {
+            return;
+        }
+        let config = |enabled: bool, helper_path: String| SemanticConfig {
+            enabled,
+            helper_path,
+            timeout_ms: 2000,
+            max_in_flight: 1,
+            coalesce_ms: 0,
+            build: BuildConsent::Never,
+            build_dir: dir.join("build"),
+        };
+
+        assert_eq!(
+            check_go_mod(std::slice::from_ref(&package)).status,
+            CheckStatus::Ok
+        );
+        let missing = check_go_mod(&[package.clone(), outside.clone()]);
+        assert_eq!(missing.status, CheckStatus::Warning);
+        assert!(missing.detail.contains("scratch"));
+        assert!(missing.hint.is_some());
+
+        let helper_path = helper.display().to_string();
+        let disabled = check_semantic_helper(&config(false, helper_path.clone()));
+        assert_eq!(disabled.status, CheckStatus::Warning);
+        let not_executable = check_semantic_helper(&config(true, helper_path.clone()));
+        assert_eq!(not_executable.status, CheckStatus::Error);
+        assert!(not_executable.detail.contains("not executable"));
+        let absent = check_semantic_helper(&config(true, dir.join("absent").display().to_string()));
+        assert_eq!(absent.status, CheckStatus::Error);
+        assert!(absent.detail.contains("not found"));
+        if std::fs::set_permissions(&helper, std::fs::Permissions::from_mode(0o755)).is_err() {
+            return;
+        }
+        assert_eq!(
+            check_semantic_helper(&config(true, helper_path.clone())).status,
+            CheckStatus::Ok
+        );
+
+        let report = run_doctor(&config(true, helper_path), &[module], &dir.join("cache"));
+        let _ = std::fs::remove_dir_all(&dir);
+        let status = |name: &str| {
+            report
+                .checks
+                .iter()
+                .find(|check| check.name == name)
+                .map(|check| check.status)
+        };
+        assert_eq!(status("semantic_helper"), Some(CheckStatus::Ok));
+        assert_eq!(status("go_mod"), Some(CheckStatus::Ok));
+        assert_eq!(status("grammar"), Some(CheckStatus::Ok));
+        assert_eq!(status("cache_dir"), Some(CheckStatus::Ok));
+        assert!(status("go_toolchain").is_some());
+        assert_eq!(
+            report.status,
+            report
+                .checks
+                .iter()
+                .map(|check| check.status)
+                .max()
+                .unwrap_or(CheckStatus::Ok)
+        );
+        let schema = crate::protocol::protocol_schema();
+        assert!(validate_schema(
+            &schema["definitions"]["DoctorReport"],
+            &serde_json::json!(report),
+            &schema,
+            "DoctorReport"
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_lock_order_cycles_across_functions() {
+        let code = r#"package main
+
+import "sync"
+
+type Bank struct {
+	a sync.Mutex
+	b sync.Mutex
+}
+
+func (s *Bank) transfer() {
+	s.a.Lock()
+	defer s.a.Unlock()
+	s.b.Lock()
+	s.b.Unlock()
+}
+
+func (r *Bank) refund() {
+	r.b.Lock()
+	r.debit()
+	r.b.Unlock()
+}
+
+func (r *Bank) debit() {
+	r.a.Lock()
+	r.a.Unlock()
+}
+
+var x, y sync.Mutex
+
+func ordered() {
+	x.Lock()
+	y.Lock()
+	y.Unlock()
+	x.Unlock()
+}
+
+func alsoOrdered() {
+	x.Lock()
+	defer x.Unlock()
+	y.Lock()
+	defer y.Unlock()
+}
+
+func spawn() {
+	var p, q sync.Mutex
+	go func() {
+		p.Lock()
+		q.Lock()
+		q.Unlock()
+		p.Unlock()
+	}()
+	go func() {
+		q.Lock()
+		p.Lock()
+		p.Unlock()
+		q.Unlock()
+	}()
+}
+
+func unrelated() {
+	var p, q sync.Mutex
+	q.Lock()
+	p.Lock()
+	p.Unlock()
+	q.Unlock()
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok(cycles) = crate::locks::find_lock_order_cycles(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        assert_eq!(cycles.len(), 2, "{:?}", cycles);
+
+        let bank = &cycles[0].edges;
+        assert_eq!(bank.len(), 2);
+        assert_eq!((bank[0].from.as_str(), bank[0].to.as_str()), ("s.a", "s.b"));
+        assert_eq!(bank[0].function, "transfer");
+        assert_eq!(bank[0].held.start.line, 10);
+        assert_eq!(bank[0].acquired.start.line, 12);
+        assert!(bank[0].callee.is_none());
+        assert_eq!((bank[1].from.as_str(), bank[1].to.as_str()), ("r.b", "r.a"));
+        assert_eq!(bank[1].function, "refund");
+        assert_eq!(bank[1].acquired.start.line, 18);
+        let (callee, lock) = bank[1].callee.clone().unwrap_or_default();
+        assert_eq!(callee, "debit");
+        assert_eq!(lock.start.line, 23);
+        assert!(cycles[0]
+            .message()
+            .contains("`transfer` locks `s.a` then `s.b`"));
+
+        let literals = &cycles[1].edges;
+        assert_eq!(
+            (literals[0].from.as_str(), literals[0].to.as_str()),
+            ("p", "q")
+        );
+        assert_eq!(
+            (literals[1].from.as_str(), literals[1].to.as_str()),
+            ("q", "p")
+        );
+        assert_eq!(literals[0].acquired.start.line, 47);
+        assert_eq!(literals[1].acquired.start.line, 53);
+    }
+
+    #[test]
+    fn test_channel_close_responsibility() {
+        let code = r#"package main
+
+import "sync"
+
+func produce(out chan<- int, n int) {
+	for i := 0; i < n; i++ {
+		out <- i
+	}
+}
+
+func leaky() {
+	ch := make(chan int)
+	go produce(ch, 3)
+	for v := range ch {
+		_ = v
+	}
+}
+
+func closed() {
+	ch := make(chan int)
+	go func() {
+		produce(ch, 3)
+		close(ch)
+	}()
+	for v := range ch {
+		_ = v
+	}
+}
+
+func racingClose() {
+	ch := make(chan int, 4)
+	var wg sync.WaitGroup
+	wg.Add(1)
+	go func() {
+		defer wg.Done()
+		ch <- 1
+	}()
+	go func() {
+		ch <- 2
+		close(ch)
+	}()
+	for v := range ch {
+		_ = v
+	}
+	wg.Wait()
+}
+
+func owner() {
+	ch := make(chan int)
+	var wg sync.WaitGroup
+	for i := 0; i < 2; i++ {
+		wg.Add(1)
+		go func() {
+			defer wg.Done()
+			ch <- 1
+		}()
+	}
+	go func() {
+		wg.Wait()
+		close(ch)
+	}()
+	for v := range ch {
+		_ = v
+	}
+}
+
+func escaped() chan int {
+	ch := make(chan int)
+	go func() { ch <- 1 }()
+	for v := range ch {
+		_ = v
+	}
+	return ch
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok((unclosed, producer_closes)) =
+            crate::channels::find_close_responsibility_issues(&tree, code)
+        else {
+            panic!("the tree was parsed from the code");
+        };
+        assert_eq!(unclosed.len(), 1, "{:?}", unclosed);
+        assert_eq!(unclosed[0].channel, "ch");
+        assert_eq!(unclosed[0].range.start.line, 13);
+        assert_eq!(unclosed[0].creation.start.line, 11);
+        let sends: Vec<u32> = unclosed[0].sends.iter().map(|r| r.start.line).collect();
+        assert_eq!(sends, vec![6]);
+
+        assert_eq!(producer_closes.len(), 1, "{:?}", producer_closes);
+        assert_eq!(producer_closes[0].close.start.line, 39);
+        let others: Vec<u32> = producer_closes[0]
+            .other_sends
+            .iter()
+            .map(|r| r.start.line)
+            .collect();
+        assert_eq!(others, vec![35]);
+    }
+
+    #[test]
+    fn test_race_severity_distinguishes_read_locks() {
+        let code = r#"
+func demo() {
+    go func() {
+        mu.RLock()
+        _ = shared
+        mu.RUnlock()
+    }()
+    go func() {
+        mu.RLock()
+        shared = 1
+        mu.RUnlock()
+    }()
+    go func() {
+        mu.Lock()
+        shared = 2
+        mu.Unlock()
+    }()
+}
+        "#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let sync_funcs: HashSet<String> = HashSet::new();
+        let at = |line: u32| Range::new(Position::new(line, 8), Position::new(line, 8));
+        assert_eq!(
+            crate::analysis::access_guard(&tree, at(4), code, &sync_funcs),
+            crate::analysis::AccessGuard::Shared
+        );
+        assert_eq!(
+            determine_race_severity(&tree, at(4), code, false, &sync_funcs),
+            RaceSeverity::Low
+        );
+        assert_eq!(
+            determine_race_severity(&tree, at(9), code, true, &sync_funcs),
+            RaceSeverity::High,
+            "a write under RLock still races with readers"
+        );
+        assert_eq!(
+            crate::analysis::access_guard(&tree, at(14), code, &sync_funcs),
+            crate::analysis::AccessGuard::Exclusive
+        );
+        assert_eq!(
+            determine_race_severity(&tree, at(14), code, true, &sync_funcs),
+            RaceSeverity::Low
+        );
+    }
+
+    #[test]
+    fn test_custom_rule_registry() {
+        use crate::rule_api::{Rule, RuleFinding, RuleInput, RuleRegistry};
+        use crate::rules::RuleMeta;
+        use crate::types::GraphEntityType;
+
+        struct GoroutineBudget(&'static str);
+        impl Rule for GoroutineBudget {
+            fn meta(&self) -> RuleMeta {
+                RuleMeta {
+                    code: self.0,
+                    title: "Too many goroutines in one file",
+                    memory_model: "Every goroutine is another party to synchronize with",
+                }
+            }
+            fn check(&self, input: &RuleInput) -> Vec<RuleFinding> {
+                let budget = input.settings["max"].as_u64().unwrap_or(u64::MAX) as usize;
+                let goroutines: Vec<Range> = input
+                    .symbols()
+                    .nodes
+                    .iter()
+                    .filter(|node| matches!(node.entity_type, GraphEntityType::Goroutine))
+                    .map(|node| node.range)
+                    .collect();
+                goroutines
+                    .iter()
+                    .skip(budget)
+                    .map(|range| RuleFinding {
+                        range: *range,
+                        severity: tower_lsp::lsp_types::DiagnosticSeverity::INFORMATION,
+                        message: format!("goroutine over the budget of {}", budget),
+                        related: Vec::new(),
+                    })
+                    .collect()
+            }
+        }
+
+        let code = r#"package main
+
+func main() {
+	go work()
+	go work()
+	go work()
+}
+
+func work() {}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let uri = match tower_lsp::lsp_types::Url::parse("file:///tmp/main.go") {
+            Ok(uri) => uri,
+            Err(_) => return,
+        };
+        let mut registry = RuleRegistry::default();
+        assert!(registry
+            .register(GoroutineBudget("org-goroutine-budget"))
+            .is_ok());
+        assert!(registry
+            .register(GoroutineBudget("org-goroutine-budget"))
+            .is_err());
+        assert!(registry
+            .register(GoroutineBudget("lock-double-lock"))
+            .is_err());
+        assert_eq!(registry.rules().len(), 1);
+        assert!(registry.settings("org-goroutine-budget").is_null());
+        registry.set_settings("org-goroutine-budget", serde_json::json!({"max": 2}));
+
+        let rule = &registry.rules()[0];
+        let input = RuleInput::new(&uri, &tree, code, registry.settings(rule.meta().code));
+        let findings = rule.check(&input);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].range.start.line, 5);
+    }
+
+    #[test]
+    fn test_once_do_publishes_initialization() {
+        use crate::analysis::is_published_by_once;
+        let code = r#"package main
+
+import "sync"
+
+var (
+	once   sync.Once
+	config map[string]string
+	other  map[string]string
+)
+
+func load() {
+	config = map[string]string{"a": "b"}
+}
+
+func main() {
+	go func() {
+		once.Do(load)
+		_ = config["a"]
+	}()
+	go func() {
+		_ = config["a"]
+		once.Do(load)
+	}()
+	go func() {
+		once.Do(func() { other = map[string]string{} })
+		_ = other["a"]
+	}()
+	other = nil
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let use_on = |var_info: &crate::types::VariableInfo, line: u32| {
+            var_info
+                .uses
+                .iter()
+                .copied()
+                .find(|range| range.start.line == line)
+        };
+        let Ok(Some(config)) = find_variable_at_position(&tree, code, Position::new(6, 1)) else {
+            panic!("config not found");
+        };
+        let (Some(after_do), Some(before_do)) = (use_on(&config, 17), use_on(&config, 20)) else {
+            panic!("config uses not found: {:?}", config.uses);
+        };
+        assert_eq!(
+            is_published_by_once(&tree, code, &config, after_do),
+            Ok(true)
+        );
+        assert_eq!(
+            is_published_by_once(&tree, code, &config, before_do),
+            Ok(false),
+            "Do must run before the read"
+        );
+
+        let Ok(Some(other)) = find_variable_at_position(&tree, code, Position::new(7, 1)) else {
+            panic!("other not found");
+        };
+        let Some(read) = use_on(&other, 25) else {
+            panic!("other uses not found: {:?}", other.uses);
+        };
+        assert_eq!(
+            is_published_by_once(&tree, code, &other, read),
+            Ok(false),
+            "a write outside Do breaks the guarantee"
+        );
+    }
+    #[test]
+    fn test_sync_map_calls_are_synchronized() {
+        let code = r#"package main
+
+import "sync"
+
+type Registry struct {
+    entries sync.Map
+}
+
+var cache sync.Map
+
+func demo(r *Registry, plain map[string]int) {
+    local := &sync.Map{}
+    go func() {
+        cache.Store("a", 1)
+        local.LoadOrStore("b", 2)
+        r.entries.Delete("c")
+    }()
+    go func() {
+        plain.Delete("d")
+    }()
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let sync_funcs: HashSet<String> = HashSet::new();
+        let at = |line: u32| Range::new(Position::new(line, 8), Position::new(line, 8));
+        for line in [13, 14, 15] {
+            assert_eq!(
+                crate::analysis::access_guard(&tree, at(line), code, &sync_funcs),
+                crate::analysis::AccessGuard::Exclusive,
+                "line {}",
+                line
+            );
+            assert_eq!(
+                determine_race_severity(&tree, at(line), code, true, &sync_funcs),
+                RaceSeverity::Low
+            );
+        }
+        assert_eq!(
+            determine_race_severity(&tree, at(18), code, true, &sync_funcs),
+            RaceSeverity::High,
+            "a method named like a sync.Map method on another type is not synchronized"
+        );
+        assert!(has_synchronization_in_block(&tree, at(13), code));
+
+        let graph = crate::analysis::build_graph_data(&tree, code);
+        let mut maps: Vec<&str> = graph
+            .nodes
+            .iter()
+            .filter(|node| node.entity_type == crate::types::GraphEntityType::SyncMap)
+            .map(|node| node.label.as_str())
+            .collect();
+        maps.sort();
+        assert_eq!(maps, ["cache", "entries", "local"]);
+    }
+    #[test]
+    fn test_mixed_atomic_and_plain_access() {
+        let code = r#"package main
+
+import "sync/atomic"
+
+var hits int64
+var total int64
+
+type Stats struct{ served int64 }
+
+func (s *Stats) serve() {
+    go func() {
+        atomic.AddInt64(&s.served, 1)
+    }()
+    s.served = 0
+}
+
+func demo() {
+    go func() {
+        atomic.AddInt64(&hits, 1)
+        hits++
+        atomic.AddInt64(&total, 1)
+    }()
+    println(atomic.LoadInt64(&total))
+    println(hits)
+}
+
+func reset() {
+    atomic.AddInt64(&hits, 1)
+    hits = 2
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok(findings) = crate::atomics::find_mixed_atomic_accesses(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let found: Vec<(&str, u32, u32, bool)> = findings
+            .iter()
+            .map(|f| {
+                (
+                    f.variable.as_str(),
+                    f.plain.start.line,
+                    f.atomic.start.line,
+                    f.plain_is_write,
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("s.served", 13, 11, true),
+                ("hits", 19, 18, true),
+                ("hits", 28, 18, true),
+            ],
+            "a read after the goroutines and a purely atomic variable are not reported"
+        );
+        assert!(findings[1]
+            .message()
+            .contains("written without sync/atomic"));
+
+        let at = |line: u32| Range::new(Position::new(line, 4), Position::new(line, 4));
+        assert!(
+            !has_synchronization_in_block(&tree, at(28), code),
+            "an atomic call on a variable the block also writes plainly does not synchronize it"
+        );
+    }
+
+    #[test]
+    fn test_lock_copies() {
+        use crate::locks::LockCopyKind;
+        let code = r#"package main
+
+import "sync"
+
+type Counter struct {
+    mu sync.Mutex
+    n  int
+}
+
+type Safe struct{ c *Counter }
+
+func (c Counter) Value() int { return c.n }
+
+func (c *Counter) Inc() { c.n++ }
+
+func report(c Counter) {}
+
+func worker(wg sync.WaitGroup) {}
+
+func demo(list []Counter) {
+    var wg sync.WaitGroup
+    a := Counter{}
+    b := a
+    report(a)
+    go worker(wg)
+    for _, item := range list {
+        _ = item
+    }
+    for i := range list {
+        list[i].Inc()
+    }
+    p := &a
+    _ = p
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok(copies) = crate::locks::find_lock_copies(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let found: Vec<(LockCopyKind, u32, &str)> = copies
+            .iter()
+            .map(|c| (c.kind, c.range.start.line, c.subject.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (LockCopyKind::ValueReceiver, 11, "Counter.Value"),
+                (LockCopyKind::Parameter, 15, "c"),
+                (LockCopyKind::Parameter, 17, "wg"),
+                (LockCopyKind::Assignment, 22, "a"),
+                (LockCopyKind::Argument, 23, "a"),
+                (LockCopyKind::Argument, 24, "wg"),
+                (LockCopyKind::RangeValue, 25, "item"),
+            ]
+        );
+        assert_eq!(copies[2].type_name, "sync.WaitGroup");
+        assert!(copies[6].message().contains("each `Counter` element"));
+    }
+
+    #[test]
+    fn test_concurrent_map_access() {
+        let code = r#"package main
+
+import "fmt"
+
+var cache = map[string]int{}
+
+func fill(keys []string) {
+    for _, k := range keys {
+        go func(k string) {
+            cache[k] = len(k)
+        }(k)
+    }
+}
+
+func safe() {
+    seen := make(map[int]bool)
+    done := make(chan struct{})
+    go func() {
+        seen[1] = true
+        close(done)
+    }()
+    <-done
+    fmt.Println(len(seen))
+}
+
+func counts() {
+    hits := map[string]int{}
+    go func() {
+        hits["a"]++
+    }()
+    fmt.Println(hits["a"])
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok(findings) = crate::maps::find_concurrent_map_accesses(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let found: Vec<(&str, u32, bool, Vec<u32>)> = findings
+            .iter()
+            .map(|f| {
+                (
+                    f.map.as_str(),
+                    f.access.range.start.line,
+                    f.access.write,
+                    f.others.iter().map(|o| o.range.start.line).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [("cache", 9, true, vec![]), ("hits", 28, true, vec![30])],
+            "a map only read after the goroutine is waited for is not reported"
+        );
+
+        let mut insertions = findings[1].fix.insertions.clone();
+        insertions.sort_by_key(|(at, _)| std::cmp::Reverse((at.line, at.character)));
+        let mut lines: Vec<String> = code.lines().map(str::to_string).collect();
+        for (at, text) in insertions {
+            lines[at.line as usize].insert_str(at.character as usize, &text);
+        }
+        let fixed = lines.join("\n");
+        assert!(fixed.contains("import \"fmt\"\n\nimport \"sync\""));
+        assert!(fixed.contains(
+            "    hits := map[string]int{}\n    var hitsMu sync.Mutex\n    go func() {\n        hitsMu.Lock()\n        hits[\"a\"]++\n        hitsMu.Unlock()\n    }()\n    hitsMu.Lock()\n    fmt.Println(hits[\"a\"])\n    hitsMu.Unlock()\n}"
+        ), "{}", fixed);
+    }
+
+    #[test]
+    fn test_goroutine_leaks() {
+        use crate::channels::GoroutineLeakKind;
+        let code = r#"package main
+
+func leaks() {
+    results := make(chan int)
+    quit := make(chan bool)
+    go func() {
+        v := <-results
+        _ = v
+    }()
+    go func() {
+        quit <- true
+    }()
+    done := make(chan struct{})
+    go func() {
+        select {
+        case <-done:
+        case quit <- false:
+        }
+    }()
+    go func() {
+        select {}
+    }()
+}
+
+func fine() {
+    jobs := make(chan int)
+    go worker(jobs)
+    jobs <- 1
+    close(jobs)
+    stop := make(chan bool)
+    go func() {
+        select {
+        case <-stop:
+        default:
+        }
+    }()
+}
+
+func worker(jobs chan int) {
+    for j := range jobs {
+        _ = j
+    }
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok(leaks) = crate::channels::find_goroutine_leaks(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let found: Vec<(GoroutineLeakKind, u32, &str)> = leaks
+            .iter()
+            .map(|leak| (leak.kind, leak.range.start.line, leak.channel.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (GoroutineLeakKind::ReceiveWithoutSender, 6, "results"),
+                (GoroutineLeakKind::SendWithoutReceiver, 10, "quit"),
+                (GoroutineLeakKind::SelectBlocked, 14, ""),
+                (GoroutineLeakKind::SelectBlocked, 20, ""),
+            ]
+        );
+        assert_eq!(leaks[2].creations.len(), 2);
+        assert!(leaks[0].message().starts_with("Possible goroutine leak"));
+    }
+
+    #[test]
+    fn test_errgroup_go_and_wait() {
+        use crate::analysis::{
+            build_graph_data, is_in_goroutine_or_group, is_joined_by_group_wait,
+        };
+        use crate::types::GraphEntityType;
+        let code = r#"package main
+
+import "golang.org/x/sync/errgroup"
+
+var (
+	total  int
+	shared int
+)
+
+func fetch() error {
+	var g errgroup.Group
+	g.Go(func() error {
+		total = 42
+		return nil
+	})
+	if err := g.Wait(); err != nil {
+		return err
+	}
+	println(total)
+	return nil
+}
+
+func racy(ctx context.Context) {
+	g, ctx := errgroup.WithContext(ctx)
+	g.Go(func() error {
+		shared++
+		return nil
+	})
+	println(shared)
+	g.Wait()
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let use_on = |var_info: &crate::types::VariableInfo, line: u32| {
+            var_info
+                .uses
+                .iter()
+                .copied()
+                .find(|range| range.start.line == line)
+        };
+        let Ok(Some(total)) = find_variable_at_position(&tree, code, Position::new(5, 1)) else {
+            panic!("total not found");
+        };
+        let Some(write) = use_on(&total, 12) else {
+            panic!("total uses not found: {:?}", total.uses);
+        };
+        assert!(!is_in_goroutine(&tree, write));
+        assert!(is_in_goroutine_or_group(&tree, write, code));
+        assert_eq!(
+            is_joined_by_group_wait(&tree, code, &total, write),
+            Ok(true),
+            "the read after Wait is ordered after the write"
+        );
+
+        let Ok(Some(shared)) = find_variable_at_position(&tree, code, Position::new(6, 1)) else {
+            panic!("shared not found");
+        };
+        let Some(increment) = use_on(&shared, 25) else {
+            panic!("shared uses not found: {:?}", shared.uses);
+        };
+        assert_eq!(
+            is_joined_by_group_wait(&tree, code, &shared, increment),
+            Ok(false),
+            "the read before Wait races with the increment"
+        );
+        assert_eq!(
+            determine_race_severity(&tree, increment, code, true, &HashSet::new()),
+            RaceSeverity::High
+        );
+
+        let graph = build_graph_data(&tree, code);
+        let groups = graph
+            .nodes
+            .iter()
+            .filter(|node| {
+                node.entity_type == GraphEntityType::Goroutine
+                    && node
+                        .extra
+                        .as_ref()
+                        .is_some_and(|extra| extra["errgroup"] == "g")
+            })
+            .count();
+        assert_eq!(groups, 2);
+    }
+
+    #[test]
+    fn test_time_after_in_loop() {
+        use crate::timers::find_time_after_in_loops;
+        let code = "package main
+
+import \"time\"
+
+func poll(events chan int) {
+\tfor {
+\t\tselect {
+\t\tcase e := <-events:
+\t\t\t_ = e
+\t\tcase <-time.After(5 * time.Second):
+\t\t\treturn
+\t\t}
+\t}
+}
+
+func perItem(items []time.Duration, done chan bool) {
+\tfor _, d := range items {
+\t\tselect {
+\t\tcase <-done:
+\t\tcase <-time.After(d):
+\t\t}
+\t}
+}
+
+func once(done chan bool) {
+\tselect {
+\tcase <-done:
+\tcase <-time.After(time.Second):
+\t}
+}
+";
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok(findings) = find_time_after_in_loops(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        assert_eq!(findings.len(), 2, "{:?}", findings);
+        assert_eq!(findings[0].range.start.line, 9);
+        assert_eq!(findings[0].loop_range.start.line, 5);
+        assert!(
+            findings[1].timer_fix.is_none() && findings[1].ticker_fix.is_none(),
+            "the duration depends on the range variable"
+        );
+
+        let Some(fix) = &findings[0].timer_fix else {
+            panic!("no timer fix");
+        };
+        let mut edits = fix.edits.clone();
+        edits
+            .sort_by_key(|(range, _)| std::cmp::Reverse((range.start.line, range.start.character)));
+        let mut lines: Vec<String> = code.lines().map(str::to_string).collect();
+        for (range, text) in edits {
+            assert_eq!(range.start.line, range.end.line);
+            lines[range.start.line as usize].replace_range(
+                range.start.character as usize..range.end.character as usize,
+                &text,
+            );
+        }
+        let fixed = lines.join("\n");
+        assert!(
+            fixed.contains(
+                "\ttimer := time.NewTimer(5 * time.Second)\n\tdefer timer.Stop()\n\tfor {\n\t\ttimer.Reset(5 * time.Second)\n\t\tselect {"
+            ),
+            "{}",
+            fixed
+        );
+        assert!(fixed.contains("case <-timer.C:"), "{}", fixed);
+        let Some(ticker) = &findings[0].ticker_fix else {
+            panic!("no ticker fix");
+        };
+        assert_eq!(ticker.name, "ticker");
+    }
+
+    #[test]
+    fn test_select_modeling() {
+        use crate::analysis::build_graph_data;
+        use crate::channels::find_busy_wait_selects;
+        use crate::types::GraphEdgeType;
+        let code = r#"package main
+
+var counter int
+
+func worker(jobs chan int, results chan int, quit chan bool) {
+    go func() {
+        for {
+            select {
+            case j := <-jobs:
+                counter = j
+            case results <- counter:
+            case <-quit:
+                return
+            }
+        }
+    }()
+}
+
+func spin(ready *bool) {
+    for {
+        select {
+        default:
+            if *ready {
+                return
+            }
+        }
+    }
+}
+
+func poll() {
+    select {
+    default:
+    }
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let write = Range::new(Position::new(9, 16), Position::new(9, 23));
+        assert_eq!(
+            determine_race_severity(&tree, write, code, true, &HashSet::new()),
+            RaceSeverity::Low
+        );
+
+        let graph = build_graph_data(&tree, code);
+        let has_edge = |edge_type: GraphEdgeType, channel: &str| {
+            graph.edges.iter().any(|edge| {
+                edge.edge_type == edge_type
+                    && crate::analysis::graph_id_kind(&edge.to) == Some("chan")
+                    && crate::analysis::graph_id_name(&edge.to) == channel
+            })
+        };
+        assert!(has_edge(GraphEdgeType::Receive, "jobs"));
+        assert!(has_edge(GraphEdgeType::Receive, "quit"));
+        assert!(has_edge(GraphEdgeType::Send, "results"));
+
+        let busy = find_busy_wait_selects(&tree);
+        assert_eq!(busy.len(), 2, "{:?}", busy);
+        assert_eq!(busy[0].range.start.line, 20);
+        assert_eq!(busy[0].in_loop.map(|range| range.start.line), Some(19));
+        assert!(busy[1].in_loop.is_none());
+    }
+
+    #[test]
+    fn test_channel_happens_before() {
+        use crate::channels::is_ordered_by_channel;
+        let code = r#"package main
+
+var result int
+
+func ordered() {
+    done := make(chan bool)
+    go func() {
+        result = 42
+        done <- true
+    }()
+    <-done
+    println(result)
+}
+
+func racy() {
+    ch := make(chan bool)
+    go func() {
+        ch <- true
+        result = 1
+    }()
+    <-ch
+    println(result)
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let at = |line: u32, character: u32| {
+            Range::new(
+                Position::new(line, character),
+                Position::new(line, character + 6),
+            )
+        };
+        assert!(is_ordered_by_channel(&tree, at(7, 8), code));
+        assert!(is_ordered_by_channel(&tree, at(11, 12), code));
+        assert_eq!(
+            determine_race_severity(&tree, at(7, 8), code, true, &HashSet::new()),
+            RaceSeverity::Low
+        );
+        assert!(
+            !is_ordered_by_channel(&tree, at(19, 8), code),
+            "the write follows the send"
+        );
+        assert_eq!(
+            determine_race_severity(&tree, at(19, 8), code, true, &HashSet::new()),
+            RaceSeverity::High
+        );
+    }
+
+    #[test]
+    fn test_waitgroup_passed_by_value() {
+        use crate::locks::LockCopyKind;
+        let code = r#"package main
+
+import "sync"
+
+func run(jobs []int) {
+    var wg sync.WaitGroup
+    for range jobs {
+        wg.Add(1)
+        go func(wg sync.WaitGroup) {
+            defer wg.Done()
+        }(wg)
+    }
+    wg.Wait()
+}
+
+func fine(wg *sync.WaitGroup) {
+    go func(group *sync.WaitGroup) {
+        group.Done()
+    }(wg)
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok(copies) = crate::locks::find_lock_copies(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let found: Vec<(LockCopyKind, u32)> = copies
+            .iter()
+            .map(|c| (c.kind, c.range.start.line))
+            .collect();
+        assert_eq!(
+            found,
+            [(LockCopyKind::Parameter, 8), (LockCopyKind::Argument, 10)]
+        );
+        assert!(copies[0].message().contains("never releases `Wait()`"));
+    }
+
+    #[test]
+    fn test_pointer_alias_accesses() {
+        use crate::aliases::alias_accesses;
+        let code = r#"package main
+
+func demo() {
+    count := 0
+    p := &count
+    go func() {
+        *p++
+    }()
+    go func(q *int) {
+        println(*q)
+    }(&count)
+    println(count)
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok(Some(count)) = find_variable_at_position(&tree, code, Position::new(3, 4)) else {
+            panic!("count not found");
+        };
+        let aliases = alias_accesses(&tree, code, &count);
+        let found: Vec<(&str, u32, bool)> = aliases
+            .iter()
+            .map(|alias| (alias.pointer.as_str(), alias.range.start.line, alias.write))
+            .collect();
+        assert_eq!(found, [("p", 6, true), ("q", 9, false)]);
+        assert_eq!(
+            determine_race_severity(&tree, aliases[0].range, code, true, &HashSet::new()),
+            RaceSeverity::High
+        );
+    }
+
+    #[test]
+    fn test_global_races() {
+        use crate::globals::find_global_races;
+        let code = r#"package main
+
+import "sync"
+
+var (
+	hits   int
+	config string
+	safe   int
+	mu     sync.Mutex
+)
+
+func record() {
+	hits++
+}
+
+func serve() {
+	for i := 0; i < 3; i++ {
+		go record()
+	}
+	println(hits)
+}
+
+func main() {
+	var wg sync.WaitGroup
+	wg.Add(1)
+	go func() {
+		defer wg.Done()
+		config = "y"
+		mu.Lock()
+		safe++
+		mu.Unlock()
+	}()
+	wg.Wait()
+	println(config)
+	mu.Lock()
+	println(safe)
+	mu.Unlock()
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok(races) = find_global_races(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        assert_eq!(races.len(), 1, "{:?}", races);
+        let race = &races[0];
+        assert_eq!(race.variable, "hits");
+        assert_eq!(race.declaration.start.line, 5);
+        let writes: Vec<(u32, &str)> = race
+            .writes
+            .iter()
+            .map(|(range, who)| (range.start.line, who.as_str()))
+            .collect();
+        assert_eq!(writes, [(12, "`record`")]);
+        let conflicts: Vec<(u32, &str)> = race
+            .conflicts
+            .iter()
+            .map(|(range, who)| (range.start.line, who.as_str()))
+            .collect();
+        assert_eq!(conflicts, [(19, "`serve`")]);
+        assert!(race.message().contains("written in `record`"));
+    }
+
+    #[test]
+    fn test_global_written_through_a_call_is_reported_once() {
+        use crate::backend::FileAnalyzer;
+        use crate::callgraph::find_interprocedural_races;
+        use crate::rule_api::RuleRegistry;
+        use crate::rules::RuleDocsConfig;
+        use tower_lsp::lsp_types::NumberOrString;
+
+        let code = r#"package main
+
+var hits int
+
+func record() {
+	hits++
+}
+
+func main() {
+	for i := 0; i < 3; i++ {
+		go record()
+	}
+	println(hits)
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok(uri) = tower_lsp::lsp_types::Url::parse("file:///tmp/hits.go") else {
+            panic!("a valid file URL");
+        };
+        // Both passes see the write; the diagnostics report it once.
+        let Ok(races) = find_interprocedural_races(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        assert!(races.iter().any(|race| race.variable == "hits"));
+        let analyzer = FileAnalyzer {
+            rule_docs: RuleDocsConfig::from_env(),
+            custom_rules: RuleRegistry::default(),
+        };
+        let Ok(findings) = analyzer.file_diagnostics(&uri, &tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let races: Vec<_> = findings
+            .diagnostics
+            .iter()
+            .filter_map(|diagnostic| match &diagnostic.code {
+                Some(NumberOrString::String(code)) if code.ends_with("race") => Some(code.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(races, ["global-race"]);
+    }
+
+    #[test]
+    fn test_shared_receiver_fields() {
+        let code = r#"package main
+
+type Counter struct {
+	n int
+}
+
+func (c *Counter) Increment() { c.n++ }
+
+func (c *Counter) Add(d int) { c.n += d }
+
+func (c *Counter) Reset() { c.n = 0 }
+
+func (c *Counter) Value() int { return c.n }
+
+func shared() {
+	c := &Counter{}
+	go c.Increment()
+	println(c.Value())
+}
+
+func reads() {
+	c := &Counter{}
+	go c.Add(2)
+	println(c.n)
+}
+
+func alone() {
+	c := &Counter{}
+	go c.Reset()
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok(races) = crate::callgraph::find_interprocedural_races(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let summary: Vec<(&str, u32, Option<&str>)> = races
+            .iter()
+            .map(|race| {
+                (
+                    race.function.as_str(),
+                    race.call_site.start.line,
+                    race.receiver.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![("Increment", 16, Some("c")), ("Add", 22, Some("c"))],
+            "a receiver used by a single goroutine is not shared"
+        );
+    }
+
+    #[test]
+    fn test_generic_code() {
+        let code = r#"
+type Stack[T any] struct {
+    items []T
+}
+
+func (s *Stack[T]) Push(v T) {
+    s.items = append(s.items, v)
+}
+
+func Map[T, U any](xs []T, f func(T) U) []U {
+    out := make([]U, 0, len(xs))
+    for _, x := range xs {
+        out = append(out, f(x))
+    }
+    return out
+}
+
+func main() {
+    s := Stack[int]{}
+    s.Push(1)
+    ys := Map[int, string](nil, nil)
+    _ = ys
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        // `T` and `U` are type parameters, not variables.
+        assert!(matches!(
+            find_variable_at_position(&tree, code, Position::new(1, 11)),
+            Ok(None)
+        ));
+        assert!(matches!(
+            find_variable_at_position(&tree, code, Position::new(9, 12)),
+            Ok(None)
+        ));
+        assert!(matches!(
+            find_variable_at_position(&tree, code, Position::new(9, 9)),
+            Ok(None)
+        ));
+        let context = find_node_at_cursor_with_context(&tree, Position::new(9, 12));
+        assert_eq!(
+            context.map(|context| context.context_type),
+            Some(CursorContextType::TypeReference)
+        );
+
+        let xs = find_variable_at_position(&tree, code, Position::new(9, 19));
+        assert_eq!(
+            xs.map(|xs| xs.map(|xs| xs.name)),
+            Ok(Some("xs".to_string()))
+        );
+        let Ok(Some(s)) = find_variable_at_position(&tree, code, Position::new(18, 4)) else {
+            panic!("s not found");
+        };
+        assert_eq!(s.name, "s");
+        assert_eq!(s.uses.len(), 1);
+
+        let counts = count_entities(&tree, code);
+        assert_eq!(counts.variables, 3);
+        assert_eq!(counts.functions, 3);
+
+        let graph = crate::analysis::build_graph_data(&tree, code);
+        let map = graph.nodes.iter().find(|node| node.label == "Map");
+        assert_eq!(
+            map.and_then(|map| map.extra.clone()),
+            Some(serde_json::json!({"type_parameters": ["T", "U"]}))
+        );
+        assert!(graph.nodes.iter().any(|node| node.label == "Stack.Push"));
+        assert!(!graph.nodes.iter().any(|node| node.label == "T"));
+    }
+
+    #[test]
+    fn test_go_version_from_go_mod_and_build_constraints() {
+        use crate::goversion::{
+            build_constraint_version, file_go_version, module_go_version, parse_go_mod, GoVersion,
+        };
+
+        let go_mod =
+            "module example.com/m\n\n// go 1.10\ngo 1.22.3 // patch\n\ntoolchain go1.23.0\n";
+        assert_eq!(parse_go_mod(go_mod), Some(GoVersion::new(1, 22)));
+        assert_eq!(parse_go_mod("module example.com/m\n"), None);
+        assert_eq!(GoVersion::parse("1.21rc1"), Some(GoVersion::new(1, 21)));
+
+        let constrained = "//go:build go1.22 && linux\n\npackage main\n";
+        assert_eq!(
+            build_constraint_version(constrained),
+            Some(GoVersion::new(1, 22))
+        );
+        assert_eq!(
+            build_constraint_version("//go:build go1.21 || go1.22\n\npackage main\n"),
+            Some(GoVersion::new(1, 21))
+        );
+        assert_eq!(
+            build_constraint_version("//go:build !go1.22\n\npackage main\n"),
+            None
+        );
+        assert_eq!(
+            build_constraint_version("package main\n\n//go:build go1.22\n"),
+            None
+        );
+
+        // The constraint only sets the file's version in modules on Go 1.21 or later.
+        let old = Some(GoVersion::new(1, 20));
+        let new = Some(GoVersion::new(1, 21));
+        assert_eq!(file_go_version(old, constrained), old);
+        assert_eq!(
+            file_go_version(new, constrained),
+            Some(GoVersion::new(1, 22))
+        );
+        assert_eq!(file_go_version(None, "package main\n"), None);
+        assert!(!GoVersion::new(1, 21).has_per_iteration_loop_variables());
+        assert!(GoVersion::new(1, 22).has_per_iteration_loop_variables());
+
+        let dir =
+            std::env::temp_dir().join(format!("go-analyzer-goversion-{}", std::process::id()));
+        let package = dir.join("pkg");
+        if std::fs::create_dir_all(&package).is_err()
+            || std::fs::write(dir.join("go.mod"), "module example.com/m\n\ngo 1.22\n").is_err()
+        {
+            return;
+        }
+        assert_eq!(
+            module_go_version(&package.join("main.go")),
+            Some(GoVersion::new(1, 22))
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_channel_ownership_transfer() {
+        let code = r#"
+type Item struct{ v int }
+
+func produce(out chan *Item) {
+    item := &Item{}
+    go func() {
+        item.v = 1
+        out <- item
+    }()
+}
+
+func reuse(out chan *Item) {
+    item := &Item{}
+    go func() {
+        for {
+            item.v++
+            out <- item
+        }
+    }()
+}
+
+func fresh(out chan *Item) {
+    go func() {
+        for i := 0; i < 3; i++ {
+            item := &Item{}
+            item.v = i
+            out <- item
+        }
+    }()
+}
+
+func touchAfter(out chan *Item) {
+    item := &Item{}
+    go func() {
+        item.v = 1
+        out <- item
+        item.v = 2
+    }()
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let sync_funcs = HashSet::new();
+        let severity = |line: u32, character: u32| {
+            let range = Range::new(
+                Position::new(line, character),
+                Position::new(line, character + 4),
+            );
+            determine_race_severity(&tree, range, code, true, &sync_funcs)
+        };
+        // Written, then sent, then never touched again by the sender.
+        assert_eq!(severity(6, 8), RaceSeverity::Low);
+        assert_eq!(severity(25, 12), RaceSeverity::Low);
+        // The next iteration writes what the previous one sent.
+        assert_eq!(severity(15, 12), RaceSeverity::High);
+        // The sender keeps writing after the handoff.
+        assert_eq!(severity(34, 8), RaceSeverity::High);
+        assert_eq!(severity(36, 8), RaceSeverity::High);
+    }
+
+    #[test]
+    fn test_x_sync_primitives() {
+        let code = r#"
+var group singleflight.Group
+var sem = semaphore.NewWeighted(1)
+var wide = semaphore.NewWeighted(4)
+var hits, total int
+
+func work(ctx context.Context) {
+    go func() {
+        group.Do("k", func() (interface{}, error) {
+            hits++
+            return nil, nil
+        })
+    }()
+    go func() {
+        sem.Acquire(ctx, 1)
+        total++
+        sem.Release(1)
+    }()
+    go func() {
+        wide.Acquire(ctx, 1)
+        total++
+        wide.Release(1)
+    }()
+}
+
+func limited() {
+    var g errgroup.Group
+    g.SetLimit(1)
+    count := 0
+    for i := 0; i < 3; i++ {
+        g.Go(func() error {
+            count = count + i
+            return nil
+        })
+    }
+    g.Wait()
+    fmt.Println(count)
+}
+
+func unlimited() {
+    var g errgroup.Group
+    count := 0
+    for i := 0; i < 3; i++ {
+        g.Go(func() error {
+            count = count + i
+            return nil
+        })
+    }
+    g.Wait()
+    fmt.Println(count)
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let sync_funcs = crate::analysis::collect_sync_functions(&tree, code);
+        let severity = |line: u32, character: u32, len: u32| {
+            let range = Range::new(
+                Position::new(line, character),
+                Position::new(line, character + len),
+            );
+            determine_race_severity(&tree, range, code, true, &sync_funcs)
+        };
+        // Inside `singleflight.Group.Do` and between `Acquire`/`Release` of a weight-1 semaphore.
+        assert_eq!(severity(9, 12, 4), RaceSeverity::Low);
+        assert_eq!(severity(15, 8, 5), RaceSeverity::Low);
+        // A weight-4 semaphore lets four goroutines in at once.
+        assert_eq!(severity(20, 8, 5), RaceSeverity::High);
+
+        // `g.SetLimit(1)` runs the group's functions one at a time.
+        let joined = |line: u32| {
+            let Ok(Some(count)) = find_variable_at_position(&tree, code, Position::new(line, 4))
+            else {
+                panic!("count not found");
+            };
+            let Some(write) = count
+                .uses
+                .iter()
+                .copied()
+                .find(|range| range.start.line == line + 3)
+            else {
+                panic!("count write not found: {:?}", count.uses);
+            };
+            crate::analysis::is_joined_by_group_wait(&tree, code, &count, write) == Ok(true)
+        };
+        assert!(joined(28));
+        assert!(!joined(41));
+    }
+
+    #[test]
+    fn test_sync_function_config() {
+        use crate::sync_config::{parse_list, SyncConfig};
+
+        assert_eq!(
+            parse_list("LockShared, UnlockShared\nWithLock,,"),
+            vec!["LockShared", "UnlockShared", "WithLock"]
+        );
+
+        let path = std::env::temp_dir().join(format!(
+            "go-analyzer-sync-config-{}.json",
+            std::process::id()
+        ));
+        if std::fs::write(
+            &path,
+            r#"{"syncFunctions": ["LockShared", "UnlockShared", "locks.With", 3, " "]}"#,
+        )
+        .is_err()
+        {
+            return;
+        }
+        let config = SyncConfig::from_file(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(
+            config.functions,
+            vec!["LockShared", "UnlockShared", "locks.With"]
+        );
+        assert!(config.contains("s.mu.LockShared", "LockShared"));
+        assert!(config.contains("locks.With", "With"));
+        assert!(!config.contains("other.With", "With"));
+        assert_eq!(
+            config.lock_event("s.mu.LockShared", "LockShared"),
+            Some((1, true))
+        );
+        assert_eq!(
+            config.lock_event("s.mu.UnlockShared", "UnlockShared"),
+            Some((-1, true))
+        );
+        assert_eq!(config.lock_event("s.mu.Lock", "Lock"), None);
+        assert_eq!(
+            SyncConfig::from_file(std::path::Path::new("/nonexistent/.go-analyzer.json")),
+            SyncConfig::default()
+        );
+    }
+
+    #[test]
+    fn test_unbounded_spawns() {
+        let code = r#"
+func process(items []string, jobs chan string) {
+    for _, item := range items {
+        go func() {
+            handle(item)
+        }()
+    }
+    for job := range jobs {
+        go handle(job)
+    }
+    for i := 0; i < 4; i++ {
+        go worker(jobs)
+    }
+    sem := make(chan struct{}, 4)
+    for _, item := range items {
+        sem <- struct{}{}
+        go func(item string) {
+            defer func() { <-sem }()
+            handle(item)
+        }(item)
+    }
+    for _, batch := range items {
+        var wg sync.WaitGroup
+        wg.Add(1)
+        go func() {
+            defer wg.Done()
+            handle(batch)
+        }()
+        wg.Wait()
+    }
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok(findings) = crate::spawns::find_unbounded_spawns(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let lines: Vec<u32> = findings
+            .iter()
+            .map(|finding| finding.range.start.line)
+            .collect();
+        // A fixed worker pool, a semaphore and a per-iteration Wait bound the others.
+        assert_eq!(lines, vec![3, 8]);
+        assert_eq!(findings[0].loop_range.start.line, 2);
+        assert!(findings[1].fix.is_none());
+        let Some(fix) = &findings[0].fix else {
+            panic!("no fix for the function literal goroutine");
+        };
+        // `sem` is taken by the function already.
+        assert_eq!(fix.name, "sem2");
+        let texts: Vec<&str> = fix.edits.iter().map(|(_, text)| text.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec![
+                "sem2 := make(chan struct{}, 8)\n    ",
+                "sem2 <- struct{}{}\n        ",
+                "\n            defer func() { <-sem2 }()",
+            ]
+        );
+        assert_eq!(fix.edits[2].0.start, Position::new(3, 19));
+    }
+
+    #[test]
+    fn test_unjoined_goroutines() {
+        use crate::joins::Escape::{Parameter, Returned};
+        let code = r#"
+func fill(c *Config) []string {
+    var out []string
+    go func() {
+        out = append(out, "x")
+        c.Name = "x"
+    }()
+    return out
+}
+func count() (n int) {
+    go func() {
+        n++
+    }()
+    return
+}
+func joined() int {
+    total := 0
+    done := make(chan struct{})
+    go func() {
+        total = 1
+        close(done)
+    }()
+    <-done
+    return total
+}
+func local() {
+    x := 0
+    go func() {
+        x = 1
+    }()
+}
+func (s *Server) start() {
+    go func() { s.ready = true }()
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok(findings) = crate::joins::find_unjoined_goroutines(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let summary: Vec<(u32, &str, crate::joins::Escape)> = findings
+            .iter()
+            .map(|finding| {
+                (
+                    finding.range.start.line,
+                    finding.variable.as_str(),
+                    finding.via,
+                )
+            })
+            .collect();
+        // `joined` waits on `done`; `local` keeps `x` to itself.
+        assert_eq!(
+            summary,
+            vec![
+                (3, "out", Returned),
+                (3, "c", Parameter),
+                (10, "n", Returned),
+                (32, "s", Parameter),
+            ]
+        );
+        assert_eq!(findings[0].write.start.line, 4);
+        assert_eq!(findings[0].escape.start, Position::new(7, 11));
+        assert!(findings[0]
+            .message()
+            .contains("`fill` returns without waiting"));
+    }
+
+    #[test]
+    fn test_goroutine_panics() {
+        use crate::panics::PanicCause::{Explicit, Index, NilDereference, TypeAssertion};
+        let code = r#"
+package main
+
+import "fmt"
+
+func run(jobs []Job, cfg *Config, m map[string]int, v interface{}) {
+    go func() {
+        s := v.(string)
+        fmt.Println(s, jobs[len(jobs)-1], cfg.Name, m[s])
+        panic("boom")
+    }()
+    go func() {
+        defer func() {
+            if r := recover(); r != nil {
+                fmt.Println(r)
+            }
+        }()
+        panic("boom")
+    }()
+    go func() {
+        if s, ok := v.(string); ok {
+            fmt.Println(s)
+        }
+        for i := range jobs {
+            fmt.Println(jobs[i])
+        }
+    }()
+    go func() {
+        defer recover()
+        panic("boom")
+    }()
+    go worker(jobs)
+}
+func worker(jobs []Job) {
+    panic("no jobs")
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok(findings) = crate::panics::find_goroutine_panics(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let lines: Vec<u32> = findings
+            .iter()
+            .map(|finding| finding.range.start.line)
+            .collect();
+        // A deferred literal calling recover() protects the second; `defer recover()` does not.
+        assert_eq!(lines, vec![6, 27, 31]);
+        let causes: Vec<_> = findings[0].causes.iter().map(|(_, cause)| *cause).collect();
+        assert_eq!(causes, vec![TypeAssertion, Index, NilDereference, Explicit]);
+        assert_eq!(findings[0].causes[1].0.start, Position::new(8, 23));
+        let texts: Vec<&str> = findings[0]
+            .fix
+            .iter()
+            .map(|(_, text)| text.as_str())
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                "\n\nimport \"log\"",
+                "\n        defer func() {\n            if r := recover(); r != nil {\n                log.Printf(\"goroutine panic: %v\", r)\n            }\n        }()",
+            ]
+        );
+        assert_eq!(findings[0].fix[0].0.start, Position::new(3, 12));
+        assert_eq!(findings[0].fix[1].0.start, Position::new(6, 15));
+        // The named function's body gets the recover.
+        assert_eq!(findings[2].fix[1].0.start, Position::new(33, 25));
+    }
+
+    #[test]
+    fn test_sleep_synchronization() {
+        let code = r#"
+func fetch() string {
+    var result string
+    go func() {
+        result = load()
+    }()
+    time.Sleep(time.Second)
+    return result
+}
+func poll() {
+    done := false
+    go func() {
+        done = true
+    }()
+    for !done {
+        time.Sleep(10 * time.Millisecond)
+    }
+}
+func waits() int {
+    var n int
+    var wg sync.WaitGroup
+    wg.Add(1)
+    go func() {
+        defer wg.Done()
+        n = 1
+    }()
+    time.Sleep(time.Second)
+    wg.Wait()
+    return n
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok(findings) = crate::sleeps::find_sleep_synchronization(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let summary: Vec<(u32, &str, u32)> = findings
+            .iter()
+            .map(|finding| {
+                (
+                    finding.range.start.line,
+                    finding.variable.as_str(),
+                    finding.read.start.line,
+                )
+            })
+            .collect();
+        // `waits` joins with the WaitGroup; the sleep there is harmless.
+        assert_eq!(summary, vec![(6, "result", 7), (15, "done", 14)]);
+        assert_eq!(findings[0].write.start, Position::new(4, 8));
+    }
+
+    #[test]
+    fn test_typed_atomics_and_value_store_types() {
+        let code = r#"
+type Server struct {
+    cfg atomic.Pointer[Config]
+}
+var current atomic.Value
+func (s *Server) reload(c Config) {
+    s.cfg.Store(&c)
+    current.Store(c)
+    current.Store(&Config{})
+    current.Store("v1")
+}
+func update() {
+    var name atomic.Value
+    name.Store("a")
+    name.Store("b")
+    plain.Store(1)
+    plain.Store("x")
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        // `s.cfg` is declared as an `atomic.Pointer[Config]`; `plain` is not an atomic.
+        let range = Range::new(Position::new(6, 17), Position::new(6, 17));
+        assert_eq!(is_access_in_atomic_context(&tree, range, code), Ok(true));
+        let range = Range::new(Position::new(15, 16), Position::new(15, 16));
+        assert_eq!(is_access_in_atomic_context(&tree, range, code), Ok(false));
+
+        let Ok(findings) = crate::atomics::find_inconsistent_atomic_stores(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let summary: Vec<(&str, &str, &str, Position)> = findings
+            .iter()
+            .map(|finding| {
+                (
+                    finding.variable.as_str(),
+                    finding.stored_type.as_str(),
+                    finding.first_type.as_str(),
+                    finding.range.start,
+                )
+            })
+            .collect();
+        // `c` has no evident type and the strings stored in `name` agree.
+        assert_eq!(
+            summary,
+            vec![("current", "string", "*Config", Position::new(9, 18))]
+        );
+        assert_eq!(findings[0].first.start.line, 8);
+    }
+
+    #[test]
+    fn test_cond_misuse() {
+        use crate::locks::CondMisuseKind::{WaitOutsideLoop, WaitWithoutLock};
+        let code = r#"
+type Queue struct {
+    mu    sync.Mutex
+    cond  *sync.Cond
+    items []int
+}
+func NewQueue() *Queue {
+    q := &Queue{}
+    q.cond = sync.NewCond(&q.mu)
+    return q
+}
+func (q *Queue) Pop() int {
+    q.mu.Lock()
+    defer q.mu.Unlock()
+    for len(q.items) == 0 {
+        q.cond.Wait()
+    }
+    return q.items[0]
+}
+func (q *Queue) PopOnce() int {
+    q.mu.Lock()
+    if len(q.items) == 0 {
+        q.cond.Wait()
+    }
+    q.mu.Unlock()
+    return 0
+}
+func (q *Queue) Peek() {
+    for len(q.items) == 0 {
+        q.cond.Wait()
+    }
+}
+func (q *Queue) Push(v int) {
+    q.mu.Lock()
+    q.items = append(q.items, v)
+    q.mu.Unlock()
+    q.cond.Signal()
+}
+func waitLocked(c *sync.Cond) {
+    c.Wait()
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let Ok(findings) = crate::locks::find_cond_misuses(&tree, code) else {
+            panic!("the tree was parsed from the code");
+        };
+        let summary: Vec<(u32, crate::locks::CondMisuseKind)> = findings
+            .iter()
+            .map(|finding| (finding.range.start.line, finding.kind))
+            .collect();
+        // `waitLocked` is assumed to be called with the lock held.
+        assert_eq!(
+            summary,
+            vec![
+                (22, WaitOutsideLoop),
+                (29, WaitWithoutLock),
+                (39, WaitOutsideLoop)
+            ]
+        );
+        assert_eq!(findings[1].lock.as_deref(), Some("q.mu"));
+
+        let graph = crate::analysis::build_graph_data(&tree, code);
+        // Lock, Unlock, then Signal in Push.
+        assert!(graph.edges.iter().any(|edge| {
+            edge.edge_type == crate::types::GraphEdgeType::Sync
+                && edge.to == "Queue.Push::sync::sync#2"
+        }));
+    }
+
+    #[test]
+    fn test_graph_dot_export() {
+        let code = r#"
+func main() {
+    ch := make(chan int)
+    go func() {
+        ch <- 1
+    }()
+    v := <-ch
+    _ = v
+}
+"#;
+        let Some(dot) = crate::dot::source_to_dot(code) else {
+            panic!("no DOT output");
+        };
+        assert!(dot.starts_with("digraph go_analyzer {"));
+        assert!(dot.trim_end().ends_with('}'));
+        assert!(dot.contains("shape=cds"));
+        assert!(dot.contains("shape=hexagon"));
+        assert!(dot.contains("label=\"send\", style=bold"));
+        let graph = crate::types::GraphData {
+            nodes: vec![crate::types::GraphNode {
+                id: "var:a\"b".to_string(),
+                label: "a\"b".to_string(),
+                entity_type: crate::types::GraphEntityType::Variable,
+                range: Range::new(Position::new(4, 0), Position::new(4, 1)),
+                extra: None,
+                bytes: None,
+            }],
+            edges: vec![crate::types::GraphEdge {
+                from: "var:a\"b".to_string(),
+                to: "callsite:x".to_string(),
+                edge_type: crate::types::GraphEdgeType::Sync,
+            }],
+            truncated: false,
+        };
+        let dot = crate::dot::graph_to_dot(&graph);
+        assert!(dot.contains("\"var:a\\\"b\" [label=\"a\\\"b\\nline 5\", shape=ellipse"));
+        // Endpoints that are not entities are drawn as points.
+        assert!(dot.contains("\"callsite:x\" [label=\"\", shape=point"));
+        assert!(dot.contains("style=dotted"));
+    }
+
+    #[test]
+    fn test_graph_mermaid_export_and_filters() {
+        use crate::mermaid::{graph_to_mermaid, GraphFilter};
+        use crate::types::{GraphEdge, GraphEdgeType, GraphEntityType, GraphNode};
+        let node = |id: &str, label: &str, entity_type: GraphEntityType| GraphNode {
+            id: id.to_string(),
+            label: label.to_string(),
+            entity_type,
+            range: Range::new(Position::new(2, 0), Position::new(2, 1)),
+            extra: None,
+            bytes: None,
+        };
+        let edge = |from: &str, to: &str, edge_type: GraphEdgeType| GraphEdge {
+            from: from.to_string(),
+            to: to.to_string(),
+            edge_type,
+        };
+        let graph = crate::types::GraphData {
+            nodes: vec![
+                node("fn:main", "main", GraphEntityType::Function),
+                node("go:1", "go func", GraphEntityType::Goroutine),
+                node("ch:jobs", "jobs", GraphEntityType::Channel),
+                node("var:x", "x \"quoted\"", GraphEntityType::Variable),
+            ],
+            edges: vec![
+                edge("fn:main", "go:1", GraphEdgeType::Spawn),
+                edge("go:1", "ch:jobs", GraphEdgeType::Send),
+                edge("ch:jobs", "var:x", GraphEdgeType::Use),
+                edge("callsite::handle#0", "fn:main", GraphEdgeType::Call),
+            ],
+            truncated: false,
+        };
+        let mermaid = graph_to_mermaid(&graph);
+        let expected = r#"flowchart LR
+    n0["main<br/>line 3"]
+    n1{{"go func<br/>line 3"}}
+    n2[/"jobs<br/>line 3"/]
+    n3("x #quot;quoted#quot;<br/>line 3")
+    n4(("handle"))
+    n0 ==>|go| n1
+    n1 ==>|send| n2
+    n2 --> n3
+    n4 -->|call| n0
+"#;
+        assert_eq!(mermaid, expected);
+
+        let filter = GraphFilter {
+            entities: vec![GraphEntityType::Function, GraphEntityType::Goroutine],
+            ..Default::default()
+        };
+        let filtered = filter.apply(&graph);
+        assert_eq!(filtered.nodes.len(), 2);
+        assert_eq!(filtered.edges.len(), 2);
+
+        let filter = GraphFilter {
+            root: Some("jobs".to_string()),
+            depth: Some(1),
+            ..Default::default()
+        };
+        let around = filter.apply(&graph);
+        let around: Vec<&str> = around.nodes.iter().map(|node| node.id.as_str()).collect();
+        assert_eq!(around, vec!["go:1", "ch:jobs", "var:x"]);
+
+        let args = |list: &[&str]| list.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert_eq!(crate::cli::run(&args(&[])), None);
+        assert_eq!(crate::cli::run(&args(&["mermaid"])), Some(2));
+        assert_eq!(
+            crate::cli::run(&args(&["mermaid", "x.go", "--entities", "Nope"])),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn test_workspace_graph_resolves_across_files() {
+        use crate::types::{GraphEdgeType, GraphEntityType};
+        use crate::workspace::{build_workspace_graph, GraphParams, GraphScope, SourceFile};
+        use std::path::PathBuf;
+
+        let sources = [
+            (
+                "/ws/app/main.go",
+                r#"
+package main
+
+import "example.com/ws/store"
+
+var counter int
+
+func main() {
+    helper()
+    store.Save(counter)
+}
+"#,
+            ),
+            (
+                "/ws/app/helper.go",
+                r#"
+package main
+
+func helper() {
+    counter++
+}
+"#,
+            ),
+            (
+                "/ws/store/store.go",
+                r#"
+package store
+
+func Save(n int) {
+    counter := n
+    _ = counter
+}
+"#,
+            ),
+        ];
+        let mut files = Vec::new();
+        for (path, code) in sources {
+            let tree = match parse_go(code) {
+                Ok(tree) => tree,
+                Err(_) => return,
+            };
+            files.push(SourceFile {
+                path: PathBuf::from(path),
+                code: code.to_string(),
+                tree,
+            });
+        }
+        let graph = build_workspace_graph(&files);
+
+        let node = |label: &str, file: &str| {
+            graph
+                .nodes
+                .iter()
+                .find(|node| {
+                    node.label == label
+                        && node.entity_type == GraphEntityType::Function
+                        && node.extra.as_ref().and_then(|extra| extra["file"].as_str())
+                            == Some(file)
+                })
+                .map(|node| node.id.clone())
+        };
+        let helper = node("helper", "/ws/app/helper.go");
+        let save = node("Save", "/ws/store/store.go");
+        assert!(helper.is_some() && save.is_some());
+        let calls: Vec<_> = graph
+            .edges
+            .iter()
+            .filter(|edge| edge.edge_type == GraphEdgeType::Call)
+            .map(|edge| edge.to.clone())
+            .collect();
+        assert!(helper.as_ref().is_some_and(|id| calls.contains(id)));
+        assert!(save.as_ref().is_some_and(|id| calls.contains(id)));
+        assert!(graph.nodes.iter().all(|node| node
+            .extra
+            .as_ref()
+            .is_some_and(|extra| extra["file"].is_string())));
+
+        // `counter` in helper.go uses main.go's package variable; store.go's local does not.
+        let declaration = graph
+            .nodes
+            .iter()
+            .find(|node| node.id == "/ws/app/main.go::var::counter#0")
+            .map(|node| node.id.clone());
+        let uses: Vec<_> = graph
+            .edges
+            .iter()
+            .filter(|edge| {
+                edge.edge_type == GraphEdgeType::Use && Some(&edge.from) == declaration.as_ref()
+            })
+            .map(|edge| edge.to.clone())
+            .collect();
+        assert!(uses.contains(&"/ws/app/helper.go::helper::use::counter#0".to_string()));
+        assert!(!uses.iter().any(|id| id.starts_with("/ws/store/")));
+
+        let params: Result<GraphParams, _> =
+            serde_json::from_value(serde_json::json!({"uri": "file:///a.go"}));
+        assert!(params.is_ok_and(|params| params.scope == GraphScope::File));
+    }
+
+    #[test]
+    fn test_graph_call_edges_resolve_to_declarations() {
+        use crate::analysis::{build_graph_data, graph_id_kind};
+        use crate::types::{GraphEdgeType, GraphEntityType};
+
+        let code = r#"
+package main
+
+import "fmt"
+
+type Server struct{}
+type Client struct{}
+type App struct{ srv *Server }
+
+func (s *Server) run() {}
+func (s *Server) stop() {}
+func (c Client) run() {}
+
+func helper() {}
+
+func main() {
+    helper()
+    s := &Server{}
+    s.run()
+    var c Client
+    c.run()
+    app := App{}
+    app.srv.stop()
+    fmt.Println("done")
+    go func() {
+        helper()
+    }()
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let graph = build_graph_data(&tree, code);
+        let id = |label: &str| {
+            graph
+                .nodes
+                .iter()
+                .find(|node| {
+                    matches!(
+                        node.entity_type,
+                        GraphEntityType::Function | GraphEntityType::Method
+                    ) && node.label == label
+                })
+                .map(|node| node.id.clone())
+                .unwrap_or_default()
+        };
+        let calls: Vec<(String, String)> = graph
+            .edges
+            .iter()
+            .filter(|edge| edge.edge_type == GraphEdgeType::Call)
+            .map(|edge| (edge.from.clone(), edge.to.clone()))
+            .collect();
+        let main = id("main");
+        let called = |target: String| calls.contains(&(main.clone(), target));
+        assert!(called(id("helper")));
+        assert!(called(id("Server.run")));
+        assert!(called(id("Client.run")));
+        assert!(called(id("Server.stop")));
+        // The second `helper()` runs in the goroutine, which calls it.
+        assert_eq!(
+            calls
+                .iter()
+                .filter(|(from, to)| *from == main && *to == id("helper"))
+                .count(),
+            1
+        );
+        assert!(calls
+            .iter()
+            .any(|(from, to)| graph_id_kind(from) == Some("go") && *to == id("helper")));
+        // Calls into other packages keep an id made from the callee.
+        assert!(calls
+            .iter()
+            .any(|(from, to)| *from == main && to == "fn::fmt.Println#0"));
+    }
+
+    #[test]
+    fn test_graph_variable_nodes_carry_race_results() {
+        use crate::analysis::build_graph_data;
+        use crate::types::GraphEntityType;
+
+        let code = r#"
+package main
+
+import "sync"
+
+func main() {
+    var mu sync.Mutex
+    var racy int
+    var guarded int
+    var quiet int
+    go func() {
+        racy++
+        mu.Lock()
+        guarded++
+        mu.Unlock()
+    }()
+    quiet++
+    _ = racy
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let graph = build_graph_data(&tree, code);
+        let extra = |label: &str| {
+            graph
+                .nodes
+                .iter()
+                .find(|node| {
+                    node.entity_type == GraphEntityType::Variable
+                        && node.label == label
+                        && crate::analysis::graph_id_kind(&node.id) == Some("var")
+                })
+                .and_then(|node| node.extra.clone())
+                .unwrap_or_default()
+        };
+        let racy = extra("racy");
+        assert_eq!(racy["potential_race"], true);
+        assert!(racy["race_severity"].is_string());
+        let guarded = extra("guarded");
+        assert_eq!(guarded["potential_race"], false);
+        assert_eq!(guarded["guarded_by"], "mu");
+        let quiet = extra("quiet");
+        assert_eq!(quiet["potential_race"], false);
+        assert!(quiet.get("race_severity").is_none());
+
+        let dot = crate::dot::graph_to_dot(&graph);
+        assert!(dot.contains("penwidth=2"));
+        let mermaid = crate::mermaid::graph_to_mermaid(&graph);
+        assert!(mermaid.contains("classDef race"));
+    }
+
+    #[test]
+    fn test_channel_flow_subgraph() {
+        use crate::channels::channel_flow;
+        use crate::types::{GraphEdgeType, GraphEntityType};
+        use tower_lsp::lsp_types::Position;
+
+        let code = r#"
+package main
+
+func produce(out chan int, n int) {
+    for i := 0; i < n; i++ {
+        out <- i
+    }
+}
+
+func main() {
+    results := make(chan int)
+    done := make(chan struct{})
+    go func() {
+        produce(results, 3)
+        close(results)
+    }()
+    go func() {
+        for r := range results {
+            _ = r
+        }
+        done <- struct{}{}
+    }()
+    <-done
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let flow = match channel_flow(&tree, code, Position::new(10, 5)) {
+            Some(flow) => flow,
+            None => panic!("no flow for `results`"),
+        };
+        let channel = &flow.nodes[0];
+        assert_eq!(channel.entity_type, GraphEntityType::Channel);
+        assert_eq!(channel.label, "results");
+        assert_eq!(channel.range.start.line, 10);
+        let goroutines: Vec<_> = flow
+            .nodes
+            .iter()
+            .filter(|node| node.entity_type == GraphEntityType::Goroutine)
+            .collect();
+        assert_eq!(goroutines.len(), 2);
+        let edges = |edge_type: GraphEdgeType| {
+            flow.edges
+                .iter()
+                .filter(|edge| edge.edge_type == edge_type)
+                .count()
+        };
+        assert_eq!(edges(GraphEdgeType::Send), 1);
+        assert_eq!(edges(GraphEdgeType::Receive), 1);
+        assert_eq!(edges(GraphEdgeType::Close), 1);
+        assert_eq!(edges(GraphEdgeType::Spawn), 2);
+        // The send inside `produce` is attributed to the goroutine calling it.
+        let producer = goroutines
+            .iter()
+            .find(|node| node.range.start.line == 12)
+            .and_then(|node| node.extra.clone())
+            .unwrap_or_default();
+        assert_eq!(producer["sends"][0]["start"]["line"], 5);
+        assert_eq!(producer["closes"].as_array().map(Vec::len), Some(1));
+        assert!(flow
+            .nodes
+            .iter()
+            .any(|node| node.entity_type == GraphEntityType::Function && node.label == "main"));
+        assert!(channel_flow(&tree, code, Position::new(1, 0)).is_none());
+    }
+
+    #[test]
+    fn test_graph_spawn_tree() {
+        use crate::analysis::build_graph_data;
+        use crate::types::{GraphEdgeType, GraphEntityType};
+
+        let code = r#"
+package main
+
+func worker(id int) {}
+
+func serve() {
+    go func() {
+        go worker(2)
+    }()
+}
+
+func main() {
+    go worker(1)
+    serve()
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let graph = build_graph_data(&tree, code);
+        let function = |label: &str| {
+            graph
+                .nodes
+                .iter()
+                .find(|node| node.entity_type == GraphEntityType::Function && node.label == label)
+                .map(|node| node.id.clone())
+                .unwrap_or_default()
+        };
+        let goroutine = |line: u32| {
+            graph
+                .nodes
+                .iter()
+                .find(|node| {
+                    node.entity_type == GraphEntityType::Goroutine && node.range.start.line == line
+                })
+                .map(|node| node.id.clone())
+                .unwrap_or_default()
+        };
+        let has = |from: &str, to: &str, edge_type: GraphEdgeType| {
+            graph
+                .edges
+                .iter()
+                .any(|edge| edge.from == from && edge.to == to && edge.edge_type == edge_type)
+        };
+        // serve -> go func() -> go worker(2) -> worker
+        assert!(has(&function("serve"), &goroutine(6), GraphEdgeType::Spawn));
+        assert!(has(&goroutine(6), &goroutine(7), GraphEdgeType::Spawn));
+        assert!(has(&goroutine(7), &function("worker"), GraphEdgeType::Call));
+        // main -> go worker(1) -> worker, and main -> serve
+        assert!(has(&function("main"), &goroutine(12), GraphEdgeType::Spawn));
+        assert!(has(
+            &goroutine(12),
+            &function("worker"),
+            GraphEdgeType::Call
+        ));
+        assert!(has(
+            &function("main"),
+            &function("serve"),
+            GraphEdgeType::Call
+        ));
+        assert!(!graph
+            .edges
+            .iter()
+            .any(|edge| crate::analysis::graph_id_kind(&edge.from) == Some("spawnsite")));
+    }
+
+    #[test]
+    fn test_graph_ids_survive_unrelated_edits() {
+        use crate::analysis::{build_file_graph, ExternalSymbols};
+
+        let before = r#"
+package main
+
+func worker(ch chan int) {
+    var x int
+    x++
+    ch <- x
+}
+
+func main() {
+    ch := make(chan int)
+    go worker(ch)
+    <-ch
+}
+"#;
+        let after = r#"
+package main
+
+// worker sends one value.
+func worker(ch chan int) {
+
+    var x int
+    x++
+    ch <- x
+}
+
+func main() {
+    ch := make(chan int)
+    go worker(ch)
+    <-ch
+}
+"#;
+        let ids = |code: &str| {
+            let tree = match parse_go(code) {
+                Ok(tree) => tree,
+                Err(_) => return None,
+            };
+            let graph = build_file_graph(&tree, code, "main.go", &ExternalSymbols::default());
+            let mut ids: Vec<String> = graph.nodes.iter().map(|node| node.id.clone()).collect();
+            ids.extend(
+                graph
+                    .edges
+                    .iter()
+                    .flat_map(|edge| [edge.from.clone(), edge.to.clone()]),
+            );
+            Some(ids)
+        };
+        let (Some(before), Some(after)) = (ids(before), ids(after)) else {
+            return;
+        };
+        assert_eq!(before, after);
+        assert!(before.contains(&"main.go::worker::var::x#0".to_string()));
+        assert!(before.contains(&"main.go::worker::use::x#0".to_string()));
+        assert!(before.contains(&"main.go::fn::worker#0".to_string()));
+        assert!(before.contains(&"main.go::main::go::goroutine#0".to_string()));
+    }
+
+    #[test]
+    fn test_graph_interchange_round_trip() {
+        use crate::analysis::build_graph_data;
+        use crate::interchange::{
+            graph_from_graphml, graph_from_jgf, graph_to_graphml, graph_to_jgf, GraphFormat,
+        };
+        use crate::types::{ByteRange, GraphEntityType, GraphNode};
+
+        let code = r#"
+package main
+
+var counter int
+
+func worker(ch chan int) {
+    counter++
+    ch <- counter
+    close(ch)
+}
+
+func main() {
+    ch := make(chan int)
+    go worker(ch)
+    for v := range ch {
+        counter += v
+    }
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(t) => t,
+            Err(_) => return,
+        };
+        let mut graph = build_graph_data(&tree, code);
+        graph.nodes.push(GraphNode {
+            id: "odd::<\"a\" & 'b'>#0".to_string(),
+            label: "x < y && y > \"z\"".to_string(),
+            entity_type: GraphEntityType::SyncBlock,
+            range: tower_lsp::lsp_types::Range::new(
+                tower_lsp::lsp_types::Position::new(3, 4),
+                tower_lsp::lsp_types::Position::new(3, 9),
+            ),
+            extra: Some(serde_json::json!({"note": "<tag> & \"quote\""})),
+            bytes: Some(ByteRange {
+                start_byte: 20,
+                end_byte: 25,
+            }),
+        });
+        assert!(graph.nodes.iter().any(|node| node.extra.is_some()));
+        assert!(graph
+            .edges
+            .iter()
+            .any(|edge| !graph.nodes.iter().any(|node| node.id == edge.from)));
+
+        let graphml = graph_to_graphml(&graph);
+        assert!(graphml.contains("<graph id=\"go_analyzer\" edgedefault=\"directed\">"));
+        assert_eq!(graph_from_graphml(&graphml), Some(graph.clone()));
+        let jgf = graph_to_jgf(&graph);
+        assert_eq!(graph_from_jgf(&jgf), Some(graph.clone()));
+        assert_eq!(
+            GraphFormat::parse("GraphML").map(|f| f.render(&graph)),
+            Some(graphml)
+        );
+
+        // Keys renumbered by another tool, and JGF 2 nodes keyed by id.
+        let foreign = r#"<?xml version="1.0"?>
+<!-- saved elsewhere -->
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="d0" for="node" attr.name="label" attr.type="string"/>
+  <key id="d1" for="node" attr.name="entity_type" attr.type="string"/>
+  <key id="d2" for="node" attr.name="start_line"/><key id="d3" for="node" attr.name="start_character"/>
+  <key id="d4" for="node" attr.name="end_line"/><key id="d5" for="node" attr.name="end_character"/>
+  <key id="d6" for="edge" attr.name="edge_type"/>
+  <graph edgedefault="directed">
+    <node id="a"><data key="d0">a</data><data key="d1">Channel</data>
+      <data key="d2">1</data><data key="d3">0</data><data key="d4">1</data><data key="d5">1</data></node>
+    <node id="site"/>
+    <edge source="site" target="a"><data key="d6">Send</data></edge>
+  </graph>
+</graphml>"#;
+        let parsed = graph_from_graphml(foreign);
+        assert_eq!(parsed.as_ref().map(|g| g.nodes.len()), Some(1));
+        assert_eq!(
+            parsed.map(|g| g.edges),
+            Some(vec![crate::types::GraphEdge {
+                from: "site".to_string(),
+                to: "a".to_string(),
+                edge_type: crate::types::GraphEdgeType::Send,
+            }])
+        );
+        let jgf2 = serde_json::json!({"graph": {"nodes": {"a": {"label": "a", "metadata": {
+            "entity_type": "Channel",
+            "range": {"start": {"line": 1, "character": 0}, "end": {"line": 1, "character": 1}},
+        }}}, "edges": []}});
+        assert_eq!(
+            graph_from_jgf(&jgf2.to_string()).map(|g| g.nodes[0].entity_type.clone()),
+            Some(GraphEntityType::Channel)
+        );
+        assert_eq!(
+            graph_from_graphml("<graphml><node id=\"a\"></graphml"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_graph_query() {
+        use crate::analysis::build_graph_data;
+        use crate::query::{run_query, GraphQueryParams, GraphQueryResult};
+        use crate::types::{GraphEdge, GraphEdgeType};
+
+        let code = r#"
+package main
+
+var total int
+
+func worker(ch chan int) {
+    var x int
+    x++
+    total += x
+    ch <- x
+}
+
+func main() {
+    ch := make(chan int)
+    go worker(ch)
+    <-ch
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(t) => t,
+            Err(_) => return,
+        };
+        let graph = build_graph_data(&tree, code);
+        let query = |query: serde_json::Value| {
+            let params: Result<GraphQueryParams, _> = serde_json::from_value(query);
+            params.map(|params| run_query(&graph, &params.query))
+        };
+
+        let Ok(reached) = query(serde_json::json!({
+            "uri": "file:///a.go",
+            "query": "reachable",
+            "node": "main::go::goroutine#0",
+        })) else {
+            panic!("reachable query did not parse");
+        };
+        assert!(reached.ids.contains(&"fn::worker#0".to_string()));
+        assert!(reached.ids.contains(&"worker::chan::ch#0".to_string()));
+        assert!(!reached.ids.contains(&"main::chan::ch#0".to_string()));
+        assert!(reached.edges.contains(&GraphEdge {
+            from: "worker::send::ch#0".to_string(),
+            to: "worker::chan::ch#0".to_string(),
+            edge_type: GraphEdgeType::Send,
+        }));
+
+        let Ok(used) = query(serde_json::json!({
+            "uri": "file:///a.go",
+            "query": "used_variables",
+            "node": "worker",
+        })) else {
+            panic!("used_variables query did not parse");
+        };
+        assert_eq!(used.ids, vec!["worker::var::x#0", "var::total#0"]);
+        assert_eq!(used.nodes.len(), 2);
+
+        let Ok(neighbors) = query(serde_json::json!({
+            "uri": "file:///a.go",
+            "query": "neighbors",
+            "node": "main",
+            "edge_types": ["Spawn"],
+        })) else {
+            panic!("neighbors query did not parse");
+        };
+        assert_eq!(
+            neighbors.ids.first().map(String::as_str),
+            Some("main::go::goroutine#0")
+        );
+        assert!(neighbors.ids.contains(&"main::recv::ch#0".to_string()));
+        assert!(!neighbors.ids.contains(&"fn::make#0".to_string()));
+
+        let Ok(paths) = query(serde_json::json!({
+            "uri": "file:///a.go",
+            "query": "paths",
+            "from": "worker::var::x#0",
+            "to": "worker::chan::ch#0",
+            "max_paths": 1,
+        })) else {
+            panic!("paths query did not parse");
+        };
+        assert_eq!(
+            paths.paths,
+            vec![vec![
+                "worker::var::x#0",
+                "fn::worker#0",
+                "worker::chan::ch#0",
+            ]]
+        );
+
+        let Ok(unknown) = query(serde_json::json!({
+            "uri": "file:///a.go",
+            "query": "reachable",
+            "node": "nowhere",
+        })) else {
+            panic!("reachable query did not parse");
+        };
+        assert_eq!(unknown, GraphQueryResult::default());
+    }
+
+    #[test]
+    fn test_graph_store_pushes_only_changes() {
+        use crate::analysis::{build_file_graph, ExternalSymbols};
+        use crate::graphdelta::GraphStore;
+        use crate::types::{GraphEdge, GraphEdgeType};
+
+        let before = r#"
+package main
+
+func worker(ch chan int) {
+    var x int
+    x++
+    ch <- x
+}
+
+func main() {
+    ch := make(chan int)
+    go worker(ch)
+    <-ch
+}
+"#;
+        let after = r#"
+package main
+
+func worker(ch chan int) {
+    var x int
+    x++
+    ch <- x
+}
+
+func main() {
+    ch := make(chan int)
+    go worker(ch)
+    <-ch
+    var y int
+    y++
+}
+"#;
+        let graph = |code: &str| {
+            let tree = parse_go(code).ok()?;
+            Some(build_file_graph(
+                &tree,
+                code,
+                "main.go",
+                &ExternalSymbols::default(),
+            ))
+        };
+        let (Some(before), Some(after)) = (graph(before), graph(after)) else {
+            return;
+        };
+        let uri = match tower_lsp::lsp_types::Url::parse("file:///main.go") {
+            Ok(uri) => uri,
+            Err(_) => return,
+        };
+        let mut store = GraphStore::default();
+        assert_eq!(store.update(&uri, Some(1), before.clone()), None);
+        store.track(&uri, before.clone());
+        assert_eq!(store.update(&uri, Some(1), before.clone()), None);
+
+        let Some(delta) = store.update(&uri, Some(2), after.clone()) else {
+            panic!("expected a delta");
+        };
+        assert_eq!(delta.version, Some(2));
+        let ids: Vec<&str> = delta.nodes.iter().map(|node| node.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            vec!["main.go::main::var::y#0", "main.go::main::use::y#0"]
+        );
+        assert!(delta.removed_nodes.is_empty());
+        assert_eq!(
+            delta.edges,
+            vec![GraphEdge {
+                from: "main.go::main::var::y#0".to_string(),
+                to: "main.go::main::use::y#0".to_string(),
+                edge_type: GraphEdgeType::Use,
+            }]
+        );
+        assert!(delta.removed_edges.is_empty());
+
+        let Some(undo) = store.update(&uri, Some(3), before) else {
+            panic!("expected a delta");
+        };
+        assert!(undo.nodes.is_empty());
+        assert_eq!(undo.removed_nodes, ids);
+        assert_eq!(undo.removed_edges, delta.edges);
+
+        store.remove(&uri);
+        assert!(!store.is_tracked(&uri));
+    }
+
+    #[test]
+    fn test_graph_lock_layer() {
+        use crate::analysis::build_graph_data;
+        use crate::types::{GraphEdge, GraphEdgeType, GraphEntityType};
+
+        let code = r#"
+package main
+
+import "sync"
+
+var mu sync.Mutex
+var counter int
+
+type Store struct {
+    mu sync.Mutex
+}
+
+func (s *Store) add() {
+    s.mu.Lock()
+    counter++
+    s.mu.Unlock()
+}
+
+func main() {
+    go func() {
+        mu.Lock()
+        counter++
+        mu.Unlock()
+    }()
+    s := &Store{}
+    s.add()
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(t) => t,
+            Err(_) => return,
+        };
+        let graph = build_graph_data(&tree, code);
+        let edge = |from: &str, to: &str, edge_type: GraphEdgeType| GraphEdge {
+            from: from.to_string(),
+            to: to.to_string(),
+            edge_type,
+        };
+        for expected in [
+            edge(
+                "main::go::goroutine#0",
+                "var::mu#0",
+                GraphEdgeType::Acquires,
+            ),
+            edge("fn::Store.add#0", "lock::s.mu#0", GraphEdgeType::Acquires),
+            edge("var::mu#0", "var::counter#0", GraphEdgeType::Guards),
+            edge("lock::s.mu#0", "var::counter#0", GraphEdgeType::Guards),
+        ] {
+            assert!(graph.edges.contains(&expected), "missing {:?}", expected);
+        }
+        assert!(!graph
+            .edges
+            .iter()
+            .any(|e| e.edge_type == GraphEdgeType::Guards && e.to == "var::mu#0"));
+        assert!(!graph
+            .edges
+            .iter()
+            .any(|e| e.edge_type == GraphEdgeType::Acquires && e.from == "fn::main#0"));
+        let locks: Vec<_> = graph
+            .nodes
+            .iter()
+            .filter(|node| node.id == "lock::s.mu#0")
+            .collect();
+        assert_eq!(locks.len(), 1);
+        assert_eq!(locks[0].entity_type, GraphEntityType::SyncBlock);
+        assert_eq!(locks[0].label, "s.mu");
+    }
+
+    #[test]
+    fn test_graph_type_entities() {
+        use crate::analysis::build_graph_data;
+        use crate::types::{GraphEdge, GraphEdgeType, GraphEntityType};
+
+        let code = r#"
+package main
+
+import "io"
+
+type Closer interface {
+    Close() error
+}
+
+type Store interface {
+    Closer
+    Get(key string) string
+}
+
+type Streamer interface {
+    io.Reader
+}
+
+type Any interface{}
+
+type Memory struct {
+    data map[string]string
+}
+
+func (m *Memory) Get(key string) string { return m.data[key] }
+func (m Memory) Close() error { return nil }
+
+type Empty struct{}
+
+func (e Empty) Close() error { return nil }
+"#;
+        let tree = match parse_go(code) {
+            Ok(t) => t,
+            Err(_) => return,
+        };
+        let graph = build_graph_data(&tree, code);
+        let entity = |id: &str| {
+            graph
+                .nodes
+                .iter()
+                .find(|node| node.id == id)
+                .map(|node| node.entity_type.clone())
+        };
+        assert_eq!(entity("type::Memory#0"), Some(GraphEntityType::Struct));
+        assert_eq!(entity("type::Store#0"), Some(GraphEntityType::Interface));
+        assert_eq!(entity("fn::Memory.Get#0"), Some(GraphEntityType::Method));
+
+        let edge = |from: &str, to: &str, edge_type: GraphEdgeType| GraphEdge {
+            from: from.to_string(),
+            to: to.to_string(),
+            edge_type,
+        };
+        for expected in [
+            edge(
+                "type::Memory#0",
+                "fn::Memory.Get#0",
+                GraphEdgeType::HasMethod,
+            ),
+            edge(
+                "type::Memory#0",
+                "fn::Memory.Close#0",
+                GraphEdgeType::HasMethod,
+            ),
+            edge("type::Memory#0", "type::Store#0", GraphEdgeType::Implements),
+            edge(
+                "type::Memory#0",
+                "type::Closer#0",
+                GraphEdgeType::Implements,
+            ),
+            edge("type::Empty#0", "type::Closer#0", GraphEdgeType::Implements),
+        ] {
+            assert!(graph.edges.contains(&expected), "missing {:?}", expected);
+        }
+        let implemented: Vec<&str> = graph
+            .edges
+            .iter()
+            .filter(|edge| edge.edge_type == GraphEdgeType::Implements)
+            .map(|edge| edge.to.as_str())
+            .collect();
+        assert!(!implemented.contains(&"type::Streamer#0"));
+        assert!(!implemented.contains(&"type::Any#0"));
+        assert!(!graph.edges.contains(&edge(
+            "type::Empty#0",
+            "type::Store#0",
+            GraphEdgeType::Implements
+        )));
+    }
+
+    #[test]
+    fn test_workspace_graph_package_nodes() {
+        use crate::types::{GraphEdge, GraphEdgeType, GraphEntityType};
+        use crate::workspace::{build_workspace_graph, SourceFile};
+        use std::path::PathBuf;
+
+        let sources = [
+            (
+                "/ws/app/main.go",
+                r#"
+package main
+
+import (
+    "fmt"
+    "example.com/ws/jobs"
+)
+
+func main() {
+    fmt.Println(jobs.Run())
+}
+"#,
+            ),
+            (
+                "/ws/jobs/run.go",
+                r#"
+package jobs
+
+import "sync"
+
+func Run() int {
+    var wg sync.WaitGroup
+    results := make(chan int, 1)
+    wg.Add(1)
+    go func() {
+        defer wg.Done()
+        results <- 1
+    }()
+    wg.Wait()
+    return <-results
+}
+"#,
+            ),
+        ];
+        let mut files = Vec::new();
+        for (path, code) in sources {
+            let tree = match parse_go(code) {
+                Ok(tree) => tree,
+                Err(_) => return,
+            };
+            files.push(SourceFile {
+                path: PathBuf::from(path),
+                code: code.to_string(),
+                tree,
+            });
+        }
+        let graph = build_workspace_graph(&files);
+        let package = |id: &str| {
+            graph
+                .nodes
+                .iter()
+                .find(|node| node.id == id && node.entity_type == GraphEntityType::Package)
+        };
+        let (Some(app), Some(jobs)) = (
+            package("/ws/app::pkg::main#0"),
+            package("/ws/jobs::pkg::jobs#0"),
+        ) else {
+            panic!("missing package nodes");
+        };
+        assert_eq!(app.label, "main");
+        let extra = |node: &crate::types::GraphNode, key: &str| {
+            node.extra.as_ref().map(|extra| extra[key].clone())
+        };
+        assert_eq!(extra(app, "concurrent"), Some(serde_json::json!(false)));
+        assert_eq!(extra(jobs, "concurrent"), Some(serde_json::json!(true)));
+        assert_eq!(extra(jobs, "goroutines"), Some(serde_json::json!(1)));
+        assert_eq!(
+            package("pkg::sync#0").and_then(|node| extra(node, "external")),
+            Some(serde_json::json!(true))
+        );
+
+        let imports = |from: &str, to: &str| GraphEdge {
+            from: from.to_string(),
+            to: to.to_string(),
+            edge_type: GraphEdgeType::Imports,
+        };
+        for expected in [
+            imports("/ws/app::pkg::main#0", "pkg::fmt#0"),
+            imports("/ws/app::pkg::main#0", "/ws/jobs::pkg::jobs#0"),
+            imports("/ws/jobs::pkg::jobs#0", "pkg::sync#0"),
+        ] {
+            assert!(graph.edges.contains(&expected), "missing {:?}", expected);
+        }
+        assert_eq!(
+            graph
+                .edges
+                .iter()
+                .filter(|edge| edge.edge_type == GraphEdgeType::Imports)
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_graph_pages_cover_the_graph_once() {
+        use crate::analysis::build_graph_data;
+        use crate::graphpage::GraphPageCache;
+        use crate::types::GraphData;
+        use crate::workspace::GraphScope;
+
+        let code = r#"
+package main
+
+func worker(id int, ch chan int) {
+    var x int
+    x = id
+    ch <- x
+}
+
+func main() {
+    ch := make(chan int)
+    for i := 0; i < 3; i++ {
+        go worker(i, ch)
+    }
+    <-ch
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(t) => t,
+            Err(_) => return,
+        };
+        let graph = build_graph_data(&tree, code);
+        let uri = match tower_lsp::lsp_types::Url::parse("file:///main.go") {
+            Ok(uri) => uri,
+            Err(_) => return,
+        };
+        let mut cache = GraphPageCache::default();
+        let mut page = cache.first_page(&uri, GraphScope::File, Some(4), graph.clone(), 3);
+        assert_eq!(page.total_nodes, graph.nodes.len());
+        assert_eq!(page.total_edges, graph.edges.len());
+        let first_cursor = page.next_cursor.clone();
+        let mut joined = GraphData::default();
+        let mut pages = 0;
+        loop {
+            assert!(page.nodes.len() + page.edges.len() <= 3);
+            assert!(
+                page.edges.is_empty() || joined.nodes.len() + page.nodes.len() == graph.nodes.len()
+            );
+            joined.nodes.extend(page.nodes);
+            joined.edges.extend(page.edges);
+            pages += 1;
+            let Some(cursor) = page.next_cursor else {
+                break;
+            };
+            page = match cache.page(&uri, GraphScope::File, Some(4), &cursor, 3) {
+                Ok(page) => page,
+                Err(e) => panic!("{}", e),
+            };
+        }
+        assert_eq!(joined, graph);
+        assert_eq!(pages, (graph.nodes.len() + graph.edges.len()).div_ceil(3));
+
+        let Some(cursor) = first_cursor else {
+            panic!("expected more than one page");
+        };
+        assert!(cache
+            .page(&uri, GraphScope::File, Some(5), &cursor, 3)
+            .is_err());
+        assert!(cache
+            .page(&uri, GraphScope::Package, Some(4), &cursor, 3)
+            .is_err());
+        assert!(cache
+            .page(&uri, GraphScope::File, Some(4), "bogus", 3)
+            .is_err());
+        cache.first_page(&uri, GraphScope::File, Some(4), graph, 3);
+        assert!(cache
+            .page(&uri, GraphScope::File, Some(4), &cursor, 3)
+            .is_err());
+    }
+
+    #[test]
+    fn test_graph_select_and_critical_sections() {
+        use crate::analysis::build_graph_data;
+        use crate::types::{GraphEdge, GraphEdgeType, GraphEntityType};
+
+        let code = r#"
+package main
+
+import "sync"
+
+var mu sync.Mutex
+var total int
+
+func add(n int) {
+    mu.Lock()
+    total += n
+    mu.Unlock()
+    println(n)
+}
+
+func read() int {
+    mu.Lock()
+    defer mu.Unlock()
+    return total
+}
+
+func pump(in, out chan int, done chan struct{}) {
+    select {
+    case v := <-in:
+        out <- v
+    case out <- 0:
+    case <-done:
+    default:
+    }
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(t) => t,
+            Err(_) => return,
+        };
+        let graph = build_graph_data(&tree, code);
+        let regions: Vec<_> = graph
+            .nodes
+            .iter()
+            .filter(|node| {
+                node.entity_type == GraphEntityType::SyncBlock
+                    && node
+                        .extra
+                        .as_ref()
+                        .is_some_and(|extra| extra["critical_section"] == true)
+            })
+            .collect();
+        assert_eq!(regions.len(), 2);
+        // add: from the Lock() through the Unlock(), not the println after it.
+        assert_eq!(
+            (regions[0].range.start.line, regions[0].range.end.line),
+            (9, 11)
+        );
+        // read: the deferred unlock holds the lock through the end of the body.
+        assert_eq!(
+            (regions[1].range.start.line, regions[1].range.end.line),
+            (16, 18)
+        );
+        for region in &regions {
+            assert!(graph.edges.contains(&GraphEdge {
+                from: region.id.clone(),
+                to: "var::mu#0".to_string(),
+                edge_type: GraphEdgeType::Sync,
+            }));
+        }
+
+        let Some(select) = graph
+            .nodes
+            .iter()
+            .find(|node| node.entity_type == GraphEntityType::Select)
+        else {
+            panic!("missing select node");
+        };
+        assert_eq!(
+            select.extra,
+            Some(serde_json::json!({"cases": 3, "default": true}))
+        );
+        let mut channels: Vec<(String, GraphEdgeType)> = graph
+            .edges
+            .iter()
+            .filter(|edge| edge.from == select.id)
+            .map(|edge| {
+                (
+                    crate::analysis::graph_id_name(&edge.to).to_string(),
+                    edge.edge_type.clone(),
+                )
+            })
+            .collect();
+        channels.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            channels,
+            vec![
+                ("done".to_string(), GraphEdgeType::Receive),
+                ("in".to_string(), GraphEdgeType::Receive),
+                ("out".to_string(), GraphEdgeType::Send),
+            ]
+        );
+        // The select points at the same channel ids as the send and receive sites.
+        assert!(graph
+            .edges
+            .iter()
+            .any(|edge| edge.edge_type == GraphEdgeType::Receive
+                && edge.from != select.id
+                && graph
+                    .edges
+                    .iter()
+                    .any(|other| other.from == select.id && other.to == edge.to)));
+    }
+
+    #[test]
+    fn test_function_subgraph() {
+        use crate::analysis::{build_graph_data, function_label_at};
+        use crate::query::{function_subgraph, has_concurrency};
+
+        let code = r#"
+package main
+
+var total int
+
+func helper() {}
+
+func worker(ch chan int) {
+    var x int
+    x = total
+    helper()
+    ch <- x
+}
+
+func main() {
+    ch := make(chan int)
+    go worker(ch)
+    <-ch
+}
+
+func idle() {
+    helper()
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(t) => t,
+            Err(_) => return,
+        };
+        let graph = build_graph_data(&tree, code);
+        assert_eq!(
+            function_label_at(&tree, code, Position::new(10, 6)).as_deref(),
+            Some("worker")
+        );
+        assert_eq!(function_label_at(&tree, code, Position::new(3, 4)), None);
+
+        let Some(worker) = function_subgraph(&graph, "worker") else {
+            panic!("missing worker subgraph");
+        };
+        let ids: Vec<&str> = worker.nodes.iter().map(|node| node.id.as_str()).collect();
+        assert!(ids.contains(&"fn::worker#0"));
+        assert!(ids.contains(&"worker::var::x#0"));
+        assert!(ids.contains(&"var::total#0"));
+        assert!(ids.contains(&"fn::helper#0"));
+        assert!(ids.contains(&"main::go::goroutine#0"));
+        assert!(!ids.contains(&"fn::main#0"));
+        assert!(!ids.iter().any(|id| id.starts_with("main::chan")));
+        assert!(worker
+            .edges
+            .iter()
+            .all(|edge| edge.from.starts_with("worker::")
+                || edge.to.starts_with("worker::")
+                || edge.from == "fn::worker#0"
+                || edge.to == "fn::worker#0"));
+        assert!(has_concurrency(&worker));
+
+        let Some(main) = function_subgraph(&graph, "main") else {
+            panic!("missing main subgraph");
+        };
+        assert!(main
+            .nodes
+            .iter()
+            .any(|node| node.id == "main::go::goroutine#0"));
+        assert!(has_concurrency(&main));
+        assert!(function_subgraph(&graph, "idle").is_some_and(|idle| !has_concurrency(&idle)));
+        assert!(function_subgraph(&graph, "missing").is_none());
+    }
+
+    #[test]
+    fn test_incremental_reparse_matches_full_parse() {
+        use crate::anchor::{apply_content_change, edit_tree};
+        use tower_lsp::lsp_types::TextDocumentContentChangeEvent;
+
+        let text = r#"
+package main
+
+func worker(ch chan int) {
+    ch <- 1
+}
+
+func main() {
+    ch := make(chan int)
+    go worker(ch)
+}
+"#;
+        let mut tree = match parse_go(text) {
+            Ok(t) => t,
+            Err(_) => return,
+        };
+        let mut rope = ropey::Rope::from_str(text);
+        let change = |range: Range, new_text: &str| TextDocumentContentChangeEvent {
+            range: Some(range),
+            range_length: None,
+            text: new_text.to_string(),
+        };
+        let changes = [
+            change(Range::new(Position::new(4, 10), Position::new(4, 11)), "42"),
+            change(
+                Range::new(Position::new(9, 17), Position::new(9, 17)),
+                "\n    <-ch",
+            ),
+            change(
+                Range::new(Position::new(2, 0), Position::new(2, 0)),
+                "var total int\n",
+            ),
+        ];
+        let deltas: Vec<_> = changes
+            .iter()
+            .filter_map(|change| apply_content_change(&mut rope, change))
+            .collect();
+        assert_eq!(deltas.len(), 3);
+        let text = rope.to_string();
+        assert!(text.contains("ch <- 42") && text.contains("<-ch\n}"));
+
+        edit_tree(&mut tree, &deltas);
+        let mut parser = Parser::new();
+        if parser.set_language(tree_sitter_go::language()).is_err() {
+            return;
+        }
+        let reparsed = match parser.parse(&text, Some(&tree)) {
+            Some(tree) => tree,
+            None => panic!("incremental parse failed"),
+        };
+        let full = match parse_go(&text) {
+            Ok(t) => t,
+            Err(_) => return,
+        };
+        assert!(!reparsed.root_node().has_error());
+        assert_eq!(reparsed.root_node().to_sexp(), full.root_node().to_sexp());
+        assert_eq!(reparsed.root_node().end_byte(), text.len());
+        let changed: Vec<_> = tree.changed_ranges(&reparsed).collect();
+        assert!(!changed.is_empty());
+        assert!(changed.iter().all(|range| range.start_byte < text.len()));
+    }
+
+    #[test]
+    fn test_document_store_per_document_state() {
+        use crate::documents::DocumentStore;
+        use tower_lsp::lsp_types::{TextDocumentContentChangeEvent, Url};
+
+        let store = DocumentStore::default();
+        let uri = |name: &str| match Url::parse(&format!("file:///tmp/{}.go", name)) {
+            Ok(uri) => uri,
+            Err(_) => panic!("valid uri"),
+        };
+        let (a, b) = (uri("a"), uri("b"));
+        let code = "package main\n\nfunc main() {\n    x := 1\n    _ = x\n}\n";
+        if store.open(&a, code.to_string(), 1).is_none() {
+            return;
+        }
+        store.open(&b, code.to_string(), 1);
+        assert_eq!(store.version(&a), Some(1));
+        assert_eq!(store.text(&uri("missing")), None);
+
+        let change = |line: u32, new_text: &str| TextDocumentContentChangeEvent {
+            range: Some(Range::new(Position::new(line, 9), Position::new(line, 10))),
+            range_length: None,
+            text: new_text.to_string(),
+        };
+        std::thread::scope(|scope| {
+            scope.spawn(|| store.change(&a, 2, &[change(3, "2")]));
+            scope.spawn(|| store.change(&b, 3, &[change(3, "3")]));
+        });
+        assert_eq!(store.version(&a), Some(2));
+        assert_eq!(store.version(&b), Some(3));
+        assert!(store.text(&a).is_some_and(|text| text.contains("x := 2")));
+        assert!(store.text(&b).is_some_and(|text| text.contains("x := 3")));
+
+        // Older versions and empty changes leave the document alone.
+        assert!(store.change(&a, 1, &[change(3, "9")]).is_none());
+        assert!(store.change(&a, 4, &[]).is_none());
+        assert_eq!(store.version(&a), Some(2));
+
+        let Some((text, deltas)) = store.change(&a, 5, &[change(3, "42")]) else {
+            panic!("change applies");
+        };
+        let text = text.to_string();
+        assert_eq!(deltas.map(|deltas| deltas.len()), Some(1));
+        let full = match parse_go(&text) {
+            Ok(t) => t,
+            Err(_) => return,
+        };
+        // The edited tree waits for the next parse, which reuses it.
+        assert!(store.tree(&a).is_none());
+        assert_eq!(
+            store
+                .parse(&a, &text, Some(&[]))
+                .map(|tree| tree.root_node().to_sexp()),
+            Some(full.root_node().to_sexp())
+        );
+        assert_eq!(
+            store.tree(&a).map(|tree| tree.root_node().to_sexp()),
+            Some(full.root_node().to_sexp())
+        );
+
+        store.evict();
+        assert!(store.tree(&a).is_some() && store.tree(&b).is_none());
+        assert_eq!(store.clear(), (2, 2));
+        assert_eq!(store.version(&a), None);
+    }
+
+    #[tokio::test]
+    async fn test_parse_sources_in_parallel_keeps_order() {
+        use crate::documents::parse_sources;
+        use std::path::PathBuf;
+
+        let files: Vec<(PathBuf, String)> = (0..16)
+            .map(|i| {
+                (
+                    PathBuf::from(format!("/tmp/pkg/file{}.go", i)),
+                    format!("package pkg\n\nfunc f{}() {{\n    go f{}()\n}}\n", i, i),
+                )
+            })
+            .collect();
+        let sources = parse_sources(files.clone(), &crate::workers::WorkerPool::new(4)).await;
+        assert_eq!(sources.len(), files.len());
+        for (source, (path, code)) in sources.iter().zip(&files) {
+            assert_eq!(&source.path, path);
+            assert_eq!(&source.code, code);
+            let full = match parse_go(code) {
+                Ok(t) => t,
+                Err(_) => return,
+            };
+            assert_eq!(
+                source.tree.root_node().to_sexp(),
+                full.root_node().to_sexp()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_analysis_queue_runs_once_per_burst() {
+        use crate::queue::AnalysisQueue;
+        use std::time::Duration;
+        use tower_lsp::lsp_types::Url;
+
+        let queue = AnalysisQueue::new(Duration::from_millis(20));
+        let (a, b) = match (
+            Url::parse("file:///tmp/a.go"),
+            Url::parse("file:///tmp/b.go"),
+        ) {
+            (Ok(a), Ok(b)) => (a, b),
+            _ => panic!("valid uris"),
+        };
+        let burst = futures::future::join_all([
+            queue.settle(&a),
+            queue.settle(&a),
+            queue.settle(&b),
+            queue.settle(&a),
+        ])
+        .await;
+        let ran: Vec<bool> = burst.iter().map(Option::is_some).collect();
+        assert_eq!(ran, vec![false, false, true, true]);
+
+        let Some(ticket) = burst[3] else {
+            panic!("last change of the burst runs");
+        };
+        assert!(queue.is_current(&a, ticket).await);
+        let (_, later) = futures::join!(
+            async {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            },
+            queue.settle(&a)
+        );
+        let Some(later) = later else {
+            panic!("a change after the burst settles on its own");
+        };
+        // Results of the earlier ticket are outdated once a later change settled.
+        assert!(!queue.is_current(&a, ticket).await);
+        queue.remove(&a).await;
+        assert!(!queue.is_current(&a, later).await);
+    }
+
+    #[test]
+    fn test_analysis_cache_round_trip() {
+        use crate::diskcache::{cache_key, AnalysisCache, AnalysisCacheConfig, FileArtifacts};
+        use crate::types::EntityCount;
+        use std::path::PathBuf;
+        use std::time::{Duration, SystemTime};
+        use tower_lsp::lsp_types::Diagnostic;
+
+        let dir =
+            std::env::temp_dir().join(format!("go-analyzer-cache-test-{}", std::process::id()));
+        let cache = AnalysisCache::new(AnalysisCacheConfig {
+            enabled: true,
+            dir: dir.clone(),
+            max_bytes: 1024 * 1024,
+            max_age: Duration::from_secs(3600),
+        });
+        let path = PathBuf::from("/work/pkg/main.go");
+        let code = "package main\n\nfunc main() {}\n";
+        let key = cache_key(&path, code, "go1.22");
+        assert_eq!(key, cache_key(&path, code, "go1.22"));
+        // Keys and document hashes are the same in every build, so the disk cache outlives one.
+        assert_eq!(
+            crate::record::document_hash("package main\n"),
+            "6ade73b2b0461367-13"
+        );
+        assert_ne!(key, cache_key(&path, code, "go1.21"));
+        assert_ne!(key, cache_key(&path, "package main\n", "go1.22"));
+        assert_ne!(
+            key,
+            cache_key(&PathBuf::from("/work/other.go"), code, "go1.22")
+        );
+
+        assert_eq!(cache.load(&key, &path), None);
+        let artifacts = FileArtifacts {
+            path: path.clone(),
+            counts: EntityCount {
+                variables: 2,
+                functions: 1,
+                channels: 1,
+                goroutines: 3,
+            },
+            synchronized_goroutines: 2,
+            diagnostics: vec![Diagnostic::new_simple(
+                Range::new(Position::new(2, 0), Position::new(2, 4)),
+                "finding".to_string(),
+            )],
+        };
+        cache.store(&key, &artifacts);
+        assert_eq!(cache.load(&key, &path), Some(artifacts.clone()));
+        // Another file landing on the same key is a miss, not its artifacts.
+        assert_eq!(cache.load(&key, &PathBuf::from("/work/other.go")), None);
+        let report = artifacts.clone().into_report();
+        assert_eq!(FileArtifacts::from_report(&report), artifacts);
+
+        let disabled = AnalysisCache::new(AnalysisCacheConfig {
+            enabled: false,
+            dir: dir.clone(),
+            max_bytes: 1024 * 1024,
+            max_age: Duration::from_secs(3600),
+        });
+        assert_eq!(disabled.load(&key, &path), None);
+
+        // A full cache drops its least recently written entries, and entries past their age.
+        let written = |key: &str| dir.join(format!("{}.json", key));
+        let backdate = |key: &str, secs: u64| {
+            std::fs::File::options()
+                .append(true)
+                .open(written(key))
+                .and_then(|file| file.set_modified(SystemTime::now() - Duration::from_secs(secs)))
+        };
+        let Ok(size) = std::fs::metadata(written(&key)).map(|metadata| metadata.len()) else {
+            panic!("the entry was stored");
+        };
+        if backdate(&key, 60).is_err() {
+            return;
+        }
+        let bounded = AnalysisCache::new(AnalysisCacheConfig {
+            enabled: true,
+            dir: dir.clone(),
+            max_bytes: size,
+            max_age: Duration::from_secs(3600),
+        });
+        bounded.store("newer", &artifacts);
+        assert_eq!(bounded.load(&key, &path), None);
+        assert_eq!(bounded.load("newer", &path), Some(artifacts.clone()));
+        if backdate("newer", 2 * 3600).is_err() {
+            return;
+        }
+        bounded.prune();
+        assert_eq!(bounded.load("newer", &path), None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_worker_pool_maps_in_order_off_the_event_loop() {
+        use crate::workers::WorkerPool;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let pool = WorkerPool::new(4);
+        let running = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+        let (counting, peak) = (running.clone(), most.clone());
+        let squares = pool
+            .map((0..64u64).collect(), move |n| {
+                let now = counting.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                std::thread::sleep(std::time::Duration::from_millis(1));
+                counting.fetch_sub(1, Ordering::SeqCst);
+                n * n
+            })
+            .await;
+        assert_eq!(squares, (0..64u64).map(|n| n * n).collect::<Vec<_>>());
+        // No more traversals run at once than the pool has slots.
+        assert!((1..=4).contains(&most.load(Ordering::SeqCst)));
+
+        assert_eq!(WorkerPool::new(0).parallelism, 1);
+        assert_eq!(
+            WorkerPool::new(1).map(vec!["a", "bb"], str::len).await,
+            vec![1, 2]
+        );
+        assert_eq!(pool.map(Vec::<u8>::new(), |n| n).await, Vec::<u8>::new());
+        // The single-threaded event loop runs the task a traversal waits for.
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let unblocker = tokio::spawn(async move { sender.send(7) });
+        assert_eq!(pool.run(move || receiver.recv().ok()).await, Some(7));
+        assert!(matches!(unblocker.await, Ok(Ok(()))));
+    }
+
+    #[test]
+    fn test_query_detectors() {
+        use crate::detect::{CHANNEL_OPS, ENTITIES, SYNC_CALLS};
+
+        let code = r#"
+package main
+
+import "sync"
+
+var mu sync.Mutex
+
+func worker(in <-chan int, out chan<- int, done chan struct{}) {
+    a, b := 1, Map[int](xs)
+    for v := range in {
+        out <- v + a + b
+    }
+    select {
+    case <-done:
+    }
+    close(out)
+    go func() { mu.Lock() }()
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(t) => t,
+            Err(_) => return,
+        };
+        let counts = count_entities(&tree, code);
+        assert_eq!(
+            (
+                counts.variables,
+                counts.functions,
+                counts.goroutines,
+                counts.channels
+            ),
+            (3, 1, 1, 3)
+        );
+
+        let mut sites = Vec::new();
+        CHANNEL_OPS.for_each_match(tree.root_node(), code, |captures| {
+            let kind = ["send", "close", "receive", "range"]
+                .into_iter()
+                .find(|kind| captures.get(kind).is_some());
+            if let (Some(kind), Some(channel)) = (kind, captures.get("channel")) {
+                sites.push((kind, &code[channel.byte_range()]));
+            }
+            true
+        });
+        assert_eq!(
+            sites,
+            vec![
+                ("range", "in"),
+                ("send", "out"),
+                ("receive", "done"),
+                ("close", "out")
+            ]
+        );
+
+        let mut calls = 0;
+        SYNC_CALLS.for_each_match(tree.root_node(), code, |captures| {
+            calls += usize::from(captures.get("call").is_some());
+            true
+        });
+        assert_eq!(calls, 3);
+        assert!(
+            SYNC_CALLS.any_match(tree.root_node(), code, |captures| captures
+                .get("select")
+                .is_some())
+        );
+        assert!(!ENTITIES.any_match(tree.root_node(), code, |_| false));
+    }
+
+    #[test]
+    fn test_deeply_nested_code_does_not_overflow_the_stack() {
+        use crate::analysis::build_graph_data;
+        use crate::backend::FileAnalyzer;
+        use crate::callgraph::collect_kind;
+        use crate::rule_api::RuleRegistry;
+        use crate::rules::RuleDocsConfig;
+        use crate::util::{walk_tree, Visit, MAX_TRAVERSAL_DEPTH};
+
+        let Ok(uri) = tower_lsp::lsp_types::Url::parse("file:///tmp/deep.go") else {
+            panic!("a valid file URL");
+        };
+        let analyzer = FileAnalyzer {
+            rule_docs: RuleDocsConfig::from_env(),
+            custom_rules: RuleRegistry::default(),
+        };
+        let depth = 3000;
+        let code = format!(
+            "package main\n\nfunc main() {{\n    x := {}x0{}\n    go func() {{ x++ }}()\n}}\n",
+            "(".repeat(depth),
+            ")".repeat(depth)
+        );
+        let tree = match parse_go(&code) {
+            Ok(t) => t,
+            Err(_) => return,
+        };
+        // A small stack, as on a runtime worker thread, that recursion per AST level overflows.
+        let result = std::thread::Builder::new()
+            .stack_size(256 * 1024)
+            .spawn(move || {
+                let counts = count_entities(&tree, &code);
+                let mut parens = Vec::new();
+                collect_kind(tree.root_node(), "parenthesized_expression", &mut parens);
+                let info = find_variable_at_position(&tree, &code, Position::new(4, 17));
+                let graph = build_graph_data(&tree, &code);
+                // Every file-level pass, as the server runs them on a document.
+                let diagnostics = analyzer
+                    .file_diagnostics(&uri, &tree, &code)
+                    .map(|findings| findings.diagnostics.len());
+                let (mut entered, mut left) = (0, 0);
+                walk_tree(tree.root_node(), |visit| {
+                    match visit {
+                        Visit::Enter(_) => entered += 1,
+                        Visit::Leave(_) => left += 1,
+                    }
+                    true
+                });
+                (
+                    counts.goroutines,
+                    parens.len(),
+                    info.ok().flatten().map(|info| info.uses.len()),
+                    graph.nodes.is_empty(),
+                    entered == left,
+                    diagnostics,
+                )
+            });
+        let Ok(Ok((goroutines, parens, uses, empty_graph, balanced, diagnostics))) =
+            result.map(|handle| handle.join())
+        else {
+            panic!("traversal overflowed the stack");
+        };
+        assert_eq!(goroutines, 1);
+        assert_eq!(parens, depth.min(MAX_TRAVERSAL_DEPTH));
+        assert_eq!(uses, Some(1));
+        assert!(!empty_graph);
+        assert!(balanced);
+        assert!(diagnostics.is_ok());
+    }
+
+    #[test]
+    fn test_server_stats_report_caches_and_timings() {
+        use crate::diskcache::{AnalysisCache, AnalysisCacheConfig, FileArtifacts};
+        use crate::documents::DocumentStore;
+        use crate::stats::{AnalysisTimings, ServerStats};
+        use std::path::PathBuf;
+        use std::time::Duration;
+        use tower_lsp::lsp_types::{TextDocumentContentChangeEvent, Url};
+
+        let store = DocumentStore::default();
+        let uri = match Url::parse("file:///tmp/stats.go") {
+            Ok(uri) => uri,
+            Err(_) => return,
+        };
+        let code = "package main\n\nfunc main() {\n    x := 1\n    _ = x\n}\n";
+        if store.open(&uri, code.to_string(), 1).is_none() {
+            return;
+        }
+        assert!(store.tree(&uri).is_some());
+        let change = TextDocumentContentChangeEvent {
+            range: Some(Range::new(Position::new(3, 9), Position::new(3, 10))),
+            range_length: None,
+            text: "2".to_string(),
+        };
+        assert!(store.change(&uri, 2, &[change]).is_some());
+        assert!(store.tree(&uri).is_none());
+        let [documents, trees] = store.stats();
+        assert_eq!(
+            (documents.name.as_str(), documents.entries),
+            ("documents", 1)
+        );
+        assert_eq!(documents.estimated_bytes, Some(code.len()));
+        assert_eq!(
+            (trees.entries, trees.hits, trees.misses),
+            (1, Some(1), Some(1))
+        );
+        assert_eq!(trees.hit_ratio, Some(0.5));
+        assert!(trees.estimated_bytes.is_some_and(|bytes| bytes > 0));
+        assert!(trees.oldest_entry_age_ms >= trees.newest_entry_age_ms);
+
+        let dir =
+            std::env::temp_dir().join(format!("go-analyzer-stats-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let cache = AnalysisCache::new(AnalysisCacheConfig {
+            enabled: true,
+            dir: dir.clone(),
+            max_bytes: 1024 * 1024,
+            max_age: std::time::Duration::from_secs(3600),
+        });
+        let path = PathBuf::from("/work/stats.go");
+        assert!(cache.load("key", &path).is_none());
+        cache.store(
+            "key",
+            &FileArtifacts {
+                path: path.clone(),
+                counts: Default::default(),
+                synchronized_goroutines: 0,
+                diagnostics: Vec::new(),
+            },
+        );
+        assert!(cache.load("key", &path).is_some());
+        let disk = cache.stats();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(
+            (disk.entries, disk.hits, disk.misses),
+            (1, Some(1), Some(1))
+        );
+        assert!(disk.estimated_bytes.is_some_and(|bytes| bytes > 0));
+
+        let timings = AnalysisTimings::default();
+        timings.record("parse", Duration::from_millis(4));
+        timings.record("parse", Duration::from_millis(2));
+        assert_eq!(timings.time("cursor", || 7), 7);
+        let snapshot = timings.snapshot();
+        let names: Vec<&str> = snapshot.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["cursor", "parse"]);
+        let parse = &snapshot[1];
+        assert_eq!(parse.runs, 2);
+        assert!((parse.total_ms - 6.0).abs() < 1e-6 && (parse.mean_ms - 3.0).abs() < 1e-6);
+        assert!((parse.max_ms - 4.0).abs() < 1e-6 && (parse.last_ms - 2.0).abs() < 1e-6);
+
+        let stats = ServerStats {
+            uptime_ms: 1,
+            caches: vec![documents, trees, disk],
+            timings: snapshot,
+        };
+        let schema = crate::protocol::protocol_schema();
+        assert!(validate_schema(
+            &schema["definitions"]["ServerStats"],
+            &serde_json::json!(stats),
+            &schema,
+            "ServerStats"
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_document_cache_limits_spare_open_documents() {
+        use crate::documents::{CacheLimits, DocumentStore};
+        use std::time::Duration;
+        use tower_lsp::lsp_types::Url;
+
+        let uri = |name: &str| match Url::parse(&format!("file:///tmp/{}.go", name)) {
+            Ok(uri) => uri,
+            Err(_) => panic!("valid uri"),
+        };
+        let (a, b, c) = (uri("a"), uri("b"), uri("c"));
+        let code = "package main\n\nfunc main() {}\n";
+
+        // With no TTL, open documents and their trees stay; a closed one expires.
+        let store = DocumentStore::new(CacheLimits {
+            max_trees: 1,
+            max_documents: 1,
+            ttl: Duration::ZERO,
+        });
+        if store.open(&a, code.to_string(), 1).is_none() {
+            return;
+        }
+        store.open(&b, code.to_string(), 1);
+        store.open(&c, code.to_string(), 1);
+        std::thread::sleep(Duration::from_millis(20));
+        store.close(&a);
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(store.text(&a).is_none());
+        assert!(store.text(&b).is_some() && store.tree(&b).is_some());
+        store.evict();
+        assert_eq!(store.version(&a), None);
+        // Both open documents stay over the document limit; only one tree is kept.
+        assert_eq!((store.version(&b), store.version(&c)), (Some(1), Some(1)));
+        let [documents, trees] = store.stats();
+        assert_eq!((documents.entries, trees.entries), (2, 1));
+        assert!(store.text(&c).is_some());
+
+        // Closed documents go least recently used first once over the limit.
+        let store = DocumentStore::new(CacheLimits {
+            max_trees: 10,
+            max_documents: 1,
+            ttl: Duration::from_secs(300),
+        });
+        store.open(&a, code.to_string(), 1);
+        store.open(&b, code.to_string(), 1);
+        store.close(&a);
+        assert!(store.text(&a).is_some());
+        store.evict();
+        assert_eq!(store.version(&a), None);
+        assert_eq!(store.version(&b), Some(1));
+    }
+
+    #[test]
+    fn test_analysis_errors_for_invalid_positions() {
+        let code = "package main\n\nfunc main() {\n\tx := 1\n\t_ = x\n}\n";
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let beyond_line = Position::new(3, 40);
+        let error = match find_variable_at_position(&tree, code, beyond_line) {
+            Err(error) => error,
+            Ok(found) => panic!("expected an invalid position, got {:?}", found),
+        };
+        assert_eq!(error, AnalysisError::InvalidPosition(beyond_line));
+        let response = error.to_rpc_error();
+        assert_eq!(response.code, tower_lsp::jsonrpc::ErrorCode::InvalidParams);
+        assert_eq!(
+            response.data,
+            Some(serde_json::json!({"kind": "invalid_position"}))
+        );
+        assert!(matches!(
+            crate::analysis::check_position(code, Position::new(7, 0)),
+            Err(AnalysisError::InvalidPosition(_))
+        ));
+        assert_eq!(
+            crate::analysis::check_position(code, Position::new(3, 7)),
+            Ok(())
+        );
+
+        // A tree that no longer matches the text it is read against.
+        let node = match crate::analysis::node_at(&tree, Position::new(4, 5)) {
+            Ok(node) => node,
+            Err(error) => panic!("no node at the use of x: {}", error),
+        };
+        assert!(matches!(
+            crate::analysis::node_text("package main", node),
+            Err(AnalysisError::ByteRangeOutOfBounds { len: 12, .. })
+        ));
+
+        // A pass fails on such a tree instead of reading past the text.
+        let error = match crate::locks::find_unlock_issues(&tree, "package main") {
+            Err(error) => error,
+            Ok(_) => panic!("a pass read a tree that does not match the text"),
+        };
+        assert!(matches!(
+            error,
+            AnalysisError::ByteRangeOutOfBounds { len: 12, .. }
+        ));
+        assert_eq!(
+            error.to_rpc_error().code,
+            tower_lsp::jsonrpc::ErrorCode::InternalError
+        );
+    }
+
+    #[tokio::test]
+    async fn test_a_panicking_handler_fails_only_its_own_request() {
+        use crate::transport::PanicGuard;
+        use futures::future::BoxFuture;
+        use std::convert::Infallible;
+        use std::task::{Context, Poll};
+        use tower_lsp::jsonrpc::{ErrorCode, Request, Response};
+        use tower_service::Service;
+
+        struct Panicking;
+        impl Service<Request> for Panicking {
+            type Response = Option<Response>;
+            type Error = Infallible;
+            type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+            fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn call(&mut self, _: Request) -> Self::Future {
+                Box::pin(futures::future::lazy(
+                    |_| -> Result<Option<Response>, Infallible> { panic!("broken invariant") },
+                ))
+            }
+        }
+
+        let mut service = PanicGuard(Panicking);
+        let hover = Request::build("textDocument/hover").id(7).finish();
+        let Ok(Some(response)) = service.call(hover).await else {
+            panic!("a request is answered");
+        };
+        let (id, result) = response.into_parts();
+        assert_eq!(id, tower_lsp::jsonrpc::Id::Number(7));
+        let Err(error) = result else {
+            panic!("a panicking handler has no result");
+        };
+        assert_eq!(error.code, ErrorCode::InternalError);
+        assert!(error.message.contains("textDocument/hover"));
+
+        // The session goes on; a notification has nobody to answer.
+        let change = Request::build("textDocument/didChange").finish();
+        assert!(matches!(service.call(change).await, Ok(None)));
+    }
+
+    #[test]
+    fn test_request_spans_carry_log_events_and_timing() {
+        use std::sync::{Arc, Mutex, PoisonError};
+        use tower_lsp::lsp_types::TraceValue;
+        use tracing::level_filters::LevelFilter;
+
+        #[derive(Clone, Default)]
+        struct Buffer(Arc<Mutex<Vec<u8>>>);
+        impl std::io::Write for Buffer {
+            fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+                self.0
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .extend_from_slice(bytes);
+                Ok(bytes.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        assert_eq!(
+            crate::logging::LogConfig::default().level,
+            LevelFilter::INFO
+        );
+        let trace_level = crate::logging::trace_level;
+        assert_eq!(
+            trace_level(TraceValue::Off, LevelFilter::WARN),
+            LevelFilter::WARN
+        );
+        assert_eq!(
+            trace_level(TraceValue::Messages, LevelFilter::WARN),
+            LevelFilter::DEBUG
+        );
+        assert_eq!(
+            trace_level(TraceValue::Messages, LevelFilter::TRACE),
+            LevelFilter::TRACE
+        );
+        assert_eq!(
+            trace_level(TraceValue::Verbose, LevelFilter::ERROR),
+            LevelFilter::TRACE
+        );
+
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let (subscriber, handle) = crate::logging::subscriber(
+            LevelFilter::INFO,
+            tracing_subscriber::fmt::writer::BoxMakeWriter::new(move || writer.clone()),
+        );
+        let trace_id = tracing::subscriber::with_default(subscriber, || {
+            let ctx = crate::trace::RequestContext::new("goanalyzer/cursor");
+            tracing::debug!(parent: &ctx.span, "before setTrace");
+            let failed = ctx.logged::<()>(
+                "find_variable",
+                Err(AnalysisError::NoNodeAt(Position::new(1, 2))),
+            );
+            assert!(failed.is_err());
+            assert!(handle
+                .reload(trace_level(TraceValue::Messages, LevelFilter::INFO))
+                .is_ok());
+            tracing::debug!(parent: &ctx.span, "after setTrace");
+            ctx.trace_id.clone()
+        });
+
+        let log = String::from_utf8_lossy(&buffer.0.lock().unwrap_or_else(PoisonError::into_inner))
+            .to_string();
+        assert!(!log.contains("before setTrace"), "{}", log);
+        assert!(log.contains("after setTrace"), "{}", log);
+        let failure = log
+            .lines()
+            .find(|line| line.contains("find_variable failed: no syntax node at 1:2"));
+        assert!(
+            failure.is_some_and(|line| line.contains("ERROR") && line.contains(&trace_id)),
+            "{}",
+            log
+        );
+        assert!(
+            log.lines()
+                .any(|line| line.contains(&trace_id) && line.contains("close time.busy")),
+            "{}",
+            log
+        );
+    }
+
+    #[test]
+    fn test_document_text_snapshots_share_storage_until_changed() {
+        use crate::anchor::apply_content_change;
+        use crate::documents::DocumentStore;
+        use std::sync::Arc;
+        use tower_lsp::lsp_types::{TextDocumentContentChangeEvent, Url};
+
+        let store = DocumentStore::default();
+        let uri = match Url::parse("file:///tmp/snapshot.go") {
+            Ok(uri) => uri,
+            Err(_) => panic!("valid uri"),
+        };
+        store.open(&uri, "package main\r\n\nvar s = \"日本\"\n".to_string(), 1);
+        let (Some(first), Some(second)) = (store.text(&uri), store.text(&uri)) else {
+            panic!("document is open");
+        };
+        assert!(Arc::ptr_eq(&first, &second));
+
+        // Line 2, UTF-16 column 10 is after `日`, which is three bytes but one code unit; the
+        // `\r` before the first newline stays part of line 0.
+        let change = TextDocumentContentChangeEvent {
+            range: Some(Range::new(Position::new(2, 10), Position::new(2, 11))),
+            range_length: None,
+            text: "本語".to_string(),
+        };
+        let Some((rope, Some(deltas))) = store.change(&uri, 2, std::slice::from_ref(&change))
+        else {
+            panic!("ranged change applies");
+        };
+        assert_eq!(deltas[0].old_bytes.start_byte, 27);
+        assert_eq!(deltas[0].old.start, Position::new(2, 12));
+        let Some(changed) = store.text(&uri) else {
+            panic!("document is open");
+        };
+        assert!(!Arc::ptr_eq(&first, &changed));
+        assert_eq!(&*changed, "package main\r\n\nvar s = \"日本語\"\n");
+        assert_eq!(rope, &*changed);
+        assert_eq!(&*first, "package main\r\n\nvar s = \"日本\"\n");
+
+        let mut past_end = ropey::Rope::from_str("a\nb");
+        let append = TextDocumentContentChangeEvent {
+            range: Some(Range::new(Position::new(5, 0), Position::new(5, 0))),
+            range_length: None,
+            text: "c".to_string(),
+        };
+        assert!(apply_content_change(&mut past_end, &append).is_some());
+        assert_eq!(past_end, "a\nbc");
+    }
+
+    #[tokio::test]
+    async fn test_transport_flags_and_serving_over_a_stream() {
+        use crate::transport::{accept_backoff, serve_stream, Transport};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let args = |list: &[&str]| list.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert_eq!(Transport::from_args(&args(&[])), Ok(Transport::Stdio));
+        assert_eq!(
+            Transport::from_args(&args(&["--listen", "127.0.0.1:9257"])),
+            Ok(Transport::Listen("127.0.0.1:9257".to_string()))
+        );
+        assert_eq!(
+            Transport::from_args(&args(&["--pipe=/tmp/lsp.sock"])),
+            Ok(Transport::Pipe("/tmp/lsp.sock".to_string()))
+        );
+        assert!(Transport::from_args(&args(&["--listen"])).is_err());
+        assert!(Transport::from_args(&args(&["--listen", "a:1", "--pipe", "b"])).is_err());
+        assert_eq!(crate::cli::run(&args(&["--pipe"])), Some(2));
+        assert_eq!(crate::cli::run(&args(&["--listen", "127.0.0.1:0"])), None);
+        // A failed accept is retried: at once for an aborted connection, after a pause when out
+        // of file descriptors.
+        let aborted = std::io::Error::from(std::io::ErrorKind::ConnectionAborted);
+        assert_eq!(accept_backoff(&aborted), std::time::Duration::ZERO);
+        #[cfg(unix)]
+        assert!(accept_backoff(&std::io::Error::from_raw_os_error(24)) > std::time::Duration::ZERO);
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let (_stop, stopped) = tokio::sync::watch::channel(false);
+        let session = tokio::spawn(serve_stream(server, stopped));
+        let (mut read, mut write) = tokio::io::split(client);
+        let send = |body: serde_json::Value| {
+            let body = body.to_string();
+            format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+        };
+        let initialize = send(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {"capabilities": {}, "trace": "messages"}
+        }));
+        assert!(write.write_all(initialize.as_bytes()).await.is_ok());
+        let mut received = Vec::new();
+        let mut buffer = [0u8; 4096];
+        while !String::from_utf8_lossy(&received).contains("goanalyzer/stats") {
+            match read.read(&mut buffer).await {
+                Ok(0) | Err(_) => panic!("session closed before answering initialize"),
+                Ok(n) => received.extend_from_slice(&buffer[..n]),
+            }
+        }
+        assert!(String::from_utf8_lossy(&received).contains("\"executeCommandProvider\""));
+
+        let exit = send(serde_json::json!({"jsonrpc": "2.0", "method": "exit"}));
+        assert!(write.write_all(exit.as_bytes()).await.is_ok());
+        assert!(write.shutdown().await.is_ok());
+        let finished = tokio::time::timeout(std::time::Duration::from_secs(5), session).await;
+        assert!(matches!(finished, Ok(Ok(()))));
+
+        // Stopping ends a session whose client keeps its end open.
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let (stop, stopped) = tokio::sync::watch::channel(false);
+        let session = tokio::spawn(serve_stream(server, stopped));
+        let (mut read, mut write) = tokio::io::split(client);
+        assert!(write.write_all(initialize.as_bytes()).await.is_ok());
+        let mut received = Vec::new();
+        while !String::from_utf8_lossy(&received).contains("goanalyzer/stats") {
+            match read.read(&mut buffer).await {
+                Ok(0) | Err(_) => panic!("session closed before answering initialize"),
+                Ok(n) => received.extend_from_slice(&buffer[..n]),
+            }
+        }
+        stop.send_replace(true);
+        let finished = tokio::time::timeout(std::time::Duration::from_secs(5), session).await;
+        assert!(matches!(finished, Ok(Ok(()))));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_cancels_queued_analyses_and_flushes_the_record() {
+        use crate::queue::AnalysisQueue;
+        use crate::record::{
+            document_hash, RecordConfig, RecordEntry, RecordWriter, SessionRecorder,
+        };
+        use std::time::{Duration, Instant};
+        use tower_lsp::lsp_types::Url;
+
+        let queue = AnalysisQueue::new(Duration::from_secs(30));
+        let uri = match Url::parse("file:///tmp/a.go") {
+            Ok(uri) => uri,
+            Err(_) => panic!("valid uri"),
+        };
+        let started = Instant::now();
+        let (settled, _) = futures::join!(queue.settle(&uri), async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            queue.close();
+        });
+        assert_eq!(settled, None);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(queue.is_closed());
+        assert_eq!(queue.settle(&uri).await, None);
+
+        let dir =
+            std::env::temp_dir().join(format!("go-analyzer-flush-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = RecordConfig {
+            enabled: true,
+            dir: dir.clone(),
+            capacity: 2,
+        };
+        let entry = |trace_id: String| RecordEntry {
+            timestamp_ms: 0,
+            trace_id,
+            method: "goanalyzer/cursor".to_string(),
+            uri: uri.to_string(),
+            version: None,
+            document_hash: document_hash("package main\n"),
+            position: None,
+            findings: serde_json::json!([]),
+        };
+        let mut recorder = SessionRecorder::new(config.clone());
+        for i in 0..3 {
+            assert!(recorder.record(entry(format!("t-{}", i))).is_ok());
+        }
+        let lines = |recorder: &SessionRecorder| {
+            std::fs::read_to_string(recorder.path())
+                .map(|text| text.lines().count())
+                .unwrap_or_default()
+        };
+        assert_eq!(lines(&recorder), 3);
+        assert!(recorder.flush().is_ok());
+        assert_eq!(lines(&recorder), 2);
+        // Nothing left to compact: the file is synced as it is.
+        assert!(recorder.flush().is_ok());
+        assert_eq!(lines(&recorder), 2);
+        let resumed = SessionRecorder::new(config.clone());
+        let traces: Vec<String> = resumed
+            .dump()
+            .entries
+            .into_iter()
+            .map(|entry| entry.trace_id)
+            .collect();
+        assert_eq!(traces, vec!["t-1", "t-2"]);
+
+        // Sessions served at once record to files of their own, written by the recorder thread.
+        let first = RecordWriter::spawn(config.clone());
+        let second = RecordWriter::spawn(config);
+        first.record(entry("first".to_string()));
+        second.record(entry("second".to_string()));
+        assert!(first.flush().await.is_ok());
+        assert!(second.flush().await.is_ok());
+        let (first, second) = (first.dump().await, second.dump().await);
+        assert_ne!(first.path, second.path);
+        for (dump, trace_id) in [(first, "first"), (second, "second")] {
+            let last = dump.entries.last().map(|entry| entry.trace_id.clone());
+            assert_eq!(last.as_deref(), Some(trace_id));
+            let written = std::fs::read_to_string(&dump.path).unwrap_or_default();
+            assert_eq!(written.lines().count(), 2);
+            assert!(written.contains(&format!("\"trace_id\":\"{}\"", trace_id)));
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_graphs_past_their_time_budget_are_truncated() {
+        use crate::analysis::{build_file_graph_within, build_graph_data};
+        use crate::budget::{AnalysisBudget, Deadline};
+        use crate::types::GraphEntityType;
+        use crate::workspace::{build_workspace_graph_within, SourceFile};
+        use std::path::PathBuf;
+        use std::time::{Duration, Instant};
+
+        let code = r#"package main
+
+func main() {
+    x := 0
+    go func() { x++ }()
+    _ = x
+}
+"#;
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let within =
+            |deadline| build_file_graph_within(&tree, code, "", &Default::default(), deadline);
+
+        let full = within(Deadline::NONE);
+        assert!(!full.truncated);
+        assert_eq!(full, build_graph_data(&tree, code));
+        assert!(serde_json::to_value(&full)
+            .map(|value| value.get("truncated").is_none())
+            .unwrap_or(false));
+
+        let expired = Deadline::at(Instant::now());
+        let partial = within(expired);
+        assert!(partial.truncated);
+        assert!(partial.nodes.len() < full.nodes.len());
+        let value = serde_json::to_value(&partial).unwrap_or_default();
+        assert_eq!(value["truncated"], serde_json::json!(true));
+        let page = crate::graphpage::graph_page(&partial, 1, 0, 10, None);
+        assert!(page.truncated);
+
+        let files = vec![SourceFile {
+            path: PathBuf::from("/w/main.go"),
+            code: code.to_string(),
+            tree: tree.clone(),
+        }];
+        assert!(!build_workspace_graph_within(&files, Deadline::NONE).truncated);
+        let workspace = build_workspace_graph_within(&files, expired);
+        assert!(workspace.truncated);
+        assert!(workspace
+            .nodes
+            .iter()
+            .all(|node| node.entity_type == GraphEntityType::Package));
+
+        let started = Instant::now();
+        assert_eq!(
+            AnalysisBudget { limit: None }.deadline(started),
+            Deadline::NONE
+        );
+        let budget = AnalysisBudget {
+            limit: Some(Duration::from_secs(3600)),
+        };
+        assert!(!budget.deadline(started).expired());
+        assert!(AnalysisBudget {
+            limit: Some(Duration::ZERO)
+        }
+        .deadline(started)
+        .expired());
+    }
+
+    #[test]
+    fn test_perf_report_covers_parse_analysis_and_cache_status() {
+        use crate::perf::DocumentSample;
+        use crate::trace::RequestContext;
+        use std::time::Duration;
+
+        let code = "package main\n\nfunc main() {\n    x := 1\n    _ = x\n}\n";
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        };
+        let schema = crate::protocol::protocol_schema();
+        let valid = |report: &crate::perf::PerfParams| {
+            validate_schema(
+                &schema["definitions"]["PerfParams"],
+                &serde_json::json!(report),
+                &schema,
+                "PerfParams",
+            )
+            .is_ok()
+        };
+
+        let ctx = RequestContext::new("goanalyzer/stats");
+        let report = ctx.perf_report();
+        assert_eq!(report.trace_id, ctx.trace_id);
+        assert_eq!(report.method, "goanalyzer/stats");
+        assert_eq!(report.uri, None);
+        assert_eq!(report.nodes, None);
+        assert!(valid(&report));
+
+        let ctx = RequestContext::new("textDocument/hover");
+        let sample = |parse| DocumentSample {
+            uri: "file:///a.go".to_string(),
+            code_len: code.len(),
+            tree: tree.clone(),
+            parse,
+        };
+        ctx.perf.document(sample(Some(Duration::from_millis(4))));
+        ctx.perf.document(sample(None));
+        std::thread::sleep(Duration::from_millis(20));
+        let report = ctx.perf_report();
+        assert_eq!(report.uri.as_deref(), Some("file:///a.go"));
+        assert_eq!(report.code_len, Some(code.len()));
+        assert_eq!(report.parse_ms, Some(4.0));
+        assert_eq!(report.tree_cache_hit, Some(false));
+        assert!(report.nodes.is_some_and(|nodes| nodes > 10));
+        assert!(report.total_ms >= 20.0);
+        assert!((report.analysis_ms - (report.total_ms - 4.0)).abs() < 1e-6);
+        assert!(!report.truncated);
+        assert!(valid(&report));
+
+        // Restarting leaves out the wait; a later cache hit alone is reported as one.
+        ctx.perf.restart();
+        ctx.perf.truncated();
+        let clone = ctx.clone();
+        clone.perf.document(DocumentSample {
+            uri: "file:///b.go".to_string(),
+            ..sample(None)
+        });
+        let report = ctx.perf_report();
+        assert!(report.total_ms < 20.0);
+        assert_eq!(report.tree_cache_hit, Some(true));
+        assert_eq!(report.parse_ms, None);
+        assert!(report.truncated);
+        assert_eq!(
+            serde_json::json!(report)["truncated"],
+            serde_json::json!(true)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_semantic_helper_is_located_or_built_from_bundled_sources() {
+        use crate::bootstrap::{
+            build_helper, helper_version, locate_helper, resolve_executable, BuildConsent,
+            HELPER_NAME,
+        };
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir =
+            std::env::temp_dir().join(format!("go-analyzer-bootstrap-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let build_dir = dir.join("build");
+        let go = dir.join("go");
+        let broken_go = dir.join("broken-go");
+        // `go build -ldflags=<flags> -o <out> .`: the fake toolchain writes a script to <out> and
+        // the flags next to it.
+        if std::fs::create_dir_all(&dir).is_err()
+            || std::fs::write(
+                &go,
+                "#!/bin/sh\nprintf '%s' \"$2\" > \"$4.flags\"\nprintf '#!/bin/sh\\n' > \"$4\"\nchmod +x \"$4\"\n",
+            )
+            .is_err()
+            || std::fs::write(
+                &broken_go,
+                "#!/bin/sh\necho 'main.go:1: syntax error' >&2\nexit 1\n",
+            )
+            .is_err()
+            || std::fs::set_permissions(&go, std::fs::Permissions::from_mode(0o755)).is_err()
+            || std::fs::set_permissions(&broken_go, std::fs::Permissions::from_mode(0o755)).is_err()
+        {
+            return;
+        }
+
+        assert_eq!(BuildConsent::parse("always"), BuildConsent::Always);
+        assert_eq!(BuildConsent::parse("never"), BuildConsent::Never);
+        assert_eq!(BuildConsent::parse("ask"), BuildConsent::Ask);
+        assert_eq!(BuildConsent::parse(""), BuildConsent::Ask);
+
+        let failed = build_helper(&broken_go.display().to_string(), &build_dir);
+        assert!(failed.is_err_and(|e| e.to_string().contains("syntax error")));
+        assert_eq!(
+            locate_helper(&dir.join("absent").display().to_string(), &build_dir, &[]),
+            None
+        );
+        let on_path = resolve_executable(HELPER_NAME);
+        assert_eq!(locate_helper(HELPER_NAME, &build_dir, &[]), on_path);
+
+        let built = match build_helper(&go.display().to_string(), &build_dir) {
+            Ok(built) => built,
+            Err(e) => panic!("{}", e),
+        };
+        assert!(build_dir.join("src").join("main.go").is_file());
+        assert!(build_dir.join("src").join("go.mod").is_file());
+        // The build stamps the version the handshake expects.
+        assert_eq!(
+            std::fs::read_to_string(built.with_extension("partial.flags")).ok(),
+            Some(format!(
+                "-ldflags=-X main.helperVersion={}",
+                helper_version()
+            ))
+        );
+        // A configured path wins over the build; a command name falls back to it.
+        assert_eq!(
+            locate_helper(&go.display().to_string(), &build_dir, &[]),
+            Some(go.clone())
+        );
+        if on_path.is_none() {
+            assert_eq!(
+                locate_helper(HELPER_NAME, &build_dir, &[]),
+                Some(built.clone())
+            );
+            // A built helper that failed the handshake is not picked again.
+            assert_eq!(locate_helper(HELPER_NAME, &build_dir, &[built]), None);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_gopls_types_and_implementations_are_merged_into_the_graph() {
+        use crate::analysis::build_file_graph;
+        use crate::budget::Deadline;
+        use crate::gopls::{type_from_hover, GoplsClient, GoplsConfig};
+        use crate::types::{GraphEdgeType, GraphEntityType};
+        use std::os::unix::fs::PermissionsExt;
+
+        assert_eq!(
+            type_from_hover("```go\nfield name string\n```\n\n---\ndocs"),
+            Some("string".to_string())
+        );
+        assert_eq!(
+            type_from_hover("```go\nconst limit untyped int = 10\n```"),
+            Some("untyped int".to_string())
+        );
+        assert_eq!(type_from_hover("```go\nfunc main()\n```"), None);
+
+        let dir = std::env::temp_dir().join(format!("go-analyzer-gopls-{}", std::process::id()));
+        let _ = std::fs::create_dir_all(&dir);
+        let file = dir.join("main.go");
+        let uri = match tower_lsp::lsp_types::Url::from_file_path(&file) {
+            Ok(uri) => uri,
+            Err(()) => return,
+        };
+        // Logs every message and answers requests by method with canned results; notifications
+        // get no answer. It keeps the default UTF-16 positions: the definition's character 8 is
+        // after `π`, two bytes but one code unit, at byte column 9.
+        let log = dir.join("gopls.log");
+        let script = r#"#!/bin/sh
+while :; do
+  len=
+  while IFS= read -r line; do
+    line=$(printf '%s' "$line" | tr -d '\r')
+    [ -z "$line" ] && break
+    case "$line" in Content-Length:*) len=${line#Content-Length: };; esac
+  done
+  [ -z "$len" ] && exit 0
+  body=$(dd bs=1 count="$len" 2>/dev/null)
+  printf '%s\n' "$body" >> 'LOG'
+  id=$(printf '%s' "$body" | sed -n 's/^{"id":\([0-9]*\),.*/\1/p')
+  [ -z "$id" ] && continue
+  case "$body" in
+    *'"method":"initialize"'*) result='{"capabilities":{}}' ;;
+    *'"method":"textDocument/hover"'*) result='{"contents":{"kind":"markdown","value":"```go\nvar total int64\n```"}}' ;;
+    *'"method":"textDocument/definition"'*) result='{"uri":"URI","range":{"start":{"line":11,"character":8},"end":{"line":11,"character":13}}}' ;;
+    *'"method":"textDocument/implementation"'*) result='[{"uri":"URI","range":{"start":{"line":2,"character":5},"end":{"line":2,"character":10}}},{"uri":"file:///elsewhere/io.go","range":{"start":{"line":7,"character":5},"end":{"line":7,"character":11}}}]' ;;
+    *) result='null' ;;
+  esac
+  reply="{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":$result}"
+  printf 'Content-Length: %s\r\n\r\n%s' "${#reply}" "$reply"
+done
+"#
+        .replace("URI", uri.as_str())
+        .replace("LOG", &log.display().to_string());
+        let gopls = dir.join("gopls.sh");
+        let code = "package main\n\ntype Shape interface {\n\tArea() float64\n}\n\ntype Square struct{ side float64 }\n\nfunc (s Square) Area() float64 { return s.side * s.side }\n\nfunc main() {\n\tvar π, total = 0, 0\n\ttotal++\n}\n";
+        let client = GoplsClient::new(GoplsConfig {
+            enabled: true,
+            path: gopls.display().to_string(),
+            timeout_ms: 2000,
+        });
+
+        // A gopls that failed to start is not tried again until the workspace changes.
+        assert_eq!(
+            client.declaration(&uri, Position::new(12, 2), code).await,
+            None
+        );
+        if std::fs::write(&gopls, script).is_err()
+            || std::fs::set_permissions(&gopls, std::fs::Permissions::from_mode(0o755)).is_err()
+        {
+            return;
+        }
+        assert_eq!(
+            client.declaration(&uri, Position::new(12, 2), code).await,
+            None
+        );
+        client.set_root(tower_lsp::lsp_types::Url::from_directory_path(&dir).ok());
+        assert_eq!(
+            client.declaration(&uri, Position::new(12, 2), code).await,
+            Some(Position::new(11, 9))
+        );
+        let tree = match parse_go(code) {
+            Ok(tree) => tree,
+            Err(e) => panic!("{}", e),
+        };
+        let mut graph = build_file_graph(&tree, code, "main.go", &Default::default());
+        client
+            .enrich_graph(&uri, code, &mut graph, Deadline::NONE)
+            .await;
+        // Closing the document closes it in gopls too, once; a later query opens it again.
+        client.close(&uri).await;
+        client.close(&uri).await;
+        assert_eq!(
+            client.declaration(&uri, Position::new(12, 2), code).await,
+            Some(Position::new(11, 9))
+        );
+        client.stop().await;
+        let sent = |method: &str| {
+            std::fs::read_to_string(&log)
+                .map(|text| {
+                    text.lines()
+                        .filter(|line| line.contains(&format!("\"method\":\"{}\"", method)))
+                        .count()
+                })
+                .unwrap_or_default()
+        };
+        assert_eq!(sent("textDocument/didClose"), 1);
+        assert_eq!(sent("textDocument/didOpen"), 2);
+        assert!(std::fs::read_to_string(&log)
+            .unwrap_or_default()
+            .contains(r#""positionEncodings":["utf-8","utf-16"]"#));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let total = graph.nodes.iter().find(|node| {
+            node.label == "total"
+                && node.entity_type == GraphEntityType::Variable
+                && node
+                    .extra
+                    .as_ref()
+                    .and_then(|extra| extra.get("use"))
+                    .is_none()
+        });
+        assert_eq!(
+            total
+                .and_then(|node| node.extra.as_ref())
+                .map(|extra| extra["type"].clone()),
+            Some(serde_json::json!("int64"))
+        );
+        let square = graph
+            .nodes
+            .iter()
+            .find(|node| node.entity_type == GraphEntityType::Struct);
+        assert_eq!(
+            square
+                .and_then(|node| node.extra.as_ref())
+                .map(|extra| extra["implements"].clone()),
+            Some(serde_json::json!([{ "uri": "file:///elsewhere/io.go", "line": 7 }]))
+        );
+        assert_eq!(
+            graph
+                .edges
+                .iter()
+                .filter(|edge| edge.edge_type == GraphEdgeType::Implements)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_race_detector_output_becomes_diagnostics_at_workspace_lines() {
+        use crate::racedetector::{
+            parse_race_output, race_diagnostics, DataRace, RaceDetectorReport, RaceSectionKind,
+        };
+
+        let root = std::env::temp_dir().join(format!("go-analyzer-race-{}", std::process::id()));
+        let file = root.join("counter.go");
+        let code = "package m\n\ntype Counter struct{ n int }\n\nfunc (c *Counter) Inc() {\n\tc.n++ // zähler\n}\n\nfunc (c *Counter) Get() int {\n\treturn c.n\n}\n";
+        if std::fs::create_dir_all(&root).is_err() || std::fs::write(&file, code).is_err() {
+            return;
+        }
+        let path = file.display();
+        let test_file = root.join("counter_test.go");
+        let test_path = test_file.display();
+        let output = format!(
+            "=== RUN   TestCounter\n==================\nWARNING: DATA RACE\nWrite at 0x00c00001c0f8 by goroutine 7:\n  example.com/m.(*Counter).Inc()\n      {path}:6 +0x44\n  example.com/m.TestCounter.func1()\n      {test_path}:12 +0x38\n\nPrevious read at 0x00c00001c0f8 by goroutine 6:\n  example.com/m.(*Counter).Get()\n      {path}:10 +0x3a\n  testing.tRunner()\n      /usr/local/go/src/testing/testing.go:1595 +0x238\n\nGoroutine 7 (running) created at:\n  example.com/m.TestCounter()\n      {test_path}:11 +0x10c\n\nGoroutine 6 (running) created at:\n  testing.(*T).Run()\n      /usr/local/go/src/testing/testing.go:1648 +0x82a\n==================\n    testing.go:1465: race detected during execution of test\n--- FAIL: TestCounter (0.00s)\nFAIL\n"
+        );
+
+        let races = parse_race_output(&output);
+        assert_eq!(races.len(), 1);
+        let sections = &races[0].sections;
+        assert_eq!(sections.len(), 4);
+        assert_eq!(sections[0].kind, RaceSectionKind::Access);
+        assert_eq!(sections[0].description, "Write by goroutine 7");
+        assert_eq!(sections[0].goroutine, Some(7));
+        assert_eq!(
+            sections[0].frames[0].function,
+            "example.com/m.(*Counter).Inc()"
+        );
+        assert_eq!(sections[0].frames[0].line, 6);
+        assert_eq!(sections[1].description, "Previous read by goroutine 6");
+        assert_eq!(sections[2].kind, RaceSectionKind::Creation);
+        assert_eq!(sections[2].description, "Goroutine 7 created");
+        assert!(parse_race_output("ok  \texample.com/m\t0.01s\n").is_empty());
+
+        let by_file = race_diagnostics(&races, &root);
+        let _ = std::fs::remove_dir_all(&root);
+        let uri = match tower_lsp::lsp_types::Url::from_file_path(&file) {
+            Ok(uri) => uri,
+            Err(()) => return,
+        };
+        let diagnostics = &by_file[&uri];
+        assert_eq!(diagnostics.len(), 2);
+        // The write's line without its indentation, to the byte column after `ä`'s two bytes.
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(5, 1), Position::new(5, 17))
+        );
+        assert!(diagnostics[0].message.contains("write by goroutine 7"));
+        assert_eq!(diagnostics[1].range.start, Position::new(9, 1));
+        let related = diagnostics[0]
+            .related_information
+            .clone()
+            .unwrap_or_default();
+        assert_eq!(related.len(), 2);
+        assert_eq!(related[0].location.range.start.line, 9);
+        assert!(related[1].message.starts_with("Goroutine 7 created"));
+        // The test harness goroutine has no frame in the workspace and is left out.
+        assert_eq!(by_file.len(), 1);
+
+        let report = RaceDetectorReport {
+            passed: false,
+            races: races.clone(),
+            diagnostics: 2,
+            output: None,
+        };
+        let schema = crate::protocol::protocol_schema();
+        if let Err(e) = validate_schema(
+            &schema["definitions"]["RaceDetectorReport"],
+            &serde_json::json!(report),
+            &schema,
+            "RaceDetectorReport",
+        ) {
+            panic!("{}", e);
+        }
+        let empty: Vec<DataRace> = Vec::new();
+        assert!(race_diagnostics(&empty, &root).is_empty());
+    }
+
+    #[test]
+    fn test_go_build_errors_become_diagnostics_on_the_reported_words() {
+        use crate::compile::{
+            build_args, compiler_diagnostics, parse_build_output, CompilerError, COMPILE_ERROR,
+        };
+        use std::path::{Path, PathBuf};
+
+        let dir = PathBuf::from("/work/m");
+        // Test files are compiled by `go test`, which runs no test; `go build` skips them.
+        assert_eq!(build_args(&dir.join("main.go"))[0], "build");
+        assert_eq!(
+            build_args(&dir.join("main_test.go")),
+            ["test", "-gcflags=-e", "-run", "^$", "-count=1", "."]
+        );
+        let output = "# example.com/m\n./main.go:6:2: undefined: fmt\n./main.go:7:6: cannot use \"x\" (untyped string constant) as int value in assignment\n./util.go:3: missing return\n../lib/lib.go:2:9: syntax error: unexpected newline\n\thave (int)\n\twant (string)\nnote: module requires Go 1.22\n";
+        let errors = parse_build_output(output, &dir);
+        assert_eq!(errors.len(), 4);
+        assert_eq!(errors[0].file, dir.join("main.go"));
+        assert_eq!((errors[0].line, errors[0].column), (6, Some(2)));
+        assert_eq!(errors[0].message, "undefined: fmt");
+        assert_eq!((errors[2].line, errors[2].column), (3, None));
+        assert_eq!(errors[2].message, "missing return");
+        assert_eq!(errors[3].file, dir.join("../lib/lib.go"));
+        assert_eq!(
+            errors[3].message,
+            "syntax error: unexpected newline\nhave (int)\nwant (string)"
+        );
+        assert!(parse_build_output("", &dir).is_empty());
+
+        let main = "package main\n\nfunc main() {\n\tvar n int\n\t_ = n\n\tfmt.Println(\"héllo\")\n\tn = \"x\" // ünicode\n}\n";
+        let util = "package main\n\nfunc f() int {\n}\n";
+        let mut errors = errors[..3].to_vec();
+        // `héllo` inside the string literal, at byte column 15.
+        errors.push(CompilerError {
+            file: dir.join("main.go"),
+            line: 6,
+            column: Some(15),
+            message: "undefined: héllo".to_string(),
+        });
+        let by_file = compiler_diagnostics(&errors, |path: &Path| {
+            match path.file_name().and_then(|name| name.to_str()) {
+                Some("main.go") => Some(main.to_string()),
+                Some("util.go") => Some(util.to_string()),
+                _ => None,
+            }
+        });
+        let uri = |name: &str| match tower_lsp::lsp_types::Url::from_file_path(dir.join(name)) {
+            Ok(uri) => uri,
+            Err(()) => panic!("not a file path"),
+        };
+        let in_main = &by_file[&uri("main.go")];
+        assert_eq!(in_main.len(), 3);
+        // `fmt` at byte column 2.
+        assert_eq!(
+            in_main[0].range,
+            Range::new(Position::new(5, 1), Position::new(5, 4))
+        );
+        assert_eq!(
+            in_main[0].code,
+            Some(tower_lsp::lsp_types::NumberOrString::String(
+                COMPILE_ERROR.to_string()
+            ))
+        );
+        // `"` is not a word character: one character is marked.
+        assert_eq!(
+            in_main[1].range,
+            Range::new(Position::new(6, 5), Position::new(6, 6))
+        );
+        // The word ends after `é`'s two bytes.
+        assert_eq!(
+            in_main[2].range,
+            Range::new(Position::new(5, 14), Position::new(5, 20))
+        );
+        // No column: the whole line without its indentation.
+        assert_eq!(
+            by_file[&uri("util.go")][0].range,
+            Range::new(Position::new(2, 0), Position::new(2, 14))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_saving_again_supersedes_the_running_build_of_the_package() {
+        use crate::compile::PackageBuilds;
+        use std::path::PathBuf;
+        use std::sync::Arc;
+        use std::time::Duration;
+        use tokio::sync::oneshot;
+
+        let builds = Arc::new(PackageBuilds::default());
+        let (package, other) = (PathBuf::from("/work/m"), PathBuf::from("/work/lib"));
+        let start = |dir: &PathBuf, wait: Duration| {
+            let (done, finished) = oneshot::channel();
+            let (task_builds, task_dir) = (builds.clone(), dir.clone());
+            builds.start(dir.clone(), move |generation| async move {
+                tokio::time::sleep(wait).await;
+                let _ = done.send(task_builds.finish(&task_dir, generation));
+            });
+            finished
+        };
+        let first = start(&package, Duration::from_secs(30));
+        let beside = start(&other, Duration::ZERO);
+        let second = start(&package, Duration::ZERO);
+
+        assert_eq!(second.await, Ok(true));
+        // Another package's build is not affected.
+        assert_eq!(beside.await, Ok(true));
+        // The first build was aborted before it finished.
+        assert!(first.await.is_err());
+        // Generation 1, the first build, is no longer the latest of its package.
+        let third = start(&package, Duration::from_secs(30));
+        assert!(!builds.finish(&package, 1));
+        builds.cancel_all();
+        assert!(third.await.is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_semantic_helper_returns_uses_across_the_package() {
+        use crate::bootstrap::BuildConsent;
+        use crate::semantic::{SemanticConfig, SemanticHelper};
+        use std::os::unix::fs::PermissionsExt;
+        use tower_lsp::lsp_types::{Location, Url};
+
+        let dir = std::env::temp_dir().join(format!(
+            "go-analyzer-semantic-package-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::create_dir_all(&dir);
+        let requests = dir.join("requests.log");
+        let other = dir.join("other.go");
+        let helper = dir.join("helper.sh");
+        // Answers that `total`, used once here, is declared and used again in other.go.
+        let script = format!(
+            r#"#!/bin/sh
+{}while IFS= read -r request; do
+  printf '%s\n' "$request" >> '{}'
+  id=$(printf '%s\n' "$request" | sed 's/^{{"jsonrpc":"2.0","id":\([0-9]*\).*/\1/')
+  printf '{{"jsonrpc":"2.0","id":%s,"result":{{"name":"total","decl":{{"start":{{"line":2,"col":4}},"end":{{"line":2,"col":9}}}},"decl_file":"{}","uses":[{{"range":{{"start":{{"line":3,"col":1}},"end":{{"line":3,"col":6}}}},"reassign":true,"captured":false}},{{"file":"{}","range":{{"start":{{"line":5,"col":8}},"end":{{"line":5,"col":13}}}},"reassign":false,"captured":false}}],"is_pointer":false,"type":"int"}}}}\n' "$id"
+done
+"#,
+            helper_handshake(),
+            requests.display(),
+            other.display(),
+            other.display()
+        );
+        if std::fs::write(&helper, script).is_err()
+            || std::fs::set_permissions(&helper, std::fs::Permissions::from_mode(0o755)).is_err()
+        {
+            return;
+        }
+        let helper = SemanticHelper::new(SemanticConfig {
+            enabled: true,
+            helper_path: helper.display().to_string(),
+            timeout_ms: 2000,
+            max_in_flight: 1,
+            coalesce_ms: 0,
+            build: BuildConsent::Never,
+            build_dir: dir.join("build"),
+        });
+        let (uri, other_uri) = match (
+            Url::from_file_path(dir.join("main.go")),
+            Url::from_file_path(&other),
+        ) {
+            (Ok(uri), Ok(other_uri)) => (uri, other_uri),
+            _ => panic!("valid uris"),
+        };
+        let code = "package main\n\nfunc add() {\n\ttotal++\n}\n";
+        let semantic = match helper.resolve(&uri, Position::new(3, 2), code).await {
+            Some(semantic) => semantic,
+            None => panic!("the helper answers"),
+        };
+        helper.stop().await;
+
+        // The request names the package directory.
+        let sent = std::fs::read_to_string(&requests).unwrap_or_default();
+        assert!(sent.contains(&format!("\"dir\":\"{}\"", dir.display())));
+
+        // Uses in this document stay in `uses`; those in other.go join as locations.
+        let here = Range::new(Position::new(3, 1), Position::new(3, 6));
+        assert_eq!(semantic.uses.len(), 1);
+        assert!(semantic.uses[0].reassign);
+        assert_eq!(semantic.info.uses, vec![here]);
+        assert_eq!(
+            semantic.info.package_uses,
+            vec![Location::new(
+                other_uri.clone(),
+                Range::new(Position::new(5, 8), Position::new(5, 13))
+            )]
+        );
+        assert_eq!(semantic.info.declared_in, Some(other_uri));
+
+        let markdown = crate::hover::render_hover(&crate::hover::HoverInput {
+            uri: &uri,
+            code,
+            var_info: &semantic.info,
+            type_name: semantic.type_name.as_deref(),
+            writes: &[true],
+            goroutines: &[],
+            guards: &[None],
+            severity: None,
+        });
+        assert!(markdown.contains("**Declared at**: [other.go line 3]("));
+        assert!(!markdown.contains("```go"));
+        assert!(markdown.contains("**Uses** (2):"));
+        assert!(markdown.contains("#L4,2) write"));
+        assert!(markdown.contains("- [other.go line 6]("));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_did_change_for_an_older_version_is_ignored() {
+        use crate::backend::Backend;
+        use tower_lsp::lsp_types::{
+            DidChangeTextDocumentParams, DidOpenTextDocumentParams, TextDocumentContentChangeEvent,
+            TextDocumentItem, Url, VersionedTextDocumentIdentifier,
+        };
+        use tower_lsp::{LanguageServer, LspService};
+
+        let (service, _socket) = LspService::new(Backend::new);
+        let backend = service.inner();
+        let uri = match Url::parse("file:///tmp/versions.go") {
+            Ok(uri) => uri,
+            Err(_) => panic!("valid uri"),
+        };
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "go".to_string(),
+                    1,
+                    "package one\n".to_string(),
+                ),
+            })
+            .await;
+        let change = |version: i32, text: &str| DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(uri.clone(), version),
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: text.to_string(),
+            }],
+        };
+
+        // Version 2 arrives after version 3, for a buffer the client has already moved past.
+        backend.did_change(change(3, "package three\n")).await;
+        backend.did_change(change(2, "package two\n")).await;
+        assert_eq!(backend.documents.version(&uri), Some(3));
+        assert_eq!(
+            backend.documents.text(&uri).as_deref(),
+            Some("package three\n")
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_hover_reports_content_modified_when_the_version_changes_during_analysis() {
+        use crate::backend::Backend;
+        use crate::bootstrap::BuildConsent;
+        use crate::semantic::{SemanticCoalescer, SemanticConfig};
+        use std::time::Duration;
+        use tower_lsp::lsp_types::{
+            DidChangeTextDocumentParams, DidOpenTextDocumentParams, HoverParams,
+            TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentItem,
+            TextDocumentPositionParams, Url, VersionedTextDocumentIdentifier,
+            WorkDoneProgressParams,
+        };
+        use tower_lsp::{LanguageServer, LspService};
+
+        // Semantic lookups wait out their coalescing window before finding no helper, which
+        // holds the hover long enough for an edit to land.
+        let build_dir = std::env::temp_dir().join("go-analyzer-content-modified-build");
+        let (service, _socket) = LspService::new(move |client| {
+            let mut backend = Backend::new(client);
+            backend.semantic = SemanticCoalescer::new(SemanticConfig {
+                enabled: true,
+                helper_path: build_dir.join("absent-helper").display().to_string(),
+                timeout_ms: 100,
+                max_in_flight: 1,
+                coalesce_ms: 400,
+                build: BuildConsent::Never,
+                build_dir,
+            });
+            backend
+        });
+        let backend = service.inner();
+        let uri = match Url::parse("file:///tmp/modified.go") {
+            Ok(uri) => uri,
+            Err(_) => panic!("valid uri"),
+        };
+        let code = "package main\n\nfunc main() {\n\tcount := 1\n\t_ = count\n}\n";
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "go".to_string(),
+                    1,
+                    code.to_string(),
+                ),
+            })
+            .await;
+        let hover = || {
+            backend.hover(HoverParams {
+                text_document_position_params: TextDocumentPositionParams {
+                    text_document: TextDocumentIdentifier::new(uri.clone()),
+                    position: Position::new(3, 2),
+                },
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            })
+        };
+
+        // Unchanged while analyzed, the hover is answered.
+        assert!(matches!(hover().await, Ok(Some(_))));
+
+        let edit = DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(uri.clone(), 2),
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: format!("// edited\n{}", code),
+            }],
+        };
+        let (answer, _) = futures::join!(hover(), async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            backend.did_change(edit).await;
+        });
+        match answer {
+            Err(error) => assert_eq!(
+                error.code,
+                tower_lsp::jsonrpc::Error::content_modified().code
+            ),
+            Ok(_) => panic!("a hover computed for version 1 was answered after version 2"),
+        }
+    }
+
+    /// A client session with a [`crate::backend::Backend`] over an in-memory stream, for tests
+    /// that watch what the server sends on its own.
+    struct TestClient {
+        write: tokio::io::WriteHalf<tokio::io::DuplexStream>,
+        received: tokio::sync::mpsc::UnboundedReceiver<serde_json::Value>,
+        next_id: i64,
+        /// Notifications received while waiting for a response.
+        notifications: Vec<serde_json::Value>,
+    }
+
+    impl TestClient {
+        /// Starts a session with a backend adjusted by `configure` and initializes it.
+        async fn start(
+            configure: impl FnOnce(&mut crate::backend::Backend) + Send + 'static,
+        ) -> Self {
+            use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+
+            let (service, socket) = tower_lsp::LspService::build(move |client| {
+                let mut backend = crate::backend::Backend::new(client);
+                configure(&mut backend);
+                backend
+            })
+            .finish();
+            let (client, server) = tokio::io::duplex(1 << 20);
+            let (server_read, server_write) = tokio::io::split(server);
+            tokio::spawn(tower_lsp::Server::new(server_read, server_write, socket).serve(service));
+            let (read, write) = tokio::io::split(client);
+            let (sender, received) = tokio::sync::mpsc::unbounded_channel();
+            tokio::spawn(async move {
+                let mut read = tokio::io::BufReader::new(read);
+                loop {
+                    let mut length = 0;
+                    loop {
+                        let mut line = String::new();
+                        if !matches!(read.read_line(&mut line).await, Ok(n) if n > 0) {
+                            return;
+                        }
+                        match line.trim() {
+                            "" => break,
+                            header => {
+                                if let Some(value) = header.strip_prefix("Content-Length:") {
+                                    length = value.trim().parse().unwrap_or_default();
+                                }
+                            }
+                        }
+                    }
+                    let mut body = vec![0; length];
+                    if read.read_exact(&mut body).await.is_err() {
+                        return;
+                    }
+                    if let Ok(message) = serde_json::from_slice(&body) {
+                        let _ = sender.send(message);
+                    }
+                }
+            });
+            let mut client = Self {
+                write,
+                received,
+                next_id: 0,
+                notifications: Vec::new(),
+            };
+            client
+                .request("initialize", serde_json::json!({"capabilities": {}}))
+                .await;
+            client.notify("initialized", serde_json::json!({})).await;
+            client
+        }
+
+        async fn send(&mut self, message: serde_json::Value) {
+            use tokio::io::AsyncWriteExt;
+            let body = message.to_string();
+            let framed = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+            assert!(self.write.write_all(framed.as_bytes()).await.is_ok());
+        }
+
+        async fn notify(&mut self, method: &str, params: serde_json::Value) {
+            self.send(serde_json::json!({"jsonrpc": "2.0", "method": method, "params": params}))
+                .await;
+        }
+
+        /// Sends a request and returns its response.
+        async fn request(&mut self, method: &str, params: serde_json::Value) -> serde_json::Value {
+            self.next_id += 1;
+            let id = self.next_id;
+            self.send(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": method,
+                "params": params
+            }))
+            .await;
+            loop {
+                let message = match self.received.recv().await {
+                    Some(message) => message,
+                    None => panic!("session closed before answering {}", method),
+                };
+                if message.get("method").is_none() && message["id"] == id {
+                    return message;
+                }
+                self.handle(message).await;
+            }
+        }
+
+        /// The params of the `method` notifications received until the server has been quiet
+        /// for `quiet`.
+        async fn notifications(
+            &mut self,
+            method: &str,
+            quiet: std::time::Duration,
+        ) -> Vec<serde_json::Value> {
+            while let Ok(Some(message)) = tokio::time::timeout(quiet, self.received.recv()).await {
+                self.handle(message).await;
+            }
+            let (matching, rest): (Vec<_>, Vec<_>) = std::mem::take(&mut self.notifications)
+                .into_iter()
+                .partition(|notification| notification["method"] == method);
+            self.notifications = rest;
+            matching
+                .into_iter()
+                .map(|notification| notification["params"].clone())
+                .collect()
+        }
+
+        /// Keeps a notification; answers a request from the server with `null`.
+        async fn handle(&mut self, message: serde_json::Value) {
+            if message.get("method").is_none() {
+                return;
+            }
+            match message.get("id").cloned() {
+                Some(id) => {
+                    self.send(serde_json::json!({"jsonrpc": "2.0", "id": id, "result": null}))
+                        .await
+                }
+                None => self.notifications.push(message),
+            }
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_changes_push_decorations_for_the_last_cursor_once_per_burst() {
+        use crate::findings::DecorationPushConfig;
+        use serde_json::json;
+        use std::time::Duration;
+        use tower_lsp::lsp_types::Url;
+
+        let mut client = TestClient::start(|backend| {
+            backend.push = DecorationPushConfig { enabled: true };
+        })
+        .await;
+        let uri = match Url::from_file_path(std::env::temp_dir().join("debounce.go")) {
+            Ok(uri) => uri.to_string(),
+            Err(_) => panic!("valid uri"),
+        };
+        let code = "package main\n\nfunc main() {\n\tfirst := 1\n\tsecond := 2\n\t_ = first\n\t_ = second\n}\n";
+        client
+            .notify(
+                "textDocument/didOpen",
+                json!({"textDocument": {"uri": uri, "languageId": "go", "version": 1, "text": code}}),
+            )
+            .await;
+        let cursor = |line: u32, character: u32| {
+            json!({
+                "command": "goanalyzer/cursor",
+                "arguments": [{
+                    "textDocument": {"uri": uri},
+                    "position": {"line": line, "character": character}
+                }]
+            })
+        };
+        let change = |version: i32, line: u32| {
+            json!({
+                "textDocument": {"uri": uri, "version": version},
+                "contentChanges": [{
+                    "range": {
+                        "start": {"line": line, "character": 0},
+                        "end": {"line": line, "character": 0}
+                    },
+                    "text": "\t// edited\n"
+                }]
+            })
+        };
+        // Each change first pushes the previous set re-anchored and marked stale; the analysis
+        // after the debounce pushes a fresh one.
+        let analyzed = |pushes: Vec<serde_json::Value>| -> Vec<serde_json::Value> {
+            pushes
+                .into_iter()
+                .filter(|push| {
+                    push["decorations"]
+                        .as_array()
+                        .is_some_and(|list| list.iter().any(|d| d["stale"] != true))
+                })
+                .collect()
+        };
+        let declarations = |pushes: &[serde_json::Value], version: i32| -> Vec<String> {
+            pushes
+                .iter()
+                .filter(|push| push["version"] == version)
+                .flat_map(|push| push["decorations"].as_array().cloned().unwrap_or_default())
+                .filter(|decoration| decoration["kind"] == "Declaration")
+                .filter_map(|decoration| decoration["hover_text"].as_str().map(str::to_string))
+                .collect()
+        };
+        let quiet = Duration::from_millis(700);
+
+        // The cursor moves from `first` to `second`; the change is analyzed at `second`.
+        client
+            .request("workspace/executeCommand", cursor(3, 1))
+            .await;
+        client
+            .request("workspace/executeCommand", cursor(4, 1))
+            .await;
+        client.notifications("goanalyzer/decorations", quiet).await;
+        client.notify("textDocument/didChange", change(2, 7)).await;
+        let pushes = analyzed(client.notifications("goanalyzer/decorations", quiet).await);
+        assert_eq!(pushes.len(), 1);
+        assert_eq!(
+            declarations(&pushes, 2),
+            vec!["Declaration of `second`".to_string()]
+        );
+
+        // Of two changes within the debounce window only the later one pushes.
+        client.notify("textDocument/didChange", change(3, 8)).await;
+        client.notify("textDocument/didChange", change(4, 9)).await;
+        let pushes = analyzed(client.notifications("goanalyzer/decorations", quiet).await);
+        assert_eq!(pushes.len(), 1);
+        assert!(declarations(&pushes, 3).is_empty());
+        assert_eq!(
+            declarations(&pushes, 4),
+            vec!["Declaration of `second`".to_string()]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_incremental_reparse_after_a_multi_edit_change_matches_a_full_parse() {
+        use crate::backend::Backend;
+        use crate::util::for_each_node;
+        use tower_lsp::lsp_types::{
+            DidChangeTextDocumentParams, DidOpenTextDocumentParams, TextDocumentContentChangeEvent,
+            TextDocumentItem, Url, VersionedTextDocumentIdentifier,
+        };
+        use tower_lsp::{LanguageServer, LspService};
+
+        let (service, _socket) = LspService::new(Backend::new);
+        let backend = service.inner();
+        let uri = match Url::parse("file:///tmp/reparse.go") {
+            Ok(uri) => uri,
+            Err(_) => panic!("valid uri"),
+        };
+        let code = "package main\n\nimport \"fmt\"\n\nfunc main() {\n\ttotal := 0\n\tfor i := 0; i < 3; i++ {\n\t\ttotal += i\n\t}\n\tfmt.Println(total)\n}\n";
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "go".to_string(),
+                    1,
+                    code.to_string(),
+                ),
+            })
+            .await;
+        assert!(backend
+            .parse_document_with_cache(&uri, &std::sync::Arc::from(code))
+            .await
+            .is_some());
+
+        // Each edit is relative to the text the previous one left: a line inserted, a word
+        // replaced, a span across two lines deleted and a comment appended.
+        let edit =
+            |start: (u32, u32), end: (u32, u32), text: &str| TextDocumentContentChangeEvent {
+                range: Some(Range::new(
+                    Position::new(start.0, start.1),
+                    Position::new(end.0, end.1),
+                )),
+                range_length: None,
+                text: text.to_string(),
+            };
+        backend
+            .did_change(DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier::new(uri.clone(), 2),
+                content_changes: vec![
+                    edit((5, 0), (5, 0), "\tname := \"naïve\"\n"),
+                    edit((8, 2), (8, 7), "count"),
+                    edit((9, 2), (10, 19), ""),
+                    edit((10, 1), (10, 1), "\n// trailing"),
+                ],
+            })
+            .await;
+        let expected = "package main\n\nimport \"fmt\"\n\nfunc main() {\n\tname := \"naïve\"\n\ttotal := 0\n\tfor i := 0; i < 3; i++ {\n\t\tcount += i\n\t}\n}\n// trailing\n";
+        let text = match backend.get_document(&uri).await {
+            Some(text) => text,
+            None => panic!("document is open"),
+        };
+        assert_eq!(&*text, expected);
+
+        let incremental = match backend.parse_document_with_cache(&uri, &text).await {
+            Some(tree) => tree,
+            None => panic!("edited document parses"),
+        };
+        let full = match parse_go(expected) {
+            Ok(tree) => tree,
+            Err(e) => panic!("{}", e),
+        };
+        let nodes = |tree: &tree_sitter::Tree| {
+            let mut nodes = Vec::new();
+            for_each_node(tree.root_node(), |node| {
+                nodes.push((
+                    node.kind(),
+                    node.byte_range(),
+                    node.start_position(),
+                    node.end_position(),
+                ))
+            });
+            nodes
+        };
+        assert!(!incremental.root_node().has_error());
+        assert_eq!(
+            incremental.root_node().to_sexp(),
+            full.root_node().to_sexp()
+        );
+        assert_eq!(nodes(&incremental), nodes(&full));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_code_actions_reuse_the_published_analysis() {
+        use crate::backend::Backend;
+        use tower_lsp::lsp_types::{
+            CodeActionContext, CodeActionOrCommand, CodeActionParams, DidChangeTextDocumentParams,
+            DidOpenTextDocumentParams, PartialResultParams, TextDocumentContentChangeEvent,
+            TextDocumentIdentifier, TextDocumentItem, Url, VersionedTextDocumentIdentifier,
+            WorkDoneProgressParams,
+        };
+        use tower_lsp::{LanguageServer, LspService};
+
+        let code = "package main\n\nimport \"sync\"\n\ntype Counter struct {\n\tmu sync.Mutex\n}\n\nfunc NewCounter() Counter {\n\treturn Counter{}\n}\n";
+        let (service, _socket) = LspService::new(Backend::new);
+        let backend = service.inner();
+        let uri = match Url::parse("file:///tmp/actions.go") {
+            Ok(uri) => uri,
+            Err(_) => panic!("valid uri"),
+        };
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "go".to_string(),
+                    1,
+                    code.to_string(),
+                ),
+            })
+            .await;
+        let analyses = |stats: crate::stats::ServerStats| {
+            stats
+                .timings
+                .iter()
+                .find(|timing| timing.name == "file_diagnostics")
+                .map_or(0, |timing| timing.runs)
+        };
+        let actions_at = |line: u32| CodeActionParams {
+            text_document: TextDocumentIdentifier::new(uri.clone()),
+            range: Range::new(Position::new(line, 5), Position::new(line, 5)),
+            context: CodeActionContext::default(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let titles = |response: tower_lsp::jsonrpc::Result<Option<Vec<CodeActionOrCommand>>>| {
+            response
+                .ok()
+                .flatten()
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|action| match action {
+                    CodeActionOrCommand::CodeAction(action) => Some(action.title),
+                    CodeActionOrCommand::Command(_) => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let opened = analyses(backend.stats().await);
+        assert_eq!(
+            titles(backend.code_action(actions_at(8)).await),
+            vec!["Return `*Counter` instead of `Counter`"]
+        );
+        assert!(titles(backend.code_action(actions_at(1)).await).is_empty());
+        assert_eq!(analyses(backend.stats().await), opened);
+
+        // An edit moves the fixes; a request before the debounced analysis analyzes the new
+        // text itself.
+        let change = backend.did_change(DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(uri.clone(), 2),
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: format!("\n{}", code),
+            }],
+        });
+        let early_request = async {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            backend.code_action(actions_at(9)).await
+        };
+        let ((), actions) = tokio::join!(change, early_request);
+        assert_eq!(
+            titles(actions),
+            vec!["Return `*Counter` instead of `Counter`"]
+        );
+        assert_eq!(analyses(backend.stats().await), opened + 2);
+        assert_eq!(titles(backend.code_action(actions_at(9)).await).len(), 1);
+        assert_eq!(analyses(backend.stats().await), opened + 2);
+    }
 }
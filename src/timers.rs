@@ -0,0 +1,187 @@
+use tower_lsp::lsp_types::{Position, Range};
+use tree_sitter::{Node, Tree};
+
+use crate::analysis::{check_tree, text};
+use crate::callgraph::collect_kind;
+use crate::error::AnalysisResult;
+use crate::util::node_to_range;
+
+/// `case <-time.After(d):` in a `select` inside a `for` loop. Every iteration allocates a new
+/// timer, and a timer whose case did not win stays allocated until it fires, so a loop that spins
+/// on other cases piles up timers for the whole duration.
+#[derive(Debug, Clone)]
+pub struct TimeAfterInLoop {
+    /// The `time.After(d)` call.
+    pub range: Range,
+    /// The `for` keyword of the loop.
+    pub loop_range: Range,
+    pub duration: String,
+    /// Creates a `time.Timer` before the loop and resets it at the top of every iteration, which
+    /// keeps the per-iteration timeout.
+    pub timer_fix: Option<TimerFix>,
+    /// Creates a `time.Ticker` before the loop; the case then fires every `d` regardless of the
+    /// other cases.
+    pub ticker_fix: Option<TimerFix>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TimerFix {
+    pub name: String,
+    pub edits: Vec<(Range, String)>,
+}
+
+impl TimeAfterInLoop {
+    pub fn message(&self) -> String {
+        format!(
+            "`time.After({})` in a loop allocates a new timer on every iteration, and timers whose case is not selected are not released until they fire; create a `time.Timer` or `time.Ticker` once before the loop",
+            self.duration
+        )
+    }
+}
+
+/// `select` cases receiving from `time.After(...)` whose nearest enclosing loop is in the same
+/// function. Fixes are offered when the duration does not use variables declared in the loop and
+/// the loop is not itself repeated by an outer loop, where the hoisted `defer Stop()` would pile
+/// up instead.
+pub fn find_time_after_in_loops(tree: &Tree, code: &str) -> AnalysisResult<Vec<TimeAfterInLoop>> {
+    check_tree(tree, code)?;
+    let mut cases = Vec::new();
+    collect_kind(tree.root_node(), "communication_case", &mut cases);
+    let mut findings = Vec::new();
+    for case in cases {
+        let Some(call) = time_after_receive(case, code) else {
+            continue;
+        };
+        let Some(for_statement) = enclosing_loop(case) else {
+            continue;
+        };
+        let duration = call
+            .child_by_field_name("arguments")
+            .and_then(|arguments| arguments.named_child(0))
+            .map(|argument| text(code, argument).to_string())
+            .unwrap_or_default();
+        let hoistable = !duration.is_empty()
+            && enclosing_loop(for_statement).is_none()
+            && !uses_loop_variables(call, for_statement, code);
+        let fix = |base: &str, reset: bool| {
+            if !hoistable {
+                return None;
+            }
+            let name = unused_name(for_statement, code, base);
+            let indent = line_indent(code, for_statement);
+            let start = node_to_range(for_statement).start;
+            let mut edits = vec![
+                (
+                    Range::new(start, start),
+                    format!(
+                        "{name} := time.New{}({duration})\n{indent}defer {name}.Stop()\n{indent}",
+                        if reset { "Timer" } else { "Ticker" }
+                    ),
+                ),
+                (node_to_range(call), format!("{}.C", name)),
+            ];
+            if reset {
+                let body = for_statement.child_by_field_name("body")?;
+                let first = body.named_child(0)?;
+                let open = node_to_range(body).start;
+                let after_brace = Position::new(open.line, open.character + 1);
+                edits.push((
+                    Range::new(after_brace, after_brace),
+                    format!("\n{}{}.Reset({})", line_indent(code, first), name, duration),
+                ));
+            }
+            Some(TimerFix { name, edits })
+        };
+        findings.push(TimeAfterInLoop {
+            range: node_to_range(call),
+            loop_range: node_to_range(for_statement.child(0).unwrap_or(for_statement)),
+            duration: duration.clone(),
+            timer_fix: fix("timer", true),
+            ticker_fix: fix("ticker", false),
+        });
+    }
+    Ok(findings)
+}
+
+/// The `time.After(...)` call a `select` case receives from.
+fn time_after_receive<'a>(case: Node<'a>, code: &str) -> Option<Node<'a>> {
+    let communication = case.child_by_field_name("communication")?;
+    let mut receives = Vec::new();
+    collect_kind(communication, "unary_expression", &mut receives);
+    let receive = receives
+        .into_iter()
+        .find(|unary| text(code, *unary).starts_with("<-"))?;
+    let call = receive.child_by_field_name("operand")?;
+    let function = call.child_by_field_name("function")?;
+    (call.kind() == "call_expression" && text(code, function) == "time.After").then_some(call)
+}
+
+/// The innermost `for` statement around `node` within its function.
+pub(crate) fn enclosing_loop(node: Node) -> Option<Node> {
+    let mut current = node.parent();
+    while let Some(candidate) = current {
+        match candidate.kind() {
+            "for_statement" => return Some(candidate),
+            "func_literal" | "function_declaration" | "method_declaration" => return None,
+            _ => current = candidate.parent(),
+        }
+    }
+    None
+}
+
+/// The call's arguments name a variable declared by the loop clause or in its body.
+fn uses_loop_variables(call: Node, for_statement: Node, code: &str) -> bool {
+    let mut used = Vec::new();
+    collect_kind(call, "identifier", &mut used);
+    let mut declared = Vec::new();
+    for kind in ["short_var_declaration", "var_spec", "range_clause"] {
+        collect_kind(for_statement, kind, &mut declared);
+    }
+    let mut names = Vec::new();
+    for declaration in declared {
+        let target = match declaration.kind() {
+            "var_spec" => declaration.child_by_field_name("name"),
+            _ => declaration.child_by_field_name("left"),
+        };
+        if let Some(target) = target {
+            collect_kind(target, "identifier", &mut names);
+        }
+    }
+    used.iter().any(|ident| {
+        names
+            .iter()
+            .any(|name| text(code, *name) == text(code, *ident))
+    })
+}
+
+/// `base`, or `base` with a numeric suffix when the enclosing function already uses the name.
+pub(crate) fn unused_name(node: Node, code: &str, base: &str) -> String {
+    let mut scope = node;
+    while let Some(parent) = scope.parent() {
+        if matches!(
+            scope.kind(),
+            "func_literal" | "function_declaration" | "method_declaration"
+        ) {
+            break;
+        }
+        scope = parent;
+    }
+    let mut identifiers = Vec::new();
+    collect_kind(scope, "identifier", &mut identifiers);
+    let taken = |name: &str| identifiers.iter().any(|ident| text(code, *ident) == name);
+    if !taken(base) {
+        return base.to_string();
+    }
+    (2..)
+        .map(|n| format!("{}{}", base, n))
+        .find(|name| !taken(name))
+        .unwrap_or_else(|| base.to_string())
+}
+
+pub(crate) fn line_indent(code: &str, node: Node) -> String {
+    let line_start = code[..node.start_byte()].rfind('\n').map_or(0, |i| i + 1);
+    code[line_start..]
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .collect()
+}
@@ -0,0 +1,81 @@
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use serde_json::json;
+use tower_lsp::jsonrpc;
+use tracing::Span;
+
+use crate::budget::{AnalysisBudget, Deadline};
+use crate::error::AnalysisResult;
+use crate::perf::{PerfParams, PerfRecorder};
+
+static NEXT_TRACE: AtomicU64 = AtomicU64::new(1);
+
+/// Per-request context. Its trace id tags server log lines, progress notifications and error
+/// responses, so a slow or wrong answer in the editor can be matched to the server log.
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    pub trace_id: String,
+    pub method: String,
+    /// The `request` span the server logs this request's events under; closing it when the
+    /// last clone of the context drops logs how long the request took.
+    pub span: Span,
+    /// Measurements for the request's `goanalyzer/perf` report, shared by its clones.
+    pub perf: Arc<PerfRecorder>,
+    started: Instant,
+}
+
+impl RequestContext {
+    pub fn new(method: &str) -> Self {
+        let sequence = NEXT_TRACE.fetch_add(1, Ordering::Relaxed);
+        // The process id keeps ids distinct across server restarts writing to the same log.
+        let trace_id = format!("{:x}-{}", std::process::id(), sequence);
+        Self {
+            span: tracing::info_span!("request", trace = %trace_id, method),
+            trace_id,
+            method: method.to_string(),
+            perf: Arc::default(),
+            started: Instant::now(),
+        }
+    }
+
+    /// When this request's analyses have to stop under `budget`.
+    pub fn deadline(&self, budget: &AnalysisBudget) -> Deadline {
+        budget.deadline(self.started)
+    }
+
+    /// The request's `goanalyzer/perf` report as measured so far.
+    pub fn perf_report(&self) -> PerfParams {
+        self.perf.report(&self.trace_id, &self.method)
+    }
+
+    pub fn elapsed_ms(&self) -> u128 {
+        self.started.elapsed().as_millis()
+    }
+
+    /// `[trace method] message`, for log messages sent to the client.
+    pub fn tag(&self, message: impl Display) -> String {
+        format!("[{} {}] {}", self.trace_id, self.method, message)
+    }
+
+    /// Adds the trace id to an error response's message and `data.trace_id`.
+    pub fn error(&self, mut error: jsonrpc::Error) -> jsonrpc::Error {
+        error.message = format!("{} (trace {})", error.message, self.trace_id).into();
+        let mut data = error.data.take().unwrap_or_else(|| json!({}));
+        if let Some(object) = data.as_object_mut() {
+            object.insert("trace_id".to_string(), json!(self.trace_id));
+        }
+        error.data = Some(data);
+        error
+    }
+
+    /// `result`, logging the error of a failed `pass` under this trace.
+    pub fn logged<T>(&self, pass: &str, result: AnalysisResult<T>) -> AnalysisResult<T> {
+        if let Err(e) = &result {
+            tracing::error!(parent: &self.span, "{} failed: {}", pass, e);
+        }
+        result
+    }
+}
@@ -0,0 +1,236 @@
+use std::any::Any;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tower_lsp::jsonrpc::{Request, Response};
+use tower_lsp::{jsonrpc, ClientSocket, LspService, Server};
+use tower_service::Service;
+
+use crate::backend::Backend;
+
+/// How the language server talks to its client.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Transport {
+    /// stdin/stdout of a process the editor started.
+    #[default]
+    Stdio,
+    /// `--listen <addr>`: a standalone server accepting TCP connections on `addr`, each with its
+    /// own session.
+    Listen(String),
+    /// `--pipe <name>`: connects to the pipe the editor created, a Unix domain socket path or a
+    /// Windows named pipe, as `vscode-languageclient` does for `TransportKind.pipe`.
+    Pipe(String),
+}
+
+impl Transport {
+    /// The transport the arguments ask for; both `--flag value` and `--flag=value` work.
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut transport = Self::Stdio;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) => (flag, Some(value.to_string())),
+                None => (arg.as_str(), None),
+            };
+            let make: fn(String) -> Self = match flag {
+                "--listen" => Self::Listen,
+                "--pipe" => Self::Pipe,
+                _ => continue,
+            };
+            if transport != Self::Stdio {
+                return Err("--listen and --pipe are mutually exclusive".to_string());
+            }
+            let value = inline
+                .or_else(|| args.next().cloned())
+                .filter(|value| !value.is_empty())
+                .ok_or_else(|| format!("{} needs a value", flag))?;
+            transport = make(value);
+        }
+        Ok(transport)
+    }
+}
+
+/// The language service every transport serves.
+pub fn service() -> (LspService<Backend>, ClientSocket) {
+    LspService::build(Backend::new)
+        .custom_method("$/setTrace", Backend::set_trace)
+        .finish()
+}
+
+/// Runs the server over `transport` until the client exits or `stop` is set; a `--listen`
+/// server runs until `stop` is set. Once it is, sessions read no further input: they answer the
+/// requests already read and return, and a `--listen` server waits for all of them.
+pub async fn serve(transport: Transport, stop: watch::Receiver<bool>) -> std::io::Result<()> {
+    match transport {
+        Transport::Stdio => {
+            serve_io(tokio::io::stdin(), tokio::io::stdout(), stop).await;
+            Ok(())
+        }
+        Transport::Listen(addr) => {
+            let listener = TcpListener::bind(&addr).await?;
+            tracing::info!("Listening on {}", listener.local_addr()?);
+            let mut sessions = JoinSet::new();
+            loop {
+                let accepted = tokio::select! {
+                    accepted = listener.accept() => accepted,
+                    _ = stopped(stop.clone()) => break,
+                };
+                let (stream, peer) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        // A failed accept costs that connection, not the server.
+                        tracing::warn!("Failed to accept a connection: {}", e);
+                        tokio::select! {
+                            _ = tokio::time::sleep(accept_backoff(&e)) => continue,
+                            _ = stopped(stop.clone()) => break,
+                        }
+                    }
+                };
+                tracing::info!("Client connected from {}", peer);
+                let stop = stop.clone();
+                sessions.spawn(async move {
+                    serve_stream(stream, stop).await;
+                    tracing::info!("Client {} disconnected", peer);
+                });
+                while sessions.try_join_next().is_some() {}
+            }
+            while sessions.join_next().await.is_some() {}
+            Ok(())
+        }
+        Transport::Pipe(name) => {
+            serve_stream(connect_pipe(&name).await?, stop).await;
+            Ok(())
+        }
+    }
+}
+
+/// How long to wait before accepting again after `error`. A connection the peer dropped before
+/// it was accepted is retried at once; out of file descriptors or memory (`EMFILE`, `ENFILE`,
+/// `ENOMEM`), accepting again right away would fail the same way until a session ends.
+pub fn accept_backoff(error: &std::io::Error) -> Duration {
+    match error.kind() {
+        std::io::ErrorKind::ConnectionAborted
+        | std::io::ErrorKind::ConnectionReset
+        | std::io::ErrorKind::Interrupted => Duration::ZERO,
+        _ => Duration::from_millis(100),
+    }
+}
+
+/// Serves one session over a bidirectional stream, until the client exits or `stop` is set.
+pub async fn serve_stream(stream: impl AsyncRead + AsyncWrite, stop: watch::Receiver<bool>) {
+    let (read, write) = tokio::io::split(stream);
+    serve_io(read, write, stop).await;
+}
+
+async fn serve_io(
+    read: impl AsyncRead + Unpin,
+    write: impl AsyncWrite,
+    stop: watch::Receiver<bool>,
+) {
+    let (service, socket) = service();
+    let read = UntilStopped {
+        read,
+        stopped: stopped(stop).boxed(),
+        done: false,
+    };
+    Server::new(read, write, socket)
+        .serve(PanicGuard(service))
+        .await;
+}
+
+/// Resolves once `stop` is set; never when its sender is dropped without setting it.
+async fn stopped(mut stop: watch::Receiver<bool>) {
+    let set = stop.wait_for(|stop| *stop).await.is_ok();
+    if !set {
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Input that ends, as if the client had closed it, once `stopped` resolves. The server then
+/// answers the requests it has already read before returning.
+struct UntilStopped<R> {
+    read: R,
+    stopped: BoxFuture<'static, ()>,
+    done: bool,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for UntilStopped<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        if !self.done && self.stopped.poll_unpin(cx).is_ready() {
+            self.done = true;
+        }
+        if self.done {
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut self.read).poll_read(cx, buf)
+    }
+}
+
+/// Answers a message whose handler panicked with an internal error instead of letting the panic
+/// end the session. A panic is a bug in the server; this is the one place it is caught.
+pub struct PanicGuard<S>(pub S);
+
+impl<S> Service<Request> for PanicGuard<S>
+where
+    S: Service<Request, Response = Option<Response>>,
+    S::Future: Send + 'static,
+{
+    type Response = Option<Response>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let id = request.id().cloned();
+        let method = request.method().to_string();
+        let response = AssertUnwindSafe(self.0.call(request)).catch_unwind();
+        Box::pin(async move {
+            response.await.unwrap_or_else(|panic| {
+                tracing::error!("{} panicked: {}", method, panic_message(panic.as_ref()));
+                let mut error = jsonrpc::Error::internal_error();
+                error.message = format!("{} failed on a server bug", method).into();
+                Ok(id.map(|id| Response::from_error(id, error)))
+            })
+        })
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+#[cfg(unix)]
+async fn connect_pipe(name: &str) -> std::io::Result<tokio::net::UnixStream> {
+    tokio::net::UnixStream::connect(name).await
+}
+
+#[cfg(windows)]
+async fn connect_pipe(
+    name: &str,
+) -> std::io::Result<tokio::net::windows::named_pipe::NamedPipeClient> {
+    let path = if name.starts_with(r"\\.\pipe\") {
+        name.to_string()
+    } else {
+        format!(r"\\.\pipe\{}", name)
+    };
+    tokio::net::windows::named_pipe::ClientOptions::new().open(path)
+}
@@ -1,10 +1,63 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tower_lsp::lsp_types::Range;
+use tower_lsp::lsp_types::{Location, Position, Range, TextDocumentIdentifier, Url};
 
 pub struct ProgressNotification;
 impl tower_lsp::lsp_types::notification::Notification for ProgressNotification {
     const METHOD: &'static str = "goanalyzer/progress";
-    type Params = String;
+    type Params = ProgressParams;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct ProgressParams {
+    pub message: String,
+    /// Trace id of the request that reported the progress.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+}
+
+pub struct IndexingStatusNotification;
+impl tower_lsp::lsp_types::notification::Notification for IndexingStatusNotification {
+    const METHOD: &'static str = "goanalyzer/indexingStatus";
+    type Params = IndexingStatusParams;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct IndexingStatusParams {
+    pub uri: String,
+    pub variables: usize,
+    pub functions: usize,
+    pub channels: usize,
+    pub goroutines: usize,
+}
+
+pub struct ParseInfoNotification;
+impl tower_lsp::lsp_types::notification::Notification for ParseInfoNotification {
+    const METHOD: &'static str = "goanalyzer/parseInfo";
+    type Params = ParseInfoParams;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct ParseInfoParams {
+    pub uri: String,
+    pub source: Option<String>,
+    pub cache_hit: bool,
+    pub parse_ms: Option<u128>,
+    pub code_len: usize,
+}
+
+/// Arguments of the `goanalyzer/cursor` command.
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct CursorCommandParams {
+    #[serde(rename = "textDocument")]
+    #[schemars(with = "crate::protocol::lsp::TextDocumentIdentifier")]
+    pub text_document: TextDocumentIdentifier,
+    #[schemars(with = "crate::protocol::lsp::Position")]
+    pub position: Position,
+    pub source: Option<String>,
+    pub dump_json: Option<bool>,
+    pub delta: Option<bool>,
+    pub version: Option<i32>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -16,9 +69,17 @@ pub struct VariableInfo {
     pub potential_race: bool,
     pub race_severity: RaceSeverity,
     pub var_id: VarId,
+    /// The file `declaration` is in, when the semantic helper found it in another file of the
+    /// package than the document analyzed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub declared_in: Option<Url>,
+    /// Uses in the package's other files, found by the semantic helper; `uses` holds those in
+    /// the document analyzed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub package_uses: Vec<Location>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
 pub enum DecorationType {
     Declaration,
     Use,
@@ -36,23 +97,33 @@ pub enum RaceSeverity {
     Low,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct Decoration {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+    #[schemars(with = "crate::protocol::lsp::Range")]
     pub range: Range,
     pub kind: DecorationType,
     pub hover_text: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub diagnostic: Option<DecorationDiagnostic>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<ByteRange>,
+    /// Shifted by edits since it was computed; replaced once re-analysis completes.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stale: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct DecorationDiagnostic {
     pub severity: DecorationDiagnosticSeverity,
     pub code: String,
     pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub href: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
 pub enum DecorationDiagnosticSeverity {
     Error,
     Warning,
@@ -60,6 +131,22 @@ pub enum DecorationDiagnosticSeverity {
     Hint,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GoroutineType {
+    AnonymousClosure,
+    NamedFunction,
+    MethodCall,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GoroutineUsage {
+    pub launch: Range,
+    pub goroutine_type: GoroutineType,
+    pub reads: usize,
+    pub writes: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct EntityCount {
     pub variables: usize,
     pub functions: usize,
@@ -73,6 +160,14 @@ pub struct VarId {
     pub end_byte: usize,
 }
 
+/// Byte offsets of a range in the analyzed document, sent next to the line/character range so
+/// clients can address an entity independently of the position encoding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct ByteRange {
+    pub start_byte: usize,
+    pub end_byte: usize,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CursorContext {
     pub target_node_kind: String,
@@ -127,16 +222,25 @@ pub const ATOMIC_FUNCS: &[&str] = &[
     "StoreUint64",
 ];
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, JsonSchema)]
 pub enum GraphEntityType {
     Variable,
     Function,
     Channel,
     Goroutine,
     SyncBlock,
+    /// A variable or struct field holding a `sync.Map`.
+    SyncMap,
+    Struct,
+    Interface,
+    Method,
+    /// A directory of Go files in a workspace graph.
+    Package,
+    /// A `select` statement.
+    Select,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, JsonSchema)]
 pub enum GraphEdgeType {
     Use,
     Call,
@@ -144,26 +248,54 @@ pub enum GraphEdgeType {
     Receive,
     Spawn,
     Sync,
+    Close,
+    /// From a mutex to a variable used while it is held.
+    Guards,
+    /// From a goroutine or function to a mutex it locks.
+    Acquires,
+    /// From a struct to an interface whose methods it declares.
+    Implements,
+    /// From a struct to a method declared on it.
+    HasMethod,
+    /// From a package to a package it imports.
+    Imports,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct GraphNode {
     pub id: String,
     pub label: String,
     pub entity_type: GraphEntityType,
+    #[schemars(with = "crate::protocol::lsp::Range")]
     pub range: Range,
     pub extra: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<ByteRange>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+impl GraphNode {
+    /// The race analysis marked the node `potential_race`.
+    pub fn is_racy(&self) -> bool {
+        self.extra
+            .as_ref()
+            .and_then(|extra| extra["potential_race"].as_bool())
+            .unwrap_or(false)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, JsonSchema)]
 pub struct GraphEdge {
     pub from: String,
     pub to: String,
     pub edge_type: GraphEdgeType,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, JsonSchema)]
 pub struct GraphData {
     pub nodes: Vec<GraphNode>,
     pub edges: Vec<GraphEdge>,
+    /// Set when building the graph ran out of its time budget and it holds only what was found
+    /// by then.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
 }
@@ -1,6 +1,93 @@
 use tower_lsp::lsp_types::{Position, Range};
 use tree_sitter::Node;
 
+use crate::types::ByteRange;
+
+/// Deepest nesting [`walk_tree`] descends into. Generated Go files can nest expressions
+/// thousands of levels deep; subtrees below this depth are skipped instead.
+pub const MAX_TRAVERSAL_DEPTH: usize = 4096;
+
+/// A step of [`walk_tree`].
+pub enum Visit<'t> {
+    Enter(Node<'t>),
+    Leave(Node<'t>),
+}
+
+/// Walks `root` in preorder with a tree cursor instead of recursion, so deep trees cannot
+/// overflow the stack. `visit` sees every node when entering it and again when leaving it, after
+/// its children; returning `false` ends the walk.
+pub fn walk_tree<'t>(root: Node<'t>, mut visit: impl FnMut(Visit<'t>) -> bool) {
+    let mut cursor = root.walk();
+    let mut depth = 0;
+    loop {
+        if !visit(Visit::Enter(cursor.node())) {
+            return;
+        }
+        if depth < MAX_TRAVERSAL_DEPTH && cursor.goto_first_child() {
+            depth += 1;
+            continue;
+        }
+        loop {
+            if !visit(Visit::Leave(cursor.node())) || depth == 0 {
+                return;
+            }
+            if cursor.goto_next_sibling() {
+                break;
+            }
+            cursor.goto_parent();
+            depth -= 1;
+        }
+    }
+}
+
+/// The first node of `root`, in preorder, that `matches`; see [`walk_tree`].
+pub fn find_node<'t>(
+    root: Node<'t>,
+    mut matches: impl FnMut(Node<'t>) -> bool,
+) -> Option<Node<'t>> {
+    let mut found = None;
+    walk_tree(root, |visit| match visit {
+        Visit::Enter(node) if matches(node) => {
+            found = Some(node);
+            false
+        }
+        _ => true,
+    });
+    found
+}
+
+/// Calls `enter` on every node of `root` in preorder; see [`walk_tree`].
+pub fn for_each_node<'t>(root: Node<'t>, mut enter: impl FnMut(Node<'t>)) {
+    walk_tree(root, |visit| {
+        if let Visit::Enter(node) = visit {
+            enter(node);
+        }
+        true
+    });
+}
+
+/// Like [`for_each_node`], but skips the children of every node `enter` returns `false` for.
+pub fn for_each_node_pruned<'t>(root: Node<'t>, mut enter: impl FnMut(Node<'t>) -> bool) {
+    let mut cursor = root.walk();
+    let mut depth = 0;
+    loop {
+        if enter(cursor.node()) && depth < MAX_TRAVERSAL_DEPTH && cursor.goto_first_child() {
+            depth += 1;
+            continue;
+        }
+        loop {
+            if depth == 0 {
+                return;
+            }
+            if cursor.goto_next_sibling() {
+                break;
+            }
+            cursor.goto_parent();
+            depth -= 1;
+        }
+    }
+}
+
 pub fn node_to_range(node: Node) -> Range {
     Range {
         start: Position::new(
@@ -13,3 +100,35 @@ pub fn node_to_range(node: Node) -> Range {
         ),
     }
 }
+
+/// Line start offsets of a document, for converting ranges to [`ByteRange`]s. Characters in
+/// ranges are byte columns, as in tree-sitter points.
+pub struct LineIndex {
+    starts: Vec<usize>,
+    len: usize,
+}
+
+impl LineIndex {
+    pub fn new(code: &str) -> Self {
+        let mut starts = vec![0];
+        starts.extend(code.match_indices('\n').map(|(i, _)| i + 1));
+        Self {
+            starts,
+            len: code.len(),
+        }
+    }
+
+    pub fn offset(&self, position: Position) -> usize {
+        match self.starts.get(position.line as usize) {
+            Some(start) => (start + position.character as usize).min(self.len),
+            None => self.len,
+        }
+    }
+
+    pub fn bytes(&self, range: Range) -> ByteRange {
+        ByteRange {
+            start_byte: self.offset(range.start),
+            end_byte: self.offset(range.end),
+        }
+    }
+}
@@ -0,0 +1,370 @@
+use std::collections::HashSet;
+
+use tower_lsp::lsp_types::Range;
+use tree_sitter::{Node, Tree};
+
+use crate::analysis::{check_tree, text};
+use crate::error::AnalysisResult;
+use crate::util::{for_each_node, node_to_range};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WaitGroupIssueKind {
+    /// Literal `Add` total differs from the number of `Done` calls.
+    AddDoneMismatch { added: i64, done: usize },
+    /// `Add` runs inside the goroutine it is meant to account for, racing with `Wait`.
+    AddInGoroutine,
+    /// `Wait` blocks on a counter that no `Done` can bring back to zero.
+    WaitUnsatisfiable,
+}
+
+#[derive(Debug, Clone)]
+pub struct WaitGroupIssue {
+    pub kind: WaitGroupIssueKind,
+    pub waitgroup: String,
+    pub range: Range,
+}
+
+impl WaitGroupIssue {
+    pub fn code(&self) -> &'static str {
+        match self.kind {
+            WaitGroupIssueKind::AddDoneMismatch { .. } => "waitgroup-add-done-mismatch",
+            WaitGroupIssueKind::AddInGoroutine => "waitgroup-add-in-goroutine",
+            WaitGroupIssueKind::WaitUnsatisfiable => "waitgroup-wait-unsatisfiable",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match &self.kind {
+            WaitGroupIssueKind::AddDoneMismatch { added, done } => format!(
+                "`{}.Add` adds {} but `Done` is called {} time(s)",
+                self.waitgroup, added, done
+            ),
+            WaitGroupIssueKind::AddInGoroutine => format!(
+                "`{}.Add` runs inside the goroutine it guards; `Wait` may return before it executes",
+                self.waitgroup
+            ),
+            WaitGroupIssueKind::WaitUnsatisfiable => format!(
+                "`{}.Wait` can never return: nothing calls `Done`",
+                self.waitgroup
+            ),
+        }
+    }
+}
+
+struct WaitGroupCall<'a> {
+    method: &'a str,
+    call: Node<'a>,
+    /// Literal argument of `Add`; `None` for computed deltas.
+    delta: Option<i64>,
+    /// The `go` statement running the call, if it is spawned from the analyzed function.
+    goroutine: Option<usize>,
+    /// Innermost loop around the call within the analyzed function.
+    lp: Option<usize>,
+}
+
+/// Checks `Add`/`Done`/`Wait` usage of each `sync.WaitGroup` within every function and method.
+pub fn analyze_waitgroups(tree: &Tree, code: &str) -> AnalysisResult<Vec<WaitGroupIssue>> {
+    check_tree(tree, code)?;
+    let root = tree.root_node();
+    let names = waitgroup_names(root, code);
+    if names.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut issues = Vec::new();
+    let mut cursor = root.walk();
+    for function in root.named_children(&mut cursor) {
+        if !matches!(
+            function.kind(),
+            "function_declaration" | "method_declaration"
+        ) {
+            continue;
+        }
+        let Some(body) = function.child_by_field_name("body") else {
+            continue;
+        };
+        let mut calls = Vec::new();
+        collect_waitgroup_calls(body, body, code, &names, &mut calls);
+        let mut groups: Vec<&str> = calls.iter().map(|c| receiver_text(c.call, code)).collect();
+        groups.sort_unstable();
+        groups.dedup();
+        for group in groups {
+            let group_calls: Vec<&WaitGroupCall> = calls
+                .iter()
+                .filter(|c| receiver_text(c.call, code) == group)
+                .collect();
+            analyze_group(body, code, group, &group_calls, &mut issues);
+        }
+    }
+    Ok(issues)
+}
+
+fn analyze_group(
+    body: Node,
+    code: &str,
+    waitgroup: &str,
+    calls: &[&WaitGroupCall],
+    issues: &mut Vec<WaitGroupIssue>,
+) {
+    let adds: Vec<&&WaitGroupCall> = calls.iter().filter(|c| c.method == "Add").collect();
+    let dones: Vec<&&WaitGroupCall> = calls.iter().filter(|c| c.method == "Done").collect();
+    let wait = calls.iter().find(|c| c.method == "Wait");
+
+    for add in &adds {
+        let Some(goroutine) = add.goroutine else {
+            continue;
+        };
+        let guarded = dones.iter().any(|done| done.goroutine == Some(goroutine));
+        if guarded
+            && wait
+                .map(|w| w.goroutine != Some(goroutine))
+                .unwrap_or(false)
+        {
+            issues.push(WaitGroupIssue {
+                kind: WaitGroupIssueKind::AddInGoroutine,
+                waitgroup: waitgroup.to_string(),
+                range: node_to_range(add.call),
+            });
+        }
+    }
+
+    // Only a WaitGroup that is declared here and never handed to another function has all of
+    // its Add/Done calls in view.
+    if !is_declared_in(body, code, waitgroup) || escapes(body, code, waitgroup) {
+        return;
+    }
+    if let Some(wait) = wait {
+        if !adds.is_empty() && dones.is_empty() {
+            issues.push(WaitGroupIssue {
+                kind: WaitGroupIssueKind::WaitUnsatisfiable,
+                waitgroup: waitgroup.to_string(),
+                range: node_to_range(wait.call),
+            });
+            return;
+        }
+    }
+    let single_loop_context = calls
+        .iter()
+        .filter(|c| c.method != "Wait")
+        .map(|c| c.lp)
+        .collect::<HashSet<_>>()
+        .len()
+        <= 1;
+    let literal_total: Option<i64> = adds.iter().map(|add| add.delta).sum();
+    if let (true, Some(added)) = (single_loop_context, literal_total) {
+        if !adds.is_empty() && added != dones.len() as i64 {
+            let anchor = wait.map(|w| w.call).unwrap_or(adds[0].call);
+            issues.push(WaitGroupIssue {
+                kind: WaitGroupIssueKind::AddDoneMismatch {
+                    added,
+                    done: dones.len(),
+                },
+                waitgroup: waitgroup.to_string(),
+                range: node_to_range(anchor),
+            });
+        }
+    }
+}
+
+/// Names of variables, parameters and fields typed `sync.WaitGroup` / `*sync.WaitGroup`.
+fn waitgroup_names(root: Node, code: &str) -> HashSet<String> {
+    let mut names = HashSet::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        match node.kind() {
+            "var_spec" | "parameter_declaration" | "field_declaration" => {
+                let is_waitgroup = node
+                    .child_by_field_name("type")
+                    .map(|t| is_waitgroup_type(text(code, t)))
+                    .unwrap_or(false)
+                    || node
+                        .child_by_field_name("value")
+                        .map(|v| is_waitgroup_value(text(code, v)))
+                        .unwrap_or(false);
+                if is_waitgroup {
+                    let mut cursor = node.walk();
+                    for name in node.children_by_field_name("name", &mut cursor) {
+                        names.insert(text(code, name).to_string());
+                    }
+                }
+            }
+            "short_var_declaration" => {
+                if let (Some(left), Some(right)) = (
+                    node.child_by_field_name("left"),
+                    node.child_by_field_name("right"),
+                ) {
+                    for i in 0..left.named_child_count() {
+                        if let (Some(name), Some(value)) =
+                            (left.named_child(i), right.named_child(i))
+                        {
+                            if is_waitgroup_value(text(code, value)) {
+                                names.insert(text(code, name).to_string());
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            stack.push(child);
+        }
+    }
+    names
+}
+
+fn is_waitgroup_type(type_text: &str) -> bool {
+    matches!(type_text.trim_start_matches('*'), "sync.WaitGroup")
+}
+
+fn is_waitgroup_value(value_text: &str) -> bool {
+    let value = value_text.trim().trim_start_matches('&');
+    value.starts_with("sync.WaitGroup{") || value == "new(sync.WaitGroup)"
+}
+
+fn collect_waitgroup_calls<'a>(
+    node: Node<'a>,
+    body: Node<'a>,
+    code: &'a str,
+    names: &HashSet<String>,
+    out: &mut Vec<WaitGroupCall<'a>>,
+) {
+    for_each_node(node, |node| {
+        if node.kind() != "call_expression" {
+            return;
+        }
+        let Some(function) = node
+            .child_by_field_name("function")
+            .filter(|f| f.kind() == "selector_expression")
+        else {
+            return;
+        };
+        let method = function
+            .child_by_field_name("field")
+            .map(|f| text(code, f))
+            .unwrap_or("");
+        let operand = function.child_by_field_name("operand");
+        let last_name = operand.map(|o| match o.kind() {
+            "selector_expression" => o
+                .child_by_field_name("field")
+                .map(|f| text(code, f))
+                .unwrap_or(""),
+            _ => text(code, o),
+        });
+        if matches!(method, "Add" | "Done" | "Wait")
+            && last_name.map(|n| names.contains(n)).unwrap_or(false)
+        {
+            let delta = if method == "Add" {
+                node.child_by_field_name("arguments")
+                    .and_then(|args| args.named_child(0))
+                    .filter(|arg| arg.kind() == "int_literal")
+                    .and_then(|arg| text(code, arg).parse::<i64>().ok())
+            } else {
+                None
+            };
+            let (goroutine, lp) = call_context(node, body);
+            out.push(WaitGroupCall {
+                method,
+                call: node,
+                delta,
+                goroutine,
+                lp,
+            });
+        }
+    });
+}
+
+/// Innermost spawning `go` statement and innermost loop between `node` and `body`.
+fn call_context(node: Node, body: Node) -> (Option<usize>, Option<usize>) {
+    let mut goroutine = None;
+    let mut lp = None;
+    let mut current = node.parent();
+    while let Some(candidate) = current {
+        if candidate.id() == body.id() {
+            break;
+        }
+        match candidate.kind() {
+            "go_statement" if goroutine.is_none() => goroutine = Some(candidate.id()),
+            "for_statement" if lp.is_none() && goroutine.is_none() => lp = Some(candidate.id()),
+            _ => {}
+        }
+        current = candidate.parent();
+    }
+    (goroutine, lp)
+}
+
+fn receiver_text<'a>(call: Node, code: &'a str) -> &'a str {
+    call.child_by_field_name("function")
+        .and_then(|f| f.child_by_field_name("operand"))
+        .map(|o| text(code, o))
+        .unwrap_or("")
+}
+
+fn is_declared_in(body: Node, code: &str, waitgroup: &str) -> bool {
+    let mut stack = vec![body];
+    while let Some(node) = stack.pop() {
+        match node.kind() {
+            "var_spec" => {
+                let mut cursor = node.walk();
+                if node
+                    .children_by_field_name("name", &mut cursor)
+                    .any(|name| text(code, name) == waitgroup)
+                {
+                    return true;
+                }
+            }
+            "short_var_declaration" => {
+                if let Some(left) = node.child_by_field_name("left") {
+                    let mut cursor = left.walk();
+                    if left
+                        .named_children(&mut cursor)
+                        .any(|name| text(code, name) == waitgroup)
+                    {
+                        return true;
+                    }
+                }
+            }
+            _ => {}
+        }
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            stack.push(child);
+        }
+    }
+    false
+}
+
+/// The WaitGroup is passed to a call (`worker(&wg)`) or stored elsewhere.
+fn escapes(body: Node, code: &str, waitgroup: &str) -> bool {
+    let mut stack = vec![body];
+    while let Some(node) = stack.pop() {
+        if node.kind() == "identifier" && text(code, node) == waitgroup {
+            let mut parent = node.parent();
+            if parent
+                .map(|p| p.kind() == "unary_expression")
+                .unwrap_or(false)
+            {
+                parent = parent.and_then(|p| p.parent());
+            }
+            if let Some(parent) = parent {
+                let declared_or_assigned = parent
+                    .parent()
+                    .and_then(|p| p.child_by_field_name("left"))
+                    .map(|left| left.id() == parent.id())
+                    .unwrap_or(false);
+                if matches!(
+                    parent.kind(),
+                    "argument_list" | "expression_list" | "keyed_element" | "literal_value"
+                ) && !declared_or_assigned
+                {
+                    return true;
+                }
+            }
+        }
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            stack.push(child);
+        }
+    }
+    false
+}
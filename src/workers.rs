@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use futures::future::join_all;
+use tokio::sync::Semaphore;
+
+/// Where CPU-bound analysis runs: tree traversals happen on the runtime's blocking threads, off
+/// the LSP event loop, at most `parallelism` at once across every request and file
+/// (`GO_ANALYZER_WORKERS`, default: the number of cores).
+#[derive(Clone, Debug)]
+pub struct WorkerPool {
+    pub parallelism: usize,
+    slots: Arc<Semaphore>,
+}
+
+impl WorkerPool {
+    pub fn new(parallelism: usize) -> Self {
+        let parallelism = parallelism.max(1);
+        Self {
+            parallelism,
+            slots: Arc::new(Semaphore::new(parallelism)),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let parallelism = std::env::var("GO_ANALYZER_WORKERS")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+        Self::new(parallelism)
+    }
+
+    /// Runs `work` on a blocking thread once one of the pool's slots is free. The caller only
+    /// awaits, so a long traversal never stalls the event loop; a panic in `work` resumes in the
+    /// caller.
+    pub async fn run<T>(&self, work: impl FnOnce() -> T + Send + 'static) -> T
+    where
+        T: Send + 'static,
+    {
+        // The semaphore is never closed, so a slot always comes.
+        let slot = self.slots.clone().acquire_owned().await.ok();
+        let task = tokio::task::spawn_blocking(move || {
+            let _slot = slot;
+            work()
+        });
+        match task.await {
+            Ok(output) => output,
+            Err(e) => std::panic::resume_unwind(
+                e.try_into_panic()
+                    .unwrap_or_else(|e| Box::new(e.to_string())),
+            ),
+        }
+    }
+
+    /// `f` applied to every item, up to `parallelism` at a time, in the order of `items`.
+    pub async fn map<I, O, F>(&self, items: Vec<I>, f: F) -> Vec<O>
+    where
+        I: Send + 'static,
+        O: Send + 'static,
+        F: Fn(I) -> O + Send + Sync + 'static,
+    {
+        let f = Arc::new(f);
+        join_all(items.into_iter().map(|item| {
+            let f = f.clone();
+            self.run(move || f(item))
+        }))
+        .await
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tower_lsp::lsp_types::{Range, Url};
+use tree_sitter::{Node, Tree};
+
+use crate::analysis::{build_file_graph_within, graph_id, text, ExternalSymbols};
+use crate::budget::Deadline;
+use crate::callgraph::collect_kind;
+use crate::interchange::GraphFormat;
+use crate::types::{GraphData, GraphEdge, GraphEdgeType, GraphEntityType, GraphNode};
+use crate::util::node_to_range;
+
+/// Which files `goanalyzer/graph` covers.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GraphScope {
+    /// The document alone.
+    #[default]
+    File,
+    /// Every Go file in the document's directory.
+    Package,
+    /// Every Go file under the workspace folders.
+    Workspace,
+}
+
+/// Arguments of `goanalyzer/graph` and `goanalyzer/graph.export`. Without `scope` this is a
+/// plain `TextDocumentIdentifier`.
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct GraphParams {
+    pub uri: Url,
+    #[serde(default)]
+    pub scope: GraphScope,
+    /// Output format of `goanalyzer/graph.export`; ignored by `goanalyzer/graph`.
+    #[serde(default)]
+    pub format: GraphFormat,
+}
+
+/// A parsed file taking part in a workspace graph.
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub code: String,
+    pub tree: Tree,
+}
+
+/// Package-level functions and variables of one file, by name and graph id.
+#[derive(Default)]
+struct FileDeclarations {
+    functions: HashMap<String, String>,
+    variables: HashMap<String, String>,
+}
+
+/// Merges the graphs of `files` into one. Node ids start with the file path and every node's
+/// `extra` carries its `file`. Calls to package-level functions declared in another file of
+/// the same directory, or in an imported package among `files`, point at the function's node,
+/// and uses of package-level variables declared in another file get a `Use` edge from the
+/// declaration. Each directory also gets a `Package` node, linked by `Imports` edges to the
+/// packages its files import; see [`add_packages`].
+pub fn build_workspace_graph(files: &[SourceFile]) -> GraphData {
+    build_workspace_graph_within(files, Deadline::NONE)
+}
+
+/// [`build_workspace_graph`] that stops at `deadline`: the files not reached by then are left
+/// out and the graph is marked `truncated`.
+pub fn build_workspace_graph_within(files: &[SourceFile], deadline: Deadline) -> GraphData {
+    let mut package_names: HashMap<&Path, String> = HashMap::new();
+    let declarations: Vec<FileDeclarations> = files
+        .iter()
+        .map(|file| {
+            let path = file.path.display().to_string();
+            let mut declared = FileDeclarations::default();
+            let root = file.tree.root_node();
+            let mut cursor = root.walk();
+            for decl in root.named_children(&mut cursor) {
+                match decl.kind() {
+                    "package_clause" => {
+                        if let Some(name) = decl.named_child(0) {
+                            package_names
+                                .entry(package_dir(file))
+                                .or_insert_with(|| text(&file.code, name).to_string());
+                        }
+                    }
+                    "function_declaration" => {
+                        if let Some(ident) = decl.child_by_field_name("name") {
+                            let name = text(&file.code, ident);
+                            declared
+                                .functions
+                                .insert(name.to_string(), graph_id(&path, "", "fn", name, 0));
+                        }
+                    }
+                    "var_declaration" => {
+                        let mut specs = Vec::new();
+                        collect_kind(decl, "var_spec", &mut specs);
+                        for spec in specs {
+                            let mut names = spec.walk();
+                            for ident in spec.children_by_field_name("name", &mut names) {
+                                let name = text(&file.code, ident);
+                                declared
+                                    .variables
+                                    .insert(name.to_string(), graph_id(&path, "", "var", name, 0));
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            declared
+        })
+        .collect();
+
+    let mut graph = GraphData::default();
+    let mut concurrency: HashMap<&Path, (usize, usize)> = HashMap::new();
+    for (index, file) in files.iter().enumerate() {
+        if deadline.expired() {
+            graph.truncated = true;
+            break;
+        }
+        let dir = package_dir(file);
+        let mut external = ExternalSymbols::default();
+        for (other_index, (other, declared)) in files.iter().zip(&declarations).enumerate() {
+            if other_index != index && package_dir(other) == dir {
+                external.functions.extend(declared.functions.clone());
+                external.variables.extend(declared.variables.clone());
+            }
+        }
+        for (alias, path) in imports(file.tree.root_node(), &file.code) {
+            for (other, declared) in files.iter().zip(&declarations) {
+                let other_dir = package_dir(other);
+                if other_dir == dir || !imported_package(other_dir, &package_names, &path) {
+                    continue;
+                }
+                for (name, id) in &declared.functions {
+                    if name.starts_with(char::is_uppercase) {
+                        external
+                            .functions
+                            .insert(format!("{}.{}", alias, name), id.clone());
+                    }
+                }
+            }
+        }
+        let path = file.path.display().to_string();
+        let file_graph =
+            build_file_graph_within(&file.tree, &file.code, &path, &external, deadline);
+        graph.truncated |= file_graph.truncated;
+        let counts = concurrency.entry(dir).or_default();
+        for node in &file_graph.nodes {
+            match node.entity_type {
+                GraphEntityType::Goroutine => counts.0 += 1,
+                GraphEntityType::Channel => counts.1 += 1,
+                _ => {}
+            }
+        }
+        graph
+            .nodes
+            .extend(file_graph.nodes.into_iter().map(|mut node| {
+                node.extra = Some(with_file(node.extra.take(), &path));
+                node
+            }));
+        graph.edges.extend(file_graph.edges);
+    }
+    add_packages(files, &package_names, &concurrency, &mut graph);
+    graph
+}
+
+/// Adds a `Package` node per directory of `files`, with the goroutines and channels created
+/// in it, and an `Imports` edge for every package its files import. Imports of packages
+/// outside `files` point at a `Package` node marked `external`, named by import path.
+fn add_packages(
+    files: &[SourceFile],
+    package_names: &HashMap<&Path, String>,
+    concurrency: &HashMap<&Path, (usize, usize)>,
+    graph: &mut GraphData,
+) {
+    let package_id = |dir: &Path| {
+        let name = package_names.get(dir).map_or("", String::as_str);
+        graph_id(&dir.display().to_string(), "", "pkg", name, 0)
+    };
+    let mut known: HashSet<String> = HashSet::new();
+    let mut imports_seen: HashSet<(String, String)> = HashSet::new();
+    for file in files {
+        let dir = package_dir(file);
+        let id = package_id(dir);
+        if known.insert(id.clone()) {
+            let (goroutines, channels) = concurrency.get(dir).copied().unwrap_or_default();
+            let root = file.tree.root_node();
+            let mut cursor = root.walk();
+            let clause = root
+                .named_children(&mut cursor)
+                .find(|child| child.kind() == "package_clause")
+                .unwrap_or(root);
+            graph.nodes.push(GraphNode {
+                id: id.clone(),
+                label: package_names.get(dir).cloned().unwrap_or_default(),
+                entity_type: GraphEntityType::Package,
+                range: node_to_range(clause),
+                extra: Some(json!({
+                    "dir": dir.display().to_string(),
+                    "file": file.path.display().to_string(),
+                    "goroutines": goroutines,
+                    "channels": channels,
+                    "concurrent": goroutines > 0 || channels > 0,
+                })),
+                bytes: None,
+            });
+        }
+        for (_, path) in imports(file.tree.root_node(), &file.code) {
+            let imported = files
+                .iter()
+                .map(package_dir)
+                .find(|other| *other != dir && imported_package(other, package_names, &path));
+            let target = match imported {
+                Some(other) => package_id(other),
+                None => {
+                    let target = graph_id("", "", "pkg", &path, 0);
+                    if known.insert(target.clone()) {
+                        graph.nodes.push(GraphNode {
+                            id: target.clone(),
+                            label: path.clone(),
+                            entity_type: GraphEntityType::Package,
+                            range: Range::default(),
+                            extra: Some(json!({ "external": true })),
+                            bytes: None,
+                        });
+                    }
+                    target
+                }
+            };
+            if imports_seen.insert((id.clone(), target.clone())) {
+                graph.edges.push(GraphEdge {
+                    from: id.clone(),
+                    to: target,
+                    edge_type: GraphEdgeType::Imports,
+                });
+            }
+        }
+    }
+}
+
+fn package_dir(file: &SourceFile) -> &Path {
+    file.path.parent().unwrap_or(Path::new(""))
+}
+
+fn with_file(extra: Option<serde_json::Value>, path: &str) -> serde_json::Value {
+    match extra {
+        Some(serde_json::Value::Object(mut fields)) => {
+            fields.insert("file".to_string(), json!(path));
+            serde_json::Value::Object(fields)
+        }
+        _ => json!({ "file": path }),
+    }
+}
+
+/// Import name (alias, or the last path segment) to import path.
+fn imports(root: Node, code: &str) -> HashMap<String, String> {
+    let mut specs = Vec::new();
+    collect_kind(root, "import_spec", &mut specs);
+    specs
+        .into_iter()
+        .filter_map(|spec| {
+            let path = text(code, spec.child_by_field_name("path")?).trim_matches('"');
+            let name = match spec.child_by_field_name("name") {
+                Some(alias) => text(code, alias).to_string(),
+                None => path.rsplit('/').next().unwrap_or(path).to_string(),
+            };
+            Some((name, path.to_string()))
+        })
+        .collect()
+}
+
+/// Whether the directory `dir` is the package imported as `path`: its path ends with the
+/// import path's last segment and its package clause agrees.
+fn imported_package(dir: &Path, package_names: &HashMap<&Path, String>, path: &str) -> bool {
+    let last = path.rsplit('/').next().unwrap_or(path);
+    dir.file_name().is_some_and(|name| name == last)
+        && package_names.get(dir).is_none_or(|name| name == last)
+}
@@ -0,0 +1,126 @@
+package main
+
+import (
+	"bufio"
+	"encoding/json"
+	"fmt"
+	"io"
+	"sync"
+)
+
+// Daemon mode (--daemon) serves JSON-RPC 2.0 requests, one JSON value per line, until stdin
+// closes. Each "resolve" request names a file and a position; its content is sent only when it
+// changed, and later requests refer to it by hash. A request naming the package directory gets
+// the uses in every file of the package, the other files read from disk. A "version" request,
+// sent first, answers with helperVersion so the server can tell a stale helper from a current one.
+
+const (
+	codeMethodNotFound = -32601
+	codeInternalError  = -32603
+	// The request referred to content by a hash the daemon has not been sent.
+	codeUnknownContent = -32001
+)
+
+type rpcRequest struct {
+	ID     uint64        `json:"id"`
+	Method string        `json:"method"`
+	Params resolveParams `json:"params"`
+}
+
+type resolveParams struct {
+	File    string  `json:"file"`
+	Line    int     `json:"line"`
+	Col     int     `json:"col"`
+	Hash    string  `json:"hash"`
+	Content *string `json:"content,omitempty"`
+	Dir     string  `json:"dir,omitempty"`
+}
+
+type rpcResult struct {
+	JSONRPC string  `json:"jsonrpc"`
+	ID      uint64  `json:"id"`
+	Result  *Output `json:"result"`
+}
+
+type rpcError struct {
+	Code    int    `json:"code"`
+	Message string `json:"message"`
+}
+
+type rpcErrorResponse struct {
+	JSONRPC string   `json:"jsonrpc"`
+	ID      uint64   `json:"id"`
+	Error   rpcError `json:"error"`
+}
+
+// helperVersion identifies the sources the helper was built from. The server stamps it at build
+// time with -ldflags "-X main.helperVersion=..." and does not run a helper reporting another.
+var helperVersion = "dev"
+
+type versionResult struct {
+	Version string `json:"version"`
+}
+
+type rpcVersionResult struct {
+	JSONRPC string        `json:"jsonrpc"`
+	ID      uint64        `json:"id"`
+	Result  versionResult `json:"result"`
+}
+
+type cachedContent struct {
+	hash    string
+	content string
+}
+
+// serve answers the requests read from r on w. Requests are resolved concurrently and answered
+// as they finish, matched to their request by id.
+func serve(r io.Reader, w io.Writer) {
+	dec := json.NewDecoder(bufio.NewReader(r))
+	enc := json.NewEncoder(w)
+	var mu sync.Mutex
+	var wg sync.WaitGroup
+	reply := func(response interface{}) {
+		mu.Lock()
+		defer mu.Unlock()
+		_ = enc.Encode(response)
+	}
+	replyError := func(id uint64, code int, message string) {
+		reply(rpcErrorResponse{JSONRPC: "2.0", ID: id, Error: rpcError{Code: code, Message: message}})
+	}
+	contents := make(map[string]cachedContent)
+	for {
+		var req rpcRequest
+		if err := dec.Decode(&req); err != nil {
+			break
+		}
+		if req.Method == "version" {
+			reply(rpcVersionResult{JSONRPC: "2.0", ID: req.ID, Result: versionResult{Version: helperVersion}})
+			continue
+		}
+		if req.Method != "resolve" {
+			replyError(req.ID, codeMethodNotFound, "unknown method "+req.Method)
+			continue
+		}
+		p := req.Params
+		if p.Content != nil {
+			contents[p.File] = cachedContent{hash: p.Hash, content: *p.Content}
+		}
+		cached, ok := contents[p.File]
+		if !ok || cached.hash != p.Hash {
+			replyError(req.ID, codeUnknownContent, "no content with hash "+p.Hash+" for "+p.File)
+			continue
+		}
+		in := Input{File: p.File, Line: p.Line, Col: p.Col, Content: cached.content, Dir: p.Dir}
+		wg.Add(1)
+		go func(id uint64) {
+			defer wg.Done()
+			defer func() {
+				if panicked := recover(); panicked != nil {
+					replyError(id, codeInternalError, fmt.Sprint(panicked))
+				}
+			}()
+			reply(rpcResult{JSONRPC: "2.0", ID: id, Result: resolve(in)})
+		}(req.ID)
+	}
+	wg.Wait()
+}
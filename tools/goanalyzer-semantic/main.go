@@ -17,6 +17,9 @@ type Input struct {
 	Line    int    `json:"line"`
 	Col     int    `json:"col"`
 	Content string `json:"content"`
+	// The package directory. When set, uses in all files of the package are returned; otherwise
+	// only those in File.
+	Dir     string `json:"dir,omitempty"`
 }
 
 type Pos struct {
@@ -30,16 +33,21 @@ type Range struct {
 }
 
 type UseEntry struct {
-	Range    Range `json:"range"`
-	Reassign bool  `json:"reassign"`
-	Captured bool  `json:"captured"`
+	// Set for a use in another file than the one requested.
+	File     string `json:"file,omitempty"`
+	Range    Range  `json:"range"`
+	Reassign bool   `json:"reassign"`
+	Captured bool   `json:"captured"`
 }
 
 type Output struct {
 	Name      string     `json:"name"`
 	Decl      Range      `json:"decl"`
+	// Set when the declaration is in another file than the one requested.
+	DeclFile  string     `json:"decl_file,omitempty"`
 	Uses      []UseEntry `json:"uses"`
 	IsPointer bool       `json:"is_pointer"`
+	Type      string     `json:"type,omitempty"`
 }
 
 type typeSwitchTarget struct {
@@ -48,6 +56,10 @@ type typeSwitchTarget struct {
 }
 
 func main() {
+	if len(os.Args) > 1 && os.Args[1] == "--daemon" {
+		serve(os.Stdin, os.Stdout)
+		return
+	}
 	var in Input
 	if err := json.NewDecoder(os.Stdin).Decode(&in); err != nil {
 		encodeNil()
@@ -73,11 +85,19 @@ func resolve(in Input) *Output {
 		filePath = abs
 	}
 
+	dir := filepath.Dir(filePath)
+	if in.Dir != "" {
+		if abs, err := filepath.Abs(in.Dir); err == nil {
+			dir = abs
+		}
+	}
+
 	fset := token.NewFileSet()
-	file, files := parsePackageFiles(fset, filePath, in.Content)
+	file, files := parsePackageFiles(fset, dir, filePath, in.Content)
 	if file == nil || len(files) == 0 {
 		return nil
 	}
+	scope := useScope{fset: fset, target: filepath.Clean(filePath), packageWide: in.Dir != ""}
 
 	info := &types.Info{
 		Defs:       make(map[*ast.Ident]types.Object),
@@ -91,9 +111,14 @@ func resolve(in Input) *Output {
 		Error:    func(error) {},
 	}
 	pkgName := file.Name.Name
-	_, _ = config.Check(pkgName, fset, files, info)
+	pkg, _ := config.Check(pkgName, fset, files, info)
 
-	parentMap := buildParentMap(file)
+	parentMap := make(map[ast.Node]ast.Node)
+	for _, f := range files {
+		for child, parent := range buildParentMap(f) {
+			parentMap[child] = parent
+		}
+	}
 	ident, selMap := findIdentAtPosition(fset, file, in.Line, in.Col)
 	if ident == nil {
 		return nil
@@ -128,8 +153,9 @@ func resolve(in Input) *Output {
 			return nil
 		}
 		decl := rangeForIdent(fset, tsTarget.declIdent)
+		declFile := scope.fileOf(tsTarget.declIdent)
 		declFunc := enclosingFunc(tsTarget.declIdent, parentMap)
-		uses := collectUsesForObjects(info, fset, tsTarget.objects, decl, declFunc, parentMap)
+		uses := collectUsesForObjects(info, scope, tsTarget.objects, declFile, decl, declFunc, parentMap)
 		isPointer := false
 		for _, o := range tsTarget.objects {
 			if isPointerType(o.Type()) {
@@ -140,6 +166,7 @@ func resolve(in Input) *Output {
 		return &Output{
 			Name:      tsTarget.declIdent.Name,
 			Decl:      decl,
+			DeclFile:  declFile,
 			Uses:      uses,
 			IsPointer: isPointer,
 		}
@@ -152,8 +179,9 @@ func resolve(in Input) *Output {
 			return nil
 		}
 		decl := rangeForIdent(fset, tsTarget.declIdent)
+		declFile := scope.fileOf(tsTarget.declIdent)
 		declFunc := enclosingFunc(tsTarget.declIdent, parentMap)
-		uses := collectUsesForObjects(info, fset, tsTarget.objects, decl, declFunc, parentMap)
+		uses := collectUsesForObjects(info, scope, tsTarget.objects, declFile, decl, declFunc, parentMap)
 		isPointer := false
 		for _, o := range tsTarget.objects {
 			if isPointerType(o.Type()) {
@@ -164,24 +192,27 @@ func resolve(in Input) *Output {
 		return &Output{
 			Name:      tsTarget.declIdent.Name,
 			Decl:      decl,
+			DeclFile:  declFile,
 			Uses:      uses,
 			IsPointer: isPointer,
 		}
 	}
 	decl := rangeForIdent(fset, declIdent)
+	declFile := scope.fileOf(declIdent)
 	declFunc := enclosingFunc(declIdent, parentMap)
-	uses := collectUses(info, fset, obj, decl, declFunc, parentMap)
+	uses := collectUses(info, scope, obj, declFile, decl, declFunc, parentMap)
 
 	return &Output{
 		Name:      obj.Name(),
 		Decl:      decl,
+		DeclFile:  declFile,
 		Uses:      uses,
 		IsPointer: isPointerType(obj.Type()),
+		Type:      typeString(obj.Type(), pkg),
 	}
 }
 
-func parsePackageFiles(fset *token.FileSet, targetFile string, content string) (*ast.File, []*ast.File) {
-	dir := filepath.Dir(targetFile)
+func parsePackageFiles(fset *token.FileSet, dir string, targetFile string, content string) (*ast.File, []*ast.File) {
 	pkgs, err := parser.ParseDir(fset, dir, nil, parser.ParseComments)
 	if err != nil {
 		return parseSingleFile(fset, targetFile, content)
@@ -279,44 +310,32 @@ func findDeclIdent(info *types.Info, obj types.Object) *ast.Ident {
 	return nil
 }
 
-func collectUses(info *types.Info, fset *token.FileSet, obj types.Object, decl Range, declFunc ast.Node, parentMap map[ast.Node]ast.Node) []UseEntry {
-	uses := make([]UseEntry, 0)
-	seen := make(map[string]bool)
-	objSet := map[types.Object]bool{obj: true}
+// useScope tells which files uses are reported from and how they are labelled.
+type useScope struct {
+	fset        *token.FileSet
+	target      string
+	packageWide bool
+}
 
-	add := func(r Range, reassign bool, captured bool) {
-		key := keyForRange(r)
-		if seen[key] {
-			return
-		}
-		if sameRange(r, decl) {
-			return
-		}
-		seen[key] = true
-		uses = append(uses, UseEntry{
-			Range:    r,
-			Reassign: reassign,
-			Captured: captured,
-		})
+// fileOf is the file of ident, or "" for the requested file.
+func (s useScope) fileOf(ident *ast.Ident) string {
+	file := filepath.Clean(s.fset.Position(ident.Pos()).Filename)
+	if file == s.target {
+		return ""
 	}
+	return file
+}
 
-	for ident, o := range info.Uses {
-		if objSet[o] {
-			r := rangeForIdent(fset, ident)
-			add(r, isReassign(ident, info, parentMap), isCaptured(ident, obj, declFunc, parentMap))
-		}
-	}
-	for sel, selInfo := range info.Selections {
-		if selInfo != nil && objSet[selInfo.Obj()] {
-			r := rangeForIdent(fset, sel.Sel)
-			add(r, isReassign(sel.Sel, info, parentMap), isCaptured(sel.Sel, obj, declFunc, parentMap))
-		}
-	}
+// includes reports whether a use in file (as returned by fileOf) is reported.
+func (s useScope) includes(file string) bool {
+	return file == "" || s.packageWide
+}
 
-	return uses
+func collectUses(info *types.Info, scope useScope, obj types.Object, declFile string, decl Range, declFunc ast.Node, parentMap map[ast.Node]ast.Node) []UseEntry {
+	return collectUsesForObjects(info, scope, []types.Object{obj}, declFile, decl, declFunc, parentMap)
 }
 
-func collectUsesForObjects(info *types.Info, fset *token.FileSet, objs []types.Object, decl Range, declFunc ast.Node, parentMap map[ast.Node]ast.Node) []UseEntry {
+func collectUsesForObjects(info *types.Info, scope useScope, objs []types.Object, declFile string, decl Range, declFunc ast.Node, parentMap map[ast.Node]ast.Node) []UseEntry {
 	objSet := make(map[types.Object]bool)
 	for _, o := range objs {
 		if o != nil {
@@ -326,32 +345,36 @@ func collectUsesForObjects(info *types.Info, fset *token.FileSet, objs []types.O
 	uses := make([]UseEntry, 0)
 	seen := make(map[string]bool)
 
-	add := func(r Range, reassign bool, captured bool) {
-		key := keyForRange(r)
+	add := func(ident *ast.Ident, obj types.Object) {
+		file := scope.fileOf(ident)
+		if !scope.includes(file) {
+			return
+		}
+		r := rangeForIdent(scope.fset, ident)
+		key := file + ":" + keyForRange(r)
 		if seen[key] {
 			return
 		}
-		if sameRange(r, decl) {
+		if file == declFile && sameRange(r, decl) {
 			return
 		}
 		seen[key] = true
 		uses = append(uses, UseEntry{
+			File:     file,
 			Range:    r,
-			Reassign: reassign,
-			Captured: captured,
+			Reassign: isReassign(ident, info, parentMap),
+			Captured: isCaptured(ident, obj, declFunc, parentMap),
 		})
 	}
 
 	for ident, o := range info.Uses {
 		if objSet[o] {
-			r := rangeForIdent(fset, ident)
-			add(r, isReassign(ident, info, parentMap), isCaptured(ident, o, declFunc, parentMap))
+			add(ident, o)
 		}
 	}
 	for sel, selInfo := range info.Selections {
 		if selInfo != nil && objSet[selInfo.Obj()] {
-			r := rangeForIdent(fset, sel.Sel)
-			add(r, isReassign(sel.Sel, info, parentMap), isCaptured(sel.Sel, selInfo.Obj(), declFunc, parentMap))
+			add(sel.Sel, selInfo.Obj())
 		}
 	}
 
@@ -466,6 +489,13 @@ func isPointerType(t types.Type) bool {
 	}
 }
 
+func typeString(t types.Type, pkg *types.Package) string {
+	if t == nil {
+		return ""
+	}
+	return types.TypeString(t, types.RelativeTo(pkg))
+}
+
 func sameRange(a, b Range) bool {
 	return a.Start.Line == b.Start.Line &&
 		a.Start.Col == b.Start.Col &&
@@ -129,6 +129,7 @@ const lastStatus = {
 };
 
 interface Decoration {
+    id?: string;
     range: vscode.Range;
     kind:
     | "Declaration"
@@ -143,12 +144,16 @@ interface Decoration {
         severity: "Error" | "Warning" | "Information" | "Hint";
         code: string;
         message: string;
+        href?: string;
     };
+    bytes?: { start_byte: number; end_byte: number };
+    stale?: boolean;
 }
 
-const ProgressNotification = new NotificationType<string>(
-    "goanalyzer/progress",
-);
+const ProgressNotification = new NotificationType<{
+    message: string;
+    trace_id?: string;
+}>("goanalyzer/progress");
 
 const IndexingStatusNotification = new NotificationType<{
     uri?: string;
@@ -166,11 +171,56 @@ const ParseInfoNotification = new NotificationType<{
     code_len: number;
 }>("goanalyzer/parseInfo");
 
+type PerfParams = {
+    trace_id: string;
+    method: string;
+    uri?: string;
+    total_ms: number;
+    parse_ms?: number;
+    analysis_ms: number;
+    code_len?: number;
+    nodes?: number;
+    tree_cache_hit?: boolean;
+    truncated?: boolean;
+};
+
+const PerfNotification = new NotificationType<PerfParams>("goanalyzer/perf");
+
+function logPerf(p: PerfParams) {
+    const document = p.uri
+        ? ` ${p.uri} code_len=${p.code_len} nodes=${p.nodes} tree_cache_hit=${p.tree_cache_hit}`
+        : "";
+    const parse = p.parse_ms == null ? "" : ` parse=${p.parse_ms.toFixed(1)}ms`;
+    const truncated = p.truncated ? " truncated" : "";
+    log(`Perf [trace ${p.trace_id}] ${p.method}: total=${p.total_ms.toFixed(1)}ms${parse} analysis=${p.analysis_ms.toFixed(1)}ms${document}${truncated}`);
+}
+
 const LifecycleDumpNotification = new NotificationType<{
     uri: string;
     points: unknown[];
 }>("goanalyzer/lifecycleDump");
 
+const DecorationsDeltaNotification = new NotificationType<{
+    uri: string;
+    version?: number;
+    full: boolean;
+    added: Decoration[];
+    removed: string[];
+}>("goanalyzer/decorationsDelta");
+
+// LSP "content modified": the server answered for a document version that is no longer current.
+function isContentModified(err: unknown): boolean {
+    return (err as { code?: number } | undefined)?.code === -32801;
+}
+
+const DecorationsNotification = new NotificationType<{
+    uri: string;
+    version?: number;
+    decorations: Decoration[];
+}>("goanalyzer/decorations");
+
+const decorationSets = new Map<string, Map<string, Decoration>>();
+
 function addColorValues(points: unknown[]): unknown[] {
     const cfg = vscode.workspace.getConfiguration("goAnalyzer");
     return (points as Array<Record<string, unknown>>).map(point => {
@@ -226,7 +276,9 @@ function applyDiagnostics(
             d.diagnostic.message,
             toDiagnosticSeverity(d.diagnostic.severity),
         );
-        diag.code = d.diagnostic.code;
+        diag.code = d.diagnostic.href
+            ? { value: d.diagnostic.code, target: vscode.Uri.parse(d.diagnostic.href) }
+            : d.diagnostic.code;
         diag.source = "go-analyzer";
         diagnostics.push(diag);
     }
@@ -276,17 +328,72 @@ export function activate(context: vscode.ExtensionContext) {
     const semanticHelperPathOverride = vscode.workspace.getConfiguration("goAnalyzer")
         .get<string>("semanticHelperPath", "").trim();
     const semanticHelperPath = semanticHelperPathOverride || resolveSemanticHelperPath(context, serverModule);
-    const semanticEnabled = semanticEnable && !!semanticHelperPath;
+    // Without a helper found here the server looks for one itself, and offers to build it.
+    const semanticEnabled = semanticEnable;
+    const semanticBuild = vscode.workspace.getConfiguration("goAnalyzer")
+        .get<string>("semanticBuild", "ask");
+    const goplsEnable = vscode.workspace.getConfiguration("goAnalyzer")
+        .get<boolean>("goplsEnable", false);
+    const goplsPath = vscode.workspace.getConfiguration("goAnalyzer")
+        .get<string>("goplsPath", "").trim();
     const semanticTimeoutMs = vscode.workspace.getConfiguration("goAnalyzer")
         .get<number>("semanticTimeoutMs", 2000);
+    const semanticMaxInFlight = vscode.workspace.getConfiguration("goAnalyzer")
+        .get<number>("semanticMaxInFlight", 2);
+
+    const ruleDocsBaseUrl = vscode.workspace.getConfiguration("goAnalyzer")
+        .get<string>("ruleDocsBaseUrl", "").trim();
+    const pushDecorations = vscode.workspace.getConfiguration("goAnalyzer")
+        .get<boolean>("pushDecorations", false);
+    const autoAnalysisDelay = vscode.workspace.getConfiguration("goAnalyzer")
+        .get<number>("autoAnalysisDelay", 300);
+    const recordSession = vscode.workspace.getConfiguration("goAnalyzer")
+        .get<boolean>("recordSession", false);
+    const buildOnSave = vscode.workspace.getConfiguration("goAnalyzer")
+        .get<boolean>("buildOnSave", false);
+    const perfTelemetry = vscode.workspace.getConfiguration("goAnalyzer")
+        .get<boolean>("perfTelemetry", false);
+    const syncFunctions = vscode.workspace.getConfiguration("goAnalyzer")
+        .get<string[]>("syncFunctions", []);
+    const maxCachedTrees = vscode.workspace.getConfiguration("goAnalyzer")
+        .get<number>("maxCachedTrees", 20);
+    const maxCachedDocuments = vscode.workspace.getConfiguration("goAnalyzer")
+        .get<number>("maxCachedDocuments", 50);
+    const cacheTtlSeconds = vscode.workspace.getConfiguration("goAnalyzer")
+        .get<number>("cacheTtlSeconds", 300);
+    const analysisBudgetMs = vscode.workspace.getConfiguration("goAnalyzer")
+        .get<number>("analysisBudgetMs", 2000);
+    const logLevel = vscode.workspace.getConfiguration("goAnalyzer")
+        .get<string>("logLevel", "info");
+    const logFile = vscode.workspace.getConfiguration("goAnalyzer")
+        .get<string>("logFile", "");
+    const workspaceRoot = vscode.workspace.workspaceFolders?.[0]?.uri.fsPath;
 
     const semanticEnv = {
         ...process.env,
         GO_ANALYZER_SEMANTIC: semanticEnabled ? "1" : "0",
         GO_ANALYZER_SEMANTIC_PATH: semanticHelperPath ?? "",
+        GO_ANALYZER_SEMANTIC_BUILD: semanticBuild,
         GO_ANALYZER_SEMANTIC_TIMEOUT_MS: String(semanticTimeoutMs),
+        GO_ANALYZER_SEMANTIC_MAX_IN_FLIGHT: String(semanticMaxInFlight),
+        GO_ANALYZER_GOPLS: goplsEnable ? "1" : "0",
+        GO_ANALYZER_GOPLS_PATH: goplsPath,
+        GO_ANALYZER_RULE_DOCS_URL: ruleDocsBaseUrl,
+        GO_ANALYZER_PUSH_DECORATIONS: pushDecorations ? "1" : "0",
+        GO_ANALYZER_ANALYSIS_DEBOUNCE_MS: String(autoAnalysisDelay),
+        GO_ANALYZER_RECORD: recordSession ? "1" : "0",
+        GO_ANALYZER_PERF: perfTelemetry ? "1" : "0",
+        GO_ANALYZER_BUILD_ON_SAVE: buildOnSave ? "1" : "0",
+        GO_ANALYZER_SYNC_FUNCTIONS: syncFunctions.join(","),
+        GO_ANALYZER_MAX_CACHED_TREES: String(maxCachedTrees),
+        GO_ANALYZER_MAX_CACHED_DOCUMENTS: String(maxCachedDocuments),
+        GO_ANALYZER_CACHE_TTL_SECONDS: String(cacheTtlSeconds),
+        GO_ANALYZER_ANALYSIS_BUDGET_MS: String(analysisBudgetMs),
+        GO_ANALYZER_LOG_LEVEL: logLevel,
+        GO_ANALYZER_LOG_FILE: logFile,
+        GO_ANALYZER_CONFIG: workspaceRoot ? path.join(workspaceRoot, ".go-analyzer.json") : "",
     };
-    log(`Semantic helper: ${semanticEnabled ? (semanticHelperPath ?? "enabled") : "disabled"}`);
+    log(`Semantic helper: ${semanticEnabled ? (semanticHelperPath ?? "located by the server") : "disabled"}`);
     const serverOptions: ServerOptions = {
         run: {
             command: serverModule,
@@ -336,15 +443,17 @@ export function activate(context: vscode.ExtensionContext) {
         const target = p.uri ? ` ${p.uri}` : "";
         log(`Indexing status:${target} vars=${p.variables}, funcs=${p.functions}, chans=${p.channels}, goroutines=${p.goroutines}`);
     });
-    client.onNotification(ProgressNotification, message => {
-        vscode.window.showInformationMessage(message);
-        log(`Progress: ${message}`);
+    client.onNotification(ProgressNotification, p => {
+        vscode.window.showInformationMessage(p.message);
+        const trace = p.trace_id ? ` [trace ${p.trace_id}]` : "";
+        log(`Progress: ${p.message}${trace}`);
     });
     client.onNotification(ParseInfoNotification, p => {
         if (p.source !== "auto") return;
         const parseMs = p.parse_ms == null ? "cache" : `${p.parse_ms}ms`;
         log(`Parse info (auto): ${p.uri} cache_hit=${p.cache_hit} parse_ms=${parseMs} code_len=${p.code_len}`);
     });
+    client.onNotification(PerfNotification, logPerf);
     client.onNotification(LifecycleDumpNotification, p => {
         const maxChars = vscode.workspace.getConfiguration("goAnalyzer")
             .get<number>("lifecycleJsonMaxChars", 20000);
@@ -402,6 +511,67 @@ export function activate(context: vscode.ExtensionContext) {
             overviewRulerLane: vscode.OverviewRulerLane.Right,
         }),
     };
+    const renderDecorations = (editor: vscode.TextEditor, resp: Decoration[] | undefined) => {
+        for (const key in decorationTypes) {
+            editor.setDecorations(decorationTypes[key as keyof typeof decorationTypes], []);
+        }
+        if (!Array.isArray(resp)) return;
+        const byType: Record<string, vscode.DecorationOptions[]> = {
+            Declaration: [],
+            Use: [],
+            Pointer: [],
+            Race: [],
+            RaceLow: [],
+            AliasReassigned: [],
+            AliasCaptured: [],
+        };
+        for (const d of resp) {
+            const range = new vscode.Range(
+                new vscode.Position(d.range.start.line, d.range.start.character),
+                new vscode.Position(d.range.end.line, d.range.end.character),
+            );
+            if (byType[d.kind]) {
+                byType[d.kind].push({ range, hoverMessage: d.hover_text });
+            }
+        }
+        for (const [k, decos] of Object.entries(byType)) {
+            editor.setDecorations(decorationTypes[k as keyof typeof decorationTypes], decos);
+        }
+    };
+    const onDecorationsDelta = (p: {
+        uri: string;
+        version?: number;
+        full: boolean;
+        added: Decoration[];
+        removed: string[];
+    }) => {
+        let set = decorationSets.get(p.uri);
+        if (!set || p.full) {
+            set = new Map();
+            decorationSets.set(p.uri, set);
+        }
+        for (const id of p.removed) {
+            set.delete(id);
+        }
+        for (const d of p.added) {
+            if (d.id) set.set(d.id, d);
+        }
+        const current = Array.from(set.values());
+        for (const editor of vscode.window.visibleTextEditors) {
+            if (editor.document.uri.toString() === p.uri) {
+                renderDecorations(editor, current);
+            }
+        }
+        applyDiagnostics(diagnosticsCollection, vscode.Uri.parse(p.uri), current);
+        log(`Decorations delta: ${p.uri} full=${p.full} +${p.added.length} -${p.removed.length}`);
+    };
+    client.onNotification(DecorationsDeltaNotification, onDecorationsDelta);
+    const onDecorations = (p: { uri: string; version?: number; decorations: Decoration[] }) => {
+        const doc = vscode.workspace.textDocuments.find(d => d.uri.toString() === p.uri);
+        if (doc && p.version != null && doc.version !== p.version) return;
+        onDecorationsDelta({ uri: p.uri, full: true, added: p.decorations, removed: [] });
+    };
+    client.onNotification(DecorationsNotification, onDecorations);
     const lifecycleCmd = vscode.commands.registerCommand(
         "goanalyzer.showLifecycle",
         async () => {
@@ -424,6 +594,8 @@ export function activate(context: vscode.ExtensionContext) {
                     },
                     async progress => {
                         progress.report({ message: "Analyzing variable…" });
+                        const useDelta = vscode.workspace.getConfiguration("goAnalyzer")
+                            .get<boolean>("decorationDelta", false);
                         const resp: Decoration[] = await client!.sendRequest(
                             "workspace/executeCommand",
                             {
@@ -433,6 +605,8 @@ export function activate(context: vscode.ExtensionContext) {
                                         textDocument: { uri: document.uri.toString() },
                                         position: selection.active,
                                         source: "manual",
+                                        version: document.version,
+                                        delta: useDelta,
                                         dump_json: vscode.workspace.getConfiguration("goAnalyzer")
                                             .get<boolean>("debugLifecycleJson", false),
                                     },
@@ -445,37 +619,15 @@ export function activate(context: vscode.ExtensionContext) {
                         if (dumpAst) {
                             await dumpAstForDocument(document.uri);
                         }
-                        for (const key in decorationTypes) {
-                            editor.setDecorations(decorationTypes[key as keyof typeof decorationTypes], []);
-                        }
-                        if (Array.isArray(resp)) {
-                            const byType: Record<string, vscode.DecorationOptions[]> = {
-                                Declaration: [],
-                                Use: [],
-                                Pointer: [],
-                                Race: [],
-                                RaceLow: [],
-                                AliasReassigned: [],
-                                AliasCaptured: [],
-                            };
-                            for (const d of resp) {
-                                const range = new vscode.Range(
-                                    new vscode.Position(d.range.start.line, d.range.start.character),
-                                    new vscode.Position(d.range.end.line, d.range.end.character),
-                                );
-                                if (byType[d.kind]) {
-                                    byType[d.kind].push({ range, hoverMessage: d.hover_text });
-                                }
-                            }
-                            for (const [k, decos] of Object.entries(byType)) {
-                                editor.setDecorations(decorationTypes[k as keyof typeof decorationTypes], decos);
-                            }
+                        if (!useDelta) {
+                            renderDecorations(editor, resp);
+                            applyDiagnostics(diagnosticsCollection, document.uri, resp);
                         }
-                        applyDiagnostics(diagnosticsCollection, document.uri, resp);
                         progress.report({ message: "Analysis complete" });
                     },
                 );
             } catch (err) {
+                if (isContentModified(err)) return;
                 vscode.window.showErrorMessage(`Go Analyzer error: ${err}`);
                 console.error(err);
             }
@@ -494,6 +646,421 @@ export function activate(context: vscode.ExtensionContext) {
         },
     );
     context.subscriptions.push(dumpAstCmd);
+    const exportGraphCmd = vscode.commands.registerCommand(
+        "goanalyzer.exportGraph",
+        async () => {
+            const editor = vscode.window.activeTextEditor;
+            if (!editor || editor.document.languageId !== "go") {
+                vscode.window.showErrorMessage("No Go editor is active.");
+                return;
+            }
+            if (!client) {
+                vscode.window.showErrorMessage("Go Analyzer server is not running.");
+                return;
+            }
+            const scope = await vscode.window.showQuickPick(["file", "package", "workspace"], {
+                placeHolder: "Graph of the current file, its package or the whole workspace",
+            });
+            if (!scope) return;
+            const formats = [
+                { label: "dot", description: "Graphviz", filter: "Graphviz", ext: "dot" },
+                { label: "graphml", description: "Gephi, Cytoscape, yEd", filter: "GraphML", ext: "graphml" },
+                { label: "jgf", description: "JSON Graph Format", filter: "JSON Graph", ext: "json" },
+            ];
+            const format = await vscode.window.showQuickPick(formats, {
+                placeHolder: "Export format",
+            });
+            if (!format) return;
+            try {
+                const output: string | null = await client.sendRequest("workspace/executeCommand", {
+                    command: "goanalyzer/graph.export",
+                    arguments: [{ uri: editor.document.uri.toString(), scope, format: format.label }],
+                });
+                if (!output) {
+                    log(`Graph export: no data for ${editor.document.uri.toString()}`);
+                    return;
+                }
+                const target = await vscode.window.showSaveDialog({
+                    filters: { [format.filter]: [format.ext] },
+                    defaultUri: editor.document.uri.with({
+                        path: editor.document.uri.path.replace(/\.go$/, `.${format.ext}`),
+                    }),
+                });
+                if (!target) return;
+                await vscode.workspace.fs.writeFile(target, Buffer.from(output, "utf8"));
+                log(`Graph written to ${target.fsPath}`);
+            } catch (err) {
+                vscode.window.showErrorMessage(`Go Analyzer error: ${err}`);
+                console.error(err);
+            }
+        },
+    );
+    context.subscriptions.push(exportGraphCmd);
+    const copyMermaidCmd = vscode.commands.registerCommand(
+        "goanalyzer.copyGraphMermaid",
+        async () => {
+            const editor = vscode.window.activeTextEditor;
+            if (!editor || editor.document.languageId !== "go") {
+                vscode.window.showErrorMessage("No Go editor is active.");
+                return;
+            }
+            if (!client) {
+                vscode.window.showErrorMessage("Go Analyzer server is not running.");
+                return;
+            }
+            try {
+                const root = await vscode.window.showInputBox({
+                    prompt: "Center the graph on an entity (leave empty for the whole file)",
+                    placeHolder: "handle or Server.run",
+                });
+                if (root === undefined) return;
+                const args: { uri: string; root?: string; depth?: number } = {
+                    uri: editor.document.uri.toString(),
+                };
+                if (root.trim()) {
+                    args.root = root.trim();
+                    args.depth = 2;
+                }
+                const mermaid: string | null = await client.sendRequest(
+                    "workspace/executeCommand",
+                    { command: "goanalyzer/graph.mermaid", arguments: [args] },
+                );
+                if (!mermaid) {
+                    log(`Mermaid export: no data for ${editor.document.uri.toString()}`);
+                    return;
+                }
+                await vscode.env.clipboard.writeText("```mermaid\n" + mermaid + "```\n");
+                vscode.window.showInformationMessage("Go Analyzer: Mermaid graph copied to the clipboard.");
+            } catch (err) {
+                vscode.window.showErrorMessage(`Go Analyzer error: ${err}`);
+                console.error(err);
+            }
+        },
+    );
+    context.subscriptions.push(copyMermaidCmd);
+    const channelFlowCmd = vscode.commands.registerCommand(
+        "goanalyzer.showChannelFlow",
+        async () => {
+            const editor = vscode.window.activeTextEditor;
+            if (!editor || editor.document.languageId !== "go") {
+                vscode.window.showErrorMessage("No Go editor is active.");
+                return;
+            }
+            if (!client) {
+                vscode.window.showErrorMessage("Go Analyzer server is not running.");
+                return;
+            }
+            try {
+                type Site = { start: vscode.Position; end: vscode.Position };
+                type FlowNode = {
+                    label: string;
+                    entity_type: string;
+                    range: Site;
+                    extra?: Record<string, Site[]>;
+                };
+                const flow: { nodes: FlowNode[] } | null = await client.sendRequest(
+                    "workspace/executeCommand",
+                    {
+                        command: "goanalyzer/channelFlow",
+                        arguments: [
+                            {
+                                textDocument: { uri: editor.document.uri.toString() },
+                                position: editor.selection.active,
+                            },
+                        ],
+                    },
+                );
+                if (!flow || flow.nodes.length === 0) {
+                    vscode.window.showInformationMessage("Go Analyzer: no channel at the cursor.");
+                    return;
+                }
+                const [channel, ...actors] = flow.nodes;
+                const items = [
+                    {
+                        label: `$(symbol-event) ${channel.label}`,
+                        description: "created",
+                        site: channel.range,
+                    },
+                ];
+                for (const actor of actors) {
+                    for (const role of ["sends", "receives", "ranges", "closes"]) {
+                        for (const site of actor.extra?.[role] ?? []) {
+                            items.push({
+                                label: `${role} in ${actor.label}`,
+                                description: `line ${site.start.line + 1}`,
+                                site,
+                            });
+                        }
+                    }
+                }
+                const picked = await vscode.window.showQuickPick(items, {
+                    placeHolder: `Producers and consumers of ${channel.label}`,
+                });
+                if (!picked) return;
+                const { start, end } = picked.site;
+                editor.selection = new vscode.Selection(
+                    start.line,
+                    start.character,
+                    end.line,
+                    end.character,
+                );
+                editor.revealRange(editor.selection);
+            } catch (err) {
+                vscode.window.showErrorMessage(`Go Analyzer error: ${err}`);
+                console.error(err);
+            }
+        },
+    );
+    context.subscriptions.push(channelFlowCmd);
+    const functionGraphCmd = vscode.commands.registerCommand(
+        "goanalyzer.showFunctionGraph",
+        async (target?: { uri: string; name: string }) => {
+            const editor = vscode.window.activeTextEditor;
+            if (!target && (!editor || editor.document.languageId !== "go")) {
+                vscode.window.showErrorMessage("No Go editor is active.");
+                return;
+            }
+            if (!client) {
+                vscode.window.showErrorMessage("Go Analyzer server is not running.");
+                return;
+            }
+            const uri = target ? vscode.Uri.parse(target.uri) : editor!.document.uri;
+            try {
+                type Site = { start: vscode.Position; end: vscode.Position };
+                type Node = { id: string; label: string; entity_type: string; range: Site };
+                const graph: { nodes: Node[]; edges: unknown[] } | null = await client.sendRequest(
+                    "workspace/executeCommand",
+                    {
+                        command: "goanalyzer/graphForFunction",
+                        arguments: [
+                            target
+                                ? { uri: target.uri, name: target.name }
+                                : { uri: uri.toString(), position: editor!.selection.active },
+                        ],
+                    },
+                );
+                if (!graph) {
+                    vscode.window.showInformationMessage("Go Analyzer: no function at the cursor.");
+                    return;
+                }
+                const items = graph.nodes.map(node => ({
+                    label: node.label,
+                    description: `${node.entity_type}, line ${node.range.start.line + 1}`,
+                    site: node.range,
+                }));
+                const picked = await vscode.window.showQuickPick(items, {
+                    placeHolder: `${graph.nodes.length} entities and ${graph.edges.length} edges of ${target?.name ?? "the function"}`,
+                });
+                if (!picked) return;
+                const document = await vscode.workspace.openTextDocument(uri);
+                const shown = await vscode.window.showTextDocument(document);
+                const { start, end } = picked.site;
+                shown.selection = new vscode.Selection(
+                    start.line,
+                    start.character,
+                    end.line,
+                    end.character,
+                );
+                shown.revealRange(shown.selection);
+            } catch (err) {
+                vscode.window.showErrorMessage(`Go Analyzer error: ${err}`);
+                console.error(err);
+            }
+        },
+    );
+    context.subscriptions.push(functionGraphCmd);
+    const dumpSessionCmd = vscode.commands.registerCommand(
+        "goanalyzer.dumpSession",
+        async () => {
+            if (!client) {
+                vscode.window.showErrorMessage("Go Analyzer server is not running.");
+                return;
+            }
+            try {
+                const dump: { enabled: boolean; entries: unknown[] } = await client.sendRequest(
+                    "workspace/executeCommand",
+                    { command: "goanalyzer/debug.dumpSession", arguments: [] },
+                );
+                if (!dump.enabled) {
+                    vscode.window.showWarningMessage(
+                        "Session recording is off. Enable goAnalyzer.recordSession and restart the server.",
+                    );
+                    return;
+                }
+                const target = await vscode.window.showSaveDialog({
+                    filters: { JSON: ["json"] },
+                    defaultUri: vscode.Uri.file("go-analyzer-session.json"),
+                });
+                if (!target) return;
+                await vscode.workspace.fs.writeFile(
+                    target,
+                    Buffer.from(JSON.stringify(dump, null, 2), "utf8"),
+                );
+                log(`Session dump: ${dump.entries.length} entries written to ${target.fsPath}`);
+            } catch (err) {
+                vscode.window.showErrorMessage(`Go Analyzer error: ${err}`);
+                console.error(err);
+            }
+        },
+    );
+    context.subscriptions.push(dumpSessionCmd);
+    const workspaceSummaryCmd = vscode.commands.registerCommand(
+        "goanalyzer.workspaceSummary",
+        async () => {
+            if (!client) {
+                vscode.window.showErrorMessage("Go Analyzer server is not running.");
+                return;
+            }
+            try {
+                const summary: {
+                    packages: {
+                        package: string;
+                        files: number;
+                        goroutines: number;
+                        channels: number;
+                        sync_coverage: number | null;
+                        findings: number;
+                        errors: number;
+                    }[];
+                } = await client.sendRequest("workspace/executeCommand", {
+                    command: "goanalyzer/workspaceSummary",
+                    arguments: [{}],
+                });
+                logRaw("package\tfiles\tgoroutines\tchannels\tsync\tfindings\terrors");
+                for (const p of summary.packages) {
+                    const coverage =
+                        p.sync_coverage === null ? "-" : `${Math.round(p.sync_coverage * 100)}%`;
+                    logRaw(
+                        `${p.package}\t${p.files}\t${p.goroutines}\t${p.channels}\t${coverage}\t${p.findings}\t${p.errors}`,
+                    );
+                }
+            } catch (err) {
+                vscode.window.showErrorMessage(`Go Analyzer error: ${err}`);
+                console.error(err);
+            }
+        },
+    );
+    context.subscriptions.push(workspaceSummaryCmd);
+    const doctorCmd = vscode.commands.registerCommand(
+        "goanalyzer.doctor",
+        async () => {
+            if (!client) {
+                vscode.window.showErrorMessage("Go Analyzer server is not running.");
+                return;
+            }
+            try {
+                const report: {
+                    status: string;
+                    checks: { name: string; status: string; detail: string; hint?: string }[];
+                } = await client.sendRequest("workspace/executeCommand", {
+                    command: "goanalyzer/doctor",
+                    arguments: [],
+                });
+                for (const check of report.checks) {
+                    logRaw(`[${check.status}] ${check.name}: ${check.detail}`);
+                    if (check.hint) logRaw(`    ${check.hint}`);
+                }
+                if (report.status !== "ok") {
+                    vscode.window.showWarningMessage(
+                        "Go Analyzer: some checks failed, see the Go Analyzer output for hints.",
+                    );
+                }
+            } catch (err) {
+                vscode.window.showErrorMessage(`Go Analyzer error: ${err}`);
+                console.error(err);
+            }
+        },
+    );
+    context.subscriptions.push(doctorCmd);
+    const raceDetectorCmd = vscode.commands.registerCommand(
+        "goanalyzer.runRaceDetector",
+        async () => {
+            if (!client) {
+                vscode.window.showErrorMessage("Go Analyzer server is not running.");
+                return;
+            }
+            const pkg = await vscode.window.showInputBox({
+                prompt: "Package to test with the race detector",
+                value: "./...",
+            });
+            if (pkg === undefined) return;
+            try {
+                const report: {
+                    passed: boolean;
+                    races: { sections: { description: string }[] }[];
+                    diagnostics: number;
+                    output?: string;
+                } | null = await client.sendRequest("workspace/executeCommand", {
+                    command: "goanalyzer/runRaceDetector",
+                    arguments: [{ package: pkg || "./..." }],
+                });
+                if (!report) return;
+                for (const race of report.races) {
+                    logRaw(`DATA RACE: ${race.sections.map(s => s.description).join("; ")}`);
+                }
+                if (report.output) logRaw(report.output);
+                if (report.races.length > 0) {
+                    vscode.window.showWarningMessage(
+                        `Go Analyzer: the race detector found ${report.races.length} race(s), see the Problems panel.`,
+                    );
+                } else if (report.passed) {
+                    vscode.window.showInformationMessage("Go Analyzer: no data races found.");
+                } else {
+                    vscode.window.showWarningMessage(
+                        "Go Analyzer: go test failed, see the Go Analyzer output.",
+                    );
+                }
+            } catch (err) {
+                vscode.window.showErrorMessage(`Go Analyzer error: ${err}`);
+                console.error(err);
+            }
+        },
+    );
+    context.subscriptions.push(raceDetectorCmd);
+    const statsCmd = vscode.commands.registerCommand(
+        "goanalyzer.stats",
+        async () => {
+            if (!client) {
+                vscode.window.showErrorMessage("Go Analyzer server is not running.");
+                return;
+            }
+            try {
+                const stats: {
+                    uptime_ms: number;
+                    caches: {
+                        name: string;
+                        entries: number;
+                        oldest_entry_age_ms?: number;
+                        estimated_bytes?: number;
+                        hit_ratio?: number;
+                    }[];
+                    timings: { name: string; runs: number; mean_ms: number; max_ms: number }[];
+                } = await client.sendRequest("workspace/executeCommand", {
+                    command: "goanalyzer/stats",
+                    arguments: [],
+                });
+                logRaw(`uptime: ${Math.round(stats.uptime_ms / 1000)}s`);
+                logRaw("cache\tentries\tbytes\toldest\thit ratio");
+                for (const c of stats.caches) {
+                    const ratio =
+                        c.hit_ratio === undefined ? "-" : `${Math.round(c.hit_ratio * 100)}%`;
+                    const oldest =
+                        c.oldest_entry_age_ms === undefined
+                            ? "-"
+                            : `${Math.round(c.oldest_entry_age_ms / 1000)}s`;
+                    logRaw(`${c.name}\t${c.entries}\t${c.estimated_bytes ?? "-"}\t${oldest}\t${ratio}`);
+                }
+                logRaw("analysis\truns\tmean ms\tmax ms");
+                for (const t of stats.timings) {
+                    logRaw(`${t.name}\t${t.runs}\t${t.mean_ms.toFixed(1)}\t${t.max_ms.toFixed(1)}`);
+                }
+            } catch (err) {
+                vscode.window.showErrorMessage(`Go Analyzer error: ${err}`);
+                console.error(err);
+            }
+        },
+    );
+    context.subscriptions.push(statsCmd);
     const activateCmd = vscode.commands.registerCommand(
         "goanalyzer.activate",
         async () => {
@@ -523,15 +1090,17 @@ export function activate(context: vscode.ExtensionContext) {
                         const target = p.uri ? ` ${p.uri}` : "";
                         log(`Indexing status:${target} vars=${p.variables}, funcs=${p.functions}, chans=${p.channels}, goroutines=${p.goroutines}`);
                     });
-                    client.onNotification(ProgressNotification, message => {
-                        vscode.window.showInformationMessage(message);
-                        log(`Progress: ${message}`);
+                    client.onNotification(ProgressNotification, p => {
+                        vscode.window.showInformationMessage(p.message);
+                        const trace = p.trace_id ? ` [trace ${p.trace_id}]` : "";
+                        log(`Progress: ${p.message}${trace}`);
                     });
                     client.onNotification(ParseInfoNotification, p => {
                         if (p.source !== "auto") return;
                         const parseMs = p.parse_ms == null ? "cache" : `${p.parse_ms}ms`;
                         log(`Parse info (auto): ${p.uri} cache_hit=${p.cache_hit} parse_ms=${parseMs} code_len=${p.code_len}`);
                     });
+                    client.onNotification(PerfNotification, logPerf);
                     client.onNotification(LifecycleDumpNotification, p => {
                         const maxChars = vscode.workspace.getConfiguration("goAnalyzer")
                             .get<number>("lifecycleJsonMaxChars", 20000);
@@ -542,6 +1111,9 @@ export function activate(context: vscode.ExtensionContext) {
                             : json;
                         logRaw(clipped);
                     });
+                    client.onNotification(DecorationsDeltaNotification, onDecorationsDelta);
+                    client.onNotification(DecorationsNotification, onDecorations);
+                    decorationSets.clear();
                     await client.start();
                     log("Go Analyzer LSP server restarted successfully");
                     startCursorTracking();
@@ -606,6 +1178,8 @@ export function activate(context: vscode.ExtensionContext) {
                 .get<number>("autoAnalysisDelay", 300);
             timeoutHandle = setTimeout(async () => {
                 try {
+                    const useDelta = vscode.workspace.getConfiguration("goAnalyzer")
+                        .get<boolean>("decorationDelta", false);
                     const resp: Decoration[] = await client!.sendRequest(
                         "workspace/executeCommand",
                         {
@@ -615,6 +1189,8 @@ export function activate(context: vscode.ExtensionContext) {
                                     textDocument: { uri: editor.document.uri.toString() },
                                     position: pos,
                                     source: "auto",
+                                    version: editor.document.version,
+                                    delta: useDelta,
                                     dump_json: vscode.workspace.getConfiguration("goAnalyzer")
                                         .get<boolean>("debugLifecycleJson", false),
                                 },
@@ -627,34 +1203,12 @@ export function activate(context: vscode.ExtensionContext) {
                     if (dumpAst) {
                         await dumpAstForDocument(editor.document.uri);
                     }
-                    for (const key in decorationTypes) {
-                        editor.setDecorations(decorationTypes[key as keyof typeof decorationTypes], []);
+                    if (!useDelta) {
+                        renderDecorations(editor, resp);
+                        applyDiagnostics(diagnosticsCollection, editor.document.uri, resp);
                     }
-                    if (Array.isArray(resp)) {
-                        const byType: Record<string, vscode.DecorationOptions[]> = {
-                            Declaration: [],
-                            Use: [],
-                            Pointer: [],
-                            Race: [],
-                            RaceLow: [],
-                            AliasReassigned: [],
-                            AliasCaptured: [],
-                        };
-                        for (const d of resp) {
-                            const range = new vscode.Range(
-                                new vscode.Position(d.range.start.line, d.range.start.character),
-                                new vscode.Position(d.range.end.line, d.range.end.character),
-                            );
-                            if (byType[d.kind]) {
-                                byType[d.kind].push({ range, hoverMessage: d.hover_text });
-                            }
-                        }
-                        for (const [k, decos] of Object.entries(byType)) {
-                            editor.setDecorations(decorationTypes[k as keyof typeof decorationTypes], decos);
-                        }
-                    }
-                    applyDiagnostics(diagnosticsCollection, editor.document.uri, resp);
                 } catch (err) {
+                    if (isContentModified(err)) return;
                     console.error("Auto-analysis error:", err);
                 }
             }, delay);
@@ -664,6 +1218,14 @@ export function activate(context: vscode.ExtensionContext) {
     const editorChangeDisposable = vscode.window.onDidChangeActiveTextEditor(ed => {
         if (ed && ed.document.languageId === "go") {
             startCursorTracking();
+            const useDelta = vscode.workspace.getConfiguration("goAnalyzer")
+                .get<boolean>("decorationDelta", false);
+            if (useDelta && client && client.state === 2) {
+                client.sendRequest("workspace/executeCommand", {
+                    command: "goanalyzer/resyncDecorations",
+                    arguments: [{ uri: ed.document.uri.toString() }],
+                }).then(undefined, err => console.error("Decoration resync error:", err));
+            }
         }
     });
     addDisposable(editorChangeDisposable);